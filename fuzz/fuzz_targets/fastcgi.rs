@@ -0,0 +1,35 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tiny_web::Parse;
+
+/// FastCGI header type BEGIN_REQUEST
+const FASTCGI_BEGIN_REQUEST: u8 = 1;
+/// FastCGI header type PARAMS
+const FASTCGI_PARAMS: u8 = 4;
+
+fuzz_target!(|data: &[u8]| {
+    // The records of the connection one by one
+    let mut pos = 0;
+    let mut params = Vec::new();
+    while let Some(header) = data.get(pos..).and_then(Parse::fastcgi_header) {
+        pos += 8;
+        let body = match data.get(pos..pos + header.content_length as usize) {
+            Some(body) => body,
+            None => break,
+        };
+        pos += header.content_length as usize + header.padding_length as usize;
+        match header.header_type {
+            FASTCGI_BEGIN_REQUEST => {
+                let _ = Parse::fastcgi_begin(body);
+            }
+            FASTCGI_PARAMS => params.extend_from_slice(body),
+            _ => {}
+        }
+    }
+    for params in [&params[..], data] {
+        if let Some(pairs) = Parse::fastcgi_pairs(params) {
+            let _ = Parse::cgi(&pairs, Some("tinysession"));
+        }
+    }
+});
@@ -0,0 +1,24 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tiny_web::{HeadScan, Parse};
+
+fuzz_target!(|data: &[u8]| {
+    let end = HeadScan::default().end(data);
+    // The search by parts finds the same end
+    let mut scan = HeadScan::default();
+    let part = scan.end(&data[..data.len() / 2]).or_else(|| scan.end(data));
+    assert_eq!(end, part);
+    if let Some(end) = end {
+        if let Ok(head) = Parse::http_head(&data[..end]) {
+            if let Some(url) = head.header.get("ORIGIN_URL") {
+                if let Some((_, query)) = url.split_once('?') {
+                    Parse::query(query, &mut Default::default());
+                }
+            }
+            if let Some(cookie) = head.header.get("COOKIE") {
+                Parse::cookie(cookie, Some("tinysession"), &mut Default::default());
+            }
+        }
+    }
+});
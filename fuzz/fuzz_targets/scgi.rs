@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tiny_web::Parse;
+
+fuzz_target!(|data: &[u8]| {
+    let (len, shift) = match Parse::scgi_len(data) {
+        Ok(Some(len)) => len,
+        _ => return,
+    };
+    // The header is ended by the comma
+    let header = match data.get(shift..shift + len + 1) {
+        Some([header @ .., b',']) => header,
+        _ => return,
+    };
+    if let Some(vars) = Parse::scgi_vars(header) {
+        let _ = Parse::cgi(&vars, Some("tinysession"));
+    }
+});
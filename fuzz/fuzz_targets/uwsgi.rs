@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tiny_web::Parse;
+
+fuzz_target!(|data: &[u8]| {
+    let (_, len, _) = match Parse::uwsgi_header(data) {
+        Some(header) => header,
+        None => return,
+    };
+    if let Some(vars) = data.get(4..4 + len).and_then(Parse::uwsgi_vars) {
+        let _ = Parse::cgi(&vars, None);
+    }
+});
@@ -0,0 +1,229 @@
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+    parse::Parser, parse_macro_input, punctuated::Punctuated, Attribute, Error, Expr, FnArg, Ident, ItemFn, Lit, Meta, Pat, Token,
+    Visibility,
+};
+
+/// Pre-checks of the controller
+///
+/// The checks run by the dispatch before the body of the controller, on failure the controller returns 401/403
+/// or the redirect of `redirect`, the ajax request always gets 401/403.
+/// All `#[guard]` attributes of the controller are joined into one check, `#[controller]` may be used with them.
+///
+/// * `auth` - the user must be authorized.
+/// * `role = "admin"` - the role of the user or its parent must have the permission "role.admin", see `Action::can`.
+/// * `redirect = "/user/login"` - the url instead of 401/403.
+///
+/// # Example
+///
+/// ```ignore
+/// #[guard(auth, redirect = "/user/login")]
+/// #[guard(role = "admin")]
+/// pub async fn index(this: &mut Action) -> Answer {
+///     ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn guard(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(item as ItemFn);
+    match wrap("guard", attr.into(), func) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Options of the controller
+///
+/// The options are applied by the dispatch before the body of the controller, not for the internal call of `Action::load`.
+///
+/// * `json` - Content-Type: application/json; charset=utf-8.
+/// * `content_type = "text/plain"` - the custom Content-Type.
+/// * `cache = "300s"` - Cache-Control for "300s", "5m", "1h", "1d" or "300" and the page cache of `Action::cache_page`.
+/// * `nosession` - the session is not written.
+/// * `max_size = 1048576` - max size of the answer in bytes.
+/// * `size_policy = Truncate` - what to do with the larger answer, `Error`, `Truncate` or `Disk`.
+///
+/// # Example
+///
+/// ```ignore
+/// #[controller(json, cache = "300s")]
+/// pub async fn index(this: &mut Action) -> Answer {
+///     ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn controller(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(item as ItemFn);
+    match wrap("controller", attr.into(), func) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Items of `#[guard]` and `#[controller]` of the controller
+#[derive(Default)]
+struct Items {
+    guards: Vec<TokenStream2>,
+    options: Vec<TokenStream2>,
+}
+
+impl Items {
+    /// The list of the attribute, the items of the previous attributes are added as `guard(...)` or `controller(...)`
+    fn parse(&mut self, name: &str, tokens: TokenStream2) -> Result<(), Error> {
+        for meta in Punctuated::<Meta, Token![,]>::parse_terminated.parse2(tokens)? {
+            if let Meta::List(list) = &meta {
+                if let Some(name) = list.path.get_ident().and_then(attribute_name) {
+                    self.parse(name, list.tokens.clone())?;
+                    continue;
+                }
+            }
+            match name {
+                "guard" => self.guards.push(guard_item(&meta)?),
+                _ => self.options.push(option_item(&meta)?),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The function of `addfn!` becomes the wrapper of the dispatch, the body is the inner function
+///
+/// The wrapper is made by the last attribute of the controller, the previous ones pass their items to the next one.
+fn wrap(name: &str, attr: TokenStream2, mut func: ItemFn) -> Result<TokenStream2, Error> {
+    if let Some(next) = func.attrs.iter_mut().find(|attr| attribute(attr).is_some()) {
+        let list = match &mut next.meta {
+            Meta::List(list) => list,
+            meta => return Err(Error::new_spanned(meta, "expected the list of the items")),
+        };
+        let ident = Ident::new(name, Span::call_site());
+        let tokens = &list.tokens;
+        list.tokens = if tokens.is_empty() { quote!(#ident(#attr)) } else { quote!(#tokens, #ident(#attr)) };
+        return Ok(quote!(#func));
+    }
+    let mut items = Items::default();
+    items.parse(name, attr)?;
+
+    if func.sig.asyncness.is_none() {
+        return Err(Error::new_spanned(&func.sig, format!("#[{}] requires `async fn`", name)));
+    }
+    let this = match func.sig.inputs.first() {
+        Some(FnArg::Typed(arg)) => match &*arg.pat {
+            Pat::Ident(pat) => pat.ident.clone(),
+            _ => return Err(Error::new_spanned(&arg.pat, format!("#[{}] requires the name of the `&mut Action`", name))),
+        },
+        _ => return Err(Error::new_spanned(&func.sig, format!("#[{}] requires `this: &mut Action`", name))),
+    };
+
+    let mut attributes = quote!(::tiny_web::sys::web::handler::Attributes::new());
+    if !items.guards.is_empty() {
+        let guards = &items.guards;
+        attributes.extend(quote!(.guards(&[#(#guards),*])));
+    }
+    if !items.options.is_empty() {
+        let options = &items.options;
+        attributes.extend(quote!(.options(&[#(#options),*])));
+    }
+    let mut body = func.clone();
+    body.attrs.clear();
+    body.vis = Visibility::Inherited;
+    body.sig.ident = Ident::new("body", Span::call_site());
+    *func.block = syn::parse_quote!({
+        #body
+        const ATTRIBUTES: ::tiny_web::sys::web::handler::Attributes = #attributes;
+        #this.dispatch(&ATTRIBUTES, body).await
+    });
+    Ok(quote!(#func))
+}
+
+/// The attribute `#[guard(...)]`, `#[controller(...)]` or with the path, for example `#[tiny_web::guard(...)]`
+fn attribute(attr: &Attribute) -> Option<&'static str> {
+    attribute_name(&attr.path().segments.last()?.ident)
+}
+
+fn attribute_name(ident: &Ident) -> Option<&'static str> {
+    if ident == "guard" {
+        Some("guard")
+    } else if ident == "controller" {
+        Some("controller")
+    } else {
+        None
+    }
+}
+
+/// Item of `Guard`: `auth`, `role = "admin"` or `redirect = "/user/login"`
+fn guard_item(meta: &Meta) -> Result<TokenStream2, Error> {
+    let guard = match meta {
+        Meta::Path(path) if path.is_ident("auth") => quote!(::tiny_web::sys::web::guard::Guard::Auth),
+        Meta::NameValue(item) if item.path.is_ident("role") => {
+            let role = lit_str(&item.value)?;
+            quote!(::tiny_web::sys::web::guard::Guard::Role(#role))
+        }
+        Meta::NameValue(item) if item.path.is_ident("redirect") => {
+            let url = lit_str(&item.value)?;
+            quote!(::tiny_web::sys::web::guard::Guard::Redirect(#url))
+        }
+        _ => return Err(Error::new_spanned(meta, "expected `auth`, `role = \"...\"` or `redirect = \"...\"`")),
+    };
+    Ok(guard)
+}
+
+/// Item of `Controller`, for example `json` or `cache = "300s"`
+fn option_item(meta: &Meta) -> Result<TokenStream2, Error> {
+    let option = match meta {
+        Meta::Path(path) if path.is_ident("json") => quote!(::tiny_web::sys::web::controller::Controller::Json),
+        Meta::Path(path) if path.is_ident("nosession") => quote!(::tiny_web::sys::web::controller::Controller::NoSession),
+        Meta::NameValue(item) if item.path.is_ident("content_type") => {
+            let value = lit_str(&item.value)?;
+            quote!(::tiny_web::sys::web::controller::Controller::ContentType(#value))
+        }
+        Meta::NameValue(item) if item.path.is_ident("cache") => {
+            let value = lit_str(&item.value)?;
+            if !is_duration(&value) {
+                return Err(Error::new_spanned(
+                    &item.value,
+                    "expected the duration, for example \"300s\", \"5m\", \"1h\", \"1d\" or \"300\"",
+                ));
+            }
+            quote!(::tiny_web::sys::web::controller::Controller::Cache(#value))
+        }
+        Meta::NameValue(item) if item.path.is_ident("max_size") => {
+            let value = &item.value;
+            quote!(::tiny_web::sys::web::controller::Controller::MaxSize(#value))
+        }
+        Meta::NameValue(item) if item.path.is_ident("size_policy") => {
+            let value = match &item.value {
+                Expr::Path(path) if path.path.segments.len() == 1 => quote!(::tiny_web::sys::web::controller::SizePolicy::#path),
+                value => quote!(#value),
+            };
+            quote!(::tiny_web::sys::web::controller::Controller::SizePolicy(#value))
+        }
+        _ => {
+            return Err(Error::new_spanned(
+                meta,
+                "expected `json`, `content_type = \"...\"`, `cache = \"...\"`, `nosession`, `max_size = ...` or `size_policy = ...`",
+            ))
+        }
+    };
+    Ok(option)
+}
+
+/// "300s", "5m", "1h", "1d" or "300", the same as `Controller::parse_duration`
+fn is_duration(value: &str) -> bool {
+    let value = value.trim();
+    let num = value.strip_suffix(['s', 'm', 'h', 'd']).unwrap_or(value);
+    num.trim().parse::<u64>().is_ok()
+}
+
+fn lit_str(expr: &Expr) -> Result<String, Error> {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Str(value) => Ok(value.value()),
+            _ => Err(Error::new_spanned(expr, "expected the string")),
+        },
+        _ => Err(Error::new_spanned(expr, "expected the string")),
+    }
+}
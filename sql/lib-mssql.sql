@@ -358,6 +358,67 @@ INSERT INTO [lang] ([lang_id], [name], [enable], [code], [sort], [index]) VALUES
 INSERT INTO [lang] ([lang_id], [name], [enable], [code], [sort], [index]) VALUES (181, 'Zulu (isiZulu)', 0, 'zu', 181, null);
 SET IDENTITY_INSERT [lang] OFF;
 
+-- ----------------------------
+-- Table structure for link_check
+-- ----------------------------
+CREATE TABLE [link_check] (
+  [link_check_id] BIGINT IDENTITY NOT NULL,
+  [url] VARCHAR(4000) NOT NULL,
+  [referer] VARCHAR(4000) NOT NULL,
+  [status] BIGINT NOT NULL,
+  [hops] BIGINT NOT NULL,
+  [chain] NVARCHAR(MAX) NOT NULL,
+  [create] DATETIMEOFFSET NOT NULL DEFAULT SYSDATETIMEOFFSET(),
+  PRIMARY KEY CLUSTERED ([link_check_id])
+);
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Identifier',
+'SCHEMA', N'dbo',
+'TABLE', N'link_check',
+'COLUMN', N'link_check_id';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Checked URL',
+'SCHEMA', N'dbo',
+'TABLE', N'link_check',
+'COLUMN', N'url';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Page with the link',
+'SCHEMA', N'dbo',
+'TABLE', N'link_check',
+'COLUMN', N'referer';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Http code, 0 if no answer',
+'SCHEMA', N'dbo',
+'TABLE', N'link_check',
+'COLUMN', N'status';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Number of redirects',
+'SCHEMA', N'dbo',
+'TABLE', N'link_check',
+'COLUMN', N'hops';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Redirect chain, one URL per line',
+'SCHEMA', N'dbo',
+'TABLE', N'link_check',
+'COLUMN', N'chain';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Date created',
+'SCHEMA', N'dbo',
+'TABLE', N'link_check',
+'COLUMN', N'create';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Broken-link checker report',
+'SCHEMA', N'dbo',
+'TABLE', N'link_check';
+
 -- ----------------------------
 -- Table structure for mail
 -- ----------------------------
@@ -398,6 +459,89 @@ EXEC sp_addextendedproperty
 'SCHEMA', N'dbo',
 'TABLE', N'mail';
 
+-- ----------------------------
+-- Table structure for mail_invalid
+-- ----------------------------
+CREATE TABLE [mail_invalid] (
+  [mail_invalid_id] BIGINT IDENTITY NOT NULL,
+  [email] NVARCHAR(450) NOT NULL,
+  [kind] NVARCHAR(16) NOT NULL,
+  [reason] NVARCHAR(MAX) NOT NULL,
+  [create] DATETIMEOFFSET NOT NULL,
+  PRIMARY KEY CLUSTERED ([mail_invalid_id])
+);
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Identifier',
+'SCHEMA', N'dbo',
+'TABLE', N'mail_invalid',
+'COLUMN', N'mail_invalid_id';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Address in lower case',
+'SCHEMA', N'dbo',
+'TABLE', N'mail_invalid',
+'COLUMN', N'email';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'hard or complaint',
+'SCHEMA', N'dbo',
+'TABLE', N'mail_invalid',
+'COLUMN', N'kind';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Status and diagnostic of the bounce',
+'SCHEMA', N'dbo',
+'TABLE', N'mail_invalid',
+'COLUMN', N'reason';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Date of the last bounce',
+'SCHEMA', N'dbo',
+'TABLE', N'mail_invalid',
+'COLUMN', N'create';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Invalid addresses by the bounces and the complaints, feature mail-inbound',
+'SCHEMA', N'dbo',
+'TABLE', N'mail_invalid';
+
+-- ----------------------------
+-- Table structure for permission
+-- ----------------------------
+CREATE TABLE [permission] (
+  [permission_id] BIGINT IDENTITY NOT NULL,
+  [name] VARCHAR(255) NOT NULL,
+  [description] NVARCHAR(MAX) NOT NULL,
+  PRIMARY KEY CLUSTERED ([permission_id])
+);
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Identifier',
+'SCHEMA', N'dbo',
+'TABLE', N'permission',
+'COLUMN', N'permission_id';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Name, for example article.edit or article.*',
+'SCHEMA', N'dbo',
+'TABLE', N'permission',
+'COLUMN', N'name';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Description',
+'SCHEMA', N'dbo',
+'TABLE', N'permission',
+'COLUMN', N'description';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Business permissions',
+'SCHEMA', N'dbo',
+'TABLE', N'permission';
+
+INSERT INTO [permission] ([name], [description]) VALUES ('role.admin', '{}'); -- #[guard(role = "admin")]
+INSERT INTO [permission] ([name], [description]) VALUES ('role.user', '{}'); -- #[guard(role = "user")]
+
 -- ----------------------------
 -- Table structure for provider
 -- ----------------------------
@@ -531,6 +675,82 @@ INSERT INTO [role] ([role_id], [name], [description]) VALUES (1, '{}', '{}'); --
 INSERT INTO [role] ([role_id], [name], [description]) VALUES (2, '{}', '{}'); -- Registered user
 SET IDENTITY_INSERT [role] OFF;
 
+-- ----------------------------
+-- Table structure for role_parent
+-- ----------------------------
+CREATE TABLE [role_parent] (
+  [role_parent_id] BIGINT IDENTITY NOT NULL,
+  [role_id] BIGINT NOT NULL,
+  [parent_id] BIGINT NOT NULL,
+  PRIMARY KEY CLUSTERED ([role_parent_id])
+);
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Identifier',
+'SCHEMA', N'dbo',
+'TABLE', N'role_parent',
+'COLUMN', N'role_parent_id';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Role ID',
+'SCHEMA', N'dbo',
+'TABLE', N'role_parent',
+'COLUMN', N'role_id';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Parent role ID, the role inherits its permissions',
+'SCHEMA', N'dbo',
+'TABLE', N'role_parent',
+'COLUMN', N'parent_id';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Role hierarchy',
+'SCHEMA', N'dbo',
+'TABLE', N'role_parent';
+
+-- ----------------------------
+-- Table structure for role_permission
+-- ----------------------------
+CREATE TABLE [role_permission] (
+  [role_permission_id] BIGINT IDENTITY NOT NULL,
+  [role_id] BIGINT NOT NULL,
+  [permission_id] BIGINT NOT NULL,
+  [access] BIT NOT NULL,
+  PRIMARY KEY CLUSTERED ([role_permission_id])
+);
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Identifier',
+'SCHEMA', N'dbo',
+'TABLE', N'role_permission',
+'COLUMN', N'role_permission_id';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Role ID',
+'SCHEMA', N'dbo',
+'TABLE', N'role_permission',
+'COLUMN', N'role_id';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Permission ID',
+'SCHEMA', N'dbo',
+'TABLE', N'role_permission',
+'COLUMN', N'permission_id';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Access flag, 0 denies the permission of the parent role',
+'SCHEMA', N'dbo',
+'TABLE', N'role_permission',
+'COLUMN', N'access';
+
+EXEC sp_addextendedproperty
+'MS_Description', N'Permissions of roles',
+'SCHEMA', N'dbo',
+'TABLE', N'role_permission';
+
+INSERT INTO [role_permission] ([role_id], [permission_id], [access]) SELECT 1, [permission_id], 1 FROM [permission] WHERE [name]='role.admin'; -- Administrator
+INSERT INTO [role_permission] ([role_id], [permission_id], [access]) SELECT 2, [permission_id], 1 FROM [permission] WHERE [name]='role.user'; -- Registered user
+
 -- ----------------------------
 -- Table structure for route
 -- ----------------------------
@@ -855,6 +1075,16 @@ CREATE NONCLUSTERED INDEX [lang_index_i] ON [lang] ([index]);
 -- ----------------------------
 CREATE NONCLUSTERED INDEX [mail_user_id_i] ON [mail] ([user_id]);
 
+-- ----------------------------
+-- Indexes structure for table mail_invalid
+-- ----------------------------
+CREATE UNIQUE NONCLUSTERED INDEX [mail_invalid_email_u] ON [mail_invalid] ([email]);
+
+-- ----------------------------
+-- Indexes structure for table permission
+-- ----------------------------
+CREATE UNIQUE NONCLUSTERED INDEX [permission_name_u] ON [permission] ([name]);
+
 -- ----------------------------
 -- Indexes structure for table provider
 -- ----------------------------
@@ -868,6 +1098,17 @@ CREATE UNIQUE NONCLUSTERED INDEX [provider_name_u] ON [provider] ([name]);
 -- ----------------------------
 CREATE UNIQUE NONCLUSTERED INDEX [redirect_url_u] ON [redirect] ([url]);
 
+-- ----------------------------
+-- Indexes structure for table role_parent
+-- ----------------------------
+CREATE UNIQUE NONCLUSTERED INDEX [role_parent_role_id_parent_id_u] ON [role_parent] ([role_id], [parent_id]);
+
+-- ----------------------------
+-- Indexes structure for table role_permission
+-- ----------------------------
+CREATE NONCLUSTERED INDEX [role_permission_permission_id_i] ON [role_permission] ([permission_id]);
+CREATE UNIQUE NONCLUSTERED INDEX [role_permission_role_id_permission_id_u] ON [role_permission] ([role_id], [permission_id]);
+
 -- ----------------------------
 -- Indexes structure for table route
 -- ----------------------------
@@ -882,6 +1123,7 @@ CREATE UNIQUE NONCLUSTERED INDEX [route_url_u] ON [route] ([url]);
 CREATE UNIQUE NONCLUSTERED INDEX [session_session_u] ON [session] ([session]);
 CREATE UNIQUE NONCLUSTERED INDEX [session_session_key_u] ON [session] ([session_key]);
 CREATE NONCLUSTERED INDEX [session_user_id_i] ON [session] ([user_id]);
+CREATE NONCLUSTERED INDEX [session_last_i] ON [session] ([last]);
 
 -- ----------------------------
 -- Indexes structure for table setting
@@ -910,6 +1152,10 @@ CREATE UNIQUE NONCLUSTERED INDEX [user_provider_user_id_provider_id_u] ON [user_
 ALTER TABLE [access] ADD FOREIGN KEY ([controller_id]) REFERENCES [controller] ([controller_id]);
 ALTER TABLE [access] ADD FOREIGN KEY ([role_id]) REFERENCES [role] ([role_id]);
 ALTER TABLE [mail] ADD FOREIGN KEY ([user_id]) REFERENCES [user] ([user_id]);
+ALTER TABLE [role_parent] ADD FOREIGN KEY ([role_id]) REFERENCES [role] ([role_id]);
+ALTER TABLE [role_parent] ADD FOREIGN KEY ([parent_id]) REFERENCES [role] ([role_id]);
+ALTER TABLE [role_permission] ADD FOREIGN KEY ([permission_id]) REFERENCES [permission] ([permission_id]);
+ALTER TABLE [role_permission] ADD FOREIGN KEY ([role_id]) REFERENCES [role] ([role_id]);
 ALTER TABLE [route] ADD FOREIGN KEY ([controller_id]) REFERENCES [controller] ([controller_id]);
 ALTER TABLE [route] ADD FOREIGN KEY ([lang_id]) REFERENCES [lang] ([lang_id]);
 ALTER TABLE [session] ADD FOREIGN KEY ([user_id]) REFERENCES [user] ([user_id]);
@@ -953,4 +1199,76 @@ BEGIN
 		LEFT JOIN (SELECT MAX([index]) AS [index] FROM [lang]) n ON 1=1
 	WHERE [lang].[lang_id]=i.[lang_id];
 END;
-GO
\ No newline at end of file
+GO-- ----------------------------
+-- Trigers for the invalidation of the cache of all nodes, [db] notify = "tiny_cache"
+-- The queues and the services are created by the nodes, the Service Broker must be enabled:
+-- ALTER DATABASE [db] SET ENABLE_BROKER;
+-- ----------------------------
+CREATE PROCEDURE [cache_notify] @channel nvarchar(63), @key nvarchar(max) AS
+BEGIN
+  SET NOCOUNT ON;
+  IF NOT EXISTS (SELECT 1 FROM sys.services WHERE [name]=@channel) RETURN;
+  DECLARE @service sysname;
+  DECLARE @send nvarchar(max) = N'DECLARE @handle uniqueidentifier;
+    BEGIN DIALOG @handle FROM SERVICE ' + QUOTENAME(@channel) + N' TO SERVICE @service ON CONTRACT [DEFAULT] WITH ENCRYPTION=OFF;
+    SEND ON CONVERSATION @handle (CAST(@key AS varbinary(max)));
+    END CONVERSATION @handle;';
+  DECLARE services CURSOR LOCAL FAST_FORWARD FOR
+    SELECT [name] FROM sys.services WHERE [name] LIKE REPLACE(@channel, N'_', N'[_]') + N'[_]%';
+  OPEN services;
+  FETCH NEXT FROM services INTO @service;
+  WHILE @@FETCH_STATUS = 0
+  BEGIN
+    EXEC sp_executesql @send, N'@service sysname, @key nvarchar(max)', @service, @key;
+    FETCH NEXT FROM services INTO @service;
+  END;
+  CLOSE services;
+  DEALLOCATE services;
+END;
+GO
+CREATE TRIGGER [route_cache_t] ON [route] AFTER INSERT, UPDATE, DELETE AS
+BEGIN
+  SET NOCOUNT ON;
+  EXEC [cache_notify] N'tiny_cache', N'sys:route:';
+  EXEC [cache_notify] N'tiny_cache', N'sys:url:';
+END;
+GO
+CREATE TRIGGER [controller_cache_t] ON [controller] AFTER INSERT, UPDATE, DELETE AS
+BEGIN
+  SET NOCOUNT ON;
+  EXEC [cache_notify] N'tiny_cache', N'sys:route:';
+  EXEC [cache_notify] N'tiny_cache', N'sys:url:';
+  EXEC [cache_notify] N'tiny_cache', N'sys:access:';
+END;
+GO
+CREATE TRIGGER [redirect_cache_t] ON [redirect] AFTER INSERT, UPDATE, DELETE AS
+BEGIN
+  SET NOCOUNT ON;
+  EXEC [cache_notify] N'tiny_cache', N'sys:redirect:';
+END;
+GO
+CREATE TRIGGER [access_cache_t] ON [access] AFTER INSERT, UPDATE, DELETE AS
+BEGIN
+  SET NOCOUNT ON;
+  EXEC [cache_notify] N'tiny_cache', N'sys:access:';
+END;
+GO
+CREATE TRIGGER [permission_cache_t] ON [permission] AFTER INSERT, UPDATE, DELETE AS
+BEGIN
+  SET NOCOUNT ON;
+  EXEC [cache_notify] N'tiny_cache', N'sys:permission:';
+END;
+GO
+CREATE TRIGGER [role_permission_cache_t] ON [role_permission] AFTER INSERT, UPDATE, DELETE AS
+BEGIN
+  SET NOCOUNT ON;
+  EXEC [cache_notify] N'tiny_cache', N'sys:permission:';
+END;
+GO
+CREATE TRIGGER [role_parent_cache_t] ON [role_parent] AFTER INSERT, UPDATE, DELETE AS
+BEGIN
+  SET NOCOUNT ON;
+  EXEC [cache_notify] N'tiny_cache', N'sys:permission:';
+  EXEC [cache_notify] N'tiny_cache', N'sys:access:';
+END;
+GO
@@ -17,6 +17,23 @@ INSERT INTO "access" VALUES (1, 0, 't', 1);-- \n
 INSERT INTO "access" VALUES (2, 0, 't', 4);-- \n
 INSERT INTO "access" VALUES (3, 0, 't', 5);-- \n
 
+-- ----------------------------
+-- Table structure for audit
+-- ----------------------------
+CREATE TABLE "audit" (
+  "audit_id" int8 NOT NULL GENERATED BY DEFAULT AS IDENTITY,
+  "user_id" int8 NOT NULL,
+  "action" text NOT NULL,
+  "data" jsonb NOT NULL,
+  "create" timestamptz NOT NULL
+);-- \n
+COMMENT ON COLUMN "audit"."audit_id" IS 'Identifier';-- \n
+COMMENT ON COLUMN "audit"."user_id" IS 'User';-- \n
+COMMENT ON COLUMN "audit"."action" IS 'Action, for example export or erase';-- \n
+COMMENT ON COLUMN "audit"."data" IS 'Details';-- \n
+COMMENT ON COLUMN "audit"."create" IS 'Date created';-- \n
+COMMENT ON TABLE "audit" IS 'Audit of the personal data';-- \n
+
 -- ----------------------------
 -- Table structure for controller
 -- ----------------------------
@@ -248,6 +265,27 @@ INSERT INTO "lang" VALUES (179, 'Zhuang, Chuang (Saɯ cueŋƅ, Saw cuengh)', 'f'
 INSERT INTO "lang" VALUES (180, 'Chinese (中文 (Zhōngwén), 汉语, 漢語)', 'f', 'zh', 180, null);-- \n
 INSERT INTO "lang" VALUES (181, 'Zulu (isiZulu)', 'f', 'zu', 181, null);-- \n
 
+-- ----------------------------
+-- Table structure for link_check
+-- ----------------------------
+CREATE TABLE "link_check" (
+  "link_check_id" int8 NOT NULL GENERATED BY DEFAULT AS IDENTITY,
+  "url" text NOT NULL,
+  "referer" text NOT NULL,
+  "status" int8 NOT NULL,
+  "hops" int8 NOT NULL,
+  "chain" text NOT NULL,
+  "create" timestamptz NOT NULL DEFAULT now()
+);-- \n
+COMMENT ON COLUMN "link_check"."link_check_id" IS 'Identifier';-- \n
+COMMENT ON COLUMN "link_check"."url" IS 'Checked URL';-- \n
+COMMENT ON COLUMN "link_check"."referer" IS 'Page with the link';-- \n
+COMMENT ON COLUMN "link_check"."status" IS 'Http code, 0 if no answer';-- \n
+COMMENT ON COLUMN "link_check"."hops" IS 'Number of redirects';-- \n
+COMMENT ON COLUMN "link_check"."chain" IS 'Redirect chain, one URL per line';-- \n
+COMMENT ON COLUMN "link_check"."create" IS 'Date created';-- \n
+COMMENT ON TABLE "link_check" IS 'Broken-link checker report';-- \n
+
 -- ----------------------------
 -- Table structure for mail
 -- ----------------------------
@@ -264,6 +302,40 @@ COMMENT ON COLUMN "mail"."mail" IS 'Message';-- \n
 COMMENT ON COLUMN "mail"."create" IS 'Date created';-- \n
 COMMENT ON TABLE "mail" IS 'Email';-- \n
 
+-- ----------------------------
+-- Table structure for mail_invalid
+-- ----------------------------
+CREATE TABLE "mail_invalid" (
+  "mail_invalid_id" int8 NOT NULL GENERATED BY DEFAULT AS IDENTITY,
+  "email" text NOT NULL,
+  "kind" text NOT NULL,
+  "reason" text NOT NULL,
+  "create" timestamptz NOT NULL
+);-- \n
+
+COMMENT ON COLUMN "mail_invalid"."mail_invalid_id" IS 'Identifier';-- \n
+COMMENT ON COLUMN "mail_invalid"."email" IS 'Address in lower case';-- \n
+COMMENT ON COLUMN "mail_invalid"."kind" IS 'hard or complaint';-- \n
+COMMENT ON COLUMN "mail_invalid"."reason" IS 'Status and diagnostic of the bounce';-- \n
+COMMENT ON COLUMN "mail_invalid"."create" IS 'Date of the last bounce';-- \n
+COMMENT ON TABLE "mail_invalid" IS 'Invalid addresses by the bounces and the complaints, feature mail-inbound';-- \n
+
+-- ----------------------------
+-- Table structure for permission
+-- ----------------------------
+CREATE TABLE "permission" (
+  "permission_id" int8 NOT NULL GENERATED BY DEFAULT AS IDENTITY,
+  "name" text NOT NULL,
+  "description" jsonb NOT NULL
+);-- \n
+COMMENT ON COLUMN "permission"."permission_id" IS 'Identifier';-- \n
+COMMENT ON COLUMN "permission"."name" IS 'Name, for example article.edit or article.*';-- \n
+COMMENT ON COLUMN "permission"."description" IS 'Description';-- \n
+COMMENT ON TABLE "permission" IS 'Business permissions';-- \n
+
+INSERT INTO "permission" ("name", "description") VALUES ('role.admin', '{}'); -- #[guard(role = "admin")] ;-- \n
+INSERT INTO "permission" ("name", "description") VALUES ('role.user', '{}'); -- #[guard(role = "user")] ;-- \n
+
 -- ----------------------------
 -- Table structure for provider
 -- ----------------------------
@@ -315,6 +387,37 @@ INSERT INTO "role" VALUES (0, '{}', '{}'); -- Unregistered user ;-- \n
 INSERT INTO "role" VALUES (1, '{}', '{}'); -- Administrator ;-- \n
 INSERT INTO "role" VALUES (2, '{}', '{}'); -- Registered user ;-- \n
 
+-- ----------------------------
+-- Table structure for role_parent
+-- ----------------------------
+CREATE TABLE "role_parent" (
+  "role_parent_id" int8 NOT NULL GENERATED BY DEFAULT AS IDENTITY,
+  "role_id" int8 NOT NULL,
+  "parent_id" int8 NOT NULL
+);-- \n
+COMMENT ON COLUMN "role_parent"."role_parent_id" IS 'Identifier';-- \n
+COMMENT ON COLUMN "role_parent"."role_id" IS 'Role ID';-- \n
+COMMENT ON COLUMN "role_parent"."parent_id" IS 'Parent role ID, the role inherits its permissions';-- \n
+COMMENT ON TABLE "role_parent" IS 'Role hierarchy';-- \n
+
+-- ----------------------------
+-- Table structure for role_permission
+-- ----------------------------
+CREATE TABLE "role_permission" (
+  "role_permission_id" int8 NOT NULL GENERATED BY DEFAULT AS IDENTITY,
+  "role_id" int8 NOT NULL,
+  "permission_id" int8 NOT NULL,
+  "access" bool NOT NULL
+);-- \n
+COMMENT ON COLUMN "role_permission"."role_permission_id" IS 'Identifier';-- \n
+COMMENT ON COLUMN "role_permission"."role_id" IS 'Role ID';-- \n
+COMMENT ON COLUMN "role_permission"."permission_id" IS 'Permission ID';-- \n
+COMMENT ON COLUMN "role_permission"."access" IS 'Access flag, false denies the permission of the parent role';-- \n
+COMMENT ON TABLE "role_permission" IS 'Permissions of roles';-- \n
+
+INSERT INTO "role_permission" ("role_id", "permission_id", "access") SELECT 1, "permission_id", true FROM "permission" WHERE "name"='role.admin'; -- Administrator ;-- \n
+INSERT INTO "role_permission" ("role_id", "permission_id", "access") SELECT 2, "permission_id", true FROM "permission" WHERE "name"='role.user'; -- Registered user ;-- \n
+
 -- ----------------------------
 -- Table structure for route
 -- ----------------------------
@@ -434,6 +537,12 @@ CREATE UNIQUE INDEX ON "access" USING btree ("role_id", "controller_id");-- \n
 CREATE INDEX ON "access" USING btree ("role_id");-- \n
 ALTER TABLE "access" ADD CONSTRAINT "access_pkey" PRIMARY KEY ("access_id");-- \n
 
+-- ----------------------------
+-- Indexes structure for table audit
+-- ----------------------------
+CREATE INDEX ON "audit" USING btree ("user_id");-- \n
+ALTER TABLE "audit" ADD CONSTRAINT "audit_pkey" PRIMARY KEY ("audit_id");-- \n
+
 -- ----------------------------
 -- Indexes structure for table controller
 -- ----------------------------
@@ -453,12 +562,29 @@ CREATE INDEX ON "lang" USING btree ("name");-- \n
 CREATE INDEX ON "lang" USING btree ("index");-- \n
 ALTER TABLE "lang" ADD CONSTRAINT "lang_pkey" PRIMARY KEY ("lang_id");-- \n
 
+-- ----------------------------
+-- Indexes structure for table link_check
+-- ----------------------------
+ALTER TABLE "link_check" ADD CONSTRAINT "link_check_pkey" PRIMARY KEY ("link_check_id");-- \n
+
 -- ----------------------------
 -- Indexes structure for table mail
 -- ----------------------------
 CREATE INDEX ON "mail" USING btree ("user_id");-- \n
 ALTER TABLE "mail" ADD CONSTRAINT "mail_pkey" PRIMARY KEY ("mail_id");-- \n
 
+-- ----------------------------
+-- Indexes structure for table mail_invalid
+-- ----------------------------
+CREATE UNIQUE INDEX ON "mail_invalid" USING btree ("email");-- \n
+ALTER TABLE "mail_invalid" ADD CONSTRAINT "mail_invalid_pkey" PRIMARY KEY ("mail_invalid_id");-- \n
+
+-- ----------------------------
+-- Indexes structure for table permission
+-- ----------------------------
+CREATE UNIQUE INDEX ON "permission" USING btree ("name");-- \n
+ALTER TABLE "permission" ADD CONSTRAINT "permission_pkey" PRIMARY KEY ("permission_id");-- \n
+
 -- ----------------------------
 -- Indexes structure for table provider
 -- ----------------------------
@@ -479,6 +605,19 @@ ALTER TABLE "redirect" ADD CONSTRAINT "redirect_pkey" PRIMARY KEY ("redirect_id"
 -- ----------------------------
 ALTER TABLE "role" ADD CONSTRAINT "role_pkey" PRIMARY KEY ("role_id");-- \n
 
+-- ----------------------------
+-- Indexes structure for table role_parent
+-- ----------------------------
+CREATE UNIQUE INDEX ON "role_parent" USING btree ("role_id", "parent_id");-- \n
+ALTER TABLE "role_parent" ADD CONSTRAINT "role_parent_pkey" PRIMARY KEY ("role_parent_id");-- \n
+
+-- ----------------------------
+-- Indexes structure for table role_permission
+-- ----------------------------
+CREATE INDEX ON "role_permission" USING btree ("permission_id");-- \n
+CREATE UNIQUE INDEX ON "role_permission" USING btree ("role_id", "permission_id");-- \n
+ALTER TABLE "role_permission" ADD CONSTRAINT "role_permission_pkey" PRIMARY KEY ("role_permission_id");-- \n
+
 -- ----------------------------
 -- Indexes structure for table route
 -- ----------------------------
@@ -494,6 +633,7 @@ ALTER TABLE "route" ADD CONSTRAINT "route_pkey" PRIMARY KEY ("route_id");-- \n
 CREATE UNIQUE INDEX ON "session" USING btree ("session_key");-- \n
 CREATE UNIQUE INDEX ON "session" USING btree ("session");-- \n
 CREATE INDEX ON "session" USING btree ("user_id");-- \n
+CREATE INDEX ON "session" USING btree ("last");-- \n
 ALTER TABLE "session" ADD CONSTRAINT "session_pkey" PRIMARY KEY ("session_id");-- \n
 
 -- ----------------------------
@@ -526,6 +666,10 @@ ALTER TABLE "user_provider" ADD CONSTRAINT "user_provider_pkey" PRIMARY KEY ("us
 ALTER TABLE "access" ADD CONSTRAINT "access_controller_id_fkey" FOREIGN KEY ("controller_id") REFERENCES "controller" ("controller_id");-- \n
 ALTER TABLE "access" ADD CONSTRAINT "access_role_id_fkey" FOREIGN KEY ("role_id") REFERENCES "role" ("role_id");-- \n
 ALTER TABLE "mail" ADD CONSTRAINT "mail_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "user" ("user_id");-- \n
+ALTER TABLE "role_parent" ADD CONSTRAINT "role_parent_role_id_fkey" FOREIGN KEY ("role_id") REFERENCES "role" ("role_id");-- \n
+ALTER TABLE "role_parent" ADD CONSTRAINT "role_parent_parent_id_fkey" FOREIGN KEY ("parent_id") REFERENCES "role" ("role_id");-- \n
+ALTER TABLE "role_permission" ADD CONSTRAINT "role_permission_permission_id_fkey" FOREIGN KEY ("permission_id") REFERENCES "permission" ("permission_id");-- \n
+ALTER TABLE "role_permission" ADD CONSTRAINT "role_permission_role_id_fkey" FOREIGN KEY ("role_id") REFERENCES "role" ("role_id");-- \n
 ALTER TABLE "route" ADD CONSTRAINT "route_controller_id_fkey" FOREIGN KEY ("controller_id") REFERENCES "controller" ("controller_id");-- \n
 ALTER TABLE "route" ADD CONSTRAINT "route_lang_id_fkey" FOREIGN KEY ("lang_id") REFERENCES "lang" ("lang_id");-- \n
 ALTER TABLE "session" ADD CONSTRAINT "session_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "user" ("user_id");-- \n
@@ -565,3 +709,46 @@ $$ LANGUAGE plpgsql;-- \n
 CREATE TRIGGER trigger_lang_change_row
 BEFORE UPDATE ON lang
 FOR EACH ROW EXECUTE FUNCTION lang_change_row();-- \n
+
+-- ----------------------------
+-- Trigers for the invalidation of the cache of all nodes, [db] notify = "tiny_cache"
+-- ----------------------------
+CREATE FUNCTION cache_notify()
+RETURNS TRIGGER AS $$
+DECLARE
+  key text;
+BEGIN
+  FOREACH key IN ARRAY TG_ARGV LOOP
+    PERFORM pg_notify('tiny_cache', key);
+  END LOOP;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;-- \n
+
+CREATE TRIGGER trigger_route_cache_notify
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON route
+FOR EACH STATEMENT EXECUTE FUNCTION cache_notify('sys:route:', 'sys:url:');-- \n
+
+CREATE TRIGGER trigger_controller_cache_notify
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON controller
+FOR EACH STATEMENT EXECUTE FUNCTION cache_notify('sys:route:', 'sys:url:', 'sys:access:');-- \n
+
+CREATE TRIGGER trigger_redirect_cache_notify
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON redirect
+FOR EACH STATEMENT EXECUTE FUNCTION cache_notify('sys:redirect:');-- \n
+
+CREATE TRIGGER trigger_access_cache_notify
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON access
+FOR EACH STATEMENT EXECUTE FUNCTION cache_notify('sys:access:');-- \n
+
+CREATE TRIGGER trigger_permission_cache_notify
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON permission
+FOR EACH STATEMENT EXECUTE FUNCTION cache_notify('sys:permission:');-- \n
+
+CREATE TRIGGER trigger_role_permission_cache_notify
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON role_permission
+FOR EACH STATEMENT EXECUTE FUNCTION cache_notify('sys:permission:');-- \n
+
+CREATE TRIGGER trigger_role_parent_cache_notify
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON role_parent
+FOR EACH STATEMENT EXECUTE FUNCTION cache_notify('sys:permission:', 'sys:access:');-- \n
@@ -11,14 +11,30 @@ impl Help {
 {}
 {} version: {}
 
-Usage: {} [start|stop|status|help] [-r <path to root folder>]
+Usage: {} [start|stop|status|reload|help] [-r <path to root folder>]
 
 Actions:
     start         : start server in the background mode
     stop          : stop server
     status        : show server status
+    reload        : restart server without dropping connections (not Windows)
+    plugin        : reload controllers from the folder "plugin" (feature "plugin")
+    module        : switch off the routes of the module with the answer 503 or 404, or switch on
+                    module off <name> [--code 404] | module on <name>
+    loglevel      : change the verbosity of the log of the running server, not more than the debug feature of the build
+                    loglevel <v|vv|vvv>
     run           : start server in interactive mode
     help          : show this help
+    linkcheck     : check links of the site, report 4xx/5xx answers and long redirect chains
+                    linkcheck <http://host:port> [--hops 2] [--limit 10000] [--host example.com]
+    redirect      : import redirects from the CSV file "url,redirect[,permanently]" (feature "redirect-db")
+                    redirect <path to file> [--dry-run]
+    migrate       : apply the SQL files "<version>_<name>.sql" of the folder "migrations" (feature "pgsql" or "mssql")
+                    migrate [--dry-run] [--rollback <number of the last migrations>]
+    extract-lang  : add the keys of lang("key") of "src/app" and {{ t.key }} of the templates to the language files,
+                    report the keys of the language files which are not used
+                    extract-lang [--src <folder of the controllers>] [--dry-run] (feature "lang-static" or "lang-reload")
+    sessions gc   : remove the expired sessions of the files or the database (feature "session-file" or "session-db")
     
 Options:
     -r            : path to root folder, where located the config file "config.toml"
@@ -1,4 +1,39 @@
-use sys::{app::app::App, web::action::ModuleMap};
+use std::{future::Future, pin::Pin};
+
+use sys::{
+    app::{app::App, run::Hooks},
+    web::{action::ModuleMap, router::Router},
+};
+
+pub use sys::app::config::{AppConfig, DbOptions};
+
+pub use sys::app::error::TinyError;
+
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+pub use sys::db::{
+    adapter::SlowQuery,
+    builder::{Cmp, Select, Update},
+    error::{DBError, DBErrorKind},
+    migrate::Migration,
+};
+
+#[cfg(feature = "pgsql")]
+pub use sys::db::pgsql::{QueryCursor, Transaction};
+
+#[cfg(feature = "fuzz")]
+#[doc(hidden)]
+pub use sys::net::parse::{CgiRequest, FastCGIHeader, HeadScan, HttpHead, Parse, ParseError};
+
+#[cfg(feature = "privacy")]
+pub use sys::web::privacy::{EraseFn, ExportFn, Privacy};
+
+pub use tiny_web_guard::controller;
+
+#[cfg(feature = "access-db")]
+pub use tiny_web_guard::guard;
+
+#[cfg(feature = "privacy")]
+use sys::web::privacy::PrivacyProvider;
 
 /// Show help message
 pub(crate) mod help;
@@ -7,10 +42,93 @@ pub(crate) mod help;
 pub mod sys;
 
 /// Different useful functions
-pub(crate) mod tool;
+pub mod tool;
+
+pub fn run(name: &str, version: &str, desc: &str, func: ModuleMap) -> Result<(), TinyError> {
+    App::run(name, version, desc, func, Router::default(), Hooks::default(), AppConfig::new())
+}
+
+/// Run with the static route table
+pub fn run_router(name: &str, version: &str, desc: &str, func: ModuleMap, router: Router) -> Result<(), TinyError> {
+    App::run(name, version, desc, func, router, Hooks::default(), AppConfig::new())
+}
+
+/// Run with the lifecycle hooks or the configuration in the code
+///
+/// ```ignore
+/// tiny_web::run_with(name, version, desc, addfn!(...)).router(router).on_start(start).on_stop(stop).start();
+/// ```
+pub fn run_with(name: &str, version: &str, desc: &str, func: ModuleMap) -> Server {
+    Server {
+        name: name.to_owned(),
+        version: version.to_owned(),
+        desc: desc.to_owned(),
+        func,
+        router: Router::default(),
+        hooks: Hooks::default(),
+        config: AppConfig::new(),
+    }
+}
+
+/// Lifecycle hook of the application
+///
+/// The start hook returns `false` to cancel the start of the server.
+pub type HookFn = fn() -> Pin<Box<dyn Future<Output = bool> + Send>>;
+
+/// Server with the lifecycle hooks, see `run_with`
+pub struct Server {
+    name: String,
+    version: String,
+    desc: String,
+    func: ModuleMap,
+    router: Router,
+    hooks: Hooks,
+    config: AppConfig,
+}
+
+impl Server {
+    /// Static route table
+    pub fn router(mut self, router: Router) -> Server {
+        self.router = router;
+        self
+    }
+
+    /// Hook before the server accepts connections, the hooks run in the order of adding
+    pub fn on_start(mut self, hook: HookFn) -> Server {
+        self.hooks.start.push(hook);
+        self
+    }
 
-pub fn run(name: &str, version: &str, desc: &str, func: ModuleMap) -> bool {
-    App::run(name, version, desc, func).is_ok()
+    /// Hook after the graceful shutdown of the server, the hooks run in the reverse order of adding
+    pub fn on_stop(mut self, hook: HookFn) -> Server {
+        self.hooks.stop.push(hook);
+        self
+    }
+
+    /// Data provider of the application for `Privacy::export` and `Privacy::erase`, for example the orders of the user
+    ///
+    /// ```ignore
+    /// fn export(tx: &mut Transaction, user_id: i64) -> Pin<Box<dyn Future<Output = Option<Value>> + Send + '_>> {
+    ///     Box::pin(async move { ... })
+    /// }
+    /// tiny_web::run_with(name, version, desc, addfn!(...)).privacy("orders", Some(export), Some(erase)).start();
+    /// ```
+    #[cfg(feature = "privacy")]
+    pub fn privacy(mut self, name: &str, export: Option<ExportFn>, erase: Option<EraseFn>) -> Server {
+        self.hooks.privacy.push(PrivacyProvider { name: name.to_owned(), export, erase });
+        self
+    }
+
+    /// Configuration in the code, overrides or replaces the file "init.toml"
+    pub fn config(mut self, config: AppConfig) -> Server {
+        self.config = config;
+        self
+    }
+
+    /// Run the application
+    pub fn start(self) -> Result<(), TinyError> {
+        App::run(&self.name, &self.version, &self.desc, self.func, self.router, self.hooks, self.config)
+    }
 }
 
 /// fnv1a_64 hash function
@@ -21,5 +139,16 @@ pub fn fnv1a_64(bytes: &[u8]) -> i64 {
         hash ^= u64::from(*c);
         hash = hash.wrapping_mul(0x100000001b3);
     }
-    unsafe { std::mem::transmute(hash) }
+    hash as i64
+}
+
+/// Continue the fnv1a_64 hash, `fnv1a_64_add(fnv1a_64(a), b) == fnv1a_64(a + b)`
+#[inline]
+pub(crate) fn fnv1a_64_add(hash: i64, bytes: &[u8]) -> i64 {
+    let mut hash = hash as u64;
+    for c in bytes {
+        hash ^= u64::from(*c);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash as i64
 }
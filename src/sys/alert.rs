@@ -0,0 +1,341 @@
+use std::{
+    collections::HashMap,
+    process,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use ring::rand::{SecureRandom, SystemRandom};
+use rustls::{pki_types::ServerName, ClientConfig, RootCertStore};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::mpsc::{channel, Receiver, Sender},
+    time::timeout,
+};
+use tokio_rustls::TlsConnector;
+
+use crate::{log, tool::to_hex};
+
+use super::{
+    app::init::{AlertConfig, AlertTarget},
+    log::LogView,
+    net::eyeballs::HappyEyeballs,
+};
+
+/// Number of the events in the queue of the forwarder, the other events are dropped
+const ALERT_QUEUE: usize = 256;
+/// Max number of the different codes and locations, the oldest is removed
+const ALERT_KEYS: usize = 1024;
+/// Timeout of the request to the receiver
+const ALERT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Aggregator, set when the section [alert] is present
+static ALERT: OnceLock<Aggregator> = OnceLock::new();
+
+/// Mozilla roots for the HTTPS receivers
+static TLS: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+
+struct Aggregator {
+    config: Arc<AlertConfig>,
+    sender: Sender<AlertEvent>,
+    /// Key is the code, the line and the file
+    counts: Mutex<HashMap<(u16, u32, String), AlertCount>>,
+}
+
+/// Rolling count of the same code and location
+#[derive(Debug, Clone)]
+pub(crate) struct AlertCount {
+    pub view: LogView,
+    pub number: u16,
+    pub file: String,
+    pub line: u32,
+    /// Text of the last event
+    pub text: String,
+    /// All events since the start
+    pub total: u64,
+    /// Events in the current interval
+    pub window: u64,
+    pub first: DateTime<Utc>,
+    pub last: DateTime<Utc>,
+    start: Instant,
+    sent: bool,
+}
+
+#[derive(Debug)]
+struct AlertEvent {
+    view: LogView,
+    number: u16,
+    file: String,
+    line: u32,
+    text: String,
+    request: Option<u64>,
+    /// Events of the previous interval which were not forwarded
+    repeated: u64,
+    total: u64,
+    /// First event of the code and location
+    first: DateTime<Utc>,
+    time: DateTime<Utc>,
+}
+
+/// Aggregation of the warnings and the errors of the log
+///
+/// The events with the same code and location are counted, only the first event of the interval
+/// is forwarded to the Sentry-compatible server and the webhook, with the number of the repeats.
+pub(crate) struct Alert;
+
+impl Alert {
+    /// Start the forwarder, only once
+    pub(crate) fn start(config: Arc<AlertConfig>) {
+        let (tx, rx) = channel(ALERT_QUEUE);
+        let aggregator = Aggregator {
+            config: Arc::clone(&config),
+            sender: tx,
+            counts: Mutex::new(HashMap::new()),
+        };
+        if ALERT.set(aggregator).is_ok() {
+            tokio::spawn(Alert::forward(config, rx));
+        }
+    }
+
+    /// Count the record of the log, called from `Log::save`
+    pub(crate) fn push(view: LogView, number: u16, file: &str, line: u32, text: &str, request: Option<u64>) {
+        let alert = match ALERT.get() {
+            Some(alert) => alert,
+            None => return,
+        };
+        // The errors of the forwarder are not forwarded
+        if view < alert.config.level || file == file!() {
+            return;
+        }
+        let event = {
+            let mut counts = match alert.counts.lock() {
+                Ok(counts) => counts,
+                Err(e) => e.into_inner(),
+            };
+            let key = (number, line, file.to_owned());
+            if !counts.contains_key(&key) && counts.len() >= ALERT_KEYS {
+                if let Some(old) = counts.iter().min_by_key(|(_, count)| count.last).map(|(key, _)| key.clone()) {
+                    counts.remove(&old);
+                }
+            }
+            let now = Utc::now();
+            let count = counts.entry(key).or_insert_with(|| AlertCount {
+                view,
+                number,
+                file: file.to_owned(),
+                line,
+                text: String::new(),
+                total: 0,
+                window: 0,
+                first: now,
+                last: now,
+                start: Instant::now(),
+                sent: false,
+            });
+            let mut repeated = 0;
+            if count.start.elapsed() >= alert.config.interval {
+                repeated = count.window.saturating_sub(1);
+                count.start = Instant::now();
+                count.window = 0;
+                count.sent = false;
+            }
+            count.view = view;
+            count.text = text.to_owned();
+            count.total += 1;
+            count.window += 1;
+            count.last = now;
+            if count.sent {
+                return;
+            }
+            // The skipped interval is not sampled again until its end
+            count.sent = true;
+            if !Alert::sample(alert.config.sample) {
+                return;
+            }
+            AlertEvent {
+                view: count.view,
+                number: count.number,
+                file: count.file.clone(),
+                line: count.line,
+                text: count.text.clone(),
+                request,
+                repeated,
+                total: count.total,
+                first: count.first,
+                time: now,
+            }
+        };
+        // The full queue drops the event, the log is not delayed by the receiver
+        let _ = alert.sender.try_send(event);
+    }
+
+    /// Rolling counts, the most frequent first
+    #[cfg(feature = "admin")]
+    pub(crate) fn list() -> Vec<AlertCount> {
+        let alert = match ALERT.get() {
+            Some(alert) => alert,
+            None => return Vec::new(),
+        };
+        let counts = match alert.counts.lock() {
+            Ok(counts) => counts,
+            Err(e) => e.into_inner(),
+        };
+        let mut list: Vec<AlertCount> = counts
+            .values()
+            .map(|count| {
+                let mut count = count.clone();
+                if count.start.elapsed() >= alert.config.interval {
+                    count.window = 0;
+                }
+                count
+            })
+            .collect();
+        list.sort_by(|a, b| b.total.cmp(&a.total).then(b.last.cmp(&a.last)));
+        list
+    }
+
+    async fn forward(config: Arc<AlertConfig>, mut rx: Receiver<AlertEvent>) {
+        while let Some(event) = rx.recv().await {
+            if let Some(target) = &config.sentry {
+                let auth = format!(
+                    "Sentry sentry_version=7, sentry_client=tiny-web/{}, sentry_key={}",
+                    env!("CARGO_PKG_VERSION"),
+                    target.key.as_deref().unwrap_or_default()
+                );
+                let body = Alert::sentry(&config, &event).to_string();
+                if let Err(_e) = Alert::post(target, Some(&auth), &body).await {
+                    log!(warning, 0, "Sentry {}:{}{}. Error: {}", target.host, target.port, target.path, _e);
+                }
+            }
+            if let Some(target) = &config.webhook {
+                let body = Alert::webhook(&config, &event).to_string();
+                if let Err(_e) = Alert::post(target, None, &body).await {
+                    log!(warning, 0, "Webhook {}:{}{}. Error: {}", target.host, target.port, target.path, _e);
+                }
+            }
+        }
+    }
+
+    /// Event of the store API of Sentry, the fingerprint is the code and the location
+    fn sentry(config: &AlertConfig, event: &AlertEvent) -> Value {
+        let level = match event.view {
+            LogView::Info => "info",
+            LogView::Warning => "warning",
+            LogView::Stop => "error",
+            LogView::Error | LogView::Critical => "fatal",
+        };
+        let culprit = format!("{}:{}", event.file, event.line);
+        let mut value = json!({
+            "event_id": Alert::event_id(),
+            "timestamp": event.time.to_rfc3339(),
+            "platform": "other",
+            "level": level,
+            "logger": "tiny-web",
+            "server_name": config.service,
+            "culprit": culprit,
+            "fingerprint": [event.number.to_string(), culprit],
+            "message": { "formatted": event.text },
+            "tags": { "code": event.number.to_string(), "pid": process::id().to_string() },
+            "extra": {
+                "repeated": event.repeated,
+                "total": event.total,
+                "first": event.first.to_rfc3339(),
+                "request": event.request,
+            },
+        });
+        if let Some(environment) = &config.environment {
+            value["environment"] = Value::String(environment.clone());
+        }
+        value
+    }
+
+    fn webhook(config: &AlertConfig, event: &AlertEvent) -> Value {
+        json!({
+            "service": config.service,
+            "environment": config.environment,
+            "pid": process::id(),
+            "level": format!("{:?}", event.view),
+            "code": event.number,
+            "file": event.file,
+            "line": event.line,
+            "text": event.text,
+            "request": event.request,
+            "repeated": event.repeated,
+            "total": event.total,
+            "first": event.first.to_rfc3339(),
+            "time": event.time.to_rfc3339(),
+        })
+    }
+
+    /// Simple HTTP/1.1 POST request, HTTPS with the Mozilla roots
+    async fn post(target: &AlertTarget, auth: Option<&str>, body: &str) -> Result<(), String> {
+        timeout(ALERT_TIMEOUT, async {
+            let tcp = HappyEyeballs::connect(&target.host, target.port).await.map_err(|e| e.to_string())?;
+            let mut request = format!(
+                "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+                target.path,
+                target.host,
+                target.port,
+                body.len()
+            );
+            if let Some(auth) = auth {
+                request.push_str(&format!("X-Sentry-Auth: {}\r\n", auth));
+            }
+            request.push_str("\r\n");
+            request.push_str(body);
+            let status = if target.tls {
+                let name = ServerName::try_from(target.host.clone()).map_err(|e| e.to_string())?;
+                let tls = TLS.get_or_init(|| {
+                    let mut roots = RootCertStore::empty();
+                    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+                    Arc::new(ClientConfig::builder().with_root_certificates(roots).with_no_client_auth())
+                });
+                let mut stream = TlsConnector::from(Arc::clone(tls)).connect(name, tcp).await.map_err(|e| e.to_string())?;
+                Alert::send(&mut stream, &request).await?
+            } else {
+                let mut stream = tcp;
+                Alert::send(&mut stream, &request).await?
+            };
+            match status.split(' ').nth(1) {
+                Some(code) if code.starts_with('2') => Ok(()),
+                _ => Err(status.lines().next().unwrap_or_default().to_owned()),
+            }
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    /// First bytes of the answer
+    async fn send<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S, request: &str) -> Result<String, String> {
+        stream.write_all(request.as_bytes()).await.map_err(|e| e.to_string())?;
+        stream.flush().await.map_err(|e| e.to_string())?;
+        let mut buf = [0u8; 1024];
+        let len = stream.read(&mut buf).await.map_err(|e| e.to_string())?;
+        Ok(String::from_utf8_lossy(&buf[..len]).into_owned())
+    }
+
+    fn sample(percent: u64) -> bool {
+        if percent >= 100 {
+            return true;
+        }
+        let mut bytes = [0u8; 8];
+        if SystemRandom::new().fill(&mut bytes).is_err() {
+            return true;
+        }
+        u64::from_le_bytes(bytes) % 100 < percent
+    }
+
+    /// 32 hex digits without the dashes
+    fn event_id() -> String {
+        let mut bytes = [0u8; 16];
+        if SystemRandom::new().fill(&mut bytes).is_err() {
+            let now = Utc::now().timestamp_nanos_opt().unwrap_or_default().to_be_bytes();
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = now[i % now.len()] ^ (i as u8);
+            }
+        }
+        to_hex(&bytes)
+    }
+}
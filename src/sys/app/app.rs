@@ -11,16 +11,35 @@ use crate::{
     fnv1a_64,
     help::Help,
     log,
-    sys::{net::stream::Socket, web::action::ModuleMap},
+    sys::{
+        net::stream::Socket,
+        web::{action::ModuleMap, router::Router},
+    },
 };
 
 #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
 use crate::sys::log::{InitLog, Log};
 
+#[cfg(feature = "redirect-db")]
+use super::redirect::RedirectImport;
+
+use super::linkcheck::LinkCheck;
+
+#[cfg(any(feature = "session-file", feature = "session-db"))]
+use super::sessions::Sessions;
+
+#[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+use super::extract::LangExtract;
+
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+use super::migrate::Migrate;
+
 use super::{
     arg::{Arg, Mode},
+    config::AppConfig,
+    error::TinyError,
     init::{Init, SIGNAL_TIMEOUT, SIGNAL_TIMEOUT_WAIT},
-    run::Run,
+    run::{Hooks, Run},
 };
 
 /// Application
@@ -28,30 +47,58 @@ use super::{
 pub(crate) struct App {}
 
 impl App {
-    pub(crate) fn run(name: &str, version: &str, desc: &str, engine: ModuleMap) -> Result<(), ()> {
+    pub(crate) fn run(
+        name: &str,
+        version: &str,
+        desc: &str,
+        engine: ModuleMap,
+        router: Router,
+        hooks: Hooks,
+        config: AppConfig,
+    ) -> Result<(), TinyError> {
         let args = match Arg::get() {
             Ok(args) => args,
-            Err(_e) => {
+            Err(e) => {
                 #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
                 Log::init(InitLog::None);
-                log!(stop, 0, "Неможливо прочитати параметри запуска. Помилка: {}", _e);
-                return Err(());
+                log!(stop, 0, "Неможливо прочитати параметри запуска. Помилка: {}", e);
+                return Err(TinyError::Args(e.to_string()));
             }
         };
-        let init = match Init::parse(name.to_owned(), version.to_owned(), desc.to_owned(), &args.root) {
+        let init = match Init::parse(name.to_owned(), version.to_owned(), desc.to_owned(), &args.root, &config) {
             Ok(init) => init,
-            Err(_e) => {
-                log!(stop, 0, "Неможливо прочитати файл з налаштуваннями чи файл неправильного формату. Помилка: {}", _e);
-                return Err(());
+            Err(e) => {
+                log!(stop, 0, "Неможливо прочитати файл з налаштуваннями чи файл неправильного формату. Помилка: {}", e);
+                return Err(TinyError::Config(e.to_string()));
             }
         };
 
         match args.mode {
             Mode::Help => Help::show(init),
             Mode::Start => App::start(args),
-            Mode::Stop => App::stop(init),
+            Mode::Stop => App::signal(init, "stop", &[]),
+            #[cfg(not(target_family = "windows"))]
+            Mode::Reload => App::signal(init, "reload", &[]),
+            #[cfg(feature = "plugin")]
+            Mode::Plugin => App::signal(init, "plugin", &[]),
+            Mode::Module(name, code) => App::signal(init, "module", &[fnv1a_64(name.as_bytes()), i64::from(code)]),
+            #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+            Mode::LogLevel(level) => App::signal(init, "loglevel", &[i64::from(level)]),
             Mode::Status => App::status(init),
-            Mode::Run => return Run::start(args, init, engine),
+            Mode::Run => return Run::start(args, init, engine, router, hooks),
+            Mode::LinkCheck(option) => return LinkCheck::run(init, option).map_err(|_| TinyError::Command("linkcheck".to_owned())),
+            #[cfg(feature = "redirect-db")]
+            Mode::Redirect(path, dry_run) => {
+                return RedirectImport::run(init, &path, dry_run).map_err(|_| TinyError::Command("redirect".to_owned()))
+            }
+            #[cfg(any(feature = "pgsql", feature = "mssql"))]
+            Mode::Migrate(option) => return Migrate::run(init, &args.root, option).map_err(|_| TinyError::Command("migrate".to_owned())),
+            #[cfg(any(feature = "session-file", feature = "session-db"))]
+            Mode::SessionGc => return Sessions::gc(init).map_err(|_| TinyError::Command("sessions gc".to_owned())),
+            #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+            Mode::ExtractLang(option) => {
+                return LangExtract::run(init, &args.root, option).map_err(|_| TinyError::Command("extract-lang".to_owned()))
+            }
         }
         Ok(())
     }
@@ -89,30 +136,34 @@ impl App {
         };
     }
 
-    fn stop(init: Init) {
-        let stop = fnv1a_64(format!("stop{}", init.web.salt).as_bytes()).to_be_bytes();
+    /// Send the signal "stop", "reload", "plugin", "module" or "loglevel" with the arguments to the running server
+    fn signal(init: Init, name: &str, args: &[i64]) {
+        let mut signal = fnv1a_64(format!("{}{}", name, init.web.salt).as_bytes()).to_be_bytes().to_vec();
+        for arg in args {
+            signal.extend_from_slice(&arg.to_be_bytes());
+        }
         match init.net.rpc {
             Socket::Inet(socket) => {
                 let mut tcp = match TcpStream::connect_timeout(&socket, Duration::from_millis(SIGNAL_TIMEOUT)) {
                     Ok(tcp) => tcp,
                     Err(_e) => {
-                        log!(stop, 0, "Неможливо відправити сигнал stop. Помилка: {}", _e);
+                        log!(stop, 0, "Неможливо відправити сигнал {}. Помилка: {}", name, _e);
                         return;
                     }
                 };
 
-                if let Err(_e) = tcp.write(&stop) {
-                    log!(stop, 0, "Неможливо відправити сигнал stop. Помилка: {}", _e);
+                if let Err(_e) = tcp.write(&signal) {
+                    log!(stop, 0, "Неможливо відправити сигнал {}. Помилка: {}", name, _e);
                     return;
                 };
                 if let Err(_e) = tcp.set_read_timeout(Some(Duration::from_millis(SIGNAL_TIMEOUT_WAIT))) {
-                    log!(stop, 0, "Неможливо відправити сигнал stop. Помилка: {}", _e);
+                    log!(stop, 0, "Неможливо відправити сигнал {}. Помилка: {}", name, _e);
                     return;
                 }
 
                 let mut buf: [u8; 8] = [0; 8];
                 if let Err(_e) = tcp.read_exact(&mut buf) {
-                    log!(stop, 0, "Неможливо відправити сигнал stop. Помилка: {}", _e);
+                    log!(stop, 0, "Неможливо відправити сигнал {}. Помилка: {}", name, _e);
                     return;
                 };
                 let _pid = u64::from_be_bytes(buf);
@@ -123,26 +174,26 @@ impl App {
                 let mut tcp = match UnixStream::connect(path) {
                     Ok(tcp) => tcp,
                     Err(_e) => {
-                        log!(stop, 0, "Неможливо відправити сигнал stop. Помилка: {}", _e);
+                        log!(stop, 0, "Неможливо відправити сигнал {}. Помилка: {}", name, _e);
                         return;
                     }
                 };
                 if let Err(_e) = tcp.set_write_timeout(Some(Duration::from_millis(SIGNAL_TIMEOUT))) {
-                    log!(stop, 0, "Неможливо відправити сигнал stop. Помилка: {}", _e);
+                    log!(stop, 0, "Неможливо відправити сигнал {}. Помилка: {}", name, _e);
                     return;
                 }
-                if let Err(_e) = tcp.write(&stop) {
-                    log!(stop, 0, "Неможливо відправити сигнал stop. Помилка: {}", _e);
+                if let Err(_e) = tcp.write(&signal) {
+                    log!(stop, 0, "Неможливо відправити сигнал {}. Помилка: {}", name, _e);
                     return;
                 }
                 if let Err(_e) = tcp.set_read_timeout(Some(Duration::from_millis(SIGNAL_TIMEOUT_WAIT))) {
-                    log!(stop, 0, "Неможливо відправити сигнал stop. Помилка: {}", _e);
+                    log!(stop, 0, "Неможливо відправити сигнал {}. Помилка: {}", name, _e);
                     return;
                 }
 
                 let mut buf: [u8; 8] = [0; 8];
                 if let Err(_e) = tcp.read_exact(&mut buf) {
-                    log!(stop, 0, "Неможливо відправити сигнал stop. Помилка: {}", _e);
+                    log!(stop, 0, "Неможливо відправити сигнал {}. Помилка: {}", name, _e);
                     return;
                 };
                 let _pid = u64::from_be_bytes(buf);
@@ -1,5 +1,16 @@
 use std::{env, io::Error, path::PathBuf, sync::Arc};
 
+use super::linkcheck::LinkCheckOption;
+
+#[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+use crate::sys::log::Log;
+
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+use super::migrate::MigrateOption;
+
+#[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+use super::extract::LangExtractOption;
+
 #[derive(Debug)]
 pub(crate) enum Mode {
     Help,
@@ -7,6 +18,31 @@ pub(crate) enum Mode {
     Stop,
     Status,
     Run,
+    /// Zero-downtime restart with the handover of the listening socket
+    #[cfg(not(target_family = "windows"))]
+    Reload,
+    /// Reload the plugins of the running server
+    #[cfg(feature = "plugin")]
+    Plugin,
+    /// Switch the module on or off at runtime, the name and the code of the answer, 0 is on
+    Module(String, u16),
+    /// Change the verbosity of the log of the running server, from 1 to 3
+    #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+    LogLevel(u8),
+    /// Broken-link checker
+    LinkCheck(LinkCheckOption),
+    /// Import redirects from the CSV file, the flag is dry run
+    #[cfg(feature = "redirect-db")]
+    Redirect(PathBuf, bool),
+    /// Apply or roll back the migrations of the folder "migrations"
+    #[cfg(any(feature = "pgsql", feature = "mssql"))]
+    Migrate(MigrateOption),
+    /// Remove the expired sessions of the files or the database
+    #[cfg(any(feature = "session-file", feature = "session-db"))]
+    SessionGc,
+    /// Add the missing keys of the translations of the controllers and the templates to the language files
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    ExtractLang(LangExtractOption),
 }
 
 #[derive(Debug)]
@@ -37,6 +73,82 @@ impl Arg {
                 "stop" => mode = Mode::Stop,
                 "status" => mode = Mode::Status,
                 "run" => mode = Mode::Run,
+                #[cfg(not(target_family = "windows"))]
+                "reload" => mode = Mode::Reload,
+                #[cfg(feature = "plugin")]
+                "plugin" => mode = Mode::Plugin,
+                "module" => match (args.next().as_deref(), args.next()) {
+                    (Some("on"), Some(name)) => mode = Mode::Module(name, 0),
+                    (Some("off"), Some(name)) => mode = Mode::Module(name, 503),
+                    _ => break,
+                },
+                "--code" => {
+                    if let (Mode::Module(_, code), Some(Ok(value))) = (&mut mode, args.next().map(|v| v.parse())) {
+                        if *code != 0 {
+                            *code = value;
+                        }
+                    }
+                }
+                #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+                "loglevel" => match args.next().as_deref().and_then(Log::parse_level) {
+                    Some(level) => mode = Mode::LogLevel(level),
+                    None => break,
+                },
+                "linkcheck" => match args.next() {
+                    Some(url) => mode = Mode::LinkCheck(LinkCheckOption { url, hops: 2, limit: 10000, host: None }),
+                    None => break,
+                },
+                "--hops" => {
+                    if let (Mode::LinkCheck(option), Some(Ok(hops))) = (&mut mode, args.next().map(|v| v.parse())) {
+                        option.hops = hops;
+                    }
+                }
+                "--limit" => {
+                    if let (Mode::LinkCheck(option), Some(Ok(limit))) = (&mut mode, args.next().map(|v| v.parse())) {
+                        option.limit = limit;
+                    }
+                }
+                "--host" => {
+                    if let (Mode::LinkCheck(option), Some(host)) = (&mut mode, args.next()) {
+                        option.host = Some(host);
+                    }
+                }
+                #[cfg(feature = "redirect-db")]
+                "redirect" => match args.next() {
+                    Some(path) => mode = Mode::Redirect(path.into(), false),
+                    None => break,
+                },
+                #[cfg(any(feature = "pgsql", feature = "mssql"))]
+                "migrate" => mode = Mode::Migrate(MigrateOption { dry_run: false, rollback: None }),
+                #[cfg(any(feature = "pgsql", feature = "mssql"))]
+                "--rollback" => {
+                    if let (Mode::Migrate(option), Some(Ok(steps))) = (&mut mode, args.next().map(|v| v.parse())) {
+                        option.rollback = Some(steps);
+                    }
+                }
+                #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+                "extract-lang" => mode = Mode::ExtractLang(LangExtractOption { src: None, dry_run: false }),
+                #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+                "--src" => {
+                    if let (Mode::ExtractLang(option), Some(path)) = (&mut mode, args.next()) {
+                        option.src = Some(path.into());
+                    }
+                }
+                #[cfg(any(feature = "pgsql", feature = "mssql", feature = "lang-static", feature = "lang-reload"))]
+                "--dry-run" => match &mut mode {
+                    #[cfg(feature = "redirect-db")]
+                    Mode::Redirect(_, dry_run) => *dry_run = true,
+                    #[cfg(any(feature = "pgsql", feature = "mssql"))]
+                    Mode::Migrate(option) => option.dry_run = true,
+                    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+                    Mode::ExtractLang(option) => option.dry_run = true,
+                    _ => {}
+                },
+                #[cfg(any(feature = "session-file", feature = "session-db"))]
+                "sessions" => match args.next().as_deref() {
+                    Some("gc") => mode = Mode::SessionGc,
+                    _ => break,
+                },
                 "-r" => match args.next() {
                     Some(path) => root = path.into(),
                     None => break,
@@ -0,0 +1,174 @@
+use toml::{Table, Value};
+
+/// Connection to the database, the section [db]
+#[derive(Debug, Clone)]
+pub struct DbOptions {
+    pub host: String,
+    pub port: Option<u16>,
+    pub name: String,
+    pub user: Option<String>,
+    pub pwd: Option<String>,
+    pub ssl: bool,
+    /// Number of the connections, `None` is "auto"
+    pub max: Option<usize>,
+}
+
+/// Configuration of the application in the code
+///
+/// The values override the same parameters of the file "init.toml", the environment variables TINY_* override both.
+/// After `without_file` the file is not read, the parameters without the values use the defaults.
+///
+/// # Example
+///
+/// ```ignore
+/// let config = AppConfig::new().bind("0.0.0.0:8080").workers(8).salt("secret");
+/// tiny_web::run_with(name, version, desc, addfn!(...)).config(config).start();
+/// ```
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    /// Read the file "init.toml"
+    pub(crate) file: bool,
+    pub(crate) table: Table,
+}
+
+impl Default for AppConfig {
+    fn default() -> AppConfig {
+        AppConfig::new()
+    }
+}
+
+impl AppConfig {
+    /// Configuration of the file "init.toml" with the overrides
+    pub fn new() -> AppConfig {
+        AppConfig { file: true, table: Table::new() }
+    }
+
+    /// Configuration without the file "init.toml"
+    ///
+    /// The defaults: bind "127.0.0.1:12500", rpc "127.0.0.1:12501" from "127.0.0.1", [async] is "auto".
+    /// The salt and the index controller are required.
+    pub fn without_file() -> AppConfig {
+        let mut config = AppConfig { file: false, table: Table::new() };
+        config = config.bind("127.0.0.1:12500").bind_from("127.0.0.1").rpc("127.0.0.1:12501").rpc_from("127.0.0.1");
+        for key in [
+            "worker_threads",
+            "event_interval",
+            "global_queue_interval",
+            "max_blocking_threads",
+            "max_io_events_per_tick",
+            "thread_keep_alive",
+            "thread_stack_size",
+        ] {
+            config = config.set("async", key, "auto");
+        }
+        config
+    }
+
+    /// Any parameter of the section, for example `set("upload", "max_files", 10)`
+    pub fn set(mut self, section: &str, key: &str, value: impl Into<Value>) -> AppConfig {
+        let section = self.table.entry(section).or_insert_with(|| Value::Table(Table::new()));
+        if let Value::Table(table) = section {
+            table.insert(key.to_owned(), value.into());
+        }
+        self
+    }
+
+    /// Log file
+    pub fn log(mut self, path: &str) -> AppConfig {
+        self.table.insert("log".to_owned(), Value::String(path.to_owned()));
+        self
+    }
+
+    /// Address "ip:port" or the path of the Unix domain socket
+    pub fn bind(self, addr: &str) -> AppConfig {
+        self.set("net", "bind", addr)
+    }
+
+    /// IP address from which to accept connections, "0.0.0.0" is any
+    pub fn bind_from(self, ip: &str) -> AppConfig {
+        self.set("net", "bind_from", ip)
+    }
+
+    /// Address of the management of the server
+    pub fn rpc(self, addr: &str) -> AppConfig {
+        self.set("net", "rpc", addr)
+    }
+
+    /// IP address from which to accept the management
+    pub fn rpc_from(self, ip: &str) -> AppConfig {
+        self.set("net", "rpc_from", ip)
+    }
+
+    /// Number of the worker threads
+    pub fn workers(self, count: usize) -> AppConfig {
+        self.set("async", "worker_threads", count as i64)
+    }
+
+    pub fn salt(self, salt: &str) -> AppConfig {
+        self.set("web", "salt", salt)
+    }
+
+    /// Default language, ISO 639-1
+    pub fn lang(self, lang: &str) -> AppConfig {
+        self.set("web", "lang", lang)
+    }
+
+    /// Name of the session cookie
+    pub fn session(self, name: &str) -> AppConfig {
+        self.set("web", "session", name)
+    }
+
+    /// Controller of the main page
+    pub fn index(self, module: &str, class: &str, action: &str) -> AppConfig {
+        self.set("web", "index", vec![module, class, action])
+    }
+
+    /// Controller of the page not found
+    pub fn not_found(self, module: &str, class: &str, action: &str) -> AppConfig {
+        self.set("web", "not_found", vec![module, class, action])
+    }
+
+    /// Controller of the page of the panic of the controller
+    pub fn action_err(self, module: &str, class: &str, action: &str) -> AppConfig {
+        self.set("web", "action_err", vec![module, class, action])
+    }
+
+    /// Route of the url pattern, the same as the section [route]
+    pub fn route(self, pattern: &str, module: &str, class: &str, action: &str) -> AppConfig {
+        self.set("route", pattern, vec![module, class, action])
+    }
+
+    /// Connection to the database
+    pub fn db(mut self, db: DbOptions) -> AppConfig {
+        self = self.set("db", "host", db.host).set("db", "name", db.name).set("db", "ssl", db.ssl);
+        if let Some(port) = db.port {
+            self = self.set("db", "port", i64::from(port));
+        }
+        if let Some(user) = db.user {
+            self = self.set("db", "user", user);
+        }
+        if let Some(pwd) = db.pwd {
+            self = self.set("db", "pwd", pwd);
+        }
+        match db.max {
+            Some(max) => self.set("db", "max", max as i64),
+            None => self.set("db", "max", "auto"),
+        }
+    }
+
+    /// Add the values to the parameters of the file
+    pub(crate) fn merge(&self, file: &mut Table) {
+        for (key, value) in &self.table {
+            match (file.get_mut(key), value) {
+                (Some(Value::Table(section)), Value::Table(table)) => {
+                    for (key, value) in table {
+                        section.insert(key.clone(), value.clone());
+                    }
+                }
+                _ => {
+                    file.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+}
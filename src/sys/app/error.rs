@@ -0,0 +1,61 @@
+use std::fmt;
+
+/// Error of the application, returned by `run`, `run_router` and `Server::start`
+///
+/// The details are also written to the log.
+///
+/// # Example
+///
+/// ```ignore
+/// match tiny_web::run(name, version, desc, addfn!(...)) {
+///     Ok(()) => {}
+///     Err(TinyError::Bind(e)) => eprintln!("The port is busy: {}", e),
+///     Err(e) => eprintln!("{}", e),
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TinyError {
+    /// Arguments of the command line
+    Args(String),
+    /// File "init.toml", `AppConfig` or the route table
+    Config(String),
+    /// Runtime of tokio
+    Runtime(String),
+    /// Listening socket or the rpc socket
+    Bind(String),
+    /// Connection to the database or the data of the start from it
+    Db(String),
+    /// Certificates of https
+    Tls(String),
+    /// Templates or translations
+    Template(String),
+    /// Sessions or Redis
+    Service(String),
+    /// The hook `on_start` cancelled the start or the hook `on_stop` failed
+    Hook,
+    /// Command of the command line, for example "migrate" or "linkcheck", failed
+    Command(String),
+    /// Stop of the server, for example the socket file is not removed
+    Stop(String),
+}
+
+impl fmt::Display for TinyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TinyError::Args(e) => write!(f, "Args: {}", e),
+            TinyError::Config(e) => write!(f, "Config: {}", e),
+            TinyError::Runtime(e) => write!(f, "Runtime: {}", e),
+            TinyError::Bind(e) => write!(f, "Bind: {}", e),
+            TinyError::Db(e) => write!(f, "Db: {}", e),
+            TinyError::Tls(e) => write!(f, "Tls: {}", e),
+            TinyError::Template(e) => write!(f, "Template: {}", e),
+            TinyError::Service(e) => write!(f, "Service: {}", e),
+            TinyError::Hook => f.write_str("Hook: the hook of the start or the stop failed"),
+            TinyError::Command(e) => write!(f, "Command: {}", e),
+            TinyError::Stop(e) => write!(f, "Stop: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TinyError {}
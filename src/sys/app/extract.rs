@@ -0,0 +1,244 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{create_dir_all, read_dir, read_to_string, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use toml::Table;
+
+use crate::log;
+
+use super::init::Init;
+
+/// Comment of the added key, the value is the key until the translation
+const TODO: &str = "# TODO: translate";
+
+/// Options of the extraction of the translations
+#[derive(Debug)]
+pub(crate) struct LangExtractOption {
+    /// Folder of the controllers "<module>/<class>.rs", default "src/app" of the root folder
+    pub src: Option<PathBuf>,
+    /// Only show the missing keys
+    pub dry_run: bool,
+}
+
+/// Keys of the translations by the module and the class
+type Keys = BTreeMap<(String, String), BTreeSet<String>>;
+
+/// Extraction of the keys of the translations from the controllers and the templates
+///
+/// The controllers "src/app/<module>/<class>.rs" are scanned for `lang("key")`, `set_lang("key")` and
+/// `set_lang_arr(&["key", ...])`, the templates "app/<module>/<class>/*.html" for `t.key`.
+/// The missing keys are appended to "app/<module>/<class>/lang.<code>.toml" of each language with the comment
+/// "# TODO: translate", the value is the key. The languages are the files of the folder "app" and [web] lang.
+/// The keys of the language files which are not found in the controllers and the templates are only reported,
+/// the keys of `lang(variable)` can not be found.
+pub(crate) struct LangExtract;
+
+impl LangExtract {
+    pub(crate) fn run(init: Init, root: &Path, option: LangExtractOption) -> Result<(), ()> {
+        let app = root.join("app");
+        let src = option.src.unwrap_or_else(|| root.join("src").join("app"));
+
+        let mut keys = Keys::new();
+        let mut codes = BTreeSet::new();
+        codes.insert(init.web.lang.as_ref().clone());
+        LangExtract::walk(&src, &mut |module, class, path| {
+            if path.extension().and_then(|ext| ext.to_str()) == Some("rs") {
+                if let Ok(text) = read_to_string(path) {
+                    let class = class.strip_suffix(".rs").unwrap_or(class);
+                    keys.entry((module.to_owned(), class.to_owned())).or_default().extend(LangExtract::code_keys(&text));
+                }
+            }
+        });
+        LangExtract::walk(&app, &mut |module, class, _| {
+            keys.entry((module.to_owned(), class.to_owned())).or_default();
+        });
+        for ((module, class), list) in keys.iter_mut() {
+            let dir = app.join(module).join(class);
+            let files = match read_dir(&dir) {
+                Ok(files) => files,
+                Err(_) => continue,
+            };
+            for entry in files.flatten() {
+                let path = entry.path();
+                let name = match path.file_name().and_then(|name| name.to_str()) {
+                    Some(name) => name,
+                    None => continue,
+                };
+                if name.ends_with(".html") {
+                    if let Ok(text) = read_to_string(&path) {
+                        list.extend(LangExtract::template_keys(&text));
+                    }
+                } else if name.starts_with("lang.") && name.ends_with(".toml") && name.len() == 12 {
+                    codes.insert(name[5..7].to_owned());
+                }
+            }
+        }
+
+        let mut total = 0;
+        let mut orphaned = 0;
+        let mut error = false;
+        for ((module, class), list) in &keys {
+            for code in &codes {
+                let path = app.join(module).join(class).join(format!("lang.{}.toml", code));
+                let exists = match read_to_string(&path) {
+                    Ok(text) => match text.parse::<Table>() {
+                        Ok(table) => table,
+                        Err(e) => {
+                            println!("{}: {}", path.display(), e);
+                            error = true;
+                            continue;
+                        }
+                    },
+                    Err(_) => Table::new(),
+                };
+                let unused: Vec<&String> = exists.keys().filter(|key| !list.contains(*key)).collect();
+                if !unused.is_empty() {
+                    println!("{}: {} not used", path.display(), unused.len());
+                    for key in &unused {
+                        println!("    {}", key);
+                    }
+                    orphaned += unused.len();
+                }
+                let missing: Vec<&String> = list.iter().filter(|key| !exists.contains_key(*key)).collect();
+                if missing.is_empty() {
+                    continue;
+                }
+                println!("{}: +{}", path.display(), missing.len());
+                for key in &missing {
+                    println!("    {}", key);
+                }
+                total += missing.len();
+                if !option.dry_run {
+                    if let Err(e) = LangExtract::append(&path, &missing) {
+                        log!(warning, 0, "{:?}. Error: {}", path, e);
+                        println!("{}: {}", path.display(), e);
+                        error = true;
+                    }
+                }
+            }
+        }
+        match option.dry_run {
+            false => println!("Added: {}. Not used: {}", total, orphaned),
+            true => println!("Dry run. Would be added: {}. Not used: {}", total, orphaned),
+        }
+        if error {
+            return Err(());
+        }
+        Ok(())
+    }
+
+    /// Items of the folders "<module>/<class>", the class is the name of the file or the folder
+    fn walk(root: &Path, f: &mut impl FnMut(&str, &str, &Path)) {
+        let modules = match read_dir(root) {
+            Ok(modules) => modules,
+            Err(_) => return,
+        };
+        for module in modules.flatten() {
+            let path = module.path();
+            let module = match (path.is_dir(), path.file_name().and_then(|name| name.to_str())) {
+                (true, Some(module)) => module.to_owned(),
+                _ => continue,
+            };
+            let classes = match read_dir(&path) {
+                Ok(classes) => classes,
+                Err(_) => continue,
+            };
+            for class in classes.flatten() {
+                let path = class.path();
+                if let Some(class) = path.file_name().and_then(|name| name.to_str()) {
+                    f(&module, class, &path);
+                }
+            }
+        }
+    }
+
+    /// Keys of `lang("key")`, `lang_fmt("key", ..)`, `lang_plural("key", ..)`, `set_lang("key")` and `set_lang_arr(&["key", ...])` of the controller
+    fn code_keys(text: &str) -> Vec<String> {
+        let mut keys = Vec::new();
+        for (name, list) in
+            [(".lang(", false), (".lang_fmt(", false), (".lang_plural(", false), (".set_lang(", false), (".set_lang_arr(", true)]
+        {
+            let mut rest = text;
+            while let Some(start) = rest.find(name) {
+                rest = &rest[start + name.len()..];
+                let args = rest.trim_start();
+                let args = match list {
+                    true => match args.strip_prefix("&[").or_else(|| args.strip_prefix('[')) {
+                        Some(args) => &args[..args.find(']').unwrap_or(args.len())],
+                        None => continue,
+                    },
+                    false => &args[..args.find(')').unwrap_or(args.len())],
+                };
+                let mut args = args.trim_start();
+                while let Some(body) = args.strip_prefix('"') {
+                    let end = match body.find('"') {
+                        Some(end) => end,
+                        None => break,
+                    };
+                    if end > 0 && !body[..end].contains('\\') {
+                        keys.push(body[..end].to_owned());
+                    }
+                    if !list {
+                        break;
+                    }
+                    args = body[end + 1..].trim_start().trim_start_matches(',').trim_start();
+                }
+            }
+        }
+        keys
+    }
+
+    /// Keys of `t.key` in the tags "{{ }}" and "{% %}" of the template
+    fn template_keys(text: &str) -> Vec<String> {
+        let mut keys = Vec::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            rest = &rest[start + 1..];
+            let close = match rest.as_bytes().first() {
+                Some(b'{') => "}}",
+                Some(b'%') => "%}",
+                _ => continue,
+            };
+            let end = match rest.find(close) {
+                Some(end) => end,
+                None => break,
+            };
+            let tag = &rest[1..end];
+            rest = &rest[end..];
+            let mut prev = ' ';
+            for (pos, c) in tag.char_indices() {
+                if c == 't' && !(prev.is_alphanumeric() || prev == '_' || prev == '.') && tag[pos + 1..].starts_with('.') {
+                    let key: String = tag[pos + 2..].chars().take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '-').collect();
+                    if !key.is_empty() {
+                        keys.push(key);
+                    }
+                }
+                prev = c;
+            }
+        }
+        keys
+    }
+
+    /// Append the keys to the end of the file, the comments and the order of the file are kept
+    fn append(path: &Path, keys: &[&String]) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        let mut text = String::new();
+        if path.metadata().map(|meta| meta.len() > 0).unwrap_or(false) && !read_to_string(path)?.ends_with('\n') {
+            text.push('\n');
+        }
+        for key in keys {
+            let value = toml::Value::String(key.to_string()).to_string();
+            let name = match key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                true => key.to_string(),
+                false => value.clone(),
+            };
+            text.push_str(&format!("{} = {} {}\n", name, value, TODO));
+        }
+        OpenOptions::new().create(true).append(true).open(path)?.write_all(text.as_bytes())
+    }
+}
@@ -1,6 +1,25 @@
-#[cfg(any(feature = "session-memory", feature = "session-file"))]
+#[cfg(any(
+    feature = "session-memory",
+    feature = "session-file",
+    feature = "profile",
+    feature = "storage"
+))]
 use std::path::PathBuf;
+#[cfg(any(
+    feature = "pgsql",
+    feature = "mssql",
+    feature = "profile",
+    feature = "file-disk",
+    feature = "cache",
+    feature = "session-file",
+    feature = "mail-api",
+    feature = "alert",
+    feature = "http",
+    feature = "https"
+))]
+use std::time::Duration;
 use std::{
+    env,
     fs::read_to_string,
     io::{Error, ErrorKind},
     net::{IpAddr, SocketAddr},
@@ -8,18 +27,30 @@ use std::{
     sync::Arc,
 };
 
-use toml::Table;
+use toml::{Table, Value};
 
 use crate::{fnv1a_64, sys::net::stream::Socket};
 
+use super::config::AppConfig;
+
+#[cfg(feature = "alert")]
+use crate::sys::log::LogView;
 #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
-use crate::sys::log::{InitLog, Log};
+use crate::sys::log::{InitLog, Log, LogRotate};
 
 /// Час очикування для сигналів
 pub(crate) const SIGNAL_TIMEOUT: u64 = 2000;
 /// Час очикування для завершення роботи
 pub(crate) const SIGNAL_TIMEOUT_WAIT: u64 = 30000;
 
+/// Prefix of the environment variables with the parameters, for example TINY_NET_BIND
+const ENV_PREFIX: &str = "TINY_";
+/// Sections of the file that can be set by the environment variables
+const ENV_SECTIONS: [&str; 16] = [
+    "web", "net", "http", "upload", "hot", "security", "queue", "async", "db", "redis", "mail", "otel", "alert", "profile", "storage",
+    "logging",
+];
+
 #[derive(Debug, Clone)]
 pub(crate) enum AutoCount<T>
 where
@@ -62,24 +93,432 @@ pub(crate) struct DBConfig {
     pub pwd: Option<String>,
     pub ssl: bool,
     pub max: AutoCount<usize>,
+    /// Connections opened at the start and kept by the health check, `None` is all
+    pub min: Option<usize>,
+    /// Max time of the waiting for the free connection, `None` is without the limit
+    pub timeout: Option<Duration>,
+    /// Interval of the health check of the free connections, `None` is off
+    pub health: Option<Duration>,
+    /// Max time of the retries of the connection at the start, `None` is the stop after the first error
+    pub wait: Option<Duration>,
+    /// Named instance of SQL Server, the port is from SQL Server Browser
+    #[cfg(feature = "mssql")]
+    pub instance: Option<String>,
+    /// ApplicationIntent=ReadOnly, for the readable secondary replica
+    #[cfg(feature = "mssql")]
+    pub readonly: bool,
+    /// Trusted certificate, for MS SQL the only one, for PostgreSQL the root certificates instead of the Mozilla roots
+    pub ca: Option<String>,
+    /// Check of the certificate of the server
+    #[cfg(feature = "pgsql")]
+    pub sslmode: SslMode,
+    /// Client certificate and its private key
+    #[cfg(feature = "pgsql")]
+    pub cert: Option<(String, String)>,
+    /// SCRAM-SHA-256-PLUS channel binding
+    #[cfg(feature = "pgsql")]
+    pub channel_binding: ChannelBinding,
+    /// Channel of the invalidation of the cache of all nodes, `None` is off
+    #[cfg(feature = "cache")]
+    pub notify: Option<String>,
+    /// Queue of the messages of the channel
+    #[cfg(all(feature = "cache", feature = "pgsql"))]
+    pub notify_bus: BusConfig,
+}
+
+/// Bounded queue of the internal messages, the parameters [db] notify_capacity, notify_policy and notify_lag
+#[cfg(all(feature = "cache", feature = "pgsql"))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BusConfig {
+    /// Max number of the messages in the queue
+    pub capacity: usize,
+    pub policy: BusPolicy,
+    /// Lag of the message for the warning
+    pub lag: Duration,
+}
+
+/// Sending to the full queue
+#[cfg(all(feature = "cache", feature = "pgsql"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BusPolicy {
+    /// The sender waits for the free place, PostgreSQL keeps the notifications
+    Wait,
+    /// The new message is dropped
+    Drop,
+}
+
+/// Check of the certificate of the PostgreSQL server, the parameter [db] sslmode
+#[cfg(feature = "pgsql")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SslMode {
+    /// Encryption without the check of the certificate
+    Require,
+    /// The certificate is signed by the trusted CA
+    VerifyCa,
+    /// The certificate is signed by the trusted CA and matches the host
+    VerifyFull,
+}
+
+/// SCRAM-SHA-256-PLUS channel binding, the parameter [db] channel_binding
+#[cfg(feature = "pgsql")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChannelBinding {
+    Disable,
+    /// Use it if the server supports it
+    Prefer,
+    /// Connection fails without it
+    Require,
 }
 
 #[derive(Debug)]
 pub(crate) struct Web {
     #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
     pub lang: Arc<String>,
+    /// Language of the url prefix "/en/..."
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    pub lang_prefix: bool,
+    /// Language of the header Accept-Language for the session without the language
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    pub lang_accept: bool,
     pub salt: Arc<String>,
     #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
     pub session_key: Arc<String>,
     #[cfg(any(feature = "session-memory", feature = "session-file"))]
     pub session_path: Arc<PathBuf>,
+    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+    pub session_bind: SessionBind,
+    #[cfg(any(feature = "session-file", feature = "session-db"))]
+    pub session_gc: SessionGcConfig,
     pub index: Arc<[i64; 3]>,
     pub not_found: Option<Arc<[i64; 3]>>,
+    /// Controller of the page of the panic of the controller, 500 Internal Server Error
+    pub action_err: Option<Arc<[i64; 3]>>,
+    /// Prefixes of the keys of the cache with the single flight of the missing value
+    #[cfg(feature = "cache")]
+    pub coalesce: Vec<String>,
+    /// Max time of the waiting for the first request of the missing key
+    #[cfg(feature = "cache")]
+    pub coalesce_wait: Duration,
+}
+
+/// Binding of the session to the client, the parameter [web] session_bind
+///
+/// The session of the other client is cleared, this protects from the session fixation and the stolen cookie.
+#[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SessionBind {
+    /// The same IP address
+    pub ip: bool,
+    /// The same network, /24 for IPv4 and /64 for IPv6
+    pub subnet: bool,
+    /// The same User-Agent
+    pub agent: bool,
+}
+
+/// Removal of the expired sessions, the parameters [web] session_ttl, session_gc and session_gc_batch
+#[cfg(any(feature = "session-file", feature = "session-db"))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SessionGcConfig {
+    /// Time of the session without changes for the file, without requests for the database
+    pub ttl: Duration,
+    /// Interval of the removal in the running server, `None` is only the command "sessions gc"
+    pub interval: Option<Duration>,
+    /// Number of the sessions of one step, the progress is logged after each step
+    pub batch: usize,
+}
+
+/// Limits of the request body
+#[derive(Debug)]
+pub(crate) struct UploadConfig {
+    /// Max size of the request body
+    pub max_body_size: usize,
+    /// Max size of the one uploaded file
+    pub max_file_size: usize,
+    /// Max number of the uploaded files
+    pub max_files: usize,
+    /// Allowed Content-Type of the uploaded files, "image/*" is allowed. Empty list allows everything.
+    pub mime: Vec<String>,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        UploadConfig {
+            max_body_size: 256 * 1024 * 1024,
+            max_file_size: 128 * 1024 * 1024,
+            max_files: 100,
+            mime: Vec::new(),
+        }
+    }
+}
+
+/// Keep-alive and limits of the head of the HTTP connection, the section [http]
+#[cfg(any(feature = "http", feature = "https"))]
+#[derive(Debug)]
+pub(crate) struct HttpConfig {
+    /// Waiting for the next request of the connection, `None` is without the limit
+    pub keep_alive: Option<Duration>,
+    /// Max number of the requests of the connection, 0 is unlimited
+    pub max_requests: usize,
+    /// Max time of the whole head from its first byte, the protection from the slow clients
+    pub header_timeout: Duration,
+    /// Max time of the one read of the head and the body
+    pub read_timeout: Duration,
+    /// Max size of the head, not more than the buffer of the connection
+    pub max_header: usize,
+    /// Max size of the request line
+    pub max_line: usize,
+}
+
+#[cfg(any(feature = "http", feature = "https"))]
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            keep_alive: Some(Duration::from_secs(75)),
+            max_requests: 1000,
+            header_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_millis(300),
+            max_header: 8192,
+            max_line: 4096,
+        }
+    }
+}
+
+/// Hot files of `Action::file` kept in memory, the section [hot]
+#[cfg(feature = "file-disk")]
+#[derive(Debug)]
+pub(crate) struct HotConfig {
+    /// Max size of the one file
+    pub size: usize,
+    /// Max size of all files
+    pub total: usize,
+    /// Number of the requests of the file before it is kept in memory
+    pub hits: u64,
+    /// Interval of the check of the file on the disk
+    pub check: Duration,
+}
+
+/// Limits of the request queue of the worker
+#[derive(Debug)]
+pub(crate) struct QueueConfig {
+    /// Max number of the running requests, 0 is unlimited
+    pub max_active: usize,
+    /// Max number of the waiting requests, the others are rejected with 503 Service Unavailable
+    pub max_queue: usize,
+    /// Url prefixes of the high priority requests
+    pub high: Vec<String>,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        QueueConfig {
+            max_active: 1024,
+            max_queue: 4096,
+            high: vec!["/health".to_owned(), "/admin".to_owned()],
+        }
+    }
+}
+
+/// Shared cache in Redis, the section [redis]
+#[cfg(feature = "cache-redis")]
+#[derive(Debug)]
+pub(crate) struct RedisConfig {
+    pub host: String,
+    pub port: u16,
+    /// User of ACL, Redis 6
+    pub user: Option<String>,
+    pub pwd: Option<String>,
+    /// Number of the database, SELECT
+    pub db: u32,
+    /// Prefix of the keys of the application, the name of the application by default
+    pub prefix: String,
+    /// Default time to live of the keys, `None` is without the expiration
+    pub ttl: Option<Duration>,
+    /// Number of the connections
+    pub max: usize,
+    /// Max time of the command
+    pub timeout: Duration,
+    /// Number of the elements of the cache in the memory before Redis, 0 is off
+    pub l1: usize,
+    /// Max time of the element in the memory, after the break of the invalidation too
+    pub l1_ttl: Duration,
+    /// Max time of the retries of PING at the start, `None` is without the check
+    pub wait: Option<Duration>,
+}
+
+/// Export of the traces to the OpenTelemetry collector, the section [otel]
+#[cfg(feature = "otel")]
+#[derive(Debug)]
+pub(crate) struct OtelConfig {
+    pub host: String,
+    pub port: u16,
+    /// Path of the OTLP/HTTP traces, "/v1/traces" by default
+    pub path: String,
+    /// service.name of the resource
+    pub service: String,
+}
+
+/// Receiver of the events of the errors, HTTP or HTTPS
+#[cfg(feature = "alert")]
+#[derive(Debug)]
+pub(crate) struct AlertTarget {
+    pub tls: bool,
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+    /// Public key of the Sentry DSN
+    pub key: Option<String>,
+}
+
+/// Aggregation of the warnings and the errors of the log, the section [alert]
+#[cfg(feature = "alert")]
+#[derive(Debug)]
+pub(crate) struct AlertConfig {
+    /// Store endpoint of the Sentry-compatible server
+    pub sentry: Option<AlertTarget>,
+    /// URL of the JSON webhook
+    pub webhook: Option<AlertTarget>,
+    /// Min level of the forwarded events
+    pub level: LogView,
+    /// Percent of the forwarded events, 0..=100
+    pub sample: u64,
+    /// One event with the same code and location in the interval, the repeats are counted
+    pub interval: Duration,
+    /// Name of the server in the events
+    pub service: String,
+    pub environment: Option<String>,
+}
+
+/// Sampling profiler of the requests, the section [profile]
+#[cfg(feature = "profile")]
+#[derive(Debug)]
+pub(crate) struct ProfileConfig {
+    /// Percent of the sampled requests, 0..=100
+    pub rate: u64,
+    /// The requests slower than this are sampled too
+    pub slow: Option<Duration>,
+    /// Folder of the profiles
+    pub path: PathBuf,
+    /// Interval of the writing of the profiles
+    pub interval: Duration,
+}
+
+/// Backend of `Storage`, the parameter [storage] backend
+#[cfg(feature = "storage")]
+#[derive(Debug)]
+pub(crate) enum StorageBackend {
+    /// Folder of the files
+    Local(PathBuf),
+    S3(S3Config),
+    Memory,
+}
+
+/// S3-compatible storage
+#[cfg(feature = "storage")]
+#[derive(Debug)]
+pub(crate) struct S3Config {
+    pub tls: bool,
+    pub host: String,
+    pub port: u16,
+    pub bucket: String,
+    pub region: String,
+    /// Access key
+    pub key: String,
+    pub secret: String,
+    /// "host/bucket/key" instead of "bucket.host/key"
+    pub path_style: bool,
+    /// Lifetime of the presigned urls, seconds
+    pub url_ttl: u64,
+}
+
+/// Storage of the files, the section [storage]
+#[cfg(feature = "storage")]
+#[derive(Debug)]
+pub(crate) struct StorageConfig {
+    pub backend: StorageBackend,
+    /// Prefix of the urls of the files, S3 without it has the presigned urls
+    pub url: Option<String>,
+}
+
+/// Default security headers of the response, the controller can override them in `Response::headers`
+#[derive(Debug)]
+pub(crate) struct SecurityConfig {
+    /// Strict-Transport-Security
+    pub hsts: Option<String>,
+    /// X-Content-Type-Options
+    pub content_type_options: Option<String>,
+    /// X-Frame-Options
+    pub frame_options: Option<String>,
+    /// Referrer-Policy
+    pub referrer_policy: Option<String>,
+    /// Content-Security-Policy, "{nonce}" is replaced with the nonce of the request
+    pub csp: Option<String>,
+    /// Send the Server-Timing header with the phases of the request
+    pub server_timing: bool,
+    /// Weak ETag of the hash of the answer of GET and HEAD without the ETag of the controller
+    pub etag: bool,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        SecurityConfig {
+            hsts: None,
+            content_type_options: Some("nosniff".to_owned()),
+            frame_options: Some("SAMEORIGIN".to_owned()),
+            referrer_policy: Some("strict-origin-when-cross-origin".to_owned()),
+            csp: None,
+            server_timing: false,
+            etag: false,
+        }
+    }
+}
+
+impl SecurityConfig {
+    /// The Content-Security-Policy uses the nonce
+    pub fn use_nonce(&self) -> bool {
+        matches!(&self.csp, Some(csp) if csp.contains("{nonce}"))
+    }
+
+    /// List of the headers
+    pub fn headers(&self, nonce: Option<&str>) -> Vec<(&'static str, String)> {
+        let mut list = Vec::with_capacity(5);
+        if let Some(value) = &self.hsts {
+            list.push(("Strict-Transport-Security", value.to_owned()));
+        }
+        if let Some(value) = &self.content_type_options {
+            list.push(("X-Content-Type-Options", value.to_owned()));
+        }
+        if let Some(value) = &self.frame_options {
+            list.push(("X-Frame-Options", value.to_owned()));
+        }
+        if let Some(value) = &self.referrer_policy {
+            list.push(("Referrer-Policy", value.to_owned()));
+        }
+        if let Some(value) = &self.csp {
+            list.push(("Content-Security-Policy", value.replace("{nonce}", nonce.unwrap_or_default())));
+        }
+        list
+    }
+}
+
+impl UploadConfig {
+    /// Checks Content-Type of the uploaded file
+    pub fn allow_mime(&self, mime: &str) -> bool {
+        if self.mime.is_empty() {
+            return true;
+        }
+        let mime = mime.split(';').next().unwrap_or_default().trim().to_lowercase();
+        self.mime.iter().any(|allow| match allow.strip_suffix("/*") {
+            Some(group) => mime.split('/').next() == Some(group),
+            None => *allow == mime,
+        })
+    }
 }
 
 #[derive(Debug)]
 pub(crate) struct Net {
     pub bind: Socket,
+    /// The other addresses of the list [net] bind
+    pub bind_extra: Vec<SocketAddr>,
     pub bind_from: IpAddr,
     pub rpc: Socket,
     pub rpc_from: IpAddr,
@@ -107,7 +546,22 @@ pub(crate) enum Auth {
     XOAuth2,
 }
 
-#[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+/// HTTP API of the mail provider, the parameter [mail] provider
+#[cfg(feature = "mail-api")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MailProvider {
+    Mailgun,
+    /// Amazon SES API v2
+    Ses,
+    Postmark,
+}
+
+#[cfg(any(
+    feature = "mail-sendmail",
+    feature = "mail-smtp",
+    feature = "mail-file",
+    feature = "mail-api"
+))]
 #[derive(Debug)]
 pub(crate) struct MailConfig {
     #[cfg(feature = "mail-sendmail")]
@@ -126,6 +580,23 @@ pub(crate) struct MailConfig {
     pub user: Option<String>,
     #[cfg(feature = "mail-smtp")]
     pub pwd: Option<String>,
+    #[cfg(feature = "mail-api")]
+    pub provider: MailProvider,
+    /// API key of Mailgun, server token of Postmark or access key id of Amazon
+    #[cfg(feature = "mail-api")]
+    pub key: String,
+    /// Secret access key of Amazon
+    #[cfg(feature = "mail-api")]
+    pub secret: Option<String>,
+    /// Sending domain of Mailgun
+    #[cfg(feature = "mail-api")]
+    pub domain: Option<String>,
+    /// Region of Amazon, for example "eu-west-1", or "eu" for api.eu.mailgun.net
+    #[cfg(feature = "mail-api")]
+    pub region: Option<String>,
+    /// Interval between the messages by the rate limit of the provider, `None` is without the limit
+    #[cfg(feature = "mail-api")]
+    pub interval: Option<Duration>,
 }
 
 #[derive(Debug)]
@@ -136,32 +607,63 @@ pub(crate) struct Init {
     pub web: Web,
     pub net: Net,
     pub proc: Async,
+    #[cfg(any(feature = "http", feature = "https"))]
+    pub http: Arc<HttpConfig>,
+    pub upload: Arc<UploadConfig>,
+    /// `None` is without the hot files
+    #[cfg(feature = "file-disk")]
+    pub hot: Option<Arc<HotConfig>>,
+    pub security: Arc<SecurityConfig>,
+    pub queue: Arc<QueueConfig>,
+    #[cfg(feature = "otel")]
+    pub otel: Option<Arc<OtelConfig>>,
+    #[cfg(feature = "alert")]
+    pub alert: Option<Arc<AlertConfig>>,
+    #[cfg(feature = "profile")]
+    pub profile: Option<Arc<ProfileConfig>>,
+    #[cfg(feature = "storage")]
+    pub storage: Arc<StorageConfig>,
+    /// Path patterns: pattern => [module_id, class_id, action_id]
+    pub route: Vec<(String, [i64; 3])>,
     #[cfg(any(feature = "pgsql", feature = "mssql"))]
     pub db: Arc<DBConfig>,
-    #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+    #[cfg(feature = "cache-redis")]
+    pub redis: Arc<RedisConfig>,
+    #[cfg(any(
+        feature = "mail-sendmail",
+        feature = "mail-smtp",
+        feature = "mail-file",
+        feature = "mail-api"
+    ))]
     pub mail: Arc<MailConfig>,
 }
 
 impl Init {
-    pub(crate) fn parse(name: String, version: String, desc: String, root: &Path) -> Result<Init, Error> {
-        let mut path = root.to_path_buf();
-        path.push("init.toml");
-        let content = match read_to_string(path) {
-            Ok(content) => content,
-            Err(e) => {
-                #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
-                Log::init(InitLog::Path(root.to_owned()));
-                return Err(e);
-            }
-        };
-        let res: Table = match toml::from_str(&content) {
-            Ok(res) => res,
-            Err(e) => {
-                #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
-                Log::init(InitLog::Path(root.to_owned()));
-                return Err(Error::new(ErrorKind::InvalidData, e));
+    pub(crate) fn parse(name: String, version: String, desc: String, root: &Path, config: &AppConfig) -> Result<Init, Error> {
+        let mut res = if config.file {
+            let mut path = root.to_path_buf();
+            path.push("init.toml");
+            let content = match read_to_string(path) {
+                Ok(content) => content,
+                Err(e) => {
+                    #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+                    Log::init(InitLog::Path(root.to_owned()));
+                    return Err(e);
+                }
+            };
+            match toml::from_str::<Table>(&content) {
+                Ok(res) => res,
+                Err(e) => {
+                    #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+                    Log::init(InitLog::Path(root.to_owned()));
+                    return Err(Error::new(ErrorKind::InvalidData, e));
+                }
             }
+        } else {
+            Table::new()
         };
+        config.merge(&mut res);
+        Init::env(&mut res, env::vars());
 
         #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
         match res.get("log") {
@@ -174,9 +676,32 @@ impl Init {
         let mut web = None;
         let mut net = None;
         let mut proc = None;
+        #[cfg(any(feature = "http", feature = "https"))]
+        let mut http = HttpConfig::default();
+        let mut upload = UploadConfig::default();
+        #[cfg(feature = "file-disk")]
+        let mut hot = None;
+        let mut security = SecurityConfig::default();
+        let mut queue = QueueConfig::default();
+        #[cfg(feature = "otel")]
+        let mut otel = None;
+        #[cfg(feature = "alert")]
+        let mut alert = None;
+        #[cfg(feature = "profile")]
+        let mut profile = None;
+        #[cfg(feature = "storage")]
+        let mut storage = None;
+        let mut route = Vec::new();
         #[cfg(any(feature = "pgsql", feature = "mssql"))]
         let mut db = None;
-        #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+        #[cfg(feature = "cache-redis")]
+        let mut redis = None;
+        #[cfg(any(
+            feature = "mail-sendmail",
+            feature = "mail-smtp",
+            feature = "mail-file",
+            feature = "mail-api"
+        ))]
         let mut mail = None;
 
         for (key, val) in res {
@@ -185,23 +710,105 @@ impl Init {
                     if let Some(list) = val.as_table() {
                         #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
                         let mut lang = None;
+                        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+                        let mut lang_prefix = false;
+                        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+                        let mut lang_accept = false;
                         let mut salt = None;
                         #[cfg(any(feature = "session-db", feature = "session-file", feature = "session-memory"))]
                         let mut session_key = None;
                         let mut index = None;
                         let mut not_found = None;
+                        let mut action_err = None;
+                        #[cfg(feature = "cache")]
+                        let mut coalesce = Vec::new();
+                        #[cfg(feature = "cache")]
+                        let mut coalesce_wait = Duration::from_secs(10);
                         #[cfg(any(feature = "session-memory", feature = "session-file"))]
                         let mut session_path = None;
+                        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+                        let mut session_bind = None;
+                        #[cfg(any(feature = "session-file", feature = "session-db"))]
+                        let mut session_gc = SessionGcConfig {
+                            ttl: Duration::from_secs(30 * 86400),
+                            interval: None,
+                            batch: 1000,
+                        };
 
                         for (key, val) in list {
                             match key.as_str() {
                                 #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
                                 "lang" => lang = val.as_str(),
+                                #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+                                "lang_prefix" => {
+                                    lang_prefix = val.as_bool().ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [web] lang_prefix. Повинен бути true чи false.")
+                                    })?
+                                }
+                                #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+                                "lang_accept" => {
+                                    lang_accept = val.as_bool().ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [web] lang_accept. Повинен бути true чи false.")
+                                    })?
+                                }
                                 "salt" => salt = val.as_str(),
                                 #[cfg(any(feature = "session-db", feature = "session-file", feature = "session-memory"))]
                                 "session" => session_key = val.as_str(),
                                 #[cfg(any(feature = "session-memory", feature = "session-file"))]
                                 "session_path" => session_path = val.as_str(),
+                                #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+                                "session_bind" => session_bind = Some(val),
+                                #[cfg(any(feature = "session-file", feature = "session-db"))]
+                                "session_ttl" => {
+                                    let ttl = val.as_integer().and_then(|v| u64::try_from(v).ok()).filter(|v| *v > 0).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            "Параметр [web] session_ttl. Повинен бути значення u64 більше 0, секунди.",
+                                        )
+                                    })?;
+                                    session_gc.ttl = Duration::from_secs(ttl);
+                                }
+                                #[cfg(any(feature = "session-file", feature = "session-db"))]
+                                "session_gc" => {
+                                    let interval = val.as_integer().and_then(|v| u64::try_from(v).ok()).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [web] session_gc. Повинен бути значення u64, секунди.")
+                                    })?;
+                                    session_gc.interval = (interval > 0).then(|| Duration::from_secs(interval));
+                                }
+                                #[cfg(any(feature = "session-file", feature = "session-db"))]
+                                "session_gc_batch" => {
+                                    session_gc.batch =
+                                        val.as_integer().and_then(|v| usize::try_from(v).ok()).filter(|v| *v > 0).ok_or_else(|| {
+                                            Error::new(
+                                                ErrorKind::InvalidData,
+                                                "Параметр [web] session_gc_batch. Повинен бути значення usize більше 0.",
+                                            )
+                                        })?
+                                }
+                                #[cfg(feature = "cache")]
+                                "coalesce" => {
+                                    coalesce = val
+                                        .as_array()
+                                        .and_then(|list| {
+                                            list.iter().map(|v| v.as_str().filter(|v| !v.is_empty()).map(str::to_owned)).collect()
+                                        })
+                                        .ok_or_else(|| {
+                                            Error::new(
+                                                ErrorKind::InvalidData,
+                                                r#"Параметр [web] coalesce. Повинен бути масив непорожніх рядків, префіксів ключів кешу ["sys:page:"]"#,
+                                            )
+                                        })?
+                                }
+                                #[cfg(feature = "cache")]
+                                "coalesce_wait" => {
+                                    let wait = val.as_integer().and_then(|v| u64::try_from(v).ok()).filter(|v| *v > 0).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            "Параметр [web] coalesce_wait. Повинен бути значення u64 більше 0, мілісекунди.",
+                                        )
+                                    })?;
+                                    coalesce_wait = Duration::from_millis(wait);
+                                }
                                 "index" => {
                                     if let Some(vec) = val.as_array() {
                                         let module = match unsafe { vec.get_unchecked(0) }.as_str() {
@@ -247,6 +854,27 @@ impl Init {
                                         ]));
                                     }
                                 }
+                                "action_err" => {
+                                    let route = val.as_array().filter(|vec| vec.len() == 3).and_then(|vec| {
+                                        let list: Vec<&str> = vec.iter().filter_map(|v| v.as_str()).filter(|v| !v.is_empty()).collect();
+                                        (list.len() == 3).then_some(list)
+                                    });
+                                    match route {
+                                        Some(list) => {
+                                            action_err = Some(Arc::new([
+                                                fnv1a_64(list[0].as_bytes()),
+                                                fnv1a_64(list[1].as_bytes()),
+                                                fnv1a_64(list[2].as_bytes()),
+                                            ]))
+                                        }
+                                        None => {
+                                            return Err(Error::new(
+                                                ErrorKind::InvalidData,
+                                                r#"Параметр [web] action_err. Повинен бути ["module", "class", "action"]."#,
+                                            ))
+                                        }
+                                    }
+                                }
                                 _ => {}
                             }
                         }
@@ -283,6 +911,33 @@ impl Init {
                                 )
                             })?
                             .to_owned();
+                        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+                        let session_bind = match session_bind {
+                            Some(val) => {
+                                let list = match (val.as_str(), val.as_array()) {
+                                    (Some("off"), _) => Vec::new(),
+                                    (Some(v), _) => vec![v],
+                                    (_, Some(vec)) => vec.iter().filter_map(|v| v.as_str()).collect(),
+                                    _ => vec![""],
+                                };
+                                let mut bind = SessionBind { ip: false, subnet: false, agent: false };
+                                for item in list {
+                                    match item {
+                                        "ip" => bind.ip = true,
+                                        "subnet" => bind.subnet = true,
+                                        "agent" => bind.agent = true,
+                                        _ => {
+                                            return Err(Error::new(
+                                                ErrorKind::InvalidData,
+                                                r#"Параметр [web] session_bind. Повинен бути "off" чи масив із "ip", "subnet" і "agent"."#,
+                                            ));
+                                        }
+                                    }
+                                }
+                                bind
+                            }
+                            None => SessionBind { ip: false, subnet: false, agent: true },
+                        };
                         let index = index.ok_or_else(|| {
                             Error::new(
                                 ErrorKind::InvalidData,
@@ -293,26 +948,55 @@ impl Init {
                         web = Some(Web {
                             #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
                             lang: Arc::new(lang),
+                            #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+                            lang_prefix,
+                            #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+                            lang_accept,
                             salt: Arc::new(salt),
                             #[cfg(any(feature = "session-db", feature = "session-file", feature = "session-memory"))]
                             session_key: Arc::new(session_key),
                             #[cfg(any(feature = "session-memory", feature = "session-file"))]
                             session_path: Arc::new(PathBuf::from(session_path)),
+                            #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+                            session_bind,
+                            #[cfg(any(feature = "session-file", feature = "session-db"))]
+                            session_gc,
                             index: Arc::new(index),
                             not_found,
+                            action_err,
+                            #[cfg(feature = "cache")]
+                            coalesce,
+                            #[cfg(feature = "cache")]
+                            coalesce_wait,
                         });
                     }
                 }
                 "net" => {
                     if let Some(list) = val.as_table() {
                         let mut bind = None;
+                        let mut bind_extra = Vec::new();
                         let mut bind_from = None;
                         let mut rpc = None;
                         let mut rpc_from = None;
                         for (key, val) in list {
                             match key.as_str() {
                                 "bind" => {
-                                    if let Some(addr) = val.as_str() {
+                                    if let Some(addrs) = val.as_array() {
+                                        let mut addrs =
+                                            addrs.iter().map(|addr| addr.as_str().and_then(|addr| addr.parse::<SocketAddr>().ok()));
+                                        bind = addrs.next().flatten().map(Socket::Inet);
+                                        for addr in addrs {
+                                            match addr {
+                                                Some(addr) => bind_extra.push(addr),
+                                                None => {
+                                                    return Err(Error::new(
+                                                        ErrorKind::InvalidData,
+                                                        r#"Параметр [net] bind. Список повинен містити лише "ip:port""#,
+                                                    ))
+                                                }
+                                            }
+                                        }
+                                    } else if let Some(addr) = val.as_str() {
                                         bind = if addr.starts_with('/') {
                                             #[cfg(not(target_family = "windows"))]
                                             {
@@ -374,7 +1058,13 @@ impl Init {
                         })?;
                         let rpc_from = rpc_from
                             .ok_or_else(|| Error::new(ErrorKind::InvalidData, r#"Параметр [net] rpc_from. Повинена бути IP адреса"#))?;
-                        net = Some(Net { bind, bind_from, rpc, rpc_from })
+                        net = Some(Net {
+                            bind,
+                            bind_extra,
+                            bind_from,
+                            rpc,
+                            rpc_from,
+                        })
                     }
                 }
                 "async" => {
@@ -549,6 +1239,526 @@ impl Init {
                         })
                     }
                 }
+                "route" => {
+                    if let Some(list) = val.as_table() {
+                        for (pattern, val) in list {
+                            let controller = val.as_array().and_then(|vec| {
+                                let vec: Vec<&str> = vec.iter().filter_map(|v| v.as_str()).filter(|v| !v.is_empty()).collect();
+                                match vec.as_slice() {
+                                    [module, class, action] => {
+                                        Some([fnv1a_64(module.as_bytes()), fnv1a_64(class.as_bytes()), fnv1a_64(action.as_bytes())])
+                                    }
+                                    _ => None,
+                                }
+                            });
+                            match controller {
+                                Some(controller) => route.push((pattern.to_owned(), controller)),
+                                None => {
+                                    return Err(Error::new(
+                                        ErrorKind::InvalidData,
+                                        format!(r#"Параметр [route] "{}". Повинен бути масив ["module", "class", "action"]"#, pattern),
+                                    ))
+                                }
+                            }
+                        }
+                    }
+                }
+                #[cfg(any(feature = "http", feature = "https"))]
+                "http" => {
+                    if let Some(list) = val.as_table() {
+                        for (key, val) in list {
+                            match key.as_str() {
+                                "keep_alive" => {
+                                    let secs = val.as_integer().and_then(|v| u64::try_from(v).ok()).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            "Параметр [http] keep_alive. Повинен бути значення u64, секунди.",
+                                        )
+                                    })?;
+                                    http.keep_alive = if secs > 0 { Some(Duration::from_secs(secs)) } else { None };
+                                }
+                                "max_requests" => {
+                                    http.max_requests = val.as_integer().and_then(|v| usize::try_from(v).ok()).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [http] max_requests. Повинен бути значення usize")
+                                    })?
+                                }
+                                "header_timeout" => {
+                                    let wait =
+                                        val.as_integer().and_then(|v| u64::try_from(v).ok()).filter(|v| *v > 0).ok_or_else(|| {
+                                            Error::new(
+                                                ErrorKind::InvalidData,
+                                                "Параметр [http] header_timeout. Повинен бути значення u64 більше 0, мілісекунди.",
+                                            )
+                                        })?;
+                                    http.header_timeout = Duration::from_millis(wait);
+                                }
+                                "read_timeout" => {
+                                    let wait =
+                                        val.as_integer().and_then(|v| u64::try_from(v).ok()).filter(|v| *v > 0).ok_or_else(|| {
+                                            Error::new(
+                                                ErrorKind::InvalidData,
+                                                "Параметр [http] read_timeout. Повинен бути значення u64 більше 0, мілісекунди.",
+                                            )
+                                        })?;
+                                    http.read_timeout = Duration::from_millis(wait);
+                                }
+                                "max_header" => {
+                                    http.max_header = Init::parse_size(val).filter(|v| *v > 0).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            r#"Параметр [http] max_header. Повинен бути значення usize більше 0 чи рядок "8K""#,
+                                        )
+                                    })?
+                                }
+                                "max_line" => {
+                                    http.max_line = Init::parse_size(val).filter(|v| *v > 0).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            r#"Параметр [http] max_line. Повинен бути значення usize більше 0 чи рядок "4K""#,
+                                        )
+                                    })?
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                "upload" => {
+                    if let Some(list) = val.as_table() {
+                        for (key, val) in list {
+                            match key.as_str() {
+                                "max_body_size" => {
+                                    upload.max_body_size = Init::parse_size(val).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            r#"Параметр [upload] max_body_size. Повинен бути значення usize чи рядок "10M""#,
+                                        )
+                                    })?
+                                }
+                                "max_file_size" => {
+                                    upload.max_file_size = Init::parse_size(val).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            r#"Параметр [upload] max_file_size. Повинен бути значення usize чи рядок "10M""#,
+                                        )
+                                    })?
+                                }
+                                "max_files" => {
+                                    upload.max_files = val.as_integer().and_then(|v| usize::try_from(v).ok()).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [upload] max_files. Повинен бути значення usize")
+                                    })?
+                                }
+                                "mime" => {
+                                    let list = val.as_array().ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            r#"Параметр [upload] mime. Повинен бути масив рядків ["image/*"]"#,
+                                        )
+                                    })?;
+                                    upload.mime = list.iter().filter_map(|v| v.as_str()).map(|v| v.trim().to_lowercase()).collect();
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                #[cfg(feature = "file-disk")]
+                "hot" => {
+                    if let Some(list) = val.as_table() {
+                        let mut size = 64 * 1024;
+                        let mut total = 16 * 1024 * 1024;
+                        let mut hits = 3;
+                        let mut check = 1000;
+                        for (key, val) in list {
+                            match key.as_str() {
+                                "size" => {
+                                    size = Init::parse_size(val).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            r#"Параметр [hot] size. Повинен бути значення usize чи рядок "64K""#,
+                                        )
+                                    })?
+                                }
+                                "total" => {
+                                    total = Init::parse_size(val).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            r#"Параметр [hot] total. Повинен бути значення usize чи рядок "16M""#,
+                                        )
+                                    })?
+                                }
+                                "hits" => {
+                                    hits = val.as_integer().and_then(|v| u64::try_from(v).ok()).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [hot] hits. Повинен бути значення u64")
+                                    })?
+                                }
+                                "check" => {
+                                    check = val.as_integer().and_then(|v| u64::try_from(v).ok()).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [hot] check. Повинен бути значення u64, мілісекунди")
+                                    })?
+                                }
+                                _ => {}
+                            }
+                        }
+                        if size > 0 && total > 0 {
+                            hot = Some(Arc::new(HotConfig {
+                                size,
+                                total,
+                                hits,
+                                check: Duration::from_millis(check),
+                            }));
+                        }
+                    }
+                }
+                "security" => {
+                    if let Some(list) = val.as_table() {
+                        for (key, val) in list {
+                            if key == "server_timing" {
+                                security.server_timing = val.as_bool().ok_or_else(|| {
+                                    Error::new(ErrorKind::InvalidData, "Параметр [security] server_timing. Повинен бути значення bool")
+                                })?;
+                                continue;
+                            }
+                            if key == "etag" {
+                                security.etag = val.as_bool().ok_or_else(|| {
+                                    Error::new(ErrorKind::InvalidData, "Параметр [security] etag. Повинен бути значення bool")
+                                })?;
+                                continue;
+                            }
+                            let value = match val.as_str() {
+                                Some(value) => value.trim(),
+                                None => {
+                                    return Err(Error::new(
+                                        ErrorKind::InvalidData,
+                                        format!("Параметр [security] {}. Повинен бути рядок, пустий рядок вимикає заголовок", key),
+                                    ))
+                                }
+                            };
+                            let value = if value.is_empty() { None } else { Some(value.to_owned()) };
+                            match key.as_str() {
+                                "hsts" => security.hsts = value,
+                                "content_type_options" => security.content_type_options = value,
+                                "frame_options" => security.frame_options = value,
+                                "referrer_policy" => security.referrer_policy = value,
+                                "csp" => security.csp = value,
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                "queue" => {
+                    if let Some(list) = val.as_table() {
+                        for (key, val) in list {
+                            match key.as_str() {
+                                "max_active" => {
+                                    queue.max_active = val.as_integer().and_then(|v| usize::try_from(v).ok()).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [queue] max_active. Повинен бути значення usize")
+                                    })?
+                                }
+                                "max_queue" => {
+                                    queue.max_queue = val.as_integer().and_then(|v| usize::try_from(v).ok()).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [queue] max_queue. Повинен бути значення usize")
+                                    })?
+                                }
+                                "high" => {
+                                    let list = val.as_array().ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            r#"Параметр [queue] high. Повинен бути масив рядків ["/health"]"#,
+                                        )
+                                    })?;
+                                    queue.high = list
+                                        .iter()
+                                        .filter_map(|v| v.as_str())
+                                        .map(|v| v.trim().to_owned())
+                                        .filter(|v| !v.is_empty())
+                                        .collect();
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                #[cfg(feature = "otel")]
+                "otel" => {
+                    if let Some(list) = val.as_table() {
+                        let mut endpoint = None;
+                        let mut service = None;
+                        for (key, val) in list {
+                            match key.as_str() {
+                                "endpoint" => endpoint = val.as_str().map(|v| v.trim().to_owned()),
+                                "service" => service = val.as_str().map(|v| v.trim().to_owned()),
+                                _ => {}
+                            }
+                        }
+                        if let Some(endpoint) = endpoint.filter(|v| !v.is_empty()) {
+                            let (host, port, path) = Init::parse_endpoint(&endpoint).ok_or_else(|| {
+                                Error::new(ErrorKind::InvalidData, r#"Параметр [otel] endpoint. Повинен бути адреса "http://host:port""#)
+                            })?;
+                            otel = Some(Arc::new(OtelConfig {
+                                host,
+                                port,
+                                path,
+                                service: service.filter(|v| !v.is_empty()).unwrap_or_else(|| name.clone()),
+                            }))
+                        }
+                    }
+                }
+                #[cfg(feature = "alert")]
+                "alert" => {
+                    if let Some(list) = val.as_table() {
+                        let mut sentry = None;
+                        let mut webhook = None;
+                        let mut level = LogView::Warning;
+                        let mut sample = 100;
+                        let mut interval = Duration::from_secs(60);
+                        let mut environment = None;
+                        for (key, val) in list {
+                            match key.as_str() {
+                                "dsn" => {
+                                    if let Some(dsn) = val.as_str().map(str::trim).filter(|v| !v.is_empty()) {
+                                        sentry = Some(Init::parse_dsn(dsn).ok_or_else(|| {
+                                            Error::new(
+                                                ErrorKind::InvalidData,
+                                                r#"Параметр [alert] dsn. Повинен бути адреса "https://key@host/project""#,
+                                            )
+                                        })?);
+                                    }
+                                }
+                                "webhook" => {
+                                    if let Some(url) = val.as_str().map(str::trim).filter(|v| !v.is_empty()) {
+                                        webhook = Some(Init::parse_target(url).ok_or_else(|| {
+                                            Error::new(
+                                                ErrorKind::InvalidData,
+                                                r#"Параметр [alert] webhook. Повинен бути адреса "https://host/path""#,
+                                            )
+                                        })?);
+                                    }
+                                }
+                                "level" => {
+                                    level = match val.as_str().map(str::trim) {
+                                        Some("warning") => LogView::Warning,
+                                        Some("stop") => LogView::Stop,
+                                        Some("error") => LogView::Error,
+                                        _ => {
+                                            return Err(Error::new(
+                                                ErrorKind::InvalidData,
+                                                r#"Параметр [alert] level. Повинен бути "warning", "stop" або "error""#,
+                                            ))
+                                        }
+                                    }
+                                }
+                                "sample" => {
+                                    sample =
+                                        val.as_integer().and_then(|v| u64::try_from(v).ok()).filter(|v| *v <= 100).ok_or_else(|| {
+                                            Error::new(
+                                                ErrorKind::InvalidData,
+                                                "Параметр [alert] sample. Повинен бути відсоток від 0 до 100",
+                                            )
+                                        })?
+                                }
+                                "interval" => {
+                                    let sec = val.as_integer().and_then(|v| u64::try_from(v).ok()).filter(|v| *v > 0).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            "Параметр [alert] interval. Повинен бути значення u64 більше 0, секунди",
+                                        )
+                                    })?;
+                                    interval = Duration::from_secs(sec);
+                                }
+                                "environment" => environment = val.as_str().map(|v| v.trim().to_owned()).filter(|v| !v.is_empty()),
+                                _ => {}
+                            }
+                        }
+                        alert = Some(Arc::new(AlertConfig {
+                            sentry,
+                            webhook,
+                            level,
+                            sample,
+                            interval,
+                            service: name.clone(),
+                            environment,
+                        }));
+                    }
+                }
+                #[cfg(feature = "profile")]
+                "profile" => {
+                    if let Some(list) = val.as_table() {
+                        let mut rate = 0;
+                        let mut slow = None;
+                        let mut path = "profile".to_owned();
+                        let mut period = 10;
+                        for (key, val) in list {
+                            match key.as_str() {
+                                "rate" => {
+                                    rate = val.as_integer().and_then(|v| u64::try_from(v).ok()).filter(|v| *v <= 100).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [profile] rate. Повинен бути відсоток від 0 до 100")
+                                    })?
+                                }
+                                "slow" => {
+                                    let ms = val.as_integer().and_then(|v| u64::try_from(v).ok()).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            "Параметр [profile] slow. Повинен бути значення u64, мілісекунди",
+                                        )
+                                    })?;
+                                    slow = (ms > 0).then(|| Duration::from_millis(ms));
+                                }
+                                "path" => {
+                                    path = val.as_str().filter(|v| !v.trim().is_empty()).map(|v| v.trim().to_owned()).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [profile] path. Повинен бути не пустим рядком")
+                                    })?
+                                }
+                                "interval" => {
+                                    period = val.as_integer().and_then(|v| u64::try_from(v).ok()).filter(|v| *v > 0).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            "Параметр [profile] interval. Повинен бути значення u64 більше 0, секунди",
+                                        )
+                                    })?
+                                }
+                                _ => {}
+                            }
+                        }
+                        if rate > 0 || slow.is_some() {
+                            profile = Some(Arc::new(ProfileConfig {
+                                rate,
+                                slow,
+                                path: PathBuf::from(path),
+                                interval: Duration::from_secs(period),
+                            }));
+                        }
+                    }
+                }
+                #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+                "logging" => {
+                    if let Some(list) = val.as_table() {
+                        let mut rotate = LogRotate { max_size: 0, daily: false, keep: 7 };
+                        for (name, val) in list {
+                            match name.as_str() {
+                                "level" => {
+                                    let level = val.as_str().and_then(|v| Log::parse_level(v.trim())).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, r#"Параметр [logging] level. Повинен бути "v", "vv" або "vvv""#)
+                                    })?;
+                                    Log::set_level(level);
+                                }
+                                "max_size" => {
+                                    rotate.max_size = val
+                                        .as_integer()
+                                        .and_then(|v| u64::try_from(v).ok())
+                                        .map(|v| v.saturating_mul(1024 * 1024))
+                                        .ok_or_else(|| {
+                                            Error::new(
+                                                ErrorKind::InvalidData,
+                                                "Параметр [logging] max_size. Повинен бути значення u64, мегабайти",
+                                            )
+                                        })?
+                                }
+                                "daily" => {
+                                    rotate.daily = val.as_bool().ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [logging] daily. Повинен бути true або false")
+                                    })?
+                                }
+                                "keep" => {
+                                    rotate.keep = val.as_integer().and_then(|v| usize::try_from(v).ok()).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [logging] keep. Повинен бути значення usize")
+                                    })?
+                                }
+                                _ => {}
+                            }
+                        }
+                        Log::rotate(rotate);
+                    }
+                }
+                #[cfg(feature = "storage")]
+                "storage" => {
+                    if let Some(list) = val.as_table() {
+                        let mut backend = "local".to_owned();
+                        let mut path = "storage".to_owned();
+                        let mut url = None;
+                        let mut endpoint = None;
+                        let mut bucket = None;
+                        let mut region = "us-east-1".to_owned();
+                        let mut key = None;
+                        let mut secret = None;
+                        let mut path_style = false;
+                        let mut url_ttl = 3600;
+                        for (name, val) in list {
+                            match name.as_str() {
+                                "backend" => backend = val.as_str().map(|v| v.trim().to_owned()).unwrap_or_default(),
+                                "path" => {
+                                    path = val.as_str().filter(|v| !v.trim().is_empty()).map(|v| v.trim().to_owned()).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [storage] path. Повинен бути не пустим рядком")
+                                    })?
+                                }
+                                "url" => url = val.as_str().map(|v| v.trim().to_owned()).filter(|v| !v.is_empty()),
+                                "endpoint" => endpoint = val.as_str().map(|v| v.trim().to_owned()).filter(|v| !v.is_empty()),
+                                "bucket" => bucket = val.as_str().map(|v| v.trim().to_owned()).filter(|v| !v.is_empty()),
+                                "region" => {
+                                    region =
+                                        val.as_str().filter(|v| !v.trim().is_empty()).map(|v| v.trim().to_owned()).ok_or_else(|| {
+                                            Error::new(ErrorKind::InvalidData, "Параметр [storage] region. Повинен бути не пустим рядком")
+                                        })?
+                                }
+                                "key" => key = val.as_str().map(|v| v.trim().to_owned()).filter(|v| !v.is_empty()),
+                                "secret" => secret = val.as_str().map(|v| v.to_owned()).filter(|v| !v.is_empty()),
+                                "path_style" => {
+                                    path_style = val.as_bool().ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [storage] path_style. Повинен бути true або false")
+                                    })?
+                                }
+                                "url_ttl" => {
+                                    url_ttl = val.as_integer().and_then(|v| u64::try_from(v).ok()).filter(|v| *v > 0).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            "Параметр [storage] url_ttl. Повинен бути значення u64 більше 0, секунди",
+                                        )
+                                    })?
+                                }
+                                _ => {}
+                            }
+                        }
+                        let backend = match backend.as_str() {
+                            "local" => {
+                                url = url.or_else(|| Some("/storage".to_owned()));
+                                StorageBackend::Local(root.join(path))
+                            }
+                            "memory" => {
+                                url = url.or_else(|| Some("/storage".to_owned()));
+                                StorageBackend::Memory
+                            }
+                            "s3" => {
+                                let (tls, host, port) = endpoint.as_deref().and_then(Init::parse_origin).ok_or_else(|| {
+                                    Error::new(
+                                        ErrorKind::InvalidData,
+                                        r#"Параметр [storage] endpoint обов'язковий для s3. Повинен бути адреса "https://host:port""#,
+                                    )
+                                })?;
+                                let missing = |name: &str| {
+                                    Error::new(ErrorKind::InvalidData, format!("Параметр [storage] {} обов'язковий для s3.", name))
+                                };
+                                StorageBackend::S3(S3Config {
+                                    tls,
+                                    host,
+                                    port,
+                                    bucket: bucket.ok_or_else(|| missing("bucket"))?,
+                                    region,
+                                    key: key.ok_or_else(|| missing("key"))?,
+                                    secret: secret.ok_or_else(|| missing("secret"))?,
+                                    path_style,
+                                    url_ttl,
+                                })
+                            }
+                            _ => {
+                                return Err(Error::new(
+                                    ErrorKind::InvalidData,
+                                    r#"Параметр [storage] backend. Повинен бути "local", "s3" або "memory""#,
+                                ))
+                            }
+                        };
+                        storage = Some(StorageConfig { backend, url });
+                    }
+                }
                 #[cfg(any(feature = "pgsql", feature = "mssql"))]
                 "db" => {
                     if let Some(list) = val.as_table() {
@@ -559,14 +1769,91 @@ impl Init {
                         let mut pwd = None;
                         let mut ssl = None;
                         let mut max = None;
+                        let mut min = None;
+                        let mut timeout = None;
+                        let mut health = None;
+                        let mut wait = None;
+                        #[cfg(feature = "mssql")]
+                        let mut instance = None;
+                        #[cfg(feature = "mssql")]
+                        let mut readonly = None;
+                        let mut ca = None;
+                        #[cfg(feature = "pgsql")]
+                        let mut sslmode = None;
+                        #[cfg(feature = "pgsql")]
+                        let mut cert = None;
+                        #[cfg(feature = "pgsql")]
+                        let mut key_file = None;
+                        #[cfg(feature = "pgsql")]
+                        let mut channel_binding = None;
+                        #[cfg(feature = "cache")]
+                        let mut notify = None;
+                        #[cfg(all(feature = "cache", feature = "pgsql"))]
+                        let mut notify_bus = BusConfig {
+                            capacity: 10000,
+                            policy: BusPolicy::Wait,
+                            lag: Duration::from_secs(1),
+                        };
                         for (key, val) in list {
                             match key.as_str() {
                                 "host" => host = val.as_str(),
+                                #[cfg(feature = "mssql")]
+                                "instance" => instance = val.as_str(),
+                                #[cfg(feature = "mssql")]
+                                "readonly" => readonly = val.as_bool(),
+                                "ca" => ca = val.as_str(),
+                                #[cfg(feature = "pgsql")]
+                                "sslmode" => sslmode = val.as_str(),
+                                #[cfg(feature = "pgsql")]
+                                "cert" => cert = val.as_str(),
+                                #[cfg(feature = "pgsql")]
+                                "key" => key_file = val.as_str(),
+                                #[cfg(feature = "pgsql")]
+                                "channel_binding" => channel_binding = val.as_str(),
+                                #[cfg(feature = "cache")]
+                                "notify" => notify = val.as_str(),
+                                #[cfg(all(feature = "cache", feature = "pgsql"))]
+                                "notify_capacity" => {
+                                    notify_bus.capacity =
+                                        val.as_integer().and_then(|v| usize::try_from(v).ok()).filter(|v| *v > 0).ok_or_else(|| {
+                                            Error::new(
+                                                ErrorKind::InvalidData,
+                                                "Параметр [db] notify_capacity. Повинен бути значення usize більше 0.",
+                                            )
+                                        })?
+                                }
+                                #[cfg(all(feature = "cache", feature = "pgsql"))]
+                                "notify_policy" => {
+                                    notify_bus.policy = match val.as_str() {
+                                        Some("wait") => BusPolicy::Wait,
+                                        Some("drop") => BusPolicy::Drop,
+                                        _ => {
+                                            return Err(Error::new(
+                                                ErrorKind::InvalidData,
+                                                r#"Параметр [db] notify_policy. Повинен бути "wait" чи "drop"."#,
+                                            ))
+                                        }
+                                    }
+                                }
+                                #[cfg(all(feature = "cache", feature = "pgsql"))]
+                                "notify_lag" => {
+                                    let lag = val.as_integer().and_then(|v| u64::try_from(v).ok()).filter(|v| *v > 0).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            "Параметр [db] notify_lag. Повинен бути значення u64 більше 0, мілісекунди.",
+                                        )
+                                    })?;
+                                    notify_bus.lag = Duration::from_millis(lag);
+                                }
                                 "port" => port = val.as_integer(),
                                 "name" => name = val.as_str(),
                                 "user" => user = val.as_str(),
                                 "pwd" => pwd = val.as_str(),
                                 "ssl" => ssl = val.as_bool(),
+                                "min" => min = val.as_integer(),
+                                "timeout" => timeout = val.as_integer(),
+                                "health" => health = val.as_integer(),
+                                "wait" => wait = val.as_integer(),
                                 "max" => {
                                     val.as_str()
                                         .map(|v| {
@@ -615,10 +1902,275 @@ impl Init {
                                 r#"Параметр [db] max обов'язковий. Повинен бути рядок "auto" чи значення usize"#,
                             )
                         })?;
-                        db = Some(DBConfig { host, port, name, user, pwd, ssl, max });
+                        let min = match min {
+                            Some(v) => match usize::try_from(v) {
+                                Ok(v) if matches!(max, AutoCount::Count(max) if v > max) => {
+                                    return Err(Error::new(ErrorKind::InvalidData, "Параметр [db] min. Повинен бути не більшим за max."));
+                                }
+                                Ok(v) => Some(v),
+                                Err(_) => return Err(Error::new(ErrorKind::InvalidData, "Параметр [db] min. Повинен бути usize.")),
+                            },
+                            None => None,
+                        };
+                        let timeout = match timeout {
+                            Some(0) | None => None,
+                            Some(v) => match u64::try_from(v) {
+                                Ok(v) => Some(Duration::from_millis(v)),
+                                Err(_) => {
+                                    return Err(Error::new(
+                                        ErrorKind::InvalidData,
+                                        "Параметр [db] timeout. Повинен бути u64, мілісекунди.",
+                                    ));
+                                }
+                            },
+                        };
+                        let health = match health {
+                            Some(0) => None,
+                            None => Some(Duration::from_secs(30)),
+                            Some(v) => match u64::try_from(v) {
+                                Ok(v) => Some(Duration::from_secs(v)),
+                                Err(_) => {
+                                    return Err(Error::new(ErrorKind::InvalidData, "Параметр [db] health. Повинен бути u64, секунди."));
+                                }
+                            },
+                        };
+                        let wait = match wait {
+                            Some(0) | None => None,
+                            Some(v) => match u64::try_from(v) {
+                                Ok(v) => Some(Duration::from_secs(v)),
+                                Err(_) => {
+                                    return Err(Error::new(ErrorKind::InvalidData, "Параметр [db] wait. Повинен бути u64, секунди."));
+                                }
+                            },
+                        };
+                        #[cfg(feature = "mssql")]
+                        let (host, instance) = match host.split_once('\\') {
+                            // "server\\INSTANCE"
+                            Some((host, name)) => (host.to_owned(), Some(name.to_owned())),
+                            None => (host, instance.filter(|v| !v.is_empty()).map(|v| v.to_owned())),
+                        };
+                        let ca = match ca.filter(|v| !v.is_empty()) {
+                            Some(_) if !ssl => {
+                                return Err(Error::new(ErrorKind::InvalidData, "Параметр [db] ca можливий тільки з ssl = true."));
+                            }
+                            Some(ca) if !Path::new(ca).is_file() => {
+                                return Err(Error::new(ErrorKind::InvalidData, format!("Параметр [db] ca. Файл {} не знайдено.", ca)));
+                            }
+                            Some(ca)
+                                if !matches!(
+                                    Path::new(ca).extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref(),
+                                    Some("pem" | "crt" | "der")
+                                ) =>
+                            {
+                                return Err(Error::new(ErrorKind::InvalidData, "Параметр [db] ca. Файл повинен бути .pem, .crt чи .der."));
+                            }
+                            ca => ca.map(|v| v.to_owned()),
+                        };
+                        #[cfg(feature = "pgsql")]
+                        let sslmode = match sslmode.filter(|v| !v.is_empty()) {
+                            Some(_) if !ssl => {
+                                return Err(Error::new(ErrorKind::InvalidData, "Параметр [db] sslmode можливий тільки з ssl = true."));
+                            }
+                            Some("require") => SslMode::Require,
+                            Some("verify-ca") => SslMode::VerifyCa,
+                            Some("verify-full") => SslMode::VerifyFull,
+                            Some(_) => {
+                                return Err(Error::new(
+                                    ErrorKind::InvalidData,
+                                    r#"Параметр [db] sslmode. Повинен бути "require", "verify-ca" чи "verify-full"."#,
+                                ));
+                            }
+                            // As libpq, the root certificate without sslmode is verify-ca
+                            None if ca.is_some() => SslMode::VerifyCa,
+                            None => SslMode::Require,
+                        };
+                        #[cfg(feature = "pgsql")]
+                        let cert = match (cert.filter(|v| !v.is_empty()), key_file.filter(|v| !v.is_empty())) {
+                            (None, None) => None,
+                            (Some(_), Some(_)) if !ssl => {
+                                return Err(Error::new(ErrorKind::InvalidData, "Параметри [db] cert і key можливі тільки з ssl = true."));
+                            }
+                            (Some(cert), Some(key)) => {
+                                for file in [cert, key] {
+                                    if !Path::new(file).is_file() {
+                                        return Err(Error::new(
+                                            ErrorKind::InvalidData,
+                                            format!("Параметр [db] cert чи key. Файл {} не знайдено.", file),
+                                        ));
+                                    }
+                                }
+                                Some((cert.to_owned(), key.to_owned()))
+                            }
+                            _ => {
+                                return Err(Error::new(ErrorKind::InvalidData, "Параметри [db] cert і key повинні бути разом."));
+                            }
+                        };
+                        #[cfg(feature = "pgsql")]
+                        let channel_binding = match channel_binding.filter(|v| !v.is_empty()) {
+                            Some("disable") => ChannelBinding::Disable,
+                            Some("prefer") | None => ChannelBinding::Prefer,
+                            Some("require") if !ssl => {
+                                return Err(Error::new(
+                                    ErrorKind::InvalidData,
+                                    r#"Параметр [db] channel_binding = "require" можливий тільки з ssl = true."#,
+                                ));
+                            }
+                            Some("require") => ChannelBinding::Require,
+                            Some(_) => {
+                                return Err(Error::new(
+                                    ErrorKind::InvalidData,
+                                    r#"Параметр [db] channel_binding. Повинен бути "disable", "prefer" чи "require"."#,
+                                ));
+                            }
+                        };
+                        // The channel is the name of the LISTEN of PostgreSQL and the prefix of the services of MS SQL Server
+                        #[cfg(feature = "cache")]
+                        let notify = match notify.filter(|v| !v.is_empty()) {
+                            Some(v) if v.len() > 63 || !v.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') => {
+                                return Err(Error::new(
+                                    ErrorKind::InvalidData,
+                                    "Параметр [db] notify. Повинен бути рядком до 63 символів з латинських літер, цифр і _.",
+                                ));
+                            }
+                            v => v.map(|v| v.to_owned()),
+                        };
+                        db = Some(DBConfig {
+                            host,
+                            port,
+                            name,
+                            user,
+                            pwd,
+                            ssl,
+                            max,
+                            min,
+                            timeout,
+                            health,
+                            wait,
+                            #[cfg(feature = "mssql")]
+                            instance,
+                            #[cfg(feature = "mssql")]
+                            readonly: readonly.unwrap_or(false),
+                            ca,
+                            #[cfg(feature = "pgsql")]
+                            sslmode,
+                            #[cfg(feature = "pgsql")]
+                            cert,
+                            #[cfg(feature = "pgsql")]
+                            channel_binding,
+                            #[cfg(feature = "cache")]
+                            notify,
+                            #[cfg(all(feature = "cache", feature = "pgsql"))]
+                            notify_bus,
+                        });
                     }
                 }
-                #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+                #[cfg(feature = "cache-redis")]
+                "redis" => {
+                    if let Some(list) = val.as_table() {
+                        let mut host = None;
+                        let mut port = 6379;
+                        let mut user = None;
+                        let mut pwd = None;
+                        let mut index = 0;
+                        let mut prefix = None;
+                        let mut ttl = 0;
+                        let mut max = 4;
+                        let mut timeout = 1000;
+                        let mut l1 = 0;
+                        let mut l1_ttl = 60;
+                        let mut wait = 0;
+                        for (key, val) in list {
+                            match key.as_str() {
+                                "host" => host = val.as_str().map(|v| v.trim().to_owned()),
+                                "port" => {
+                                    port = val
+                                        .as_integer()
+                                        .and_then(|v| u16::try_from(v).ok())
+                                        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Параметр [redis] port. Повинен бути u16."))?
+                                }
+                                "user" => user = val.as_str().filter(|v| !v.is_empty()).map(|v| v.to_owned()),
+                                "pwd" => pwd = val.as_str().filter(|v| !v.is_empty()).map(|v| v.to_owned()),
+                                "db" => {
+                                    index = val
+                                        .as_integer()
+                                        .and_then(|v| u32::try_from(v).ok())
+                                        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Параметр [redis] db. Повинен бути u32."))?
+                                }
+                                "prefix" => prefix = val.as_str().map(|v| v.trim().to_owned()),
+                                "ttl" => {
+                                    ttl = val.as_integer().and_then(|v| u64::try_from(v).ok()).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [redis] ttl. Повинен бути значення u64, секунди.")
+                                    })?
+                                }
+                                "max" => {
+                                    max = val.as_integer().and_then(|v| usize::try_from(v).ok()).filter(|v| *v > 0).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [redis] max. Повинен бути значення usize більше 0.")
+                                    })?
+                                }
+                                "timeout" => {
+                                    timeout = val.as_integer().and_then(|v| u64::try_from(v).ok()).filter(|v| *v > 0).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            "Параметр [redis] timeout. Повинен бути значення u64 більше 0, мілісекунди.",
+                                        )
+                                    })?
+                                }
+                                "l1" => {
+                                    l1 = val.as_integer().and_then(|v| usize::try_from(v).ok()).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [redis] l1. Повинен бути значення usize.")
+                                    })?
+                                }
+                                "l1_ttl" => {
+                                    l1_ttl = val.as_integer().and_then(|v| u64::try_from(v).ok()).filter(|v| *v > 0).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            "Параметр [redis] l1_ttl. Повинен бути значення u64 більше 0, секунди.",
+                                        )
+                                    })?
+                                }
+                                "wait" => {
+                                    wait = val.as_integer().and_then(|v| u64::try_from(v).ok()).ok_or_else(|| {
+                                        Error::new(ErrorKind::InvalidData, "Параметр [redis] wait. Повинен бути значення u64, секунди.")
+                                    })?
+                                }
+                                _ => {}
+                            }
+                        }
+                        let host = host.filter(|v| !v.is_empty()).ok_or_else(|| {
+                            Error::new(ErrorKind::InvalidData, "Параметр [redis] host обов'язковий. Повинен бути не пустим рядком.")
+                        })?;
+                        if pwd.is_none() && user.is_some() {
+                            return Err(Error::new(ErrorKind::InvalidData, "Параметр [redis] user можливий тільки з pwd."));
+                        }
+                        let prefix = prefix.unwrap_or_else(|| name.clone());
+                        if prefix.is_empty() || prefix.contains(|c: char| c.is_whitespace() || "*?[]\\".contains(c)) {
+                            return Err(Error::new(
+                                ErrorKind::InvalidData,
+                                r"Параметр [redis] prefix. Повинен бути не пустим рядком без пробілів і символів *?[]\.",
+                            ));
+                        }
+                        redis = Some(RedisConfig {
+                            host,
+                            port,
+                            user,
+                            pwd,
+                            db: index,
+                            prefix,
+                            ttl: (ttl > 0).then(|| Duration::from_secs(ttl)),
+                            max,
+                            timeout: Duration::from_millis(timeout),
+                            l1,
+                            l1_ttl: Duration::from_secs(l1_ttl),
+                            wait: (wait > 0).then(|| Duration::from_secs(wait)),
+                        });
+                    }
+                }
+                #[cfg(any(
+                    feature = "mail-sendmail",
+                    feature = "mail-smtp",
+                    feature = "mail-file",
+                    feature = "mail-api"
+                ))]
                 "mail" => {
                     if let Some(list) = val.as_table() {
                         #[cfg(feature = "mail-sendmail")]
@@ -637,6 +2189,18 @@ impl Init {
                         let mut user = None;
                         #[cfg(feature = "mail-smtp")]
                         let mut pwd = None;
+                        #[cfg(feature = "mail-api")]
+                        let mut provider = None;
+                        #[cfg(feature = "mail-api")]
+                        let mut api_key = None;
+                        #[cfg(feature = "mail-api")]
+                        let mut secret = None;
+                        #[cfg(feature = "mail-api")]
+                        let mut domain = None;
+                        #[cfg(feature = "mail-api")]
+                        let mut region = None;
+                        #[cfg(feature = "mail-api")]
+                        let mut rate = 0;
                         for (key, val) in list {
                             match key.as_str() {
                                 #[cfg(feature = "mail-sendmail")]
@@ -655,6 +2219,25 @@ impl Init {
                                 "user" => user = val.as_str(),
                                 #[cfg(feature = "mail-smtp")]
                                 "pwd" => pwd = val.as_str(),
+                                #[cfg(feature = "mail-api")]
+                                "provider" => provider = val.as_str(),
+                                #[cfg(feature = "mail-api")]
+                                "key" => api_key = val.as_str(),
+                                #[cfg(feature = "mail-api")]
+                                "secret" => secret = val.as_str(),
+                                #[cfg(feature = "mail-api")]
+                                "domain" => domain = val.as_str(),
+                                #[cfg(feature = "mail-api")]
+                                "region" => region = val.as_str(),
+                                #[cfg(feature = "mail-api")]
+                                "rate" => {
+                                    rate = val.as_integer().and_then(|v| u32::try_from(v).ok()).ok_or_else(|| {
+                                        Error::new(
+                                            ErrorKind::InvalidData,
+                                            "Параметр [mail] rate. Повинен бути значення u32, листів за секунду",
+                                        )
+                                    })?
+                                }
                                 _ => {}
                             }
                         }
@@ -717,6 +2300,52 @@ impl Init {
                         #[cfg(feature = "mail-smtp")]
                         let pwd = pwd.filter(|v| !v.is_empty()).map(|v| v.to_owned());
 
+                        #[cfg(feature = "mail-api")]
+                        let provider = provider
+                            .and_then(|provider| match provider {
+                                "mailgun" => Some(MailProvider::Mailgun),
+                                "ses" => Some(MailProvider::Ses),
+                                "postmark" => Some(MailProvider::Postmark),
+                                _ => None,
+                            })
+                            .ok_or_else(|| {
+                                Error::new(
+                                    ErrorKind::InvalidData,
+                                    "Параметр [mail] provider обов'язковий. Може бути тільки: mailgun, ses, postmark.",
+                                )
+                            })?;
+                        #[cfg(feature = "mail-api")]
+                        let key = api_key
+                            .filter(|s| !s.is_empty())
+                            .ok_or_else(|| {
+                                Error::new(ErrorKind::InvalidData, "Параметр [mail] key обов'язковий. Повинен бути не пустим рядком.")
+                            })?
+                            .to_owned();
+                        #[cfg(feature = "mail-api")]
+                        let secret = secret.filter(|v| !v.is_empty()).map(|v| v.to_owned());
+                        #[cfg(feature = "mail-api")]
+                        let domain = domain.filter(|v| !v.is_empty()).map(|v| v.to_owned());
+                        #[cfg(feature = "mail-api")]
+                        let region = region.filter(|v| !v.is_empty()).map(|v| v.to_owned());
+                        #[cfg(feature = "mail-api")]
+                        match provider {
+                            MailProvider::Mailgun if domain.is_none() => {
+                                return Err(Error::new(
+                                    ErrorKind::InvalidData,
+                                    "Параметр [mail] domain обов'язковий для mailgun. Повинен бути не пустим рядком.",
+                                ))
+                            }
+                            MailProvider::Ses if secret.is_none() || region.is_none() => {
+                                return Err(Error::new(
+                                    ErrorKind::InvalidData,
+                                    "Параметри [mail] secret і region обов'язкові для ses. Повинні бути не пустими рядками.",
+                                ))
+                            }
+                            _ => {}
+                        }
+                        #[cfg(feature = "mail-api")]
+                        let interval = if rate > 0 { Some(Duration::from_secs(1) / rate) } else { None };
+
                         mail = Some(MailConfig {
                             #[cfg(feature = "mail-sendmail")]
                             sendmail,
@@ -734,6 +2363,18 @@ impl Init {
                             user,
                             #[cfg(feature = "mail-smtp")]
                             pwd,
+                            #[cfg(feature = "mail-api")]
+                            provider,
+                            #[cfg(feature = "mail-api")]
+                            key,
+                            #[cfg(feature = "mail-api")]
+                            secret,
+                            #[cfg(feature = "mail-api")]
+                            domain,
+                            #[cfg(feature = "mail-api")]
+                            region,
+                            #[cfg(feature = "mail-api")]
+                            interval,
                         });
                     }
                 }
@@ -757,7 +2398,17 @@ impl Init {
             Some(db) => Arc::new(db),
             None => return Err(Error::new(ErrorKind::InvalidData, "Секція [db] не знайдена.")),
         };
-        #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+        #[cfg(feature = "cache-redis")]
+        let redis = match redis {
+            Some(redis) => Arc::new(redis),
+            None => return Err(Error::new(ErrorKind::InvalidData, "Секція [redis] не знайдена.")),
+        };
+        #[cfg(any(
+            feature = "mail-sendmail",
+            feature = "mail-smtp",
+            feature = "mail-file",
+            feature = "mail-api"
+        ))]
         let mail = match mail {
             Some(mail) => Arc::new(mail),
             None => return Err(Error::new(ErrorKind::InvalidData, "Секція [mail] не знайдена.")),
@@ -770,10 +2421,173 @@ impl Init {
             web,
             net,
             proc,
+            #[cfg(any(feature = "http", feature = "https"))]
+            http: Arc::new(http),
+            upload: Arc::new(upload),
+            #[cfg(feature = "file-disk")]
+            hot,
+            security: Arc::new(security),
+            queue: Arc::new(queue),
+            #[cfg(feature = "otel")]
+            otel,
+            #[cfg(feature = "alert")]
+            alert,
+            #[cfg(feature = "profile")]
+            profile,
+            #[cfg(feature = "storage")]
+            storage: Arc::new(storage.unwrap_or_else(|| StorageConfig {
+                backend: StorageBackend::Local(root.join("storage")),
+                url: Some("/storage".to_owned()),
+            })),
+            route,
             #[cfg(any(feature = "pgsql", feature = "mssql"))]
             db,
-            #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+            #[cfg(feature = "cache-redis")]
+            redis,
+            #[cfg(any(
+                feature = "mail-sendmail",
+                feature = "mail-smtp",
+                feature = "mail-file",
+                feature = "mail-api"
+            ))]
             mail,
         })
     }
+
+    /// Override the parameters by the environment variables TINY_LOG and TINY_<SECTION>_<KEY>
+    ///
+    /// For example TINY_NET_BIND, TINY_DB_PWD or TINY_WEB_SALT. The value is parsed as TOML,
+    /// so TINY_DB_PORT=5432 is the number and TINY_WEB_INDEX='["index","index","index"]' is the array,
+    /// the other values and the values of the string parameters of the file are the strings.
+    /// The quoted value is always the string, for example TINY_DB_PWD='"123"'.
+    fn env(res: &mut Table, vars: impl Iterator<Item = (String, String)>) {
+        for (name, value) in vars {
+            let name = match name.strip_prefix(ENV_PREFIX) {
+                Some(name) => name.to_ascii_lowercase(),
+                None => continue,
+            };
+            if name == "log" {
+                res.insert(name, Value::String(value));
+                continue;
+            }
+            let (section, key) = match name.split_once('_') {
+                Some((section, key)) if ENV_SECTIONS.contains(&section) && !key.is_empty() => (section, key),
+                _ => continue,
+            };
+            let section = res.entry(section).or_insert_with(|| Value::Table(Table::new()));
+            let table = match section.as_table_mut() {
+                Some(table) => table,
+                None => continue,
+            };
+            let value = match table.get(key) {
+                Some(Value::String(_)) => Value::String(value),
+                _ => match toml::from_str::<Table>(&format!("v = {}", value)) {
+                    Ok(mut parsed) => parsed.remove("v").unwrap_or(Value::String(value)),
+                    Err(_) => Value::String(value),
+                },
+            };
+            table.insert(key.to_owned(), value);
+        }
+    }
+
+    /// TLS, host and port of "https://host:port", the default port is 443 or 80
+    #[cfg(feature = "storage")]
+    fn parse_origin(url: &str) -> Option<(bool, String, u16)> {
+        let (tls, addr) = match url.strip_prefix("https://") {
+            Some(addr) => (true, addr),
+            None => (false, url.strip_prefix("http://")?),
+        };
+        let addr = addr.trim_end_matches('/');
+        if addr.contains('/') {
+            return None;
+        }
+        let (host, port) = match addr.rsplit_once(':').filter(|(_, port)| !port.ends_with(']')) {
+            Some((host, port)) => (host, port.parse().ok()?),
+            None => (addr, if tls { 443 } else { 80 }),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return None;
+        }
+        Some((tls, host.to_owned(), port))
+    }
+
+    /// Host, port and path of "http://host:port/path", the default port is 4318 and the path is "/v1/traces"
+    #[cfg(feature = "otel")]
+    fn parse_endpoint(endpoint: &str) -> Option<(String, u16, String)> {
+        let endpoint = endpoint.strip_prefix("http://")?;
+        let (addr, path) = match endpoint.find('/') {
+            Some(pos) => endpoint.split_at(pos),
+            None => (endpoint, ""),
+        };
+        let (host, port) = match addr.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().ok()?),
+            None => (addr, 4318),
+        };
+        if host.is_empty() {
+            return None;
+        }
+        let path = if path.is_empty() || path == "/" { "/v1/traces".to_owned() } else { path.to_owned() };
+        Some((host.trim_start_matches('[').trim_end_matches(']').to_owned(), port, path))
+    }
+
+    /// Scheme, host, port and path of "https://host:port/path"
+    #[cfg(feature = "alert")]
+    fn parse_target(url: &str) -> Option<AlertTarget> {
+        let (tls, url) = match url.strip_prefix("https://") {
+            Some(url) => (true, url),
+            None => (false, url.strip_prefix("http://")?),
+        };
+        let (addr, path) = match url.find('/') {
+            Some(pos) => url.split_at(pos),
+            None => (url, "/"),
+        };
+        let (key, addr) = match addr.rsplit_once('@') {
+            Some((key, addr)) => (Some(key.split(':').next().unwrap_or_default().to_owned()), addr),
+            None => (None, addr),
+        };
+        let (host, port) = match addr.rsplit_once(':').filter(|(_, port)| !port.ends_with(']')) {
+            Some((host, port)) => (host, port.parse().ok()?),
+            None => (addr, if tls { 443 } else { 80 }),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return None;
+        }
+        Some(AlertTarget {
+            tls,
+            host: host.to_owned(),
+            port,
+            path: path.to_owned(),
+            key,
+        })
+    }
+
+    /// Store endpoint of the DSN "https://key@host/project", the server on the subpath has the path "/sentry/project"
+    #[cfg(feature = "alert")]
+    fn parse_dsn(dsn: &str) -> Option<AlertTarget> {
+        let mut target = Init::parse_target(dsn)?;
+        target.key.as_ref().filter(|key| !key.is_empty())?;
+        let (prefix, project) = target.path.trim_end_matches('/').rsplit_once('/')?;
+        if project.is_empty() {
+            return None;
+        }
+        target.path = format!("{}/api/{}/store/", prefix, project);
+        Some(target)
+    }
+
+    /// Parse size in bytes, for example 1048576, "1024K", "10M" or "1G"
+    fn parse_size(val: &toml::Value) -> Option<usize> {
+        if let Some(val) = val.as_integer() {
+            return usize::try_from(val).ok();
+        }
+        let val = val.as_str()?.trim();
+        let (num, mul) = match val.as_bytes().last()? {
+            b'K' | b'k' => (&val[..val.len() - 1], 1024),
+            b'M' | b'm' => (&val[..val.len() - 1], 1024 * 1024),
+            b'G' | b'g' => (&val[..val.len() - 1], 1024 * 1024 * 1024),
+            _ => (val, 1),
+        };
+        num.trim().parse::<usize>().ok().and_then(|num| num.checked_mul(mul))
+    }
 }
@@ -0,0 +1,343 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    time::Duration,
+};
+
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+use std::sync::Arc;
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    runtime::Builder,
+    time::timeout,
+};
+
+use crate::{log, sys::net::eyeballs::HappyEyeballs};
+
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+use crate::sys::db::adapter::DB;
+
+use super::init::Init;
+
+/// Timeout of the one request
+const LINKCHECK_TIMEOUT: u64 = 10;
+/// Max size of the answer
+const LINKCHECK_MAX_BODY: usize = 4 * 1024 * 1024;
+
+/// Options of the broken-link checker
+#[derive(Debug)]
+pub(crate) struct LinkCheckOption {
+    /// Site url, for example "http://127.0.0.1:8080"
+    pub url: String,
+    /// Max number of the redirects without the report
+    pub hops: usize,
+    /// Max number of the checked pages
+    pub limit: usize,
+    /// Domain of the site for the Host header and links, by default the host of the url
+    pub host: Option<String>,
+}
+
+/// Problem found by the checker
+#[derive(Debug)]
+struct LinkReport {
+    url: String,
+    referer: String,
+    status: u16,
+    /// Redirect chain, the first is url
+    chain: Vec<String>,
+}
+
+/// Answer of the server
+#[derive(Debug)]
+struct LinkAnswer {
+    status: u16,
+    location: Option<String>,
+    html: Option<String>,
+}
+
+/// Broken-link checker
+///
+/// Crawls the own site from "/" and "/sitemap.xml", reports 4xx/5xx answers and long redirect chains.
+/// With the database, the report is saved to the `link_check` table.
+pub(crate) struct LinkCheck;
+
+impl LinkCheck {
+    /// Run the checker
+    pub(crate) fn run(init: Init, option: LinkCheckOption) -> Result<(), ()> {
+        let (host, port) = match LinkCheck::parse_site(&option.url) {
+            Some(site) => site,
+            None => {
+                log!(stop, 0, "Неправильний url {}. Повинен бути http://host[:port]", option.url);
+                return Err(());
+            }
+        };
+        let runtime = match Builder::new_current_thread().enable_all().build() {
+            Ok(runtime) => runtime,
+            Err(_e) => {
+                log!(stop, 0, "{}", _e);
+                return Err(());
+            }
+        };
+        #[cfg(not(any(feature = "pgsql", feature = "mssql")))]
+        let _ = init;
+        runtime.block_on(async move {
+            let report = LinkCheck::crawl(&host, port, &option).await;
+            for item in &report {
+                println!("{} {} (from {}) {}", item.status, item.url, item.referer, item.chain.join(" -> "));
+            }
+            println!("Problems: {}", report.len());
+
+            #[cfg(any(feature = "pgsql", feature = "mssql"))]
+            {
+                let db = DB::new(Arc::clone(&init.db)).await?;
+                LinkCheck::save(&db, &report).await?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Save the report to the `link_check` table
+    #[cfg(any(feature = "pgsql", feature = "mssql"))]
+    async fn save(db: &DB, report: &[LinkReport]) -> Result<(), ()> {
+        db.execute("DELETE FROM link_check", &[]).await.ok_or(())?;
+        #[cfg(feature = "pgsql")]
+        let sql = "INSERT INTO link_check (url, referer, status, hops, chain) VALUES ($1, $2, $3, $4, $5)";
+        #[cfg(feature = "mssql")]
+        let sql = "INSERT INTO [link_check] ([url], [referer], [status], [hops], [chain]) VALUES (@P1, @P2, @P3, @P4, @P5)";
+        for item in report {
+            let status = item.status as i64;
+            let hops = item.chain.len().saturating_sub(1) as i64;
+            let chain = item.chain.join("\n");
+            db.execute(sql, &[&item.url, &item.referer, &status, &hops, &chain]).await.ok_or(())?;
+        }
+        Ok(())
+    }
+
+    /// Breadth-first crawling of the site
+    async fn crawl(addr: &str, port: u16, option: &LinkCheckOption) -> Vec<LinkReport> {
+        let host = option.host.as_deref().unwrap_or(addr);
+        let mut report = Vec::new();
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        seen.insert("/".to_owned());
+        queue.push_back(("/".to_owned(), String::new()));
+
+        if let Ok(answer) = LinkCheck::get(addr, port, host, "/sitemap.xml").await {
+            if answer.status == 200 {
+                for url in LinkCheck::sitemap(answer.html.as_deref().unwrap_or_default(), host) {
+                    if seen.insert(url.clone()) {
+                        queue.push_back((url, "/sitemap.xml".to_owned()));
+                    }
+                }
+            }
+        }
+
+        let mut count = 0;
+        while let Some((url, referer)) = queue.pop_front() {
+            if count >= option.limit {
+                break;
+            }
+            count += 1;
+
+            let mut chain = vec![url.clone()];
+            let mut current = url.clone();
+            let mut looped = false;
+            let answer = loop {
+                let answer = match LinkCheck::get(addr, port, host, &current).await {
+                    Ok(answer) => answer,
+                    Err(_e) => {
+                        log!(warning, 0, "{} {}", current, _e);
+                        break LinkAnswer { status: 0, location: None, html: None };
+                    }
+                };
+                let next = match (answer.status, &answer.location) {
+                    (300..=399, Some(location)) => LinkCheck::resolve(location, &current, host),
+                    _ => None,
+                };
+                match next {
+                    Some(next) if !chain.contains(&next) && chain.len() <= option.hops + 1 => {
+                        chain.push(next.clone());
+                        current = next;
+                    }
+                    Some(next) => {
+                        // Loop or too long chain
+                        looped = chain.contains(&next);
+                        chain.push(next);
+                        break answer;
+                    }
+                    None => break answer,
+                }
+            };
+            if answer.status == 0 || answer.status >= 400 || looped || chain.len() > option.hops + 1 {
+                report.push(LinkReport {
+                    url: url.clone(),
+                    referer: referer.clone(),
+                    status: answer.status,
+                    chain: if chain.len() > 1 { chain } else { Vec::new() },
+                });
+            }
+            if let Some(html) = &answer.html {
+                for link in LinkCheck::links(html) {
+                    if let Some(link) = LinkCheck::resolve(&link, &current, host) {
+                        if seen.insert(link.clone()) {
+                            queue.push_back((link, current.clone()));
+                        }
+                    }
+                }
+            }
+        }
+        report
+    }
+
+    /// Simple HTTP/1.1 GET request
+    async fn get(addr: &str, port: u16, host: &str, path: &str) -> Result<LinkAnswer, String> {
+        let res = timeout(Duration::from_secs(LINKCHECK_TIMEOUT), async {
+            let mut stream = HappyEyeballs::connect(addr, port).await.map_err(|e| e.to_string())?;
+            let request = format!(
+                "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: tiny-web linkcheck\r\nAccept: text/html\r\nConnection: close\r\n\r\n",
+                path, host
+            );
+            stream.write_all(request.as_bytes()).await.map_err(|e| e.to_string())?;
+            let mut buf = Vec::with_capacity(65536);
+            let mut chunk = [0u8; 65536];
+            loop {
+                let len = stream.read(&mut chunk).await.map_err(|e| e.to_string())?;
+                if len == 0 {
+                    break;
+                }
+                buf.extend_from_slice(&chunk[..len]);
+                if buf.len() > LINKCHECK_MAX_BODY {
+                    break;
+                }
+            }
+            Ok::<Vec<u8>, String>(buf)
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        LinkCheck::parse_answer(&res).ok_or_else(|| "Invalid answer".to_owned())
+    }
+
+    fn parse_answer(buf: &[u8]) -> Option<LinkAnswer> {
+        let end = buf.windows(4).position(|w| w == b"\r\n\r\n")?;
+        let head = String::from_utf8_lossy(&buf[..end]);
+        let mut lines = head.lines();
+        let status = lines.next()?.split(' ').nth(1)?.parse().ok()?;
+        let mut location = None;
+        let mut html = false;
+        let mut chunked = false;
+        for line in lines {
+            if let Some((key, value)) = line.split_once(':') {
+                let value = value.trim();
+                match key.trim().to_lowercase().as_str() {
+                    "location" => location = Some(value.to_owned()),
+                    "content-type" => html = value.contains("html") || value.contains("xml"),
+                    "transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+                    _ => {}
+                }
+            }
+        }
+        let body = &buf[end + 4..];
+        let html = if html {
+            let body = if chunked { LinkCheck::dechunk(body) } else { body.to_vec() };
+            Some(String::from_utf8_lossy(&body).to_string())
+        } else {
+            None
+        };
+        Some(LinkAnswer { status, location, html })
+    }
+
+    fn dechunk(mut body: &[u8]) -> Vec<u8> {
+        let mut res = Vec::with_capacity(body.len());
+        while let Some(pos) = body.windows(2).position(|w| w == b"\r\n") {
+            let size = String::from_utf8_lossy(&body[..pos]);
+            let size = match usize::from_str_radix(size.split(';').next().unwrap_or_default().trim(), 16) {
+                Ok(size) if size > 0 => size,
+                _ => break,
+            };
+            let start = pos + 2;
+            let end = (start + size).min(body.len());
+            res.extend_from_slice(&body[start..end]);
+            body = &body[(end + 2).min(body.len())..];
+        }
+        res
+    }
+
+    /// Parse "http://host[:port]"
+    fn parse_site(url: &str) -> Option<(String, u16)> {
+        let site = url.strip_prefix("http://")?.trim_end_matches('/');
+        match site.rsplit_once(':') {
+            Some((host, port)) => Some((host.to_owned(), port.parse().ok()?)),
+            None if !site.is_empty() => Some((site.to_owned(), 80)),
+            None => None,
+        }
+    }
+
+    /// Find links `href="..."` in the html
+    fn links(html: &str) -> Vec<String> {
+        let mut list = Vec::new();
+        let mut rest = html;
+        while let Some(pos) = rest.find("href=") {
+            rest = &rest[pos + 5..];
+            let quote = match rest.chars().next() {
+                Some(c) if c == '"' || c == '\'' => c,
+                _ => continue,
+            };
+            if let Some(end) = rest[1..].find(quote) {
+                list.push(rest[1..end + 1].replace("&amp;", "&"));
+                rest = &rest[end + 1..];
+            }
+        }
+        list
+    }
+
+    /// Find urls `<loc>...</loc>` in the sitemap
+    fn sitemap(xml: &str, host: &str) -> Vec<String> {
+        let mut list = Vec::new();
+        let mut rest = xml;
+        while let Some(pos) = rest.find("<loc>") {
+            rest = &rest[pos + 5..];
+            if let Some(end) = rest.find("</loc>") {
+                if let Some(url) = LinkCheck::resolve(rest[..end].trim(), "/", host) {
+                    list.push(url);
+                }
+                rest = &rest[end..];
+            }
+        }
+        list
+    }
+
+    /// Resolve the link to the path of the own site, `None` for external links
+    fn resolve(link: &str, current: &str, host: &str) -> Option<String> {
+        let link = link.trim();
+        let link = link.split('#').next().unwrap_or_default();
+        if link.is_empty() {
+            return None;
+        }
+        let path = if let Some(rest) =
+            link.strip_prefix("http://").or_else(|| link.strip_prefix("https://")).or_else(|| link.strip_prefix("//"))
+        {
+            let (site, path) = match rest.find('/') {
+                Some(pos) => (&rest[..pos], &rest[pos..]),
+                None => (rest, "/"),
+            };
+            if site.split(':').next() != Some(host) {
+                return None;
+            }
+            path.to_owned()
+        } else if link.starts_with('/') {
+            link.to_owned()
+        } else if matches!(link.find(':'), Some(pos) if link[..pos].bytes().all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'-' || b == b'.'))
+        {
+            // mailto:, tel:, javascript:
+            return None;
+        } else {
+            let current = current.split('?').next().unwrap_or_default();
+            let dir = match current.rfind('/') {
+                Some(pos) => &current[..pos + 1],
+                None => "/",
+            };
+            format!("{}{}", dir, link)
+        };
+        Some(path)
+    }
+}
@@ -0,0 +1,62 @@
+use std::{path::Path, sync::Arc};
+
+use tokio::runtime::Builder;
+
+use crate::{log, sys::db::adapter::DB};
+
+use super::init::Init;
+
+/// Folder of the migrations in the root folder
+const MIGRATE_DIR: &str = "migrations";
+
+/// Options of the migration runner
+#[derive(Debug)]
+pub(crate) struct MigrateOption {
+    /// Only show the migrations
+    pub dry_run: bool,
+    /// Number of the last migrations to roll back
+    pub rollback: Option<usize>,
+}
+
+/// Migration runner of the folder "migrations"
+///
+/// The connections do not prepare the statements of the library, the tables may not exist yet.
+pub(crate) struct Migrate;
+
+impl Migrate {
+    /// Apply the pending migrations or roll back the last ones
+    pub(crate) fn run(init: Init, root: &Path, option: MigrateOption) -> Result<(), ()> {
+        let runtime = match Builder::new_current_thread().enable_all().build() {
+            Ok(runtime) => runtime,
+            Err(_e) => {
+                log!(stop, 0, "{}", _e);
+                return Err(());
+            }
+        };
+        let dir = root.join(MIGRATE_DIR);
+        runtime.block_on(async move {
+            let db = DB::without_statements(Arc::clone(&init.db)).await?;
+            let res = match option.rollback {
+                Some(steps) => db.rollback(&dir, steps, option.dry_run).await,
+                None => db.migrate(&dir, option.dry_run).await,
+            };
+            let list = match res {
+                Ok(list) => list,
+                Err(e) => {
+                    println!("{}", e);
+                    return Err(());
+                }
+            };
+            for migration in &list {
+                println!("{}", migration.file());
+            }
+            match (option.rollback.is_some(), option.dry_run) {
+                (false, false) => println!("Applied: {}", list.len()),
+                (false, true) => println!("Dry run. Would be applied: {}", list.len()),
+                (true, false) => println!("Rolled back: {}", list.len()),
+                (true, true) => println!("Dry run. Would be rolled back: {}", list.len()),
+            }
+            Ok(())
+        })
+    }
+}
@@ -3,6 +3,27 @@ pub(crate) mod app;
 
 pub(crate) mod arg;
 
+pub(crate) mod config;
+
+pub(crate) mod error;
+
+#[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+pub(crate) mod extract;
+
 pub(crate) mod init;
 
+pub(crate) mod linkcheck;
+
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+pub(crate) mod migrate;
+
+#[cfg(feature = "redirect-db")]
+pub(crate) mod redirect;
+
+#[cfg(not(target_family = "windows"))]
+pub(crate) mod reload;
+
 pub(crate) mod run;
+
+#[cfg(any(feature = "session-file", feature = "session-db"))]
+pub(crate) mod sessions;
@@ -0,0 +1,216 @@
+use std::{collections::HashMap, fs::read_to_string, path::Path, sync::Arc};
+
+use percent_encoding::percent_decode_str;
+use tiny_web_macro::fnv1a_64 as m_fnv1a_64;
+use tokio::runtime::Builder;
+
+use crate::{log, sys::db::adapter::DB};
+
+use super::init::Init;
+
+/// Max length of the redirect chain for the loop detection
+const REDIRECT_CHAIN_MAX: usize = 32;
+
+/// One row of the CSV file
+#[derive(Debug)]
+struct RedirectRow {
+    line: usize,
+    url: String,
+    redirect: String,
+    permanently: bool,
+}
+
+/// Import of the legacy URL mappings into the redirect table
+///
+/// CSV format: `url,redirect[,permanently]`, the header line is optional.
+/// `permanently` is `1`, `true` or `301` (the default), `0`, `false` or `302`.
+pub(crate) struct RedirectImport;
+
+impl RedirectImport {
+    /// Validate the CSV file and insert new redirects
+    ///
+    /// With `dry_run` only the report is shown.
+    pub(crate) fn run(init: Init, path: &Path, dry_run: bool) -> Result<(), ()> {
+        let text = match read_to_string(path) {
+            Ok(text) => text,
+            Err(_e) => {
+                log!(stop, 0, "Неможливо прочитати файл {}. Помилка: {}", path.display(), _e);
+                return Err(());
+            }
+        };
+        let runtime = match Builder::new_current_thread().enable_all().build() {
+            Ok(runtime) => runtime,
+            Err(_e) => {
+                log!(stop, 0, "{}", _e);
+                return Err(());
+            }
+        };
+        runtime.block_on(async move {
+            let db = DB::new(Arc::clone(&init.db)).await?;
+            let res = match db.query_prepare(m_fnv1a_64!("lib_get_redirect_all"), &[]).await {
+                Some(res) => res,
+                None => return Err(()),
+            };
+            let mut exists = HashMap::with_capacity(res.len());
+            for row in res {
+                let url: String = row.get(0);
+                let redirect: String = row.get(1);
+                exists.insert(url, redirect);
+            }
+
+            let (rows, errors) = RedirectImport::check(&text, &exists);
+            for error in &errors {
+                println!("{}", error);
+            }
+            if !errors.is_empty() {
+                println!("Errors: {}. Nothing is imported.", errors.len());
+                return Err(());
+            }
+            if dry_run {
+                println!("Dry run. Would be imported: {}", rows.len());
+                return Ok(());
+            }
+            let mut count = 0;
+            for row in &rows {
+                if db.execute_prepare(m_fnv1a_64!("lib_add_redirect"), &[&row.url, &row.permanently, &row.redirect]).await.is_none() {
+                    println!("Line {}: cannot insert {}. Imported: {}", row.line, row.url, count);
+                    return Err(());
+                }
+                count += 1;
+            }
+            println!("Imported: {}", count);
+            Ok(())
+        })
+    }
+
+    /// Validate rows, detect duplicates and loops
+    ///
+    /// Returns new rows and the list of errors.
+    fn check(text: &str, exists: &HashMap<String, String>) -> (Vec<RedirectRow>, Vec<String>) {
+        let mut rows: Vec<RedirectRow> = Vec::new();
+        let mut errors = Vec::new();
+        let mut urls: HashMap<String, usize> = HashMap::new();
+
+        for (idx, fields) in RedirectImport::parse_csv(text).into_iter().enumerate() {
+            let line = idx + 1;
+            if fields.iter().all(|f| f.is_empty()) {
+                continue;
+            }
+            let url = fields.first().map(|f| f.trim()).unwrap_or_default();
+            let redirect = fields.get(1).map(|f| f.trim()).unwrap_or_default();
+            if line == 1 && !url.starts_with('/') && !url.contains("://") {
+                // Header
+                continue;
+            }
+            let permanently = match fields.get(2).map(|f| f.trim().to_lowercase()).as_deref() {
+                None | Some("") | Some("1") | Some("true") | Some("301") => true,
+                Some("0") | Some("false") | Some("302") => false,
+                Some(value) => {
+                    errors.push(format!("Line {}: invalid permanently value {}", line, value));
+                    continue;
+                }
+            };
+            let url = match RedirectImport::normalize(url) {
+                Some(url) => url,
+                None => {
+                    errors.push(format!("Line {}: invalid url {:?}, must start with \"/\" without the query string", line, url));
+                    continue;
+                }
+            };
+            if !(redirect.starts_with('/') || redirect.starts_with("http://") || redirect.starts_with("https://")) {
+                errors.push(format!("Line {}: invalid redirect {:?}, must start with \"/\", \"http://\" or \"https://\"", line, redirect));
+                continue;
+            }
+            if let Some(old) = exists.get(&url) {
+                if old == redirect {
+                    println!("Line {}: skip {}, already exists", line, url);
+                } else {
+                    errors.push(format!("Line {}: {} already redirects to {}", line, url, old));
+                }
+                continue;
+            }
+            if let Some(first) = urls.get(&url) {
+                let first = unsafe { rows.get_unchecked(*first) };
+                if first.redirect == redirect {
+                    println!("Line {}: skip {}, duplicate of line {}", line, url, first.line);
+                } else {
+                    errors.push(format!("Line {}: {} duplicates line {} with another redirect", line, url, first.line));
+                }
+                continue;
+            }
+            urls.insert(url.clone(), rows.len());
+            rows.push(RedirectRow {
+                line,
+                url,
+                redirect: redirect.to_owned(),
+                permanently,
+            });
+        }
+
+        // Loop detection over existing and new redirects
+        let mut map: HashMap<&str, &str> = exists.iter().map(|(url, redirect)| (url.as_str(), redirect.as_str())).collect();
+        for row in &rows {
+            map.insert(&row.url, &row.redirect);
+        }
+        for row in &rows {
+            let mut next = row.redirect.as_str();
+            for _ in 0..REDIRECT_CHAIN_MAX {
+                let key = match RedirectImport::normalize(next) {
+                    Some(key) => key,
+                    None => break,
+                };
+                if key == row.url {
+                    errors.push(format!("Line {}: loop, redirect {} -> {} leads back to {}", row.line, row.url, row.redirect, row.url));
+                    break;
+                }
+                next = match map.get(key.as_str()) {
+                    Some(next) => next,
+                    None => break,
+                };
+            }
+        }
+        (rows, errors)
+    }
+
+    /// Decode the url as in the request, `None` if the url is not a local path
+    fn normalize(url: &str) -> Option<String> {
+        if !url.starts_with('/') || url.contains('?') || url.contains('#') {
+            return None;
+        }
+        percent_decode_str(url).decode_utf8().ok().map(|url| url.to_string())
+    }
+
+    /// Simple CSV parser with quoted fields
+    fn parse_csv(text: &str) -> Vec<Vec<String>> {
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted => {
+                    if chars.peek() == Some(&'"') {
+                        field.push('"');
+                        chars.next();
+                    } else {
+                        quoted = false;
+                    }
+                }
+                '"' if field.is_empty() => quoted = true,
+                ',' | ';' if !quoted => row.push(std::mem::take(&mut field)),
+                '\r' if !quoted => {}
+                '\n' if !quoted => {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                c => field.push(c),
+            }
+        }
+        if !field.is_empty() || !row.is_empty() {
+            row.push(field);
+            rows.push(row);
+        }
+        rows
+    }
+}
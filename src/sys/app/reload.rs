@@ -0,0 +1,185 @@
+use std::{
+    env,
+    fs::OpenOptions,
+    io::{Error, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, RawFd},
+        unix::process::CommandExt,
+    },
+    path::Path,
+    process::{self, Command, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    net::{TcpListener, UnixListener},
+    process::ChildStdout,
+    time,
+};
+
+use crate::{
+    log,
+    sys::net::stream::{Listener, Socket},
+};
+
+use super::init::{Net, SIGNAL_TIMEOUT_WAIT};
+
+/// Number of the first passed socket, as in the systemd socket activation
+const LISTEN_FD: RawFd = 3;
+
+/// Answer of the new process when it accepts connections
+const HANDOVER_READY: &str = "READY";
+
+/// Environment variable of the process started by the reload
+const HANDOVER_ENV: &str = "TINY_HANDOVER";
+
+/// The process got the listener from the previous process
+static HANDOVER: AtomicBool = AtomicBool::new(false);
+
+/// Zero-downtime reload
+///
+/// The running process starts the new process with the listening socket in `LISTEN_FDS`,
+/// waits until the new process accepts connections, then stops accepting and drains its workers.
+/// The connections in the backlog of the socket are accepted by the new process.
+pub(crate) struct Handover;
+
+impl Handover {
+    /// Listener passed by the previous process or by the systemd socket activation
+    ///
+    /// Every address of [net] bind has its own descriptor, starting from 3.
+    pub(crate) fn listener(net: &Net) -> Option<Result<Listener, Error>> {
+        if env::var("LISTEN_FDS").ok()?.parse::<usize>().ok()? != net.bind_extra.len() + 1 {
+            return None;
+        }
+        if let Ok(pid) = env::var("LISTEN_PID") {
+            if pid != process::id().to_string() {
+                return None;
+            }
+        }
+        env::remove_var("LISTEN_FDS");
+        env::remove_var("LISTEN_PID");
+        if env::var_os(HANDOVER_ENV).is_some() {
+            env::remove_var(HANDOVER_ENV);
+            HANDOVER.store(true, Ordering::SeqCst);
+        }
+        let listener = match &net.bind {
+            Socket::Inet(_) if !net.bind_extra.is_empty() => (0..=net.bind_extra.len())
+                .map(|i| {
+                    let listener = unsafe { std::net::TcpListener::from_raw_fd(LISTEN_FD + i as RawFd) };
+                    listener.set_nonblocking(true).and_then(|_| TcpListener::from_std(listener))
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(Listener::TcpList),
+            Socket::Inet(_) => {
+                let listener = unsafe { std::net::TcpListener::from_raw_fd(LISTEN_FD) };
+                listener.set_nonblocking(true).and_then(|_| TcpListener::from_std(listener)).map(Listener::TcpListener)
+            }
+            Socket::Unix(_) => {
+                let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(LISTEN_FD) };
+                listener.set_nonblocking(true).and_then(|_| UnixListener::from_std(listener)).map(Listener::UnixListener)
+            }
+        };
+        Some(listener)
+    }
+
+    /// The process is started by the reload of the previous process
+    pub(crate) fn is_handover() -> bool {
+        HANDOVER.load(Ordering::SeqCst)
+    }
+
+    /// Tell the previous process that the connections are accepted
+    ///
+    /// The output is closed after the answer, so the process does not write to the pipe of the finished process.
+    pub(crate) fn ready() {
+        if !Handover::is_handover() {
+            return;
+        }
+        let mut stdout = std::io::stdout();
+        if let Err(_e) = writeln!(stdout, "{}", HANDOVER_READY).and_then(|_| stdout.flush()) {
+            log!(warning, 0, "{}", _e);
+        }
+        match OpenOptions::new().write(true).open("/dev/null") {
+            Ok(null) => {
+                if unsafe { libc::dup2(null.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+                    log!(warning, 0, "{}", Error::last_os_error());
+                }
+            }
+            Err(_e) => log!(warning, 0, "{}", _e),
+        }
+    }
+
+    /// Start the new process with the listeners, returns the PID when the new process accepts connections
+    pub(crate) async fn spawn(exe: &Path, root: &Path, fds: &[RawFd]) -> Result<u32, ()> {
+        let mut command = Command::new(exe);
+        command
+            .arg("run")
+            .arg("-r")
+            .arg(root)
+            .current_dir(root)
+            .env("LISTEN_FDS", fds.len().to_string())
+            .env(HANDOVER_ENV, "1")
+            .env_remove("LISTEN_PID")
+            .stdout(Stdio::piped());
+        let fds = fds.to_vec();
+        let mut copies = vec![-1; fds.len()];
+        unsafe {
+            command.pre_exec(move || {
+                // The copies above the range do not overwrite the descriptors which are not moved yet
+                let top = LISTEN_FD + fds.len() as RawFd;
+                for (copy, fd) in copies.iter_mut().zip(&fds) {
+                    *copy = libc::fcntl(*fd, libc::F_DUPFD_CLOEXEC, top);
+                    if *copy < 0 {
+                        return Err(Error::last_os_error());
+                    }
+                }
+                // dup2 clears FD_CLOEXEC
+                for (i, copy) in copies.iter().enumerate() {
+                    if libc::dup2(*copy, LISTEN_FD + i as RawFd) < 0 {
+                        return Err(Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+        let mut child = match tokio::process::Command::from(command).spawn() {
+            Ok(child) => child,
+            Err(_e) => {
+                log!(warning, 0, "{:?}. Error: {}", exe, _e);
+                return Err(());
+            }
+        };
+        let ready = match child.stdout.take() {
+            Some(stdout) => time::timeout(Duration::from_millis(SIGNAL_TIMEOUT_WAIT), Handover::wait_ready(stdout)).await.unwrap_or(false),
+            None => false,
+        };
+        match child.id() {
+            Some(pid) if ready => Ok(pid),
+            _ => {
+                log!(warning, 0, "{}", "Новий процес не почав приймати з'єднання");
+                let _ = child.kill().await;
+                Err(())
+            }
+        }
+    }
+
+    async fn wait_ready(stdout: ChildStdout) -> bool {
+        let mut lines = BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if line.trim() == HANDOVER_READY {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Descriptors of the listener for the new process
+    pub(crate) fn fds(listener: &Listener) -> Vec<RawFd> {
+        match listener {
+            Listener::TcpListener(tcp) => vec![tcp.as_raw_fd()],
+            Listener::TcpList(list) => list.iter().map(|tcp| tcp.as_raw_fd()).collect(),
+            Listener::UnixListener(unix) => vec![unix.as_raw_fd()],
+        }
+    }
+}
@@ -17,7 +17,7 @@ use tokio::{
 use tokio::{
     net::{TcpListener, TcpStream},
     runtime::Builder,
-    sync::{oneshot, Mutex},
+    sync::{oneshot, Mutex, Notify},
     task::JoinHandle,
     time,
 };
@@ -28,13 +28,16 @@ use tokio::sync::RwLock;
 use crate::{
     fnv1a_64, log,
     sys::{
+        app::error::TinyError,
         net::{
+            queue::RequestQueue,
             stream::{Listener, Socket},
             worker::{Worker, WorkerData},
         },
         stat::stat::Stat,
-        web::action::ModuleMap,
+        web::{action::ModuleMap, router::Router, toggle::ModuleToggle},
     },
+    HookFn,
 };
 
 #[cfg(any(feature = "html-static", feature = "html-reload"))]
@@ -46,21 +49,95 @@ use crate::sys::web::{lang::Lang, lang::LangParam};
 #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
 use crate::sys::web::session::{SessionArg, SessionLoader};
 
+#[cfg(any(feature = "session-file", feature = "session-db"))]
+use crate::sys::web::session::SessionGc;
+
 #[cfg(any(feature = "pgsql", feature = "mssql"))]
 use crate::sys::db::adapter::DB;
 
+#[cfg(feature = "route-db")]
+use tiny_web_macro::fnv1a_64 as m_fnv1a_64;
+
 use super::{
     arg::Arg,
-    init::{AutoCount, Init, SIGNAL_TIMEOUT},
+    init::{AutoCount, Init, SIGNAL_TIMEOUT, SIGNAL_TIMEOUT_WAIT},
 };
 
+#[cfg(not(target_family = "windows"))]
+use std::{os::fd::RawFd, path::PathBuf};
+
+#[cfg(not(target_family = "windows"))]
+use super::reload::Handover;
+
+/// Descriptors of the listening sockets for the new process
+#[cfg(not(target_family = "windows"))]
+type ListenFd = Vec<RawFd>;
+#[cfg(target_family = "windows")]
+type ListenFd = ();
+
 pub(crate) struct Run;
 
+/// Lifecycle hooks of the application
+#[derive(Default)]
+pub(crate) struct Hooks {
+    /// Before the server accepts connections
+    pub start: Vec<HookFn>,
+    /// After the graceful shutdown
+    pub stop: Vec<HookFn>,
+    /// Data providers of the application for the export and the erasure of the user data
+    #[cfg(feature = "privacy")]
+    pub privacy: Vec<PrivacyProvider>,
+}
+
+/// Data of the reload for the rpc listener
+struct ReloadArg {
+    /// Stops accepting of the connections and drains the workers
+    #[cfg(not(target_family = "windows"))]
+    notify: Arc<Notify>,
+    #[cfg(not(target_family = "windows"))]
+    fd: ListenFd,
+    #[cfg(not(target_family = "windows"))]
+    exe: PathBuf,
+    #[cfg(not(target_family = "windows"))]
+    root: Arc<PathBuf>,
+    #[cfg(feature = "plugin")]
+    plugins: Arc<Plugins>,
+}
+
 #[cfg(feature = "cache")]
 use crate::sys::web::cache::Cache;
 
+#[cfg(feature = "file-disk")]
+use crate::sys::web::file::HotFiles;
+
+#[cfg(all(feature = "cache", any(feature = "pgsql", feature = "mssql")))]
+use crate::sys::db::notify::CacheNotify;
+
+#[cfg(feature = "plugin")]
+use crate::sys::plugin::Plugins;
+
+#[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+use crate::sys::log::Log;
+
+#[cfg(feature = "alert")]
+use crate::sys::alert::Alert;
+#[cfg(feature = "otel")]
+use crate::sys::otel::Otel;
+
+#[cfg(feature = "profile")]
+use crate::sys::profile::Profile;
+
+#[cfg(feature = "privacy")]
+use crate::sys::web::privacy::{Privacy, PrivacyProvider};
+
+#[cfg(feature = "storage")]
+use crate::sys::web::storage::Storage;
+
+#[cfg(feature = "admin")]
+use crate::sys::web::admin::Admin;
+
 impl Run {
-    pub(crate) fn start(args: Arg, init: Init, engine: ModuleMap) -> Result<(), ()> {
+    pub(crate) fn start(args: Arg, init: Init, engine: ModuleMap, router: Router, hooks: Hooks) -> Result<(), TinyError> {
         let mut builder = Builder::new_multi_thread();
         builder.thread_name(format!("{} {}", init.name, init.version));
         if let AutoCount::Count(worker_threads) = init.proc.worker_threads {
@@ -87,13 +164,33 @@ impl Run {
 
         let runtime = match builder.enable_all().build() {
             Ok(r) => r,
-            Err(_e) => {
-                log!(stop, 0, "{}", _e);
-                return Err(());
+            Err(e) => {
+                log!(stop, 0, "{}", e);
+                return Err(TinyError::Runtime(e.to_string()));
             }
         };
         // Start runtime
         runtime.block_on(async move {
+            #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+            Log::hook();
+            #[cfg(feature = "otel")]
+            if let Some(otel) = &init.otel {
+                Otel::start(Arc::clone(otel));
+            }
+            #[cfg(feature = "alert")]
+            if let Some(alert) = &init.alert {
+                Alert::start(Arc::clone(alert));
+            }
+            #[cfg(feature = "profile")]
+            if let Some(profile) = &init.profile {
+                Profile::start(Arc::clone(profile));
+            }
+            #[cfg(feature = "storage")]
+            Storage::start(Arc::clone(&init.storage));
+            #[cfg(feature = "file-disk")]
+            if let Some(hot) = &init.hot {
+                HotFiles::start(Arc::clone(hot));
+            }
             let mon = Arc::new(Stat::new());
             let stop = Arc::new(AtomicBool::new(false));
             let init = Arc::new(init);
@@ -102,65 +199,208 @@ impl Run {
             let stop_clone = Arc::clone(&stop);
             let init_clone = Arc::clone(&init);
 
-            #[cfg(not(target_family = "windows"))]
+            #[cfg(feature = "privacy")]
+            Privacy::register(hooks.privacy);
+
+            for hook in &hooks.start {
+                if !hook().await {
+                    log!(stop, 0, "{}", "Запуск скасовано хуком on_start");
+                    return Err(TinyError::Hook);
+                }
+            }
+
             let mut res = Ok(());
-            #[cfg(target_family = "windows")]
-            let res = Ok(());
-            if let Ok(listener) = Run::listen(stop_clone, mon_clone, init_clone, args, engine).await {
-                if Run::listen_rpc(stop, listener, mon, Arc::clone(&init)).await.is_ok() {
-                    #[cfg(not(target_family = "windows"))]
-                    if let Socket::Unix(uds) = &init.net.rpc {
-                        if let Err(e) = remove_file(uds).await {
-                            if e.kind() != ErrorKind::NotFound {
-                                log!(stop, 0, "{}", e);
-                                res = Err(());
+            let notify = Arc::new(Notify::new());
+            #[cfg(not(target_family = "windows"))]
+            let (exe, root) = (args.exe.clone(), Arc::clone(&args.root));
+            #[cfg(feature = "plugin")]
+            let plugins = {
+                let plugins = Arc::new(Plugins::new(&args.root));
+                let _len = plugins.load().await;
+                log!(info, 0, "Plugins: {}", _len);
+                plugins
+            };
+            match Run::listen(
+                stop_clone,
+                mon_clone,
+                init_clone,
+                args,
+                engine,
+                router,
+                Arc::clone(&notify),
+                #[cfg(feature = "plugin")]
+                Arc::clone(&plugins),
+            )
+            .await
+            {
+                Ok((listener, _fd)) => {
+                    let reload = ReloadArg {
+                        #[cfg(not(target_family = "windows"))]
+                        notify,
+                        #[cfg(not(target_family = "windows"))]
+                        fd: _fd,
+                        #[cfg(not(target_family = "windows"))]
+                        exe,
+                        #[cfg(not(target_family = "windows"))]
+                        root,
+                        #[cfg(feature = "plugin")]
+                        plugins,
+                    };
+                    match Run::listen_rpc(stop, listener, mon, Arc::clone(&init), reload).await {
+                        // The sockets are used by the new process
+                        Ok(true) => {}
+                        Ok(false) => {
+                            #[cfg(not(target_family = "windows"))]
+                            if let Socket::Unix(uds) = &init.net.rpc {
+                                if let Err(e) = remove_file(uds).await {
+                                    if e.kind() != ErrorKind::NotFound {
+                                        log!(stop, 0, "{}", e);
+                                        res = Err(TinyError::Stop(e.to_string()));
+                                    }
+                                }
+                            }
+                            #[cfg(not(target_family = "windows"))]
+                            if let Socket::Unix(uds) = &init.net.bind {
+                                if let Err(e) = remove_file(uds).await {
+                                    if e.kind() != ErrorKind::NotFound {
+                                        log!(stop, 0, "{}", e);
+                                        res = Err(TinyError::Stop(e.to_string()));
+                                    }
+                                }
                             }
                         }
-                    }
-                };
-
-                #[cfg(not(target_family = "windows"))]
-                if let Socket::Unix(uds) = &init.net.bind {
-                    if let Err(e) = remove_file(uds).await {
-                        if e.kind() != ErrorKind::NotFound {
-                            log!(stop, 0, "{}", e);
-                            res = Err(());
+                        Err(e) => {
+                            res = Err(e);
+                            #[cfg(not(target_family = "windows"))]
+                            if let Socket::Unix(uds) = &init.net.bind {
+                                if let Err(_e) = remove_file(uds).await {
+                                    if _e.kind() != ErrorKind::NotFound {
+                                        log!(stop, 0, "{}", _e);
+                                    }
+                                }
+                            }
                         }
                     }
                 }
+                Err(e) => res = Err(e),
+            }
+            for hook in hooks.stop.iter().rev() {
+                if !hook().await && res.is_ok() {
+                    res = Err(TinyError::Hook);
+                }
             }
             res
         })
     }
 
-    async fn listen(stop: Arc<AtomicBool>, mon: Arc<Stat>, init: Arc<Init>, _args: Arg, engine: ModuleMap) -> Result<JoinHandle<()>, ()> {
-        let bind = match &init.net.bind {
-            Socket::Inet(addr) => match TcpListener::bind(addr).await {
-                Ok(i) => Listener::TcpListener(i),
-                Err(_e) => {
-                    log!(stop, 0, "{}", _e);
-                    return Err(());
-                }
-            },
-            #[cfg(not(target_family = "windows"))]
-            Socket::Unix(uds) => match UnixListener::bind(uds) {
-                Ok(i) => Listener::UnixListener(i),
-                Err(_e) => {
-                    log!(stop, 0, "{}", _e);
-                    return Err(());
+    /// Load path patterns from the route table
+    #[cfg(feature = "route-db")]
+    async fn load_route(db: &DB, router: &mut Router) -> Result<(), TinyError> {
+        let res = match db.query_prepare(m_fnv1a_64!("lib_get_route_pattern"), &[]).await {
+            Some(res) => res,
+            None => return Err(TinyError::Db("lib_get_route_pattern".to_owned())),
+        };
+        for row in res {
+            #[cfg(feature = "pgsql")]
+            let (url, module_id, class_id, action_id) =
+                (row.get::<_, String>(0), row.get::<_, i64>(1), row.get::<_, i64>(2), row.get::<_, i64>(3));
+            #[cfg(feature = "mssql")]
+            let (url, module_id, class_id, action_id) =
+                match (row.get::<&str, _>(0), row.get::<i64, _>(1), row.get::<i64, _>(2), row.get::<i64, _>(3)) {
+                    (Some(url), Some(module_id), Some(class_id), Some(action_id)) => (url.to_owned(), module_id, class_id, action_id),
+                    _ => continue,
+                };
+            if let Err(e) = router.add_route(&url, module_id, class_id, action_id) {
+                log!(stop, 0, "{}", e);
+                return Err(TinyError::Config(e));
+            }
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn listen(
+        stop: Arc<AtomicBool>,
+        mon: Arc<Stat>,
+        init: Arc<Init>,
+        _args: Arg,
+        engine: ModuleMap,
+        router: Router,
+        reload: Arc<Notify>,
+        #[cfg(feature = "plugin")] plugins: Arc<Plugins>,
+    ) -> Result<(JoinHandle<Result<(), TinyError>>, ListenFd), TinyError> {
+        #[cfg(not(target_family = "windows"))]
+        let handover = Handover::listener(&init.net);
+        #[cfg(target_family = "windows")]
+        let handover = None;
+        let bind = match handover {
+            Some(Ok(bind)) => bind,
+            Some(Err(e)) => {
+                log!(stop, 0, "{}", e);
+                return Err(TinyError::Bind(e.to_string()));
+            }
+            None => match &init.net.bind {
+                Socket::Inet(addr) if !init.net.bind_extra.is_empty() => {
+                    let mut list = Vec::with_capacity(init.net.bind_extra.len() + 1);
+                    list.push(*addr);
+                    list.extend_from_slice(&init.net.bind_extra);
+                    match Listener::bind_list(&list) {
+                        Ok(i) => i,
+                        Err(e) => {
+                            log!(stop, 0, "{}", e);
+                            return Err(TinyError::Bind(e.to_string()));
+                        }
+                    }
                 }
+                Socket::Inet(addr) => match TcpListener::bind(addr).await {
+                    Ok(i) => Listener::TcpListener(i),
+                    Err(e) => {
+                        log!(stop, 0, "{}", e);
+                        return Err(TinyError::Bind(e.to_string()));
+                    }
+                },
+                #[cfg(not(target_family = "windows"))]
+                Socket::Unix(uds) => match UnixListener::bind(uds) {
+                    Ok(i) => Listener::UnixListener(i),
+                    Err(e) => {
+                        log!(stop, 0, "{}", e);
+                        return Err(TinyError::Bind(e.to_string()));
+                    }
+                },
             },
         };
-        Ok(tokio::spawn(async move {
+        #[cfg(not(target_family = "windows"))]
+        let fd = Handover::fds(&bind);
+        #[cfg(target_family = "windows")]
+        let fd = ();
+        let handle = tokio::spawn(async move {
             let ip = init.net.bind_from;
             let workers: Arc<Mutex<HashMap<u64, JoinHandle<()>>>> =
                 Arc::new(Mutex::new(HashMap::with_capacity(init.proc.worker_threads.value() + 1)));
+            #[cfg(feature = "admin")]
+            let mut engine = engine;
+            #[cfg(feature = "admin")]
+            Admin::add(&mut engine);
             let engine = Arc::new(engine);
             #[cfg(any(feature = "pgsql", feature = "mssql"))]
-            let db = match DB::new(Arc::clone(&init.db)).await {
+            let db = match DB::start(Arc::clone(&init.db)).await {
                 Ok(db) => Arc::new(db),
-                Err(_) => return,
+                Err(()) => return Err(TinyError::Db("Неможливо підключитися до бази даних".to_owned())),
             };
+            #[cfg(any(feature = "pgsql", feature = "mssql"))]
+            mon.add_limit(Arc::clone(&db.limit));
+            #[cfg(any(feature = "pgsql", feature = "mssql"))]
+            mon.add_pool(Arc::clone(&db.pool));
+            let mut router = router;
+            for (pattern, controller) in &init.route {
+                if let Err(e) = router.add_route(pattern, controller[0], controller[1], controller[2]) {
+                    log!(stop, 0, "{}", e);
+                    return Err(TinyError::Config(e));
+                }
+            }
+            #[cfg(feature = "route-db")]
+            Run::load_route(&db, &mut router).await?;
+            let router = Arc::new(router.build());
             #[cfg(any(feature = "html-static", feature = "html-reload"))]
             let html = match Html::new(Arc::clone(&_args.root)).await {
                 Ok(html) => {
@@ -173,19 +413,40 @@ impl Run {
                         Arc::new(RwLock::new(html))
                     }
                 }
-                Err(_) => {
+                Err(()) => {
                     log!(stop, 0);
-                    return;
+                    return Err(TinyError::Template("Неможливо завантажити шаблони".to_owned()));
                 }
             };
 
+            #[cfg(all(feature = "cache", not(feature = "cache-redis")))]
+            let mut cache = Cache::new();
+            #[cfg(feature = "cache-redis")]
+            let mut cache = Cache::new(Arc::clone(&init.redis));
             #[cfg(feature = "cache")]
-            let cache = Arc::new(Cache::new());
+            cache.coalesce(init.web.coalesce.clone(), init.web.coalesce_wait);
+            #[cfg(feature = "cache-redis")]
+            if !cache.wait().await {
+                return Err(TinyError::Service("Redis недоступний".to_owned()));
+            }
+            #[cfg(feature = "cache")]
+            let cache = Arc::new(cache);
+            #[cfg(all(feature = "cache", any(feature = "pgsql", feature = "mssql")))]
+            CacheNotify::start(
+                Arc::clone(&init.db),
+                Arc::clone(&cache),
+                #[cfg(feature = "pgsql")]
+                &mon,
+            );
+
+            let queue = RequestQueue::new(Arc::clone(&init.queue));
 
             #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
             let param = LangParam {
                 root: Arc::clone(&_args.root),
                 default_lang: Arc::clone(&init.web.lang),
+                prefix: init.web.lang_prefix,
+                accept: init.web.lang_accept,
                 #[cfg(feature = "session-db")]
                 db: Arc::clone(&db),
             };
@@ -202,9 +463,9 @@ impl Run {
                         Arc::new(lang)
                     }
                 }
-                Err(_) => {
+                Err(()) => {
                     log!(stop, 0);
-                    return;
+                    return Err(TinyError::Template("Неможливо завантажити переклади".to_owned()));
                 }
             };
 
@@ -213,28 +474,45 @@ impl Run {
                 session_key: Arc::clone(&init.web.session_key),
                 #[cfg(any(feature = "session-memory", feature = "session-file"))]
                 session_path: Arc::clone(&init.web.session_path),
+                bind: init.web.session_bind,
                 #[cfg(feature = "session-db")]
                 db: Arc::clone(&db),
             };
             #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
             let session = match SessionLoader::start(sess_arg).await {
                 Ok(session) => Arc::new(session),
-                Err(_) => {
+                Err(()) => {
                     log!(stop, 0);
-                    return;
+                    return Err(TinyError::Service("Неможливо запустити сесії".to_owned()));
                 }
             };
+            #[cfg(any(feature = "session-file", feature = "session-db"))]
+            SessionGc::new(
+                init.web.session_gc,
+                #[cfg(feature = "session-file")]
+                Arc::clone(&init.web.session_path),
+                #[cfg(feature = "session-db")]
+                Arc::clone(&db),
+            )
+            .start();
 
             #[cfg(feature = "https")]
             let acceptor = match Worker::load_cert(Arc::clone(&_args.root)) {
                 Ok(acceptor) => acceptor,
-                Err(_e) => {
-                    log!(stop, 507, "{}", _e);
-                    return;
+                Err(e) => {
+                    log!(stop, 507, "{}", e);
+                    return Err(TinyError::Tls(e.to_string()));
                 }
             };
-            loop {
-                let (stream, _ip) = match bind.accept(&ip).await {
+            mon.set_ready();
+            #[cfg(not(target_family = "windows"))]
+            Handover::ready();
+            let drain = loop {
+                let accept = tokio::select! {
+                    accept = bind.accept(&ip) => accept,
+                    _ = reload.notified() => break true,
+                };
+                let (stream, _ip) = match accept {
                     Ok(stream) => stream,
                     Err(_e) => {
                         log!(stop, 0, "{}", _e);
@@ -242,17 +520,26 @@ impl Run {
                     }
                 };
                 if stop.load(Ordering::SeqCst) {
-                    break;
+                    break false;
                 }
                 let id = mon.worker.fetch_add(1, Ordering::SeqCst);
                 let (tx, rx) = oneshot::channel();
                 let mon = Arc::clone(&mon);
                 let engine = Arc::clone(&engine);
+                #[cfg(feature = "plugin")]
+                let plugins = Arc::clone(&plugins);
+                let router = Arc::clone(&router);
+                #[cfg(any(feature = "http", feature = "https"))]
+                let http = Arc::clone(&init.http);
+                let upload = Arc::clone(&init.upload);
+                let security = Arc::clone(&init.security);
+                let queue = Arc::clone(&queue);
                 #[cfg(any(feature = "http", feature = "https"))]
                 let root = Arc::clone(&_args.root);
                 let salt = Arc::clone(&init.web.salt);
                 let index = Arc::clone(&init.web.index);
                 let not_found = init.web.not_found.clone();
+                let action_err = init.web.action_err.clone();
                 #[cfg(feature = "https")]
                 let acceptor = Arc::clone(&acceptor);
                 #[cfg(any(feature = "html-static", feature = "html-reload"))]
@@ -263,7 +550,12 @@ impl Run {
                 let lang = Arc::clone(&lang);
                 #[cfg(any(feature = "pgsql", feature = "mssql"))]
                 let db = Arc::clone(&db);
-                #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+                #[cfg(any(
+                    feature = "mail-sendmail",
+                    feature = "mail-smtp",
+                    feature = "mail-file",
+                    feature = "mail-api"
+                ))]
                 let mail = Arc::clone(&init.mail);
                 #[cfg(feature = "cache")]
                 let cache = Arc::clone(&cache);
@@ -274,6 +566,14 @@ impl Run {
                         id,
                         mon,
                         engine,
+                        #[cfg(feature = "plugin")]
+                        plugins,
+                        router,
+                        #[cfg(any(feature = "http", feature = "https"))]
+                        http,
+                        upload,
+                        security,
+                        queue,
                         #[cfg(any(feature = "http", feature = "https"))]
                         root,
                         salt,
@@ -281,6 +581,7 @@ impl Run {
                         ip: _ip,
                         index,
                         not_found,
+                        action_err,
                         #[cfg(any(feature = "pgsql", feature = "mssql"))]
                         db,
                         #[cfg(feature = "https")]
@@ -291,7 +592,12 @@ impl Run {
                         session,
                         #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
                         lang,
-                        #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+                        #[cfg(any(
+                            feature = "mail-sendmail",
+                            feature = "mail-smtp",
+                            feature = "mail-file",
+                            feature = "mail-api"
+                        ))]
                         mail,
                         #[cfg(feature = "cache")]
                         cache,
@@ -309,7 +615,19 @@ impl Run {
                     };
                 });
                 if stop.load(Ordering::Relaxed) {
-                    break;
+                    break false;
+                }
+            };
+            if drain {
+                // The new process accepts the connections, the current requests are completed
+                drop(bind);
+                let wait = async {
+                    while !workers.lock().await.is_empty() {
+                        time::sleep(Duration::from_millis(100)).await;
+                    }
+                };
+                if time::timeout(Duration::from_millis(SIGNAL_TIMEOUT_WAIT), wait).await.is_err() {
+                    log!(warning, 0, "{}", "Не всі запити завершено до перезапуску");
                 }
             }
             for (_, handle) in workers.lock().await.iter() {
@@ -324,23 +642,56 @@ impl Run {
                     }
                 }
             }
-        }))
+            Ok(())
+        });
+        Ok((handle, fd))
     }
 
-    async fn listen_rpc(stop: Arc<AtomicBool>, listener: JoinHandle<()>, mon: Arc<Stat>, init: Arc<Init>) -> Result<(), ()> {
-        let rpc = match init.net.rpc.bind().await {
+    /// Listen to the signals, returns true if the server is replaced by the new process
+    async fn listen_rpc(
+        stop: Arc<AtomicBool>,
+        mut listener: JoinHandle<Result<(), TinyError>>,
+        mon: Arc<Stat>,
+        init: Arc<Init>,
+        reload: ReloadArg,
+    ) -> Result<bool, TinyError> {
+        let rpc = match Run::bind_rpc(&init).await {
             Ok(listener) => listener,
-            Err(_e) => {
-                log!(stop, 0, "{}", _e);
+            Err(e) => {
+                log!(stop, 0, "{}", e);
                 Run::send_stop(stop, listener, init).await;
-                return Err(());
+                return Err(TinyError::Bind(e.to_string()));
             }
         };
         let stop_signal = fnv1a_64(format!("stop{}", init.web.salt).as_bytes());
         let status_signal = fnv1a_64(format!("status{}", init.web.salt).as_bytes());
+        let reload_signal = fnv1a_64(format!("reload{}", init.web.salt).as_bytes());
+        #[cfg(feature = "plugin")]
+        let plugin_signal = fnv1a_64(format!("plugin{}", init.web.salt).as_bytes());
+        let module_signal = fnv1a_64(format!("module{}", init.web.salt).as_bytes());
+        #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+        let loglevel_signal = fnv1a_64(format!("loglevel{}", init.web.salt).as_bytes());
 
         loop {
-            let (mut stream, _) = match rpc.accept(&init.net.rpc_from).await {
+            let accept = tokio::select! {
+                accept = rpc.accept(&init.net.rpc_from) => accept,
+                // The listener stops itself only if the start has failed: the database, the templates, the certificates
+                res = &mut listener => {
+                    drop(rpc);
+                    #[cfg(not(target_family = "windows"))]
+                    if let Socket::Unix(uds) = &init.net.rpc {
+                        if let Err(_e) = remove_file(uds).await {
+                            log!(warning, 0, "{}", _e);
+                        }
+                    }
+                    return match res {
+                        Ok(Ok(())) => Ok(false),
+                        Ok(Err(e)) => Err(e),
+                        Err(e) => Err(TinyError::Runtime(e.to_string())),
+                    };
+                }
+            };
+            let (mut stream, _) = match accept {
                 Ok(stream) => stream,
                 Err(_e) => {
                     log!(warning, 0, "{}", _e);
@@ -372,15 +723,74 @@ impl Run {
                 let last = if last > 0 { last.to_string() } else { "empty".to_owned() };
                 let online = mon.get_online();
                 let total = mon.get_total();
+                let queued = mon.get_queued();
+                let rejected = mon.get_rejected();
+                let queue_max = mon.get_queue_max();
+                let mut limits = String::new();
+                for limit in mon.get_limits() {
+                    limits.push_str(&format!(
+                        "Limit {}: {} of {}, running {}, waiting {}, latency {} us.\n",
+                        limit.name, limit.limit, limit.max, limit.inflight, limit.waiting, limit.latency
+                    ));
+                }
+                #[cfg(all(feature = "cache", feature = "pgsql"))]
+                for bus in mon.get_buses() {
+                    limits.push_str(&format!(
+                        "Bus {}: {} of {} messages, sent {}, received {}, dropped {}, average lag {} us, max lag {} us.\n",
+                        bus.name,
+                        bus.len,
+                        bus.capacity,
+                        bus.sent,
+                        bus.received,
+                        bus.dropped,
+                        bus.lag_total / bus.received.max(1),
+                        bus.lag_max
+                    ));
+                }
+                for (name, count, time) in mon.get_timings() {
+                    limits.push_str(&format!("Timing {}: {} requests, average {} us.\n", name, count, time / count.max(1)));
+                }
+                #[cfg(any(feature = "pgsql", feature = "mssql"))]
+                if let Some(pool) = mon.get_pool() {
+                    limits.push_str(&format!(
+                        "Pool: {} connections, min {}, open {}, idle {}, waiting {}, average acquire {} us, max acquire {} us, timeouts {}, broken {}.\n",
+                        pool.size,
+                        pool.min,
+                        pool.open,
+                        pool.idle,
+                        pool.waiters,
+                        pool.acquire_time / pool.acquires.max(1),
+                        pool.acquire_max,
+                        pool.timeouts,
+                        pool.broken
+                    ));
+                    for item in mon.get_statements() {
+                        limits.push_str(&format!(
+                            "Statement {}: {} calls, {} errors, average {} us, max {} us.\n",
+                            item.name,
+                            item.count,
+                            item.errors,
+                            item.time / item.count.max(1),
+                            item.max
+                        ));
+                    }
+                }
+                let state = match mon.is_ready() {
+                    true => "The system is working ...\nReady: yes.",
+                    false => "The system is starting, waiting for the database or Redis ...\nReady: no.",
+                };
                 let status = format!(
                     r#"
-The system is working ...
+{}
 Number of workers: {}.
 Last worker id: {}.
 Number of online requests: {}.
 Number of total requests: {}.
-"#,
-                    len, last, online, total
+Number of queued requests: {}.
+Number of rejected requests: {}.
+Max queue time: {} us.
+{}"#,
+                    state, len, last, online, total, queued, rejected, queue_max, limits
                 );
                 if let Err(_e) = stream.signal_write_str(&status).await {
                     log!(stop, 0, "{}", _e);
@@ -393,14 +803,98 @@ Number of total requests: {}.
                     log!(stop, 0, "{}", _e);
                 }
                 break;
+            } else if signal == reload_signal {
+                log!(info, 0);
+                #[cfg(not(target_family = "windows"))]
+                if let Ok(pid) = Handover::spawn(&reload.exe, &reload.root, &reload.fd).await {
+                    if let Err(_e) = stream.signal_write_u64(pid as u64).await {
+                        log!(warning, 0, "{}", _e);
+                    }
+                    drop(rpc);
+                    if let Socket::Unix(uds) = &init.net.rpc {
+                        if let Err(_e) = remove_file(uds).await {
+                            log!(warning, 0, "{}", _e);
+                        }
+                    }
+                    reload.notify.notify_one();
+                    match listener.await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(_e)) => log!(stop, 0, "{}", _e),
+                        Err(_e) => log!(stop, 0, "{}", _e),
+                    }
+                    return Ok(true);
+                }
+                if let Err(_e) = stream.signal_write_u64(0).await {
+                    log!(warning, 0, "{}", _e);
+                }
+            } else if signal == module_signal {
+                let (module_id, code) = match (stream.signal_read_i64().await, stream.signal_read_i64().await) {
+                    (Ok(module_id), Ok(code)) => (module_id, u16::try_from(code).unwrap_or_default()),
+                    (Err(_e), _) | (_, Err(_e)) => {
+                        log!(warning, 0, "{}", _e);
+                        continue;
+                    }
+                };
+                ModuleToggle::set(module_id, code);
+                log!(info, 0, "Module {}: {}", module_id, code);
+                let pid = process::id() as u64;
+                if let Err(_e) = stream.signal_write_u64(pid).await {
+                    log!(warning, 0, "{}", _e);
+                }
             } else {
+                #[cfg(feature = "plugin")]
+                if signal == plugin_signal {
+                    let _len = reload.plugins.load().await;
+                    log!(info, 0, "Plugins: {}", _len);
+                    let pid = process::id() as u64;
+                    if let Err(_e) = stream.signal_write_u64(pid).await {
+                        log!(warning, 0, "{}", _e);
+                    }
+                    continue;
+                }
+                #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+                if signal == loglevel_signal {
+                    let level = match stream.signal_read_i64().await {
+                        Ok(level) => u8::try_from(level).unwrap_or_default(),
+                        Err(_e) => {
+                            log!(warning, 0, "{}", _e);
+                            continue;
+                        }
+                    };
+                    let level = Log::set_level(level);
+                    log!(info, 0, "Log level: {}", level);
+                    let pid = process::id() as u64;
+                    if let Err(_e) = stream.signal_write_u64(pid).await {
+                        log!(warning, 0, "{}", _e);
+                    }
+                    continue;
+                }
                 log!(warning, 0, "{}", signal.to_string());
             }
         }
-        Ok(())
+        Ok(false)
+    }
+
+    /// The new process of the reload waits until the previous process releases the rpc socket
+    async fn bind_rpc(init: &Init) -> Result<Listener, std::io::Error> {
+        #[cfg(not(target_family = "windows"))]
+        if Handover::is_handover() {
+            let mut wait = 0;
+            loop {
+                match init.net.rpc.bind().await {
+                    Ok(listener) => return Ok(listener),
+                    Err(e) if e.kind() == ErrorKind::AddrInUse && wait < SIGNAL_TIMEOUT_WAIT => {
+                        time::sleep(Duration::from_millis(100)).await;
+                        wait += 100;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        init.net.rpc.bind().await
     }
 
-    async fn send_stop(stop: Arc<AtomicBool>, listener: JoinHandle<()>, init: Arc<Init>) {
+    async fn send_stop(stop: Arc<AtomicBool>, listener: JoinHandle<Result<(), TinyError>>, init: Arc<Init>) {
         stop.store(true, Ordering::SeqCst);
         match &init.net.bind {
             Socket::Inet(addr) => {
@@ -421,8 +915,10 @@ Number of total requests: {}.
                 }
             }
         }
-        if let Err(_e) = listener.await {
-            log!(stop, 0, "{}", _e);
+        match listener.await {
+            Ok(Ok(())) => {}
+            Ok(Err(_e)) => log!(stop, 0, "{}", _e),
+            Err(_e) => log!(stop, 0, "{}", _e),
         }
     }
 }
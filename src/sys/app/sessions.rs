@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use tokio::runtime::Builder;
+
+#[cfg(feature = "session-db")]
+use crate::sys::db::adapter::DB;
+
+use crate::{log, sys::web::session::SessionGc};
+
+use super::init::Init;
+
+/// Commands of the sessions
+pub(crate) struct Sessions;
+
+impl Sessions {
+    /// Remove the expired sessions now, the server may run at the same time
+    pub(crate) fn gc(init: Init) -> Result<(), ()> {
+        let runtime = match Builder::new_current_thread().enable_all().build() {
+            Ok(runtime) => runtime,
+            Err(_e) => {
+                log!(stop, 0, "{}", _e);
+                return Err(());
+            }
+        };
+        runtime.block_on(async move {
+            #[cfg(feature = "session-db")]
+            let db = Arc::new(DB::new(Arc::clone(&init.db)).await?);
+            let gc = SessionGc::new(
+                init.web.session_gc,
+                #[cfg(feature = "session-file")]
+                Arc::clone(&init.web.session_path),
+                #[cfg(feature = "session-db")]
+                db,
+            );
+            let stat = gc.run().await;
+            println!("{}", stat);
+            log!(info, 0, "{}", stat);
+            if stat.errors > 0 {
+                return Err(());
+            }
+            Ok(())
+        })
+    }
+}
@@ -1,6 +1,14 @@
-use std::sync::Arc;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::{Mutex, MutexGuard};
 
-use tokio::sync::{Mutex, Semaphore};
+#[cfg(feature = "pgsql")]
+use tokio::sync::{OwnedMutexGuard, OwnedSemaphorePermit};
 
 #[cfg(all(
     feature = "pgsql",
@@ -28,55 +36,182 @@ use tiberius::Row;
 
 use crate::{
     log,
-    sys::app::init::{AutoCount, DBConfig},
+    sys::{
+        app::init::{AutoCount, DBConfig},
+        stat::{limit::AdaptiveLimit, pool::PoolMetrics},
+    },
+    tool::paginator::Paginator,
 };
 
 #[cfg(feature = "pgsql")]
-use super::pgsql::{DataRow, PgSql, QueryParam, QueryStream};
+use crate::sys::stat::limit::LimitPermit;
+
+#[cfg(feature = "row-data")]
+use crate::sys::web::data::Data;
+
+#[cfg(feature = "otel")]
+use crate::sys::otel::Span;
+
+#[cfg(feature = "profile")]
+use crate::sys::profile::Frame;
+
+#[cfg(feature = "pgsql")]
+use super::pgsql::{DataRow, Param, PgSql, QueryCursor, QueryParam, QueryStream, Transaction, CURSOR_NAME};
 
 #[cfg(feature = "mssql")]
-use super::mssql::{DataRow, MsSql, QueryParam};
+use super::mssql::{DataRow, MsSql, Param, QueryParam};
 
-#[cfg(feature = "row-data")]
+#[cfg(all(feature = "row-data", feature = "pgsql"))]
 use super::pgsql::PgColumn;
 
+use super::{
+    builder::{Select, Sql, Update},
+    error::DBError,
+};
+
+/// Connection of the pool
+#[cfg(feature = "pgsql")]
+type Connection = PgSql;
+/// Connection of the pool
+#[cfg(feature = "mssql")]
+type Connection = MsSql;
+
 /// Pool of database connections for asynchronous work.
 ///
 /// # Values
 ///
-/// * `connections: Vec<Arc<Mutex<DB>>>` - Vector of database connections;
-/// * `semaphore: Arc<Semaphore>` - Semaphore for finding free connection;
+/// * `connections: Vec<Arc<Mutex<Connection>>>` - Vector of database connections;
+/// * `pool: Arc<PoolMetrics>` - Semaphore for finding free connection with the metrics of the pool;
+/// * `limit: Arc<AdaptiveLimit>` - Adaptive concurrency limit by the latency of the queries;
+/// * `slow: Mutex<VecDeque<SlowQuery>>` - Last slow queries;
+/// * `notify: Option<String>` - Channel of the invalidation of the cache of all nodes;
 /// * `size: usize` - Number of connected databases.
 #[derive(Debug)]
 pub struct DB {
-    /// Vector of database connections.
-    #[cfg(feature = "pgsql")]
-    connections: Vec<Arc<Mutex<PgSql>>>,
-    /// Vector of database connections.
-    #[cfg(feature = "mssql")]
-    connections: Vec<Arc<Mutex<MsSql>>>,
-    /// Semaphore for finding free connection.
-    semaphore: Arc<Semaphore>,
+    /// Vector of database connections, the first `min` are opened at the start, the others on demand.
+    connections: Vec<Arc<Mutex<Connection>>>,
+    /// Semaphore for finding free connection with the metrics of the pool.
+    pub(crate) pool: Arc<PoolMetrics>,
+    /// Adaptive concurrency limit of the database.
+    pub(crate) limit: Arc<AdaptiveLimit>,
+    /// Last slow queries, the newest is the first.
+    slow: std::sync::Mutex<VecDeque<SlowQuery>>,
+    /// Channel of the invalidation of the cache of all nodes, see `DB::notify_cache`.
+    #[cfg(feature = "cache")]
+    pub(crate) notify: Option<String>,
+}
+
+/// Query slower than `DB::SLOW_QUERY`
+#[derive(Debug, Clone)]
+pub struct SlowQuery {
+    /// Text of the query
+    pub query: String,
+    pub time: Duration,
+    /// End of the query
+    pub at: DateTime<Utc>,
+}
+
+/// Rows of the page and the pages of the query, see `DB::paginate`
+#[derive(Debug)]
+pub struct Page {
+    pub rows: Vec<DataRow>,
+    pub paginator: Paginator,
 }
 
 impl DB {
+    /// Time of the slow query
+    const SLOW_QUERY: Duration = Duration::from_millis(500);
+    /// Number of the remembered slow queries
+    const SLOW_COUNT: usize = 20;
+    /// Names of the prepared statements of the library
+    const STATEMENTS: [&'static str; 16] = [
+        "lib_add_redirect",
+        "lib_add_session",
+        "lib_get_all_langs",
+        "lib_get_auth",
+        "lib_get_langs",
+        "lib_get_permission",
+        "lib_get_redirect",
+        "lib_get_redirect_all",
+        "lib_get_route",
+        "lib_get_route_all",
+        "lib_get_route_pattern",
+        "lib_get_session",
+        "lib_get_setting",
+        "lib_get_url",
+        "lib_mail_add",
+        "lib_set_session",
+    ];
+
     /// Initialize pool of database connections for asynchronous work.
     pub(crate) async fn new(config: Arc<DBConfig>) -> Result<DB, ()> {
+        DB::open(config, true).await
+    }
+
+    /// Pool of the server, the connection is retried during [db] wait with the delay from 0.5 to 10 seconds
+    pub(crate) async fn start(config: Arc<DBConfig>) -> Result<DB, ()> {
+        let end = match config.wait {
+            Some(wait) => Instant::now() + wait,
+            None => return DB::new(config).await,
+        };
+        let mut delay = Duration::from_millis(500);
+        loop {
+            if let Ok(db) = DB::new(Arc::clone(&config)).await {
+                return Ok(db);
+            }
+            let left = end.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                log!(stop, 0, "{}", "Немає з'єднання з базою даних після [db] wait");
+                return Err(());
+            }
+            log!(warning, 0, "Немає з'єднання з базою даних, повтор через {:?}", delay.min(left));
+            tokio::time::sleep(delay.min(left)).await;
+            delay = (delay * 2).min(Duration::from_secs(10));
+        }
+    }
+
+    /// Pool without the prepared statements of the library, for the migrations of the empty database
+    pub(crate) async fn without_statements(config: Arc<DBConfig>) -> Result<DB, ()> {
+        DB::open(config, false).await
+    }
+
+    async fn open(config: Arc<DBConfig>, statements: bool) -> Result<DB, ()> {
         let size = match config.max {
             AutoCount::Auto => 3 * num_cpus::get(),
             AutoCount::Count(max) => max,
         };
+        let min = config.min.map_or(size, |min| min.min(size));
         let mut connections = Vec::with_capacity(size);
         let mut list = Vec::with_capacity(size);
 
-        for _ in 0..size {
+        for index in 0..size {
             let config = Arc::clone(&config);
             let handle = tokio::spawn(async move {
                 #[cfg(feature = "pgsql")]
                 let mut db = PgSql::new(config)?;
                 #[cfg(feature = "mssql")]
                 let mut db = MsSql::new(Arc::clone(&config))?;
-                if db.connect().await {
+                #[cfg(any(
+                    feature = "session-db",
+                    feature = "redirect-db",
+                    feature = "route-db",
+                    feature = "access-db",
+                    feature = "setting-db",
+                    feature = "mail-db"
+                ))]
+                if !statements {
+                    db.without_statements();
+                }
+                #[cfg(not(any(
+                    feature = "session-db",
+                    feature = "redirect-db",
+                    feature = "route-db",
+                    feature = "access-db",
+                    feature = "setting-db",
+                    feature = "mail-db"
+                )))]
+                let _ = statements;
+                if index >= min || db.connect().await {
                     Some(db)
                 } else {
                     None
@@ -97,107 +232,486 @@ impl DB {
             };
             connections.push(db);
         }
-        let semaphore = Arc::new(Semaphore::new(size));
+        let pool = PoolMetrics::new(size, min, config.timeout, &DB::STATEMENTS);
+        if let Some(interval) = config.health {
+            tokio::spawn(DB::health(connections.iter().map(Arc::downgrade).collect(), Arc::clone(&pool), interval));
+        }
+        let limit = AdaptiveLimit::new("db", size);
 
-        Ok(DB { connections, semaphore })
+        Ok(DB {
+            connections,
+            pool,
+            limit,
+            slow: std::sync::Mutex::new(VecDeque::with_capacity(DB::SLOW_COUNT)),
+            #[cfg(feature = "cache")]
+            notify: config.notify.clone(),
+        })
+    }
+
+    /// Free connection, the closed connection is opened
+    ///
+    /// The open connections are used first.
+    async fn free(&self) -> Option<MutexGuard<'_, Connection>> {
+        let mut closed = None;
+        for connection in &self.connections {
+            if let Ok(db) = connection.try_lock() {
+                if db.is_open() {
+                    return Some(db);
+                }
+                if closed.is_none() {
+                    closed = Some(db);
+                }
+            }
+        }
+        let mut db = closed?;
+        db.connect().await.then_some(db)
+    }
+
+    /// Free connection without the lifetime, the closed connection is opened
+    #[cfg(feature = "pgsql")]
+    async fn free_owned(&self) -> Option<OwnedMutexGuard<Connection>> {
+        let mut closed = None;
+        for connection in &self.connections {
+            if let Ok(db) = Arc::clone(connection).try_lock_owned() {
+                if db.is_open() {
+                    return Some(db);
+                }
+                if closed.is_none() {
+                    closed = Some(db);
+                }
+            }
+        }
+        let mut db = closed?;
+        db.connect().await.then_some(db)
+    }
+
+    /// Health check of the free connections
+    ///
+    /// The broken connections are closed, the first `min` connections are opened again.
+    async fn health(connections: Vec<Weak<Mutex<Connection>>>, pool: Arc<PoolMetrics>, interval: Duration) {
+        let mut timer = tokio::time::interval(interval);
+        timer.tick().await;
+        loop {
+            timer.tick().await;
+            let mut open = 0;
+            let mut broken = 0;
+            for (index, connection) in connections.iter().enumerate() {
+                let connection = match connection.upgrade() {
+                    Some(connection) => connection,
+                    None => return,
+                };
+                // The permit keeps the free connection for the query with the permit
+                let permit = match pool.try_acquire() {
+                    Some(permit) => permit,
+                    None => {
+                        open += 1;
+                        continue;
+                    }
+                };
+                // The busy connection is checked by its query
+                let mut db = match connection.try_lock() {
+                    Ok(db) => db,
+                    Err(_) => {
+                        open += 1;
+                        continue;
+                    }
+                };
+                if db.is_open() && !db.ping().await {
+                    broken += 1;
+                    db.close();
+                }
+                if !db.is_open() && index < pool.min() {
+                    db.connect().await;
+                }
+                if db.is_open() {
+                    open += 1;
+                }
+                drop(db);
+                drop(permit);
+            }
+            pool.checked(open, broken);
+        }
     }
 
     /// Execute query to database
     #[cfg(feature = "row-native")]
     pub async fn query(&self, query: &str, params: QueryParam<'_>) -> Option<Vec<DataRow>> {
-        let permit = match self.semaphore.acquire().await {
+        self.try_query(query, params).await.ok()
+    }
+
+    /// Execute query to database, the error has the kind for the message to the user
+    #[cfg(feature = "row-native")]
+    pub async fn try_query(&self, query: &str, params: QueryParam<'_>) -> Result<Vec<DataRow>, DBError> {
+        #[cfg(feature = "otel")]
+        let mut span = DB::span("db.query", query);
+        #[cfg(feature = "profile")]
+        let _frame = Frame::start("db.query");
+        let (mut limit, permit) = match self.pool.acquire(&self.limit).await {
             Ok(p) => p,
-            Err(_e) => {
-                log!(warning, 0, "{}", _e);
-                return None;
+            Err(e) => {
+                log!(warning, 0, "{}", e);
+                return Err(e.into());
             }
         };
-        for connection_mutex in &self.connections {
-            if let Ok(mut db) = connection_mutex.try_lock() {
-                let res = db.query(query, params).await;
-                drop(db);
-                drop(permit);
-                return res;
-            };
+        if let Some(mut db) = self.free().await {
+            let start = Instant::now();
+            let res = db.try_query(query, params).await;
+            self.slow(query, start.elapsed());
+            limit.done(res.is_ok());
+            #[cfg(feature = "otel")]
+            if let Err(e) = &res {
+                span.error(&e.message);
+            }
+            drop(db);
+            drop(permit);
+            return res;
         }
         drop(permit);
+        limit.done(false);
         log!(warning, 0);
-        None
+        Err(DBError::connection())
     }
 
     #[cfg(feature = "row-data")]
     pub async fn query(&self, query: &str, params: QueryParam<'_>, assoc: bool) -> Option<Vec<DataRow>> {
-        let permit = match self.semaphore.acquire().await {
+        self.try_query(query, params, assoc).await.ok()
+    }
+
+    /// Execute query to database, the error has the kind for the message to the user
+    #[cfg(feature = "row-data")]
+    pub async fn try_query(&self, query: &str, params: QueryParam<'_>, assoc: bool) -> Result<Vec<DataRow>, DBError> {
+        #[cfg(feature = "otel")]
+        let mut span = DB::span("db.query", query);
+        #[cfg(feature = "profile")]
+        let _frame = Frame::start("db.query");
+        let (mut limit, permit) = match self.pool.acquire(&self.limit).await {
+            Ok(p) => p,
+            Err(e) => {
+                log!(warning, 0, "{}", e);
+                return Err(e.into());
+            }
+        };
+        if let Some(mut db) = self.free().await {
+            let start = Instant::now();
+            let res = db.try_query(query, params, assoc).await;
+            self.slow(query, start.elapsed());
+            limit.done(res.is_ok());
+            #[cfg(feature = "otel")]
+            if let Err(e) = &res {
+                span.error(&e.message);
+            }
+            drop(db);
+            drop(permit);
+            return res;
+        }
+        drop(permit);
+        limit.done(false);
+        log!(warning, 0);
+        Err(DBError::connection())
+    }
+
+    /// Remember the query if it is slow
+    fn slow(&self, query: &str, time: Duration) {
+        if time < DB::SLOW_QUERY {
+            return;
+        }
+        let mut list = match self.slow.lock() {
+            Ok(list) => list,
+            Err(e) => e.into_inner(),
+        };
+        if list.len() == DB::SLOW_COUNT {
+            list.pop_back();
+        }
+        list.push_front(SlowQuery {
+            query: query.to_owned(),
+            time,
+            at: Utc::now(),
+        });
+    }
+
+    /// Last slow queries, the newest is the first
+    pub fn slow_queries(&self) -> Vec<SlowQuery> {
+        match self.slow.lock() {
+            Ok(list) => list.iter().cloned().collect(),
+            Err(e) => e.into_inner().iter().cloned().collect(),
+        }
+    }
+
+    /// SELECT query of the table, see `Select`
+    pub fn select(&self, table: &str) -> Select<'_> {
+        Select::new(self, table)
+    }
+
+    /// UPDATE query of the table, see `Update`
+    pub fn update(&self, table: &str) -> Update<'_> {
+        Update::new(self, table)
+    }
+
+    /// Execute query with the named parameters, for example `WHERE id = :id`
+    #[cfg(feature = "row-native")]
+    pub async fn query_named(&self, query: &str, params: &[(&str, Param<'_>)]) -> Option<Vec<DataRow>> {
+        self.try_query_named(query, params).await.ok()
+    }
+
+    /// Execute query with the named parameters, the error has the kind for the message to the user
+    ///
+    /// ```ignore
+    /// let rows = this.db.try_query_named("SELECT id FROM users WHERE login = :login OR email = :login", &[("login", &login)]).await?;
+    /// ```
+    #[cfg(feature = "row-native")]
+    pub async fn try_query_named(&self, query: &str, params: &[(&str, Param<'_>)]) -> Result<Vec<DataRow>, DBError> {
+        let (query, params) = Sql::named(query, params)?;
+        self.try_query(&query, &params).await
+    }
+
+    /// Execute query with the named parameters, for example `WHERE id = :id`
+    #[cfg(feature = "row-data")]
+    pub async fn query_named(&self, query: &str, params: &[(&str, Param<'_>)], assoc: bool) -> Option<Vec<DataRow>> {
+        self.try_query_named(query, params, assoc).await.ok()
+    }
+
+    /// Execute query with the named parameters, the error has the kind for the message to the user
+    ///
+    /// ```ignore
+    /// let rows = this.db.try_query_named("SELECT id FROM users WHERE login = :login OR email = :login", &[("login", &login)], false).await?;
+    /// ```
+    #[cfg(feature = "row-data")]
+    pub async fn try_query_named(&self, query: &str, params: &[(&str, Param<'_>)], assoc: bool) -> Result<Vec<DataRow>, DBError> {
+        let (query, params) = Sql::named(query, params)?;
+        self.try_query(&query, &params, assoc).await
+    }
+
+    /// Execute query with the named parameters without the result
+    pub async fn execute_named(&self, query: &str, params: &[(&str, Param<'_>)]) -> Option<()> {
+        self.try_execute_named(query, params).await.ok()
+    }
+
+    /// Execute query with the named parameters without the result, the error has the kind for the message to the user
+    pub async fn try_execute_named(&self, query: &str, params: &[(&str, Param<'_>)]) -> Result<(), DBError> {
+        let (query, params) = Sql::named(query, params)?;
+        self.try_execute(&query, &params).await
+    }
+
+    /// Span of the query, the statement is the text or the key of the prepared statement
+    #[cfg(feature = "otel")]
+    fn span(name: &str, statement: &str) -> Span {
+        let mut span = Span::client(name);
+        #[cfg(feature = "pgsql")]
+        span.attr("db.system", "postgresql");
+        #[cfg(feature = "mssql")]
+        span.attr("db.system", "mssql");
+        span.attr("db.statement", statement);
+        span
+    }
+
+    /// Rows of the page `page` from 1 and the total number of the rows of the query
+    ///
+    /// The query is without LIMIT and OFFSET, for MS SQL the query must have ORDER BY.
+    /// The total number is counted by the second query with the same parameters.
+    #[cfg(feature = "row-native")]
+    pub async fn paginate(&self, query: &str, params: QueryParam<'_>, page: u64, per_page: u64) -> Option<Page> {
+        let rows = self.query(&DB::count_query(query), params).await?;
+        #[cfg(feature = "pgsql")]
+        let total = rows.first().and_then(|row| row.try_get::<_, i64>(0).ok()).unwrap_or(0);
+        #[cfg(feature = "mssql")]
+        let total = rows.first().and_then(|row| row.try_get::<i64, _>(0).ok().flatten()).unwrap_or(0);
+        let paginator = Paginator::new(page, per_page, u64::try_from(total).unwrap_or(0));
+        let rows =
+            if paginator.offset() < paginator.total { self.query(&DB::page_query(query, &paginator), params).await? } else { Vec::new() };
+        Some(Page { rows, paginator })
+    }
+
+    /// Rows of the page `page` from 1 and the total number of the rows of the query
+    ///
+    /// The query is without LIMIT and OFFSET, for MS SQL the query must have ORDER BY.
+    /// The total number is counted by the second query with the same parameters.
+    #[cfg(feature = "row-data")]
+    pub async fn paginate(&self, query: &str, params: QueryParam<'_>, page: u64, per_page: u64, assoc: bool) -> Option<Page> {
+        let rows = self.query(&DB::count_query(query), params, false).await?;
+        let total = match rows.first() {
+            Some(Data::Vec(row)) => match row.first() {
+                Some(Data::I64(total)) => *total,
+                _ => 0,
+            },
+            _ => 0,
+        };
+        let paginator = Paginator::new(page, per_page, u64::try_from(total).unwrap_or(0));
+        let rows = if paginator.offset() < paginator.total {
+            self.query(&DB::page_query(query, &paginator), params, assoc).await?
+        } else {
+            Vec::new()
+        };
+        Some(Page { rows, paginator })
+    }
+
+    #[cfg(feature = "pgsql")]
+    fn count_query(query: &str) -> String {
+        format!("SELECT COUNT(*) FROM ({}) AS page_count", query.trim_end().trim_end_matches(';'))
+    }
+
+    /// ORDER BY of the subquery is allowed only with OFFSET
+    #[cfg(feature = "mssql")]
+    fn count_query(query: &str) -> String {
+        format!("SELECT COUNT_BIG(*) FROM ({} OFFSET 0 ROWS) AS page_count", query.trim_end().trim_end_matches(';'))
+    }
+
+    #[cfg(feature = "pgsql")]
+    fn page_query(query: &str, paginator: &Paginator) -> String {
+        format!("{} LIMIT {} OFFSET {}", query.trim_end().trim_end_matches(';'), paginator.per_page, paginator.offset())
+    }
+
+    #[cfg(feature = "mssql")]
+    fn page_query(query: &str, paginator: &Paginator) -> String {
+        format!("{} OFFSET {} ROWS FETCH NEXT {} ROWS ONLY", query.trim_end().trim_end_matches(';'), paginator.offset(), paginator.per_page)
+    }
+
+    /// Cursor of the query, fetches `batch` rows from the server at a time
+    ///
+    /// For the exports and the batch jobs with millions of rows, the memory does not depend on the number of rows.
+    /// The connection is held in the transaction until the cursor is dropped.
+    #[cfg(all(feature = "row-native", feature = "pgsql"))]
+    pub async fn query_cursor(&self, query: &str, params: QueryParam<'_>, batch: usize) -> Option<QueryCursor> {
+        let (permit, limit, db) = self.cursor_open(query, params).await?;
+        Some(QueryCursor {
+            permit: Some(permit),
+            limit,
+            db: Some(db),
+            fetch: format!("FETCH {} FROM {}", batch.max(1), CURSOR_NAME),
+            batch: batch.max(1),
+            rows: VecDeque::new(),
+            done: false,
+        })
+    }
+
+    /// Cursor of the query, fetches `batch` rows from the server at a time
+    ///
+    /// For the exports and the batch jobs with millions of rows, the memory does not depend on the number of rows.
+    /// The connection is held in the transaction until the cursor is dropped.
+    #[cfg(all(feature = "row-data", feature = "pgsql"))]
+    pub async fn query_cursor(&self, query: &str, params: QueryParam<'_>, batch: usize, assoc: bool) -> Option<QueryCursor> {
+        let (permit, limit, db) = self.cursor_open(query, params).await?;
+        Some(QueryCursor {
+            permit: Some(permit),
+            limit,
+            db: Some(db),
+            fetch: format!("FETCH {} FROM {}", batch.max(1), CURSOR_NAME),
+            batch: batch.max(1),
+            rows: VecDeque::new(),
+            done: false,
+            cols: if assoc { PgColumn::Map(None) } else { PgColumn::Vec(None) },
+        })
+    }
+
+    #[cfg(feature = "pgsql")]
+    async fn cursor_open(
+        &self,
+        query: &str,
+        params: QueryParam<'_>,
+    ) -> Option<(OwnedSemaphorePermit, LimitPermit, OwnedMutexGuard<PgSql>)> {
+        let (permit, mut limit, mut db) = self.hold().await?;
+        let res = db.cursor_open(query, params).await;
+        limit.done(res);
+        res.then_some((permit, limit, db))
+    }
+
+    /// Begin the transaction, the connection is held until the commit or the rollback
+    ///
+    /// The nested `begin` of the transaction is the savepoint, so the function that takes `&mut Transaction`
+    /// works the same in the new transaction and inside the transaction of the caller.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut tx = this.db.begin().await?;
+    /// tx.execute("UPDATE account SET balance=balance-$1 WHERE id=$2", &[&sum, &from]).await?;
+    /// save_history(&mut tx, from, sum).await?;
+    /// tx.commit().await?;
+    /// ```
+    #[cfg(feature = "pgsql")]
+    pub async fn begin(&self) -> Option<Transaction> {
+        let (permit, mut limit, mut db) = self.hold().await?;
+        let res = db.begin().await;
+        limit.done(res);
+        res.then(|| Transaction {
+            permit: Some(permit),
+            limit: Some(limit),
+            db: Some(db),
+            depth: 0,
+        })
+    }
+
+    /// Free connection, held by the cursor or the transaction
+    #[cfg(feature = "pgsql")]
+    async fn hold(&self) -> Option<(OwnedSemaphorePermit, LimitPermit, OwnedMutexGuard<PgSql>)> {
+        let (mut limit, permit) = match self.pool.acquire_owned(&self.limit).await {
             Ok(p) => p,
             Err(_e) => {
                 log!(warning, 0, "{}", _e);
                 return None;
             }
         };
-        for connection_mutex in &self.connections {
-            if let Ok(mut db) = connection_mutex.try_lock() {
-                let res = db.query(query, params, assoc).await;
-                drop(db);
-                drop(permit);
-                return res;
-            };
+        if let Some(db) = self.free_owned().await {
+            return Some((permit, limit, db));
         }
         drop(permit);
+        limit.done(false);
         log!(warning, 0);
         None
     }
 
     #[cfg(all(feature = "row-native", not(feature = "mssql")))]
     pub async fn query_stream<'a>(&'a self, query: &str, params: QueryParam<'_>) -> Option<QueryStream<'a>> {
-        let permit = match self.semaphore.acquire().await {
+        let (mut limit, permit) = match self.pool.acquire(&self.limit).await {
             Ok(p) => p,
             Err(_e) => {
                 log!(warning, 0, "{}", _e);
                 return None;
             }
         };
-        for connection_mutex in &self.connections {
-            if let Ok(mut db) = connection_mutex.try_lock() {
-                if let Some(stream) = db.query_stream(query, params).await {
-                    return Some(QueryStream {
-                        permit,
-                        db,
-                        stream: Box::pin(stream),
-                        #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
-                        sql: query,
-                    });
-                }
-            };
+        if let Some(mut db) = self.free().await {
+            if let Some(stream) = db.query_stream(query, params).await {
+                limit.done(true);
+                return Some(QueryStream {
+                    permit,
+                    limit,
+                    db,
+                    stream: Box::pin(stream),
+                    #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+                    sql: query,
+                });
+            }
         }
         drop(permit);
+        limit.done(false);
         log!(warning, 0);
         None
     }
 
-    #[cfg(feature = "row-data")]
+    #[cfg(all(feature = "row-data", not(feature = "mssql")))]
     pub async fn query_stream<'a>(&'a self, query: &'a str, params: QueryParam<'_>, assoc: bool) -> Option<QueryStream<'a>> {
-        let permit = match self.semaphore.acquire().await {
+        let (mut limit, permit) = match self.pool.acquire(&self.limit).await {
             Ok(p) => p,
             Err(_e) => {
                 log!(warning, 0, "{}", _e);
                 return None;
             }
         };
-        for connection_mutex in &self.connections {
-            if let Ok(mut db) = connection_mutex.try_lock() {
-                if let Some(stream) = db.query_stream(query, params).await {
-                    return Some(QueryStream {
-                        permit,
-                        db,
-                        stream: Box::pin(stream),
-                        #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
-                        sql: query,
-                        cols: if assoc { PgColumn::Map(None) } else { PgColumn::Vec(None) },
-                    });
-                }
-            };
+        if let Some(mut db) = self.free().await {
+            if let Some(stream) = db.query_stream(query, params).await {
+                limit.done(true);
+                return Some(QueryStream {
+                    permit,
+                    limit,
+                    db,
+                    stream: Box::pin(stream),
+                    #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+                    sql: query,
+                    cols: if assoc { PgColumn::Map(None) } else { PgColumn::Vec(None) },
+                });
+            }
         }
         drop(permit);
+        limit.done(false);
         log!(warning, 0);
         None
     }
@@ -210,66 +724,101 @@ impl DB {
         feature = "setting-db",
     ))]
     pub(crate) async fn query_prepare<'a>(&self, query: i64, params: QueryParam<'a>) -> Option<Vec<Row>> {
-        let permit = match self.semaphore.acquire().await {
+        #[cfg(feature = "otel")]
+        let mut span = DB::span("db.query_prepare", &query.to_string());
+        #[cfg(feature = "profile")]
+        let _frame = Frame::start("db.query_prepare");
+        let (mut limit, permit) = match self.pool.acquire(&self.limit).await {
             Ok(p) => p,
             Err(_e) => {
                 log!(warning, 0, "{}", _e);
                 return None;
             }
         };
-        for connection_mutex in &self.connections {
-            if let Ok(mut db) = connection_mutex.try_lock() {
-                let res = db.query_prepare(query, params).await;
-                drop(db);
-                drop(permit);
-                return res;
-            };
+        if let Some(mut db) = self.free().await {
+            let start = Instant::now();
+            let res = db.query_prepare(query, params).await;
+            self.pool.statement(query, start.elapsed(), res.is_some());
+            limit.done(res.is_some());
+            #[cfg(feature = "otel")]
+            if res.is_none() {
+                span.error("The query failed");
+            }
+            drop(db);
+            drop(permit);
+            return res;
         }
         drop(permit);
+        limit.done(false);
         log!(warning, 0);
         None
     }
 
     /// Execute query to database synchronously without results
     pub async fn execute<'a>(&self, query: &str, params: QueryParam<'a>) -> Option<()> {
-        let permit = match self.semaphore.acquire().await {
+        self.try_execute(query, params).await.ok()
+    }
+
+    /// Execute query to database without results, the error has the kind for the message to the user
+    pub async fn try_execute(&self, query: &str, params: QueryParam<'_>) -> Result<(), DBError> {
+        #[cfg(feature = "otel")]
+        let mut span = DB::span("db.execute", query);
+        #[cfg(feature = "profile")]
+        let _frame = Frame::start("db.execute");
+        let (mut limit, permit) = match self.pool.acquire(&self.limit).await {
             Ok(p) => p,
-            Err(_e) => {
-                log!(warning, 0, "{}", _e);
-                return None;
+            Err(e) => {
+                log!(warning, 0, "{}", e);
+                return Err(e.into());
             }
         };
-        for connection_mutex in &self.connections {
-            if let Ok(mut db) = connection_mutex.try_lock() {
-                let res = db.execute(query, params).await;
-                drop(db);
-                drop(permit);
-                return res;
-            };
+        if let Some(mut db) = self.free().await {
+            let start = Instant::now();
+            let res = db.try_execute(query, params).await;
+            self.slow(query, start.elapsed());
+            limit.done(res.is_ok());
+            #[cfg(feature = "otel")]
+            if let Err(e) = &res {
+                span.error(&e.message);
+            }
+            drop(db);
+            drop(permit);
+            return res;
         }
         drop(permit);
+        limit.done(false);
         log!(warning, 0);
-        None
+        Err(DBError::connection())
     }
 
-    #[cfg(any(feature = "session-db", feature = "mail-db"))]
+    #[cfg(any(feature = "session-db", feature = "mail-db", feature = "redirect-db"))]
     pub(crate) async fn execute_prepare<'a>(&self, query: i64, params: QueryParam<'a>) -> Option<()> {
-        let permit = match self.semaphore.acquire().await {
+        #[cfg(feature = "otel")]
+        let mut span = DB::span("db.execute_prepare", &query.to_string());
+        #[cfg(feature = "profile")]
+        let _frame = Frame::start("db.execute_prepare");
+        let (mut limit, permit) = match self.pool.acquire(&self.limit).await {
             Ok(p) => p,
             Err(_e) => {
                 log!(warning, 0, "{}", _e);
                 return None;
             }
         };
-        for connection_mutex in &self.connections {
-            if let Ok(mut db) = connection_mutex.try_lock() {
-                let res = db.execute_prepare(query, params).await;
-                drop(db);
-                drop(permit);
-                return res;
-            };
+        if let Some(mut db) = self.free().await {
+            let start = Instant::now();
+            let res = db.execute_prepare(query, params).await;
+            self.pool.statement(query, start.elapsed(), res.is_some());
+            limit.done(res.is_some());
+            #[cfg(feature = "otel")]
+            if res.is_none() {
+                span.error("The query failed");
+            }
+            drop(db);
+            drop(permit);
+            return res;
         }
         drop(permit);
+        limit.done(false);
         log!(warning, 0);
         None
     }
@@ -0,0 +1,378 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "mssql")]
+use super::mssql::{DataRow, Param};
+#[cfg(feature = "pgsql")]
+use super::pgsql::{DataRow, Param};
+
+use super::{
+    adapter::DB,
+    error::{DBError, DBErrorKind},
+};
+
+/// Comparison of the column with the parameter, see `Select::where_cmp`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Like,
+}
+
+impl Cmp {
+    fn sql(self) -> &'static str {
+        match self {
+            Cmp::Eq => "=",
+            Cmp::Ne => "<>",
+            Cmp::Lt => "<",
+            Cmp::Le => "<=",
+            Cmp::Gt => ">",
+            Cmp::Ge => ">=",
+            Cmp::Like => "LIKE",
+        }
+    }
+}
+
+/// SELECT query of the one table
+///
+/// # Example
+///
+/// ```ignore
+/// let rows = this.db.select("users").columns(&["id", "name"]).where_eq("id", &id).order_by("name", false).limit(10).query().await?;
+/// ```
+///
+/// The names of the table and the columns are quoted, the values are always the parameters of the query.
+pub struct Select<'a> {
+    db: &'a DB,
+    table: String,
+    columns: Vec<String>,
+    filter: Vec<String>,
+    params: Vec<Param<'a>>,
+    order: Vec<String>,
+    limit: Option<u64>,
+    offset: Option<u64>,
+    #[cfg(feature = "row-data")]
+    assoc: bool,
+}
+
+impl<'a> Select<'a> {
+    pub(crate) fn new(db: &'a DB, table: &str) -> Select<'a> {
+        Select {
+            db,
+            table: Sql::name(table),
+            columns: Vec::new(),
+            filter: Vec::new(),
+            params: Vec::new(),
+            order: Vec::new(),
+            limit: None,
+            offset: None,
+            #[cfg(feature = "row-data")]
+            assoc: false,
+        }
+    }
+
+    /// Columns of the result, all columns by default
+    pub fn columns(mut self, columns: &[&str]) -> Select<'a> {
+        self.columns.extend(columns.iter().map(|column| Sql::name(column)));
+        self
+    }
+
+    /// Column is equal to the value
+    pub fn where_eq(self, column: &str, value: Param<'a>) -> Select<'a> {
+        self.where_cmp(column, Cmp::Eq, value)
+    }
+
+    /// Comparison of the column with the value, all conditions are joined by AND
+    pub fn where_cmp(mut self, column: &str, cmp: Cmp, value: Param<'a>) -> Select<'a> {
+        self.params.push(value);
+        self.filter.push(format!("{} {} {}", Sql::name(column), cmp.sql(), Sql::param(self.params.len())));
+        self
+    }
+
+    /// Column is one of the values, the empty list selects nothing
+    pub fn where_in(mut self, column: &str, values: &[Param<'a>]) -> Select<'a> {
+        if values.is_empty() {
+            self.filter.push("1 = 0".to_owned());
+            return self;
+        }
+        let mut list = Vec::with_capacity(values.len());
+        for value in values {
+            self.params.push(*value);
+            list.push(Sql::param(self.params.len()));
+        }
+        self.filter.push(format!("{} IN ({})", Sql::name(column), list.join(", ")));
+        self
+    }
+
+    /// Column is NULL
+    pub fn where_null(mut self, column: &str) -> Select<'a> {
+        self.filter.push(format!("{} IS NULL", Sql::name(column)));
+        self
+    }
+
+    /// Column is not NULL
+    pub fn where_not_null(mut self, column: &str) -> Select<'a> {
+        self.filter.push(format!("{} IS NOT NULL", Sql::name(column)));
+        self
+    }
+
+    /// Sort by the column, the calls are added in order
+    pub fn order_by(mut self, column: &str, desc: bool) -> Select<'a> {
+        self.order.push(format!("{}{}", Sql::name(column), if desc { " DESC" } else { "" }));
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Select<'a> {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: u64) -> Select<'a> {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Rows are the maps with the names of the columns
+    #[cfg(feature = "row-data")]
+    pub fn assoc(mut self) -> Select<'a> {
+        self.assoc = true;
+        self
+    }
+
+    /// Text of the query
+    pub fn sql(&self) -> String {
+        let mut sql =
+            format!("SELECT {} FROM {}", if self.columns.is_empty() { "*".to_owned() } else { self.columns.join(", ") }, self.table);
+        if !self.filter.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&self.filter.join(" AND "));
+        }
+        if !self.order.is_empty() {
+            sql.push_str(" ORDER BY ");
+            sql.push_str(&self.order.join(", "));
+        }
+        #[cfg(feature = "pgsql")]
+        {
+            if let Some(limit) = self.limit {
+                sql.push_str(&format!(" LIMIT {}", limit));
+            }
+            if let Some(offset) = self.offset {
+                sql.push_str(&format!(" OFFSET {}", offset));
+            }
+        }
+        // OFFSET FETCH is allowed only after ORDER BY
+        #[cfg(feature = "mssql")]
+        if self.limit.is_some() || self.offset.is_some() {
+            if self.order.is_empty() {
+                sql.push_str(" ORDER BY (SELECT NULL)");
+            }
+            sql.push_str(&format!(" OFFSET {} ROWS", self.offset.unwrap_or(0)));
+            if let Some(limit) = self.limit {
+                sql.push_str(&format!(" FETCH NEXT {} ROWS ONLY", limit));
+            }
+        }
+        sql
+    }
+
+    /// Execute the query
+    pub async fn query(self) -> Option<Vec<DataRow>> {
+        self.try_query().await.ok()
+    }
+
+    /// Execute the query, the error has the kind for the message to the user
+    pub async fn try_query(self) -> Result<Vec<DataRow>, DBError> {
+        let sql = self.sql();
+        #[cfg(feature = "row-data")]
+        return self.db.try_query(&sql, &self.params, self.assoc).await;
+        #[cfg(feature = "row-native")]
+        return self.db.try_query(&sql, &self.params).await;
+    }
+}
+
+/// UPDATE query of the one table with the optimistic locking by the version column
+///
+/// # Example
+///
+/// ```ignore
+/// let count = this.db.update("article").set("title", &title).where_eq("id", &id).version("version", &version).try_execute().await?;
+/// if count == 0 {
+///     return this.precondition_failed();
+/// }
+/// ```
+///
+/// With `version` the column is increased by 1 and the row is updated only with the same version,
+/// so 0 updated rows is the row changed by another request or the missing row.
+pub struct Update<'a> {
+    db: &'a DB,
+    table: String,
+    set: Vec<String>,
+    filter: Vec<String>,
+    params: Vec<Param<'a>>,
+}
+
+impl<'a> Update<'a> {
+    pub(crate) fn new(db: &'a DB, table: &str) -> Update<'a> {
+        Update {
+            db,
+            table: Sql::name(table),
+            set: Vec::new(),
+            filter: Vec::new(),
+            params: Vec::new(),
+        }
+    }
+
+    /// New value of the column
+    pub fn set(mut self, column: &str, value: Param<'a>) -> Update<'a> {
+        self.params.push(value);
+        self.set.push(format!("{} = {}", Sql::name(column), Sql::param(self.params.len())));
+        self
+    }
+
+    /// Column is equal to the value
+    pub fn where_eq(self, column: &str, value: Param<'a>) -> Update<'a> {
+        self.where_cmp(column, Cmp::Eq, value)
+    }
+
+    /// Comparison of the column with the value, all conditions are joined by AND
+    pub fn where_cmp(mut self, column: &str, cmp: Cmp, value: Param<'a>) -> Update<'a> {
+        self.params.push(value);
+        self.filter.push(format!("{} {} {}", Sql::name(column), cmp.sql(), Sql::param(self.params.len())));
+        self
+    }
+
+    /// The row has the version, the version is increased by 1
+    pub fn version(mut self, column: &str, version: Param<'a>) -> Update<'a> {
+        let column = Sql::name(column);
+        self.set.push(format!("{0} = {0} + 1", column));
+        self.params.push(version);
+        self.filter.push(format!("{} = {}", column, Sql::param(self.params.len())));
+        self
+    }
+
+    /// Text of the query, the updated rows are returned for the count
+    pub fn sql(&self) -> String {
+        let mut sql = format!("UPDATE {} SET {}", self.table, self.set.join(", "));
+        #[cfg(feature = "mssql")]
+        sql.push_str(" OUTPUT 1");
+        if !self.filter.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&self.filter.join(" AND "));
+        }
+        #[cfg(feature = "pgsql")]
+        sql.push_str(" RETURNING 1");
+        sql
+    }
+
+    /// Execute the query, returns the number of the updated rows
+    pub async fn execute(self) -> Option<u64> {
+        self.try_execute().await.ok()
+    }
+
+    /// Execute the query, returns the number of the updated rows, the error has the kind for the message to the user
+    pub async fn try_execute(self) -> Result<u64, DBError> {
+        let sql = self.sql();
+        #[cfg(feature = "row-data")]
+        let rows = self.db.try_query(&sql, &self.params, false).await?;
+        #[cfg(feature = "row-native")]
+        let rows = self.db.try_query(&sql, &self.params).await?;
+        Ok(rows.len() as u64)
+    }
+}
+
+/// Text of the queries
+pub(crate) struct Sql;
+
+impl Sql {
+    /// Query with the named parameters ":name" to the query with the positional parameters
+    ///
+    /// The same name may be used several times. The names inside the strings, the quoted names and the comments are not replaced,
+    /// "::" is the cast of PostgreSQL.
+    pub(crate) fn named<'a>(query: &str, params: &[(&str, Param<'a>)]) -> Result<(String, Vec<Param<'a>>), DBError> {
+        let values: HashMap<&str, Param<'a>> = params.iter().copied().collect();
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        let mut list = Vec::with_capacity(params.len());
+        let mut sql = String::with_capacity(query.len());
+        let bytes = query.as_bytes();
+        // Start of the text without the parameters
+        let mut last = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            let end = match bytes[i] {
+                b'\'' => Sql::skip_to(bytes, i + 1, b"'"),
+                b'"' => Sql::skip_to(bytes, i + 1, b"\""),
+                #[cfg(feature = "mssql")]
+                b'[' => Sql::skip_to(bytes, i + 1, b"]"),
+                b'-' if bytes.get(i + 1) == Some(&b'-') => Sql::skip_to(bytes, i + 2, b"\n"),
+                b'/' if bytes.get(i + 1) == Some(&b'*') => Sql::skip_to(bytes, i + 2, b"*/"),
+                b':' if bytes.get(i + 1) == Some(&b':') => i + 2,
+                b':' if bytes.get(i + 1).is_some_and(|b| b.is_ascii_alphabetic() || *b == b'_') => {
+                    let start = i + 1;
+                    let mut end = start;
+                    while bytes.get(end).is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_') {
+                        end += 1;
+                    }
+                    let name = &query[start..end];
+                    let number = match index.get(name) {
+                        Some(number) => *number,
+                        None => {
+                            let value = values.get(name).ok_or_else(|| DBError {
+                                kind: DBErrorKind::Syntax,
+                                constraint: None,
+                                column: None,
+                                message: format!("Parameter :{} of the query is missing", name),
+                            })?;
+                            list.push(*value);
+                            index.insert(name, list.len());
+                            list.len()
+                        }
+                    };
+                    sql.push_str(&query[last..i]);
+                    sql.push_str(&Sql::param(number));
+                    last = end;
+                    end
+                }
+                _ => i + 1,
+            };
+            i = end;
+        }
+        sql.push_str(&query[last..]);
+        Ok((sql, list))
+    }
+
+    /// Position after the end marker, or the end of the query
+    fn skip_to(bytes: &[u8], from: usize, marker: &[u8]) -> usize {
+        let mut i = from;
+        while i < bytes.len() {
+            if bytes[i..].starts_with(marker) {
+                return i + marker.len();
+            }
+            i += 1;
+        }
+        bytes.len()
+    }
+
+    /// Placeholder of the parameter from 1
+    fn param(number: usize) -> String {
+        #[cfg(feature = "pgsql")]
+        return format!("${}", number);
+        #[cfg(feature = "mssql")]
+        return format!("@P{}", number);
+    }
+
+    /// Quoted name, "schema.table" is quoted by the parts, "*" is not quoted
+    fn name(name: &str) -> String {
+        name.split('.')
+            .map(|part| match part {
+                "*" => "*".to_owned(),
+                #[cfg(feature = "pgsql")]
+                part => format!("\"{}\"", part.replace('"', "\"\"")),
+                #[cfg(feature = "mssql")]
+                part => format!("[{}]", part.replace(']', "]]")),
+            })
+            .collect::<Vec<String>>()
+            .join(".")
+    }
+}
@@ -0,0 +1,83 @@
+use std::fmt;
+
+use crate::sys::stat::pool::PoolError;
+
+/// Kind of the error of the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DBErrorKind {
+    /// Duplicate of the unique key or the primary key
+    Unique,
+    /// Violation of the foreign key
+    ForeignKey,
+    /// NULL in the column NOT NULL
+    NotNull,
+    /// Violation of the CHECK constraint
+    Check,
+    /// Invalid value of the parameter, for example the text in the number column
+    InvalidData,
+    /// Deadlock or the serialization failure, the transaction can be repeated
+    Conflict,
+    /// Statement timeout or the lock timeout
+    Timeout,
+    /// Connection is lost or there is no free connection
+    Connection,
+    /// Syntax of the query, the unknown table or column, no privileges
+    Syntax,
+    Other,
+}
+
+/// Error of the query
+///
+/// # Example
+///
+/// ```ignore
+/// match this.db.try_execute("INSERT INTO users(login) VALUES ($1)", &[&login]).await {
+///     Ok(()) => {}
+///     Err(e) if e.kind == DBErrorKind::Unique => this.set("error", "Логін вже зайнятий".to_owned()),
+///     Err(_) => this.set_lang("db_error"),
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DBError {
+    pub kind: DBErrorKind,
+    /// Name of the constraint for Unique, ForeignKey and Check
+    pub constraint: Option<String>,
+    /// Name of the column for NotNull
+    pub column: Option<String>,
+    /// Text of the error from the server
+    pub message: String,
+}
+
+impl DBError {
+    /// No connection to the database
+    pub(crate) fn connection() -> DBError {
+        DBError {
+            kind: DBErrorKind::Connection,
+            constraint: None,
+            column: None,
+            message: "No connection to the database".to_owned(),
+        }
+    }
+}
+
+impl From<PoolError> for DBError {
+    fn from(error: PoolError) -> DBError {
+        match error {
+            PoolError::Closed => DBError::connection(),
+            PoolError::Timeout => DBError {
+                kind: DBErrorKind::Timeout,
+                constraint: None,
+                column: None,
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for DBError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for DBError {}
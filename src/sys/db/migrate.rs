@@ -0,0 +1,258 @@
+use std::{collections::HashMap, path::Path};
+
+use tokio::fs::{read_dir, read_to_string};
+
+use crate::{fnv1a_64, log};
+
+#[cfg(feature = "row-data")]
+use crate::sys::web::data::Data;
+
+use super::adapter::DB;
+
+/// Tracking table of the applied migrations
+#[cfg(feature = "pgsql")]
+const MIGRATION_TABLE: &str = "CREATE TABLE IF NOT EXISTS migration (
+    version int8 NOT NULL PRIMARY KEY,
+    name text NOT NULL,
+    checksum int8 NOT NULL,
+    applied timestamptz NOT NULL DEFAULT now()
+)";
+/// Tracking table of the applied migrations
+#[cfg(feature = "mssql")]
+const MIGRATION_TABLE: &str = "IF OBJECT_ID(N'migration', N'U') IS NULL CREATE TABLE [migration] (
+    [version] bigint NOT NULL PRIMARY KEY,
+    [name] nvarchar(255) NOT NULL,
+    [checksum] bigint NOT NULL,
+    [applied] datetimeoffset NOT NULL DEFAULT SYSDATETIMEOFFSET()
+)";
+
+/// The tracking table exists, for the dry run without changes
+#[cfg(feature = "pgsql")]
+const MIGRATION_EXISTS: &str =
+    "SELECT COUNT(*) FROM information_schema.tables WHERE table_schema=current_schema() AND table_name='migration'";
+/// The tracking table exists, for the dry run without changes
+#[cfg(feature = "mssql")]
+const MIGRATION_EXISTS: &str = "SELECT CAST(COUNT(*) AS bigint) FROM information_schema.tables WHERE table_name='migration'";
+
+#[cfg(feature = "pgsql")]
+const MIGRATION_APPLIED: &str = "SELECT version, checksum FROM migration ORDER BY version";
+#[cfg(feature = "mssql")]
+const MIGRATION_APPLIED: &str = "SELECT [version], [checksum] FROM [migration] ORDER BY [version]";
+
+#[cfg(feature = "pgsql")]
+const MIGRATION_ADD: &str = "INSERT INTO migration (version, name, checksum) VALUES ($1, $2, $3)";
+#[cfg(feature = "mssql")]
+const MIGRATION_ADD: &str = "INSERT INTO [migration] ([version], [name], [checksum]) VALUES (@P1, @P2, @P3)";
+
+#[cfg(feature = "pgsql")]
+const MIGRATION_REMOVE: &str = "DELETE FROM migration WHERE version=$1";
+#[cfg(feature = "mssql")]
+const MIGRATION_REMOVE: &str = "DELETE FROM [migration] WHERE [version]=@P1";
+
+/// Versioned SQL file of the migration
+///
+/// The name of the file is "<version>_<name>.sql", for example "0001_users.sql" or "20261014_mail.sql",
+/// the rollback is in the file "<version>_<name>.down.sql" near it.
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub version: i64,
+    pub name: String,
+    /// FNV-1a of the text of the file, the file changed after the applying is reported
+    pub checksum: i64,
+    /// Name of the file without the extension
+    file: String,
+    /// Script of the applying
+    up: String,
+    /// Script of the rollback
+    down: Option<String>,
+}
+
+impl Migration {
+    /// Name of the file without the extension
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// Migrations of the folder sorted by the version
+    async fn load(dir: &Path) -> Result<Vec<Migration>, String> {
+        let mut entries = read_dir(dir).await.map_err(|e| format!("Неможливо прочитати папку {}. Помилка: {}", dir.display(), e))?;
+        let mut up = HashMap::new();
+        let mut down = HashMap::new();
+        loop {
+            let entry = match entries.next_entry().await {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(e) => return Err(format!("Неможливо прочитати папку {}. Помилка: {}", dir.display(), e)),
+            };
+            let file = entry.file_name().to_string_lossy().to_string();
+            let (stem, list) = match file.strip_suffix(".down.sql") {
+                Some(stem) => (stem.to_owned(), &mut down),
+                None => match file.strip_suffix(".sql") {
+                    Some(stem) => (stem.to_owned(), &mut up),
+                    None => continue,
+                },
+            };
+            let (version, name) = stem.split_once('_').unwrap_or((&stem, ""));
+            let version = version
+                .parse::<i64>()
+                .map_err(|_| format!("Файл {} повинен мати назву <версія>_<назва>.sql, наприклад 0001_users.sql", file))?;
+            let text = read_to_string(entry.path()).await.map_err(|e| format!("Неможливо прочитати файл {}. Помилка: {}", file, e))?;
+            if list.insert(version, (name.to_owned(), stem.clone(), text)).is_some() {
+                return Err(format!("Версія {} файлу {} повторюється", version, file));
+            }
+        }
+        if let Some(version) = down.keys().find(|version| !up.contains_key(version)) {
+            return Err(format!("Файл відкату версії {} не має файлу міграції", version));
+        }
+        let mut list: Vec<Migration> = up
+            .into_iter()
+            .map(|(version, (name, file, up))| Migration {
+                version,
+                checksum: fnv1a_64(up.as_bytes()),
+                down: down.remove(&version).map(|(_, _, down)| down),
+                name,
+                file,
+                up,
+            })
+            .collect();
+        list.sort_by_key(|migration| migration.version);
+        Ok(list)
+    }
+}
+
+impl DB {
+    /// Apply the pending migrations of the folder in the order of the versions, each in its own transaction
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let applied = db.migrate(Path::new("migrations"), false).await?;
+    /// ```
+    ///
+    /// Returns the applied migrations, with `dry_run` the pending ones without any changes.
+    /// The error stops the applying, the previous migrations stay applied.
+    pub async fn migrate(&self, dir: &Path, dry_run: bool) -> Result<Vec<Migration>, String> {
+        let list = Migration::load(dir).await?;
+        let applied = self.applied(!dry_run).await?;
+        for migration in &list {
+            if matches!(applied.get(&migration.version), Some(checksum) if *checksum != migration.checksum) {
+                log!(warning, 0, "Migration {} is changed after the applying", migration.file());
+            }
+        }
+        let pending: Vec<Migration> = list.into_iter().filter(|migration| !applied.contains_key(&migration.version)).collect();
+        if dry_run {
+            return Ok(pending);
+        }
+        let mut done = Vec::with_capacity(pending.len());
+        for migration in pending {
+            self.apply(&migration.up, MIGRATION_ADD, &[&migration.version, &migration.name, &migration.checksum])
+                .await
+                .map_err(|e| format!("Міграція {} не виконана, виконано {}. Помилка: {}", migration.file(), done.len(), e))?;
+            done.push(migration);
+        }
+        Ok(done)
+    }
+
+    /// Roll back the last `steps` applied migrations by the files "*.down.sql", the last one first
+    ///
+    /// Nothing is rolled back if any of them has no file of the rollback.
+    /// Returns the rolled back migrations, with `dry_run` the ones to roll back without any changes.
+    pub async fn rollback(&self, dir: &Path, steps: usize, dry_run: bool) -> Result<Vec<Migration>, String> {
+        let mut list: HashMap<i64, Migration> = Migration::load(dir).await?.into_iter().map(|item| (item.version, item)).collect();
+        let mut applied: Vec<i64> = self.applied(!dry_run).await?.into_keys().collect();
+        applied.sort_unstable_by(|a, b| b.cmp(a));
+        let mut rollback = Vec::with_capacity(steps);
+        for version in applied.into_iter().take(steps) {
+            match list.remove(&version) {
+                Some(migration) if migration.down.is_some() => rollback.push(migration),
+                Some(migration) => return Err(format!("Міграція {} не має файлу відкату {}.down.sql", version, migration.file())),
+                None => return Err(format!("Міграція {} не має файлу", version)),
+            }
+        }
+        if dry_run {
+            return Ok(rollback);
+        }
+        let mut done = Vec::with_capacity(rollback.len());
+        for migration in rollback {
+            self.apply(migration.down.as_deref().unwrap_or_default(), MIGRATION_REMOVE, &[&migration.version])
+                .await
+                .map_err(|e| format!("Відкат {} не виконаний, відкочено {}. Помилка: {}", migration.file(), done.len(), e))?;
+            done.push(migration);
+        }
+        Ok(done)
+    }
+
+    /// Checksums of the applied versions, the tracking table is created if `create`
+    async fn applied(&self, create: bool) -> Result<HashMap<i64, i64>, String> {
+        if create {
+            self.try_execute(MIGRATION_TABLE, &[]).await.map_err(|e| e.to_string())?;
+        } else if self.numbers(MIGRATION_EXISTS).await?.first().and_then(|row| row.first()) == Some(&0) {
+            return Ok(HashMap::new());
+        }
+        let rows = self.numbers(MIGRATION_APPLIED).await?;
+        Ok(rows.into_iter().filter_map(|row| Some((*row.first()?, *row.get(1)?))).collect())
+    }
+
+    /// Script and the record of the tracking table in one transaction
+    #[cfg(feature = "pgsql")]
+    async fn apply(&self, script: &str, record: &str, params: super::pgsql::QueryParam<'_>) -> Result<(), super::error::DBError> {
+        let mut tx = self.begin().await.ok_or_else(super::error::DBError::connection)?;
+        if let Err(e) = tx.try_batch(script).await {
+            tx.rollback().await;
+            return Err(e);
+        }
+        if let Err(e) = tx.try_execute(record, params).await {
+            tx.rollback().await;
+            return Err(e);
+        }
+        tx.commit().await.ok_or_else(super::error::DBError::connection)
+    }
+
+    /// Script and the record of the tracking table in one transaction
+    ///
+    /// The script is one batch, the statements that must be the first in the batch (CREATE PROCEDURE, CREATE VIEW)
+    /// must be wrapped into EXEC.
+    #[cfg(feature = "mssql")]
+    async fn apply(&self, script: &str, record: &str, params: super::mssql::QueryParam<'_>) -> Result<(), super::error::DBError> {
+        let query = format!("SET XACT_ABORT ON;\nBEGIN TRANSACTION;\n{}\n;\n{};\nCOMMIT TRANSACTION;", script, record);
+        self.try_execute(&query, params).await
+    }
+
+    /// Rows of the query with the integer columns
+    async fn numbers(&self, query: &str) -> Result<Vec<Vec<i64>>, String> {
+        #[cfg(feature = "row-data")]
+        let rows = self
+            .try_query(query, &[], false)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|row| match row {
+                Data::Vec(row) => row
+                    .into_iter()
+                    .map(|value| match value {
+                        Data::I64(value) => value,
+                        _ => 0,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            })
+            .collect();
+        #[cfg(all(feature = "row-native", feature = "pgsql"))]
+        let rows = self
+            .try_query(query, &[])
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|row| (0..row.len()).map(|i| row.try_get::<_, i64>(i).unwrap_or(0)).collect())
+            .collect();
+        #[cfg(all(feature = "row-native", feature = "mssql"))]
+        let rows = self
+            .try_query(query, &[])
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|row| (0..row.len()).map(|i| row.get::<i64, _>(i).unwrap_or(0)).collect())
+            .collect();
+        Ok(rows)
+    }
+}
@@ -2,6 +2,22 @@
 #[cfg(any(feature = "pgsql", feature = "mssql"))]
 pub mod adapter;
 
+/// Builder of the queries and the named parameters
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+pub mod builder;
+
+/// Errors of the queries
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+pub mod error;
+
+/// Migrations of the folder "migrations"
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+pub mod migrate;
+
+/// Invalidation of the cache of all nodes by the channel of the database
+#[cfg(all(feature = "cache", any(feature = "pgsql", feature = "mssql")))]
+pub mod notify;
+
 /// PostgreSQL database
 #[cfg(feature = "pgsql")]
 pub mod pgsql;
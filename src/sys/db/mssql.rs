@@ -13,7 +13,7 @@ use std::collections::HashMap;
 
 use futures_util::TryStreamExt;
 
-use tiberius::{error::Error, AuthMethod, Client, Config, EncryptionLevel, QueryItem, Row, ToSql};
+use tiberius::{error::Error, AuthMethod, Client, Config, EncryptionLevel, QueryItem, Row, SqlBrowser, ToSql};
 
 use tokio::{net::TcpStream, time::timeout};
 
@@ -37,6 +37,8 @@ use tiberius::Column;
 
 use crate::{log, sys::app::init::DBConfig};
 
+use super::error::{DBError, DBErrorKind};
+
 #[cfg(feature = "row-data")]
 use crate::sys::web::data::Data;
 
@@ -46,7 +48,10 @@ pub type DataRow = Data;
 #[cfg(feature = "row-native")]
 pub type DataRow = Row;
 
-pub type QueryParam<'a> = &'a [&'a dyn ToSql];
+/// Parameter of the query
+pub type Param<'a> = &'a dyn ToSql;
+
+pub type QueryParam<'a> = &'a [Param<'a>];
 
 #[cfg(feature = "row-data")]
 type MsColumnName = (usize, fn(&Row, usize) -> Data);
@@ -62,7 +67,7 @@ enum DBResult {
     /// Connection is empty.
     NoClient,
     /// Query execution error.
-    ErrQuery(String),
+    ErrQuery(DBError),
     /// Connection is lost.
     ErrConnect(String),
 }
@@ -81,6 +86,16 @@ pub(crate) struct MsSql {
         feature = "mail-db"
     ))]
     prepare: HashMap<i64, i64>,
+    /// Prepare the statements of the library on connect, off for the migrations of the empty database
+    #[cfg(any(
+        feature = "session-db",
+        feature = "redirect-db",
+        feature = "route-db",
+        feature = "access-db",
+        feature = "setting-db",
+        feature = "mail-db"
+    ))]
+    statements: bool,
 }
 
 impl MsSql {
@@ -98,10 +113,19 @@ impl MsSql {
                 feature = "mail-db"
             ))]
             prepare: HashMap::new(),
+            #[cfg(any(
+                feature = "session-db",
+                feature = "redirect-db",
+                feature = "route-db",
+                feature = "access-db",
+                feature = "setting-db",
+                feature = "mail-db"
+            ))]
+            statements: true,
         })
     }
 
-    fn create_config(config: &DBConfig) -> Config {
+    pub(crate) fn create_config(config: &DBConfig) -> Config {
         let mut cfg = Config::new();
         cfg.host(&config.host);
         if let Some(p) = &config.port {
@@ -116,7 +140,15 @@ impl MsSql {
         }
         let app = format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
         cfg.application_name(app);
-        cfg.trust_cert();
+        if let Some(instance) = &config.instance {
+            cfg.instance_name(instance);
+        }
+        cfg.readonly(config.readonly);
+        // Without the file the certificate of the server is not checked
+        match &config.ca {
+            Some(ca) => cfg.trust_cert_ca(ca),
+            None => cfg.trust_cert(),
+        }
         if config.ssl {
             cfg.encryption(EncryptionLevel::Required);
         } else {
@@ -133,9 +165,31 @@ impl MsSql {
         }
     }
 
+    /// The connection is open and answers
+    pub async fn ping(&mut self) -> bool {
+        match self.client.as_mut() {
+            Some(client) => match client.simple_query("SELECT 1").await {
+                Ok(stream) => stream.into_results().await.is_ok(),
+                Err(_) => false,
+            },
+            None => false,
+        }
+    }
+
+    /// The connection is open
+    pub fn is_open(&self) -> bool {
+        self.client.is_some()
+    }
+
+    /// Close the connection, the next query opens it again
+    pub fn close(&mut self) {
+        self.client = None;
+    }
+
     /// Trying to connect to the database
     async fn try_connect(&mut self) -> bool {
-        let tcp = match timeout(Duration::from_secs(1), TcpStream::connect(self.config.get_addr())).await {
+        // The port of the named instance is from SQL Server Browser
+        let tcp = match timeout(Duration::from_secs(2), TcpStream::connect_named(&self.config)).await {
             Ok(Ok(tcp)) => tcp,
             Ok(Err(_e)) => {
                 log!(stop, 0, "{}", _e);
@@ -168,7 +222,7 @@ impl MsSql {
             feature = "mail-db"
         ))]
         {
-            self.prepare().await
+            !self.statements || self.prepare().await
         }
         #[cfg(not(any(
             feature = "session-db",
@@ -181,6 +235,19 @@ impl MsSql {
         true
     }
 
+    /// Do not prepare the statements of the library, the tables may not exist yet
+    #[cfg(any(
+        feature = "session-db",
+        feature = "redirect-db",
+        feature = "route-db",
+        feature = "access-db",
+        feature = "setting-db",
+        feature = "mail-db"
+    ))]
+    pub fn without_statements(&mut self) {
+        self.statements = false;
+    }
+
     /// Prepare sql statement
     #[cfg(any(
         feature = "session-db",
@@ -274,6 +341,24 @@ impl MsSql {
                     map.insert(fnv1a_64!("lib_get_redirect"), ("@P1 VARCHAR(4000)".to_owned(), sql.to_owned()));
                 }
 
+                // Get all redirects 7177042725879643480
+                #[cfg(feature = "redirect-db")]
+                {
+                    let sql = r#"
+                        SELECT [url], [redirect] FROM [redirect]
+                    "#;
+                    map.insert(fnv1a_64!("lib_get_redirect_all"), (String::new(), sql.to_owned()));
+                }
+
+                // Add redirect -676822954034863335
+                #[cfg(feature = "redirect-db")]
+                {
+                    let sql = r#"
+                        INSERT INTO [redirect] ([url], [permanently], [redirect]) VALUES (@P1, @P2, @P3)
+                    "#;
+                    map.insert(fnv1a_64!("lib_add_redirect"), ("@P1 VARCHAR(4000), @P2 BIT, @P3 VARCHAR(4000)".to_owned(), sql.to_owned()));
+                }
+
                 // Get route 3077841024002823969
                 #[cfg(feature = "route-db")]
                 {
@@ -289,6 +374,35 @@ impl MsSql {
                     map.insert(fnv1a_64!("lib_get_route"), ("@P1 VARCHAR(4000)".to_owned(), sql.to_owned()));
                 }
 
+                // Get route patterns -2089813868176699648
+                #[cfg(feature = "route-db")]
+                {
+                    let sql = r#"
+                        SELECT
+                            r.[url], c.[module_id], c.[class_id], c.[action_id]
+                        FROM
+                            [route] r
+                            INNER JOIN [controller] c ON c.[controller_id]=r.[controller_id]
+                        WHERE r.[url] LIKE '%{%'
+                        ORDER BY r.[route_id]
+                    "#;
+                    map.insert(fnv1a_64!("lib_get_route_pattern"), (String::new(), sql.to_owned()));
+                }
+
+                // Get routes without patterns for the sitemap -8500091946892302431
+                #[cfg(feature = "route-db")]
+                {
+                    let sql = r#"
+                        SELECT
+                            r.[controller_id], ISNULL(r.[params], '') AS [params], r.[lang_id], r.[url]
+                        FROM
+                            [route] r
+                        WHERE r.[url] NOT LIKE '%{%'
+                        ORDER BY r.[controller_id], [params], r.[lang_id], r.[route_id]
+                    "#;
+                    map.insert(fnv1a_64!("lib_get_route_all"), (String::new(), sql.to_owned()));
+                }
+
                 // Get route from module/class/action 8508883211214576597
                 #[cfg(feature = "route-db")]
                 {
@@ -331,6 +445,32 @@ impl MsSql {
                     );
                 }
 
+                // Get business permission 2753738345082664995
+                #[cfg(feature = "access-db")]
+                {
+                    let sql = r#"
+                        WITH r AS (
+                            SELECT CAST(@P1 AS BIGINT) AS [role_id], 0 AS [depth]
+                            UNION ALL
+                            SELECT p.[parent_id], r.[depth] + 1
+                            FROM
+                                [role_parent] p
+                                INNER JOIN r ON r.[role_id]=p.[role_id]
+                            WHERE r.[depth] < 16
+                        )
+                        SELECT TOP 1 rp.[access]
+                        FROM
+                            r
+                            INNER JOIN [role_permission] rp ON rp.[role_id]=r.[role_id]
+                            INNER JOIN [permission] p ON p.[permission_id]=rp.[permission_id]
+                        WHERE
+                            p.[name]=@P2
+                            OR (RIGHT(p.[name], 1)=CHAR(42) AND LEFT(@P2, LEN(p.[name]) - 1)=LEFT(p.[name], LEN(p.[name]) - 1))
+                        ORDER BY r.[depth], LEN(p.[name]) DESC
+                    "#;
+                    map.insert(fnv1a_64!("lib_get_permission"), ("@P1 BIGINT, @P2 VARCHAR(255)".to_owned(), sql.to_owned()));
+                }
+
                 // Get settings 2305043036426846632
                 #[cfg(feature = "setting-db")]
                 {
@@ -461,55 +601,81 @@ impl MsSql {
     }
 
     /// Get Error from query
-    fn get_error<'a>(e: Error) -> DBResult {
+    fn get_error(e: Error) -> DBResult {
         match e {
             Error::Io { kind: _, message } => DBResult::ErrConnect(message),
             Error::Tls(e) => DBResult::ErrConnect(e),
             Error::Routing { host, port } => DBResult::ErrConnect(format!("Erro route: {}:{}", host, port)),
-            Error::Protocol(e) => DBResult::ErrQuery(e.to_string()),
-            Error::Encoding(e) => DBResult::ErrQuery(e.to_string()),
-            Error::Conversion(e) => DBResult::ErrQuery(e.to_string()),
-            Error::Utf8 => DBResult::ErrQuery("Error::Utf8".to_owned()),
-            Error::Utf16 => DBResult::ErrQuery("Error::Utf16".to_owned()),
-            Error::ParseInt(e) => DBResult::ErrQuery(e.to_string()),
-            Error::Server(e) => DBResult::ErrQuery(e.to_string()),
-            Error::BulkInput(e) => DBResult::ErrQuery(e.to_string()),
+            Error::Server(e) => {
+                let kind = MsSql::error_kind(e.code(), e.message());
+                let name = MsSql::quoted(e.message());
+                DBResult::ErrQuery(DBError {
+                    kind,
+                    constraint: if matches!(kind, DBErrorKind::Unique | DBErrorKind::ForeignKey | DBErrorKind::Check) {
+                        name.clone()
+                    } else {
+                        None
+                    },
+                    column: if kind == DBErrorKind::NotNull { name } else { None },
+                    message: e.message().to_owned(),
+                })
+            }
+            e => DBResult::ErrQuery(DBError {
+                kind: DBErrorKind::Other,
+                constraint: None,
+                column: None,
+                message: e.to_string(),
+            }),
         }
     }
 
+    /// Kind of the error by the number of the message of the server
+    fn error_kind(code: u32, message: &str) -> DBErrorKind {
+        match code {
+            2627 | 2601 => DBErrorKind::Unique,
+            // The same number for the foreign key and the check
+            547 if message.contains("CHECK") => DBErrorKind::Check,
+            547 => DBErrorKind::ForeignKey,
+            515 => DBErrorKind::NotNull,
+            241 | 242 | 245 | 2628 | 8114 | 8115 | 8152 => DBErrorKind::InvalidData,
+            1205 | 3960 => DBErrorKind::Conflict,
+            1222 => DBErrorKind::Timeout,
+            102 | 105 | 156 | 207 | 208 | 229 | 230 | 262 | 2812 => DBErrorKind::Syntax,
+            _ => DBErrorKind::Other,
+        }
+    }
+
+    /// Name in the quotes of the message, for example "Violation of UNIQUE KEY constraint 'UQ_login'"
+    fn quoted(message: &str) -> Option<String> {
+        let start = message.find(['\'', '"'])?;
+        let quote = message[start..].chars().next()?;
+        let end = message[start + 1..].find(quote)?;
+        Some(message[start + 1..start + 1 + end].to_owned())
+    }
+
     /// Execute query to database and return a result
     #[cfg(feature = "row-data")]
-    pub async fn query(&mut self, query: &str, params: QueryParam<'_>, assoc: bool) -> Option<Vec<Data>> {
-        match MsSql::query_raw(&mut self.client, query, params).await {
-            DBResult::Vec(rows) => return Some(self.convert(rows, assoc)),
-            DBResult::Void => return Some(Vec::new()),
-            DBResult::ErrQuery(_e) => {
-                log!(warning, 0, "{} error={}", query, _e);
-                return None;
-            }
-            DBResult::NoClient => log!(warning, 0),
-            DBResult::ErrConnect(_e) => log!(warning, 0, "{}", _e),
-        };
-        self.client = None;
-        if self.try_connect().await {
-            match MsSql::query_raw(&mut self.client, query, params).await {
-                DBResult::Vec(rows) => return Some(self.convert(rows, assoc)),
-                DBResult::Void => return Some(Vec::new()),
-                _ => {}
-            }
+    pub async fn try_query(&mut self, query: &str, params: QueryParam<'_>, assoc: bool) -> Result<Vec<Data>, DBError> {
+        let rows = self.try_query_rows(query, params).await?;
+        if rows.is_empty() {
+            return Ok(Vec::new());
         }
-        None
+        Ok(self.convert(rows, assoc))
     }
 
+    /// Execute query to database and return a result
     #[cfg(feature = "row-native")]
-    pub async fn query(&mut self, query: &str, params: QueryParam<'_>) -> Option<Vec<Row>> {
+    pub async fn try_query(&mut self, query: &str, params: QueryParam<'_>) -> Result<Vec<Row>, DBError> {
+        self.try_query_rows(query, params).await
+    }
+
+    async fn try_query_rows(&mut self, query: &str, params: QueryParam<'_>) -> Result<Vec<Row>, DBError> {
         match MsSql::query_raw(&mut self.client, query, params).await {
-            DBResult::Vec(rows) => return Some(rows),
-            // DBResult::Stream(_) => return Some(Vec::new()),
-            DBResult::Void => return Some(Vec::new()),
-            DBResult::ErrQuery(_e) => {
-                log!(warning, 0, "{} error={}", query, _e);
-                return None;
+            DBResult::Vec(rows) => return Ok(rows),
+            DBResult::Void => return Ok(Vec::new()),
+            DBResult::ErrQuery(e) => {
+                log!(warning, 0, "{} error={}", query, e);
+                return Err(e);
             }
             DBResult::NoClient => log!(warning, 0),
             DBResult::ErrConnect(_e) => log!(warning, 0, "{}", _e),
@@ -517,12 +683,13 @@ impl MsSql {
         self.client = None;
         if self.try_connect().await {
             match MsSql::query_raw(&mut self.client, query, params).await {
-                DBResult::Vec(rows) => return Some(rows),
-                DBResult::Void => return Some(Vec::new()),
+                DBResult::Vec(rows) => return Ok(rows),
+                DBResult::Void => return Ok(Vec::new()),
+                DBResult::ErrQuery(e) => return Err(e),
                 _ => {}
             }
         }
-        None
+        Err(DBError::connection())
     }
 
     // #[cfg(feature = "row-native")]
@@ -572,7 +739,6 @@ impl MsSql {
 
         match MsSql::query_raw(&mut self.client, sql, params).await {
             DBResult::Vec(rows) => return Some(rows),
-            DBResult::Stream(_) => return Some(Vec::new()),
             DBResult::Void => return Some(Vec::new()),
             DBResult::ErrQuery(_e) => {
                 #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
@@ -607,13 +773,12 @@ impl MsSql {
     }
 
     /// Execute query to database without a result
-    pub async fn execute(&mut self, query: &str, params: &[&dyn ToSql]) -> Option<()> {
+    pub async fn try_execute(&mut self, query: &str, params: &[&dyn ToSql]) -> Result<(), DBError> {
         match MsSql::execute_raw(&mut self.client, query, params).await {
-            DBResult::Void | DBResult::Vec(_) => return Some(()),
-            // DBResult::Stream(_) => return Some(()),
-            DBResult::ErrQuery(_e) => {
-                log!(warning, 0, "{} error={}", query, _e);
-                return None;
+            DBResult::Void | DBResult::Vec(_) => return Ok(()),
+            DBResult::ErrQuery(e) => {
+                log!(warning, 0, "{} error={}", query, e);
+                return Err(e);
             }
             DBResult::NoClient => log!(warning, 0),
             DBResult::ErrConnect(_e) => log!(warning, 0, "{}", _e),
@@ -621,15 +786,16 @@ impl MsSql {
         self.client = None;
         if self.try_connect().await {
             match MsSql::execute_raw(&mut self.client, query, params).await {
-                DBResult::Void | DBResult::Vec(_) => return Some(()),
+                DBResult::Void | DBResult::Vec(_) => return Ok(()),
+                DBResult::ErrQuery(e) => return Err(e),
                 _ => {}
             }
         }
-        None
+        Err(DBError::connection())
     }
 
     /// Execute query to database without a result
-    #[cfg(any(feature = "session-db", feature = "mail-db"))]
+    #[cfg(any(feature = "session-db", feature = "mail-db", feature = "redirect-db"))]
     pub(crate) async fn execute_prepare(&mut self, query: i64, params: &[&dyn ToSql]) -> Option<()> {
         let stat = match self.prepare.get(&query) {
             Some(s) => s,
@@ -901,11 +1067,11 @@ impl MsSql {
     }
 }
 
-#[cfg(feature = "row-data")]
-pub(crate) enum PgColumn {
-    Vec(Option<Vec<PgColumnNum>>),
-    Map(Option<HashMap<i64, PgColumnName>>),
-}
+// #[cfg(feature = "row-data")]
+// pub(crate) enum PgColumn {
+//     Vec(Option<Vec<PgColumnNum>>),
+//     Map(Option<HashMap<i64, PgColumnName>>),
+// }
 
 // pub struct QueryStream<'a> {
 //     pub(crate) permit: SemaphorePermit<'a>,
@@ -0,0 +1,221 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::time::sleep;
+
+use crate::{log, sys::app::init::DBConfig, sys::web::cache::Cache};
+
+#[cfg(feature = "pgsql")]
+use futures_util::{stream, StreamExt};
+
+#[cfg(feature = "pgsql")]
+use postgres::NoTls;
+
+#[cfg(feature = "pgsql")]
+use tokio::io::{AsyncRead, AsyncWrite};
+
+#[cfg(feature = "pgsql")]
+use tokio_postgres::{AsyncMessage, Connection};
+
+#[cfg(feature = "pgsql")]
+use crate::sys::stat::{
+    bus::{Bus, BusMetrics},
+    stat::Stat,
+};
+
+#[cfg(feature = "pgsql")]
+use super::pgsql::PgSql;
+
+#[cfg(feature = "mssql")]
+use tiberius::{Client, SqlBrowser};
+
+#[cfg(feature = "mssql")]
+use tokio::{net::TcpStream, time::timeout};
+
+#[cfg(feature = "mssql")]
+use tokio_util::compat::TokioAsyncWriteCompatExt;
+
+#[cfg(feature = "mssql")]
+use crate::fnv1a_64;
+
+#[cfg(feature = "mssql")]
+use super::mssql::MsSql;
+
+use super::adapter::DB;
+
+/// Message to all nodes
+#[cfg(feature = "pgsql")]
+const NOTIFY: &str = "SELECT pg_notify($1, $2)";
+/// Message to all nodes, the procedure of sql/lib-mssql.sql
+#[cfg(feature = "mssql")]
+const NOTIFY: &str = "EXEC [cache_notify] @P1, @P2";
+
+/// Group of the cache of the library, removed after the connection, the messages of the break are lost
+const LIBRARY: &str = "sys:";
+
+/// Invalidation of the cache of all nodes by the channel of the database, the parameter [db] notify
+///
+/// The message is the key of the cache to remove, the key with the trailing ":" removes the group.
+/// PostgreSQL listens by LISTEN on the own connection, the notifications go through the bounded queue
+/// [db] notify_capacity with the metrics "cache.notify" of the stat. MS SQL Server receives from the Service Broker queue
+/// of the node "<channel>_<hash of the host and the executable>", the broker must be enabled for the database.
+pub(crate) struct CacheNotify;
+
+impl CacheNotify {
+    /// Delay of the reconnection after the error
+    const RECONNECT: Duration = Duration::from_secs(5);
+
+    /// Listen to the channel until the stop of the server
+    pub(crate) fn start(config: Arc<DBConfig>, cache: Arc<Cache>, #[cfg(feature = "pgsql")] mon: &Stat) {
+        let channel = match config.notify.clone() {
+            Some(channel) => channel,
+            None => return,
+        };
+        #[cfg(feature = "pgsql")]
+        let metrics = Arc::new(BusMetrics::new("cache.notify", config.notify_bus.capacity));
+        #[cfg(feature = "pgsql")]
+        mon.add_bus(Arc::clone(&metrics));
+        tokio::spawn(async move {
+            loop {
+                #[cfg(feature = "pgsql")]
+                let res = CacheNotify::listen(&config, &channel, &cache, &metrics).await;
+                #[cfg(feature = "mssql")]
+                let res = CacheNotify::listen(&config, &channel, &cache).await;
+                if let Err(_e) = res {
+                    log!(warning, 0, "Cache notify {}. Error: {}", channel, _e);
+                }
+                sleep(CacheNotify::RECONNECT).await;
+            }
+        });
+    }
+
+    /// Remove the keys of the messages until the break of the connection
+    ///
+    /// After the messages dropped by [db] notify_policy = "drop" the group of the library is removed,
+    /// as after the break of the connection.
+    #[cfg(feature = "pgsql")]
+    async fn listen(config: &DBConfig, channel: &str, cache: &Cache, metrics: &Arc<BusMetrics>) -> Result<(), String> {
+        let (sql_conn, tls) = PgSql::create_connect_string(config)?;
+        let bus = Bus::new(config.notify_bus, Arc::clone(metrics));
+        let client = match tls {
+            Some(tls) => {
+                let (client, connection) = sql_conn.connect(tls).await.map_err(|e| e.to_string())?;
+                tokio::spawn(CacheNotify::forward(connection, Arc::clone(&bus)));
+                client
+            }
+            None => {
+                let (client, connection) = sql_conn.connect(NoTls).await.map_err(|e| e.to_string())?;
+                tokio::spawn(CacheNotify::forward(connection, Arc::clone(&bus)));
+                client
+            }
+        };
+        client.batch_execute(&format!("LISTEN \"{}\"", channel)).await.map_err(|e| e.to_string())?;
+        cache.remove(LIBRARY).await;
+        while let Some(key) = bus.recv().await {
+            let _lost = bus.lost();
+            if _lost > 0 {
+                log!(warning, 0, "Cache notify {}. Lost messages: {}", channel, _lost);
+                cache.remove(LIBRARY).await;
+            }
+            cache.remove(&key).await;
+        }
+        Err("Connection is closed".to_owned())
+    }
+
+    /// Drive the connection and send the payloads of the notifications, the queue is closed with the connection
+    ///
+    /// The full queue with the policy "wait" stops the reading of the connection, the notifications wait in PostgreSQL.
+    #[cfg(feature = "pgsql")]
+    async fn forward<S, T>(mut connection: Connection<S, T>, bus: Arc<Bus<String>>)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut messages = stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            match message {
+                Ok(AsyncMessage::Notification(notification)) => {
+                    bus.send(notification.payload().to_owned()).await;
+                }
+                Ok(_) => {}
+                Err(_e) => {
+                    log!(warning, 0, "{}", _e);
+                    break;
+                }
+            }
+        }
+        bus.close();
+    }
+
+    /// Remove the keys of the messages of the queue of the node until the break of the connection
+    ///
+    /// The service of the channel sends the messages of `cache_notify`, the service of the node receives them.
+    #[cfg(feature = "mssql")]
+    async fn listen(config: &DBConfig, channel: &str, cache: &Cache) -> Result<(), String> {
+        let config = MsSql::create_config(config);
+        let tcp = timeout(Duration::from_secs(2), TcpStream::connect_named(&config))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+        tcp.set_nodelay(true).map_err(|e| e.to_string())?;
+        let mut client = Client::connect(config, tcp.compat_write()).await.map_err(|e| e.to_string())?;
+        let queue = CacheNotify::queue(channel);
+        let setup = format!(
+            "IF OBJECT_ID(N'[{channel}]', N'SQ') IS NULL CREATE QUEUE [{channel}];
+            IF NOT EXISTS (SELECT 1 FROM sys.services WHERE name=N'{channel}') CREATE SERVICE [{channel}] ON QUEUE [{channel}];
+            IF OBJECT_ID(N'[{queue}]', N'SQ') IS NULL CREATE QUEUE [{queue}];
+            IF NOT EXISTS (SELECT 1 FROM sys.services WHERE name=N'{queue}') CREATE SERVICE [{queue}] ON QUEUE [{queue}] ([DEFAULT]);"
+        );
+        client.simple_query(setup).await.map_err(|e| e.to_string())?.into_results().await.map_err(|e| e.to_string())?;
+        cache.remove(LIBRARY).await;
+        let receive = format!(
+            "SET NOCOUNT ON;
+            DECLARE @handle uniqueidentifier, @type sysname, @body nvarchar(max);
+            WAITFOR (
+                RECEIVE TOP (1) @handle=conversation_handle, @type=message_type_name, @body=CAST(message_body AS nvarchar(max)) FROM [{queue}]
+            ), TIMEOUT 60000;
+            IF @handle IS NOT NULL END CONVERSATION @handle;
+            SELECT @body WHERE @type=N'DEFAULT';"
+        );
+        loop {
+            let rows = client
+                .simple_query(receive.as_str())
+                .await
+                .map_err(|e| e.to_string())?
+                .into_first_result()
+                .await
+                .map_err(|e| e.to_string())?;
+            for row in rows {
+                if let Some(key) = row.get::<&str, _>(0) {
+                    cache.remove(key).await;
+                }
+            }
+        }
+    }
+
+    /// Name of the queue and the service of the node, the same after the restart of the node
+    #[cfg(feature = "mssql")]
+    fn queue(channel: &str) -> String {
+        let host = std::env::var("COMPUTERNAME").or_else(|_| std::env::var("HOSTNAME")).unwrap_or_default();
+        let exe = std::env::current_exe().map(|path| path.to_string_lossy().to_string()).unwrap_or_default();
+        format!("{}_{:016x}", channel, fnv1a_64(format!("{}:{}", host, exe).as_bytes()) as u64)
+    }
+}
+
+impl DB {
+    /// Remove the key or the group of the cache on all nodes, see `Cache::remove`
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// this.db.notify_cache("shop:price:").await;
+    /// ```
+    ///
+    /// The key is removed by the listeners of the channel [db] notify, on this node too.
+    /// Returns `false` without the channel or if the message is not sent.
+    pub async fn notify_cache(&self, key: &str) -> bool {
+        match &self.notify {
+            Some(channel) => self.execute(NOTIFY, &[channel, &key]).await.is_some(),
+            None => false,
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use std::{
+    collections::VecDeque,
     future::Future,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
+    time::Duration,
 };
 
 #[cfg(any(
@@ -16,9 +18,10 @@ use std::{
 ))]
 use std::collections::HashMap;
 
-use futures_util::{pin_mut, TryStreamExt};
+use futures_util::{pin_mut, stream, Stream, TryStreamExt};
 
 use postgres::{
+    error::SqlState,
     tls::{ChannelBinding, MakeTlsConnect, TlsConnect},
     types::ToSql,
     Error, NoTls, Row, ToStatement,
@@ -27,17 +30,22 @@ use postgres::{
 use ring::digest;
 
 use rustls::{
-    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    client::{
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        WebPkiServerVerifier,
+    },
     pki_types::{CertificateDer, ServerName, UnixTime},
-    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
 };
 
 use tokio::{
     io::{AsyncRead, AsyncWrite, ReadBuf},
-    sync::{MutexGuard, SemaphorePermit},
+    net::TcpStream,
+    sync::{MutexGuard, OwnedMutexGuard, OwnedSemaphorePermit, SemaphorePermit},
+    time::timeout,
 };
 
-use tokio_postgres::{Client, RowStream};
+use tokio_postgres::{config::Host, Client, Connection, RowStream};
 
 use tokio_rustls::{client::TlsStream, TlsConnector};
 
@@ -83,7 +91,16 @@ use postgres::Statement;
 ))]
 use tiny_web_macro::fnv1a_64;
 
-use crate::{log, sys::app::init::DBConfig};
+use crate::{
+    log,
+    sys::{
+        app::init::{ChannelBinding as ChannelBindingMode, DBConfig, SslMode},
+        net::eyeballs::HappyEyeballs,
+        stat::limit::LimitPermit,
+    },
+};
+
+use super::error::{DBError, DBErrorKind};
 
 #[cfg(feature = "row-data")]
 use crate::sys::web::data::Data;
@@ -94,13 +111,35 @@ pub type DataRow = Data;
 #[cfg(feature = "row-native")]
 pub type DataRow = Row;
 
-pub type QueryParam<'a> = &'a [&'a (dyn ToSql + Sync)];
+/// Parameter of the query
+pub type Param<'a> = &'a (dyn ToSql + Sync);
+
+pub type QueryParam<'a> = &'a [Param<'a>];
 
 #[cfg(feature = "row-data")]
 type PgColumnName = (usize, fn(&Row, usize) -> Data);
 #[cfg(feature = "row-data")]
 type PgColumnNum = fn(&Row, usize) -> Data;
 
+impl From<Error> for DBError {
+    fn from(e: Error) -> DBError {
+        match e.as_db_error() {
+            Some(db) => DBError {
+                kind: PgSql::error_kind(db.code()),
+                constraint: db.constraint().map(str::to_owned),
+                column: db.column().map(str::to_owned),
+                message: db.message().to_owned(),
+            },
+            None => DBError {
+                kind: if e.is_closed() { DBErrorKind::Connection } else { DBErrorKind::Other },
+                constraint: None,
+                column: None,
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
 /// Response to the result of the query
 enum DBResult {
     /// The request was completed successfully.
@@ -112,7 +151,7 @@ enum DBResult {
     /// Connection is empty.
     NoClient,
     /// Query execution error.
-    ErrQuery(String),
+    ErrQuery(DBError),
     /// Connection is lost.
     ErrConnect(String),
 }
@@ -136,6 +175,16 @@ pub(crate) struct PgSql {
         feature = "mail-db"
     ))]
     prepare: HashMap<i64, Statement>,
+    /// Prepare the statements of the library on connect, off for the migrations of the empty database
+    #[cfg(any(
+        feature = "session-db",
+        feature = "redirect-db",
+        feature = "route-db",
+        feature = "access-db",
+        feature = "setting-db",
+        feature = "mail-db"
+    ))]
+    statements: bool,
 }
 
 impl PgSql {
@@ -162,10 +211,19 @@ impl PgSql {
                 feature = "mail-db",
             ))]
             prepare: HashMap::new(),
+            #[cfg(any(
+                feature = "session-db",
+                feature = "redirect-db",
+                feature = "route-db",
+                feature = "access-db",
+                feature = "setting-db",
+                feature = "mail-db"
+            ))]
+            statements: true,
         })
     }
 
-    fn create_connect_string(config: &DBConfig) -> Result<(tokio_postgres::Config, Option<MakeRustlsConnect>), Error> {
+    pub(crate) fn create_connect_string(config: &DBConfig) -> Result<(tokio_postgres::Config, Option<MakeRustlsConnect>), String> {
         let mut conn_str = String::with_capacity(512);
         //host
         conn_str.push_str("host='");
@@ -197,6 +255,12 @@ impl PgSql {
         if config.ssl {
             conn_str.push_str("sslmode=require ");
         }
+        //channel_binding
+        match config.channel_binding {
+            ChannelBindingMode::Disable => conn_str.push_str("channel_binding=disable "),
+            ChannelBindingMode::Prefer => conn_str.push_str("channel_binding=prefer "),
+            ChannelBindingMode::Require => conn_str.push_str("channel_binding=require "),
+        }
         //connect_timeout
         conn_str.push_str("connect_timeout=1 ");
         //application_name
@@ -208,19 +272,62 @@ impl PgSql {
         //options
         conn_str.push_str("options='--client_encoding=UTF8'");
 
-        let sql_conn: tokio_postgres::Config = match conn_str.parse() {
-            Ok(c) => c,
-            Err(e) => return Err(e),
+        let sql_conn: tokio_postgres::Config = conn_str.parse().map_err(|e: Error| e.to_string())?;
+        let tls = if config.ssl { Some(PgSql::tls(config)?) } else { None };
+
+        Ok((sql_conn, tls))
+    }
+
+    /// TLS by sslmode, the root certificates and the client certificate
+    fn tls(config: &DBConfig) -> Result<MakeRustlsConnect, String> {
+        let builder = ClientConfig::builder();
+        let builder = match config.sslmode {
+            SslMode::Require => builder.dangerous().with_custom_certificate_verifier(Arc::new(NoCertificateVerification {})),
+            mode => {
+                let mut roots = RootCertStore::empty();
+                match &config.ca {
+                    Some(ca) => {
+                        for cert in PgSql::load_certs(ca)? {
+                            roots.add(cert).map_err(|e| format!("{}: {}", ca, e))?;
+                        }
+                    }
+                    None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+                }
+                let verifier = WebPkiServerVerifier::builder(Arc::new(roots)).build().map_err(|e| e.to_string())?;
+                if mode == SslMode::VerifyCa {
+                    builder.dangerous().with_custom_certificate_verifier(Arc::new(CaVerification(verifier)))
+                } else {
+                    builder.with_webpki_verifier(verifier)
+                }
+            }
         };
-        let tls = if config.ssl {
-            let mut config = ClientConfig::builder().with_root_certificates(RootCertStore::empty()).with_no_client_auth();
-            config.dangerous().set_certificate_verifier(Arc::new(NoCertificateVerification {}));
-            Some(MakeRustlsConnect::new(config))
-        } else {
-            None
+        let config = match &config.cert {
+            Some((cert, key)) => {
+                let certs = PgSql::load_certs(cert)?;
+                let data = std::fs::read(key).map_err(|e| format!("{}: {}", key, e))?;
+                let key = match rustls_pemfile::private_key(&mut data.as_slice()) {
+                    Ok(Some(key)) => key,
+                    Ok(None) => return Err(format!("{}: private key not found", key)),
+                    Err(e) => return Err(format!("{}: {}", key, e)),
+                };
+                builder.with_client_auth_cert(certs, key).map_err(|e| e.to_string())?
+            }
+            None => builder.with_no_client_auth(),
         };
+        Ok(MakeRustlsConnect::new(config))
+    }
 
-        Ok((sql_conn, tls))
+    /// Certificates of the file .pem or .crt, one certificate of the file .der
+    fn load_certs(file: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+        let data = std::fs::read(file).map_err(|e| format!("{}: {}", file, e))?;
+        if file.to_ascii_lowercase().ends_with(".der") {
+            return Ok(vec![CertificateDer::from(data)]);
+        }
+        let certs = rustls_pemfile::certs(&mut data.as_slice()).collect::<Result<Vec<_>, _>>().map_err(|e| format!("{}: {}", file, e))?;
+        if certs.is_empty() {
+            return Err(format!("{}: certificate not found", file));
+        }
+        Ok(certs)
     }
 
     /// Connect to the database
@@ -237,37 +344,64 @@ impl PgSql {
         }
     }
 
+    /// The connection is open and answers
+    pub async fn ping(&self) -> bool {
+        match &self.client {
+            Some(client) => !client.is_closed() && client.simple_query("SELECT 1").await.is_ok(),
+            None => false,
+        }
+    }
+
+    /// The connection is open
+    pub fn is_open(&self) -> bool {
+        matches!(&self.client, Some(client) if !client.is_closed())
+    }
+
+    /// Close the connection, the next query opens it again
+    pub fn close(&mut self) {
+        self.client = None;
+    }
+
     /// Trying to connect to the database
+    ///
+    /// The single host is connected by Happy Eyeballs, the list of the hosts and Unix sockets by tokio-postgres.
     async fn try_connect(&mut self) -> bool {
-        match self.tls.clone() {
-            Some(tls) => match self.sql_conn.connect(tls).await {
-                Ok((client, connection)) => {
-                    tokio::spawn(async move {
-                        if let Err(_e) = connection.await {
-                            log!(stop, 0, "{}", _e);
-                        }
-                    });
-                    self.client = Some(client);
-                }
-                Err(_e) => {
-                    log!(warning, 0, "Error: {} => {:?}", _e, &self.sql_conn);
-                    return false;
-                }
-            },
-            None => match self.sql_conn.connect(NoTls).await {
-                Ok((client, connection)) => {
-                    tokio::spawn(async move {
-                        if let Err(_e) = connection.await {
-                            log!(warning, 0, "{}", _e);
-                        }
-                    });
-                    self.client = Some(client);
+        let tcp = match self.sql_conn.get_hosts() {
+            [Host::Tcp(host)] => {
+                let port = self.sql_conn.get_ports().first().copied().unwrap_or(5432);
+                let wait = self.sql_conn.get_connect_timeout().copied().unwrap_or(Duration::from_secs(1));
+                match timeout(wait, HappyEyeballs::connect(host, port)).await {
+                    Ok(Ok(tcp)) => Some((host.clone(), tcp)),
+                    Ok(Err(_e)) => {
+                        log!(warning, 0, "Error: {} => {:?}", _e, &self.sql_conn);
+                        return false;
+                    }
+                    Err(_e) => {
+                        log!(warning, 0, "Error: {} => {:?}", _e, &self.sql_conn);
+                        return false;
+                    }
                 }
+            }
+            _ => None,
+        };
+        let res = match (self.tls.clone(), tcp) {
+            (Some(mut tls), Some((host, tcp))) => match MakeTlsConnect::<TcpStream>::make_tls_connect(&mut tls, &host) {
+                Ok(tls) => self.sql_conn.connect_raw(tcp, tls).await.map(PgSql::spawn),
                 Err(_e) => {
                     log!(warning, 0, "Error: {} => {:?}", _e, &self.sql_conn);
                     return false;
                 }
             },
+            (Some(tls), None) => self.sql_conn.connect(tls).await.map(PgSql::spawn),
+            (None, Some((_, tcp))) => self.sql_conn.connect_raw(tcp, NoTls).await.map(PgSql::spawn),
+            (None, None) => self.sql_conn.connect(NoTls).await.map(PgSql::spawn),
+        };
+        match res {
+            Ok(client) => self.client = Some(client),
+            Err(_e) => {
+                log!(warning, 0, "Error: {} => {:?}", _e, &self.sql_conn);
+                return false;
+            }
         }
         #[cfg(any(
             feature = "session-db",
@@ -278,7 +412,7 @@ impl PgSql {
             feature = "mail-db"
         ))]
         {
-            self.prepare().await
+            !self.statements || self.prepare().await
         }
         #[cfg(not(any(
             feature = "session-db",
@@ -291,6 +425,33 @@ impl PgSql {
         true
     }
 
+    /// The connection works in its own task
+    fn spawn<S, T>((client, connection): (Client, Connection<S, T>)) -> Client
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        tokio::spawn(async move {
+            if let Err(_e) = connection.await {
+                log!(warning, 0, "{}", _e);
+            }
+        });
+        client
+    }
+
+    /// Do not prepare the statements of the library, the tables may not exist yet
+    #[cfg(any(
+        feature = "session-db",
+        feature = "redirect-db",
+        feature = "route-db",
+        feature = "access-db",
+        feature = "setting-db",
+        feature = "mail-db"
+    ))]
+    pub fn without_statements(&mut self) {
+        self.statements = false;
+    }
+
     /// Prepare sql statement
     #[cfg(any(
         feature = "session-db",
@@ -391,6 +552,27 @@ impl PgSql {
                     map.insert(fnv1a_64!("lib_get_redirect"), (client.prepare_typed(sql, &[Type::TEXT]), sql.to_owned()));
                 }
 
+                // Get all redirects 7177042725879643480
+                #[cfg(feature = "redirect-db")]
+                {
+                    let sql = r#"
+                        SELECT url, redirect FROM redirect
+                    "#;
+                    map.insert(fnv1a_64!("lib_get_redirect_all"), (client.prepare_typed(sql, &[]), sql.to_owned()));
+                }
+
+                // Add redirect -676822954034863335
+                #[cfg(feature = "redirect-db")]
+                {
+                    let sql = r#"
+                        INSERT INTO redirect (url, permanently, redirect) VALUES ($1, $2, $3)
+                    "#;
+                    map.insert(
+                        fnv1a_64!("lib_add_redirect"),
+                        (client.prepare_typed(sql, &[Type::TEXT, Type::BOOL, Type::TEXT]), sql.to_owned()),
+                    );
+                }
+
                 // Get route 3077841024002823969
                 #[cfg(feature = "route-db")]
                 {
@@ -406,6 +588,35 @@ impl PgSql {
                     map.insert(fnv1a_64!("lib_get_route"), (client.prepare_typed(sql, &[Type::TEXT]), sql.to_owned()));
                 }
 
+                // Get route patterns -2089813868176699648
+                #[cfg(feature = "route-db")]
+                {
+                    let sql = r#"
+                        SELECT 
+                            r.url, c.module_id, c.class_id, c.action_id
+                        FROM 
+                            route r
+                            INNER JOIN controller c ON c.controller_id=r.controller_id
+                        WHERE r.url LIKE '%{%'
+                        ORDER BY r.route_id
+                    "#;
+                    map.insert(fnv1a_64!("lib_get_route_pattern"), (client.prepare_typed(sql, &[]), sql.to_owned()));
+                }
+
+                // Get routes without patterns for the sitemap -8500091946892302431
+                #[cfg(feature = "route-db")]
+                {
+                    let sql = r#"
+                        SELECT 
+                            r.controller_id, COALESCE(r.params, '') AS params, r.lang_id, r.url
+                        FROM 
+                            route r
+                        WHERE r.url NOT LIKE '%{%'
+                        ORDER BY r.controller_id, params, r.lang_id NULLS FIRST, r.route_id
+                    "#;
+                    map.insert(fnv1a_64!("lib_get_route_all"), (client.prepare_typed(sql, &[]), sql.to_owned()));
+                }
+
                 // Get route from module/class/action 8508883211214576597
                 #[cfg(feature = "route-db")]
                 {
@@ -454,6 +665,33 @@ impl PgSql {
                     );
                 }
 
+                // Get business permission 2753738345082664995
+                #[cfg(feature = "access-db")]
+                {
+                    let sql = r#"
+                        WITH RECURSIVE r AS (
+                            SELECT $1::int8 AS role_id, 0 AS depth
+                            UNION
+                            SELECT p.parent_id, r.depth + 1
+                            FROM
+                                role_parent p
+                                INNER JOIN r ON r.role_id=p.role_id
+                            WHERE r.depth < 16
+                        )
+                        SELECT rp.access
+                        FROM
+                            r
+                            INNER JOIN role_permission rp ON rp.role_id=r.role_id
+                            INNER JOIN permission p ON p.permission_id=rp.permission_id
+                        WHERE
+                            p.name=$2
+                            OR (right(p.name, 1)='*' AND left($2, length(p.name) - 1)=left(p.name, length(p.name) - 1))
+                        ORDER BY r.depth, length(p.name) DESC
+                        LIMIT 1
+                    "#;
+                    map.insert(fnv1a_64!("lib_get_permission"), (client.prepare_typed(sql, &[Type::INT8, Type::TEXT]), sql.to_owned()));
+                }
+
                 // Get setting 2305043036426846632
                 #[cfg(feature = "setting-db")]
                 {
@@ -516,21 +754,21 @@ impl PgSql {
                                             }
                                             None => break,
                                         },
-                                        Err(e) => return DBResult::ErrQuery(e.to_string()),
+                                        Err(e) => return DBResult::ErrQuery(DBError::from(e)),
                                     }
                                 }
                                 DBResult::Vec(result)
                             }
                             None => DBResult::Void,
                         },
-                        Err(e) => DBResult::ErrQuery(e.to_string()),
+                        Err(e) => DBResult::ErrQuery(DBError::from(e)),
                     }
                 }
                 Err(e) => {
                     if e.is_closed() {
                         DBResult::ErrConnect(e.to_string())
                     } else {
-                        DBResult::ErrQuery(e.to_string())
+                        DBResult::ErrQuery(DBError::from(e))
                     }
                 }
             },
@@ -549,7 +787,7 @@ impl PgSql {
                     if e.is_closed() {
                         DBResult::ErrConnect(e.to_string())
                     } else {
-                        DBResult::ErrQuery(e.to_string())
+                        DBResult::ErrQuery(DBError::from(e))
                     }
                 }
             },
@@ -569,7 +807,7 @@ impl PgSql {
                     if e.is_closed() {
                         DBResult::ErrConnect(e.to_string())
                     } else {
-                        DBResult::ErrQuery(e.to_string())
+                        DBResult::ErrQuery(DBError::from(e))
                     }
                 }
             },
@@ -577,18 +815,35 @@ impl PgSql {
         }
     }
 
+    /// Kind of the error by SQLSTATE
+    fn error_kind(code: &SqlState) -> DBErrorKind {
+        match code.code() {
+            "23505" => DBErrorKind::Unique,
+            "23503" => DBErrorKind::ForeignKey,
+            "23502" => DBErrorKind::NotNull,
+            "23514" => DBErrorKind::Check,
+            "40001" | "40P01" => DBErrorKind::Conflict,
+            "57014" | "55P03" => DBErrorKind::Timeout,
+            "57P01" | "57P02" | "57P03" => DBErrorKind::Connection,
+            code if code.starts_with("22") => DBErrorKind::InvalidData,
+            code if code.starts_with("08") => DBErrorKind::Connection,
+            code if code.starts_with("42") => DBErrorKind::Syntax,
+            _ => DBErrorKind::Other,
+        }
+    }
+
     /// Slise to ToSql
     fn slice_iter<'a>(s: &'a [&'a (dyn ToSql + Sync)]) -> impl ExactSizeIterator<Item = &'a dyn ToSql> + 'a {
         s.iter().map(|s| *s as _)
     }
 
     /// Execute query to database without a result
-    pub async fn execute(&mut self, query: &str, params: QueryParam<'_>) -> Option<()> {
+    pub async fn try_execute(&mut self, query: &str, params: QueryParam<'_>) -> Result<(), DBError> {
         match PgSql::execute_raw(&self.client, query, params).await {
-            DBResult::Void | DBResult::Vec(_) => return Some(()),
-            DBResult::ErrQuery(_e) => {
-                log!(warning, 0, "{} error={}", query, _e);
-                return None;
+            DBResult::Void | DBResult::Vec(_) => return Ok(()),
+            DBResult::ErrQuery(e) => {
+                log!(warning, 0, "{} error={}", query, e);
+                return Err(e);
             }
             DBResult::NoClient => log!(warning, 0),
             DBResult::ErrConnect(_e) => log!(warning, 0, "{}", _e),
@@ -597,14 +852,15 @@ impl PgSql {
         self.client = None;
         if self.try_connect().await {
             match PgSql::execute_raw(&self.client, query, params).await {
-                DBResult::Void | DBResult::Vec(_) => return Some(()),
+                DBResult::Void | DBResult::Vec(_) => return Ok(()),
+                DBResult::ErrQuery(e) => return Err(e),
                 _ => {}
             }
         }
-        None
+        Err(DBError::connection())
     }
 
-    #[cfg(any(feature = "session-db", feature = "mail-db"))]
+    #[cfg(any(feature = "session-db", feature = "mail-db", feature = "redirect-db"))]
     pub async fn execute_prepare(&mut self, query: i64, params: QueryParam<'_>) -> Option<()> {
         let stat = match self.prepare.get(&query) {
             Some(s) => s,
@@ -643,38 +899,27 @@ impl PgSql {
 
     /// Execute query to database and return a result
     #[cfg(feature = "row-data")]
-    pub async fn query(&mut self, query: &str, params: QueryParam<'_>, assoc: bool) -> Option<Vec<DataRow>> {
-        match PgSql::query_raw(&self.client, query, params).await {
-            DBResult::Vec(rows) => return Some(self.convert(rows, assoc)),
-            DBResult::Void => return Some(Vec::new()),
-            DBResult::ErrQuery(_e) => {
-                log!(warning, 0, "{} error={}", query, _e);
-                return None;
-            }
-            DBResult::NoClient => log!(warning, 0),
-            DBResult::ErrConnect(_e) => log!(warning, 0, "{}", _e),
-            DBResult::Stream(_) => log!(warning, 0),
-        };
-        self.client = None;
-        if self.try_connect().await {
-            match PgSql::query_raw(&self.client, query, params).await {
-                DBResult::Vec(rows) => return Some(self.convert(rows, assoc)),
-                DBResult::Void => return Some(Vec::new()),
-                _ => {}
-            }
+    pub async fn try_query(&mut self, query: &str, params: QueryParam<'_>, assoc: bool) -> Result<Vec<DataRow>, DBError> {
+        let rows = self.try_query_rows(query, params).await?;
+        if rows.is_empty() {
+            return Ok(Vec::new());
         }
-        None
+        Ok(self.convert(rows, assoc))
     }
 
     /// Execute query to database and return a result
     #[cfg(feature = "row-native")]
-    pub async fn query(&mut self, query: &str, params: QueryParam<'_>) -> Option<Vec<DataRow>> {
+    pub async fn try_query(&mut self, query: &str, params: QueryParam<'_>) -> Result<Vec<DataRow>, DBError> {
+        self.try_query_rows(query, params).await
+    }
+
+    async fn try_query_rows(&mut self, query: &str, params: QueryParam<'_>) -> Result<Vec<Row>, DBError> {
         match PgSql::query_raw(&self.client, query, params).await {
-            DBResult::Vec(rows) => return Some(rows),
-            DBResult::Void => return Some(Vec::new()),
-            DBResult::ErrQuery(_e) => {
-                log!(warning, 0, "{} error={}", query, _e);
-                return None;
+            DBResult::Vec(rows) => return Ok(rows),
+            DBResult::Void => return Ok(Vec::new()),
+            DBResult::ErrQuery(e) => {
+                log!(warning, 0, "{} error={}", query, e);
+                return Err(e);
             }
             DBResult::NoClient => log!(warning, 0),
             DBResult::ErrConnect(_e) => log!(warning, 0, "{}", _e),
@@ -683,12 +928,13 @@ impl PgSql {
         self.client = None;
         if self.try_connect().await {
             match PgSql::query_raw(&self.client, query, params).await {
-                DBResult::Vec(rows) => return Some(rows),
-                DBResult::Void => return Some(Vec::new()),
+                DBResult::Vec(rows) => return Ok(rows),
+                DBResult::Void => return Ok(Vec::new()),
+                DBResult::ErrQuery(e) => return Err(e),
                 _ => {}
             }
         }
-        None
+        Err(DBError::connection())
     }
 
     pub async fn query_stream(&mut self, query: &str, params: QueryParam<'_>) -> Option<RowStream> {
@@ -712,6 +958,60 @@ impl PgSql {
         None
     }
 
+    /// Roll back the transaction of the dropped cursor or transaction, the connection and the permit are free after it
+    fn rollback_drop(mut db: OwnedMutexGuard<PgSql>, permit: Option<OwnedSemaphorePermit>) {
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    if !matches!(PgSql::execute_raw(&db.client, "ROLLBACK", &[]).await, DBResult::Void) {
+                        db.client = None;
+                    }
+                    drop(db);
+                    drop(permit);
+                });
+            }
+            // The server rolls back the transaction of the closed connection
+            Err(_) => db.client = None,
+        }
+    }
+
+    /// Begin the transaction, reconnect once if the connection is lost
+    pub(crate) async fn begin(&mut self) -> bool {
+        match PgSql::execute_raw(&self.client, "BEGIN", &[]).await {
+            DBResult::Void => true,
+            DBResult::ErrQuery(_e) => {
+                log!(warning, 0, "BEGIN error={}", _e);
+                false
+            }
+            _ => {
+                self.client = None;
+                if !self.try_connect().await || !matches!(PgSql::execute_raw(&self.client, "BEGIN", &[]).await, DBResult::Void) {
+                    log!(warning, 0);
+                    return false;
+                }
+                true
+            }
+        }
+    }
+
+    /// Open the cursor of the query in the new transaction
+    pub(crate) async fn cursor_open(&mut self, query: &str, params: QueryParam<'_>) -> bool {
+        if !self.begin().await {
+            return false;
+        }
+        let declare = format!("DECLARE {} NO SCROLL CURSOR FOR {}", CURSOR_NAME, query.trim_end().trim_end_matches(';'));
+        match PgSql::execute_raw(&self.client, declare.as_str(), params).await {
+            DBResult::Void => true,
+            _res => {
+                if let DBResult::ErrQuery(_e) = _res {
+                    log!(warning, 0, "{} error={}", query, _e);
+                }
+                PgSql::execute_raw(&self.client, "ROLLBACK", &[]).await;
+                false
+            }
+        }
+    }
+
     /// Execute prepare query to database and return a result
     #[cfg(any(
         feature = "session-db",
@@ -979,8 +1279,36 @@ pub(crate) enum PgColumn {
     Map(Option<HashMap<i64, PgColumnName>>),
 }
 
+#[cfg(feature = "row-data")]
+impl PgColumn {
+    /// Convert the row, the types of the columns are detected by the first row
+    fn convert(&mut self, db: &PgSql, row: Row) -> Data {
+        match self {
+            PgColumn::Vec(vec) => {
+                let cols = vec.get_or_insert_with(|| db.get_column_type(row.columns()));
+                let mut v = Vec::with_capacity(cols.len());
+                let mut func;
+                for idx in 0..cols.len() {
+                    func = unsafe { cols.get_unchecked(idx) };
+                    v.push(func(&row, idx))
+                }
+                Data::Vec(v)
+            }
+            PgColumn::Map(map) => {
+                let cols = map.get_or_insert_with(|| db.get_column_type_name(row.columns()));
+                let mut t = HashMap::with_capacity(cols.len());
+                for (name, turple) in cols.iter() {
+                    t.insert(*name, turple.1(&row, turple.0));
+                }
+                Data::Map(t)
+            }
+        }
+    }
+}
+
 pub struct QueryStream<'a> {
     pub(crate) permit: SemaphorePermit<'a>,
+    pub(crate) limit: LimitPermit,
     pub(crate) db: MutexGuard<'a, PgSql>,
     pub(crate) stream: Pin<Box<RowStream>>,
     #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
@@ -1014,34 +1342,283 @@ impl<'a> QueryStream<'a> {
 
     #[cfg(feature = "row-data")]
     fn convert(&mut self, row: Row) -> Data {
-        match self.cols {
-            PgColumn::Vec(ref mut vec) => {
-                let cols = vec.get_or_insert_with(|| self.db.get_column_type(row.columns()));
-                let mut v = Vec::with_capacity(cols.len());
-                let mut func;
-                for idx in 0..cols.len() {
-                    func = unsafe { cols.get_unchecked(idx) };
-                    v.push(func(&row, idx))
+        self.cols.convert(&self.db, row)
+    }
+
+    /// The rows as `futures::Stream`
+    pub fn into_stream(self) -> impl Stream<Item = DataRow> + 'a {
+        stream::unfold(self, |mut query| async move { query.next().await.map(|row| (row, query)) })
+    }
+}
+
+/// Name of the cursor, the connection has one cursor at a time
+pub(crate) const CURSOR_NAME: &str = "tiny_web_cursor";
+
+/// Cursor of the query, the rows are fetched from the server by `batch` at a time
+///
+/// The cursor holds the connection in the transaction until it is dropped.
+/// The transaction is committed after the last row, or rolled back on drop.
+pub struct QueryCursor {
+    pub(crate) permit: Option<OwnedSemaphorePermit>,
+    pub(crate) limit: LimitPermit,
+    pub(crate) db: Option<OwnedMutexGuard<PgSql>>,
+    pub(crate) fetch: String,
+    pub(crate) batch: usize,
+    pub(crate) rows: VecDeque<Row>,
+    pub(crate) done: bool,
+    #[cfg(feature = "row-data")]
+    pub(crate) cols: PgColumn,
+}
+
+impl QueryCursor {
+    #[cfg(feature = "row-data")]
+    pub async fn next(&mut self) -> Option<Data> {
+        let row = self.next_row().await?;
+        let db = self.db.as_ref()?;
+        Some(self.cols.convert(db, row))
+    }
+
+    #[cfg(feature = "row-native")]
+    pub async fn next(&mut self) -> Option<Row> {
+        self.next_row().await
+    }
+
+    /// The rows as `futures::Stream`
+    pub fn into_stream(self) -> impl Stream<Item = DataRow> {
+        stream::unfold(self, |mut cursor| async move { cursor.next().await.map(|row| (row, cursor)) })
+    }
+
+    async fn next_row(&mut self) -> Option<Row> {
+        if self.rows.is_empty() && !self.done {
+            self.fetch().await;
+        }
+        self.rows.pop_front()
+    }
+
+    async fn fetch(&mut self) {
+        let db = match self.db.as_mut() {
+            Some(db) => db,
+            None => {
+                self.done = true;
+                return;
+            }
+        };
+        match PgSql::query_raw(&db.client, self.fetch.as_str(), &[]).await {
+            DBResult::Vec(rows) => {
+                if rows.len() < self.batch {
+                    self.finish().await;
                 }
-                Data::Vec(v)
+                self.rows = rows.into();
             }
-            PgColumn::Map(ref mut map) => {
-                let cols = map.get_or_insert_with(|| self.db.get_column_type_name(row.columns()));
-                let mut t = HashMap::with_capacity(cols.len());
-                for (name, turple) in cols {
-                    t.insert(*name, turple.1(&row, turple.0));
+            DBResult::Void => self.finish().await,
+            _res => {
+                if let DBResult::ErrQuery(_e) = _res {
+                    log!(warning, 0, "{} error={}", self.fetch, _e);
                 }
-                Data::Map(t)
+                self.done = true;
+                PgSql::execute_raw(&db.client, "ROLLBACK", &[]).await;
+            }
+        }
+    }
+
+    /// Commit the transaction after the last row
+    async fn finish(&mut self) {
+        self.done = true;
+        if let Some(db) = self.db.as_ref() {
+            if let DBResult::ErrQuery(_e) = PgSql::execute_raw(&db.client, "COMMIT", &[]).await {
+                log!(warning, 0, "COMMIT error={}", _e);
             }
         }
     }
 }
 
+/// Prefix of the savepoints of the nested transactions
+const SAVEPOINT: &str = "tiny_web_sp";
+
+/// Transaction on the held connection
+///
+/// `begin` inside the transaction is the savepoint, `commit` and `rollback` close the last level.
+/// The queries are not repeated on the lost connection, because the rest of the transaction would run without it.
+/// The open levels are rolled back on drop.
+pub struct Transaction {
+    pub(crate) permit: Option<OwnedSemaphorePermit>,
+    pub(crate) limit: Option<LimitPermit>,
+    pub(crate) db: Option<OwnedMutexGuard<PgSql>>,
+    /// Number of the open savepoints
+    pub(crate) depth: usize,
+}
+
+impl Transaction {
+    /// Execute query in the transaction
+    #[cfg(feature = "row-data")]
+    pub async fn query(&mut self, query: &str, params: QueryParam<'_>, assoc: bool) -> Option<Vec<DataRow>> {
+        self.try_query(query, params, assoc).await.ok()
+    }
+
+    /// Execute query in the transaction, the error has the kind for the message to the user
+    #[cfg(feature = "row-data")]
+    pub async fn try_query(&mut self, query: &str, params: QueryParam<'_>, assoc: bool) -> Result<Vec<DataRow>, DBError> {
+        let rows = self.try_query_rows(query, params).await?;
+        match self.db.as_ref() {
+            Some(db) if !rows.is_empty() => Ok(db.convert(rows, assoc)),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Execute query in the transaction
+    #[cfg(feature = "row-native")]
+    pub async fn query(&mut self, query: &str, params: QueryParam<'_>) -> Option<Vec<DataRow>> {
+        self.try_query(query, params).await.ok()
+    }
+
+    /// Execute query in the transaction, the error has the kind for the message to the user
+    #[cfg(feature = "row-native")]
+    pub async fn try_query(&mut self, query: &str, params: QueryParam<'_>) -> Result<Vec<DataRow>, DBError> {
+        self.try_query_rows(query, params).await
+    }
+
+    /// Execute query in the transaction without results
+    pub async fn execute(&mut self, query: &str, params: QueryParam<'_>) -> Option<()> {
+        self.try_execute(query, params).await.ok()
+    }
+
+    /// Execute query in the transaction without results, the error has the kind for the message to the user
+    pub async fn try_execute(&mut self, query: &str, params: QueryParam<'_>) -> Result<(), DBError> {
+        let db = self.db.as_ref().ok_or_else(DBError::connection)?;
+        match PgSql::execute_raw(&db.client, query, params).await {
+            DBResult::Void | DBResult::Vec(_) => Ok(()),
+            res => Err(Transaction::error(query, res)),
+        }
+    }
+
+    async fn try_query_rows(&mut self, query: &str, params: QueryParam<'_>) -> Result<Vec<Row>, DBError> {
+        let db = self.db.as_ref().ok_or_else(DBError::connection)?;
+        match PgSql::query_raw(&db.client, query, params).await {
+            DBResult::Vec(rows) => Ok(rows),
+            DBResult::Void => Ok(Vec::new()),
+            res => Err(Transaction::error(query, res)),
+        }
+    }
+
+    /// Execute the script of the several queries without the parameters in the transaction, for example the migration
+    pub async fn batch(&mut self, query: &str) -> Option<()> {
+        self.try_batch(query).await.ok()
+    }
+
+    /// Execute the script in the transaction, the error has the kind for the message to the user
+    pub async fn try_batch(&mut self, query: &str) -> Result<(), DBError> {
+        let client = self.db.as_ref().and_then(|db| db.client.as_ref()).ok_or_else(DBError::connection)?;
+        client.batch_execute(query).await.map_err(|e| {
+            let e = DBError::from(e);
+            log!(warning, 0, "Script error={}", e);
+            e
+        })
+    }
+
+    /// Nested transaction, the savepoint
+    pub async fn begin(&mut self) -> Option<()> {
+        let query = format!("SAVEPOINT {}_{}", SAVEPOINT, self.depth + 1);
+        self.execute(&query, &[]).await?;
+        self.depth += 1;
+        Some(())
+    }
+
+    /// Commit the last level, the whole transaction without the savepoints
+    pub async fn commit(&mut self) -> Option<()> {
+        if self.depth > 0 {
+            let query = format!("RELEASE SAVEPOINT {}_{}", SAVEPOINT, self.depth);
+            self.execute(&query, &[]).await?;
+            self.depth -= 1;
+            return Some(());
+        }
+        let res = self.execute("COMMIT", &[]).await;
+        if res.is_some() {
+            self.release();
+        }
+        res
+    }
+
+    /// Roll back the last level, the whole transaction without the savepoints
+    ///
+    /// After the error of the query only the rollback of the level is possible.
+    pub async fn rollback(&mut self) -> Option<()> {
+        if self.depth > 0 {
+            let query = format!("ROLLBACK TO SAVEPOINT {0}_{1}; RELEASE SAVEPOINT {0}_{1}", SAVEPOINT, self.depth);
+            let db = self.db.as_ref()?;
+            if let Err(_e) = db.client.as_ref()?.batch_execute(&query).await {
+                log!(warning, 0, "{} error={}", query, _e);
+                return None;
+            }
+            self.depth -= 1;
+            return Some(());
+        }
+        let res = self.execute("ROLLBACK", &[]).await;
+        self.release();
+        res
+    }
+
+    /// Number of the open savepoints, 0 is the transaction itself
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Is the transaction finished by the commit or the rollback
+    pub fn is_done(&self) -> bool {
+        self.db.is_none()
+    }
+
+    /// Free the connection and the permit
+    fn release(&mut self) {
+        self.depth = 0;
+        self.db = None;
+        self.permit = None;
+        self.limit = None;
+    }
+
+    fn error(_query: &str, res: DBResult) -> DBError {
+        match res {
+            DBResult::ErrQuery(e) => {
+                log!(warning, 0, "{} error={}", _query, e);
+                e
+            }
+            DBResult::ErrConnect(_e) => {
+                log!(warning, 0, "{}", _e);
+                DBError::connection()
+            }
+            _ => {
+                log!(warning, 0);
+                DBError::connection()
+            }
+        }
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if let Some(db) = self.db.take() {
+            PgSql::rollback_drop(db, self.permit.take());
+        }
+    }
+}
+
+impl Drop for QueryCursor {
+    fn drop(&mut self) {
+        let _ = &mut self.limit;
+        if self.done {
+            return;
+        }
+        if let Some(db) = self.db.take() {
+            PgSql::rollback_drop(db, self.permit.take());
+        }
+    }
+}
+
 impl Drop for QueryStream<'_> {
     fn drop(&mut self) {
         let _ = &mut self.stream;
         let _ = &mut self.db;
         let _ = &mut self.permit;
+        let _ = &mut self.limit;
     }
 }
 
@@ -1152,6 +1729,51 @@ where
     }
 }
 
+/// Check of the chain of the certificate without the host, sslmode=verify-ca
+#[derive(Debug)]
+struct CaVerification(Arc<WebPkiServerVerifier>);
+
+impl ServerCertVerifier for CaVerification {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        // The name is checked after the chain
+        match self.0.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now) {
+            Err(rustls::Error::InvalidCertificate(CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. })) => {
+                Ok(ServerCertVerified::assertion())
+            }
+            res => res,
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.0.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.0.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.supported_verify_schemes()
+    }
+}
+
 #[derive(Debug)]
 pub struct NoCertificateVerification;
 
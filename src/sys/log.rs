@@ -1,9 +1,31 @@
-use std::{cell::OnceCell, path::PathBuf, process};
+use std::{
+    backtrace::Backtrace,
+    cell::OnceCell,
+    fs,
+    future::Future,
+    panic,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Mutex,
+    },
+};
 
-use chrono::Local;
+use chrono::{DateTime, Local, NaiveDate};
 
-#[derive(Debug)]
-enum LogView {
+use crate::tool::panic_message;
+
+#[cfg(feature = "alert")]
+use super::alert::Alert;
+
+tokio::task_local! {
+    /// Number of the current request for the field "Request" of the log
+    static REQUEST: u64;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum LogView {
     Info,
     Warning,
     Stop,
@@ -28,6 +50,43 @@ pub(crate) enum InitLog {
 
 static mut LOG_FILE: OnceCell<PathBuf> = OnceCell::new();
 
+/// Max verbosity of the compiled code: 1 is "debug-v", 2 is "debug-vv", 3 is "debug-vvv"
+#[cfg(feature = "debug-vvv")]
+const LEVEL_MAX: u8 = 3;
+#[cfg(all(feature = "debug-vv", not(feature = "debug-vvv")))]
+const LEVEL_MAX: u8 = 2;
+#[cfg(not(any(feature = "debug-vv", feature = "debug-vvv")))]
+const LEVEL_MAX: u8 = 1;
+
+/// Current verbosity, the records of `log_vv!` and `log_vvv!` above it are skipped
+static LEVEL: AtomicU8 = AtomicU8::new(LEVEL_MAX);
+
+/// Rotation of the log file, the section [logging]
+static ROTATE: Mutex<Rotation> = Mutex::new(Rotation {
+    config: LogRotate { max_size: 0, daily: false, keep: 7 },
+    day: None,
+});
+
+/// Rotation of the log file
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LogRotate {
+    /// Max size of the file, bytes, 0 is without the limit
+    ///
+    /// The full file is renamed to "app.log.1", the previous "app.log.1" to "app.log.2" and so on.
+    pub max_size: u64,
+    /// The file of the previous day is renamed to "app.log.2026-10-14"
+    pub daily: bool,
+    /// Number of the old files of each kind, the older ones are removed
+    pub keep: usize,
+}
+
+#[derive(Debug)]
+struct Rotation {
+    config: LogRotate,
+    /// Day of the current file
+    day: Option<NaiveDate>,
+}
+
 pub(crate) struct Log;
 
 impl Log {
@@ -88,20 +147,99 @@ impl Log {
         unsafe { LOG_FILE = file.into() }
     }
 
+    /// Verbosity is enabled, `log!` is 1, `log_vv!` is 2 and `log_vvv!` is 3
+    #[inline]
+    pub(crate) fn verbose(level: u8) -> bool {
+        LEVEL.load(Ordering::Relaxed) >= level
+    }
+
+    /// Current verbosity from 1 to 3
+    #[cfg(feature = "admin")]
+    pub(crate) fn level() -> u8 {
+        LEVEL.load(Ordering::Relaxed)
+    }
+
+    /// Max verbosity of the build
+    #[cfg(feature = "admin")]
+    pub(crate) fn level_max() -> u8 {
+        LEVEL_MAX
+    }
+
+    /// Change the verbosity, it is limited by the debug feature of the build, returns the new verbosity
+    pub(crate) fn set_level(level: u8) -> u8 {
+        let level = level.clamp(1, LEVEL_MAX);
+        LEVEL.store(level, Ordering::Relaxed);
+        level
+    }
+
+    /// Verbosity by the name "v", "vv" or "vvv"
+    pub(crate) fn parse_level(name: &str) -> Option<u8> {
+        match name {
+            "v" => Some(1),
+            "vv" => Some(2),
+            "vvv" => Some(3),
+            _ => None,
+        }
+    }
+
+    /// Set the rotation of the log file
+    pub(crate) fn rotate(config: LogRotate) {
+        let mut rotation = match ROTATE.lock() {
+            Ok(rotation) => rotation,
+            Err(e) => e.into_inner(),
+        };
+        rotation.config = config;
+    }
+
+    /// Write the panics to the log with the backtrace, the panics of the controllers have the number of the request
+    ///
+    /// The previous hook is called after the record.
+    pub(crate) fn hook() {
+        let prev = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let (file, line) = info.location().map(|location| (location.file(), location.line())).unwrap_or(("", 0));
+            Log::save(LogText {
+                view: LogView::Critical,
+                number: 0,
+                text: Some(format!("Panic: {}\n{}", panic_message(info.payload()), Backtrace::force_capture())),
+                file,
+                line,
+            });
+            prev(info);
+        }));
+    }
+
+    /// Run the future of the request, its records have the number of the request
+    pub(crate) async fn scope<F: Future>(request: u64, future: F) -> F::Output {
+        REQUEST.scope(request, future).await
+    }
+
+    /// Path of the log file
+    #[cfg(feature = "admin")]
+    pub(crate) fn path() -> Option<PathBuf> {
+        unsafe { LOG_FILE.get() }.filter(|path| !path.as_os_str().is_empty()).cloned()
+    }
+
     fn save(log: LogText) {
         let time = Local::now().format("%Y-%m-%d %H:%M:%S%.9f").to_string();
         let text = log.text.unwrap_or_default();
+        let id = REQUEST.try_with(|request| *request).ok();
+        let request = id.map(|request| format!("Request: {} ", request)).unwrap_or_default();
         let str = format!(
-            "ID: {} Time: {} Type: {:?}. Number: {} Line:{} File:{} {}\n",
+            "ID: {} Time: {} Type: {:?}. Number: {} {}Line:{} File:{} {}\n",
             process::id(),
             time,
             log.view,
             log.number,
+            request,
             log.line,
             log.file,
             text
         );
 
+        #[cfg(feature = "alert")]
+        Alert::push(log.view, log.number, log.file, log.line, &text, id);
+
         #[cfg(debug_assertions)]
         match log.view {
             LogView::Info => println!("{}", str.trim_end()),
@@ -113,6 +251,12 @@ impl Log {
             None => Log::panic("Log is not initialized"),
         };
 
+        // The rotation and the writing do not mix the records of the threads
+        let mut rotation = match ROTATE.lock() {
+            Ok(rotation) => rotation,
+            Err(e) => e.into_inner(),
+        };
+        rotation.check(logfile, str.len());
         match std::fs::OpenOptions::new().create(true).append(true).open(logfile) {
             Ok(mut file) => match std::io::Write::write_all(&mut file, str.as_bytes()) {
                 Ok(f) => f,
@@ -129,3 +273,83 @@ impl Log {
         process::exit(1);
     }
 }
+
+impl Rotation {
+    /// Rename the file of the previous day or the full file before the writing of `add` bytes
+    ///
+    /// The errors are skipped, the log is written to the current file.
+    fn check(&mut self, path: &Path, add: usize) {
+        if self.config.daily {
+            let today = Local::now().date_naive();
+            let day = *self.day.get_or_insert_with(|| {
+                fs::metadata(path).and_then(|meta| meta.modified()).map(|time| DateTime::<Local>::from(time).date_naive()).unwrap_or(today)
+            });
+            if day != today {
+                self.day = Some(today);
+                if fs::rename(path, Rotation::name(path, &day.format("%Y-%m-%d").to_string())).is_ok() {
+                    self.clean(path);
+                }
+                return;
+            }
+        }
+        if self.config.max_size > 0 {
+            let len = match fs::metadata(path) {
+                Ok(meta) => meta.len(),
+                Err(_) => return,
+            };
+            if len > 0 && len + add as u64 > self.config.max_size {
+                self.shift(path);
+            }
+        }
+    }
+
+    /// "app.log" to "app.log.1", "app.log.1" to "app.log.2" and so on, the last one is removed
+    fn shift(&self, path: &Path) {
+        let keep = self.config.keep;
+        if keep == 0 {
+            let _ = fs::remove_file(path);
+            return;
+        }
+        let _ = fs::remove_file(Rotation::name(path, &keep.to_string()));
+        for i in (1..keep).rev() {
+            let _ = fs::rename(Rotation::name(path, &i.to_string()), Rotation::name(path, &(i + 1).to_string()));
+        }
+        let _ = fs::rename(path, Rotation::name(path, "1"));
+    }
+
+    /// Remove the files of the days except the last `keep` ones
+    fn clean(&self, path: &Path) {
+        let (dir, file) = match (path.parent(), path.file_name().and_then(|name| name.to_str())) {
+            (Some(dir), Some(file)) => (if dir.as_os_str().is_empty() { Path::new(".") } else { dir }, file),
+            _ => return,
+        };
+        let prefix = format!("{}.", file);
+        let mut days: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(list) => list
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| {
+                    entry
+                        .file_name()
+                        .to_str()
+                        .and_then(|name| name.strip_prefix(&prefix))
+                        .is_some_and(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").is_ok())
+                })
+                .map(|entry| entry.path())
+                .collect(),
+            Err(_) => return,
+        };
+        // The names of the days are sorted as the dates
+        days.sort();
+        let len = days.len().saturating_sub(self.config.keep);
+        for day in &days[..len] {
+            let _ = fs::remove_file(day);
+        }
+    }
+
+    fn name(path: &Path, suffix: &str) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".");
+        name.push(suffix);
+        name.into()
+    }
+}
@@ -1,3 +1,6 @@
+#[cfg(feature = "alert")]
+pub(crate) mod alert;
+
 pub(crate) mod app;
 
 pub(crate) mod db;
@@ -7,13 +10,24 @@ pub(crate) mod log;
 
 pub(crate) mod net;
 
-pub(crate) mod plugin;
+#[cfg(feature = "otel")]
+pub mod otel;
+
+#[cfg(feature = "plugin")]
+pub mod plugin;
+
+#[cfg(feature = "profile")]
+pub mod profile;
 
 pub(crate) mod stat;
 
 pub mod web;
 
-#[cfg(any(feature = "html-reload", feature = "lang-reload", feature = "cache"))]
+#[cfg(any(
+    feature = "html-reload",
+    feature = "lang-reload",
+    all(feature = "cache", not(feature = "cache-redis"))
+))]
 pub(crate) mod wrlock;
 
 #[cfg(any(
@@ -22,12 +36,21 @@ pub(crate) mod wrlock;
 ))]
 compile_error!("Only one features from 'debug-v', 'debug-vv', 'debug-vv' can be enabled for this crate.");
 
+#[cfg(all(
+    feature = "alert",
+    not(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))
+))]
+compile_error!("Feature 'alert' requires one of the features 'debug-v', 'debug-vv', 'debug-vvv'");
+
+#[cfg(all(feature = "plugin", target_family = "windows"))]
+compile_error!("Feature 'plugin' is not supported on Windows");
+
 #[macro_export]
 macro_rules! log {
     ($level:ident, $number:expr) => {
         {
             #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
-            {
+            if $crate::sys::log::Log::verbose(1) {
                 $crate::sys::log::Log::$level($number, None, line!(), file!());
             }
         }
@@ -35,7 +58,7 @@ macro_rules! log {
     ($level:ident, $number:expr, $fmt:expr, $($arg:tt)*) => {
         {
             #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
-            {
+            if $crate::sys::log::Log::verbose(1) {
                 $crate::sys::log::Log::$level($number, Some(format!($fmt, $($arg)*)), line!(), file!());
             }
         }
@@ -54,7 +77,7 @@ macro_rules! log_vv {
     ($level:ident, $number:expr) => {
         {
             #[cfg(any(feature = "debug-vv", feature = "debug-vvv"))]
-            {
+            if $crate::sys::log::Log::verbose(2) {
                 $crate::sys::log::Log::$level($number, None, line!(), file!());
             }
         }
@@ -62,7 +85,7 @@ macro_rules! log_vv {
     ($level:ident, $number:expr, $fmt:expr, $($arg:tt)*) => {
         {
             #[cfg(any(feature = "debug-vv", feature = "debug-vvv"))]
-            {
+            if $crate::sys::log::Log::verbose(2) {
                 $crate::sys::log::Log::$level($number, Some(format!($fmt, $($arg)*)), line!(), file!());
             }
         }
@@ -73,8 +96,8 @@ macro_rules! log_vv {
 macro_rules! log_vvv {
     ($level:ident, $number:expr) => {
         {
-            #[cfg(feature = "7-vvv")]
-            {
+            #[cfg(feature = "debug-vvv")]
+            if $crate::sys::log::Log::verbose(3) {
                 $crate::sys::log::Log::$level($number, None, line!(), file!());
             }
         }
@@ -82,7 +105,7 @@ macro_rules! log_vvv {
     ($level:ident, $number:expr, $fmt:expr, $($arg:tt)*) => {
         {
             #[cfg(feature = "debug-vvv")]
-            {
+            if $crate::sys::log::Log::verbose(3) {
                 $crate::sys::log::Log::$level($number, Some(format!($fmt, $($arg)*)), line!(), file!());
             }
         }
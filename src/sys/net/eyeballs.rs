@@ -0,0 +1,129 @@
+use std::{
+    collections::BTreeMap,
+    io::{Error, ErrorKind},
+    net::SocketAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    net::{lookup_host, TcpStream},
+    task::JoinSet,
+    time,
+};
+
+/// Delay before the next attempt, "Connection Attempt Delay" of RFC 8305
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+/// Time of the address which answered first in the memory
+const ADDRESS_TTL: Duration = Duration::from_secs(600);
+
+/// The address which answered first, the key is the host and the port
+static LAST: Mutex<BTreeMap<(String, u16), (SocketAddr, Instant)>> = Mutex::new(BTreeMap::new());
+
+/// Outbound connections by RFC 8305 "Happy Eyeballs"
+///
+/// The addresses of the host alternate between IPv6 and IPv4, the next attempt starts after 250 ms
+/// or right after the error of the previous one, the first connected socket wins.
+/// So the broken IPv6 of the network costs 250 ms instead of the timeout of the connection.
+pub(crate) struct HappyEyeballs;
+
+impl HappyEyeballs {
+    /// Connection to the first answered address of the host
+    pub(crate) async fn connect(host: &str, port: u16) -> Result<TcpStream, Error> {
+        let mut list = HappyEyeballs::resolve(host, port).await?.into_iter();
+        let mut attempts = JoinSet::new();
+        let mut error = Error::new(ErrorKind::NotFound, format!("{}:{} is not resolved", host, port));
+        if let Some(addr) = list.next() {
+            attempts.spawn(TcpStream::connect(addr));
+        }
+        while !attempts.is_empty() {
+            let res = if list.len() == 0 {
+                attempts.join_next().await
+            } else {
+                match time::timeout(ATTEMPT_DELAY, attempts.join_next()).await {
+                    Ok(res) => res,
+                    Err(_) => {
+                        if let Some(addr) = list.next() {
+                            attempts.spawn(TcpStream::connect(addr));
+                        }
+                        continue;
+                    }
+                }
+            };
+            match res {
+                // The other attempts are aborted with the JoinSet
+                Some(Ok(Ok(tcp))) => {
+                    if let Ok(addr) = tcp.peer_addr() {
+                        HappyEyeballs::remember(host, port, addr);
+                    }
+                    return Ok(tcp);
+                }
+                Some(Ok(Err(e))) => error = e,
+                Some(Err(e)) => error = Error::other(e),
+                None => break,
+            }
+            if let Some(addr) = list.next() {
+                attempts.spawn(TcpStream::connect(addr));
+            }
+        }
+        Err(error)
+    }
+
+    /// The address which answers first, for the clients which connect by themselves
+    ///
+    /// The address is checked by the connection once in 10 minutes.
+    #[cfg(feature = "mail-smtp")]
+    pub(crate) async fn address(host: &str, port: u16) -> Result<SocketAddr, Error> {
+        if let Some(addr) = HappyEyeballs::last(host, port) {
+            return Ok(addr);
+        }
+        HappyEyeballs::connect(host, port).await?.peer_addr()
+    }
+
+    /// Addresses of the host, the families alternate starting from the first address of the resolver
+    ///
+    /// The address which answered first recently is the first.
+    async fn resolve(host: &str, port: u16) -> Result<Vec<SocketAddr>, Error> {
+        let list: Vec<SocketAddr> = lookup_host((host, port)).await?.collect();
+        let first = match list.first() {
+            Some(addr) => addr.is_ipv6(),
+            None => return Ok(list),
+        };
+        let (same, other): (Vec<SocketAddr>, Vec<SocketAddr>) = list.into_iter().partition(|addr| addr.is_ipv6() == first);
+        let mut res = Vec::with_capacity(same.len() + other.len());
+        let mut same = same.into_iter();
+        let mut other = other.into_iter();
+        loop {
+            match (same.next(), other.next()) {
+                (None, None) => break,
+                (a, b) => {
+                    res.extend(a);
+                    res.extend(b);
+                }
+            }
+        }
+        if let Some(last) = HappyEyeballs::last(host, port) {
+            if let Some(pos) = res.iter().position(|addr| *addr == last) {
+                let addr = res.remove(pos);
+                res.insert(0, addr);
+            }
+        }
+        Ok(res)
+    }
+
+    fn last(host: &str, port: u16) -> Option<SocketAddr> {
+        let last = match LAST.lock() {
+            Ok(last) => last,
+            Err(e) => e.into_inner(),
+        };
+        last.get(&(host.to_owned(), port)).filter(|(_, time)| time.elapsed() < ADDRESS_TTL).map(|(addr, _)| *addr)
+    }
+
+    fn remember(host: &str, port: u16, addr: SocketAddr) {
+        let mut last = match LAST.lock() {
+            Ok(last) => last,
+            Err(e) => e.into_inner(),
+        };
+        last.insert((host.to_owned(), port), (addr, Instant::now()));
+    }
+}
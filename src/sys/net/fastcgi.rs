@@ -1,40 +1,24 @@
 use std::{
     cmp::min,
     collections::HashMap,
-    net::IpAddr,
     sync::{atomic::Ordering, Arc},
 };
 
-use percent_encoding::percent_decode_str;
+use tokio::task::JoinSet;
 
-use crate::{
-    log,
-    sys::web::{
-        action::ActionData,
-        request::{HttpMethod, HttpVersion, Input, RawData, Request},
-    },
-};
+use crate::{log, sys::web::action::ActionData};
 
 use super::{
-    stream::{StreamError, StreamRead, StreamWrite},
+    parse::{FastCGIHeader, Parse, FASTCGI_HEADER_LEN},
+    stream::{StreamRead, StreamWrite},
     worker::{Worker, WorkerData},
 };
 
-#[derive(Debug)]
-struct Header {
-    /// FastCGI header type.
-    pub header_type: u8,
-    /// Content length.
-    pub content_length: u16,
-    /// Padding length.
-    pub padding_length: u8,
-}
-
 /// Describes one record in the FastCGI protocol
 #[derive(Debug)]
 struct Record {
     /// FastCGI header.
-    pub header: Header,
+    pub header: FastCGIHeader,
     /// Data.
     pub data: Vec<u8>,
 }
@@ -47,25 +31,21 @@ enum RecordType {
     StreamClose,
 }
 
-struct FastCGIParam {
-    request: Request,
-    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-    session: Option<String>,
-}
-
-struct FastCGIArg {
-    data: Vec<u8>,
-    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-    session_key: Arc<String>,
+/// Request in progress on the connection
+#[derive(Debug, Default)]
+struct FastCGIRequest {
+    params: Vec<u8>,
+    stdin: Vec<u8>,
+    is_param_done: bool,
 }
 
-/// FastCGI header length
-pub const FASTCGI_HEADER_LEN: usize = 8;
 /// FastCGI max content length in the record
 pub const FASTCGI_MAX_CONTENT_LEN: usize = 65535;
 
 /// FastCGI header type BEGIN_REQUEST
 pub const FASTCGI_BEGIN_REQUEST: u8 = 1;
+/// FastCGI header type ABORT_REQUEST
+pub const FASTCGI_ABORT_REQUEST: u8 = 2;
 /// FastCGI header type END_REQUEST
 pub const FASTCGI_END_REQUEST: u8 = 3;
 /// FastCGI header type PARAMS
@@ -74,335 +54,184 @@ pub const FASTCGI_PARAMS: u8 = 4;
 pub const FASTCGI_STDIN: u8 = 5;
 /// FastCGI header type STDOUT
 pub const FASTCGI_STDOUT: u8 = 6;
-
-/// The value of 1 in the Big Endian u16 format
-pub const U16_BE_1: [u8; 2] = u16::to_be_bytes(1);
-/// The value of 8 in the Big Endian u16 format
-pub const U16_BE_8: [u8; 2] = u16::to_be_bytes(8);
+/// FastCGI header type GET_VALUES
+pub const FASTCGI_GET_VALUES: u8 = 9;
+/// FastCGI header type GET_VALUES_RESULT
+pub const FASTCGI_GET_VALUES_RESULT: u8 = 10;
+/// FastCGI header type UNKNOWN_TYPE
+pub const FASTCGI_UNKNOWN_TYPE: u8 = 11;
+
+/// FastCGI role RESPONDER, the only supported one
+pub const FASTCGI_RESPONDER: u16 = 1;
+/// FastCGI flag KEEP_CONN of the BEGIN_REQUEST
+pub const FASTCGI_KEEP_CONN: u8 = 1;
+/// FastCGI protocol status REQUEST_COMPLETE
+pub const FASTCGI_REQUEST_COMPLETE: u8 = 0;
+/// FastCGI protocol status UNKNOWN_ROLE
+pub const FASTCGI_UNKNOWN_ROLE: u8 = 3;
+/// Max number of the concurrent requests on the one connection
+pub const FASTCGI_MAX_REQS: u16 = u16::MAX;
 
 // FastCGI protocol
 pub(super) struct FastCGI;
 
 impl FastCGI {
+    /// Reads records of the connection
+    ///
+    /// Requests with different ids are multiplexed, each one runs in its own task.
+    /// The connection is closed after the request without the KEEP_CONN flag.
     pub(super) async fn run(mut stream_read: StreamRead, stream_write: Arc<StreamWrite>, data: WorkerData) {
-        loop {
-            if let Err(e) = stream_read.read(0).await {
-                match e {
-                    StreamError::Closed => {}
-                    _e => {
-                        log!(warning, 0, "{}", _e);
-                    }
-                }
-                break;
-            }
-
-            let id = data.mon.total.fetch_add(1, Ordering::Relaxed);
-            let online = Arc::clone(&data.mon.online);
-            online.fetch_add(1, Ordering::Relaxed);
-
-            // Gets one Record
-            let record = match FastCGI::read_record_raw(&mut stream_read, 0).await {
+        let data = Arc::new(data);
+        let mut requests: HashMap<u16, FastCGIRequest> = HashMap::new();
+        let mut tasks = JoinSet::new();
+        let mut keep_conn = true;
+
+        while keep_conn || !requests.is_empty() {
+            // Without active requests wait for the server without timeout
+            let timeout = if requests.is_empty() { 0 } else { 300 };
+            let record = match FastCGI::read_record_raw(&mut stream_read, timeout).await {
                 RecordType::Some(r) => r,
-                RecordType::StreamClose => {
-                    online.fetch_sub(1, Ordering::Relaxed);
-                    break;
-                }
+                RecordType::StreamClose => break,
             };
-            // Start parsing the protocol, only if it starts with BEGIN_REQUEST
-            if FASTCGI_BEGIN_REQUEST == record.header.header_type {
-                let mut is_param_done = false;
-                let mut is_stdin_done = false;
-                let mut params = Vec::new();
-                let mut stdin = Vec::new();
-
-                // Loop until empty records PARAMS and STDIN are received
-                loop {
-                    // Gets next Record
-                    let record = match FastCGI::read_record_raw(&mut stream_read, 300).await {
-                        RecordType::Some(r) => r,
-                        RecordType::StreamClose => {
-                            online.fetch_sub(1, Ordering::Relaxed);
-                            break;
-                        }
+            // Removes finished tasks
+            while tasks.try_join_next().is_some() {}
+
+            let request_id = record.header.request_id;
+            match record.header.header_type {
+                FASTCGI_BEGIN_REQUEST => {
+                    let (role, flags) = match Parse::fastcgi_begin(&record.data) {
+                        Some(begin) if !requests.contains_key(&request_id) => begin,
+                        _ => continue,
                     };
-                    match record.header.header_type {
-                        FASTCGI_PARAMS => {
-                            if record.data.is_empty() {
-                                is_param_done = true;
-                            } else {
-                                params.extend_from_slice(&record.data);
-                            }
-                        }
-                        FASTCGI_STDIN => {
-                            if record.data.is_empty() {
-                                is_stdin_done = true;
-                            } else {
-                                stdin.extend_from_slice(&record.data);
-                            }
-                        }
-                        _ => {
-                            online.fetch_sub(1, Ordering::Relaxed);
-                            return;
-                        }
-                    }
-                    if is_stdin_done && is_param_done {
-                        online.fetch_sub(1, Ordering::Relaxed);
-                        break;
+                    if role != FASTCGI_RESPONDER {
+                        stream_write.write_record(FastCGI::end_request(request_id, FASTCGI_UNKNOWN_ROLE)).await;
+                        continue;
                     }
-                }
-                // Reads params
-                let arg = FastCGIArg {
-                    data: params,
-                    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-                    session_key: Arc::clone(&data.session.session_key),
-                };
-
-                let param = FastCGI::read_param(arg);
-
-                // Reads POST data
-                let (post, file, raw) = Worker::read_input(stdin, param.request.content_type.as_deref()).await;
-                let mut request = param.request;
-                request.input.file = Arc::new(file);
-                request.input.post = Arc::new(post);
-                request.input.raw = Arc::new(raw);
-
-                let data = ActionData {
-                    id,
-                    mon: Arc::clone(&data.mon),
-                    engine: Arc::clone(&data.engine),
-                    salt: Arc::clone(&data.salt),
-                    request,
-                    tx: Arc::clone(&stream_write.tx),
-                    index: Arc::clone(&data.index),
-
-                    not_found: data.not_found.clone(),
-                    #[cfg(any(feature = "pgsql", feature = "mssql"))]
-                    db: Arc::clone(&data.db),
-                    #[cfg(any(feature = "html-static", feature = "html-reload"))]
-                    html: Arc::clone(&data.html),
-                    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-                    session_loader: Arc::clone(&data.session),
-                    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-                    session: param.session,
-                    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
-                    lang: Arc::clone(&data.lang),
-                    #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
-                    mail: Arc::clone(&data.mail),
-                    #[cfg(feature = "cache")]
-                    cache: Arc::clone(&data.cache),
-                };
-
-                // Run main controller
-                let answer = Worker::call_action(data).await;
-                stream_write.write(answer).await;
-                online.fetch_sub(1, Ordering::Relaxed);
-            } else {
-                online.fetch_sub(1, Ordering::Relaxed);
-                break;
-            }
-        }
-    }
-
-    /// Read params from FastCGI record
-    fn read_param(mut arg: FastCGIArg) -> FastCGIParam {
-        let mut params = HashMap::with_capacity(16);
-        let len = arg.data.len();
-        let mut size = 0;
-
-        let mut ajax = false;
-        let mut host = String::new();
-        let mut scheme = "https".to_owned();
-        let mut agent = String::new();
-        let mut referer = String::new();
-        let mut ip = None;
-        let mut method = String::new();
-        let mut path = String::new();
-        let mut url = String::new();
-
-        let mut get = HashMap::new();
-        let mut cookie = HashMap::new();
-        let mut content_type = None;
-        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-        let mut session = None;
-
-        while size < len {
-            let key_len;
-            let value_len;
-
-            // FastCGI transmits a name-value pair as the length of the name,
-            // followed by the length of the value, followed by the name, followed by the value.
-            //
-            // Lengths of 127 bytes and less can be encoded in one byte,
-            // while longer lengths are always encoded in four bytes
-
-            // We know that size < len, so tiny optimization
-            unsafe {
-                // Gets key length
-                if (*arg.data.get_unchecked(size) >> 7) == 0 {
-                    if size + 1 > len {
-                        break;
-                    }
-                    key_len = usize::from(*arg.data.get_unchecked(size));
-                    size += 1;
-                } else {
-                    if size + 4 > len {
-                        break;
+                    if flags & FASTCGI_KEEP_CONN == 0 {
+                        keep_conn = false;
                     }
-                    let elem = arg.data.get_unchecked_mut(size);
-                    *elem &= 0x7F;
-                    key_len = u32::from_be_bytes([
-                        *arg.data.get_unchecked(size),
-                        *arg.data.get_unchecked(size + 1),
-                        *arg.data.get_unchecked(size + 2),
-                        *arg.data.get_unchecked(size + 3),
-                    ]) as usize;
-                    size += 4;
-                }
-                if key_len == 0 {
-                    break;
+                    data.mon.online.fetch_add(1, Ordering::Relaxed);
+                    requests.insert(request_id, FastCGIRequest::default());
                 }
-                // Gets value length
-                if (*arg.data.get_unchecked(size) >> 7) == 0 {
-                    if size + 1 > len {
-                        break;
+                FASTCGI_ABORT_REQUEST => {
+                    if requests.remove(&request_id).is_some() {
+                        data.mon.online.fetch_sub(1, Ordering::Relaxed);
+                        stream_write.write_record(FastCGI::end_request(request_id, FASTCGI_REQUEST_COMPLETE)).await;
                     }
-                    value_len = usize::from(*arg.data.get_unchecked(size));
-                    size += 1;
-                } else {
-                    if size + 4 > len {
-                        break;
-                    }
-                    let elem = arg.data.get_unchecked_mut(size);
-                    *elem &= 0x7F;
-                    value_len = u32::from_be_bytes([
-                        *arg.data.get_unchecked(size),
-                        *arg.data.get_unchecked(size + 1),
-                        *arg.data.get_unchecked(size + 2),
-                        *arg.data.get_unchecked(size + 3),
-                    ]) as usize;
-                    size += 4;
-                }
-                if size + key_len + value_len > len {
-                    break;
                 }
-            }
-            let key = unsafe { arg.data.get_unchecked(size..size + key_len) };
-            size += key_len;
-            let value = match String::from_utf8(unsafe { arg.data.get_unchecked(size..size + value_len) }.to_vec()) {
-                Ok(value) => value,
-                Err(_) => break,
-            };
-            size += value_len;
-            // We will take some of the headers right away, and leave some for the user
-            match key {
-                b"HTTP_X_REQUESTED_WITH" => ajax = value.to_lowercase().eq("xmlhttprequest"),
-                b"HTTP_HOST" => host = value,
-                b"REQUEST_SCHEME" => scheme = value,
-                b"HTTP_USER_AGENT" => agent = value,
-                b"HTTP_REFERER" => referer = value,
-                b"REMOTE_ADDR" => {
-                    if let Ok(addr) = value.parse::<IpAddr>() {
-                        ip = Some(addr);
-                    }
-                }
-                b"REQUEST_METHOD" => method = value,
-                b"DOCUMENT_ROOT" => path = value,
-                b"REDIRECT_URL" => {
-                    if let Some(u) = value.split('?').next() {
-                        if let Ok(u) = percent_decode_str(u).decode_utf8() {
-                            url = u.to_string();
+                FASTCGI_PARAMS => {
+                    if let Some(request) = requests.get_mut(&request_id) {
+                        if record.data.is_empty() {
+                            request.is_param_done = true;
+                        } else {
+                            request.params.extend_from_slice(&record.data);
                         }
                     }
                 }
-                b"QUERY_STRING" => {
-                    if !value.is_empty() {
-                        let gets: Vec<&str> = value.split('&').collect();
-                        get.reserve(gets.len());
-                        for v in gets {
-                            let key: Vec<&str> = v.splitn(2, '=').collect();
-                            match key.len() {
-                                1 => {
-                                    if let Ok(u) = percent_decode_str(v).decode_utf8() {
-                                        get.insert(u.to_string(), String::new());
-                                    }
-                                }
-                                _ => {
-                                    if let Ok(u) = percent_decode_str(unsafe { key.get_unchecked(0) }).decode_utf8() {
-                                        if let Ok(v) = percent_decode_str(unsafe { key.get_unchecked(1) }).decode_utf8() {
-                                            get.insert(u.to_string(), v.to_string());
-                                        }
-                                    }
-                                }
-                            };
+                FASTCGI_STDIN => {
+                    let request = match requests.get_mut(&request_id) {
+                        Some(request) => request,
+                        None => continue,
+                    };
+                    if !record.data.is_empty() {
+                        request.stdin.extend_from_slice(&record.data);
+                        if request.stdin.len() > data.upload.max_body_size {
+                            requests.remove(&request_id);
+                            stream_write.write(Worker::get_error("Status:", 413), request_id).await;
+                            data.mon.online.fetch_sub(1, Ordering::Relaxed);
                         }
+                        continue;
                     }
-                }
-                b"CONTENT_TYPE" => content_type = Some(value),
-                b"HTTP_COOKIE" => {
-                    let cooks: Vec<&str> = value.split("; ").collect();
-                    cookie.reserve(cooks.len());
-                    for v in cooks {
-                        let key: Vec<&str> = v.splitn(2, '=').collect();
-                        #[cfg(not(any(feature = "session-memory", feature = "session-file", feature = "session-db")))]
-                        if key.len() == 2 {
-                            cookie.insert((*unsafe { key.get_unchecked(0) }).to_owned(), (*unsafe { key.get_unchecked(1) }).to_owned());
-                        }
-                        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-                        if key.len() == 2 && *unsafe { key.get_unchecked(0) } == arg.session_key.as_str() {
-                            let val = *unsafe { key.get_unchecked(1) };
-                            if val.len() == 128 {
-                                for b in val.as_bytes() {
-                                    if !((*b > 47 && *b < 58) || (*b > 96 && *b < 103)) {
-                                        continue;
-                                    }
-                                }
-                                session = Some((*unsafe { key.get_unchecked(1) }).to_owned());
-                            } else {
-                                cookie.insert((*unsafe { key.get_unchecked(0) }).to_owned(), (*unsafe { key.get_unchecked(1) }).to_owned());
-                            }
-                        }
+                    // Empty STDIN is the end of the request
+                    if let Some(request) = requests.remove(&request_id) {
+                        let data = Arc::clone(&data);
+                        let stream_write = Arc::clone(&stream_write);
+                        tasks.spawn(async move {
+                            FastCGI::answer(&data, &stream_write, request_id, request).await;
+                            data.mon.online.fetch_sub(1, Ordering::Relaxed);
+                        });
                     }
                 }
-                _ => {
-                    let key = match String::from_utf8(key.to_vec()) {
-                        Ok(key) => key,
-                        Err(_) => break,
-                    };
-                    params.insert(key, value);
-                }
+                FASTCGI_GET_VALUES => stream_write.write_record(FastCGI::get_values(&record.data)).await,
+                header_type => stream_write.write_record(FastCGI::unknown_type(header_type)).await,
             }
         }
-        params.shrink_to_fit();
-        let method = method.parse().unwrap_or(HttpMethod::Get);
-        let site = format!("{}://{}", scheme, host);
-        let request = Request {
-            ajax,
-            host,
-            scheme,
-            agent,
-            referer,
-            ip,
-            method,
-            root: Arc::new(path.into()),
-            url,
-            input: Input {
-                get: Arc::new(get),
-                post: Arc::new(HashMap::new()),
-                file: Arc::new(Vec::new()),
-                cookie: Arc::new(cookie),
-                params: Arc::new(params),
-                raw: Arc::new(RawData::None),
-            },
-            site,
-            version: HttpVersion::None,
-            content_type,
+        if !requests.is_empty() {
+            data.mon.online.fetch_sub(requests.len() as u64, Ordering::Relaxed);
+        }
+        // Waits for all answers before closing the connection
+        while tasks.join_next().await.is_some() {}
+    }
+
+    /// Runs the controller and writes the answer
+    async fn answer(data: &WorkerData, stream_write: &StreamWrite, request_id: u16, request: FastCGIRequest) {
+        let id = data.mon.total.fetch_add(1, Ordering::Relaxed);
+        // Reads params
+        let param = match Parse::fastcgi_pairs(&request.params).and_then(|pairs| Parse::cgi(&pairs, data.session_key())) {
+            Some(param) => param,
+            None => {
+                log!(warning, 0, "Invalid FastCGI params, size={}", request.params.len());
+                stream_write.write(Worker::get_error("Status:", 400), request_id).await;
+                return;
+            }
         };
 
-        FastCGIParam {
+        // Reads POST data
+        let (post, file, raw) = match Worker::read_input(request.stdin, param.request.content_type.as_deref(), &data.upload).await {
+            Ok(input) => input,
+            Err(e) => {
+                log!(warning, 0, "{}", e);
+                stream_write.write(Worker::get_error("Status:", e.code()), request_id).await;
+                return;
+            }
+        };
+        let mut request = param.request;
+        request.input.file = Arc::new(file);
+        request.input.post = Arc::new(post);
+        request.input.raw = Arc::new(raw);
+
+        let data = ActionData {
+            id,
+            mon: Arc::clone(&data.mon),
+            engine: Arc::clone(&data.engine),
+            #[cfg(feature = "plugin")]
+            plugins: Arc::clone(&data.plugins),
+            router: Arc::clone(&data.router),
+            salt: Arc::clone(&data.salt),
             request,
+            tx: Arc::clone(&stream_write.tx),
+            request_id,
+            index: Arc::clone(&data.index),
+            security: Arc::clone(&data.security),
+            queue: Arc::clone(&data.queue),
+
+            not_found: data.not_found.clone(),
+
+            action_err: data.action_err.clone(),
+            #[cfg(any(feature = "pgsql", feature = "mssql"))]
+            db: Arc::clone(&data.db),
+            #[cfg(any(feature = "html-static", feature = "html-reload"))]
+            html: Arc::clone(&data.html),
             #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-            session,
-        }
+            session_loader: Arc::clone(&data.session),
+            #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+            session: param.session,
+            #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+            lang: Arc::clone(&data.lang),
+            #[cfg(any(
+                feature = "mail-sendmail",
+                feature = "mail-smtp",
+                feature = "mail-file",
+                feature = "mail-api"
+            ))]
+            mail: Arc::clone(&data.mail),
+            #[cfg(feature = "cache")]
+            cache: Arc::clone(&data.cache),
+        };
+
+        // Run main controller
+        let answer = Worker::call_action(data).await;
+        stream_write.write(answer, request_id).await;
     }
 
     /// Read one record from TcpStream
@@ -417,7 +246,10 @@ impl FastCGI {
             buf = stream.get(stream.available());
         }
 
-        let header = FastCGI::read_header(unsafe { buf.get_unchecked(..FASTCGI_HEADER_LEN) });
+        let header = match Parse::fastcgi_header(buf) {
+            Some(header) => header,
+            None => return RecordType::StreamClose,
+        };
         stream.shift(FASTCGI_HEADER_LEN);
         let mut total = header.content_length as usize;
 
@@ -444,23 +276,69 @@ impl FastCGI {
         RecordType::Some(Record { header, data: vec })
     }
 
-    /// Reads the FastCGI header
-    ///
-    /// # Safety
-    ///
-    /// You have to ensure that data length = FASTCGI_HEADER_LEN
-    fn read_header(data: &[u8]) -> Header {
-        unsafe {
-            Header {
-                header_type: *data.get_unchecked(1),
-                content_length: u16::from_be_bytes([*data.get_unchecked(4), *data.get_unchecked(5)]),
-                padding_length: *data.get_unchecked(6),
-            }
+    /// Writes the record header
+    fn push_header(data: &mut Vec<u8>, header_type: u8, request_id: u16, content_length: usize) {
+        data.push(1_u8);
+        data.push(header_type);
+        data.extend_from_slice(&u16::to_be_bytes(request_id));
+        data.extend_from_slice(&u16::to_be_bytes(content_length as u16));
+        data.push(0);
+        data.push(0);
+    }
+
+    /// Record END_REQUEST with the protocol status
+    fn end_request(request_id: u16, status: u8) -> Vec<u8> {
+        let mut data = Vec::with_capacity(FASTCGI_HEADER_LEN * 2);
+        FastCGI::push_header(&mut data, FASTCGI_END_REQUEST, request_id, 8);
+        // appStatus
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.push(status);
+        // reserved
+        data.extend_from_slice(&[0, 0, 0]);
+        data
+    }
+
+    /// Record UNKNOWN_TYPE for the unsupported management record
+    fn unknown_type(header_type: u8) -> Vec<u8> {
+        let mut data = Vec::with_capacity(FASTCGI_HEADER_LEN * 2);
+        FastCGI::push_header(&mut data, FASTCGI_UNKNOWN_TYPE, 0, 8);
+        data.push(header_type);
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0]);
+        data
+    }
+
+    /// Record GET_VALUES_RESULT with the known variables from the GET_VALUES query
+    fn get_values(query: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (name, _) in Parse::fastcgi_pairs(query).unwrap_or_default() {
+            let value = match name {
+                b"FCGI_MAX_CONNS" => FASTCGI_MAX_REQS.to_string(),
+                b"FCGI_MAX_REQS" => FASTCGI_MAX_REQS.to_string(),
+                b"FCGI_MPXS_CONNS" => "1".to_owned(),
+                _ => continue,
+            };
+            FastCGI::push_len(&mut body, name.len());
+            FastCGI::push_len(&mut body, value.len());
+            body.extend_from_slice(name);
+            body.extend_from_slice(value.as_bytes());
+        }
+        let mut data = Vec::with_capacity(FASTCGI_HEADER_LEN + body.len());
+        FastCGI::push_header(&mut data, FASTCGI_GET_VALUES_RESULT, 0, body.len());
+        data.extend_from_slice(&body);
+        data
+    }
+
+    /// Writes the length of the name-value pair
+    fn push_len(data: &mut Vec<u8>, len: usize) {
+        if len < 128 {
+            data.push(len as u8);
+        } else {
+            data.extend_from_slice(&u32::to_be_bytes(len as u32 | 0x8000_0000));
         }
     }
 
     /// Writes answer to server
-    pub fn write(answer: Vec<u8>, end: bool) -> Vec<u8> {
+    pub fn write(answer: Vec<u8>, end: bool, request_id: u16) -> Vec<u8> {
         let mut seek: usize = 0;
         let len = answer.len();
         let capacity = len + FASTCGI_HEADER_LEN * (4 + len / FASTCGI_MAX_CONTENT_LEN);
@@ -475,42 +353,15 @@ impl FastCGI {
             } else {
                 size = len - seek;
             };
-            data.push(1_u8);
-            data.push(FASTCGI_STDOUT);
-            data.extend_from_slice(&U16_BE_1);
-            data.extend_from_slice(&u16::to_be_bytes(size as u16));
-            data.push(0);
-            data.push(0);
-            data.extend_from_slice(unsafe { answer.get_unchecked(seek..seek + size) });
+            FastCGI::push_header(&mut data, FASTCGI_STDOUT, request_id, size);
+            data.extend_from_slice(&answer[seek..seek + size]);
             seek += size;
         }
         if end {
             // Empty FASTCGI_STDOUT
-            data.push(1_u8);
-            data.push(FASTCGI_STDOUT);
-            data.extend_from_slice(&U16_BE_1);
-            data.push(0);
-            data.push(0);
-            data.push(0);
-            data.push(0);
-
-            // Empty FASTCGI_END_REQUEST
-            data.push(1_u8);
-            data.push(FASTCGI_END_REQUEST);
-            data.extend_from_slice(&U16_BE_1);
-            data.extend_from_slice(&U16_BE_8);
-            data.push(0);
-            data.push(0);
-
-            // FASTCGI_END_REQUEST data
-            data.push(0);
-            data.push(0);
-            data.push(0);
-            data.push(0);
-            data.push(0);
-            data.push(0);
-            data.push(0);
-            data.push(0);
+            FastCGI::push_header(&mut data, FASTCGI_STDOUT, request_id, 0);
+            // FASTCGI_END_REQUEST
+            data.extend_from_slice(&FastCGI::end_request(request_id, FASTCGI_REQUEST_COMPLETE));
         }
         data
     }
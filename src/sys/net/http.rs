@@ -1,66 +1,54 @@
-use core::str;
-
 use std::{
+    cmp::min,
     collections::HashMap,
+    mem::take,
     net::IpAddr,
     path::PathBuf,
     sync::{atomic::Ordering, Arc},
+    time::Instant,
 };
 
-use std::{
-    fmt::{Display, Formatter},
-    str::Utf8Error,
-};
+use std::fmt::{Display, Formatter};
 
 use percent_encoding::percent_decode_str;
 
 use crate::{
     log,
-    sys::web::{
-        action::ActionData,
-        request::{HttpMethod, HttpVersion, Input, RawData, Request},
+    sys::{
+        app::init::{HttpConfig, UploadConfig},
+        web::{
+            action::ActionData,
+            request::{HttpVersion, Input, Multipart, MultipartError, RawData, Request, UploadKey, UploadProgress, WebFile},
+        },
     },
 };
 
 use super::{
-    stream::{StreamError, StreamRead, StreamWrite},
+    parse::{HeadScan, HttpHead, Parse, ParseError},
+    stream::{StreamError, StreamRead, StreamWrite, BUFFER_SIZE},
     worker::{Worker, WorkerData},
 };
 
-const HTTP_MIN_HEADER_LEN: usize = 18;
-
-#[derive(Debug, Clone)]
-struct Header {
-    version: HttpVersion,
-    method: HttpMethod,
-    header: HashMap<String, String>,
-    size: Option<usize>,
-}
-
 #[derive(Debug)]
 enum StreamCloseError {
     Stream(StreamError),
-    Utf8(Utf8Error),
-    HttpProtocol(String),
-    ContentLength(String),
-    Header(String),
+    Upload(MultipartError),
 }
 
 impl Display for StreamCloseError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             StreamCloseError::Stream(err) => write!(f, "Stream error: {}", err),
-            StreamCloseError::Utf8(err) => write!(f, "UTF-8 error: {}", err),
-            StreamCloseError::HttpProtocol(msg) => write!(f, "HTTP protocol error: {}", msg),
-            StreamCloseError::ContentLength(msg) => write!(f, "Content length error: {}", msg),
-            StreamCloseError::Header(msg) => write!(f, "Header error: {}", msg),
+            StreamCloseError::Upload(err) => write!(f, "Upload error: {}", err),
         }
     }
 }
 
 enum RecordType {
-    Some(Header),
+    Some(HttpHead),
     StreamClose(StreamCloseError),
+    /// The head is over the limits, the status of the answer
+    Reject(u16),
 }
 
 struct HttpParam {
@@ -80,38 +68,44 @@ pub(super) struct Http;
 
 impl Http {
     pub(super) async fn run(mut stream_read: StreamRead, stream_write: Arc<StreamWrite>, data: WorkerData) {
+        let keep_alive = data.http.keep_alive.map_or(0, |keep_alive| keep_alive.as_millis() as u64);
+        let mut count = 0;
         loop {
-            if let Err(e) = stream_read.read(0).await {
-                match e {
-                    StreamError::Closed => {}
-                    _e => {
-                        log!(warning, 0, "{}", _e);
+            // The pipelined request may be already in the buffer
+            if stream_read.available() == 0 {
+                if let Err(e) = stream_read.read(keep_alive).await {
+                    match e {
+                        StreamError::Closed | StreamError::Timeout => {}
+                        _e => {
+                            log!(warning, 0, "{}", _e);
+                        }
                     }
+                    break;
                 }
-                break;
             }
+            count += 1;
 
             let id = data.mon.total.fetch_add(1, Ordering::Relaxed);
             let online = Arc::clone(&data.mon.online);
             online.fetch_add(1, Ordering::Relaxed);
 
-            let mut header = match Http::get_header(&mut stream_read).await {
+            let mut header = match Http::get_header(&mut stream_read, &data.http).await {
                 RecordType::Some(header) => header,
                 RecordType::StreamClose(_e) => {
                     log!(warning, 0, "{}", _e);
                     online.fetch_sub(1, Ordering::Relaxed);
                     break;
                 }
-            };
-
-            let body = match Http::get_body(&header, &mut stream_read).await {
-                Ok(body) => body,
-                Err(_e) => {
-                    log!(warning, 0, "{}", _e);
+                RecordType::Reject(code) => {
+                    log!(warning, 0, "The head of the request is rejected with {}. IP: {:?}", code, data.ip);
+                    stream_write.write(Worker::get_error("HTTP/1.1", code)).await;
                     online.fetch_sub(1, Ordering::Relaxed);
                     break;
                 }
             };
+            let close = header.version == HttpVersion::HTTP1_0
+                || count == data.http.max_requests
+                || header.header.get("CONNECTION").is_some_and(|value| value.eq_ignore_ascii_case("close"));
 
             let arg = HttpArg {
                 remote_ip: data.ip,
@@ -120,24 +114,44 @@ impl Http {
                 session_key: Arc::clone(&data.session.session_key),
             };
             let param = Http::read_param(&mut header, arg);
-
-            // Reads POST data
-            let (post, file, raw) = Worker::read_input(body, param.request.content_type.as_deref()).await;
             let mut request = param.request;
 
+            #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+            let key = UploadProgress::key(&request, param.session.as_deref());
+            #[cfg(not(any(feature = "session-memory", feature = "session-file", feature = "session-db")))]
+            let key = UploadProgress::key(&request, None);
+            let (post, file, raw) = match Http::get_body(&request, header.size, key, &mut stream_read, &data.upload, &data.http).await {
+                Ok(body) => body,
+                Err(_e) => {
+                    log!(warning, 0, "{}", _e);
+                    if let StreamCloseError::Upload(e) = &_e {
+                        stream_write.write(Worker::get_error(header.version.get_status(), e.code())).await;
+                    }
+                    online.fetch_sub(1, Ordering::Relaxed);
+                    break;
+                }
+            };
+
             request.input.file = Arc::new(file);
             request.input.post = Arc::new(post);
             request.input.raw = Arc::new(raw);
+            request.close = close;
 
             let data = ActionData {
                 id,
                 mon: Arc::clone(&data.mon),
                 engine: Arc::clone(&data.engine),
+                #[cfg(feature = "plugin")]
+                plugins: Arc::clone(&data.plugins),
+                router: Arc::clone(&data.router),
                 salt: Arc::clone(&data.salt),
                 request,
                 tx: Arc::clone(&stream_write.tx),
                 index: Arc::clone(&data.index),
+                security: Arc::clone(&data.security),
+                queue: Arc::clone(&data.queue),
                 not_found: data.not_found.clone(),
+                action_err: data.action_err.clone(),
                 #[cfg(any(feature = "pgsql", feature = "mssql"))]
                 db: Arc::clone(&data.db),
                 #[cfg(any(feature = "html-static", feature = "html-reload"))]
@@ -148,7 +162,12 @@ impl Http {
                 session: param.session,
                 #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
                 lang: Arc::clone(&data.lang),
-                #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+                #[cfg(any(
+                    feature = "mail-sendmail",
+                    feature = "mail-smtp",
+                    feature = "mail-file",
+                    feature = "mail-api"
+                ))]
                 mail: Arc::clone(&data.mail),
                 #[cfg(feature = "cache")]
                 cache: Arc::clone(&data.cache),
@@ -159,145 +178,130 @@ impl Http {
 
             #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
             online.fetch_sub(1, Ordering::Relaxed);
-            if header.version == HttpVersion::HTTP1_0 {
+            if close {
                 break;
             }
         }
     }
 
-    async fn get_header(stream: &mut StreamRead) -> RecordType {
-        let mut buf = stream.get(stream.available());
-        let mut found = false;
-        let mut shift = 0;
-        let mut b;
-
-        let mut vec = Vec::with_capacity(32);
-        let mut last = 0;
-        let mut dotdot = 0;
-
-        if buf.len() < HTTP_MIN_HEADER_LEN {
-            if let Err(e) = stream.read(300).await {
-                return RecordType::StreamClose(StreamCloseError::Stream(e));
+    /// Reads the head of the request
+    ///
+    /// The head is limited by `max_header` and the buffer of the stream, the request line by `max_line`.
+    /// The whole head must come in `header_timeout` from its first byte, the slow client gets 408 Request Timeout.
+    async fn get_header(stream: &mut StreamRead, http: &HttpConfig) -> RecordType {
+        let deadline = Instant::now() + http.header_timeout;
+        let max_header = min(http.max_header, BUFFER_SIZE);
+        let mut scan = HeadScan::default();
+        let end = loop {
+            let data = stream.get(stream.available());
+            let end = scan.end(data);
+            if let Some(code) = Http::check_head(data, end, max_header, http.max_line) {
+                return RecordType::Reject(code);
             }
-            buf = stream.get(stream.available());
-        }
-        while !found {
-            while buf.len() - 4 < shift {
-                if let Err(e) = stream.read(300).await {
-                    return RecordType::StreamClose(StreamCloseError::Stream(e));
-                }
-                buf = stream.get(stream.available());
+            if let Some(end) = end {
+                break end;
             }
-            b = *unsafe { buf.get_unchecked(shift) };
-            if b == b'\r' {
-                if *unsafe { buf.get_unchecked(shift + 1) } == b'\n' {
-                    if *unsafe { buf.get_unchecked(shift + 2) } == b'\r' {
-                        if *unsafe { buf.get_unchecked(shift + 3) } == b'\n' {
-                            found = true;
-                            vec.push((last, shift, dotdot));
-                        } else {
-                            shift += 1;
-                        }
-                    } else {
-                        vec.push((last, shift, dotdot));
-                        dotdot = 0;
-                        shift += 1;
-                        last = shift + 1;
-                    }
-                } else {
-                    shift += 1;
-                }
-            } else {
-                shift += 1;
-                if b == b':' && dotdot == 0 {
-                    dotdot = shift;
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return RecordType::Reject(408);
+            }
+            if let Err(e) = stream.read(min(left, http.read_timeout).as_millis().max(1) as u64).await {
+                if matches!(e, StreamError::Timeout) && Instant::now() >= deadline {
+                    return RecordType::Reject(408);
                 }
+                return RecordType::StreamClose(StreamCloseError::Stream(e));
+            }
+        };
+        let head = Parse::http_head(stream.get(end));
+        stream.shift(end);
+        match head {
+            Ok(head) => RecordType::Some(head),
+            Err(e) => {
+                let code = if let ParseError::TransferEncoding(_) = e { 501 } else { 400 };
+                log!(warning, 0, "{}", e);
+                RecordType::Reject(code)
             }
         }
+    }
 
-        let str: &str = match str::from_utf8(&buf[..shift]) {
-            Ok(str) => str,
-            Err(e) => return RecordType::StreamClose(StreamCloseError::Utf8(e)),
-        };
+    /// Status of the head over the limits: 414 URI Too Long or 431 Request Header Fields Too Large
+    ///
+    /// `end` is the length of the head, `None` if the head is not finished yet.
+    fn check_head(data: &[u8], end: Option<usize>, max_header: usize, max_line: usize) -> Option<u16> {
+        let line = data.iter().position(|byte| *byte == b'\n').unwrap_or(data.len());
+        if line > max_line {
+            return Some(414);
+        }
+        match end {
+            Some(end) if end > max_header => Some(431),
+            Some(_) => None,
+            None if data.len() >= max_header => Some(431),
+            None => None,
+        }
+    }
 
-        let mut head = Header {
-            version: HttpVersion::None,
-            method: HttpMethod::Get,
-            header: HashMap::with_capacity(vec.len()),
-            size: None,
+    /// Reads body and parse POST data
+    ///
+    /// The multipart body is parsed as the data comes, without buffering the entire body.
+    async fn get_body(
+        request: &Request,
+        size: Option<usize>,
+        key: Option<UploadKey>,
+        stream: &mut StreamRead,
+        upload: &Arc<UploadConfig>,
+        http: &HttpConfig,
+    ) -> Result<(HashMap<String, String>, Vec<WebFile>, RawData), StreamCloseError> {
+        let content_type = request.content_type.as_deref();
+        let mut size = match size {
+            Some(size) => size,
+            None => return Worker::read_input(Vec::new(), content_type, upload).await.map_err(StreamCloseError::Upload),
         };
-        let mut first = true;
-        for (start, finish, dot) in vec {
-            if dot == 0 && first {
-                let mut parts = str[start..finish].split(' ');
-                match parts.next() {
-                    Some(method) => head.method = method.parse().unwrap_or(HttpMethod::Get),
-                    None => return RecordType::StreamClose(StreamCloseError::HttpProtocol(str[start..finish].to_owned())),
-                };
-                match parts.next() {
-                    Some(url) => head.header.insert("ORIGIN_URL".to_owned(), url.to_owned()),
-                    None => return RecordType::StreamClose(StreamCloseError::HttpProtocol(str[start..finish].to_owned())),
-                };
-                match parts.next() {
-                    Some(version) => match version {
-                        "HTTP/1.0" => head.version = HttpVersion::HTTP1_0,
-                        "HTTP/1.1" => head.version = HttpVersion::HTTP1_1,
-                        _ => {
-                            log!(warning, 0, "{}", version);
-                        }
-                    },
-                    None => return RecordType::StreamClose(StreamCloseError::HttpProtocol(str[start..finish].to_owned())),
-                };
-                first = false;
-            } else if dot > 0 {
-                let key = str[start..dot - 1].to_uppercase();
-                match key.as_str() {
-                    "CONTENT-LENGTH" => match &str[dot + 1..finish].parse::<usize>() {
-                        Ok(len) => {
-                            if *len > 0 {
-                                head.size = Some(*len);
-                            }
-                        }
-                        Err(_) => return RecordType::StreamClose(StreamCloseError::ContentLength(str[dot + 1..finish].to_owned())),
-                    },
-                    _ => {
-                        head.header.insert(key, str[dot + 1..finish].to_owned());
+        if size > upload.max_body_size {
+            return Err(StreamCloseError::Upload(MultipartError::Limit));
+        }
+        let mut multipart = Multipart::new(content_type, size, key, upload);
+        let mut vec = match multipart {
+            Some(_) => Vec::new(),
+            None => Vec::with_capacity(size),
+        };
+        while size > 0 {
+            let mut available = stream.available();
+            if available == 0 {
+                if let Err(e) = stream.read(http.read_timeout.as_millis() as u64).await {
+                    if let Some(multipart) = multipart {
+                        multipart.abort().await;
                     }
+                    return Err(StreamCloseError::Stream(e));
                 }
-            } else {
-                return RecordType::StreamClose(StreamCloseError::Header(str[start..finish].to_owned()));
+                available = stream.available();
             }
-        }
-        stream.shift(shift + 4);
-        RecordType::Some(head)
-    }
-
-    async fn get_body(header: &Header, stream: &mut StreamRead) -> Result<Vec<u8>, StreamError> {
-        let body = match header.size {
-            Some(mut size) => {
-                let mut vec = Vec::with_capacity(size);
-                let mut buf = stream.get(stream.available());
-                while buf.len() < size {
-                    if !buf.is_empty() {
-                        vec.extend_from_slice(buf);
-                        size -= buf.len();
-                        stream.shift(buf.len());
+            let len = min(size, available);
+            let buf = stream.get(len);
+            match &mut multipart {
+                Some(m) => {
+                    if let Err(e) = m.push(buf).await {
+                        stream.shift(len);
+                        if let Some(multipart) = multipart {
+                            multipart.abort().await;
+                        }
+                        return Err(StreamCloseError::Upload(e));
                     }
-                    stream.read(300).await?;
-                    buf = stream.get(stream.available());
                 }
-                if !buf.is_empty() {
-                    vec.extend_from_slice(&buf[..size]);
-                    stream.shift(buf.len());
-                }
-                vec
+                None => vec.extend_from_slice(buf),
             }
-            None => Vec::new(),
-        };
-        Ok(body)
+            stream.shift(len);
+            size -= len;
+        }
+        match multipart {
+            Some(multipart) => {
+                let (post, file) = multipart.finish().await;
+                Ok((post, file, RawData::None))
+            }
+            None => Worker::read_input(vec, content_type, upload).await.map_err(StreamCloseError::Upload),
+        }
     }
 
-    fn read_param(header: &mut Header, mut arg: HttpArg) -> HttpParam {
+    fn read_param(header: &mut HttpHead, mut arg: HttpArg) -> HttpParam {
         let mut params = HashMap::with_capacity(16);
 
         let mut ajax = false;
@@ -328,62 +332,19 @@ impl Http {
                     }
                 }
                 "X-REQUEST-URI" => {
-                    let mut list = value.split('?');
-                    if let Some(u) = list.next() {
-                        if let Ok(u) = percent_decode_str(u).decode_utf8() {
-                            url = u.to_string();
-                        }
-                    }
-                    if let Some(value) = list.next() {
-                        if !value.is_empty() {
-                            let gets: Vec<&str> = value.split('&').collect();
-                            get.reserve(gets.len());
-                            for v in gets {
-                                let key: Vec<&str> = v.splitn(2, '=').collect();
-                                match key.len() {
-                                    1 => {
-                                        if let Ok(u) = percent_decode_str(v).decode_utf8() {
-                                            get.insert(u.to_string(), String::new());
-                                        }
-                                    }
-                                    _ => {
-                                        if let Ok(u) = percent_decode_str(unsafe { key.get_unchecked(0) }).decode_utf8() {
-                                            if let Ok(v) = percent_decode_str(unsafe { key.get_unchecked(1) }).decode_utf8() {
-                                                get.insert(u.to_string(), v.to_string());
-                                            }
-                                        }
-                                    }
-                                };
-                            }
-                        }
+                    let (u, query) = value.split_once('?').unwrap_or((&value, ""));
+                    if let Ok(u) = percent_decode_str(u).decode_utf8() {
+                        url = u.to_string();
                     }
+                    Parse::query(query, &mut get);
                 }
                 "ORIGIN_URL" => orig_url = value,
                 "CONTENT-TYPE" => content_type = Some(value),
+                #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+                "COOKIE" => session = Parse::cookie(&value, Some(&arg.session_key), &mut cookie),
+                #[cfg(not(any(feature = "session-memory", feature = "session-file", feature = "session-db")))]
                 "COOKIE" => {
-                    let cooks: Vec<&str> = value.split("; ").collect();
-                    cookie.reserve(cooks.len());
-                    for v in cooks {
-                        let key: Vec<&str> = v.splitn(2, '=').collect();
-                        #[cfg(not(any(feature = "session-memory", feature = "session-file", feature = "session-db")))]
-                        if key.len() == 2 {
-                            cookie.insert((*unsafe { key.get_unchecked(0) }).to_owned(), (*unsafe { key.get_unchecked(1) }).to_owned());
-                        }
-                        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-                        if key.len() == 2 && *unsafe { key.get_unchecked(0) } == arg.session_key.as_str() {
-                            let val = *unsafe { key.get_unchecked(1) };
-                            if val.len() == 128 {
-                                for b in val.as_bytes() {
-                                    if !((*b > 47 && *b < 58) || (*b > 96 && *b < 103)) {
-                                        continue;
-                                    }
-                                }
-                                session = Some((*unsafe { key.get_unchecked(1) }).to_owned());
-                            } else {
-                                cookie.insert((*unsafe { key.get_unchecked(0) }).to_owned(), (*unsafe { key.get_unchecked(1) }).to_owned());
-                            }
-                        }
-                    }
+                    Parse::cookie(&value, None, &mut cookie);
                 }
                 _ => {
                     params.insert(key, value);
@@ -395,35 +356,11 @@ impl Http {
             ip = arg.remote_ip.take();
         }
         if url.is_empty() {
-            let mut list = orig_url.split('?');
-            if let Some(u) = list.next() {
-                if let Ok(u) = percent_decode_str(u).decode_utf8() {
-                    url = u.to_string();
-                }
-            }
-            if let Some(value) = list.next() {
-                if !value.is_empty() {
-                    let gets: Vec<&str> = value.split('&').collect();
-                    get.reserve(gets.len());
-                    for v in gets {
-                        let key: Vec<&str> = v.splitn(2, '=').collect();
-                        match key.len() {
-                            1 => {
-                                if let Ok(u) = percent_decode_str(v).decode_utf8() {
-                                    get.insert(u.to_string(), String::new());
-                                }
-                            }
-                            _ => {
-                                if let Ok(u) = percent_decode_str(unsafe { key.get_unchecked(0) }).decode_utf8() {
-                                    if let Ok(v) = percent_decode_str(unsafe { key.get_unchecked(1) }).decode_utf8() {
-                                        get.insert(u.to_string(), v.to_string());
-                                    }
-                                }
-                            }
-                        };
-                    }
-                }
+            let (u, query) = orig_url.split_once('?').unwrap_or((&orig_url, ""));
+            if let Ok(u) = percent_decode_str(u).decode_utf8() {
+                url = u.to_string();
             }
+            Parse::query(query, &mut get);
         }
         let site = format!("{}://{}", scheme, host);
         let request = Request {
@@ -442,11 +379,13 @@ impl Http {
                 file: Arc::new(Vec::new()),
                 cookie: Arc::new(cookie),
                 params: Arc::new(params),
+                headers: Arc::new(take(&mut header.raw)),
                 raw: Arc::new(RawData::None),
             },
             site,
             version: header.version.clone(),
             content_type,
+            close: false,
         };
         HttpParam {
             request,
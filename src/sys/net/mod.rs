@@ -1,3 +1,11 @@
+pub(crate) mod eyeballs;
+
+pub(crate) mod queue;
+
+// The parsers of all protocols are kept for the fuzz targets, the server uses the parsers of its protocol
+#[cfg_attr(not(feature = "fuzz"), allow(dead_code))]
+pub mod parse;
+
 pub mod stream;
 
 pub mod worker;
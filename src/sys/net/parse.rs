@@ -0,0 +1,441 @@
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    net::IpAddr,
+    str,
+    sync::Arc,
+};
+
+use percent_encoding::percent_decode_str;
+
+use crate::sys::web::request::{HttpMethod, HttpVersion, Input, RawData, Request};
+
+/// FastCGI header length
+pub const FASTCGI_HEADER_LEN: usize = 8;
+/// Max number of the digits of the SCGI netstring length
+pub const SCGI_LEN_PACKAGE_SIZE: usize = 7;
+/// UWSGI header length
+pub const UWSGI_LEN_PACKAGE_SIZE: usize = 4;
+
+/// Error of the parsing of the untrusted input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The text is not UTF-8
+    Utf8,
+    /// Invalid request line of HTTP
+    RequestLine(String),
+    /// Invalid header line of HTTP
+    Header(String),
+    /// Invalid or repeated Content-Length
+    ContentLength(String),
+    /// Transfer-Encoding of the request body is not supported
+    TransferEncoding(String),
+    /// Invalid length of the netstring of SCGI
+    Length,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Utf8 => write!(f, "UTF-8 error"),
+            ParseError::RequestLine(line) => write!(f, "HTTP protocol error: {}", line),
+            ParseError::Header(line) => write!(f, "Header error: {}", line),
+            ParseError::ContentLength(value) => write!(f, "Content length error: {}", value),
+            ParseError::TransferEncoding(value) => write!(f, "Transfer encoding is not supported: {}", value),
+            ParseError::Length => write!(f, "Invalid length of the netstring"),
+        }
+    }
+}
+
+/// Header of the FastCGI record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FastCGIHeader {
+    /// FastCGI header type.
+    pub header_type: u8,
+    /// Request id.
+    pub request_id: u16,
+    /// Content length.
+    pub content_length: u16,
+    /// Padding length.
+    pub padding_length: u8,
+}
+
+/// Head of the HTTP request
+#[derive(Debug, Clone)]
+pub struct HttpHead {
+    pub version: HttpVersion,
+    pub method: HttpMethod,
+    /// Headers with the names in the upper case, the target of the request line is "ORIGIN_URL"
+    pub header: HashMap<String, String>,
+    /// Headers with the original names in the order of the request
+    pub raw: Vec<(String, String)>,
+    pub size: Option<usize>,
+}
+
+/// Request of the CGI-like protocols from the vars
+#[derive(Debug)]
+pub struct CgiRequest {
+    pub request: Request,
+    /// CONTENT_LENGTH
+    pub content_len: usize,
+    /// Valid key of the session from the cookie
+    pub session: Option<String>,
+}
+
+/// State of the search of the end of the HTTP head
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum HeadState {
+    #[default]
+    Text,
+    Cr,
+    CrLf,
+    CrLfCr,
+}
+
+/// Search of the empty line after the HTTP head
+///
+/// The search continues from the last position when the buffer got more data, the buffer must keep the start of the head.
+/// One scan is for one head.
+#[derive(Debug, Default)]
+pub struct HeadScan {
+    state: HeadState,
+    pos: usize,
+}
+
+impl HeadScan {
+    /// Length of the head with the empty line, `None` if the head is not finished yet
+    pub fn end(&mut self, data: &[u8]) -> Option<usize> {
+        while let Some(byte) = data.get(self.pos) {
+            self.pos += 1;
+            self.state = match (self.state, *byte) {
+                (HeadState::CrLfCr, b'\n') => return Some(self.pos),
+                (HeadState::CrLf, b'\r') => HeadState::CrLfCr,
+                (_, b'\r') => HeadState::Cr,
+                (HeadState::Cr, b'\n') => HeadState::CrLf,
+                _ => HeadState::Text,
+            };
+        }
+        None
+    }
+}
+
+/// State of the SCGI vars block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VarState {
+    Key,
+    Value,
+}
+
+/// Parsers of the untrusted input of the protocols, without the stream
+///
+/// All functions check the bounds of the data and never panic, the fuzz targets are in the folder "fuzz".
+pub struct Parse;
+
+impl Parse {
+    /// Reads the FastCGI header, the data must have at least FASTCGI_HEADER_LEN bytes
+    pub fn fastcgi_header(data: &[u8]) -> Option<FastCGIHeader> {
+        let data = data.get(..FASTCGI_HEADER_LEN)?;
+        Some(FastCGIHeader {
+            header_type: data[1],
+            request_id: u16::from_be_bytes([data[2], data[3]]),
+            content_length: u16::from_be_bytes([data[4], data[5]]),
+            padding_length: data[6],
+        })
+    }
+
+    /// Role and flags of the BEGIN_REQUEST body
+    pub fn fastcgi_begin(data: &[u8]) -> Option<(u16, u8)> {
+        let data = data.get(..3)?;
+        Some((u16::from_be_bytes([data[0], data[1]]), data[2]))
+    }
+
+    /// Splits the FastCGI params into name-value pairs
+    ///
+    /// FastCGI transmits a name-value pair as the length of the name, followed by the length of the value,
+    /// followed by the name, followed by the value. The data must be filled exactly, the name must not be empty.
+    pub fn fastcgi_pairs(data: &[u8]) -> Option<Vec<(&[u8], &[u8])>> {
+        let mut pairs = Vec::with_capacity(32);
+        let mut pos = 0;
+        while pos < data.len() {
+            let (name_len, len) = Parse::fastcgi_len(data, pos)?;
+            pos += len;
+            let (value_len, len) = Parse::fastcgi_len(data, pos)?;
+            pos += len;
+            if name_len == 0 {
+                return None;
+            }
+            let name = data.get(pos..pos.checked_add(name_len)?)?;
+            pos += name_len;
+            let value = data.get(pos..pos.checked_add(value_len)?)?;
+            pos += value_len;
+            pairs.push((name, value));
+        }
+        Some(pairs)
+    }
+
+    /// Reads the length of the name-value pair, returns the length and the size of it
+    ///
+    /// Lengths of 127 bytes and less are encoded in one byte, while longer lengths are always encoded in four bytes.
+    fn fastcgi_len(data: &[u8], pos: usize) -> Option<(usize, usize)> {
+        let first = *data.get(pos)?;
+        if first >> 7 == 0 {
+            return Some((usize::from(first), 1));
+        }
+        let bytes = data.get(pos..pos + 4)?;
+        Some((u32::from_be_bytes([first & 0x7F, bytes[1], bytes[2], bytes[3]]) as usize, 4))
+    }
+
+    /// Reads the length of the SCGI netstring "<digits>:"
+    ///
+    /// Returns the length of the header and the size of the length with the colon,
+    /// `None` if the colon is not received yet.
+    pub fn scgi_len(data: &[u8]) -> Result<Option<(usize, usize)>, ParseError> {
+        let mut len: usize = 0;
+        for (i, byte) in data.iter().enumerate() {
+            match byte {
+                b':' if i == 0 => return Err(ParseError::Length),
+                b':' => return Ok(Some((len, i + 1))),
+                // Leading zeros are not allowed by the netstring
+                b'0'..=b'9' if i > 0 && len == 0 => return Err(ParseError::Length),
+                b'0'..=b'9' if i < SCGI_LEN_PACKAGE_SIZE => len = len * 10 + usize::from(byte - b'0'),
+                _ => return Err(ParseError::Length),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Splits the SCGI header into pairs
+    ///
+    /// Each var is the key and the value ended by zero, the header must be filled exactly, the key must not be empty.
+    pub fn scgi_vars(header: &[u8]) -> Option<Vec<(&[u8], &[u8])>> {
+        let mut vars = Vec::with_capacity(32);
+        let mut state = VarState::Key;
+        let mut start = 0;
+        let mut key: &[u8] = &[];
+        for (i, byte) in header.iter().enumerate() {
+            if *byte != 0 {
+                continue;
+            }
+            let item = header.get(start..i)?;
+            state = match state {
+                VarState::Key if item.is_empty() => return None,
+                VarState::Key => {
+                    key = item;
+                    VarState::Value
+                }
+                VarState::Value => {
+                    vars.push((key, item));
+                    VarState::Key
+                }
+            };
+            start = i + 1;
+        }
+        if state != VarState::Key || start != header.len() {
+            return None;
+        }
+        Some(vars)
+    }
+
+    /// Reads the UWSGI header, returns the modifier1, the size of the vars block and the modifier2
+    pub fn uwsgi_header(data: &[u8]) -> Option<(u8, usize, u8)> {
+        let data = data.get(..UWSGI_LEN_PACKAGE_SIZE)?;
+        Some((data[0], u16::from_le_bytes([data[1], data[2]]) as usize, data[3]))
+    }
+
+    /// Splits the UWSGI vars block into pairs
+    ///
+    /// Each var is `u16 key size`, key, `u16 value size`, value, the sizes are little endian.
+    /// The block must be filled exactly, the key must not be empty.
+    pub fn uwsgi_vars(block: &[u8]) -> Option<Vec<(&[u8], &[u8])>> {
+        let mut vars = Vec::with_capacity(32);
+        let mut pos = 0;
+        while pos < block.len() {
+            let key_len = u16::from_le_bytes([*block.get(pos)?, *block.get(pos + 1)?]) as usize;
+            pos += 2;
+            if key_len == 0 {
+                return None;
+            }
+            let key = block.get(pos..pos + key_len)?;
+            pos += key_len;
+            let value_len = u16::from_le_bytes([*block.get(pos)?, *block.get(pos + 1)?]) as usize;
+            pos += 2;
+            let value = block.get(pos..pos + value_len)?;
+            pos += value_len;
+            vars.push((key, value));
+        }
+        Some(vars)
+    }
+
+    /// Parses the HTTP head found by `HeadScan`, with or without the empty line
+    ///
+    /// The header line without the colon, with the empty name or with the space before the colon is the error,
+    /// the obsolete line folding is not supported. The body of Transfer-Encoding is not supported, it is the error too.
+    pub fn http_head(data: &[u8]) -> Result<HttpHead, ParseError> {
+        let text = str::from_utf8(data).map_err(|_| ParseError::Utf8)?;
+        let mut lines = text.split("\r\n");
+        let line = lines.next().unwrap_or_default();
+        let mut parts = line.split(' ');
+        let (method, url, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(url), Some(version), None) if !method.is_empty() && !url.is_empty() => (method, url, version),
+            _ => return Err(ParseError::RequestLine(line.to_owned())),
+        };
+        let mut head = HttpHead {
+            version: match version {
+                "HTTP/1.0" => HttpVersion::HTTP1_0,
+                "HTTP/1.1" => HttpVersion::HTTP1_1,
+                _ => HttpVersion::None,
+            },
+            method: method.parse().unwrap_or(HttpMethod::Get),
+            header: HashMap::with_capacity(16),
+            raw: Vec::with_capacity(16),
+            size: None,
+        };
+        head.header.insert("ORIGIN_URL".to_owned(), url.to_owned());
+        for line in lines {
+            if line.is_empty() {
+                continue;
+            }
+            let (name, value) = match line.split_once(':') {
+                Some((name, value)) if !name.is_empty() && !name.contains(|c: char| c.is_ascii_whitespace() || c.is_ascii_control()) => {
+                    (name, value.trim_matches(|c| c == ' ' || c == '\t'))
+                }
+                _ => return Err(ParseError::Header(line.to_owned())),
+            };
+            head.raw.push((name.to_owned(), value.to_owned()));
+            let key = name.to_uppercase();
+            if key == "CONTENT-LENGTH" {
+                let len = value.parse::<usize>().map_err(|_| ParseError::ContentLength(value.to_owned()))?;
+                if head.size.is_some_and(|size| size != len) {
+                    return Err(ParseError::ContentLength(value.to_owned()));
+                }
+                if len > 0 {
+                    head.size = Some(len);
+                }
+            } else if key == "TRANSFER-ENCODING" {
+                // The body without the length can't be separated from the next request
+                return Err(ParseError::TransferEncoding(value.to_owned()));
+            }
+            head.header.insert(key, value.to_owned());
+        }
+        Ok(head)
+    }
+
+    /// Request from the vars of FastCGI, SCGI and UWSGI
+    ///
+    /// The known vars are taken right away, the rest are left in `Input::params` for the user.
+    /// `None` if any var is not UTF-8 or the CONTENT_LENGTH is not a number.
+    pub fn cgi(vars: &[(&[u8], &[u8])], session_key: Option<&str>) -> Option<CgiRequest> {
+        let mut ajax = false;
+        let mut host = String::new();
+        let mut scheme = "https".to_owned();
+        let mut agent = String::new();
+        let mut referer = String::new();
+        let mut ip = None;
+        let mut method = String::new();
+        let mut path = String::new();
+        let mut url = String::new();
+
+        let mut get = HashMap::new();
+        let mut cookie = HashMap::new();
+        let mut content_type = None;
+        let mut session = None;
+        let mut content_len = 0;
+        let mut headers = Vec::with_capacity(16);
+        let mut params = HashMap::with_capacity(vars.len());
+
+        for (key, value) in vars {
+            let value = str::from_utf8(value).ok()?.to_owned();
+            if let Some(name) = Request::cgi_header(key) {
+                headers.push((name, value.clone()));
+            }
+            match *key {
+                b"CONTENT_LENGTH" => {
+                    if !value.is_empty() {
+                        content_len = value.parse::<usize>().ok()?;
+                    }
+                }
+                b"HTTP_X_REQUESTED_WITH" => ajax = value.eq_ignore_ascii_case("xmlhttprequest"),
+                b"HTTP_HOST" => host = value,
+                b"REQUEST_SCHEME" => scheme = value,
+                b"HTTP_USER_AGENT" => agent = value,
+                b"HTTP_REFERER" => referer = value,
+                b"REMOTE_ADDR" => ip = value.parse::<IpAddr>().ok(),
+                b"REQUEST_METHOD" => method = value,
+                b"DOCUMENT_ROOT" => path = value,
+                b"REDIRECT_URL" => {
+                    if let Some(u) = value.split('?').next() {
+                        if let Ok(u) = percent_decode_str(u).decode_utf8() {
+                            url = u.to_string();
+                        }
+                    }
+                }
+                b"QUERY_STRING" => Parse::query(&value, &mut get),
+                b"CONTENT_TYPE" => content_type = Some(value),
+                b"HTTP_COOKIE" => session = Parse::cookie(&value, session_key, &mut cookie),
+                _ => {
+                    params.insert(str::from_utf8(key).ok()?.to_owned(), value);
+                }
+            }
+        }
+        params.shrink_to_fit();
+        let method = method.parse().unwrap_or(HttpMethod::Get);
+        let site = format!("{}://{}", scheme, host);
+        let request = Request {
+            ajax,
+            host,
+            scheme,
+            agent,
+            referer,
+            ip,
+            method,
+            root: Arc::new(path.into()),
+            url,
+            input: Input {
+                get: Arc::new(get),
+                post: Arc::new(HashMap::new()),
+                file: Arc::new(Vec::new()),
+                cookie: Arc::new(cookie),
+                params: Arc::new(params),
+                headers: Arc::new(headers),
+                raw: Arc::new(RawData::None),
+            },
+            site,
+            version: HttpVersion::None,
+            content_type,
+            close: false,
+        };
+        Some(CgiRequest { request, content_len, session })
+    }
+
+    /// Adds the decoded params of the query string "a=1&b=2"
+    pub fn query(value: &str, get: &mut HashMap<String, String>) {
+        if value.is_empty() {
+            return;
+        }
+        for item in value.split('&') {
+            let (key, value) = item.split_once('=').unwrap_or((item, ""));
+            if let (Ok(key), Ok(value)) = (percent_decode_str(key).decode_utf8(), percent_decode_str(value).decode_utf8()) {
+                get.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+
+    /// Adds the cookies of the header "a=1; b=2", returns the valid key of the session
+    ///
+    /// The key of the session is 128 lowercase hex digits, the cookie with the invalid key is added as the others.
+    pub fn cookie(value: &str, session_key: Option<&str>, cookie: &mut HashMap<String, String>) -> Option<String> {
+        let mut session = None;
+        for item in value.split(';') {
+            let (key, value) = match item.trim_start().split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            if Some(key) == session_key && value.len() == 128 && value.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+                session = Some(value.to_owned());
+            } else {
+                cookie.insert(key.to_owned(), value.to_owned());
+            }
+        }
+        session
+    }
+}
@@ -0,0 +1,134 @@
+use std::{
+    collections::VecDeque,
+    sync::{atomic::Ordering, Arc, Mutex},
+    time::Instant,
+};
+
+use tokio::sync::oneshot;
+
+use crate::sys::{
+    app::init::QueueConfig,
+    stat::stat::Stat,
+    web::request::{ClientKind, Request},
+};
+
+/// Priority class of the request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Priority {
+    /// Health checks, monitoring and admin
+    High = 0,
+    Normal = 1,
+    /// Uploads and crawlers
+    Low = 2,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    /// Number of running requests
+    active: usize,
+    /// Waiting requests by priority
+    waiting: [VecDeque<oneshot::Sender<QueueSlot>>; 3],
+}
+
+/// Bounded request queue with priorities
+///
+/// At most `max_active` requests run at the same time, the others wait in the queue.
+/// The free slot goes to the oldest waiting request of the highest priority.
+/// When the queue is full, the request is rejected.
+#[derive(Debug)]
+pub(crate) struct RequestQueue {
+    config: Arc<QueueConfig>,
+    state: Mutex<QueueState>,
+}
+
+/// Slot of the running request, the slot is released on drop
+#[derive(Debug)]
+pub(crate) struct QueueSlot {
+    queue: Option<Arc<RequestQueue>>,
+}
+
+impl RequestQueue {
+    pub(crate) fn new(config: Arc<QueueConfig>) -> Arc<RequestQueue> {
+        Arc::new(RequestQueue {
+            config,
+            state: Mutex::new(QueueState::default()),
+        })
+    }
+
+    /// Priority of the request, the crawlers wait with the uploads
+    pub(crate) fn priority(&self, request: &Request) -> Priority {
+        let kind = request.client_kind();
+        if kind == ClientKind::Monitoring || self.config.high.iter().any(|prefix| request.url.starts_with(prefix.as_str())) {
+            Priority::High
+        } else if kind == ClientKind::Bot
+            || !request.input.file.is_empty()
+            || request.content_type.as_deref().is_some_and(|content_type| content_type.starts_with("multipart/form-data"))
+        {
+            Priority::Low
+        } else {
+            Priority::Normal
+        }
+    }
+
+    /// Wait for the free slot, `None` if the queue is full
+    pub(crate) async fn acquire(self: &Arc<RequestQueue>, priority: Priority, mon: &Stat) -> Option<QueueSlot> {
+        if self.config.max_active == 0 {
+            return Some(QueueSlot { queue: None });
+        }
+        let rx = {
+            let mut state = match self.state.lock() {
+                Ok(state) => state,
+                Err(e) => e.into_inner(),
+            };
+            let waiting: usize = state.waiting.iter().map(|list| list.len()).sum();
+            if state.active < self.config.max_active && waiting == 0 {
+                state.active += 1;
+                return Some(QueueSlot { queue: Some(Arc::clone(self)) });
+            }
+            if waiting >= self.config.max_queue {
+                mon.rejected.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+            let (tx, rx) = oneshot::channel();
+            state.waiting[priority as usize].push_back(tx);
+            rx
+        };
+        mon.queued.fetch_add(1, Ordering::Relaxed);
+        let start = Instant::now();
+        let slot = rx.await.ok();
+        mon.queued.fetch_sub(1, Ordering::Relaxed);
+        let time = start.elapsed().as_micros() as u64;
+        mon.queue_time.fetch_add(time, Ordering::Relaxed);
+        mon.queue_max.fetch_max(time, Ordering::Relaxed);
+        slot
+    }
+
+    /// Pass the slot to the next waiting request or release it
+    fn release(self: Arc<RequestQueue>) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(e) => e.into_inner(),
+        };
+        let mut slot = QueueSlot { queue: Some(Arc::clone(&self)) };
+        for idx in 0..3 {
+            while let Some(tx) = state.waiting[idx].pop_front() {
+                match tx.send(slot) {
+                    Ok(()) => return,
+                    // The request is gone, try the next one
+                    Err(back) => slot = back,
+                }
+            }
+        }
+        // Nobody is waiting, the slot is free
+        slot.queue = None;
+        state.active -= 1;
+    }
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        if let Some(queue) = self.queue.take() {
+            queue.release();
+        }
+    }
+}
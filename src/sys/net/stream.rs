@@ -1,15 +1,17 @@
 use std::{
     cmp::min,
     fmt::{Display, Formatter},
+    future::poll_fn,
     io::{Error, ErrorKind},
     net::{IpAddr, SocketAddr},
     sync::Arc,
+    task::Poll,
     time::Duration,
 };
 
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
-    net::{TcpListener, TcpStream},
+    net::{TcpListener, TcpSocket, TcpStream},
     sync::mpsc::{self, Sender},
     task::JoinHandle,
     time,
@@ -48,20 +50,72 @@ impl Socket {
 #[derive(Debug)]
 pub(crate) enum Listener {
     TcpListener(TcpListener),
+    /// Several addresses of [net] bind, for example IPv4 and IPv6
+    TcpList(Vec<TcpListener>),
     #[cfg(not(target_family = "windows"))]
     UnixListener(UnixListener),
 }
 
 impl Listener {
+    /// Listen on all addresses, the IPv6 sockets accept only IPv6 so "0.0.0.0:port" and "[::]:port" do not conflict
+    pub(crate) fn bind_list(list: &[SocketAddr]) -> Result<Listener, Error> {
+        let mut listeners = Vec::with_capacity(list.len());
+        for addr in list {
+            let socket = match addr {
+                SocketAddr::V4(_) => TcpSocket::new_v4()?,
+                SocketAddr::V6(_) => {
+                    let socket = TcpSocket::new_v6()?;
+                    #[cfg(not(target_family = "windows"))]
+                    Listener::only_v6(&socket)?;
+                    socket
+                }
+            };
+            #[cfg(not(target_family = "windows"))]
+            socket.set_reuseaddr(true)?;
+            socket.bind(*addr)?;
+            listeners.push(socket.listen(1024)?);
+        }
+        Ok(Listener::TcpList(listeners))
+    }
+
+    /// On Windows IPV6_V6ONLY is set by default
+    #[cfg(not(target_family = "windows"))]
+    fn only_v6(socket: &TcpSocket) -> Result<(), Error> {
+        use std::os::fd::AsRawFd;
+
+        let on: libc::c_int = 1;
+        let res = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::IPPROTO_IPV6,
+                libc::IPV6_V6ONLY,
+                &on as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
     pub(crate) async fn accept(&self, ip: &IpAddr) -> Result<(Stream, Option<IpAddr>), Error> {
         match self {
             Listener::TcpListener(tcp) => {
                 let (stream, addr) = tcp.accept().await?;
-                if !ip.is_unspecified() && addr.ip() != *ip {
-                    return Err(Error::new(ErrorKind::Interrupted, "IP address is not spe"));
-                }
-                stream.set_nodelay(true)?;
-                Ok((Stream::Tcp(stream), Some(addr.ip())))
+                Listener::accept_tcp(stream, addr, ip)
+            }
+            Listener::TcpList(list) => {
+                let (stream, addr) = poll_fn(|cx| {
+                    for tcp in list {
+                        if let Poll::Ready(res) = tcp.poll_accept(cx) {
+                            return Poll::Ready(res);
+                        }
+                    }
+                    Poll::Pending
+                })
+                .await?;
+                Listener::accept_tcp(stream, addr, ip)
             }
             #[cfg(not(target_family = "windows"))]
             Listener::UnixListener(unix) => {
@@ -70,6 +124,16 @@ impl Listener {
             }
         }
     }
+
+    /// The IPv4 client of the IPv6 socket is compared as IPv4
+    fn accept_tcp(stream: TcpStream, addr: SocketAddr, ip: &IpAddr) -> Result<(Stream, Option<IpAddr>), Error> {
+        let peer = addr.ip().to_canonical();
+        if !ip.is_unspecified() && peer != *ip {
+            return Err(Error::new(ErrorKind::Interrupted, "IP address is not spe"));
+        }
+        stream.set_nodelay(true)?;
+        Ok((Stream::Tcp(stream), Some(peer)))
+    }
 }
 
 pub(crate) enum Stream {
@@ -272,8 +336,12 @@ impl StreamRead {
 pub(crate) enum MessageWrite {
     #[cfg(not(feature = "fastcgi"))]
     Message(Vec<u8>),
+    /// Data, the end of the answer and the FastCGI request id
     #[cfg(feature = "fastcgi")]
-    Message(Vec<u8>, bool),
+    Message(Vec<u8>, bool, u16),
+    /// Ready FastCGI record
+    #[cfg(feature = "fastcgi")]
+    Record(Vec<u8>),
     End,
 }
 
@@ -290,13 +358,19 @@ impl StreamWrite {
             while let Some(message) = rx.recv().await {
                 match message {
                     #[cfg(feature = "fastcgi")]
-                    MessageWrite::Message(message, end) => {
-                        let message = FastCGI::write(message, end);
+                    MessageWrite::Message(message, end, request_id) => {
+                        let message = FastCGI::write(message, end, request_id);
 
                         if let Err(_e) = write.write_all(&message).await {
                             log!(warning, 0, "{}", _e);
                         }
                     }
+                    #[cfg(feature = "fastcgi")]
+                    MessageWrite::Record(message) => {
+                        if let Err(_e) = write.write_all(&message).await {
+                            log!(warning, 0, "{}", _e);
+                        }
+                    }
                     #[cfg(not(feature = "fastcgi"))]
                     MessageWrite::Message(message) => {
                         if let Err(_e) = write.write_all(&message).await {
@@ -319,13 +393,25 @@ impl StreamWrite {
         }
     }
 
+    #[cfg(not(feature = "fastcgi"))]
     pub(super) async fn write(&self, data: Vec<u8>) {
-        #[cfg(not(feature = "fastcgi"))]
         if let Err(_e) = self.tx.send(MessageWrite::Message(data)).await {
             log!(warning, 0, "{}", _e);
         }
-        #[cfg(feature = "fastcgi")]
-        if let Err(_e) = self.tx.send(MessageWrite::Message(data, true)).await {
+    }
+
+    /// Write the whole answer of the FastCGI request
+    #[cfg(feature = "fastcgi")]
+    pub(super) async fn write(&self, data: Vec<u8>, request_id: u16) {
+        if let Err(_e) = self.tx.send(MessageWrite::Message(data, true, request_id)).await {
+            log!(warning, 0, "{}", _e);
+        }
+    }
+
+    /// Write the ready FastCGI record
+    #[cfg(feature = "fastcgi")]
+    pub(super) async fn write_record(&self, data: Vec<u8>) {
+        if let Err(_e) = self.tx.send(MessageWrite::Record(data)).await {
             log!(warning, 0, "{}", _e);
         }
     }
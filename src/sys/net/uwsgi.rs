@@ -1,39 +1,30 @@
 use std::{
     cmp::min,
     collections::HashMap,
-    net::IpAddr,
     sync::{atomic::Ordering, Arc},
 };
 
-use percent_encoding::percent_decode_str;
-
 use crate::{
     log,
-    sys::web::{
-        action::ActionData,
-        request::{HttpMethod, HttpVersion, Input, RawData, Request, WebFile},
+    sys::{
+        app::init::UploadConfig,
+        web::{
+            action::ActionData,
+            request::{HttpVersion, Multipart, MultipartError, RawData, Request, UploadProgress, WebFile},
+        },
     },
 };
 
 use super::{
+    parse::{Parse, UWSGI_LEN_PACKAGE_SIZE},
     stream::{StreamError, StreamRead, StreamWrite},
     worker::{Worker, WorkerData},
 };
 
-pub const UWSGI_LEN_PACKAGE_SIZE: usize = 4;
-
-struct UwsgiParam {
-    request: Request,
-    content_len: usize,
-    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-    session: Option<String>,
-}
-
-struct UwsgiArgs {
-    packet_len: usize,
-    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-    session_key: Arc<String>,
-}
+/// Values of modifier1 with the vars block in the packet: WSGI, PSGI, Lua, Rack, JVM, CGI, PHP
+pub const UWSGI_MODIFIER_VARS: [u8; 7] = [0, 5, 6, 7, 8, 9, 14];
+/// Value of modifier1 for the ping request
+pub const UWSGI_MODIFIER_PING: u8 = 100;
 
 /// UWSGI protocol
 pub(super) struct Uwsgi;
@@ -57,42 +48,68 @@ impl Uwsgi {
             online.fetch_add(1, Ordering::Relaxed);
 
             // Check package size
-            let mut buf = stream_read.get(UWSGI_LEN_PACKAGE_SIZE);
-            while buf.len() < UWSGI_LEN_PACKAGE_SIZE {
+            let (modifier1, packet_len, modifier2) = loop {
+                if let Some(header) = Parse::uwsgi_header(stream_read.get(UWSGI_LEN_PACKAGE_SIZE)) {
+                    break header;
+                }
                 if stream_read.read(0).await.is_err() {
                     online.fetch_sub(1, Ordering::Relaxed);
                     return;
                 }
-                buf = stream_read.get(UWSGI_LEN_PACKAGE_SIZE);
+            };
+            stream_read.shift(UWSGI_LEN_PACKAGE_SIZE);
+
+            let block = match Uwsgi::read_block(&mut stream_read, packet_len).await {
+                Some(block) => block,
+                None => {
+                    online.fetch_sub(1, Ordering::Relaxed);
+                    return;
+                }
+            };
+            if modifier1 == UWSGI_MODIFIER_PING && packet_len == 0 {
+                // The answer to the ping is the empty packet
+                stream_write.write([UWSGI_MODIFIER_PING, 0, 0, 0].to_vec()).await;
+                online.fetch_sub(1, Ordering::Relaxed);
+                continue;
             }
-            // Check header
-            if unsafe { *buf.get_unchecked(0) != 0 || *buf.get_unchecked(3) != 0 } {
+            if !UWSGI_MODIFIER_VARS.contains(&modifier1) || modifier2 != 0 {
+                log!(warning, 0, "Unsupported uwsgi modifier1={} modifier2={}", modifier1, modifier2);
                 online.fetch_sub(1, Ordering::Relaxed);
                 return;
             }
-            // Get package length
-            let packet_len = u16::from_le_bytes(unsafe { [*buf.get_unchecked(1), *buf.get_unchecked(2)] }) as usize;
-            stream_read.shift(UWSGI_LEN_PACKAGE_SIZE);
-            // Reads header
-
-            let arg = UwsgiArgs {
-                packet_len,
-                #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-                session_key: Arc::clone(&data.session.session_key),
-            };
 
-            let param = match Uwsgi::read_header(&mut stream_read, arg).await {
+            // Reads header, unknown vars are stored in `Input::params` and are available as `Request::headers`
+            let modifier = modifier1.to_string();
+            let param = match Parse::uwsgi_vars(&block).and_then(|mut vars| {
+                if modifier1 != 0 {
+                    vars.push((b"UWSGI_MODIFIER1", modifier.as_bytes()));
+                }
+                Parse::cgi(&vars, data.session_key())
+            }) {
                 Some(c) => c,
                 None => {
+                    log!(warning, 0, "Invalid uwsgi vars block, size={}", block.len());
+                    stream_write.write(Worker::get_error(HttpVersion::None.get_status(), 400)).await;
                     online.fetch_sub(1, Ordering::Relaxed);
                     return;
                 }
             };
 
             // Reads POST data
-            let (post, file, raw) = match Uwsgi::read_input(&mut stream_read, &param.request.content_type, param.content_len).await {
-                Some(c) => c,
-                None => {
+            let (post, file, raw) = match Uwsgi::read_input(
+                &mut stream_read,
+                &param.request,
+                param.session.as_deref(),
+                param.content_len,
+                &data.upload,
+            )
+            .await
+            {
+                Ok(c) => c,
+                Err(e) => {
+                    if let Some(e) = e {
+                        stream_write.write(Worker::get_error(param.request.version.get_status(), e.code())).await;
+                    }
                     online.fetch_sub(1, Ordering::Relaxed);
                     return;
                 }
@@ -106,11 +123,17 @@ impl Uwsgi {
                 id,
                 mon: Arc::clone(&data.mon),
                 engine: Arc::clone(&data.engine),
+                #[cfg(feature = "plugin")]
+                plugins: Arc::clone(&data.plugins),
+                router: Arc::clone(&data.router),
                 salt: Arc::clone(&data.salt),
                 request,
                 tx: Arc::clone(&stream_write.tx),
                 index: Arc::clone(&data.index),
+                security: Arc::clone(&data.security),
+                queue: Arc::clone(&data.queue),
                 not_found: data.not_found.clone(),
+                action_err: data.action_err.clone(),
                 #[cfg(any(feature = "pgsql", feature = "mssql"))]
                 db: Arc::clone(&data.db),
                 #[cfg(any(feature = "html-static", feature = "html-reload"))]
@@ -121,7 +144,12 @@ impl Uwsgi {
                 session: param.session,
                 #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
                 lang: Arc::clone(&data.lang),
-                #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+                #[cfg(any(
+                    feature = "mail-sendmail",
+                    feature = "mail-smtp",
+                    feature = "mail-file",
+                    feature = "mail-api"
+                ))]
                 mail: Arc::clone(&data.mail),
                 #[cfg(feature = "cache")]
                 cache: Arc::clone(&data.cache),
@@ -142,10 +170,21 @@ impl Uwsgi {
     /// * `HashMap<String, Vec<WebFile>>` - File data.
     async fn read_input(
         stream: &mut StreamRead,
-        content_type: &Option<String>,
+        request: &Request,
+        session: Option<&str>,
         mut content_len: usize,
-    ) -> Option<(HashMap<String, String>, Vec<WebFile>, RawData)> {
-        let mut data = Vec::with_capacity(content_len);
+        upload: &Arc<UploadConfig>,
+    ) -> Result<(HashMap<String, String>, Vec<WebFile>, RawData), Option<MultipartError>> {
+        if content_len > upload.max_body_size {
+            return Err(Some(MultipartError::Limit));
+        }
+        let content_type = request.content_type.as_deref();
+        // The multipart body is parsed as the data comes
+        let mut multipart = Multipart::new(content_type, content_len, UploadProgress::key(request, session), upload);
+        let mut data = match multipart {
+            Some(_) => Vec::new(),
+            None => Vec::with_capacity(content_len),
+        };
         let mut max_read;
         let mut buf;
         let mut buf_len;
@@ -153,263 +192,62 @@ impl Uwsgi {
             max_read = min(content_len, stream.available());
             while max_read == 0 {
                 if stream.read(300).await.is_err() {
-                    return None;
+                    if let Some(multipart) = multipart {
+                        multipart.abort().await;
+                    }
+                    return Err(None);
                 }
                 max_read = min(content_len, stream.available());
             }
             buf = stream.get(max_read);
             buf_len = buf.len();
-            data.extend_from_slice(buf);
+            match &mut multipart {
+                Some(m) => {
+                    if let Err(e) = m.push(buf).await {
+                        log!(warning, 0, "{}", e);
+                        if let Some(multipart) = multipart {
+                            multipart.abort().await;
+                        }
+                        return Err(Some(e));
+                    }
+                }
+                None => data.extend_from_slice(buf),
+            }
             stream.shift(buf_len);
             content_len -= buf_len;
         }
-        Some(Worker::read_input(data, content_type.as_deref()).await)
+        match multipart {
+            Some(multipart) => {
+                let (post, file) = multipart.finish().await;
+                Ok((post, file, RawData::None))
+            }
+            None => match Worker::read_input(data, content_type, upload).await {
+                Ok(input) => Ok(input),
+                Err(e) => {
+                    log!(warning, 0, "{}", e);
+                    Err(Some(e))
+                }
+            },
+        }
     }
 
-    /// Read params from UWSGI header
-    ///
-    /// # Return
-    ///
-    /// * `Request` - Request struct for web engine.
-    /// * `Option<String>` - CONTENT_TYPE parameter for recognizing FASTCGI_STDIN.
-    /// * `Option<String>` - key of session.
-    /// * `usize` - key of session.
-    async fn read_header(stream: &mut StreamRead, mut arg: UwsgiArgs) -> Option<UwsgiParam> {
-        let mut ajax = false;
-        let mut host = String::new();
-        let mut scheme = "https".to_owned();
-        let mut agent = String::new();
-        let mut referer = String::new();
-        let mut ip = None;
-        let mut method = String::new();
-        let mut path = String::new();
-        let mut url = String::new();
-
-        let mut get = HashMap::new();
-        let mut cookie = HashMap::new();
-        let mut content_type = None;
-        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-        let mut session = None;
-
-        let mut content_len = 0;
-        let mut param: Vec<u8> = Vec::with_capacity(1024);
-        let mut is_param = false;
-        let mut param_size = 0;
-        let mut value: Vec<u8> = Vec::with_capacity(1024);
-        let mut is_value = false;
-        let mut value_size = 0;
-        let mut params = HashMap::with_capacity(16);
-
-        let mut max_read;
-        let mut buf;
-        let mut buf_len;
-        while arg.packet_len > 0 {
-            // Reads data to the buffer
-            max_read = min(arg.packet_len, stream.available());
+    /// Reads the vars block of the packet
+    async fn read_block(stream: &mut StreamRead, mut packet_len: usize) -> Option<Vec<u8>> {
+        let mut block = Vec::with_capacity(packet_len);
+        while packet_len > 0 {
+            let mut max_read = min(packet_len, stream.available());
             while max_read == 0 {
                 if stream.read(300).await.is_err() {
                     return None;
                 }
-                max_read = min(arg.packet_len, stream.available());
-            }
-            if !is_param {
-                // Search params
-                if param_size == 0 {
-                    while max_read < 2 {
-                        if stream.read(300).await.is_err() {
-                            return None;
-                        }
-                        max_read = min(arg.packet_len, stream.available());
-                    }
-                    buf = stream.get(2);
-                    param_size = u16::from_le_bytes([unsafe { *buf.get_unchecked(0) }, unsafe { *buf.get_unchecked(1) }]) as usize;
-                    if param_size == 0 {
-                        return None;
-                    }
-                    arg.packet_len -= 2;
-                    stream.shift(2);
-                    continue;
-                }
-                if param_size <= max_read {
-                    buf = stream.get(param_size);
-                    buf_len = buf.len();
-                    param.extend_from_slice(buf);
-                    stream.shift(buf_len);
-                    arg.packet_len -= buf_len;
-                    param_size -= buf_len;
-                    if param_size == 0 {
-                        is_param = true;
-                    }
-                } else {
-                    buf = stream.get(max_read);
-                    buf_len = buf.len();
-                    param.extend_from_slice(buf);
-                    stream.shift(buf_len);
-                    arg.packet_len -= buf_len;
-                    param_size -= buf_len;
-                }
-            } else if !is_value {
-                // Search values
-                if value_size == 0 {
-                    while max_read < 2 {
-                        if stream.read(300).await.is_err() {
-                            return None;
-                        }
-                        max_read = min(arg.packet_len, stream.available());
-                    }
-                    buf = stream.get(2);
-                    value_size = u16::from_le_bytes([unsafe { *buf.get_unchecked(0) }, unsafe { *buf.get_unchecked(1) }]) as usize;
-                    arg.packet_len -= 2;
-                    stream.shift(2);
-                    if value_size == 0 {
-                        is_value = true;
-                    }
-                    continue;
-                }
-                if value_size <= max_read {
-                    buf = stream.get(value_size);
-                    buf_len = buf.len();
-                    value.extend_from_slice(buf);
-                    stream.shift(buf_len);
-                    arg.packet_len -= buf_len;
-                    value_size -= buf_len;
-                    if value_size == 0 {
-                        is_value = true;
-                    }
-                } else {
-                    buf = stream.get(max_read);
-                    buf_len = buf.len();
-                    value.extend_from_slice(buf);
-                    stream.shift(buf_len);
-                    arg.packet_len -= buf_len;
-                    value_size -= buf_len;
-                }
-            } else {
-                let key = param.clone();
-                let val = match String::from_utf8(value.clone()) {
-                    Ok(value) => value,
-                    Err(_) => return None,
-                };
-                match key.as_slice() {
-                    b"CONTENT_LENGTH" => {
-                        if let Ok(c) = val.parse::<usize>() {
-                            content_len = c;
-                        }
-                    }
-                    b"HTTP_X_REQUESTED_WITH" => ajax = val.to_lowercase().eq("xmlhttprequest"),
-                    b"HTTP_HOST" => host = val,
-                    b"REQUEST_SCHEME" => scheme = val,
-                    b"HTTP_USER_AGENT" => agent = val,
-                    b"HTTP_REFERER" => referer = val,
-                    b"REMOTE_ADDR" => {
-                        if let Ok(addr) = val.parse::<IpAddr>() {
-                            ip = Some(addr);
-                        }
-                    }
-                    b"REQUEST_METHOD" => method = val,
-                    b"DOCUMENT_ROOT" => path = val,
-                    b"REDIRECT_URL" => {
-                        if let Some(u) = val.split('?').next() {
-                            if let Ok(u) = percent_decode_str(u).decode_utf8() {
-                                url = u.to_string();
-                            }
-                        }
-                    }
-                    b"QUERY_STRING" => {
-                        if !val.is_empty() {
-                            let gets: Vec<&str> = val.split('&').collect();
-                            get.reserve(gets.len());
-                            for v in gets {
-                                let key: Vec<&str> = v.splitn(2, '=').collect();
-                                match key.len() {
-                                    1 => {
-                                        if let Ok(u) = percent_decode_str(v).decode_utf8() {
-                                            get.insert(u.to_string(), String::new());
-                                        }
-                                    }
-                                    _ => {
-                                        if let Ok(u) = percent_decode_str(unsafe { key.get_unchecked(0) }).decode_utf8() {
-                                            if let Ok(v) = percent_decode_str(unsafe { key.get_unchecked(1) }).decode_utf8() {
-                                                get.insert(u.to_string(), v.to_string());
-                                            }
-                                        }
-                                    }
-                                };
-                            }
-                        }
-                    }
-                    b"CONTENT_TYPE" => content_type = Some(val),
-                    b"HTTP_COOKIE" => {
-                        let cooks: Vec<&str> = val.split("; ").collect();
-                        cookie.reserve(cooks.len());
-                        for v in cooks {
-                            let key: Vec<&str> = v.splitn(2, '=').collect();
-                            #[cfg(not(any(feature = "session-memory", feature = "session-file", feature = "session-db")))]
-                            if key.len() == 2 {
-                                cookie.insert((*unsafe { key.get_unchecked(0) }).to_owned(), (*unsafe { key.get_unchecked(1) }).to_owned());
-                            }
-                            #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-                            if key.len() == 2 {
-                                if unsafe { *key.get_unchecked(0) } == arg.session_key.as_str() {
-                                    let val = unsafe { *key.get_unchecked(1) };
-                                    if val.len() == 128 {
-                                        for b in val.as_bytes() {
-                                            if !((*b > 47 && *b < 58) || (*b > 96 && *b < 103)) {
-                                                continue;
-                                            }
-                                        }
-                                        session = Some(unsafe { *key.get_unchecked(1) }.to_owned());
-                                    }
-                                } else {
-                                    cookie.insert(unsafe { *key.get_unchecked(0) }.to_owned(), unsafe { *key.get_unchecked(1) }.to_owned());
-                                }
-                            }
-                        }
-                    }
-                    _ => {
-                        let key = match String::from_utf8(key) {
-                            Ok(key) => key,
-                            Err(_) => return None,
-                        };
-                        params.insert(key, val);
-                    }
-                }
-                param.clear();
-                is_param = false;
-                value.clear();
-                is_value = false;
+                max_read = min(packet_len, stream.available());
             }
+            let buf = stream.get(max_read);
+            let buf_len = buf.len();
+            block.extend_from_slice(buf);
+            stream.shift(buf_len);
+            packet_len -= buf_len;
         }
-        params.shrink_to_fit();
-        let method = method.parse().unwrap_or(HttpMethod::Get);
-        let site = format!("{}://{}", scheme, host);
-        let request = Request {
-            ajax,
-            host,
-            scheme,
-            agent,
-            referer,
-            ip,
-            method,
-            root: Arc::new(path.into()),
-            url,
-            input: Input {
-                get: Arc::new(get),
-                post: Arc::new(HashMap::new()),
-                file: Arc::new(Vec::new()),
-                cookie: Arc::new(cookie),
-                params: Arc::new(params),
-                raw: Arc::new(RawData::None),
-            },
-            site,
-            version: HttpVersion::None,
-            content_type,
-        };
-        Some(UwsgiParam {
-            request,
-            content_len,
-            #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-            session,
-        })
+        Some(block)
     }
 }
@@ -1,4 +1,7 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, panic::AssertUnwindSafe, sync::Arc, time::Instant};
+
+#[cfg(feature = "file-disk")]
+use std::{cmp::min, io};
 
 #[cfg(feature = "https")]
 use std::io::Error;
@@ -12,31 +15,47 @@ use std::path::PathBuf;
 #[cfg(any(feature = "html-reload", feature = "lang-reload"))]
 use tokio::sync::RwLock;
 
+use futures_util::FutureExt;
 #[cfg(feature = "https")]
 use tokio_rustls::TlsAcceptor;
 
 use crate::{
-    log, log_vv,
+    fnv1a_64, log, log_vv,
     sys::{
+        app::init::{SecurityConfig, UploadConfig},
+        net::queue::RequestQueue,
         stat::stat::Stat,
         web::{
             action::{Action, ActionData, ActionRedirect, ModuleMap},
-            request::{HttpVersion, RawData, WebFile},
+            controller::SizePolicy,
+            request::{HttpMethod, HttpVersion, Multipart, MultipartError, RawData, WebFile},
+            response::ETag,
+            router::Router,
+            timing::Timings,
         },
     },
+    tool::panic_message,
 };
 
-#[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+#[cfg(any(
+    feature = "mail-sendmail",
+    feature = "mail-smtp",
+    feature = "mail-file",
+    feature = "mail-api"
+))]
 use crate::sys::app::init::MailConfig;
 
+#[cfg(any(feature = "http", feature = "https"))]
+use crate::sys::app::init::HttpConfig;
+
 #[cfg(any(feature = "pgsql", feature = "mssql"))]
 use crate::sys::db::adapter::DB;
 
 #[cfg(feature = "cache")]
 use crate::sys::web::cache::Cache;
 
-#[cfg(feature = "file-disk")]
-use crate::sys::web::file::TempFile;
+#[cfg(feature = "plugin")]
+use crate::sys::plugin::Plugins;
 
 #[cfg(any(feature = "html-static", feature = "html-reload"))]
 use crate::sys::web::html::Html;
@@ -47,6 +66,15 @@ use crate::sys::web::lang::Lang;
 #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
 use crate::sys::web::session::SessionLoader;
 
+#[cfg(feature = "file-disk")]
+use tokio::{
+    fs::{remove_file, write, File},
+    io::AsyncReadExt,
+};
+
+#[cfg(feature = "file-disk")]
+use crate::sys::web::file::TempFile;
+
 use super::stream::{MessageWrite, Stream, StreamRead, StreamWrite, BUFFER_SIZE};
 
 #[cfg(feature = "fastcgi")]
@@ -66,6 +94,14 @@ pub(crate) struct WorkerData {
     pub id: u64,
     pub mon: Arc<Stat>,
     pub engine: Arc<ModuleMap>,
+    #[cfg(feature = "plugin")]
+    pub plugins: Arc<Plugins>,
+    pub router: Arc<Router>,
+    #[cfg(any(feature = "http", feature = "https"))]
+    pub http: Arc<HttpConfig>,
+    pub upload: Arc<UploadConfig>,
+    pub security: Arc<SecurityConfig>,
+    pub queue: Arc<RequestQueue>,
     #[cfg(any(feature = "http", feature = "https"))]
     pub root: Arc<PathBuf>,
     pub salt: Arc<String>,
@@ -73,6 +109,7 @@ pub(crate) struct WorkerData {
     pub ip: Option<IpAddr>,
     pub index: Arc<[i64; 3]>,
     pub not_found: Option<Arc<[i64; 3]>>,
+    pub action_err: Option<Arc<[i64; 3]>>,
     #[cfg(any(feature = "pgsql", feature = "mssql"))]
     pub db: Arc<DB>,
     #[cfg(feature = "https")]
@@ -87,12 +124,28 @@ pub(crate) struct WorkerData {
     pub lang: Arc<Lang>,
     #[cfg(feature = "lang-reload")]
     pub lang: Arc<RwLock<Lang>>,
-    #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+    #[cfg(any(
+        feature = "mail-sendmail",
+        feature = "mail-smtp",
+        feature = "mail-file",
+        feature = "mail-api"
+    ))]
     pub mail: Arc<MailConfig>,
     #[cfg(feature = "cache")]
     pub cache: Arc<Cache>,
 }
 
+impl WorkerData {
+    /// Name of the cookie of the session, `None` without the sessions
+    #[cfg(any(feature = "fastcgi", feature = "scgi", feature = "uwsgi"))]
+    pub(crate) fn session_key(&self) -> Option<&str> {
+        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+        return Some(self.session.session_key.as_str());
+        #[cfg(not(any(feature = "session-memory", feature = "session-file", feature = "session-db")))]
+        None
+    }
+}
+
 pub(crate) struct Worker;
 
 impl Worker {
@@ -128,26 +181,65 @@ impl Worker {
         StreamWrite::end(handle, tx).await;
     }
 
-    pub(crate) async fn write(action: &Action, src: Vec<u8>) {
+    pub(crate) async fn write(action: &mut Action, src: Vec<u8>) {
+        // HEAD gets only the headers
+        let head = matches!(action.request.method, HttpMethod::Head);
         let src = if !action.header_send {
+            // The answer of HTTP/1.1 without the length keeps the connection with the chunks
+            action.chunked = action.request.version == HttpVersion::HTTP1_1
+                && !action.response.headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("Content-Length"));
             let mut vec = Worker::get_header(src.len() + 4096, action, None);
-            vec.extend_from_slice(&src);
+            if !head {
+                Worker::chunk(&mut vec, &src, action.chunked);
+            }
+            vec
+        } else if head || src.is_empty() {
+            return;
+        } else if action.chunked {
+            let mut vec = Vec::with_capacity(src.len() + 16);
+            Worker::chunk(&mut vec, &src, true);
             vec
         } else {
             src
         };
+        Worker::send(action, src).await;
+    }
+
+    /// Send the data of the answer as is, the header or the prepared body
+    async fn send(action: &mut Action, src: Vec<u8>) {
+        action.sent += src.len() as u64;
 
         #[cfg(not(feature = "fastcgi"))]
         if let Err(_e) = action.tx.send(MessageWrite::Message(src)).await {
             log!(warning, 0, "{}", _e);
         }
         #[cfg(feature = "fastcgi")]
-        if let Err(_e) = action.tx.send(MessageWrite::Message(src, false)).await {
+        if let Err(_e) = action.tx.send(MessageWrite::Message(src, false, action.request_id)).await {
             log!(warning, 0, "{}", _e);
         }
     }
 
-    pub(crate) async fn read_input(data: Vec<u8>, content_type: Option<&str>) -> (HashMap<String, String>, Vec<WebFile>, RawData) {
+    /// Add the data to the answer, as the chunk of "Transfer-Encoding: chunked" if `chunked`
+    ///
+    /// The empty chunk ends the answer, so the empty data is skipped.
+    fn chunk(answer: &mut Vec<u8>, data: &[u8], chunked: bool) {
+        if !chunked {
+            answer.extend_from_slice(data);
+        } else if !data.is_empty() {
+            answer.extend_from_slice(format!("{:x}\r\n", data.len()).as_bytes());
+            answer.extend_from_slice(data);
+            answer.extend_from_slice(b"\r\n");
+        }
+    }
+
+    pub(crate) async fn read_input(
+        data: Vec<u8>,
+        content_type: Option<&str>,
+        upload: &Arc<UploadConfig>,
+    ) -> Result<(HashMap<String, String>, Vec<WebFile>, RawData), MultipartError> {
+        if data.len() > upload.max_body_size {
+            return Err(MultipartError::Limit);
+        }
         let mut post = HashMap::new();
         let mut file = Vec::new();
         let mut raw = RawData::None;
@@ -168,95 +260,20 @@ impl Worker {
                         }
                     }
                 }
-            } else if c.len() > 30 {
+            } else if let Some(mut multipart) = Multipart::new(Some(c), data.len(), None, upload) {
                 // Multi post with files
-                if let "multipart/form-data; boundary=" = &c[..30] {
-                    let boundary = format!("--{}", &c[30..]);
-                    let stop: [u8; 4] = [13, 10, 13, 10];
-                    if !data.is_empty() {
-                        let mut seek: usize = 0;
-                        let mut start: usize;
-                        let b_len = boundary.len();
-                        let len = data.len() - 4;
-                        let mut found: Option<(usize, &str)> = None;
-                        while seek < len {
-                            // Find a boundary
-                            if boundary.as_bytes() == &data[seek..seek + b_len] {
-                                if seek + b_len == len {
-                                    if let Some((l, h)) = found {
-                                        let d = &data[l..seek - 2];
-                                        Worker::get_post_file(h, d.to_vec(), &mut post, &mut file).await;
-                                    };
-                                    break;
-                                }
-                                seek += b_len + 2;
-                                start = seek;
-                                while seek < len {
-                                    if stop == data[seek..seek + 4] {
-                                        if let Ok(s) = std::str::from_utf8(&data[start..seek]) {
-                                            if let Some((l, h)) = found {
-                                                let d = &data[l..start - b_len - 4];
-                                                Worker::get_post_file(h, d.to_vec(), &mut post, &mut file).await;
-                                            };
-                                            found = Some((seek + 4, s));
-                                        }
-                                        seek += 4;
-                                        break;
-                                    } else {
-                                        seek += 1;
-                                    }
-                                }
-                            } else {
-                                seek += 1;
-                            }
-                        }
-                    }
-                } else {
-                    raw = RawData::Raw(data);
+                if let Err(e) = multipart.push(&data).await {
+                    multipart.abort().await;
+                    return Err(e);
                 }
+                (post, file) = multipart.finish().await;
             } else {
                 raw = RawData::Raw(data);
             }
         } else if !data.is_empty() {
             raw = RawData::Raw(data)
         }
-        (post, file, raw)
-    }
-
-    async fn get_post_file(header: &str, data: Vec<u8>, post: &mut HashMap<String, String>, file: &mut Vec<WebFile>) {
-        let parts: Vec<&str> = header.splitn(3, "; ").collect();
-        let len = parts.len();
-        // Post data found
-        if len == 2 {
-            if let Ok(value) = std::str::from_utf8(&data) {
-                let name = unsafe { *parts.get_unchecked(1) };
-                let name = &name[6..name.len() - 1];
-                post.insert(name.to_owned(), value.to_owned());
-            }
-        } else if len == 3 {
-            // File data found
-            let form = unsafe { *parts.get_unchecked(1) };
-            let form = form[6..form.len() - 1].to_owned();
-            let name = unsafe { *parts.get_unchecked(2) };
-            let name: Vec<&str> = name.splitn(2, "\r\n").collect();
-            let name = &name[0][10..name[0].len() - 1];
-
-            #[cfg(feature = "file-disk")]
-            let path = TempFile::new_name();
-            #[cfg(feature = "file-disk")]
-            if TempFile::write(&path, &data).await.is_err() {
-                return;
-            }
-            file.push(WebFile {
-                name: form.to_owned(),
-                file: name.to_owned(),
-                size: data.len(),
-                #[cfg(feature = "file-disk")]
-                tmp: path,
-                #[cfg(feature = "file-memory")]
-                data,
-            });
-        }
+        Ok((post, file, raw))
     }
 
     #[cfg(feature = "https")]
@@ -312,23 +329,71 @@ impl Worker {
         #[cfg(any(feature = "html-reload", feature = "lang-reload"))]
         Worker::reload(&data).await;
 
+        let mut timings = Timings::new();
+        let start = Instant::now();
         let status = data.request.version.get_status();
+        let mon = Arc::clone(&data.mon);
+        let bytes_in = data
+            .request
+            .input
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, len)| len.parse().ok())
+            .unwrap_or(0);
+        let queue = Arc::clone(&data.queue);
+        let _slot = match queue.acquire(queue.priority(&data.request), &data.mon).await {
+            Some(slot) => slot,
+            None => {
+                log_vv!(warning, 0, "Async thread: {}. Queue is full {}", id, data.request.url);
+                let answer = Worker::get_error(status, 503);
+                mon.add_request(None, 503, bytes_in, answer.len() as u64, start.elapsed());
+                return answer;
+            }
+        };
+        timings.mark("queue");
         #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
         let session = Arc::clone(&data.session_loader);
-        let answer = match Action::init(data).await {
+        let answer = match Action::init(data, timings).await {
             Ok(ActionRedirect::Action(mut action)) => {
-                let result = Action::run(&mut action).await;
-
-                let result = if !action.header_send {
-                    // + Status + Cookie + Keep-alive + Content-Type + Content-Length + headers
-                    // max length
-                    let capacity = result.len() + 4096;
-                    let mut answer = Worker::get_header(capacity, &action, Some(result.len()));
-                    answer.extend_from_slice(&result);
-                    answer
+                let result = match AssertUnwindSafe(Action::run(&mut action)).catch_unwind().await {
+                    Ok(result) => result,
+                    Err(panic) => Action::panic_page(&mut action, panic_message(&*panic)).await,
+                };
+                action.monitor.add_timings(&action.timings);
+                let result = Worker::conditional(&mut action, result);
+
+                let result = if action.header_send {
+                    // The last chunk of the answer
+                    if action.chunked && !matches!(action.request.method, HttpMethod::Head) {
+                        b"0\r\n\r\n".to_vec()
+                    } else {
+                        Vec::new()
+                    }
                 } else {
-                    Vec::new()
+                    match Worker::limit_size(&mut action, result).await {
+                        Some(result) => {
+                            // + Status + Cookie + Keep-alive + Content-Type + Content-Length + headers
+                            // max length
+                            let capacity = result.len() + 4096;
+                            // 204 and 304 have no body and no length
+                            let len = match action.response.http_code {
+                                Some(204) | Some(304) => None,
+                                _ => Some(result.len()),
+                            };
+                            let mut answer = Worker::get_header(capacity, &action, len);
+                            // HEAD has the length of the body of GET without the body
+                            if !matches!(action.request.method, HttpMethod::Head) {
+                                answer.extend_from_slice(&result);
+                            }
+                            answer
+                        }
+                        None => Vec::new(),
+                    }
                 };
+                let code = action.response.http_code.unwrap_or(if action.response.redirect.is_some() { 302 } else { 200 });
+                let route = action.route_path();
+                mon.add_request(Some(&route), code, bytes_in, action.sent + result.len() as u64, action.timings.elapsed());
                 #[cfg(any(
                     feature = "file-disk",
                     feature = "session-memory",
@@ -337,28 +402,30 @@ impl Worker {
                 ))]
                 tokio::spawn(async move {
                     #[cfg(feature = "file-disk")]
-                    Action::clean_file(&action.request.input.file).await;
+                    Action::clean_file(Arc::clone(&action.request.input.file)).await;
                     #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-                    let _ = session.save(action.session).await;
+                    if action.session_write {
+                        let _ = session.save(action.session).await;
+                    }
                 });
                 result
             }
-            #[cfg(feature = "redirect-db")]
             Ok(ActionRedirect::Redirect(redirect)) => {
                 // Write status
                 let mut answer = Vec::with_capacity(512);
-                if redirect.permanently {
-                    answer.extend_from_slice(
-                        format!("{status} 301 {}\r\nLocation: {}\r\n\r\n", Worker::http_code_get(301), redirect.url).as_bytes(),
-                    );
-                } else {
-                    answer.extend_from_slice(
-                        format!("{status} 302 {}\r\nLocation: {}\r\n\r\n", Worker::http_code_get(302), redirect.url).as_bytes(),
-                    );
-                }
+                let code = if redirect.permanently { 301 } else { 302 };
+                answer.extend_from_slice(
+                    format!("{status} {code} {}\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n", Worker::http_code_get(code), redirect.url)
+                        .as_bytes(),
+                );
+                mon.add_request(None, code, bytes_in, answer.len() as u64, start.elapsed());
+                answer
+            }
+            Err(_) => {
+                let answer = Worker::get_500(status);
+                mon.add_request(None, 500, bytes_in, answer.len() as u64, start.elapsed());
                 answer
             }
-            Err(_) => Worker::get_500(status),
         };
 
         #[cfg(any(feature = "debug-vv", feature = "debug-vvv"))]
@@ -368,9 +435,116 @@ impl Worker {
         answer
     }
 
+    /// ETag of the hash of the answer and `304 Not Modified` without the body for the same version of the client
+    fn conditional(action: &mut Action, result: Vec<u8>) -> Vec<u8> {
+        if action.header_send {
+            return result;
+        }
+        if action.response.http_code == Some(304) {
+            return Vec::new();
+        }
+        if action.response.etag.is_none() && action.security.etag {
+            action.response.etag = Some(ETag::Hash);
+        }
+        if let Some(ETag::Hash) = action.response.etag {
+            action.response.etag = match action.request.method {
+                HttpMethod::Get | HttpMethod::Head if !result.is_empty() => {
+                    Some(ETag::Weak(format!("{:x}-{:x}", result.len(), fnv1a_64(&result))))
+                }
+                _ => None,
+            };
+        }
+        if action.not_modified() {
+            return Vec::new();
+        }
+        result
+    }
+
+    /// Apply `Response::max_size` to the answer, `None` if the answer is already sent by parts
+    async fn limit_size(action: &mut Action, mut result: Vec<u8>) -> Option<Vec<u8>> {
+        let max_size = match action.response.max_size {
+            Some(max_size) if result.len() > max_size => max_size,
+            _ => return Some(result),
+        };
+        log!(warning, 0, "Answer {} bytes is larger than {} bytes. Url: {}", result.len(), max_size, action.request.url);
+        match action.response.size_policy {
+            SizePolicy::Error => {
+                action.response.http_code = Some(500);
+                Some(Vec::new())
+            }
+            SizePolicy::Truncate => {
+                action.response.headers.push(("Warning".to_owned(), format!("199 - \"Response truncated from {} bytes\"", result.len())));
+                result.truncate(max_size);
+                Some(result)
+            }
+            #[cfg(feature = "file-disk")]
+            SizePolicy::Disk => Worker::send_disk(action, result).await,
+        }
+    }
+
+    /// Move the large answer to the temporary file and send it by parts
+    ///
+    /// Returns the answer back if the temporary file cannot be written.
+    #[cfg(feature = "file-disk")]
+    async fn send_disk(action: &mut Action, result: Vec<u8>) -> Option<Vec<u8>> {
+        let path = TempFile::new_name();
+        if let Err(_e) = write(&path, &result).await {
+            log!(warning, 0, "{}. Error: {}", path.display(), _e);
+            return Some(result);
+        }
+        let len = result.len();
+        drop(result);
+        let mut file = match File::open(&path).await {
+            Ok(file) => file,
+            Err(_e) => {
+                log!(warning, 0, "{}. Error: {}", path.display(), _e);
+                let _ = remove_file(&path).await;
+                action.response.http_code = Some(500);
+                return Some(Vec::new());
+            }
+        };
+        if let Err(_e) = Worker::send_file(action, &mut file, len as u64).await {
+            log!(warning, 0, "{}. Error: {}", path.display(), _e);
+        }
+        if let Err(_e) = remove_file(&path).await {
+            log!(warning, 0, "{}. Error: {}", path.display(), _e);
+        }
+        None
+    }
+
+    /// Send the header with the length and `len` bytes of the file from the current position by parts
+    ///
+    /// HEAD gets only the header. After the error of the reading the answer is already started and is incomplete.
+    #[cfg(feature = "file-disk")]
+    pub(crate) async fn send_file(action: &mut Action, file: &mut File, len: u64) -> io::Result<()> {
+        let header = Worker::get_header(4096, action, Some(len as usize));
+        Worker::send(action, header).await;
+        action.header_send = true;
+        if matches!(action.request.method, HttpMethod::Head) {
+            return Ok(());
+        }
+        let mut buf = vec![0; BUFFER_SIZE];
+        let mut left = len;
+        while left > 0 {
+            let size = file.read(&mut buf[..min(left, BUFFER_SIZE as u64) as usize]).await?;
+            if size == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            Worker::write(action, buf[..size].to_vec()).await;
+            left -= size as u64;
+        }
+        Ok(())
+    }
+
     #[inline]
     pub(crate) fn get_500(status: &str) -> Vec<u8> {
-        format!("{status} 500 {}\r\n\r\n", Worker::http_code_get(500)).as_bytes().to_vec()
+        Worker::get_error(status, 500)
+    }
+
+    /// Answer with the error code only, for example 413 Payload Too Large
+    #[inline]
+    pub(crate) fn get_error(status: &str, code: u16) -> Vec<u8> {
+        format!("{status} {code} {}\r\nContent-Length: 0\r\n\r\n", Worker::http_code_get(code)).as_bytes().to_vec()
     }
 
     /// Reload lang and template
@@ -408,12 +582,53 @@ impl Worker {
             Some(content_type) => answer.extend_from_slice(format!("Content-Type: {}\r\n", content_type).as_bytes()),
             None => answer.extend_from_slice(b"Content-Type: text/html; charset=utf-8\r\n"),
         }
-        answer.extend_from_slice(b"Connection: Keep-Alive\r\n");
+        if action.request.close {
+            answer.extend_from_slice(b"Connection: close\r\n");
+        } else {
+            answer.extend_from_slice(b"Connection: Keep-Alive\r\n");
+        }
+        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+        let session_cookie = action.session.cookie(&action.session_key);
+        if let Some(cache) = &action.response.cache {
+            #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+            let personal = !action.response.cookies.is_empty() || session_cookie.is_some() || action.is_personal();
+            #[cfg(not(any(feature = "session-memory", feature = "session-file", feature = "session-db")))]
+            let personal = !action.response.cookies.is_empty() || action.is_personal();
+            if personal && cache.is_public() {
+                log!(warning, 0, "Cache-Control public for the personal answer {}", action.request.url);
+            }
+            answer.extend_from_slice(format!("Cache-Control: {}\r\n", cache.header(personal)).as_bytes());
+        }
+        for (name, val) in action.security.headers(action.csp_nonce()) {
+            if !action.response.headers.iter().any(|(key, _)| key.eq_ignore_ascii_case(name)) {
+                answer.extend_from_slice(format!("{}: {}\r\n", name, val).as_bytes());
+            }
+        }
+        if action.security.server_timing {
+            answer.extend_from_slice(format!("Server-Timing: {}\r\n", action.timings.header()).as_bytes());
+        }
+        if let Some(etag) = action.response.etag.as_ref().and_then(ETag::header) {
+            answer.extend_from_slice(format!("ETag: {}\r\n", etag).as_bytes());
+        }
+        if let Some(modified) = &action.response.last_modified {
+            answer.extend_from_slice(format!("Last-Modified: {}\r\n", modified.format("%a, %d %b %Y %H:%M:%S GMT")).as_bytes());
+        }
         for (name, val) in &action.response.headers {
             answer.extend_from_slice(format!("{}: {}\r\n", name, val).as_bytes());
         }
+        for cookie in &action.response.cookies {
+            answer.extend_from_slice(format!("Set-Cookie: {}\r\n", cookie.header()).as_bytes());
+        }
+        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+        if let Some(cookie) = session_cookie {
+            if !action.response.cookies.iter().any(|item| item.name == cookie.name) {
+                answer.extend_from_slice(format!("Set-Cookie: {}\r\n", cookie.header()).as_bytes());
+            }
+        }
         if let Some(len) = content_length {
             answer.extend_from_slice(format!("Content-Length: {}\r\n", len).as_bytes());
+        } else if action.chunked {
+            answer.extend_from_slice(b"Transfer-Encoding: chunked\r\n");
         }
         answer.extend_from_slice(b"\r\n");
 
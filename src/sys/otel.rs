@@ -0,0 +1,367 @@
+use std::{
+    future::Future,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::mpsc::{channel, Receiver, Sender},
+    time::timeout,
+};
+
+use crate::{
+    log,
+    tool::{from_hex, to_hex},
+};
+
+use super::{app::init::OtelConfig, net::eyeballs::HappyEyeballs};
+
+/// Number of the spans in the queue of the exporter, the other spans are dropped
+const OTEL_QUEUE: usize = 8192;
+/// Max number of the spans in the one export
+const OTEL_BATCH: usize = 512;
+/// Interval of the export
+const OTEL_INTERVAL: Duration = Duration::from_secs(1);
+/// Timeout of the export request
+const OTEL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Queue of the exporter, set when the section [otel] has the endpoint
+static EXPORTER: OnceLock<Sender<SpanData>> = OnceLock::new();
+
+tokio::task_local! {
+    /// Span of the current request
+    static CONTEXT: TraceContext;
+}
+
+/// W3C trace context of the span
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+}
+
+impl TraceContext {
+    /// Context of the `traceparent` header, for example "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+    pub fn parse(header: &str) -> Option<TraceContext> {
+        let mut parts = header.trim().split('-');
+        if parts.next()?.len() != 2 {
+            return None;
+        }
+        let trace_id = from_hex(parts.next()?)?.try_into().ok()?;
+        let span_id = from_hex(parts.next()?)?.try_into().ok()?;
+        if trace_id == [0; 16] || span_id == [0; 8] {
+            return None;
+        }
+        Some(TraceContext { trace_id, span_id })
+    }
+
+    /// Value of the `traceparent` header for the outgoing requests
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-01", to_hex(&self.trace_id), to_hex(&self.span_id))
+    }
+
+    /// Context of the current request
+    pub fn current() -> Option<TraceContext> {
+        CONTEXT.try_with(|context| *context).ok()
+    }
+}
+
+/// Value of the attribute of the span
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttrValue {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl From<&str> for AttrValue {
+    fn from(value: &str) -> AttrValue {
+        AttrValue::String(value.to_owned())
+    }
+}
+
+impl From<String> for AttrValue {
+    fn from(value: String) -> AttrValue {
+        AttrValue::String(value)
+    }
+}
+
+impl From<i64> for AttrValue {
+    fn from(value: i64) -> AttrValue {
+        AttrValue::Int(value)
+    }
+}
+
+impl From<u16> for AttrValue {
+    fn from(value: u16) -> AttrValue {
+        AttrValue::Int(i64::from(value))
+    }
+}
+
+impl From<usize> for AttrValue {
+    fn from(value: usize) -> AttrValue {
+        AttrValue::Int(i64::try_from(value).unwrap_or(i64::MAX))
+    }
+}
+
+impl From<f64> for AttrValue {
+    fn from(value: f64) -> AttrValue {
+        AttrValue::Float(value)
+    }
+}
+
+impl From<bool> for AttrValue {
+    fn from(value: bool) -> AttrValue {
+        AttrValue::Bool(value)
+    }
+}
+
+/// Kind of the span in OTLP
+#[derive(Debug, Clone, Copy)]
+enum SpanKind {
+    Internal = 1,
+    Server = 2,
+    Client = 3,
+}
+
+#[derive(Debug)]
+struct SpanData {
+    context: TraceContext,
+    parent: Option<[u8; 8]>,
+    name: String,
+    kind: SpanKind,
+    /// Unix time of the start, nanoseconds
+    start_time: u64,
+    start: Instant,
+    end_time: u64,
+    attributes: Vec<(String, AttrValue)>,
+    error: Option<String>,
+}
+
+/// Span of the trace, sent to the collector on drop
+///
+/// # Example
+///
+/// ```ignore
+/// let mut span = Span::start("report.build");
+/// span.attr("report.rows", rows.len());
+/// ```
+///
+/// The span is the child of the current request. Without the section [otel] the span does nothing.
+#[derive(Debug)]
+pub struct Span {
+    data: Option<SpanData>,
+}
+
+impl Span {
+    /// Span of the current request or the new trace
+    pub fn start(name: &str) -> Span {
+        Span::new(name, SpanKind::Internal, TraceContext::current(), true)
+    }
+
+    /// Span of the request to the database or the other service, only inside the request
+    pub fn client(name: &str) -> Span {
+        Span::new(name, SpanKind::Client, TraceContext::current(), false)
+    }
+
+    /// Inner operation of the request, only inside the request
+    pub fn internal(name: &str) -> Span {
+        Span::new(name, SpanKind::Internal, TraceContext::current(), false)
+    }
+
+    /// Span of the incoming request, the parent is the `traceparent` of the client
+    pub(crate) fn server(name: &str, parent: Option<TraceContext>) -> Span {
+        Span::new(name, SpanKind::Server, parent, true)
+    }
+
+    fn new(name: &str, kind: SpanKind, parent: Option<TraceContext>, root: bool) -> Span {
+        if EXPORTER.get().is_none() || (parent.is_none() && !root) {
+            return Span { data: None };
+        }
+        let context = TraceContext {
+            trace_id: match parent {
+                Some(parent) => parent.trace_id,
+                None => Otel::random(),
+            },
+            span_id: Otel::random(),
+        };
+        Span {
+            data: Some(SpanData {
+                context,
+                parent: parent.map(|parent| parent.span_id),
+                name: name.to_owned(),
+                kind,
+                start_time: Otel::now(),
+                start: Instant::now(),
+                end_time: 0,
+                attributes: Vec::new(),
+                error: None,
+            }),
+        }
+    }
+
+    /// Add the attribute, for example `span.attr("db.system", "postgresql")`
+    pub fn attr(&mut self, key: &str, value: impl Into<AttrValue>) {
+        if let Some(data) = &mut self.data {
+            data.attributes.push((key.to_owned(), value.into()));
+        }
+    }
+
+    /// Mark the span as failed
+    pub fn error(&mut self, message: &str) {
+        if let Some(data) = &mut self.data {
+            data.error = Some(message.to_owned());
+        }
+    }
+
+    /// Context for the child spans and the `traceparent` of the outgoing requests
+    pub fn context(&self) -> Option<TraceContext> {
+        self.data.as_ref().map(|data| data.context)
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let (Some(mut data), Some(exporter)) = (self.data.take(), EXPORTER.get()) {
+            data.end_time = data.start_time + u64::try_from(data.start.elapsed().as_nanos()).unwrap_or(0);
+            // The full queue drops the span, the request is not delayed by the collector
+            let _ = exporter.try_send(data);
+        }
+    }
+}
+
+/// Export of the spans to the OpenTelemetry collector by OTLP/HTTP with JSON encoding
+pub(crate) struct Otel;
+
+impl Otel {
+    /// Start the exporter, only once
+    pub(crate) fn start(config: Arc<OtelConfig>) {
+        let (tx, rx) = channel(OTEL_QUEUE);
+        if EXPORTER.set(tx).is_ok() {
+            tokio::spawn(Otel::export(config, rx));
+        }
+    }
+
+    /// Run the future inside the span of the request
+    pub(crate) async fn scope<F: Future>(context: Option<TraceContext>, future: F) -> F::Output {
+        match context {
+            Some(context) => CONTEXT.scope(context, future).await,
+            None => future.await,
+        }
+    }
+
+    async fn export(config: Arc<OtelConfig>, mut rx: Receiver<SpanData>) {
+        let mut batch = Vec::with_capacity(OTEL_BATCH);
+        let mut last = Instant::now();
+        loop {
+            match timeout(OTEL_INTERVAL, rx.recv()).await {
+                Ok(Some(span)) => batch.push(span),
+                Ok(None) => break,
+                Err(_) => {}
+            }
+            if batch.len() >= OTEL_BATCH || (!batch.is_empty() && last.elapsed() >= OTEL_INTERVAL) {
+                let body = Otel::encode(&config, &batch).to_string();
+                batch.clear();
+                last = Instant::now();
+                if let Err(_e) = Otel::post(&config, &body).await {
+                    log!(warning, 0, "OpenTelemetry {}:{}{}. Error: {}", config.host, config.port, config.path, _e);
+                }
+            }
+        }
+    }
+
+    /// ExportTraceServiceRequest in JSON
+    fn encode(config: &OtelConfig, batch: &[SpanData]) -> Value {
+        let spans: Vec<Value> = batch
+            .iter()
+            .map(|span| {
+                let mut value = json!({
+                    "traceId": to_hex(&span.context.trace_id),
+                    "spanId": to_hex(&span.context.span_id),
+                    "name": span.name,
+                    "kind": span.kind as u8,
+                    "startTimeUnixNano": span.start_time.to_string(),
+                    "endTimeUnixNano": span.end_time.to_string(),
+                    "attributes": Otel::attributes(&span.attributes),
+                });
+                if let Some(parent) = &span.parent {
+                    value["parentSpanId"] = Value::String(to_hex(parent));
+                }
+                if let Some(error) = &span.error {
+                    value["status"] = json!({ "code": 2, "message": error });
+                }
+                value
+            })
+            .collect();
+        json!({
+            "resourceSpans": [{
+                "resource": { "attributes": Otel::attributes(&[("service.name".to_owned(), AttrValue::String(config.service.clone()))]) },
+                "scopeSpans": [{
+                    "scope": { "name": "tiny-web", "version": env!("CARGO_PKG_VERSION") },
+                    "spans": spans,
+                }],
+            }],
+        })
+    }
+
+    fn attributes(list: &[(String, AttrValue)]) -> Value {
+        Value::Array(
+            list.iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        AttrValue::String(value) => json!({ "stringValue": value }),
+                        // int64 is the string in the JSON encoding of OTLP
+                        AttrValue::Int(value) => json!({ "intValue": value.to_string() }),
+                        AttrValue::Float(value) => json!({ "doubleValue": value }),
+                        AttrValue::Bool(value) => json!({ "boolValue": value }),
+                    };
+                    json!({ "key": key, "value": value })
+                })
+                .collect(),
+        )
+    }
+
+    /// Simple HTTP/1.1 POST request
+    async fn post(config: &OtelConfig, body: &str) -> Result<(), String> {
+        timeout(OTEL_TIMEOUT, async {
+            let mut stream = HappyEyeballs::connect(&config.host, config.port).await.map_err(|e| e.to_string())?;
+            let request = format!(
+                "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                config.path,
+                config.host,
+                config.port,
+                body.len(),
+                body
+            );
+            stream.write_all(request.as_bytes()).await.map_err(|e| e.to_string())?;
+            let mut buf = [0u8; 1024];
+            let len = stream.read(&mut buf).await.map_err(|e| e.to_string())?;
+            let status = String::from_utf8_lossy(&buf[..len]);
+            match status.split(' ').nth(1) {
+                Some(code) if code.starts_with('2') => Ok(()),
+                _ => Err(status.lines().next().unwrap_or_default().to_owned()),
+            }
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|time| u64::try_from(time.as_nanos()).unwrap_or(0)).unwrap_or(0)
+    }
+
+    fn random<const N: usize>() -> [u8; N] {
+        let mut bytes = [0u8; N];
+        if SystemRandom::new().fill(&mut bytes).is_err() {
+            let now = Otel::now().to_be_bytes();
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = now[i % now.len()] ^ (i as u8);
+            }
+        }
+        bytes
+    }
+}
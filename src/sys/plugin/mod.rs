@@ -1 +1,212 @@
+use std::{
+    ffi::{c_void, CStr, CString},
+    mem,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
+use tokio::{fs::read_dir, sync::RwLock};
+
+use crate::{
+    fnv1a_64, log,
+    sys::web::action::{Act, Action, ModuleMap},
+};
+
+/// Version of the plugin ABI, changes with the incompatible changes of the plugin interface
+pub const PLUGIN_ABI: u32 = 1;
+
+/// Exported function with the ABI of the plugin
+const SYMBOL_ABI: &[u8] = b"tiny_web_plugin_abi\0";
+
+/// Exported function with the registration of the controllers
+const SYMBOL_REGISTER: &[u8] = b"tiny_web_plugin\0";
+
+/// Folder of the plugins in the root folder
+const PLUGIN_DIR: &str = "plugin";
+
+/// Controllers of the plugin
+///
+/// # Example
+///
+/// The plugin is the `cdylib` crate with the same version of tiny-web, the same features and the same compiler:
+///
+/// ```ignore
+/// fn register(plugin: &mut PluginRegistrar) {
+///     plugin.add("blog", "post", "index", |this| Box::pin(post::index(this)));
+/// }
+///
+/// tiny_web::plugin!(register);
+/// ```
+#[derive(Default)]
+pub struct PluginRegistrar {
+    modules: ModuleMap,
+}
+
+impl PluginRegistrar {
+    /// Add the controller "/module/class/action"
+    pub fn add(&mut self, module: &str, class: &str, action: &str, act: Act) {
+        self.modules
+            .entry(fnv1a_64(module.as_bytes()))
+            .or_default()
+            .entry(fnv1a_64(class.as_bytes()))
+            .or_default()
+            .insert(fnv1a_64(action.as_bytes()), act);
+    }
+
+    /// Hash of the ABI, the tiny-web version and the layout of `Action`
+    ///
+    /// The layout of `Action` depends on the features, so the plugin and the server must be built with the same features.
+    #[doc(hidden)]
+    pub fn abi() -> i64 {
+        fnv1a_64(format!("{}:{}:{}", PLUGIN_ABI, env!("CARGO_PKG_VERSION"), mem::size_of::<Action>()).as_bytes())
+    }
+}
+
+/// Export the registration function of the plugin
+#[macro_export]
+macro_rules! plugin {
+    ($func:path) => {
+        #[no_mangle]
+        pub extern "C" fn tiny_web_plugin_abi() -> i64 {
+            $crate::sys::plugin::PluginRegistrar::abi()
+        }
+
+        #[no_mangle]
+        pub fn tiny_web_plugin(registrar: &mut $crate::sys::plugin::PluginRegistrar) {
+            $func(registrar)
+        }
+    };
+}
+
+/// Handle of the loaded library, the library is unloaded on drop
+#[derive(Debug)]
+struct Library {
+    handle: *mut c_void,
+}
+
+// The handle is only used by dlsym and dlclose, which are thread-safe
+unsafe impl Send for Library {}
+unsafe impl Sync for Library {}
+
+impl Library {
+    fn open(path: &Path) -> Result<Library, String> {
+        let name = CString::new(path.as_os_str().as_bytes()).map_err(|e| e.to_string())?;
+        let handle = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            return Err(Library::error());
+        }
+        Ok(Library { handle })
+    }
+
+    fn symbol(&self, name: &[u8]) -> Result<*mut c_void, String> {
+        let symbol = unsafe { libc::dlsym(self.handle, name.as_ptr() as *const libc::c_char) };
+        if symbol.is_null() {
+            return Err(Library::error());
+        }
+        Ok(symbol)
+    }
+
+    fn error() -> String {
+        let error = unsafe { libc::dlerror() };
+        if error.is_null() {
+            return "Unknown error".to_owned();
+        }
+        unsafe { CStr::from_ptr(error) }.to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        unsafe {
+            libc::dlclose(self.handle);
+        }
+    }
+}
+
+/// Loaded plugin
+///
+/// The running controller holds the plugin, so the library is unloaded after the last request.
+#[derive(Debug)]
+pub(crate) struct Plugin {
+    /// Controllers of the plugin, dropped before the library
+    modules: ModuleMap,
+    _lib: Library,
+}
+
+impl Plugin {
+    fn load(path: &Path) -> Result<Plugin, String> {
+        let lib = Library::open(path)?;
+        let abi: extern "C" fn() -> i64 = unsafe { mem::transmute(lib.symbol(SYMBOL_ABI)?) };
+        if abi() != PluginRegistrar::abi() {
+            return Err("Incompatible ABI of the plugin".to_owned());
+        }
+        let register: fn(&mut PluginRegistrar) = unsafe { mem::transmute(lib.symbol(SYMBOL_REGISTER)?) };
+        let mut registrar = PluginRegistrar::default();
+        register(&mut registrar);
+        Ok(Plugin { modules: registrar.modules, _lib: lib })
+    }
+
+    fn get(&self, module_id: i64, class_id: i64, action_id: i64) -> Option<Act> {
+        self.modules.get(&module_id).and_then(|m| m.get(&class_id)).and_then(|c| c.get(&action_id)).copied()
+    }
+}
+
+/// Controllers loaded from the dynamic libraries of the folder "plugin"
+///
+/// The controllers compiled in by `addfn!` have the priority.
+/// The plugins are loaded at the start and reloaded by the command "plugin".
+#[derive(Debug)]
+pub(crate) struct Plugins {
+    path: PathBuf,
+    list: RwLock<Vec<Arc<Plugin>>>,
+}
+
+impl Plugins {
+    pub(crate) fn new(root: &Path) -> Plugins {
+        Plugins {
+            path: root.join(PLUGIN_DIR),
+            list: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Load all plugins of the folder and replace the previous plugins, returns the number of the plugins
+    ///
+    /// A plugin that fails to load is skipped.
+    pub(crate) async fn load(&self) -> usize {
+        let mut files = Vec::new();
+        match read_dir(&self.path).await {
+            Ok(mut dir) => {
+                while let Ok(Some(entry)) = dir.next_entry().await {
+                    let path = entry.path();
+                    if path.extension().is_some_and(|ext| ext == std::env::consts::DLL_EXTENSION) {
+                        files.push(path);
+                    }
+                }
+            }
+            Err(_e) => log!(warning, 0, "{:?}. Error: {}", self.path, _e),
+        }
+        files.sort();
+
+        let mut list = Vec::with_capacity(files.len());
+        for file in files {
+            match Plugin::load(&file) {
+                Ok(plugin) => list.push(Arc::new(plugin)),
+                Err(_e) => log!(warning, 0, "Неможливо завантажити плагін {:?}. Помилка: {}", file, _e),
+            }
+        }
+        let len = list.len();
+        *self.list.write().await = list;
+        len
+    }
+
+    /// Controller and its plugin
+    pub(crate) async fn get(&self, module_id: i64, class_id: i64, action_id: i64) -> Option<(Act, Arc<Plugin>)> {
+        for plugin in self.list.read().await.iter() {
+            if let Some(act) = plugin.get(module_id, class_id, action_id) {
+                return Some((act, Arc::clone(plugin)));
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    future::Future,
+    mem::take,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    fs::{create_dir_all, write},
+    time::interval,
+};
+
+use crate::log;
+
+use super::app::init::ProfileConfig;
+
+/// Profiler, set when the section [profile] samples the requests
+static PROFILER: OnceLock<Profiler> = OnceLock::new();
+
+tokio::task_local! {
+    /// Frames of the sampled request
+    static SAMPLE: RefCell<Sample>;
+}
+
+#[derive(Debug)]
+struct Profiler {
+    config: Arc<ProfileConfig>,
+    /// Number of the requests for the sampling by the rate
+    count: AtomicU64,
+    routes: Mutex<HashMap<String, RouteProfile>>,
+}
+
+/// Profile of the route since the start of the server
+#[derive(Debug, Default)]
+struct RouteProfile {
+    /// Self time of the stacks, microseconds
+    stacks: HashMap<String, u64>,
+    /// Changed after the last writing
+    changed: bool,
+}
+
+#[derive(Debug, Default)]
+struct Sample {
+    /// Open frames: the name, the start and the time of the closed children
+    stack: Vec<(String, Instant, Duration)>,
+    /// Self time of the stacks, microseconds
+    stacks: HashMap<String, u64>,
+}
+
+impl Sample {
+    fn open(&mut self, name: &str) {
+        self.stack.push((Profile::name(name), Instant::now(), Duration::ZERO));
+    }
+
+    /// Close the last frame, its time without the children is added to its stack
+    fn close(&mut self) {
+        let total = match self.stack.last() {
+            Some((_, start, child)) => {
+                let total = start.elapsed();
+                let path = self.stack.iter().map(|(name, _, _)| name.as_str()).collect::<Vec<&str>>().join(";");
+                *self.stacks.entry(path).or_default() += total.saturating_sub(*child).as_micros() as u64;
+                total
+            }
+            None => return,
+        };
+        self.stack.pop();
+        if let Some((_, _, child)) = self.stack.last_mut() {
+            *child += total;
+        }
+    }
+}
+
+/// Frame of the profile of the request, closed on drop
+///
+/// # Example
+///
+/// ```ignore
+/// let _frame = Frame::start("report.build");
+/// ```
+///
+/// The frames are nested by the order of the calls, the profile has the time of each stack without the children.
+/// The frame does nothing outside the sampled request, in the spawned tasks and without the section [profile].
+#[derive(Debug)]
+pub struct Frame {
+    open: bool,
+}
+
+impl Frame {
+    /// Open the frame inside the current one
+    pub fn start(name: &str) -> Frame {
+        Frame {
+            open: SAMPLE.try_with(|sample| sample.borrow_mut().open(name)).is_ok(),
+        }
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        if self.open {
+            let _ = SAMPLE.try_with(|sample| sample.borrow_mut().close());
+        }
+    }
+}
+
+/// Sampling profiler of the requests
+///
+/// The part of the requests by the rate and the requests slower than the threshold are written to the folder
+/// by the routes, one file "<route>.folded" with the lines "frame;frame;frame microseconds" for each route.
+/// The files are for flamegraph.pl or inferno-flamegraph and have the total time since the start of the server.
+pub(crate) struct Profile;
+
+impl Profile {
+    /// Start the writing of the profiles, only once
+    pub(crate) fn start(config: Arc<ProfileConfig>) {
+        let profiler = Profiler {
+            config,
+            count: AtomicU64::new(0),
+            routes: Mutex::new(HashMap::new()),
+        };
+        if PROFILER.set(profiler).is_ok() {
+            tokio::spawn(Profile::dump());
+        }
+    }
+
+    /// Run the request, the sampled one is added to the profile of the route
+    ///
+    /// With the threshold all requests are recorded, the fast ones not sampled by the rate are dropped.
+    pub(crate) async fn scope<F: Future>(route: &str, future: F) -> F::Output {
+        let profiler = match PROFILER.get() {
+            Some(profiler) => profiler,
+            None => return future.await,
+        };
+        let rate = profiler.config.rate;
+        let count = profiler.count.fetch_add(1, Ordering::Relaxed);
+        let sampled = count.wrapping_mul(rate) % 100 < rate;
+        if !sampled && profiler.config.slow.is_none() {
+            return future.await;
+        }
+        let mut sample = Sample::default();
+        sample.open(if route.is_empty() { "index" } else { route });
+        let (output, mut sample) = SAMPLE
+            .scope(RefCell::new(sample), async {
+                let output = future.await;
+                (output, SAMPLE.with(|sample| take(&mut *sample.borrow_mut())))
+            })
+            .await;
+        let elapsed = match sample.stack.first() {
+            Some((_, start, _)) => start.elapsed(),
+            None => Duration::ZERO,
+        };
+        if !sampled && profiler.config.slow.is_some_and(|slow| elapsed < slow) {
+            return output;
+        }
+        while !sample.stack.is_empty() {
+            sample.close();
+        }
+        let mut routes = match profiler.routes.lock() {
+            Ok(routes) => routes,
+            Err(e) => e.into_inner(),
+        };
+        let profile = routes.entry(Profile::file(route)).or_default();
+        for (stack, time) in sample.stacks {
+            *profile.stacks.entry(stack).or_default() += time;
+        }
+        profile.changed = true;
+        output
+    }
+
+    /// Write the changed profiles by the interval
+    async fn dump() {
+        let profiler = match PROFILER.get() {
+            Some(profiler) => profiler,
+            None => return,
+        };
+        let config = &profiler.config;
+        if let Err(_e) = create_dir_all(&config.path).await {
+            log!(warning, 0, "Profile {}. Error: {}", config.path.display(), _e);
+        }
+        let mut timer = interval(config.interval);
+        loop {
+            timer.tick().await;
+            let files: Vec<(String, String)> = {
+                let mut routes = match profiler.routes.lock() {
+                    Ok(routes) => routes,
+                    Err(e) => e.into_inner(),
+                };
+                routes
+                    .iter_mut()
+                    .filter(|(_, profile)| profile.changed)
+                    .map(|(file, profile)| {
+                        profile.changed = false;
+                        let mut lines: Vec<String> = profile.stacks.iter().map(|(stack, time)| format!("{} {}", stack, time)).collect();
+                        lines.sort();
+                        lines.push(String::new());
+                        (file.clone(), lines.join("\n"))
+                    })
+                    .collect()
+            };
+            for (file, text) in files {
+                let path = config.path.join(format!("{}.folded", file));
+                if let Err(_e) = write(&path, text).await {
+                    log!(warning, 0, "Profile {}. Error: {}", path.display(), _e);
+                }
+            }
+        }
+    }
+
+    /// Name of the frame without the separators of the folded format
+    fn name(name: &str) -> String {
+        name.chars().map(|c| if c == ';' || c.is_whitespace() { '_' } else { c }).collect()
+    }
+
+    /// Name of the file of the route, for example "shop.cart.add" for "/shop/cart/add"
+    fn file(route: &str) -> String {
+        let file: String = route
+            .trim_matches('/')
+            .chars()
+            .map(|c| match c {
+                '/' => '.',
+                c if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' => c,
+                _ => '_',
+            })
+            .collect();
+        if file.is_empty() {
+            "index".to_owned()
+        } else {
+            file
+        }
+    }
+}
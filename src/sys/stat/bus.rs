@@ -0,0 +1,229 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use tokio::sync::Notify;
+
+use crate::{
+    log,
+    sys::app::init::{BusConfig, BusPolicy},
+};
+
+/// Min time between the warnings about the subscriber behind, seconds
+const BUS_WARNING: u64 = 10;
+
+/// Metrics of the internal queue of the messages
+///
+/// The lag is the time of the message in the queue. The subscriber is behind when the queue is full by half
+/// or the lag is longer than the limit, then the warning is logged not often than once per 10 seconds.
+#[derive(Debug)]
+pub struct BusMetrics {
+    /// Name of the queue
+    name: String,
+    capacity: usize,
+    /// Messages in the queue
+    len: AtomicUsize,
+    sent: AtomicU64,
+    received: AtomicU64,
+    /// Messages dropped by the full queue
+    dropped: AtomicU64,
+    /// Max lag, microseconds
+    lag_max: AtomicU64,
+    /// Total lag, microseconds
+    lag_total: AtomicU64,
+    /// Time of the last warning, seconds since `start`
+    warned: Mutex<Option<u64>>,
+    start: Instant,
+}
+
+/// State of the queue
+#[derive(Debug, Clone)]
+pub struct BusStat {
+    pub name: String,
+    pub capacity: usize,
+    pub len: usize,
+    pub sent: u64,
+    pub received: u64,
+    pub dropped: u64,
+    /// Max lag, microseconds
+    pub lag_max: u64,
+    /// Total lag, microseconds
+    pub lag_total: u64,
+}
+
+impl BusMetrics {
+    pub(crate) fn new(name: &str, capacity: usize) -> BusMetrics {
+        BusMetrics {
+            name: name.to_owned(),
+            capacity,
+            len: AtomicUsize::new(0),
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            lag_max: AtomicU64::new(0),
+            lag_total: AtomicU64::new(0),
+            warned: Mutex::new(None),
+            start: Instant::now(),
+        }
+    }
+
+    pub fn stat(&self) -> BusStat {
+        BusStat {
+            name: self.name.clone(),
+            capacity: self.capacity,
+            len: self.len.load(Ordering::Relaxed),
+            sent: self.sent.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            lag_max: self.lag_max.load(Ordering::Relaxed),
+            lag_total: self.lag_total.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Log the warning about the subscriber behind, once per `BUS_WARNING`
+    fn warn(&self, _len: usize, _lag: u64) {
+        let now = self.start.elapsed().as_secs();
+        let mut warned = match self.warned.lock() {
+            Ok(warned) => warned,
+            Err(e) => e.into_inner(),
+        };
+        if warned.is_some_and(|last| now < last + BUS_WARNING) {
+            return;
+        }
+        *warned = Some(now);
+        log!(
+            warning,
+            0,
+            "Bus {}: the subscriber is behind, {} of {} messages, lag {} us, dropped {}",
+            self.name,
+            _len,
+            self.capacity,
+            _lag,
+            self.dropped.load(Ordering::Relaxed)
+        );
+    }
+}
+
+/// Bounded queue of the messages with one subscriber, see [db] notify_capacity
+///
+/// With `BusPolicy::Wait` the sender waits for the free place, with `BusPolicy::Drop` the new message is dropped
+/// and the subscriber gets the number of the lost messages by `lost`.
+#[derive(Debug)]
+pub(crate) struct Bus<T> {
+    config: BusConfig,
+    queue: Mutex<VecDeque<(Instant, T)>>,
+    /// New message or the close
+    message: Notify,
+    /// Free place
+    space: Notify,
+    closed: AtomicBool,
+    /// Messages lost after the last `lost`
+    lost: AtomicU64,
+    metrics: Arc<BusMetrics>,
+}
+
+impl<T> Bus<T> {
+    pub(crate) fn new(config: BusConfig, metrics: Arc<BusMetrics>) -> Arc<Bus<T>> {
+        Arc::new(Bus {
+            config,
+            queue: Mutex::new(VecDeque::with_capacity(config.capacity.min(1024))),
+            message: Notify::new(),
+            space: Notify::new(),
+            closed: AtomicBool::new(false),
+            lost: AtomicU64::new(0),
+            metrics,
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<(Instant, T)>> {
+        match self.queue.lock() {
+            Ok(queue) => queue,
+            Err(e) => e.into_inner(),
+        }
+    }
+
+    /// Send the message, `false` if the queue is closed or the message is dropped
+    pub(crate) async fn send(&self, item: T) -> bool {
+        let mut item = Some(item);
+        loop {
+            let space = self.space.notified();
+            {
+                let mut queue = self.lock();
+                if self.closed.load(Ordering::Relaxed) {
+                    return false;
+                }
+                if queue.len() < self.config.capacity {
+                    if let Some(item) = item.take() {
+                        queue.push_back((Instant::now(), item));
+                    }
+                    let len = queue.len();
+                    drop(queue);
+                    self.metrics.len.store(len, Ordering::Relaxed);
+                    self.metrics.sent.fetch_add(1, Ordering::Relaxed);
+                    if len * 2 >= self.config.capacity {
+                        self.metrics.warn(len, 0);
+                    }
+                    self.message.notify_one();
+                    return true;
+                }
+                if self.config.policy == BusPolicy::Drop {
+                    let len = queue.len();
+                    drop(queue);
+                    self.lost.fetch_add(1, Ordering::Relaxed);
+                    self.metrics.dropped.fetch_add(1, Ordering::Relaxed);
+                    self.metrics.warn(len, 0);
+                    return false;
+                }
+            }
+            space.await;
+        }
+    }
+
+    /// Next message, `None` after the close and the end of the queue
+    pub(crate) async fn recv(&self) -> Option<T> {
+        loop {
+            let message = self.message.notified();
+            {
+                let mut queue = self.lock();
+                if let Some((at, item)) = queue.pop_front() {
+                    let len = queue.len();
+                    drop(queue);
+                    let lag = at.elapsed().as_micros() as u64;
+                    self.metrics.len.store(len, Ordering::Relaxed);
+                    self.metrics.received.fetch_add(1, Ordering::Relaxed);
+                    self.metrics.lag_total.fetch_add(lag, Ordering::Relaxed);
+                    self.metrics.lag_max.fetch_max(lag, Ordering::Relaxed);
+                    if lag > self.config.lag.as_micros() as u64 {
+                        self.metrics.warn(len, lag);
+                    }
+                    self.space.notify_one();
+                    return Some(item);
+                }
+                if self.closed.load(Ordering::Relaxed) {
+                    return None;
+                }
+            }
+            message.await;
+        }
+    }
+
+    /// Number of the messages dropped after the last call
+    pub(crate) fn lost(&self) -> u64 {
+        self.lost.swap(0, Ordering::Relaxed)
+    }
+
+    /// The sender waiting for the place and the subscriber waiting for the message are released
+    pub(crate) fn close(&self) {
+        {
+            let _queue = self.lock();
+            self.closed.store(true, Ordering::Relaxed);
+        }
+        self.message.notify_waiters();
+        self.space.notify_waiters();
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use tokio::sync::Notify;
+
+/// Latency above the baseline in this number of times is the overload
+const LIMIT_TOLERANCE: f64 = 2.0;
+/// Multiplicative decrease of the limit
+const LIMIT_DECREASE: f64 = 0.9;
+/// Speed of the baseline growth to the current latency
+const LIMIT_BASELINE_DRIFT: f64 = 0.01;
+/// Smoothing of the latency for the stat
+const LIMIT_LATENCY_SMOOTH: f64 = 0.1;
+
+#[derive(Debug)]
+struct LimitState {
+    /// Current limit
+    limit: f64,
+    /// Running calls
+    inflight: usize,
+    /// Waiting calls
+    waiting: usize,
+    /// Baseline latency, microseconds
+    baseline: Option<f64>,
+    /// Smoothed latency, microseconds
+    latency: f64,
+    /// Time of the last decrease
+    decreased: Instant,
+}
+
+/// Adaptive concurrency limit of the upstream dependency (AIMD)
+///
+/// The limit grows by one per window of the calls while the latency is close to the baseline,
+/// and decreases by 10% once per window on the error or when the latency exceeds the baseline twice.
+/// The baseline is the minimum latency, it slowly follows the current latency to recover after the incident.
+#[derive(Debug)]
+pub struct AdaptiveLimit {
+    /// Name of the dependency
+    name: String,
+    /// Min limit
+    min: usize,
+    /// Max limit
+    max: usize,
+    state: Mutex<LimitState>,
+    notify: Notify,
+}
+
+/// Permit of the call to the dependency
+///
+/// The call releases the slot on drop, `done` records the result of the call.
+#[derive(Debug)]
+pub struct LimitPermit {
+    limit: Arc<AdaptiveLimit>,
+    start: Instant,
+    sampled: bool,
+}
+
+/// Waiting call, decreases the number of the waiting calls on drop
+struct LimitWait<'a> {
+    limit: &'a AdaptiveLimit,
+}
+
+impl Drop for LimitWait<'_> {
+    fn drop(&mut self) {
+        self.limit.lock().waiting -= 1;
+    }
+}
+
+/// Current state of the limit for the stat
+#[derive(Debug, Clone)]
+pub struct LimitStat {
+    /// Name of the dependency
+    pub name: String,
+    /// Current limit
+    pub limit: usize,
+    /// Max limit
+    pub max: usize,
+    /// Running calls
+    pub inflight: usize,
+    /// Waiting calls
+    pub waiting: usize,
+    /// Smoothed latency, microseconds
+    pub latency: u64,
+}
+
+impl AdaptiveLimit {
+    /// New limit in the range `1..=max`, starts from `max`
+    pub fn new(name: &str, max: usize) -> Arc<AdaptiveLimit> {
+        let max = max.max(1);
+        Arc::new(AdaptiveLimit {
+            name: name.to_owned(),
+            min: 1,
+            max,
+            state: Mutex::new(LimitState {
+                limit: max as f64,
+                inflight: 0,
+                waiting: 0,
+                baseline: None,
+                latency: 0.0,
+                decreased: Instant::now(),
+            }),
+            notify: Notify::new(),
+        })
+    }
+
+    /// Name of the dependency
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Wait for the free slot
+    pub async fn acquire(self: &Arc<AdaptiveLimit>) -> LimitPermit {
+        let mut wait = None;
+        loop {
+            {
+                let mut state = self.lock();
+                if state.inflight < state.limit as usize {
+                    state.inflight += 1;
+                    drop(state);
+                    drop(wait);
+                    return LimitPermit {
+                        limit: Arc::clone(self),
+                        start: Instant::now(),
+                        sampled: false,
+                    };
+                }
+                if wait.is_none() {
+                    state.waiting += 1;
+                    wait = Some(LimitWait { limit: self });
+                }
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    /// Current state
+    pub fn stat(&self) -> LimitStat {
+        let state = self.lock();
+        LimitStat {
+            name: self.name.clone(),
+            limit: state.limit as usize,
+            max: self.max,
+            inflight: state.inflight,
+            waiting: state.waiting,
+            latency: state.latency as u64,
+        }
+    }
+
+    /// Update the limit with the result of the call
+    fn sample(&self, start: Instant, ok: bool) {
+        let time = start.elapsed().as_micros() as f64;
+        let mut state = self.lock();
+        if state.latency == 0.0 {
+            state.latency = time;
+        } else {
+            state.latency += (time - state.latency) * LIMIT_LATENCY_SMOOTH;
+        }
+        if ok {
+            state.baseline = Some(match state.baseline {
+                Some(baseline) if time > baseline => baseline + (time - baseline) * LIMIT_BASELINE_DRIFT,
+                _ => time,
+            });
+        }
+        let baseline = state.baseline.unwrap_or(time);
+
+        if !ok || time > baseline * LIMIT_TOLERANCE {
+            // One decrease per window: only the calls started after the last decrease
+            if start > state.decreased {
+                state.limit = (state.limit * LIMIT_DECREASE).max(self.min as f64);
+                state.decreased = Instant::now();
+            }
+        } else if state.inflight as f64 + 1.0 >= state.limit {
+            let old = state.limit as usize;
+            state.limit = (state.limit + 1.0 / state.limit).min(self.max as f64);
+            if state.limit as usize > old {
+                self.notify.notify_one();
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LimitState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(e) => e.into_inner(),
+        }
+    }
+}
+
+impl LimitPermit {
+    /// Record the result of the call, only the first result is used
+    pub fn done(&mut self, ok: bool) {
+        if !self.sampled {
+            self.sampled = true;
+            self.limit.sample(self.start, ok);
+        }
+    }
+}
+
+impl Drop for LimitPermit {
+    fn drop(&mut self) {
+        let mut state = self.limit.lock();
+        state.inflight -= 1;
+        drop(state);
+        self.limit.notify.notify_one();
+    }
+}
@@ -1,2 +1,13 @@
+/// Queue of the invalidation of the cache by LISTEN of PostgreSQL
+#[cfg(all(feature = "cache", feature = "pgsql"))]
+pub mod bus;
+
+pub mod limit;
+
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+pub mod pool;
+
+pub mod route;
+
 #[allow(clippy::module_inception)]
 pub mod stat;
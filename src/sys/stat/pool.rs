@@ -0,0 +1,280 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "pgsql")]
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::{
+    sync::{Semaphore, SemaphorePermit},
+    time::timeout,
+};
+
+use crate::fnv1a_64;
+
+use super::limit::{AdaptiveLimit, LimitPermit};
+
+#[derive(Debug)]
+struct StatementState {
+    count: u64,
+    errors: u64,
+    /// Total time, microseconds
+    time: u64,
+    /// Max time, microseconds
+    max: u64,
+}
+
+/// Pool of the connections to the database with the metrics
+///
+/// The free connection is waited by the adaptive limit and the semaphore during the timeout,
+/// the number of the waiting calls and the time of the waiting are counted.
+#[derive(Debug)]
+pub(crate) struct PoolMetrics {
+    /// Max number of the connections
+    size: usize,
+    /// Number of the connections opened at the start and kept by the health check
+    min: usize,
+    /// Max time of the waiting for the free connection
+    timeout: Option<Duration>,
+    semaphore: Arc<Semaphore>,
+    /// Open connections after the last health check
+    open: AtomicUsize,
+    /// Broken connections found by the health check
+    broken: AtomicU64,
+    /// Calls without the free connection during the timeout
+    timeouts: AtomicU64,
+    /// Calls waiting for the free connection
+    waiting: AtomicU64,
+    /// Number of the received connections
+    acquires: AtomicU64,
+    /// Total time of the waiting for the connection, microseconds
+    acquire_time: AtomicU64,
+    /// Max time of the waiting for the connection, microseconds
+    acquire_max: AtomicU64,
+    /// Names of the prepared statements by the key
+    names: HashMap<i64, &'static str>,
+    statements: Mutex<HashMap<i64, StatementState>>,
+}
+
+/// Error of the waiting for the free connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PoolError {
+    /// The pool is closed
+    Closed,
+    /// No free connection during the timeout
+    Timeout,
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::Closed => write!(f, "The pool of the connections is closed"),
+            PoolError::Timeout => write!(f, "No free connection to the database during the timeout"),
+        }
+    }
+}
+
+/// Waiting call, decreases the number of the waiting calls on drop
+struct PoolWait<'a> {
+    pool: &'a PoolMetrics,
+}
+
+impl Drop for PoolWait<'_> {
+    fn drop(&mut self) {
+        self.pool.waiting.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Current state of the pool for the stat
+#[derive(Debug, Clone)]
+pub struct PoolStat {
+    /// Max number of the connections
+    pub size: usize,
+    /// Number of the connections kept open
+    pub min: usize,
+    /// Open connections after the last health check
+    pub open: usize,
+    /// Broken connections found by the health check
+    pub broken: u64,
+    /// Calls without the free connection during the timeout
+    pub timeouts: u64,
+    /// Free connections
+    pub idle: usize,
+    /// Calls waiting for the free connection
+    pub waiters: u64,
+    /// Number of the received connections
+    pub acquires: u64,
+    /// Total time of the waiting for the connection, microseconds
+    pub acquire_time: u64,
+    /// Max time of the waiting for the connection, microseconds
+    pub acquire_max: u64,
+}
+
+/// Calls of the prepared statement for the stat
+#[derive(Debug, Clone)]
+pub struct StatementStat {
+    /// Name of the statement, for example "lib_get_session"
+    pub name: String,
+    pub count: u64,
+    pub errors: u64,
+    /// Total time, microseconds
+    pub time: u64,
+    /// Max time, microseconds
+    pub max: u64,
+}
+
+impl PoolMetrics {
+    /// Pool of `min..=size` connections, `names` are the names of the prepared statements
+    pub(crate) fn new(size: usize, min: usize, timeout: Option<Duration>, names: &[&'static str]) -> Arc<PoolMetrics> {
+        Arc::new(PoolMetrics {
+            size,
+            min,
+            timeout,
+            semaphore: Arc::new(Semaphore::new(size)),
+            open: AtomicUsize::new(min),
+            broken: AtomicU64::new(0),
+            timeouts: AtomicU64::new(0),
+            waiting: AtomicU64::new(0),
+            acquires: AtomicU64::new(0),
+            acquire_time: AtomicU64::new(0),
+            acquire_max: AtomicU64::new(0),
+            names: names.iter().map(|name| (fnv1a_64(name.as_bytes()), *name)).collect(),
+            statements: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Wait for the adaptive limit and the free connection
+    pub(crate) async fn acquire(&self, limit: &Arc<AdaptiveLimit>) -> Result<(LimitPermit, SemaphorePermit<'_>), PoolError> {
+        let wait = self.wait();
+        let start = Instant::now();
+        let permit = async { Ok((limit.acquire().await, self.semaphore.acquire().await.map_err(|_| PoolError::Closed)?)) };
+        let permit = match self.timeout {
+            Some(time) => timeout(time, permit).await,
+            None => Ok(permit.await),
+        };
+        drop(wait);
+        self.acquired(start.elapsed(), permit)
+    }
+
+    /// Wait for the adaptive limit and the free connection, the permit is without the lifetime
+    #[cfg(feature = "pgsql")]
+    pub(crate) async fn acquire_owned(&self, limit: &Arc<AdaptiveLimit>) -> Result<(LimitPermit, OwnedSemaphorePermit), PoolError> {
+        let wait = self.wait();
+        let start = Instant::now();
+        let permit =
+            async { Ok((limit.acquire().await, Arc::clone(&self.semaphore).acquire_owned().await.map_err(|_| PoolError::Closed)?)) };
+        let permit = match self.timeout {
+            Some(time) => timeout(time, permit).await,
+            None => Ok(permit.await),
+        };
+        drop(wait);
+        self.acquired(start.elapsed(), permit)
+    }
+
+    /// Free permit without the waiting, for the health check
+    pub(crate) fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        self.semaphore.try_acquire().ok()
+    }
+
+    /// Number of the connections kept open
+    pub(crate) fn min(&self) -> usize {
+        self.min
+    }
+
+    /// Result of the health check
+    pub(crate) fn checked(&self, open: usize, broken: u64) {
+        self.open.store(open, Ordering::Relaxed);
+        self.broken.fetch_add(broken, Ordering::Relaxed);
+    }
+
+    /// Record the call of the prepared statement
+    #[cfg(any(
+        feature = "session-db",
+        feature = "redirect-db",
+        feature = "route-db",
+        feature = "access-db",
+        feature = "setting-db",
+        feature = "mail-db"
+    ))]
+    pub(crate) fn statement(&self, query: i64, time: Duration, ok: bool) {
+        let time = time.as_micros() as u64;
+        let mut list = match self.statements.lock() {
+            Ok(list) => list,
+            Err(e) => e.into_inner(),
+        };
+        let item = list.entry(query).or_insert(StatementState { count: 0, errors: 0, time: 0, max: 0 });
+        item.count += 1;
+        if !ok {
+            item.errors += 1;
+        }
+        item.time += time;
+        item.max = item.max.max(time);
+    }
+
+    /// Current state
+    pub(crate) fn stat(&self) -> PoolStat {
+        PoolStat {
+            size: self.size,
+            min: self.min,
+            open: self.open.load(Ordering::Relaxed),
+            broken: self.broken.load(Ordering::Relaxed),
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            idle: self.semaphore.available_permits(),
+            waiters: self.waiting.load(Ordering::Relaxed),
+            acquires: self.acquires.load(Ordering::Relaxed),
+            acquire_time: self.acquire_time.load(Ordering::Relaxed),
+            acquire_max: self.acquire_max.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Calls of the prepared statements, sorted by the name
+    pub(crate) fn statements(&self) -> Vec<StatementStat> {
+        let list = match self.statements.lock() {
+            Ok(list) => list,
+            Err(e) => e.into_inner(),
+        };
+        let mut res: Vec<StatementStat> = list
+            .iter()
+            .map(|(query, item)| StatementStat {
+                name: match self.names.get(query) {
+                    Some(name) => (*name).to_owned(),
+                    None => query.to_string(),
+                },
+                count: item.count,
+                errors: item.errors,
+                time: item.time,
+                max: item.max,
+            })
+            .collect();
+        res.sort_by(|a, b| a.name.cmp(&b.name));
+        res
+    }
+
+    fn wait(&self) -> PoolWait<'_> {
+        self.waiting.fetch_add(1, Ordering::Relaxed);
+        PoolWait { pool: self }
+    }
+
+    /// Count the waiting, the elapsed timeout is the error
+    fn acquired<T>(&self, time: Duration, permit: Result<Result<T, PoolError>, tokio::time::error::Elapsed>) -> Result<T, PoolError> {
+        match permit {
+            Ok(Ok(permit)) => {
+                let time = time.as_micros() as u64;
+                self.acquires.fetch_add(1, Ordering::Relaxed);
+                self.acquire_time.fetch_add(time, Ordering::Relaxed);
+                self.acquire_max.fetch_max(time, Ordering::Relaxed);
+                Ok(permit)
+            }
+            Ok(Err(e)) => Err(e),
+            Err(_) => {
+                self.timeouts.fetch_add(1, Ordering::Relaxed);
+                Err(PoolError::Timeout)
+            }
+        }
+    }
+}
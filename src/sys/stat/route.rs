@@ -0,0 +1,169 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::Duration,
+};
+
+/// Max number of the routes in the stat, the next routes are counted in `ROUTE_OTHER`
+const ROUTE_MAX: usize = 1000;
+/// Route of the requests above `ROUTE_MAX`
+const ROUTE_OTHER: &str = "other";
+/// Buckets of the latency, four per the power of two, the bucket `i` is up to 2^((i + 1) / 4) microseconds, about 67 s
+const BUCKETS: usize = 104;
+
+#[derive(Debug, Clone)]
+struct RouteState {
+    count: u64,
+    /// Answers with the status 5xx
+    errors: u64,
+    bytes_in: u64,
+    bytes_out: u64,
+    /// Total time, microseconds
+    time: u64,
+    /// Max time, microseconds
+    max: u64,
+    buckets: [u32; BUCKETS],
+}
+
+/// Requests of the route for the stat
+#[derive(Debug, Clone)]
+pub struct RouteStat {
+    /// Path of the route without the parameter, for example "/shop/cart/add"
+    pub route: String,
+    pub count: u64,
+    /// Answers with the status 5xx
+    pub errors: u64,
+    /// Bytes of the bodies of the requests by Content-Length
+    pub bytes_in: u64,
+    /// Bytes of the answers with the headers
+    pub bytes_out: u64,
+    /// Total time, microseconds
+    pub time: u64,
+    /// Latency 50%, 90%, 99% and 100% of the requests, microseconds, the precision is 19%
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+/// Sizes, latency and statuses of the answers by the routes
+#[derive(Debug)]
+pub(crate) struct RouteMetrics {
+    routes: Mutex<HashMap<String, RouteState>>,
+    /// Number of the answers by the status
+    statuses: Mutex<BTreeMap<u16, u64>>,
+}
+
+impl RouteMetrics {
+    pub(crate) fn new() -> RouteMetrics {
+        RouteMetrics {
+            routes: Mutex::new(HashMap::new()),
+            statuses: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Add the finished request, `route` is `None` for the redirects and the errors before the controller
+    pub(crate) fn add(&self, route: Option<&str>, status: u16, bytes_in: u64, bytes_out: u64, time: Duration) {
+        {
+            let mut statuses = match self.statuses.lock() {
+                Ok(statuses) => statuses,
+                Err(e) => e.into_inner(),
+            };
+            *statuses.entry(status).or_insert(0) += 1;
+        }
+        let route = match route {
+            Some(route) => route,
+            None => return,
+        };
+        let time = time.as_micros() as u64;
+        let mut routes = match self.routes.lock() {
+            Ok(routes) => routes,
+            Err(e) => e.into_inner(),
+        };
+        let route = if routes.len() < ROUTE_MAX || routes.contains_key(route) { route } else { ROUTE_OTHER };
+        let item = routes.entry(route.to_owned()).or_insert_with(|| RouteState {
+            count: 0,
+            errors: 0,
+            bytes_in: 0,
+            bytes_out: 0,
+            time: 0,
+            max: 0,
+            buckets: [0; BUCKETS],
+        });
+        item.count += 1;
+        if status >= 500 {
+            item.errors += 1;
+        }
+        item.bytes_in += bytes_in;
+        item.bytes_out += bytes_out;
+        item.time += time;
+        item.max = item.max.max(time);
+        let bucket = &mut item.buckets[RouteMetrics::bucket(time)];
+        *bucket = bucket.saturating_add(1);
+    }
+
+    /// Routes sorted by the path, `reset` clears them
+    pub(crate) fn routes(&self, reset: bool) -> Vec<RouteStat> {
+        let list = {
+            let mut routes = match self.routes.lock() {
+                Ok(routes) => routes,
+                Err(e) => e.into_inner(),
+            };
+            if reset {
+                std::mem::take(&mut *routes)
+            } else {
+                routes.clone()
+            }
+        };
+        let mut res: Vec<RouteStat> = list
+            .into_iter()
+            .map(|(route, item)| RouteStat {
+                p50: RouteMetrics::quantile(&item, 0.5),
+                p90: RouteMetrics::quantile(&item, 0.9),
+                p99: RouteMetrics::quantile(&item, 0.99),
+                route,
+                count: item.count,
+                errors: item.errors,
+                bytes_in: item.bytes_in,
+                bytes_out: item.bytes_out,
+                time: item.time,
+                max: item.max,
+            })
+            .collect();
+        res.sort_by(|a, b| a.route.cmp(&b.route));
+        res
+    }
+
+    /// Number of the answers by the status, sorted by the status, `reset` clears them
+    pub(crate) fn statuses(&self, reset: bool) -> Vec<(u16, u64)> {
+        let mut statuses = match self.statuses.lock() {
+            Ok(statuses) => statuses,
+            Err(e) => e.into_inner(),
+        };
+        let res = statuses.iter().map(|(status, count)| (*status, *count)).collect();
+        if reset {
+            statuses.clear();
+        }
+        res
+    }
+
+    fn bucket(time: u64) -> usize {
+        if time <= 1 {
+            return 0;
+        }
+        (((time as f64).log2() * 4.0) as usize).min(BUCKETS - 1)
+    }
+
+    /// Upper bound of the bucket of the quantile, not more than the max time
+    fn quantile(item: &RouteState, quantile: f64) -> u64 {
+        let rank = (item.count as f64 * quantile).ceil().max(1.0) as u64;
+        let mut sum = 0;
+        for (i, count) in item.buckets.iter().enumerate() {
+            sum += *count as u64;
+            if sum >= rank {
+                return (2f64.powf((i + 1) as f64 / 4.0) as u64).min(item.max);
+            }
+        }
+        item.max
+    }
+}
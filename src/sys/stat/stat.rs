@@ -1,10 +1,51 @@
-use std::sync::{
-    atomic::{AtomicU64, Ordering},
-    Arc,
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
+use crate::sys::web::timing::Timings;
+
+use super::{
+    limit::{AdaptiveLimit, LimitStat},
+    route::{RouteMetrics, RouteStat},
+};
+
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+use std::sync::OnceLock;
+
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+use super::pool::{PoolMetrics, PoolStat, StatementStat};
+
+#[cfg(all(feature = "cache", feature = "pgsql"))]
+use super::bus::{BusMetrics, BusStat};
+
+/// Counters of the requests at the moment, see `Stat::snapshot`
+#[derive(Debug, Clone)]
+pub struct StatSnapshot {
+    /// Time since the start of the server
+    pub uptime: Duration,
+    /// Number of the requests since the start of the server
+    pub total: u64,
+    /// Number of the requests rejected by the full queue since the start of the server
+    pub rejected: u64,
+    /// Number of the answers by the status
+    pub statuses: Vec<(u16, u64)>,
+    /// Requests by the routes
+    pub routes: Vec<RouteStat>,
+    /// Name, number and total time of the request phases, microseconds
+    pub timings: Vec<(String, u64, u64)>,
+}
+
 #[derive(Debug)]
 pub struct Stat {
+    /// Start of the server
+    start: Instant,
+    /// The database and Redis are connected, the requests are accepted
+    ready: AtomicBool,
     /// Number of workers
     pub(crate) number: Arc<AtomicU64>,
     /// Last worker ID
@@ -13,18 +54,65 @@ pub struct Stat {
     pub(crate) online: Arc<AtomicU64>,
     ///  Number of total requests
     pub(crate) total: Arc<AtomicU64>,
+    /// Number of requests waiting in the queue
+    pub(crate) queued: AtomicU64,
+    /// Number of requests rejected by the full queue
+    pub(crate) rejected: AtomicU64,
+    /// Total time in the queue, microseconds
+    pub(crate) queue_time: AtomicU64,
+    /// Max time in the queue, microseconds
+    pub(crate) queue_max: AtomicU64,
+    /// Adaptive limits of the upstream dependencies
+    limits: Mutex<Vec<Arc<AdaptiveLimit>>>,
+    /// Number and total time of the request phases by the name of the mark, microseconds
+    timings: Mutex<HashMap<String, (u64, u64)>>,
+    /// Sizes, latency and statuses of the answers by the routes
+    routes: RouteMetrics,
+    /// Pool of the connections to the database
+    #[cfg(any(feature = "pgsql", feature = "mssql"))]
+    pool: OnceLock<Arc<PoolMetrics>>,
+    /// Internal queues of the messages
+    #[cfg(all(feature = "cache", feature = "pgsql"))]
+    buses: Mutex<Vec<Arc<BusMetrics>>>,
 }
 
 impl Stat {
     pub(crate) fn new() -> Stat {
         Stat {
+            start: Instant::now(),
+            ready: AtomicBool::new(false),
             number: Arc::new(AtomicU64::new(0)),
             worker: Arc::new(AtomicU64::new(0)),
             online: Arc::new(AtomicU64::new(0)),
             total: Arc::new(AtomicU64::new(0)),
+            queued: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            queue_time: AtomicU64::new(0),
+            queue_max: AtomicU64::new(0),
+            limits: Mutex::new(Vec::new()),
+            timings: Mutex::new(HashMap::new()),
+            routes: RouteMetrics::new(),
+            #[cfg(any(feature = "pgsql", feature = "mssql"))]
+            pool: OnceLock::new(),
+            #[cfg(all(feature = "cache", feature = "pgsql"))]
+            buses: Mutex::new(Vec::new()),
         }
     }
 
+    /// Time since the start of the server
+    pub fn get_uptime(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// The requests are accepted, `false` while the server waits for the database or Redis at the start
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_ready(&self) {
+        self.ready.store(true, Ordering::Relaxed);
+    }
+
     /// Number of workers
     pub fn get_number(&self) -> u64 {
         self.number.load(Ordering::Relaxed)
@@ -44,4 +132,360 @@ impl Stat {
     pub fn get_total(&self) -> u64 {
         self.total.load(Ordering::Relaxed)
     }
+
+    /// Number of requests waiting in the queue
+    pub fn get_queued(&self) -> u64 {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Number of requests rejected by the full queue
+    pub fn get_rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Total time of the requests in the queue, microseconds
+    pub fn get_queue_time(&self) -> u64 {
+        self.queue_time.load(Ordering::Relaxed)
+    }
+
+    /// Max time of the request in the queue, microseconds
+    pub fn get_queue_max(&self) -> u64 {
+        self.queue_max.load(Ordering::Relaxed)
+    }
+
+    /// Adaptive limit of the upstream dependency, for example the outbound HTTP service
+    ///
+    /// The limit is created on the first call with the max number of the concurrent calls `max`.
+    pub fn limit(&self, name: &str, max: usize) -> Arc<AdaptiveLimit> {
+        let mut limits = match self.limits.lock() {
+            Ok(limits) => limits,
+            Err(e) => e.into_inner(),
+        };
+        if let Some(limit) = limits.iter().find(|limit| limit.name() == name) {
+            return Arc::clone(limit);
+        }
+        let limit = AdaptiveLimit::new(name, max);
+        limits.push(Arc::clone(&limit));
+        limit
+    }
+
+    /// Show the limit in the stat
+    #[cfg(any(feature = "pgsql", feature = "mssql"))]
+    pub(crate) fn add_limit(&self, limit: Arc<AdaptiveLimit>) {
+        match self.limits.lock() {
+            Ok(mut limits) => limits.push(limit),
+            Err(e) => e.into_inner().push(limit),
+        }
+    }
+
+    /// Show the queue of the messages in the stat
+    #[cfg(all(feature = "cache", feature = "pgsql"))]
+    pub(crate) fn add_bus(&self, bus: Arc<BusMetrics>) {
+        match self.buses.lock() {
+            Ok(mut buses) => buses.push(bus),
+            Err(e) => e.into_inner().push(bus),
+        }
+    }
+
+    /// Current state of the queues of the messages
+    #[cfg(all(feature = "cache", feature = "pgsql"))]
+    pub fn get_buses(&self) -> Vec<BusStat> {
+        match self.buses.lock() {
+            Ok(buses) => buses.iter().map(|bus| bus.stat()).collect(),
+            Err(e) => e.into_inner().iter().map(|bus| bus.stat()).collect(),
+        }
+    }
+
+    /// Show the pool of the database in the stat
+    #[cfg(any(feature = "pgsql", feature = "mssql"))]
+    pub(crate) fn add_pool(&self, pool: Arc<PoolMetrics>) {
+        let _ = self.pool.set(pool);
+    }
+
+    /// Current state of the pool of the database
+    #[cfg(any(feature = "pgsql", feature = "mssql"))]
+    pub fn get_pool(&self) -> Option<PoolStat> {
+        self.pool.get().map(|pool| pool.stat())
+    }
+
+    /// Calls of the prepared statements of the library, sorted by the name
+    #[cfg(any(feature = "pgsql", feature = "mssql"))]
+    pub fn get_statements(&self) -> Vec<StatementStat> {
+        self.pool.get().map(|pool| pool.statements()).unwrap_or_default()
+    }
+
+    /// Add the phases of the finished request
+    pub(crate) fn add_timings(&self, timings: &Timings) {
+        let mut list = match self.timings.lock() {
+            Ok(list) => list,
+            Err(e) => e.into_inner(),
+        };
+        for (name, time) in timings.list() {
+            let item = list.entry(name.clone()).or_insert((0, 0));
+            item.0 += 1;
+            item.1 += time.as_micros() as u64;
+        }
+    }
+
+    /// Name, number and total time of the request phases, microseconds
+    pub fn get_timings(&self) -> Vec<(String, u64, u64)> {
+        let list = match self.timings.lock() {
+            Ok(list) => list,
+            Err(e) => e.into_inner(),
+        };
+        let mut res: Vec<(String, u64, u64)> = list.iter().map(|(name, (count, time))| (name.clone(), *count, *time)).collect();
+        res.sort_by(|a, b| a.0.cmp(&b.0));
+        res
+    }
+
+    /// Add the finished request
+    ///
+    /// `route` is `None` for the redirects and the errors before the controller, they are counted only by the status.
+    pub(crate) fn add_request(&self, route: Option<&str>, status: u16, bytes_in: u64, bytes_out: u64, time: Duration) {
+        self.routes.add(route, status, bytes_in, bytes_out, time);
+    }
+
+    /// Requests by the routes, sorted by the path
+    pub fn get_routes(&self) -> Vec<RouteStat> {
+        self.routes.routes(false)
+    }
+
+    /// Number of the answers by the status, sorted by the status
+    pub fn get_statuses(&self) -> Vec<(u16, u64)> {
+        self.routes.statuses(false)
+    }
+
+    /// Counters of the requests for the external monitoring
+    pub fn snapshot(&self) -> StatSnapshot {
+        StatSnapshot {
+            uptime: self.get_uptime(),
+            total: self.get_total(),
+            rejected: self.get_rejected(),
+            statuses: self.get_statuses(),
+            routes: self.get_routes(),
+            timings: self.get_timings(),
+        }
+    }
+
+    /// Counters of the requests and `reset`, each request is in one snapshot only
+    pub fn take(&self) -> StatSnapshot {
+        StatSnapshot {
+            uptime: self.get_uptime(),
+            total: self.get_total(),
+            rejected: self.get_rejected(),
+            statuses: self.routes.statuses(true),
+            routes: self.routes.routes(true),
+            timings: self.take_timings(),
+        }
+    }
+
+    /// Clear the statuses, the routes, the request phases and the max time in the queue
+    ///
+    /// The numbers of the requests, the pool, the limits and the queues of the messages are kept.
+    pub fn reset(&self) {
+        self.routes.statuses(true);
+        self.routes.routes(true);
+        self.take_timings();
+    }
+
+    fn take_timings(&self) -> Vec<(String, u64, u64)> {
+        let list = {
+            let mut list = match self.timings.lock() {
+                Ok(list) => list,
+                Err(e) => e.into_inner(),
+            };
+            std::mem::take(&mut *list)
+        };
+        self.queue_max.store(0, Ordering::Relaxed);
+        let mut res: Vec<(String, u64, u64)> = list.into_iter().map(|(name, (count, time))| (name, count, time)).collect();
+        res.sort_by(|a, b| a.0.cmp(&b.0));
+        res
+    }
+
+    /// Current state of the adaptive limits
+    pub fn get_limits(&self) -> Vec<LimitStat> {
+        match self.limits.lock() {
+            Ok(limits) => limits.iter().map(|limit| limit.stat()).collect(),
+            Err(e) => e.into_inner().iter().map(|limit| limit.stat()).collect(),
+        }
+    }
+
+    /// Metrics in the text format of Prometheus
+    pub fn prometheus(&self) -> String {
+        let mut text = String::with_capacity(4096);
+        Stat::metric(
+            &mut text,
+            "tiny_web_uptime_seconds",
+            "gauge",
+            "Time since the start of the server",
+            &[("", self.get_uptime().as_secs())],
+        );
+        Stat::metric(&mut text, "tiny_web_workers", "gauge", "Number of the workers", &[("", self.get_number())]);
+        Stat::metric(&mut text, "tiny_web_requests_online", "gauge", "Number of the online requests", &[("", self.get_online())]);
+        Stat::metric(&mut text, "tiny_web_requests_total", "counter", "Number of the requests", &[("", self.get_total())]);
+        Stat::metric(&mut text, "tiny_web_requests_queued", "gauge", "Requests waiting in the queue", &[("", self.get_queued())]);
+        Stat::metric(
+            &mut text,
+            "tiny_web_requests_rejected_total",
+            "counter",
+            "Requests rejected by the full queue",
+            &[("", self.get_rejected())],
+        );
+        Stat::metric(
+            &mut text,
+            "tiny_web_queue_max_microseconds",
+            "gauge",
+            "Max time of the request in the queue",
+            &[("", self.get_queue_max())],
+        );
+
+        let limits: Vec<(String, LimitStat)> =
+            self.get_limits().into_iter().map(|limit| (Stat::label("name", &limit.name), limit)).collect();
+        let list: Vec<(&str, u64)> = limits.iter().map(|(label, limit)| (label.as_str(), limit.limit as u64)).collect();
+        Stat::metric(&mut text, "tiny_web_limit", "gauge", "Current adaptive limit of the dependency", &list);
+        let list: Vec<(&str, u64)> = limits.iter().map(|(label, limit)| (label.as_str(), limit.inflight as u64)).collect();
+        Stat::metric(&mut text, "tiny_web_limit_inflight", "gauge", "Running calls of the dependency", &list);
+        let list: Vec<(&str, u64)> = limits.iter().map(|(label, limit)| (label.as_str(), limit.waiting as u64)).collect();
+        Stat::metric(&mut text, "tiny_web_limit_waiting", "gauge", "Waiting calls of the dependency", &list);
+        let list: Vec<(&str, u64)> = limits.iter().map(|(label, limit)| (label.as_str(), limit.latency)).collect();
+        Stat::metric(&mut text, "tiny_web_limit_latency_microseconds", "gauge", "Smoothed latency of the dependency", &list);
+
+        #[cfg(all(feature = "cache", feature = "pgsql"))]
+        {
+            let buses: Vec<(String, BusStat)> = self.get_buses().into_iter().map(|bus| (Stat::label("name", &bus.name), bus)).collect();
+            let list: Vec<(&str, u64)> = buses.iter().map(|(label, bus)| (label.as_str(), bus.len as u64)).collect();
+            Stat::metric(&mut text, "tiny_web_bus_messages", "gauge", "Messages in the queue", &list);
+            let list: Vec<(&str, u64)> = buses.iter().map(|(label, bus)| (label.as_str(), bus.sent)).collect();
+            Stat::metric(&mut text, "tiny_web_bus_sent_total", "counter", "Messages sent to the queue", &list);
+            let list: Vec<(&str, u64)> = buses.iter().map(|(label, bus)| (label.as_str(), bus.dropped)).collect();
+            Stat::metric(&mut text, "tiny_web_bus_dropped_total", "counter", "Messages dropped by the full queue", &list);
+            let list: Vec<(&str, u64)> = buses.iter().map(|(label, bus)| (label.as_str(), bus.lag_total)).collect();
+            Stat::metric(&mut text, "tiny_web_bus_lag_microseconds_total", "counter", "Total time of the messages in the queue", &list);
+            let list: Vec<(&str, u64)> = buses.iter().map(|(label, bus)| (label.as_str(), bus.lag_max)).collect();
+            Stat::metric(&mut text, "tiny_web_bus_lag_max_microseconds", "gauge", "Max time of the message in the queue", &list);
+        }
+
+        let statuses: Vec<(String, u64)> =
+            self.get_statuses().into_iter().map(|(status, count)| (Stat::label("code", &status.to_string()), count)).collect();
+        let list: Vec<(&str, u64)> = statuses.iter().map(|(label, count)| (label.as_str(), *count)).collect();
+        Stat::metric(&mut text, "tiny_web_responses_total", "counter", "Number of the answers by the status", &list);
+
+        let routes: Vec<(String, RouteStat)> =
+            self.get_routes().into_iter().map(|item| (Stat::label("route", &item.route), item)).collect();
+        let list: Vec<(&str, u64)> = routes.iter().map(|(label, item)| (label.as_str(), item.count)).collect();
+        Stat::metric(&mut text, "tiny_web_route_requests_total", "counter", "Number of the requests of the route", &list);
+        let list: Vec<(&str, u64)> = routes.iter().map(|(label, item)| (label.as_str(), item.errors)).collect();
+        Stat::metric(&mut text, "tiny_web_route_errors_total", "counter", "Answers of the route with the status 5xx", &list);
+        let list: Vec<(&str, u64)> = routes.iter().map(|(label, item)| (label.as_str(), item.bytes_in)).collect();
+        Stat::metric(&mut text, "tiny_web_route_received_bytes_total", "counter", "Bytes of the bodies of the requests", &list);
+        let list: Vec<(&str, u64)> = routes.iter().map(|(label, item)| (label.as_str(), item.bytes_out)).collect();
+        Stat::metric(&mut text, "tiny_web_route_sent_bytes_total", "counter", "Bytes of the answers", &list);
+        let list: Vec<(&str, u64)> = routes.iter().map(|(label, item)| (label.as_str(), item.time)).collect();
+        Stat::metric(&mut text, "tiny_web_route_microseconds_total", "counter", "Total time of the requests of the route", &list);
+        let quantiles: Vec<(String, u64)> = routes
+            .iter()
+            .flat_map(|(_, item)| {
+                let route = Stat::escape(&item.route);
+                [("0.5", item.p50), ("0.9", item.p90), ("0.99", item.p99), ("1", item.max)]
+                    .map(|(quantile, value)| (format!("{{route=\"{}\",quantile=\"{}\"}}", route, quantile), value))
+            })
+            .collect();
+        let list: Vec<(&str, u64)> = quantiles.iter().map(|(label, value)| (label.as_str(), *value)).collect();
+        Stat::metric(&mut text, "tiny_web_route_latency_microseconds", "gauge", "Latency of the requests of the route", &list);
+
+        let timings: Vec<(String, u64, u64)> =
+            self.get_timings().into_iter().map(|(name, count, time)| (Stat::label("name", &name), count, time)).collect();
+        let list: Vec<(&str, u64)> = timings.iter().map(|(label, count, _)| (label.as_str(), *count)).collect();
+        Stat::metric(&mut text, "tiny_web_timing_total", "counter", "Number of the request phases", &list);
+        let list: Vec<(&str, u64)> = timings.iter().map(|(label, _, time)| (label.as_str(), *time)).collect();
+        Stat::metric(&mut text, "tiny_web_timing_microseconds_total", "counter", "Total time of the request phases", &list);
+
+        #[cfg(any(feature = "pgsql", feature = "mssql"))]
+        if let Some(pool) = self.get_pool() {
+            Stat::metric(
+                &mut text,
+                "tiny_web_db_pool_size",
+                "gauge",
+                "Number of the connections to the database",
+                &[("", pool.size as u64)],
+            );
+            Stat::metric(&mut text, "tiny_web_db_pool_min", "gauge", "Connections kept open", &[("", pool.min as u64)]);
+            Stat::metric(
+                &mut text,
+                "tiny_web_db_pool_open",
+                "gauge",
+                "Open connections after the last health check",
+                &[("", pool.open as u64)],
+            );
+            Stat::metric(
+                &mut text,
+                "tiny_web_db_pool_broken_total",
+                "counter",
+                "Broken connections found by the health check",
+                &[("", pool.broken)],
+            );
+            Stat::metric(
+                &mut text,
+                "tiny_web_db_pool_timeouts_total",
+                "counter",
+                "Calls without the free connection during the timeout",
+                &[("", pool.timeouts)],
+            );
+            Stat::metric(&mut text, "tiny_web_db_pool_idle", "gauge", "Free connections to the database", &[("", pool.idle as u64)]);
+            Stat::metric(&mut text, "tiny_web_db_pool_waiters", "gauge", "Calls waiting for the free connection", &[("", pool.waiters)]);
+            Stat::metric(
+                &mut text,
+                "tiny_web_db_pool_acquire_total",
+                "counter",
+                "Number of the received connections",
+                &[("", pool.acquires)],
+            );
+            Stat::metric(
+                &mut text,
+                "tiny_web_db_pool_acquire_microseconds_total",
+                "counter",
+                "Total time of the waiting for the connection",
+                &[("", pool.acquire_time)],
+            );
+            Stat::metric(
+                &mut text,
+                "tiny_web_db_pool_acquire_max_microseconds",
+                "gauge",
+                "Max time of the waiting for the connection",
+                &[("", pool.acquire_max)],
+            );
+
+            let statements: Vec<(String, StatementStat)> =
+                self.get_statements().into_iter().map(|item| (Stat::label("statement", &item.name), item)).collect();
+            let list: Vec<(&str, u64)> = statements.iter().map(|(label, item)| (label.as_str(), item.count)).collect();
+            Stat::metric(&mut text, "tiny_web_db_statement_total", "counter", "Calls of the prepared statement", &list);
+            let list: Vec<(&str, u64)> = statements.iter().map(|(label, item)| (label.as_str(), item.errors)).collect();
+            Stat::metric(&mut text, "tiny_web_db_statement_errors_total", "counter", "Failed calls of the prepared statement", &list);
+            let list: Vec<(&str, u64)> = statements.iter().map(|(label, item)| (label.as_str(), item.time)).collect();
+            Stat::metric(&mut text, "tiny_web_db_statement_microseconds_total", "counter", "Total time of the prepared statement", &list);
+            let list: Vec<(&str, u64)> = statements.iter().map(|(label, item)| (label.as_str(), item.max)).collect();
+            Stat::metric(&mut text, "tiny_web_db_statement_max_microseconds", "gauge", "Max time of the prepared statement", &list);
+        }
+        text
+    }
+
+    /// One metric with the values by the labels, the label "" is without the labels
+    fn metric(text: &mut String, name: &str, kind: &str, help: &str, list: &[(&str, u64)]) {
+        if list.is_empty() {
+            return;
+        }
+        text.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+        for (label, value) in list {
+            text.push_str(&format!("{}{} {}\n", name, label, value));
+        }
+    }
+
+    /// Label `{key="value"}` with the escaped value
+    fn label(key: &str, value: &str) -> String {
+        format!("{{{}=\"{}\"}}", key, Stat::escape(value))
+    }
+
+    fn escape(value: &str) -> String {
+        value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+    }
 }
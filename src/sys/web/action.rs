@@ -1,8 +1,19 @@
-use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc};
+use std::{
+    borrow::Cow, collections::HashMap, future::Future, panic::AssertUnwindSafe, pin::Pin, str::FromStr, sync::Arc, time::SystemTime,
+};
 
 #[cfg(feature = "file-disk")]
 use std::io::ErrorKind;
 
+#[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+
+#[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+use chrono::TimeZone;
+
+use futures_util::FutureExt;
 use tokio::{
     sync::mpsc::Sender,
     task::{yield_now, JoinHandle},
@@ -26,11 +37,13 @@ use tokio::fs::remove_file;
 use tokio::sync::RwLock;
 
 use crate::{
-    fnv1a_64,
+    fnv1a_64, fnv1a_64_add,
     sys::{
-        net::{stream::MessageWrite, worker::Worker},
+        app::init::SecurityConfig,
+        net::{queue::RequestQueue, stream::MessageWrite, worker::Worker},
         stat::stat::Stat,
     },
+    tool::generate_nonce,
 };
 
 #[cfg(any(
@@ -39,43 +52,133 @@ use crate::{
 ))]
 use crate::log;
 
-#[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+#[cfg(any(
+    feature = "mail-sendmail",
+    feature = "mail-smtp",
+    feature = "mail-file",
+    feature = "mail-api"
+))]
 use crate::sys::app::init::MailConfig;
 
 #[cfg(any(feature = "pgsql", feature = "mssql"))]
 use crate::sys::db::adapter::DB;
 
 use super::{
+    clock::Clock,
+    controller::SizePolicy,
     data::{Data, StrOrI64},
-    request::{Request, Route},
-    response::Response,
+    handler::{Handler, Handlers},
+    request::{HttpMethod, Request, Route, UploadProgress},
+    response::{MultipartWriter, Redirect, Response},
+    router::Router,
+    timing::Timings,
+    toggle::ModuleToggle,
 };
 
 #[cfg(feature = "cache")]
-use super::cache::Cache;
+use super::{cache::Cache, flight::FlightGuard};
+
+#[cfg(feature = "privacy")]
+use super::privacy::Privacy;
+
+#[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+use crate::sys::log::Log;
+
+#[cfg(feature = "otel")]
+use crate::sys::otel::{Otel, Span, TraceContext};
+
+#[cfg(feature = "profile")]
+use crate::sys::profile::Profile;
+
+#[cfg(all(
+    feature = "profile",
+    any(
+        feature = "html-static",
+        feature = "html-reload",
+        feature = "mail-sendmail",
+        feature = "mail-smtp",
+        feature = "mail-file",
+        feature = "mail-db",
+        feature = "mail-api"
+    )
+))]
+use crate::sys::profile::Frame;
+
+#[cfg(feature = "cache")]
+use std::time::UNIX_EPOCH;
+
+/// Group of the private answers in the cache
+#[cfg(all(
+    feature = "cache",
+    any(feature = "session-memory", feature = "session-file", feature = "session-db")
+))]
+const PRIVATE_CACHE: &str = "sys:private";
+
+/// Group of the pages in the cache
+#[cfg(feature = "cache")]
+const PAGE_CACHE: &str = "sys:page";
 
 #[cfg(any(feature = "html-static", feature = "html-reload"))]
 use super::html::{Html, Nodes};
 
 #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
-use super::lang::{Lang, LangItem};
+use super::lang::{Lang, LangItem, Plural, PluralRule};
+#[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+use super::locale::{DateStyle, Locale};
+
+#[cfg(any(
+    feature = "mail-sendmail",
+    feature = "mail-smtp",
+    feature = "mail-file",
+    feature = "mail-db",
+    feature = "mail-api"
+))]
+use super::mail::MailMessage;
 
 #[cfg(any(
     feature = "mail-sendmail",
     feature = "mail-smtp",
     feature = "mail-file",
-    feature = "mail-db"
+    feature = "mail-db",
+    all(feature = "mail-api", any(feature = "html-static", feature = "html-reload"))
 ))]
-use super::mail::{Mail, MailMessage};
+use super::mail::Mail;
 
-#[cfg(feature = "redirect-db")]
-use super::response::Redirect;
+#[cfg(feature = "mail-api")]
+use super::mailapi::MailApi;
 
-#[cfg(feature = "file-disk")]
+#[cfg(all(
+    any(feature = "html-static", feature = "html-reload"),
+    any(
+        feature = "mail-sendmail",
+        feature = "mail-smtp",
+        feature = "mail-file",
+        feature = "mail-db",
+        feature = "mail-api"
+    )
+))]
+use super::mail::{MailBody, MailBodyHtml};
+
+#[cfg(any(feature = "file-disk", feature = "image"))]
 use super::request::WebFile;
 
+#[cfg(feature = "image")]
+use super::image::Image;
+
+#[cfg(feature = "storage")]
+use super::storage::Storage;
+
 #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-use super::session::{Flash, Session, SessionLoader};
+use super::{
+    response::Cookie,
+    session::{Consent, Flash, Purpose, Session, SessionLoader},
+};
+
+#[cfg(feature = "jwt")]
+use super::jwt::{Jwt, JwtClaims};
+
+#[cfg(feature = "plugin")]
+use crate::sys::plugin::Plugins;
 
 pub type Act = fn(&mut Action) -> Pin<Box<dyn Future<Output = Answer> + Send + '_>>;
 pub type ActionMap = HashMap<i64, Act>;
@@ -93,9 +196,23 @@ pub enum Answer {
     None,
 }
 
+/// Controller of the route
+enum Call {
+    Act(Act),
+    Handler(Handler),
+}
+
+impl Call {
+    fn call<'a>(&self, this: &'a mut Action) -> Pin<Box<dyn Future<Output = Answer> + Send + 'a>> {
+        match self {
+            Call::Act(a) => a(this),
+            Call::Handler(handler) => handler(this),
+        }
+    }
+}
+
 pub(crate) enum ActionRedirect {
-    Action(Action),
-    #[cfg(feature = "redirect-db")]
+    Action(Box<Action>),
     Redirect(Redirect),
 }
 
@@ -104,11 +221,20 @@ pub(crate) struct ActionData {
     pub id: u64,
     pub mon: Arc<Stat>,
     pub engine: Arc<ModuleMap>,
+    #[cfg(feature = "plugin")]
+    pub plugins: Arc<Plugins>,
+    pub router: Arc<Router>,
     pub salt: Arc<String>,
     pub request: Request,
     pub tx: Arc<Sender<MessageWrite>>,
+    /// FastCGI request id
+    #[cfg(feature = "fastcgi")]
+    pub request_id: u16,
     pub index: Arc<[i64; 3]>,
+    pub security: Arc<SecurityConfig>,
+    pub queue: Arc<RequestQueue>,
     pub not_found: Option<Arc<[i64; 3]>>,
+    pub action_err: Option<Arc<[i64; 3]>>,
     #[cfg(any(feature = "pgsql", feature = "mssql"))]
     pub db: Arc<DB>,
 
@@ -124,7 +250,12 @@ pub(crate) struct ActionData {
     pub lang: Arc<Lang>,
     #[cfg(feature = "lang-reload")]
     pub lang: Arc<RwLock<Lang>>,
-    #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+    #[cfg(any(
+        feature = "mail-sendmail",
+        feature = "mail-smtp",
+        feature = "mail-file",
+        feature = "mail-api"
+    ))]
     pub mail: Arc<MailConfig>,
     #[cfg(feature = "cache")]
     pub cache: Arc<Cache>,
@@ -148,20 +279,61 @@ pub struct Action {
     pub monitor: Arc<Stat>,
     #[cfg(any(feature = "pgsql", feature = "mssql"))]
     pub db: Arc<DB>,
+    /// Export and erasure of the personal data of the user
+    #[cfg(feature = "privacy")]
+    pub privacy: Privacy,
     #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
     pub session: Session,
     #[cfg(feature = "cache")]
     pub cache: Arc<Cache>,
 
     pub(crate) header_send: bool,
+    /// The answer without the length is sent by the chunks of HTTP/1.1
+    pub(crate) chunked: bool,
+    /// Bytes of the answer sent by parts
+    pub(crate) sent: u64,
+    pub(crate) timings: Timings,
+    /// Time of the start of the request by `Clock`
+    now: SystemTime,
+    /// Claims of the valid bearer token
+    #[cfg(feature = "jwt")]
+    jwt: Option<JwtClaims>,
+    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+    pub(crate) session_write: bool,
+    /// Name of the session cookie
+    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+    pub(crate) session_key: Arc<String>,
+    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+    session_loader: Arc<SessionLoader>,
+    /// Key and expiration of the private answer, set by `cache_private`
+    #[cfg(all(
+        feature = "cache",
+        any(feature = "session-memory", feature = "session-file", feature = "session-db")
+    ))]
+    private_cache: Option<(String, u64)>,
+    /// Key and expiration of the page, set by `cache_page`
+    #[cfg(feature = "cache")]
+    page_cache: Option<(String, u64)>,
+    /// Flight of the missing page, the same requests wait for it, see [web] coalesce
+    #[cfg(feature = "cache")]
+    page_flight: Option<FlightGuard>,
     pub(crate) tx: Arc<Sender<MessageWrite>>,
+    #[cfg(feature = "fastcgi")]
+    pub(crate) request_id: u16,
 
     current_module_id: i64,
     current_class_id: i64,
     route: Route,
+    route_params: HashMap<String, String>,
+    pub(crate) security: Arc<SecurityConfig>,
+    csp_nonce: Option<String>,
     data: HashMap<i64, Data>,
     engine: Arc<ModuleMap>,
+    #[cfg(feature = "plugin")]
+    plugins: Arc<Plugins>,
+    pub(crate) router: Arc<Router>,
     not_found: Option<Arc<[i64; 3]>>,
+    action_err: Option<Arc<[i64; 3]>>,
     #[cfg(any(feature = "html-static", feature = "html-reload"))]
     html: Option<Arc<HashMap<i64, Nodes>>>,
     #[cfg(feature = "html-static")]
@@ -176,8 +348,22 @@ pub struct Action {
     language: Arc<RwLock<Lang>>,
     #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
     lang_id: usize,
-    #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
-    mail: Arc<MailConfig>,
+    /// Plural rule of the language `lang_id`
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    plural: PluralRule,
+    /// Formatting of the dates and the numbers of the language `lang_id`
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    locale: Locale,
+    /// Prefix "/en" of the urls of the language `lang_id`, [web] lang_prefix
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    lang_prefix: Option<String>,
+    #[cfg(any(
+        feature = "mail-sendmail",
+        feature = "mail-smtp",
+        feature = "mail-file",
+        feature = "mail-api"
+    ))]
+    pub(crate) mail: Arc<MailConfig>,
 }
 
 impl Action {
@@ -214,6 +400,44 @@ impl Action {
         self.data.remove(&key.to_i64()).map(|value| value.into())
     }
 
+    /// Get the rest of the path split by "/"
+    ///
+    /// For example `["x", "y"]` for `/module/class/action/x/y`, or the path captured by `*rest` or `{path...}` of the route pattern.
+    /// The named parameters of the route are available by `path_param`.
+    pub fn param(&self) -> Vec<String> {
+        match &self.route.param {
+            Some(param) => param.split('/').filter(|s| !s.is_empty()).map(|s| s.to_owned()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Get the nonce of the Content-Security-Policy, for example `<script nonce="...">`
+    ///
+    /// Exists only if the `[security] csp` contains "{nonce}". The templates get it as the variable `csp_nonce`.
+    pub fn csp_nonce(&self) -> Option<&str> {
+        self.csp_nonce.as_deref()
+    }
+
+    /// Get progress of the upload of this client by the value of the "X-Upload-Id" header
+    ///
+    /// Returns (received, total) while the request body is being received.
+    /// The client is the session cookie, or the IP address without the session.
+    pub fn upload_progress(&self, id: &str) -> Option<(usize, usize)> {
+        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+        if !self.session.session.is_empty() {
+            return UploadProgress::get(self.session.session.clone(), id);
+        }
+        UploadProgress::get(self.request.ip?.to_string(), id)
+    }
+
+    /// Get typed named parameter captured by the router, for example `this.path_param::<i64>("id")`
+    ///
+    /// The parameters are `{name}`, `{name:type}` and `{name:regex}` of the route pattern, the catch-all `{path...}`
+    /// and the captured subdomain `{tenant}`, the text is `this.path_param::<String>("tenant")`.
+    pub fn path_param<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.route_params.get(name).and_then(|value| value.parse().ok())
+    }
+
     #[cfg(feature = "setting-db")]
     pub async fn get_setting(&self, key: impl StrOrI64) -> Option<String> {
         let res = self.db.query_prepare(m_fnv1a_64!("lib_get_setting"), &[&key.to_i64()]).await?;
@@ -236,6 +460,32 @@ impl Action {
         self.session.take_flash()
     }
 
+    /// Consent of the visitor to the non-essential cookies and scripts, see `Consent`
+    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+    pub fn consent(&self) -> Consent {
+        self.session.consent()
+    }
+
+    /// Store the answer of the visitor from the banner, for example `this.set_consent(Consent::all())`
+    ///
+    /// The cookies of the withdrawn categories are not removed, use `Cookie::remove` for them.
+    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+    pub fn set_consent(&mut self, consent: Consent) {
+        self.session.set_consent(consent);
+    }
+
+    /// Set the cookie of the category only with the consent of the visitor
+    ///
+    /// Returns `false` if the category is not allowed, the cookie is not set.
+    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+    pub fn set_cookie_for(&mut self, purpose: Purpose, cookie: Cookie) -> bool {
+        if !self.session.consent().allows(purpose) {
+            return false;
+        }
+        self.response.set_cookie(cookie);
+        true
+    }
+
     /// Get translate
     #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
     pub fn lang(&self, text: impl StrOrI64) -> String {
@@ -251,6 +501,71 @@ impl Action {
         }
     }
 
+    /// Prefix "/en" of the urls of the current language, [web] lang_prefix
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    pub(crate) fn lang_prefix(&self) -> Option<&str> {
+        self.lang_prefix.as_deref()
+    }
+
+    /// Get translate with the placeholders `{name}` replaced by the arguments
+    ///
+    /// ```ignore
+    /// let text = this.lang_fmt("greeting", &[("name", &user.name), ("count", &count)]);
+    /// ```
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    pub fn lang_fmt(&self, text: impl StrOrI64, args: &[(&str, &dyn Display)]) -> String {
+        Lang::format(&self.lang(text), args)
+    }
+
+    /// Get translate of the plural form of the number by the rules of the current language, `{n}` is the number
+    ///
+    /// The forms are the table of the key `apples = { one = "{n} apple", other = "{n} apples" }`,
+    /// the missing form is replaced by "other", then by the key itself.
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    pub fn lang_plural(&self, text: impl StrOrI64, n: i64) -> String {
+        let key = text.to_i64();
+        let form = self.plural.category(n);
+        if let Some(l) = &self.lang {
+            let str = l
+                .get(&fnv1a_64_add(key, form.suffix().as_bytes()))
+                .or_else(|| l.get(&fnv1a_64_add(key, Plural::Other.suffix().as_bytes())))
+                .or_else(|| l.get(&key));
+            if let Some(str) = str {
+                return Lang::format(str, &[("n", &n)]);
+            }
+        }
+        if text.is_str() {
+            text.to_str().to_owned()
+        } else {
+            key.to_string()
+        }
+    }
+
+    /// Date by the rules of the current language, in the time zone of `dt`
+    ///
+    /// ```ignore
+    /// this.format_date(&this.now(), DateStyle::Long); // "31 грудня 2024 р."
+    /// ```
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    pub fn format_date<Tz: TimeZone>(&self, dt: &DateTime<Tz>, style: DateStyle) -> String
+    where
+        Tz::Offset: Display,
+    {
+        self.locale.date(dt, style)
+    }
+
+    /// Number with the separators of the current language, for example "1,234.5" or "1 234,5"
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    pub fn format_number(&self, value: f64, decimals: usize) -> String {
+        self.locale.number(value, decimals)
+    }
+
+    /// Amount of the currency ISO 4217 by the rules of the current language, for example "$1,234.50" or "1 234,50 ₴"
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    pub fn format_currency(&self, value: f64, currency: &str) -> String {
+        self.locale.currency(value, currency)
+    }
+
     #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
     pub fn set_lang(&mut self, key: impl StrOrI64) {
         let idkey = key.to_i64();
@@ -300,6 +615,19 @@ impl Action {
         }
     }
 
+    /// Id of the default language [web] lang
+    #[cfg(all(feature = "route-db", any(feature = "lang-static", feature = "lang-reload")))]
+    pub(crate) async fn lang_default(&self) -> usize {
+        #[cfg(feature = "lang-static")]
+        {
+            self.language.default
+        }
+        #[cfg(feature = "lang-reload")]
+        {
+            self.language.read().await.default
+        }
+    }
+
     #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
     pub async fn lang_list(&self) -> Arc<Vec<Arc<LangItem>>> {
         #[cfg(feature = "lang-static")]
@@ -361,6 +689,15 @@ impl Action {
         tokio::spawn(future)
     }
 
+    /// Start the multipart answer, the parts are sent by `MultipartWriter::write`
+    ///
+    /// The subtype is "x-mixed-replace" for the stream of the images or "mixed".
+    pub fn multipart(&mut self, subtype: &str) -> MultipartWriter {
+        let writer = MultipartWriter::new();
+        self.response.content_type = Some(writer.content_type(subtype));
+        writer
+    }
+
     pub async fn write(&mut self, answer: Answer) {
         let vec = match answer {
             Answer::String(str) => str.as_bytes().to_vec(),
@@ -425,7 +762,13 @@ impl Action {
                     Action::format_route(module, class, action, param)
                 } else {
                     let row = unsafe { rows.get_unchecked(0) };
+                    #[cfg(feature = "pgsql")]
                     let url: String = row.get(0);
+                    #[cfg(feature = "mssql")]
+                    let url = match row.get::<&str, _>(0) {
+                        Some(url) => url.to_owned(),
+                        None => return Action::format_route(module, class, action, param),
+                    };
                     #[cfg(feature = "cache")]
                     self.cache.set(&cache_key, Data::String(url.clone())).await;
                     url
@@ -436,8 +779,18 @@ impl Action {
     }
 
     /// Render template
+    ///
+    /// The translations of the class are in the variable `t`, for example `{{ t.contact }}`.
+    /// The flash messages are in the variable `flash` and are removed from the session,
+    /// for example `{% for m in flash %}<p class="{{ m.kind }}">{{ m.text }}</p>{% endfor %}`.
+    /// The filters `date`, `longdate`, `time`, `datetime`, `longdatetime` and `number` format the values
+    /// by the rules of the current language, for example `{{ created|date }}`.
     #[cfg(any(feature = "html-static", feature = "html-reload"))]
     pub fn render(&mut self, template: impl StrOrI64) -> Answer {
+        #[cfg(feature = "otel")]
+        let _span = Span::internal("render");
+        #[cfg(feature = "profile")]
+        let _frame = Frame::start("render");
         match &self.html {
             Some(h) => match h.get(&template.to_i64()) {
                 Some(vec) => {
@@ -455,6 +808,22 @@ impl Action {
                         }
                         self.data.insert(m_fnv1a_64!("js"), Data::Vec(vec));
                     }
+                    if let Some(nonce) = &self.csp_nonce {
+                        self.data.insert(m_fnv1a_64!("csp_nonce"), Data::String(nonce.to_owned()));
+                    }
+                    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+                    self.data.insert(m_fnv1a_64!("consent"), self.session.consent().into());
+                    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+                    if let Some(flash) = self.session.take_flash_data() {
+                        self.data.insert(m_fnv1a_64!("flash"), flash);
+                    }
+                    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+                    if let Some(lang) = &self.lang {
+                        let map = lang.iter().map(|(key, value)| (*key, Data::String(value.to_owned()))).collect();
+                        self.data.insert(m_fnv1a_64!("t"), Data::Map(map));
+                    }
+                    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+                    self.data.insert(m_fnv1a_64!("locale"), Data::String(self.locale.code.to_owned()));
                     if !self.response.meta.is_empty() {
                         let mut vec = Vec::with_capacity(self.response.meta.len());
                         for meta in self.response.meta.drain(..) {
@@ -470,6 +839,51 @@ impl Action {
         }
     }
 
+    /// Body of the mail by the template of the current class, the text part is made of the html
+    ///
+    /// The template gets `data` and the translations of the current language as `t`.
+    ///
+    /// ```ignore
+    /// let mut data = HashMap::new();
+    /// data.insert(fnv1a_64!("name"), Data::String(name));
+    /// let body = this.mail_render("mail_welcome", data);
+    /// this.mail(MailMessage { subject: this.lang("mail_welcome").into(), body: vec![body], .. }).await
+    /// ```
+    #[cfg(all(
+        any(feature = "html-static", feature = "html-reload"),
+        any(
+            feature = "mail-sendmail",
+            feature = "mail-smtp",
+            feature = "mail-file",
+            feature = "mail-db",
+            feature = "mail-api"
+        )
+    ))]
+    pub fn mail_render(&self, template: impl StrOrI64, data: HashMap<i64, Data>) -> MailBody<'static> {
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        let mut data = data;
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        if let Some(lang) = &self.lang {
+            let map = lang.iter().map(|(key, value)| (*key, Data::String(value.to_owned()))).collect();
+            data.entry(m_fnv1a_64!("t")).or_insert(Data::Map(map));
+        }
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        data.entry(m_fnv1a_64!("locale")).or_insert(Data::String(self.locale.code.to_owned()));
+        let html = match self.html.as_ref().and_then(|h| h.get(&template.to_i64())) {
+            Some(nodes) => match Html::render(&data, nodes) {
+                Answer::String(html) => html,
+                Answer::Raw(raw) => String::from_utf8_lossy(&raw).into_owned(),
+                Answer::None => String::new(),
+            },
+            None => format!("{{{}}}", template.to_str()),
+        };
+        MailBody::Html(MailBodyHtml {
+            text: Some(Cow::Owned(Mail::text(&html))),
+            html: Cow::Owned(html),
+            file: Vec::new(),
+        })
+    }
+
     /// Get access to run controller
     #[cfg(feature = "access-db")]
     pub async fn get_access(&self, module: impl StrOrI64, class: impl StrOrI64, action: impl StrOrI64) -> bool {
@@ -500,7 +914,10 @@ impl Action {
             Some(rows) => {
                 if !rows.is_empty() {
                     let row = unsafe { rows.get_unchecked(0) };
+                    #[cfg(feature = "pgsql")]
                     let access = row.get(0);
+                    #[cfg(feature = "mssql")]
+                    let access = row.get::<bool, _>(0).unwrap_or(false);
                     #[cfg(feature = "cache")]
                     self.cache.set(&cache_key, Data::Bool(access)).await;
                     access
@@ -514,17 +931,427 @@ impl Action {
         }
     }
 
+    /// Short-lived ticket of the current session for WebSocket or SSE connections
+    ///
+    /// The browser cannot always send cookies to the cross-origin connection, so the page passes the ticket in the url.
+    /// Returns `None` for the anonymous visitor without the stored session.
+    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+    pub fn ticket(&self, ttl: u64) -> Option<String> {
+        self.session.ticket(&self.salt, ttl)
+    }
+
+    /// Verify the ticket and switch the request to the session of the ticket
+    ///
+    /// Returns `false` if the ticket is changed, expired, or the user of the session is changed after the ticket was issued.
+    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+    pub async fn check_ticket(&mut self, ticket: &str) -> bool {
+        let (session, user_id) = match Session::check_ticket(ticket, &self.salt) {
+            Some(value) => value,
+            None => return false,
+        };
+        if session == self.session.session {
+            return self.session.is_ticket_user(user_id);
+        }
+        match self.session_loader.load(Some(session), self.request.ip, &self.request.agent).await {
+            Ok(session) if session.is_ticket_user(user_id) => {
+                self.session = session;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Timings of the request phases, for example to skip the optional work of the slow request
+    pub fn timings(&self) -> &Timings {
+        &self.timings
+    }
+
+    /// Time of the start of the request, the same for the whole request
+    ///
+    /// The expiration of the cache, the sessions and the tokens is checked by `Clock`, the tests can freeze it.
+    pub fn now(&self) -> DateTime<Utc> {
+        DateTime::<Utc>::from(self.now)
+    }
+
+    /// Processing of the uploaded image, see `Image`, the image of the disk is sent by `image`
+    ///
+    /// `this.image_of(file).resize(800, 600).webp().save("upload/photo.webp").await`
+    #[cfg(feature = "image")]
+    pub fn image_of(&self, file: &WebFile) -> Image {
+        Image::new(file)
+    }
+
+    /// Storage of the files of the section [storage]
+    ///
+    /// The keys are the relative paths like "avatar/15.png", `url` returns the address of the file for the templates.
+    #[cfg(feature = "storage")]
+    pub fn storage(&self) -> &'static Storage {
+        Storage::global()
+    }
+
+    /// Add the mark of the controller to the Server-Timing header and the stat, see `Timings::mark`
+    pub fn mark(&mut self, name: &str) {
+        self.timings.mark(name);
+    }
+
+    /// Cache the answer of the user for `ttl` seconds, for example the expensive dashboard
+    ///
+    /// Returns the cached answer, otherwise the answer of the controller is cached.
+    /// The answer is stored per user and session, only for GET and HEAD with the status 200,
+    /// and it is removed when the user logs out or the session is changed.
+    ///
+    /// ```ignore
+    /// if let Some(answer) = this.cache_private(30).await {
+    ///     return answer;
+    /// }
+    /// ```
+    #[cfg(all(
+        feature = "cache",
+        any(feature = "session-memory", feature = "session-file", feature = "session-db")
+    ))]
+    pub async fn cache_private(&mut self, ttl: u64) -> Option<Answer> {
+        if !matches!(self.request.method, HttpMethod::Get | HttpMethod::Head) {
+            return None;
+        }
+        let owner = self.private_owner()?;
+        let mut params: Vec<_> = self.request.input.get.iter().collect();
+        params.sort();
+        let key = format!("{}{}", owner, fnv1a_64(format!("{}{}{:?}", self.request.host, self.request.url, params).as_bytes()));
+        let now = self.now.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        if let Some(Data::Vec(vec)) = self.cache.get(&key).await {
+            if let [Data::U64(expires), Data::String(content_type), answer] = &vec[..] {
+                if *expires > now {
+                    if !content_type.is_empty() {
+                        self.response.content_type = Some(content_type.clone());
+                    }
+                    match answer {
+                        Data::String(str) => return Some(Answer::String(str.clone())),
+                        Data::Raw(raw) => return Some(Answer::Raw(raw.clone())),
+                        _ => {}
+                    }
+                }
+            }
+            self.cache.remove(&key).await;
+        }
+        self.private_cache = Some((key, now + ttl));
+        None
+    }
+
+    /// Group of the private answers of the user and the session
+    #[cfg(all(
+        feature = "cache",
+        any(feature = "session-memory", feature = "session-file", feature = "session-db")
+    ))]
+    fn private_owner(&self) -> Option<String> {
+        if self.session.session.is_empty() {
+            return None;
+        }
+        #[cfg(feature = "access-db")]
+        let user_id = self.session.user_id.unwrap_or(0);
+        #[cfg(not(feature = "access-db"))]
+        let user_id = 0;
+        Some(format!("{}:{}:{}:", PRIVATE_CACHE, user_id, self.session.session))
+    }
+
+    /// Store the answer marked by `cache_private` or remove the answers of the previous owner of the session
+    #[cfg(all(
+        feature = "cache",
+        any(feature = "session-memory", feature = "session-file", feature = "session-db")
+    ))]
+    async fn private_store(&mut self, owner: Option<String>, answer: &Answer) {
+        let current = self.private_owner();
+        if owner != current {
+            if let Some(owner) = owner {
+                if self.cache.contains_group(&owner).await {
+                    self.cache.remove(&owner).await;
+                }
+            }
+            return;
+        }
+        let (key, expires) = match self.private_cache.take() {
+            Some(private) => private,
+            None => return,
+        };
+        if !matches!(self.response.http_code, None | Some(200)) || self.response.redirect.is_some() {
+            return;
+        }
+        let answer = match answer {
+            Answer::String(str) => Data::String(str.clone()),
+            Answer::Raw(raw) => Data::Raw(raw.clone()),
+            Answer::None => return,
+        };
+        let content_type = self.response.content_type.clone().unwrap_or_default();
+        self.cache.set(&key, Data::Vec(vec![Data::U64(expires), Data::String(content_type), answer])).await;
+    }
+
+    /// Cache the page for `ttl` seconds for all visitors with the same url, language and role
+    ///
+    /// The next GET and HEAD requests of the page are answered from the cache without the controller,
+    /// only after the middleware. The answer with the status 200, without the redirect and the cookies is cached
+    /// with the headers, the nonce of the CSP in the cached page is replaced by the nonce of each request.
+    /// The answer has `Vary: Accept-Language`. The pages of the authenticated users are not cached, see `cache_private`.
+    ///
+    /// ```ignore
+    /// this.cache_page(300);
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache_page(&mut self, ttl: u64) {
+        if !matches!(self.request.method, HttpMethod::Get | HttpMethod::Head) || ttl == 0 || self.is_personal() {
+            return;
+        }
+        let now = match self.now.duration_since(UNIX_EPOCH) {
+            Ok(now) => now.as_secs(),
+            Err(_) => return,
+        };
+        if self.page_cache.is_none() {
+            self.response.headers.push(("Vary".to_owned(), "Accept-Language".to_owned()));
+        }
+        self.page_cache = Some((self.page_key(), now + ttl));
+    }
+
+    /// Remove all pages cached by `cache_page`, for example after the change of the content
+    #[cfg(feature = "cache")]
+    pub async fn cache_page_clear(&self) {
+        self.cache.remove(&format!("{}:", PAGE_CACHE)).await;
+    }
+
+    /// Key of the page by the role, the language, the host, the url and the GET params
+    #[cfg(feature = "cache")]
+    fn page_key(&self) -> String {
+        #[cfg(feature = "access-db")]
+        let role_id = self.session.role_id.unwrap_or(0);
+        #[cfg(not(feature = "access-db"))]
+        let role_id = 0;
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        let lang_id = self.lang_id;
+        #[cfg(not(any(feature = "lang-static", feature = "lang-reload")))]
+        let lang_id = 0;
+        let mut params: Vec<_> = self.request.input.get.iter().collect();
+        params.sort();
+        let page = fnv1a_64(format!("{}{}{:?}", self.request.host, self.request.url, params).as_bytes());
+        format!("{}:{}:{}:{}", PAGE_CACHE, role_id, lang_id, page)
+    }
+
+    /// The page cached by `cache_page`
+    ///
+    /// With the prefix "sys:page:" in [web] coalesce the first request of the missing page runs the controller,
+    /// the same requests wait for its answer in the cache. Without `cache_page` they run the controller after it.
+    #[cfg(feature = "cache")]
+    async fn page_load(&mut self) -> Option<Answer> {
+        if !matches!(self.request.method, HttpMethod::Get | HttpMethod::Head) {
+            return None;
+        }
+        let key = self.page_key();
+        if let Some(answer) = self.page_get(&key).await {
+            return Some(answer);
+        }
+        if !self.cache.flight().is_on(&key) {
+            return None;
+        }
+        loop {
+            match self.cache.flight().lead(&key) {
+                Some(guard) => {
+                    let answer = self.page_get(&key).await;
+                    if answer.is_none() {
+                        self.page_flight = Some(guard);
+                    }
+                    return answer;
+                }
+                None => {
+                    if self.cache.flight().wait(&key).await {
+                        return self.page_get(&key).await;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The page of the key, the expired page is removed
+    #[cfg(feature = "cache")]
+    async fn page_get(&mut self, key: &str) -> Option<Answer> {
+        let vec = match self.cache.get(key).await {
+            Some(Data::Vec(vec)) => vec,
+            Some(_) => {
+                self.cache.remove(key).await;
+                return None;
+            }
+            None => return None,
+        };
+        let now = self.now.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        if let [Data::U64(expires), Data::String(content_type), Data::String(nonce), Data::Vec(headers), answer] = &vec[..] {
+            // The nonce of the stored page is replaced by the nonce of the request
+            let new = match (nonce.is_empty(), &self.csp_nonce) {
+                (false, Some(new)) => Some(new.as_str()),
+                _ => None,
+            };
+            let answer = match (answer, new) {
+                (Data::String(str), Some(new)) => Some(Answer::String(str.replace(nonce.as_str(), new))),
+                (Data::String(str), None) => Some(Answer::String(str.clone())),
+                (Data::Raw(raw), Some(new)) => Some(Answer::Raw(Action::page_nonce(raw, nonce.as_bytes(), new.as_bytes()))),
+                (Data::Raw(raw), None) => Some(Answer::Raw(raw.clone())),
+                _ => None,
+            };
+            if let (true, Some(answer)) = (*expires > now, answer) {
+                if !content_type.is_empty() {
+                    self.response.content_type = Some(content_type.clone());
+                }
+                for header in headers {
+                    if let Data::Vec(header) = header {
+                        if let [Data::String(name), Data::String(value)] = &header[..] {
+                            let value = match new {
+                                Some(new) => value.replace(nonce.as_str(), new),
+                                None => value.clone(),
+                            };
+                            self.response.headers.push((name.clone(), value));
+                        }
+                    }
+                }
+                return Some(answer);
+            }
+        }
+        self.cache.remove(key).await;
+        None
+    }
+
+    /// Bytes of the page with the replaced nonce
+    #[cfg(feature = "cache")]
+    fn page_nonce(data: &[u8], nonce: &[u8], new: &[u8]) -> Vec<u8> {
+        let mut res = Vec::with_capacity(data.len());
+        let mut pos = 0;
+        while pos < data.len() {
+            if data[pos..].starts_with(nonce) {
+                res.extend_from_slice(new);
+                pos += nonce.len();
+            } else {
+                res.push(data[pos]);
+                pos += 1;
+            }
+        }
+        res
+    }
+
+    /// Store the page marked by `cache_page`, then the flight of the page is ended
+    #[cfg(feature = "cache")]
+    async fn page_store(&mut self, answer: &Answer) {
+        let _flight = self.page_flight.take();
+        let (key, expires) = match self.page_cache.take() {
+            Some(page) => page,
+            None => return,
+        };
+        if !matches!(self.response.http_code, None | Some(200))
+            || self.response.redirect.is_some()
+            || !self.response.cookies.is_empty()
+            || self.is_personal()
+        {
+            return;
+        }
+        let answer = match answer {
+            Answer::String(str) => Data::String(str.clone()),
+            Answer::Raw(raw) => Data::Raw(raw.clone()),
+            Answer::None => return,
+        };
+        let headers = self
+            .response
+            .headers
+            .iter()
+            .map(|(name, value)| Data::Vec(vec![Data::String(name.clone()), Data::String(value.clone())]))
+            .collect();
+        let content_type = self.response.content_type.clone().unwrap_or_default();
+        let nonce = self.csp_nonce.clone().unwrap_or_default();
+        self.cache
+            .set(&key, Data::Vec(vec![Data::U64(expires), Data::String(content_type), Data::String(nonce), Data::Vec(headers), answer]))
+            .await;
+    }
+
+    /// Issue the signed bearer token, for example `this.jwt_issue(&JwtClaims::new(user_id, role_id, 3600))`
+    ///
+    /// The request with the `Authorization: Bearer` header gets `session.user_id` and `session.role_id` from the token.
+    #[cfg(feature = "jwt")]
+    pub fn jwt_issue(&self, claims: &JwtClaims) -> Option<String> {
+        Jwt::encode(claims, &self.salt)
+    }
+
+    /// Claims of the valid bearer token of the request
+    #[cfg(feature = "jwt")]
+    pub fn jwt(&self) -> Option<&JwtClaims> {
+        self.jwt.as_ref()
+    }
+
+    /// The answer belongs to the authenticated user
+    pub(crate) fn is_personal(&self) -> bool {
+        #[cfg(feature = "access-db")]
+        if self.session.user_id.is_some_and(|user_id| user_id > 0) {
+            return true;
+        }
+        #[cfg(feature = "jwt")]
+        if self.jwt.is_some() {
+            return true;
+        }
+        false
+    }
+
+    /// Check the business permission of the user role, for example `this.can("article.edit").await`
+    ///
+    /// The permission is granted by the `role_permission` table to the role or to its parents from `role_parent`.
+    /// The nearest role wins, then the longest name, so "article.*" grants everything in "article." and
+    /// `access = false` of the child role denies the permission granted to the parent.
+    #[cfg(feature = "access-db")]
+    pub async fn can(&self, permission: &str) -> bool {
+        let role_id = match self.session.role_id {
+            Some(role_id) => role_id as i64,
+            None => 0,
+        };
+        #[cfg(feature = "cache")]
+        let cache_key = format!("sys:permission:{}:{}", role_id, permission);
+        #[cfg(feature = "cache")]
+        if let Some(Data::Bool(access)) = self.cache.get(&cache_key).await {
+            return access;
+        }
+
+        let access = match self.db.query_prepare(m_fnv1a_64!("lib_get_permission"), &[&role_id, &permission]).await {
+            #[cfg(feature = "pgsql")]
+            Some(rows) => rows.first().map(|row| row.get::<_, bool>(0)).unwrap_or(false),
+            #[cfg(feature = "mssql")]
+            Some(rows) => rows.first().and_then(|row| row.get::<bool, _>(0)).unwrap_or(false),
+            None => return false,
+        };
+        #[cfg(feature = "cache")]
+        self.cache.set(&cache_key, Data::Bool(access)).await;
+        access
+    }
+
+    /// Invalidate the cached permissions of all roles, call it after the change of `role_permission` or `role_parent`
+    ///
+    /// With the channel [db] notify the permissions are invalidated on all nodes.
+    #[cfg(feature = "access-db")]
+    pub async fn reset_permission(&self) {
+        #[cfg(feature = "cache")]
+        {
+            self.cache.remove("sys:permission:").await;
+            self.db.notify_cache("sys:permission:").await;
+        }
+    }
+
     #[cfg(any(
         feature = "mail-sendmail",
         feature = "mail-smtp",
         feature = "mail-file",
-        feature = "mail-db"
+        feature = "mail-db",
+        feature = "mail-api"
     ))]
     pub async fn mail(&self, message: MailMessage<'_>) -> Result<(), ()> {
+        #[cfg(feature = "otel")]
+        let _span = Span::client("mail.send");
+        #[cfg(feature = "profile")]
+        let _frame = Frame::start("mail.send");
         #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
         {
             Mail::send(Arc::clone(&self.mail), &self.request.host, message).await
         }
+        #[cfg(feature = "mail-api")]
+        {
+            MailApi::send(self, message).await
+        }
         #[cfg(feature = "mail-db")]
         {
             Mail::send(self, message).await
@@ -591,12 +1418,19 @@ impl Action {
         if !res.is_empty() {
             let row = unsafe { res.get_unchecked(0) };
             let route = {
-                let module_id = row.get(0);
-                let class_id = row.get(1);
-                let action_id = row.get(2);
-                let param = row.get(3);
-                #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+                #[cfg(feature = "pgsql")]
+                let (module_id, class_id, action_id, param) = (row.get(0), row.get(1), row.get(2), row.get(3));
+                #[cfg(feature = "mssql")]
+                let (module_id, class_id, action_id, param) = match (row.get::<i64, _>(0), row.get::<i64, _>(1), row.get::<i64, _>(2)) {
+                    (Some(module_id), Some(class_id), Some(action_id)) => {
+                        (module_id, class_id, action_id, row.get::<&str, _>(3).map(str::to_owned))
+                    }
+                    _ => return Err(()),
+                };
+                #[cfg(all(feature = "pgsql", any(feature = "lang-static", feature = "lang-reload")))]
                 let lang_id: Option<i64> = row.get(4);
+                #[cfg(all(feature = "mssql", any(feature = "lang-static", feature = "lang-reload")))]
+                let lang_id = row.get::<i64, _>(4);
 
                 Route {
                     module_id,
@@ -617,7 +1451,14 @@ impl Action {
         }
     }
 
-    pub(crate) async fn init(data: ActionData) -> Result<ActionRedirect, ()> {
+    pub(crate) async fn init(data: ActionData, mut timings: Timings) -> Result<ActionRedirect, ()> {
+        if let Some(url) = data.router.canonical_redirect_url(&data.request) {
+            #[cfg(feature = "file-disk")]
+            tokio::spawn(async move {
+                Action::clean_file(data.request.input.file).await;
+            });
+            return Ok(ActionRedirect::Redirect(Redirect { url, permanently: true }));
+        }
         #[cfg(any(feature = "redirect-db", feature = "route-db"))]
         let param = RouteRedirectParam {
             db: Arc::clone(&data.db),
@@ -643,9 +1484,22 @@ impl Action {
             }
             _ => {}
         }
+        // The url without the language prefix "/en/..." of [web] lang_prefix
+        #[cfg(feature = "lang-static")]
+        let prefix = data.lang.prefix(&data.request.url);
+        #[cfg(feature = "lang-reload")]
+        let prefix = data.lang.read().await.prefix(&data.request.url);
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        let (prefix_lang_id, url) = match prefix {
+            Some((lang_id, url)) => (Some(lang_id), Cow::Owned(url)),
+            None => (None, Cow::Borrowed(data.request.url.as_str())),
+        };
+        #[cfg(not(any(feature = "lang-static", feature = "lang-reload")))]
+        let url = Cow::Borrowed(data.request.url.as_str());
+
         #[cfg(feature = "route-db")]
-        let route = match Action::check_route(&param).await {
-            Ok(Some(route)) => route,
+        let (route, route_params) = match Action::check_route(&param).await {
+            Ok(Some(route)) => (route, HashMap::new()),
             Err(_) => {
                 #[cfg(feature = "file-disk")]
                 tokio::spawn(async move {
@@ -653,19 +1507,25 @@ impl Action {
                 });
                 return Err(());
             }
-            _ => Action::extract_route(&data.request, data.index),
+            _ => Action::extract_route(&data.request, &url, data.index, &data.router),
         };
         #[cfg(not(feature = "route-db"))]
-        let route = Action::extract_route(&data.request, data.index);
+        let (route, route_params) = Action::extract_route(&data.request, &url, data.index, &data.router);
 
         let response = Response {
             redirect: None,
             content_type: None,
             headers: Vec::new(),
             http_code: None,
+            cache: None,
+            etag: None,
+            last_modified: None,
             css: Vec::new(),
             js: Vec::new(),
             meta: Vec::new(),
+            cookies: Vec::new(),
+            max_size: None,
+            size_policy: SizePolicy::Error,
         };
 
         let current_module_id = route.module_id;
@@ -675,8 +1535,20 @@ impl Action {
         let html = data.html.list.get(&current_module_id).and_then(|module| module.get(&current_class_id).cloned());
         #[cfg(feature = "html-reload")]
         let html = data.html.read().await.list.get(&current_module_id).and_then(|module| module.get(&current_class_id).cloned());
+        timings.mark("route");
+
+        // The API request with the bearer token works without the session cookie, the user is taken from the token
+        #[cfg(feature = "jwt")]
+        let jwt = data.request.header("Authorization").and_then(Jwt::bearer).and_then(|token| Jwt::decode(token, &data.salt));
+        #[cfg(feature = "jwt")]
+        let session_id = if jwt.is_some() { None } else { data.session };
+        #[cfg(all(
+            not(feature = "jwt"),
+            any(feature = "session-memory", feature = "session-file", feature = "session-db")
+        ))]
+        let session_id = data.session;
         #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
-        let session = match data.session_loader.load(data.session).await {
+        let mut session = match data.session_loader.load(session_id, data.request.ip, &data.request.agent).await {
             Ok(session) => session,
             Err(_) => {
                 #[cfg(feature = "file-disk")]
@@ -687,60 +1559,105 @@ impl Action {
             }
         };
 
+        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+        timings.mark("session");
+
+        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+        if data.request.client_kind().is_robot() {
+            session.set_robot();
+        }
+
+        #[cfg(feature = "jwt")]
+        if let Some(claims) = &jwt {
+            session.user_id = claims.sub.parse().ok();
+            session.role_id = Some(claims.role);
+            session.set_robot();
+        }
+
+        #[cfg(feature = "lang-reload")]
+        let language = data.lang.read().await;
+        #[cfg(feature = "lang-static")]
+        let language = &data.lang;
         #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
-        let lang_id = if let Some(lang_id) = route.lang_id {
+        let lang_id = if let Some(lang_id) = route.lang_id.or(prefix_lang_id) {
             lang_id
         } else if let Some(lang_id) = session.get_lang_id() {
             lang_id
+        } else if let Some(lang_id) = data.request.header("accept-language").and_then(|header| language.accept(header)) {
+            lang_id
         } else {
-            #[cfg(feature = "lang-static")]
-            {
-                data.lang.default
-            }
-            #[cfg(feature = "lang-reload")]
-            {
-                data.lang.read().await.default
-            }
+            language.default
         };
-
-        #[cfg(feature = "lang-static")]
-        let lang = data
-            .lang
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        let lang_item = language.langs.iter().find(|item| item.id == lang_id);
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        let plural = lang_item.map(|item| PluralRule::new(&item.code)).unwrap_or_default();
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        let locale = lang_item.map(|item| Locale::new(&item.code)).unwrap_or_default();
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        let lang_prefix = lang_item.filter(|_| language.prefix).map(|item| format!("/{}", item.code));
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        let lang = language
             .list
             .get(&lang_id)
             .and_then(|langs| langs.get(&current_module_id))
             .and_then(|module| module.get(&current_class_id).cloned());
         #[cfg(feature = "lang-reload")]
-        let lang = data
-            .lang
-            .read()
-            .await
-            .list
-            .get(&lang_id)
-            .and_then(|langs| langs.get(&current_module_id))
-            .and_then(|module| module.get(&current_class_id).cloned());
+        drop(language);
 
-        Ok(ActionRedirect::Action(Action {
+        Ok(ActionRedirect::Action(Box::new(Action {
             id: data.id,
             request: data.request,
             response,
             salt: data.salt,
             route,
+            route_params,
             internal: false,
             monitor: data.mon,
+            #[cfg(feature = "privacy")]
+            privacy: Privacy::new(Arc::clone(&data.db)),
             #[cfg(any(feature = "pgsql", feature = "mssql"))]
             db: data.db,
             #[cfg(feature = "cache")]
             cache: data.cache,
 
             header_send: false,
+            chunked: false,
+            sent: 0,
+            timings,
+            now: Clock::system(),
+            #[cfg(feature = "jwt")]
+            jwt,
+            #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+            session_write: true,
+            #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+            session_key: Arc::clone(&data.session_loader.session_key),
+            #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+            session_loader: data.session_loader,
+            #[cfg(all(
+                feature = "cache",
+                any(feature = "session-memory", feature = "session-file", feature = "session-db")
+            ))]
+            private_cache: None,
+            #[cfg(feature = "cache")]
+            page_cache: None,
+            #[cfg(feature = "cache")]
+            page_flight: None,
             tx: data.tx,
+            #[cfg(feature = "fastcgi")]
+            request_id: data.request_id,
 
             current_module_id,
             current_class_id,
             data: HashMap::new(),
             engine: data.engine,
+            #[cfg(feature = "plugin")]
+            plugins: data.plugins,
+            router: data.router,
             not_found: data.not_found,
+            action_err: data.action_err,
+            csp_nonce: if data.security.use_nonce() { Some(generate_nonce()) } else { None },
+            security: data.security,
             #[cfg(any(feature = "html-static", feature = "html-reload"))]
             html,
             #[cfg(any(feature = "html-static", feature = "html-reload"))]
@@ -753,13 +1670,170 @@ impl Action {
             language: data.lang,
             #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
             lang_id,
-            #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+            #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+            plural,
+            #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+            locale,
+            #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+            lang_prefix,
+            #[cfg(any(
+                feature = "mail-sendmail",
+                feature = "mail-smtp",
+                feature = "mail-file",
+                feature = "mail-api"
+            ))]
             mail: data.mail,
-        }))
+        })))
     }
 
     pub(crate) async fn run(action: &mut Action) -> Vec<u8> {
-        let answer = match action.start_route(action.route.clone(), false).await {
+        #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+        {
+            let id = action.id;
+            Log::scope(id, Action::trace(action)).await
+        }
+        #[cfg(not(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv")))]
+        Action::trace(action).await
+    }
+
+    /// Answer after the panic of the controller, the panic is already in the log
+    ///
+    /// 500 with the controller `action_err`, without it in debug the page with the message of the panic.
+    /// The session is not saved, the answer already sent by parts is not changed.
+    pub(crate) async fn panic_page(action: &mut Action, _message: &str) -> Vec<u8> {
+        action.response.http_code = Some(500);
+        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+        {
+            action.session_write = false;
+        }
+        if action.header_send {
+            return Vec::new();
+        }
+        action.response.redirect = None;
+        action.response.content_type = None;
+        action.response.headers.clear();
+        action.response.cache = None;
+        action.response.etag = None;
+        action.response.last_modified = None;
+        if let Some(action_err) = action.action_err.clone() {
+            #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+            let id = action.id;
+            let answer = AssertUnwindSafe(action.invoke(
+                unsafe { *action_err.get_unchecked(0) },
+                unsafe { *action_err.get_unchecked(1) },
+                unsafe { *action_err.get_unchecked(2) },
+                None,
+                false,
+            ))
+            .catch_unwind();
+            #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+            let answer = Log::scope(id, answer).await;
+            #[cfg(not(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv")))]
+            let answer = answer.await;
+            match answer {
+                Ok(Some(Answer::String(str))) => return str.into_bytes(),
+                Ok(Some(Answer::Raw(vec))) => return vec,
+                Ok(Some(Answer::None)) | Ok(None) => {}
+                // The page of the error has panicked too
+                Err(_) => {
+                    action.response.content_type = None;
+                    return Vec::new();
+                }
+            }
+        }
+        #[cfg(debug_assertions)]
+        {
+            action.response.content_type = Some("text/html; charset=utf-8".to_owned());
+            let message = _message.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+            format!(
+                "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>500 Internal Server Error</title></head>\
+                <body><h1>500 Internal Server Error</h1><p>Panic in {}</p><pre>{}</pre><p>Backtrace is in the log, request {}</p></body></html>",
+                action.route_path().replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;"),
+                message,
+                action.id
+            )
+            .into_bytes()
+        }
+        #[cfg(not(debug_assertions))]
+        Vec::new()
+    }
+
+    /// Answer inside the span of the request
+    async fn trace(action: &mut Action) -> Vec<u8> {
+        #[cfg(feature = "otel")]
+        {
+            let parent = action.request.header("traceparent").and_then(TraceContext::parse);
+            let mut span = Span::server(action.request.method.as_str(), parent);
+            span.attr("http.request.method", action.request.method.as_str());
+            span.attr("url.path", action.request.url.as_str());
+            span.attr("server.address", action.request.host.as_str());
+            let answer = Otel::scope(span.context(), Action::sample(action)).await;
+            let status = action.response.http_code.unwrap_or(if action.response.redirect.is_some() { 302 } else { 200 });
+            span.attr("http.response.status_code", status);
+            if status >= 500 {
+                span.error(&status.to_string());
+            }
+            answer
+        }
+        #[cfg(not(feature = "otel"))]
+        Action::sample(action).await
+    }
+
+    /// Answer, the sampled request is added to the profile of the route
+    async fn sample(action: &mut Action) -> Vec<u8> {
+        #[cfg(feature = "profile")]
+        {
+            let route = action.route_path();
+            Profile::scope(&route, Action::answer(action)).await
+        }
+        #[cfg(not(feature = "profile"))]
+        Action::answer(action).await
+    }
+
+    /// Path of the route without the parameter and the query, for example "/shop/cart/add"
+    pub(crate) fn route_path(&self) -> String {
+        let path = self.request.url.split('?').next().unwrap_or_default().trim_end_matches('/');
+        match self.route.param.as_deref() {
+            Some(param) if !param.is_empty() => path.strip_suffix(param).unwrap_or(path).trim_end_matches('/').to_owned(),
+            _ => path.to_owned(),
+        }
+    }
+
+    async fn answer(action: &mut Action) -> Vec<u8> {
+        #[cfg(all(
+            feature = "cache",
+            any(feature = "session-memory", feature = "session-file", feature = "session-db")
+        ))]
+        let owner = action.private_owner();
+        #[cfg(feature = "setting-db")]
+        ModuleToggle::refresh(action).await;
+        if let Some(code) = ModuleToggle::get(action.route.module_id) {
+            action.timings.mark("controller");
+            return match action.module_off(code).await {
+                Answer::String(str) => str.into_bytes(),
+                Answer::Raw(vec) => vec,
+                Answer::None => Vec::new(),
+            };
+        }
+        let answer = match action.run_middleware(action.route.module_id).await {
+            Some(answer) => answer,
+            #[cfg(feature = "cache")]
+            None => match action.page_load().await {
+                Some(answer) => answer,
+                None => action.start_route(action.route.clone(), false).await,
+            },
+            #[cfg(not(feature = "cache"))]
+            None => action.start_route(action.route.clone(), false).await,
+        };
+        action.timings.mark("controller");
+        #[cfg(feature = "cache")]
+        action.page_store(&answer).await;
+        #[cfg(all(
+            feature = "cache",
+            any(feature = "session-memory", feature = "session-file", feature = "session-db")
+        ))]
+        action.private_store(owner, &answer).await;
+        let answer = match answer {
             Answer::String(str) => str.as_bytes().to_vec(),
             Answer::Raw(vec) => vec,
             Answer::None => Vec::new(),
@@ -768,134 +1842,295 @@ impl Action {
     }
 
     async fn start_route(&mut self, route: Route, internal: bool) -> Answer {
+        if !internal && matches!(self.request.method, HttpMethod::Options) {
+            let options = fnv1a_64_add(route.action_id, b"_options");
+            if self.get_act(route.module_id, route.class_id, options).await.is_none() {
+                return self.options(route.module_id, route.class_id, route.action_id).await;
+            }
+        }
+        let action_id = if internal {
+            route.action_id
+        } else {
+            match self.method_action(route.module_id, route.class_id, route.action_id).await {
+                Ok(action_id) => action_id,
+                Err(allow) => {
+                    self.response.http_code = Some(405);
+                    self.response.headers.push(("Allow".to_owned(), allow));
+                    return Answer::None;
+                }
+            }
+        };
         #[cfg(feature = "access-db")]
         if self.get_access(route.module_id, route.class_id, route.action_id).await {
-            if let Some(answer) = self.invoke(route.module_id, route.class_id, route.action_id, route.param, internal).await {
+            if let Some(answer) = self.invoke(route.module_id, route.class_id, action_id, route.param, internal).await {
                 return answer;
             };
         }
         #[cfg(not(feature = "access-db"))]
-        if let Some(answer) = self.invoke(route.module_id, route.class_id, route.action_id, route.param, internal).await {
+        if let Some(answer) = self.invoke(route.module_id, route.class_id, action_id, route.param, internal).await {
             return answer;
         };
         if !internal && !self.request.ajax {
             self.response.http_code = Some(404);
-            if let Some(not_found) = &self.not_found {
-                if let Some(answer) = self
-                    .invoke(
-                        unsafe { *not_found.get_unchecked(0) },
-                        unsafe { *not_found.get_unchecked(1) },
-                        unsafe { *not_found.get_unchecked(2) },
-                        None,
-                        internal,
-                    )
-                    .await
-                {
-                    return answer;
-                };
+            return self.not_found_page().await;
+        }
+        Answer::None
+    }
+
+    /// Answer of the switched off module, 404 with the page "not found" or 503
+    async fn module_off(&mut self, code: u16) -> Answer {
+        self.response.http_code = Some(code);
+        if code == 404 && !self.request.ajax {
+            return self.not_found_page().await;
+        }
+        Answer::None
+    }
+
+    /// Controller of the page "not found"
+    pub(crate) async fn not_found_page(&mut self) -> Answer {
+        if let Some(not_found) = &self.not_found {
+            if let Some(answer) = self
+                .invoke(
+                    unsafe { *not_found.get_unchecked(0) },
+                    unsafe { *not_found.get_unchecked(1) },
+                    unsafe { *not_found.get_unchecked(2) },
+                    None,
+                    false,
+                )
+                .await
+            {
+                return answer;
+            };
+        }
+        Answer::None
+    }
+
+    /// Controller `action_err` of the page of the error, `None` without it
+    pub(crate) async fn error_page(&mut self) -> Option<Answer> {
+        let action_err = self.action_err.clone()?;
+        self.invoke(
+            unsafe { *action_err.get_unchecked(0) },
+            unsafe { *action_err.get_unchecked(1) },
+            unsafe { *action_err.get_unchecked(2) },
+            None,
+            false,
+        )
+        .await
+    }
+
+    /// Select the controller by the http method
+    ///
+    /// The controller `action_post` serves POST for the url of `action`, the same for `_get`, `_put`, `_delete`, `_patch` and `_options`.
+    /// Without a method controller, the `action` serves all methods, HEAD is served by GET without the body
+    /// and OPTIONS is answered with the `Allow` header.
+    /// Returns the value of the `Allow` header if the action exists only for other methods.
+    async fn method_action(&self, module_id: i64, class_id: i64, action_id: i64) -> Result<i64, String> {
+        if let Some(suffix) = self.request.method.suffix() {
+            let id = fnv1a_64_add(action_id, suffix.as_bytes());
+            if self.get_act(module_id, class_id, id).await.is_some() {
+                return Ok(id);
             }
         }
+        if self.get_act(module_id, class_id, action_id).await.is_some() {
+            return Ok(action_id);
+        }
+        let allow = self.allow_methods(module_id, class_id, action_id).await;
+        if allow.is_empty() {
+            Ok(action_id)
+        } else {
+            Err(allow.join(", "))
+        }
+    }
+
+    /// Methods of the action for the `Allow` header, the controller `action` without the suffix serves all methods
+    async fn allow_methods(&self, module_id: i64, class_id: i64, action_id: i64) -> Vec<&'static str> {
+        if self.get_act(module_id, class_id, action_id).await.is_some() {
+            return vec!["GET", "HEAD", "POST", "PUT", "DELETE", "PATCH", "OPTIONS"];
+        }
+        let mut allow = Vec::new();
+        for (method, suffix) in HttpMethod::DISPATCH {
+            if self.get_act(module_id, class_id, fnv1a_64_add(action_id, suffix.as_bytes())).await.is_some() {
+                allow.push(method);
+                if method == "GET" {
+                    allow.push("HEAD");
+                }
+            }
+        }
+        if !allow.is_empty() {
+            allow.push("OPTIONS");
+        }
+        allow
+    }
+
+    /// Answer to OPTIONS without the controller `action_options`: 204 with the methods of the action in `Allow`
+    async fn options(&mut self, module_id: i64, class_id: i64, action_id: i64) -> Answer {
+        let allow = self.allow_methods(module_id, class_id, action_id).await;
+        if allow.is_empty() {
+            self.response.http_code = Some(404);
+            return Answer::None;
+        }
+        self.response.http_code = Some(204);
+        self.response.headers.push(("Allow".to_owned(), allow.join(", ")));
+        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+        {
+            self.session_write = false;
+        }
         Answer::None
     }
 
+    /// Controller compiled in by `addfn!`, registered by `Register` or loaded from the plugin
+    async fn get_act(&self, module_id: i64, class_id: i64, action_id: i64) -> Option<Call> {
+        if let Some(a) = self.engine.get(&module_id).and_then(|m| m.get(&class_id)).and_then(|c| c.get(&action_id)) {
+            return Some(Call::Act(*a));
+        }
+        if let Some(handler) = Handlers::get(module_id, class_id, action_id) {
+            return Some(Call::Handler(handler));
+        }
+        #[cfg(feature = "plugin")]
+        if let Some((a, _)) = self.plugins.get(module_id, class_id, action_id).await {
+            return Some(Call::Act(a));
+        }
+        None
+    }
+
     async fn invoke(&mut self, module_id: i64, class_id: i64, action_id: i64, param: Option<String>, internal: bool) -> Option<Answer> {
-        if let Some(m) = self.engine.get(&module_id) {
-            if let Some(c) = m.get(&class_id) {
-                if let Some(a) = c.get(&action_id) {
-                    if self.current_module_id == module_id && self.current_class_id == class_id {
-                        let i = self.internal;
-                        let p = match param {
-                            Some(str) => self.route.param.replace(str),
-                            None => self.route.param.take(),
-                        };
-                        self.internal = internal;
-                        let res = a(self).await;
-                        self.internal = i;
-                        self.route.param = p;
-                        return Some(res);
-                    } else {
-                        #[cfg(feature = "html-static")]
-                        let h = match self.template.list.get(&module_id) {
-                            Some(h) => match h.get(&class_id) {
-                                Some(h) => self.html.replace(Arc::clone(h)),
-                                None => self.html.take(),
-                            },
-                            None => self.html.take(),
-                        };
-                        #[cfg(feature = "html-reload")]
-                        let h = match self.template.read().await.list.get(&module_id) {
-                            Some(h) => match h.get(&class_id) {
-                                Some(h) => self.html.replace(Arc::clone(h)),
-                                None => self.html.take(),
-                            },
-                            None => self.html.take(),
-                        };
+        // The plugin is held until the controller is completed
+        #[cfg(feature = "plugin")]
+        let (a, _plugin) = match self.engine.get(&module_id).and_then(|m| m.get(&class_id)).and_then(|c| c.get(&action_id)) {
+            Some(a) => (Call::Act(*a), None),
+            None => match Handlers::get(module_id, class_id, action_id) {
+                Some(handler) => (Call::Handler(handler), None),
+                None => {
+                    let (a, plugin) = self.plugins.get(module_id, class_id, action_id).await?;
+                    (Call::Act(a), Some(plugin))
+                }
+            },
+        };
+        #[cfg(not(feature = "plugin"))]
+        let a = match self.engine.get(&module_id).and_then(|m| m.get(&class_id)).and_then(|c| c.get(&action_id)) {
+            Some(a) => Call::Act(*a),
+            None => Call::Handler(Handlers::get(module_id, class_id, action_id)?),
+        };
+        if self.current_module_id == module_id && self.current_class_id == class_id {
+            let i = self.internal;
+            let p = match param {
+                Some(str) => self.route.param.replace(str),
+                None => self.route.param.take(),
+            };
+            self.internal = internal;
+            let res = a.call(self).await;
+            self.internal = i;
+            self.route.param = p;
+            return Some(res);
+        }
+        #[cfg(feature = "html-static")]
+        let h = match self.template.list.get(&module_id) {
+            Some(h) => match h.get(&class_id) {
+                Some(h) => self.html.replace(Arc::clone(h)),
+                None => self.html.take(),
+            },
+            None => self.html.take(),
+        };
+        #[cfg(feature = "html-reload")]
+        let h = match self.template.read().await.list.get(&module_id) {
+            Some(h) => match h.get(&class_id) {
+                Some(h) => self.html.replace(Arc::clone(h)),
+                None => self.html.take(),
+            },
+            None => self.html.take(),
+        };
 
-                        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
-                        let lang_id = self.lang_id;
-
-                        #[cfg(feature = "lang-static")]
-                        let l = match self.language.list.get(&lang_id) {
-                            Some(l) => match l.get(&module_id) {
-                                Some(l) => match l.get(&class_id) {
-                                    Some(l) => self.lang.replace(Arc::clone(l)),
-                                    None => self.lang.take(),
-                                },
-                                None => self.lang.take(),
-                            },
-                            None => self.lang.take(),
-                        };
-                        #[cfg(feature = "lang-reload")]
-                        let l = match self.language.read().await.list.get(&lang_id) {
-                            Some(l) => match l.get(&module_id) {
-                                Some(l) => match l.get(&class_id) {
-                                    Some(l) => self.lang.replace(Arc::clone(l)),
-                                    None => self.lang.take(),
-                                },
-                                None => self.lang.take(),
-                            },
-                            None => self.lang.take(),
-                        };
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        let lang_id = self.lang_id;
 
-                        let i = self.internal;
-                        let p = match param {
-                            Some(str) => self.route.param.replace(str),
-                            None => self.route.param.take(),
-                        };
-                        let m = self.current_module_id;
-                        self.current_module_id = module_id;
-                        let c = self.current_class_id;
-                        self.current_class_id = class_id;
+        #[cfg(feature = "lang-static")]
+        let l = match self.language.list.get(&lang_id) {
+            Some(l) => match l.get(&module_id) {
+                Some(l) => match l.get(&class_id) {
+                    Some(l) => self.lang.replace(Arc::clone(l)),
+                    None => self.lang.take(),
+                },
+                None => self.lang.take(),
+            },
+            None => self.lang.take(),
+        };
+        #[cfg(feature = "lang-reload")]
+        let l = match self.language.read().await.list.get(&lang_id) {
+            Some(l) => match l.get(&module_id) {
+                Some(l) => match l.get(&class_id) {
+                    Some(l) => self.lang.replace(Arc::clone(l)),
+                    None => self.lang.take(),
+                },
+                None => self.lang.take(),
+            },
+            None => self.lang.take(),
+        };
 
-                        self.internal = internal;
+        let i = self.internal;
+        let p = match param {
+            Some(str) => self.route.param.replace(str),
+            None => self.route.param.take(),
+        };
+        let m = self.current_module_id;
+        self.current_module_id = module_id;
+        let c = self.current_class_id;
+        self.current_class_id = class_id;
 
-                        // Call controlle
-                        let res = a(self).await;
+        self.internal = internal;
 
-                        self.current_module_id = m;
-                        self.current_class_id = c;
-                        self.internal = i;
-                        self.route.param = p;
+        // Call controlle
+        let res = a.call(self).await;
 
-                        #[cfg(any(feature = "html-static", feature = "html-reload"))]
-                        {
-                            self.html = h;
-                        }
-                        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
-                        {
-                            self.lang = l;
-                        }
-                        return Some(res);
-                    }
-                }
-            }
+        self.current_module_id = m;
+        self.current_class_id = c;
+        self.internal = i;
+        self.route.param = p;
+
+        #[cfg(any(feature = "html-static", feature = "html-reload"))]
+        {
+            self.html = h;
         }
-        None
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        {
+            self.lang = l;
+        }
+        Some(res)
     }
 
-    fn extract_route(request: &Request, index: Arc<[i64; 3]>) -> Route {
-        if request.url != "/" {
-            let mut load: Vec<&str> = request.url.splitn(5, '/').collect();
+    /// Route of the url, the url is without the language prefix
+    fn extract_route(request: &Request, url: &str, index: Arc<[i64; 3]>, router: &Router) -> (Route, HashMap<String, String>) {
+        if let Some(found) = router.find(&request.host, url) {
+            if let Some((class_id, action_id)) = found.target {
+                let route = Route {
+                    module_id: found.module_id,
+                    class_id,
+                    action_id,
+                    param: found.param,
+                    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+                    lang_id: None,
+                };
+                return (route, found.params);
+            }
+            let mut load: Vec<&str> = found.rest.splitn(3, '/').collect();
+            load.retain(|&x| !x.is_empty());
+            let route = Route {
+                module_id: found.module_id,
+                class_id: match load.first() {
+                    Some(class) => fnv1a_64(class.as_bytes()),
+                    None => unsafe { *index.get_unchecked(1) },
+                },
+                action_id: match load.get(1) {
+                    Some(action) => fnv1a_64(action.as_bytes()),
+                    None => unsafe { *index.get_unchecked(2) },
+                },
+                param: load.get(2).map(|param| (*param).to_owned()),
+                #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+                lang_id: None,
+            };
+            return (route, found.params);
+        }
+        let route = if url != "/" {
+            let mut load: Vec<&str> = url.splitn(5, '/').collect();
             load.retain(|&x| !x.is_empty());
             match load.len() {
                 1 => {
@@ -966,12 +2201,13 @@ impl Action {
                 #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
                 lang_id: None,
             }
-        }
+        };
+        (route, HashMap::new())
     }
 
     /// Simple remove temp file
     #[cfg(feature = "file-disk")]
-    async fn clean_file(file: Arc<Vec<WebFile>>) {
+    pub(crate) async fn clean_file(file: Arc<Vec<WebFile>>) {
         for f in &*file {
             if let Err(e) = remove_file(&f.tmp).await {
                 if e.kind() != ErrorKind::NotFound {
@@ -0,0 +1,272 @@
+use serde_json::{json, Map, Value};
+use tiny_web_macro::fnv1a_64 as m_fnv1a_64;
+
+use crate::sys::db::adapter::DB;
+
+#[cfg(feature = "row-data")]
+use super::data::Data;
+
+use super::{
+    action::{Action, Answer, ModuleMap},
+    controller::Controller,
+    feed::Feed,
+    guard::Guard,
+};
+
+#[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+use super::logview::LogViewer;
+
+/// Count of the sessions
+#[cfg(feature = "pgsql")]
+const SESSIONS: &str = "SELECT COUNT(*) FROM session";
+/// Count of the sessions
+#[cfg(feature = "mssql")]
+const SESSIONS: &str = "SELECT COUNT_BIG(*) FROM [session]";
+
+/// Built-in status page of the server
+///
+/// The controllers are "/admin/status/index" with the HTML page, "/admin/status/json" with the same data in JSON
+/// and "/admin/status/metrics" for Prometheus, with the debug features also the log viewer "/admin/log/index".
+/// The access is from the table `access` as for any other controller, the user must be authorized.
+/// The controllers of the application with the same names replace the built-in ones.
+pub(crate) struct Admin;
+
+impl Admin {
+    /// Add the controllers, the existing ones are not replaced
+    pub(crate) fn add(engine: &mut ModuleMap) {
+        let class = engine.entry(m_fnv1a_64!("admin")).or_default().entry(m_fnv1a_64!("status")).or_default();
+        class.entry(m_fnv1a_64!("index")).or_insert(|this| Box::pin(Admin::index(this)));
+        class.entry(m_fnv1a_64!("json")).or_insert(|this| Box::pin(Admin::json(this)));
+        class.entry(m_fnv1a_64!("metrics")).or_insert(|this| Box::pin(Admin::metrics(this)));
+        #[cfg(any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv"))]
+        LogViewer::add(engine);
+    }
+
+    async fn index(this: &mut Action) -> Answer {
+        if let Some(answer) = this.guard(&[Guard::Auth]).await {
+            return answer;
+        }
+        this.controller(&[Controller::ContentType("text/html; charset=utf-8")]);
+        let status = Admin::status(this).await;
+        Answer::String(Admin::render(&status))
+    }
+
+    async fn json(this: &mut Action) -> Answer {
+        if let Some(answer) = this.guard(&[Guard::Auth]).await {
+            return answer;
+        }
+        this.controller(&[Controller::Json]);
+        Answer::String(Admin::status(this).await.to_string())
+    }
+
+    /// Metrics in the text format of Prometheus
+    async fn metrics(this: &mut Action) -> Answer {
+        if let Some(answer) = this.guard(&[Guard::Auth]).await {
+            return answer;
+        }
+        this.controller(&[Controller::ContentType("text/plain; version=0.0.4; charset=utf-8")]);
+        Answer::String(this.monitor.prometheus())
+    }
+
+    /// Current state of the server
+    async fn status(this: &Action) -> Value {
+        let mon = &this.monitor;
+        let uptime = mon.get_uptime().as_secs();
+        let total = mon.get_total();
+        let status = json!({
+            "uptime": uptime,
+            "workers": mon.get_number(),
+            "requests": {
+                "online": mon.get_online(),
+                "total": total,
+                "per_second": total as f64 / uptime.max(1) as f64,
+                "queued": mon.get_queued(),
+                "rejected": mon.get_rejected(),
+                "queue_max_us": mon.get_queue_max(),
+            },
+            "limits": mon.get_limits().iter().map(|limit| json!({
+                "name": limit.name,
+                "limit": limit.limit,
+                "max": limit.max,
+                "inflight": limit.inflight,
+                "waiting": limit.waiting,
+                "latency_us": limit.latency,
+            })).collect::<Vec<Value>>(),
+            "timings": mon.get_timings().iter().map(|(name, count, time)| json!({
+                "name": name,
+                "count": count,
+                "average_us": time / (*count).max(1),
+            })).collect::<Vec<Value>>(),
+            "statuses": mon.get_statuses().iter().map(|(status, count)| (status.to_string(), json!(count))).collect::<Map<String, Value>>(),
+            "routes": mon.get_routes().iter().map(|item| json!({
+                "route": item.route,
+                "count": item.count,
+                "errors": item.errors,
+                "bytes_in": item.bytes_in,
+                "bytes_out": item.bytes_out,
+                "average_us": item.time / item.count.max(1),
+                "p50_us": item.p50,
+                "p90_us": item.p90,
+                "p99_us": item.p99,
+                "max_us": item.max,
+            })).collect::<Vec<Value>>(),
+            "pool": mon.get_pool().map(|pool| json!({
+                "size": pool.size,
+                "min": pool.min,
+                "open": pool.open,
+                "idle": pool.idle,
+                "waiters": pool.waiters,
+                "acquires": pool.acquires,
+                "acquire_average_us": pool.acquire_time / pool.acquires.max(1),
+                "acquire_max_us": pool.acquire_max,
+                "timeouts": pool.timeouts,
+                "broken": pool.broken,
+            })),
+            "statements": mon.get_statements().iter().map(|item| json!({
+                "name": item.name,
+                "count": item.count,
+                "errors": item.errors,
+                "average_us": item.time / item.count.max(1),
+                "max_us": item.max,
+            })).collect::<Vec<Value>>(),
+            "slow_queries": this.db.slow_queries().iter().map(|slow| json!({
+                "query": slow.query,
+                "time_ms": slow.time.as_millis() as u64,
+                "at": slow.at.to_rfc3339(),
+            })).collect::<Vec<Value>>(),
+            "sessions": Admin::sessions(&this.db).await,
+        });
+        #[cfg(any(feature = "cache", feature = "html-reload", feature = "lang-reload", feature = "alert"))]
+        let mut status = status;
+        #[cfg(feature = "cache")]
+        {
+            status["cache"] = json!(this.cache.len().await);
+        }
+        #[cfg(feature = "html-reload")]
+        {
+            status["reloads"]["templates"] = json!(super::html::RELOADS.load(std::sync::atomic::Ordering::Relaxed));
+        }
+        #[cfg(feature = "lang-reload")]
+        {
+            status["reloads"]["translations"] = json!(super::lang::RELOADS.load(std::sync::atomic::Ordering::Relaxed));
+        }
+        #[cfg(feature = "alert")]
+        {
+            status["errors"] = crate::sys::alert::Alert::list()
+                .iter()
+                .map(|count| {
+                    json!({
+                        "level": format!("{:?}", count.view),
+                        "code": count.number,
+                        "location": format!("{}:{}", count.file, count.line),
+                        "total": count.total,
+                        "window": count.window,
+                        "first": count.first.to_rfc3339(),
+                        "last": count.last.to_rfc3339(),
+                        "text": count.text,
+                    })
+                })
+                .collect::<Vec<Value>>()
+                .into();
+        }
+        status
+    }
+
+    /// Number of the sessions in the database
+    async fn sessions(db: &DB) -> Option<i64> {
+        #[cfg(feature = "row-data")]
+        let count = match db.query(SESSIONS, &[], false).await?.first()? {
+            Data::Vec(row) => match row.first()? {
+                Data::I64(count) => Some(*count),
+                _ => None,
+            },
+            _ => None,
+        };
+        #[cfg(all(feature = "row-native", feature = "pgsql"))]
+        let count = db.query(SESSIONS, &[]).await?.first()?.try_get(0).ok();
+        #[cfg(all(feature = "row-native", feature = "mssql"))]
+        let count = db.query(SESSIONS, &[]).await?.first()?.get(0);
+        count
+    }
+
+    /// HTML page of the state
+    fn render(status: &Value) -> String {
+        let mut html = String::with_capacity(4096);
+        html.push_str(concat!(
+            r#"<!DOCTYPE html><html><head><meta charset="utf-8"><title>Status</title>"#,
+            r#"<style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin-bottom:2em}"#,
+            r#"td,th{border:1px solid #ccc;padding:4px 8px;text-align:left}</style></head><body><h1>Status</h1>"#
+        ));
+        let mut server = vec![
+            ("Uptime, s".to_owned(), status["uptime"].clone()),
+            ("Workers".to_owned(), status["workers"].clone()),
+            ("Sessions".to_owned(), status["sessions"].clone()),
+        ];
+        if let Some(cache) = status.get("cache") {
+            server.push(("Cache elements".to_owned(), cache.clone()));
+        }
+        if let Value::Object(map) = &status["requests"] {
+            server.extend(map.iter().map(|(key, value)| (format!("Requests {}", key), value.clone())));
+        }
+        if let Value::Object(map) = &status["statuses"] {
+            server.extend(map.iter().map(|(key, value)| (format!("Status {}", key), value.clone())));
+        }
+        if let Value::Object(map) = &status["pool"] {
+            server.extend(map.iter().map(|(key, value)| (format!("Pool {}", key), value.clone())));
+        }
+        if let Value::Object(map) = &status["reloads"] {
+            server.extend(map.iter().map(|(key, value)| (format!("Reloads of the {}", key), value.clone())));
+        }
+        html.push_str("<h2>Server</h2><table>");
+        for (name, value) in &server {
+            html.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>", Feed::escape(name), Feed::escape(&Admin::text(value))));
+        }
+        html.push_str("</table>");
+        Admin::table(&mut html, "Limits", &status["limits"], &["name", "limit", "max", "inflight", "waiting", "latency_us"]);
+        Admin::table(&mut html, "Timings", &status["timings"], &["name", "count", "average_us"]);
+        Admin::table(
+            &mut html,
+            "Routes",
+            &status["routes"],
+            &["route", "count", "errors", "bytes_in", "bytes_out", "average_us", "p50_us", "p90_us", "p99_us", "max_us"],
+        );
+        Admin::table(&mut html, "Prepared statements", &status["statements"], &["name", "count", "errors", "average_us", "max_us"]);
+        Admin::table(&mut html, "Slow queries", &status["slow_queries"], &["at", "time_ms", "query"]);
+        if status.get("errors").is_some() {
+            Admin::table(&mut html, "Errors", &status["errors"], &["level", "code", "location", "total", "window", "last", "text"]);
+        }
+        html.push_str("</body></html>");
+        html
+    }
+
+    /// Table of the list of the objects
+    fn table(html: &mut String, title: &str, list: &Value, columns: &[&str]) {
+        html.push_str(&format!("<h2>{}</h2><table><tr>", title));
+        for column in columns {
+            html.push_str(&format!("<th>{}</th>", column));
+        }
+        html.push_str("</tr>");
+        if let Value::Array(list) = list {
+            for item in list {
+                html.push_str("<tr>");
+                for column in columns {
+                    html.push_str(&format!("<td>{}</td>", Feed::escape(&Admin::text(&item[*column]))));
+                }
+                html.push_str("</tr>");
+            }
+        }
+        html.push_str("</table>");
+    }
+
+    fn text(value: &Value) -> String {
+        match value {
+            Value::String(value) => value.clone(),
+            Value::Null => "-".to_owned(),
+            Value::Number(number) => match number.as_f64() {
+                Some(value) if number.is_f64() => format!("{:.2}", value),
+                _ => number.to_string(),
+            },
+            value => value.to_string(),
+        }
+    }
+}
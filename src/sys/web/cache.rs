@@ -1,13 +1,21 @@
 use std::{
     collections::{HashMap, HashSet},
+    future::Future,
     sync::{atomic::Ordering, Arc},
+    time::Duration,
 };
 
 use tokio::sync::RwLock;
 
 use crate::{fnv1a_64, log, sys::wrlock::WrLock};
 
-use super::data::Data;
+use super::{data::Data, flight::Flight};
+
+#[cfg(feature = "otel")]
+use crate::sys::otel::Span;
+
+#[cfg(feature = "profile")]
+use crate::sys::profile::Frame;
 
 #[derive(Debug, Eq, Hash, PartialEq)]
 enum CacheType {
@@ -26,12 +34,17 @@ struct CacheData {
     data: HashMap<i64, Data>,
     /// Group -> list of CacheType
     key: HashMap<i64, HashSet<CacheType>>,
+    /// Tag -> elements with the groups of the key for the removal
+    tag: HashMap<i64, HashMap<i64, Vec<i64>>>,
+    /// Element -> tags
+    tagged: HashMap<i64, Vec<i64>>,
 }
 
 #[derive(Debug, Default)]
 pub struct Cache {
     data: Arc<RwLock<CacheData>>,
     lock: WrLock,
+    flight: Flight,
 }
 
 impl Cache {
@@ -39,9 +52,19 @@ impl Cache {
         Cache {
             data: Arc::new(RwLock::new(CacheData::default())),
             lock: WrLock::default(),
+            flight: Flight::default(),
         }
     }
 
+    /// Single flight of the missing keys with the prefixes, see `get_or_load`
+    pub(crate) fn coalesce(&mut self, prefix: Vec<String>, wait: Duration) {
+        self.flight = Flight::new(prefix, wait);
+    }
+
+    pub(crate) fn flight(&self) -> &Flight {
+        &self.flight
+    }
+
     /// Converts &str to Vec<i64> with ":" separator and hash function fnv1a_64
     fn get_hash(input: &[u8]) -> Option<CacheParse> {
         let last_symbol = *input.last()?;
@@ -76,6 +99,10 @@ impl Cache {
 
     /// Get cache
     pub async fn get(&self, key: &str) -> Option<Data> {
+        #[cfg(feature = "otel")]
+        let mut span = Span::internal("cache.get");
+        #[cfg(feature = "profile")]
+        let _frame = Frame::start("cache.get");
         let key = key.as_bytes();
         if *key.last()? == b':' {
             return None;
@@ -84,11 +111,87 @@ impl Cache {
             self.lock.notify.notified().await;
         }
         let read = self.data.read().await;
-        Some(read.data.get(&fnv1a_64(key))?.clone())
+        let data = read.data.get(&fnv1a_64(key)).cloned();
+        #[cfg(feature = "otel")]
+        span.attr("cache.hit", data.is_some());
+        data
+    }
+
+    /// Get cache or load and set the missing value
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let news = this.cache.get_or_load("news:last", || async { load_news(&db).await }).await;
+    /// ```
+    ///
+    /// With the prefix of the key in [web] coalesce only the first request loads the missing value,
+    /// the concurrent requests of the same key wait for it. `None` of `load` is not cached.
+    pub async fn get_or_load<F, Fut>(&self, key: &str, load: F) -> Option<Data>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Option<Data>>,
+    {
+        if let Some(data) = self.get(key).await {
+            return Some(data);
+        }
+        let _guard = if self.flight.is_on(key) {
+            loop {
+                match self.flight.lead(key) {
+                    Some(guard) => {
+                        if let Some(data) = self.get(key).await {
+                            return Some(data);
+                        }
+                        break Some(guard);
+                    }
+                    None => {
+                        if self.flight.wait(key).await {
+                            if let Some(data) = self.get(key).await {
+                                return Some(data);
+                            }
+                            // The leader has not loaded the value, load it without the flight
+                            break None;
+                        }
+                    }
+                }
+            }
+        } else {
+            None
+        };
+        let data = load().await?;
+        self.set(key, data.clone()).await;
+        Some(data)
+    }
+
+    /// Number of the elements
+    #[cfg(feature = "admin")]
+    pub(crate) async fn len(&self) -> usize {
+        while self.lock.lock.load(Ordering::Relaxed) {
+            self.lock.notify.notified().await;
+        }
+        self.data.read().await.data.len()
     }
 
     /// Set cache
     pub async fn set(&self, key: &str, data: impl Into<Data>) -> Option<Data> {
+        self.insert(key, data.into(), &[]).await
+    }
+
+    /// Set cache with the tags, all keys of the tag are removed by `invalidate_tag`
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// this.cache.set_with_tags("page:article:15", html, &["articles", "user:42"]).await;
+    /// this.cache.invalidate_tag("articles").await;
+    /// ```
+    ///
+    /// The tags replace the previous tags of the key, `set` removes them.
+    pub async fn set_with_tags(&self, key: &str, data: impl Into<Data>, tags: &[&str]) -> Option<Data> {
+        self.insert(key, data.into(), tags).await
+    }
+
+    async fn insert(&self, key: &str, data: Data, tags: &[&str]) -> Option<Data> {
         let key = key.as_bytes();
 
         if *key.last()? == b':' {
@@ -116,9 +219,9 @@ impl Cache {
         }
         let data = {
             let mut map = self.data.write().await;
-            let data = map.data.insert(key, data.into());
+            let data = map.data.insert(key, data);
             if data.is_none() {
-                if let Some(vec) = res.vec {
+                if let Some(vec) = &res.vec {
                     let mut main = *unsafe { vec.get_unchecked(0) };
                     for slave in &vec[1..] {
                         let val = map.key.entry(main).or_insert_with(HashSet::new);
@@ -129,6 +232,14 @@ impl Cache {
                     val.insert(CacheType::Element(key));
                 }
             }
+            Cache::untag(&mut map, key);
+            let tags: Vec<i64> = tags.iter().filter(|tag| !tag.is_empty()).map(|tag| fnv1a_64(tag.as_bytes())).collect();
+            if !tags.is_empty() {
+                for tag in &tags {
+                    map.tag.entry(*tag).or_default().insert(key, res.vec.clone().unwrap_or_default());
+                }
+                map.tagged.insert(key, tags);
+            }
             data
         };
         self.lock.lock.store(false, Ordering::SeqCst);
@@ -136,6 +247,61 @@ impl Cache {
         data
     }
 
+    /// Remove all keys of the tag, see `set_with_tags`
+    pub async fn invalidate_tag(&self, tag: &str) {
+        if tag.is_empty() {
+            return;
+        }
+        let tag = fnv1a_64(tag.as_bytes());
+        loop {
+            if self.lock.lock.compare_exchange(false, true, Ordering::SeqCst, Ordering::Relaxed).is_ok() {
+                break;
+            }
+            self.lock.notify.notified().await;
+        }
+        {
+            let mut map = self.data.write().await;
+            if let Some(list) = map.tag.remove(&tag) {
+                for (key, vec) in list {
+                    map.data.remove(&key);
+                    Cache::untag(&mut map, key);
+                    if !vec.is_empty() {
+                        Cache::clean_tree(&mut map, vec, CacheType::Element(key));
+                    }
+                }
+            }
+        }
+        self.lock.lock.store(false, Ordering::SeqCst);
+        self.lock.notify.notify_waiters();
+    }
+
+    /// Remove the element from its tags
+    fn untag(map: &mut tokio::sync::RwLockWriteGuard<'_, CacheData>, key: i64) {
+        if let Some(tags) = map.tagged.remove(&key) {
+            for tag in tags {
+                if let Some(list) = map.tag.get_mut(&tag) {
+                    list.remove(&key);
+                    if list.is_empty() {
+                        map.tag.remove(&tag);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The group, for example "user:1:", has the data
+    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+    pub(crate) async fn contains_group(&self, key: &str) -> bool {
+        let key = match key.strip_suffix(':') {
+            Some(key) if !key.is_empty() => fnv1a_64(key.as_bytes()),
+            _ => return false,
+        };
+        while self.lock.lock.load(Ordering::Relaxed) {
+            self.lock.notify.notified().await;
+        }
+        self.data.read().await.key.contains_key(&key)
+    }
+
     /// Remove cache
     /// If `key` is &str and ends with a `:` character, all data beginning with that `key` is deleted.
     pub async fn remove(&self, key: &str) {
@@ -157,6 +323,7 @@ impl Cache {
         match (res.key, res.vec) {
             (Some(key), None) => {
                 map.data.remove(&key);
+                Cache::untag(&mut map, key);
             }
             (None, Some(mut vec)) => {
                 let last = match vec.pop() {
@@ -171,6 +338,7 @@ impl Cache {
             }
             (Some(key), Some(vec)) => {
                 map.data.remove(&key);
+                Cache::untag(&mut map, key);
                 Cache::clean_tree(&mut map, vec, CacheType::Element(key));
             }
             (None, None) => {
@@ -216,6 +384,7 @@ impl Cache {
             match item {
                 CacheType::Element(key) => {
                     map.data.remove(&key);
+                    Cache::untag(map, key);
                 }
                 CacheType::Group(key) => {
                     Cache::remove_tree(map, key);
@@ -236,6 +405,8 @@ impl Cache {
             let mut data = self.data.write().await;
             data.key.clear();
             data.data.clear();
+            data.tag.clear();
+            data.tagged.clear();
         }
         self.lock.lock.store(false, Ordering::SeqCst);
         self.lock.notify.notify_waiters();
@@ -0,0 +1,149 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Utc};
+
+/// The source of the time is replaced by `Clock::set`
+static MOCK: AtomicBool = AtomicBool::new(false);
+
+/// Replaced source of the time
+static SOURCE: RwLock<Option<Arc<dyn TimeSource>>> = RwLock::new(None);
+
+/// Source of the time of the library
+///
+/// `system` is the wall time, `instant` is the monotonic time of the intervals.
+pub trait TimeSource: Send + Sync {
+    fn system(&self) -> SystemTime;
+    fn instant(&self) -> Instant;
+}
+
+/// Time of the library: the sessions, the tickets, the cache, the tokens and the removal of the expired sessions
+///
+/// By default it is the time of the system. The tests replace it by `FrozenClock` or by their own `TimeSource`:
+///
+/// ```ignore
+/// let clock = Arc::new(FrozenClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)));
+/// Clock::set(clock.clone());
+/// // The code sees 2023-11-14 22:13:20 UTC
+/// clock.advance(Duration::from_secs(3600));
+/// // One hour later: the cached answers and the tokens of one hour are expired
+/// Clock::reset();
+/// ```
+///
+/// The source is global for the process, the tests with the different sources must not run in parallel.
+/// The periodic tasks wait by the timers of tokio, `tokio::time::pause` controls them.
+pub struct Clock;
+
+impl Clock {
+    /// Replace the source of the time
+    pub fn set(source: Arc<dyn TimeSource>) {
+        let mut current = match SOURCE.write() {
+            Ok(current) => current,
+            Err(e) => e.into_inner(),
+        };
+        *current = Some(source);
+        MOCK.store(true, Ordering::Release);
+    }
+
+    /// Return to the time of the system
+    pub fn reset() {
+        MOCK.store(false, Ordering::Release);
+        let mut current = match SOURCE.write() {
+            Ok(current) => current,
+            Err(e) => e.into_inner(),
+        };
+        *current = None;
+    }
+
+    /// Wall time
+    pub fn system() -> SystemTime {
+        match Clock::source() {
+            Some(source) => source.system(),
+            None => SystemTime::now(),
+        }
+    }
+
+    /// Monotonic time
+    pub fn instant() -> Instant {
+        match Clock::source() {
+            Some(source) => source.instant(),
+            None => Instant::now(),
+        }
+    }
+
+    /// Wall time in UTC
+    pub fn utc() -> DateTime<Utc> {
+        DateTime::<Utc>::from(Clock::system())
+    }
+
+    /// Seconds since the Unix epoch, 0 before the epoch
+    pub fn unix() -> u64 {
+        match Clock::system().duration_since(UNIX_EPOCH) {
+            Ok(time) => time.as_secs(),
+            Err(_) => 0,
+        }
+    }
+
+    fn source() -> Option<Arc<dyn TimeSource>> {
+        if !MOCK.load(Ordering::Acquire) {
+            return None;
+        }
+        match SOURCE.read() {
+            Ok(source) => source.clone(),
+            Err(e) => e.into_inner().clone(),
+        }
+    }
+}
+
+/// Stopped time for the tests, it is changed only by `set` and `advance`
+pub struct FrozenClock {
+    state: Mutex<(SystemTime, Instant)>,
+}
+
+impl FrozenClock {
+    pub fn new(time: SystemTime) -> FrozenClock {
+        FrozenClock {
+            state: Mutex::new((time, Instant::now())),
+        }
+    }
+
+    /// Set the wall time, the monotonic time is not changed
+    pub fn set(&self, time: SystemTime) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(e) => e.into_inner(),
+        };
+        state.0 = time;
+    }
+
+    /// Move the wall and the monotonic time forward
+    pub fn advance(&self, duration: Duration) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(e) => e.into_inner(),
+        };
+        state.0 += duration;
+        state.1 += duration;
+    }
+}
+
+impl TimeSource for FrozenClock {
+    fn system(&self) -> SystemTime {
+        match self.state.lock() {
+            Ok(state) => state.0,
+            Err(e) => e.into_inner().0,
+        }
+    }
+
+    fn instant(&self) -> Instant {
+        match self.state.lock() {
+            Ok(state) => state.1,
+            Err(e) => e.into_inner().1,
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde_json::json;
+
+use crate::log;
+
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+use crate::sys::db::error::{DBError, DBErrorKind};
+
+use super::{
+    action::{Action, Answer},
+    request::HttpMethod,
+    response::{CacheControl, ETag},
+};
+
+/// Options of the controller
+///
+/// Usually set by the attribute `#[controller]` and applied by `Action::dispatch` before the controller,
+/// or by `Middleware::Controller` for the route group.
+#[derive(Debug, Clone)]
+pub enum Controller {
+    /// Content-Type: application/json; charset=utf-8
+    Json,
+    /// Set custom Content-Type
+    ContentType(&'static str),
+    /// Allow caching of the page, for example "300s", "5m", "1h", "1d" or "300".
+    /// Session writes are disabled for this action, with the feature "cache" the page is kept by `Action::cache_page`.
+    Cache(&'static str),
+    /// Disable session writes for this action
+    NoSession,
+    /// Max size of the answer in bytes, protects the memory from the huge answer
+    MaxSize(usize),
+    /// What to do with the answer larger than `MaxSize`, `SizePolicy::Error` by default
+    SizePolicy(SizePolicy),
+}
+
+/// Policy of the answer larger than `Controller::MaxSize`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizePolicy {
+    /// Answer 500 Internal Server Error
+    Error,
+    /// Send the first `MaxSize` bytes with the `Warning` header
+    Truncate,
+    /// Move the answer to the temporary file and send it by parts
+    #[cfg(feature = "file-disk")]
+    Disk,
+}
+
+/// Error of the controller, `Action::result` maps it to the status and the page of the error
+///
+/// The controller returns `Result<Answer, ControllerError>` and uses `?`, see the `answer!` macro.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControllerError {
+    /// 400 Bad Request, the message is shown to the client
+    BadRequest(String),
+    /// 401 Unauthorized
+    Unauthorized,
+    /// 403 Forbidden
+    Forbidden,
+    /// 404 Not Found with the page `not_found`
+    NotFound,
+    /// 409 Conflict, for example the duplicate of the unique key
+    Conflict(String),
+    /// 422 Unprocessable Content, the invalid data of the form
+    Invalid(String),
+    /// 503 Service Unavailable, the database or the service is not available, the request can be repeated
+    Unavailable,
+    /// 500 Internal Server Error, the message is written to the log and is not shown to the client
+    Internal(String),
+    /// Other status with the message
+    Status(u16, String),
+}
+
+impl ControllerError {
+    /// Status of the answer
+    pub fn code(&self) -> u16 {
+        match self {
+            ControllerError::BadRequest(_) => 400,
+            ControllerError::Unauthorized => 401,
+            ControllerError::Forbidden => 403,
+            ControllerError::NotFound => 404,
+            ControllerError::Conflict(_) => 409,
+            ControllerError::Invalid(_) => 422,
+            ControllerError::Unavailable => 503,
+            ControllerError::Internal(_) => 500,
+            ControllerError::Status(code, _) => *code,
+        }
+    }
+
+    /// Message for the client
+    pub fn message(&self) -> &str {
+        match self {
+            ControllerError::BadRequest(message)
+            | ControllerError::Conflict(message)
+            | ControllerError::Invalid(message)
+            | ControllerError::Status(_, message) => message,
+            ControllerError::Unauthorized => "Unauthorized",
+            ControllerError::Forbidden => "Forbidden",
+            ControllerError::NotFound => "Not Found",
+            ControllerError::Unavailable => "Service Unavailable",
+            ControllerError::Internal(_) => "Internal Server Error",
+        }
+    }
+}
+
+impl fmt::Display for ControllerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControllerError::Internal(message) => write!(f, "{} {}", self.code(), message),
+            _ => write!(f, "{} {}", self.code(), self.message()),
+        }
+    }
+}
+
+impl std::error::Error for ControllerError {}
+
+/// The constraints are the conflicts of the data, the lost connection and the timeout can be repeated
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+impl From<DBError> for ControllerError {
+    fn from(error: DBError) -> ControllerError {
+        match error.kind {
+            DBErrorKind::Unique | DBErrorKind::ForeignKey | DBErrorKind::Check => {
+                ControllerError::Conflict(error.constraint.unwrap_or_default())
+            }
+            DBErrorKind::NotNull => ControllerError::Invalid(error.column.unwrap_or_default()),
+            DBErrorKind::InvalidData => ControllerError::Invalid(String::new()),
+            DBErrorKind::Conflict | DBErrorKind::Timeout | DBErrorKind::Connection => ControllerError::Unavailable,
+            DBErrorKind::Syntax | DBErrorKind::Other => ControllerError::Internal(error.message),
+        }
+    }
+}
+
+impl From<std::io::Error> for ControllerError {
+    fn from(error: std::io::Error) -> ControllerError {
+        ControllerError::Internal(error.to_string())
+    }
+}
+
+impl Controller {
+    /// Parse "300s", "5m", "1h", "1d" or "300" into seconds
+    pub(crate) fn parse_duration(value: &str) -> Option<u64> {
+        let value = value.trim();
+        let (num, mul) = match value.as_bytes().last()? {
+            b's' => (&value[..value.len() - 1], 1),
+            b'm' => (&value[..value.len() - 1], 60),
+            b'h' => (&value[..value.len() - 1], 3600),
+            b'd' => (&value[..value.len() - 1], 86400),
+            _ => (value, 1),
+        };
+        num.trim().parse::<u64>().ok().map(|num| num * mul)
+    }
+}
+
+impl Action {
+    /// Set the Cache-Control header
+    ///
+    /// Session writes are disabled for `public`, the answer with `Set-Cookie` or of the authenticated user is sent as `private`.
+    pub fn cache_control(&mut self, cache: CacheControl) {
+        #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+        if cache.is_public() {
+            self.session_write = false;
+        }
+        self.response.cache = Some(cache);
+    }
+
+    /// Set the ETag of the answer, returns `true` if the client has the same version
+    ///
+    /// Then the answer is `304 Not Modified` without the body and the controller can skip the rendering.
+    pub fn etag(&mut self, etag: ETag) -> bool {
+        self.response.etag = Some(etag);
+        self.not_modified()
+    }
+
+    /// Set the Last-Modified of the answer, returns `true` if the version of the client is not older
+    ///
+    /// If-Modified-Since is checked only without If-None-Match.
+    pub fn last_modified(&mut self, modified: DateTime<Utc>) -> bool {
+        self.response.last_modified = Some(modified);
+        self.not_modified()
+    }
+
+    /// Set the ETag of the version of the resource and check the version of the client
+    ///
+    /// GET and HEAD get `304 Not Modified` as `etag`. PUT, PATCH and DELETE must have If-Match with the same version,
+    /// otherwise `428 Precondition Required` without the header or `412 Precondition Failed`.
+    /// Returns `true` if the controller must return `Answer::None`.
+    ///
+    /// ```ignore
+    /// if this.etag_version(article.version) {
+    ///     return Answer::None;
+    /// }
+    /// let count = this.db.update("article").set("title", &title).where_eq("id", &id).version("version", &article.version).try_execute().await?;
+    /// if count == 0 {
+    ///     return this.precondition_failed();
+    /// }
+    /// this.response.etag = Some(ETag::version(article.version + 1));
+    /// ```
+    pub fn etag_version(&mut self, version: i64) -> bool {
+        self.response.etag = Some(ETag::version(version));
+        match self.request.method {
+            HttpMethod::Put | HttpMethod::Patch | HttpMethod::Delete => match self.request.header("If-Match") {
+                Some(list) if list.trim() == "*" || self.if_match() == Some(version) => false,
+                Some(_) => {
+                    self.precondition_failed();
+                    self.response.etag = Some(ETag::version(version));
+                    true
+                }
+                None => {
+                    self.response.http_code = Some(428);
+                    true
+                }
+            },
+            _ => self.not_modified(),
+        }
+    }
+
+    /// Version of the resource from If-Match, only the strong ETag of `ETag::version`
+    pub fn if_match(&self) -> Option<i64> {
+        let list = self.request.header("If-Match")?;
+        let mut versions = list.split(',').map(str::trim).filter(|item| !item.starts_with("W/"));
+        let version = versions.next()?.trim_matches('"').parse().ok()?;
+        match versions.next() {
+            Some(_) => None,
+            None => Some(version),
+        }
+    }
+
+    /// Answer `412 Precondition Failed`, the resource is changed by another request
+    pub fn precondition_failed(&mut self) -> Answer {
+        self.response.http_code = Some(412);
+        self.response.etag = None;
+        Answer::None
+    }
+
+    /// Answer `304 Not Modified` to GET and HEAD by If-None-Match or If-Modified-Since
+    pub(crate) fn not_modified(&mut self) -> bool {
+        if !matches!(self.request.method, HttpMethod::Get | HttpMethod::Head)
+            || !matches!(self.response.http_code, None | Some(200))
+            || self.response.redirect.is_some()
+        {
+            return false;
+        }
+        let not_modified = match self.request.header("If-None-Match") {
+            Some(list) => self.response.etag.as_ref().is_some_and(|etag| etag.matches(list)),
+            None => match (self.request.header("If-Modified-Since"), self.response.last_modified) {
+                (Some(since), Some(modified)) => {
+                    DateTime::parse_from_rfc2822(since).is_ok_and(|since| modified.timestamp() <= since.timestamp())
+                }
+                _ => false,
+            },
+        };
+        if not_modified {
+            self.response.http_code = Some(304);
+        }
+        not_modified
+    }
+
+    /// Apply options of the controller
+    pub fn controller(&mut self, options: &[Controller]) {
+        for option in options {
+            match option {
+                Controller::Json => self.response.content_type = Some("application/json; charset=utf-8".to_owned()),
+                Controller::ContentType(content_type) => self.response.content_type = Some((*content_type).to_owned()),
+                Controller::Cache(value) => match Controller::parse_duration(value) {
+                    Some(max_age) => {
+                        self.cache_control(CacheControl::public().max_age(max_age));
+                        #[cfg(feature = "cache")]
+                        self.cache_page(max_age);
+                    }
+                    None => {
+                        log!(
+                            warning,
+                            0,
+                            "Invalid duration of the cache of the controller: \"{}\", expected for example \"300s\", \"5m\", \"1h\", \"1d\" or \"300\". Url: {}",
+                            value,
+                            self.request.url
+                        );
+                    }
+                },
+                Controller::NoSession => {
+                    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+                    {
+                        self.session_write = false;
+                    }
+                }
+                Controller::MaxSize(size) => self.response.max_size = Some(*size),
+                Controller::SizePolicy(policy) => self.response.size_policy = *policy,
+            }
+        }
+    }
+
+    /// Answer of the controller with `ControllerError`
+    ///
+    /// The error sets the status. The ajax request and the JSON controller get `{"code": 422, "error": "..."}`,
+    /// 404 gets the page `not_found`, the other statuses get the controller `action_err` of [web]
+    /// with the data "error_code" and "error_message" for its template.
+    ///
+    /// ```ignore
+    /// pub async fn save(this: &mut Action) -> Answer {
+    ///     let result = save_order(this).await;
+    ///     this.result(result).await
+    /// }
+    /// ```
+    pub async fn result(&mut self, result: Result<Answer, ControllerError>) -> Answer {
+        let error = match result {
+            Ok(answer) => return answer,
+            Err(error) => error,
+        };
+        if let ControllerError::Internal(_message) = &error {
+            log!(warning, 0, "{}. Error: {}", self.request.url, _message);
+        }
+        let code = error.code();
+        self.response.http_code = Some(code);
+        self.response.redirect = None;
+        self.response.cache = None;
+        self.response.etag = None;
+        self.response.last_modified = None;
+        let json = self.response.content_type.as_deref().is_some_and(|content_type| content_type.starts_with("application/json"));
+        if json || self.request.ajax {
+            self.response.content_type = Some("application/json; charset=utf-8".to_owned());
+            return Answer::String(json!({ "code": code, "error": error.message() }).to_string());
+        }
+        self.response.content_type = None;
+        if code == 404 {
+            return self.not_found_page().await;
+        }
+        self.set("error_code", code);
+        self.set("error_message", error.message().to_owned());
+        self.error_page().await.unwrap_or(Answer::None)
+    }
+}
+
+/// Body of the controller with `?`, the error is the answer of `Action::result`
+///
+/// # Example
+///
+/// ```ignore
+/// pub async fn save(this: &mut Action) -> Answer {
+///     answer!(this, {
+///         let id = this.request.input.post.get("id").and_then(|id| id.parse::<i64>().ok());
+///         let id = id.ok_or(ControllerError::BadRequest("id".to_owned()))?;
+///         this.db.update("order").set("status", &1).where_eq("id", &id).try_execute().await?;
+///         Ok(Answer::None)
+///     })
+/// }
+/// ```
+#[macro_export]
+macro_rules! answer {
+    ($this:ident, $body:block) => {{
+        let result: ::std::result::Result<$crate::sys::web::action::Answer, $crate::sys::web::controller::ControllerError> =
+            async { $body }.await;
+        $this.result(result).await
+    }};
+}
@@ -8,7 +8,7 @@ use serde_json::Value;
 
 use crate::fnv1a_64;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum Data {
     None,
     U8(u8),
@@ -60,10 +60,33 @@ macro_rules! impl_from_for_data {
                 }
             }
 
+            impl DataType for $type {
+                fn from_data(value: &Data) -> Option<&Self> {
+                    match value {
+                        Data::$variant(inner) => Some(inner),
+                        _ => None,
+                    }
+                }
+
+                fn take_data(value: Data) -> Result<Self, Data> {
+                    match value {
+                        Data::$variant(inner) => Ok(inner),
+                        value => Err(value),
+                    }
+                }
+            }
         )*
     };
 }
 
+/// Type of the value of `Data`, the conversion without the panic
+pub trait DataType: Sized {
+    /// The value, `None` if `Data` has the other type
+    fn from_data(value: &Data) -> Option<&Self>;
+    /// The value, the same `Data` if it has the other type
+    fn take_data(value: Data) -> Result<Self, Data>;
+}
+
 impl_from_for_data!(
     usize => Usize,
     u8 => U8,
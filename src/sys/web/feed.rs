@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::fnv1a_64;
+
+use super::{
+    action::{Action, Answer},
+    data::Data,
+};
+
+/// Format of the feed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedKind {
+    /// RSS 2.0, application/rss+xml
+    Rss,
+    /// Atom 1.0, application/atom+xml
+    Atom,
+}
+
+/// Channel of the feed
+///
+/// # Example
+///
+/// ```ignore
+/// let mut feed = Feed::new("Blog", "https://example.com/", "https://example.com/feed");
+/// feed.items.push(FeedItem::new("Hello", "https://example.com/hello", published));
+/// this.feed(&feed, FeedKind::Atom)
+/// ```
+#[derive(Debug, Clone)]
+pub struct Feed {
+    pub title: String,
+    /// Url of the site
+    pub link: String,
+    /// Url of the feed itself, the id of the Atom feed
+    pub url: String,
+    pub description: String,
+    pub language: Option<String>,
+    pub author: Option<String>,
+    /// Last change, the newest item if `None`
+    pub updated: Option<DateTime<Utc>>,
+    pub items: Vec<FeedItem>,
+}
+
+/// Item of the feed
+#[derive(Debug, Clone)]
+pub struct FeedItem {
+    pub title: String,
+    pub link: String,
+    /// Permanent unique id, the link if `None`
+    pub id: Option<String>,
+    /// Short text
+    pub summary: Option<String>,
+    /// Full HTML content
+    pub content: Option<String>,
+    pub author: Option<String>,
+    pub published: DateTime<Utc>,
+    pub updated: Option<DateTime<Utc>>,
+}
+
+impl Feed {
+    pub fn new(title: &str, link: &str, url: &str) -> Feed {
+        Feed {
+            title: title.to_owned(),
+            link: link.to_owned(),
+            url: url.to_owned(),
+            description: String::new(),
+            language: None,
+            author: None,
+            updated: None,
+            items: Vec::new(),
+        }
+    }
+
+    /// Last change of the feed
+    fn last_updated(&self) -> DateTime<Utc> {
+        match self.updated {
+            Some(updated) => updated,
+            None => self.items.iter().map(|item| item.updated.unwrap_or(item.published)).max().unwrap_or_else(Utc::now),
+        }
+    }
+
+    /// RSS 2.0 document
+    pub fn rss(&self) -> String {
+        let mut xml = String::with_capacity(1024 + self.items.len() * 512);
+        xml.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        xml.push_str(r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom"><channel>"#);
+        Feed::tag(&mut xml, "title", &self.title);
+        Feed::tag(&mut xml, "link", &self.link);
+        Feed::tag(&mut xml, "description", &self.description);
+        xml.push_str(&format!(r#"<atom:link href="{}" rel="self" type="application/rss+xml"/>"#, Feed::escape(&self.url)));
+        if let Some(language) = &self.language {
+            Feed::tag(&mut xml, "language", language);
+        }
+        Feed::tag(&mut xml, "lastBuildDate", &self.last_updated().to_rfc2822());
+        for item in &self.items {
+            xml.push_str("<item>");
+            Feed::tag(&mut xml, "title", &item.title);
+            Feed::tag(&mut xml, "link", &item.link);
+            match &item.id {
+                Some(id) => xml.push_str(&format!(r#"<guid isPermaLink="false">{}</guid>"#, Feed::escape(id))),
+                None => Feed::tag(&mut xml, "guid", &item.link),
+            }
+            if let Some(description) = item.summary.as_ref().or(item.content.as_ref()) {
+                Feed::tag(&mut xml, "description", description);
+            }
+            if let Some(author) = &item.author {
+                Feed::tag(&mut xml, "author", author);
+            }
+            Feed::tag(&mut xml, "pubDate", &item.published.to_rfc2822());
+            xml.push_str("</item>");
+        }
+        xml.push_str("</channel></rss>");
+        xml
+    }
+
+    /// Atom 1.0 document
+    pub fn atom(&self) -> String {
+        let mut xml = String::with_capacity(1024 + self.items.len() * 512);
+        xml.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        match &self.language {
+            Some(language) => xml.push_str(&format!(r#"<feed xmlns="http://www.w3.org/2005/Atom" xml:lang="{}">"#, Feed::escape(language))),
+            None => xml.push_str(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#),
+        }
+        Feed::tag(&mut xml, "id", &self.url);
+        Feed::tag(&mut xml, "title", &self.title);
+        if !self.description.is_empty() {
+            Feed::tag(&mut xml, "subtitle", &self.description);
+        }
+        xml.push_str(&format!(r#"<link href="{}"/>"#, Feed::escape(&self.link)));
+        xml.push_str(&format!(r#"<link href="{}" rel="self"/>"#, Feed::escape(&self.url)));
+        Feed::tag(&mut xml, "updated", &self.last_updated().to_rfc3339_opts(SecondsFormat::Secs, true));
+        if let Some(author) = &self.author {
+            xml.push_str("<author>");
+            Feed::tag(&mut xml, "name", author);
+            xml.push_str("</author>");
+        }
+        for item in &self.items {
+            xml.push_str("<entry>");
+            Feed::tag(&mut xml, "id", item.id.as_ref().unwrap_or(&item.link));
+            Feed::tag(&mut xml, "title", &item.title);
+            xml.push_str(&format!(r#"<link href="{}"/>"#, Feed::escape(&item.link)));
+            Feed::tag(&mut xml, "published", &item.published.to_rfc3339_opts(SecondsFormat::Secs, true));
+            Feed::tag(&mut xml, "updated", &item.updated.unwrap_or(item.published).to_rfc3339_opts(SecondsFormat::Secs, true));
+            if let Some(author) = &item.author {
+                xml.push_str("<author>");
+                Feed::tag(&mut xml, "name", author);
+                xml.push_str("</author>");
+            }
+            if let Some(summary) = &item.summary {
+                Feed::tag(&mut xml, "summary", summary);
+            }
+            if let Some(content) = &item.content {
+                xml.push_str(&format!(r#"<content type="html">{}</content>"#, Feed::escape(content)));
+            }
+            xml.push_str("</entry>");
+        }
+        xml.push_str("</feed>");
+        xml
+    }
+
+    fn tag(xml: &mut String, name: &str, value: &str) {
+        xml.push_str(&format!("<{}>{}</{}>", name, Feed::escape(value), name));
+    }
+
+    /// Escape the text and the attribute value, removes the characters not allowed in XML 1.0
+    pub(crate) fn escape(text: &str) -> String {
+        let mut res = String::with_capacity(text.len() + 16);
+        for c in text.chars() {
+            match c {
+                '&' => res.push_str("&amp;"),
+                '<' => res.push_str("&lt;"),
+                '>' => res.push_str("&gt;"),
+                '"' => res.push_str("&quot;"),
+                '\'' => res.push_str("&apos;"),
+                '\t' | '\n' | '\r' => res.push(c),
+                c if c < ' ' || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+                c => res.push(c),
+            }
+        }
+        res
+    }
+}
+
+impl FeedItem {
+    pub fn new(title: &str, link: &str, published: DateTime<Utc>) -> FeedItem {
+        FeedItem {
+            title: title.to_owned(),
+            link: link.to_owned(),
+            id: None,
+            summary: None,
+            content: None,
+            author: None,
+            published,
+            updated: None,
+        }
+    }
+}
+
+/// The feed for the custom template, the keys are the names of the fields, the items are in "items"
+impl From<&Feed> for Data {
+    fn from(feed: &Feed) -> Data {
+        let mut map = HashMap::with_capacity(9);
+        map.insert(fnv1a_64(b"title"), Data::String(feed.title.clone()));
+        map.insert(fnv1a_64(b"link"), Data::String(feed.link.clone()));
+        map.insert(fnv1a_64(b"url"), Data::String(feed.url.clone()));
+        map.insert(fnv1a_64(b"description"), Data::String(feed.description.clone()));
+        map.insert(fnv1a_64(b"language"), feed.language.clone().map(Data::String).unwrap_or(Data::None));
+        map.insert(fnv1a_64(b"author"), feed.author.clone().map(Data::String).unwrap_or(Data::None));
+        map.insert(fnv1a_64(b"updated"), Data::Date(feed.last_updated()));
+        map.insert(fnv1a_64(b"items"), Data::Vec(feed.items.iter().map(Data::from).collect()));
+        Data::Map(map)
+    }
+}
+
+impl From<&FeedItem> for Data {
+    fn from(item: &FeedItem) -> Data {
+        let mut map = HashMap::with_capacity(8);
+        map.insert(fnv1a_64(b"title"), Data::String(item.title.clone()));
+        map.insert(fnv1a_64(b"link"), Data::String(item.link.clone()));
+        map.insert(fnv1a_64(b"id"), Data::String(item.id.clone().unwrap_or_else(|| item.link.clone())));
+        map.insert(fnv1a_64(b"summary"), item.summary.clone().map(Data::String).unwrap_or(Data::None));
+        map.insert(fnv1a_64(b"content"), item.content.clone().map(Data::String).unwrap_or(Data::None));
+        map.insert(fnv1a_64(b"author"), item.author.clone().map(Data::String).unwrap_or(Data::None));
+        map.insert(fnv1a_64(b"published"), Data::Date(item.published));
+        map.insert(fnv1a_64(b"updated"), Data::Date(item.updated.unwrap_or(item.published)));
+        Data::Map(map)
+    }
+}
+
+impl Action {
+    /// Answer with the feed, sets the Content-Type of the format
+    pub fn feed(&mut self, feed: &Feed, kind: FeedKind) -> Answer {
+        match kind {
+            FeedKind::Rss => {
+                self.response.content_type = Some("application/rss+xml; charset=utf-8".to_owned());
+                Answer::String(feed.rss())
+            }
+            FeedKind::Atom => {
+                self.response.content_type = Some("application/atom+xml; charset=utf-8".to_owned());
+                Answer::String(feed.atom())
+            }
+        }
+    }
+}
@@ -1,14 +1,37 @@
 use std::{
+    collections::HashMap,
     env,
-    path::PathBuf,
-    time::{SystemTime, UNIX_EPOCH},
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
-use chrono::Local;
+use chrono::{DateTime, Local, Utc};
 
-use tokio::{fs::File, io::AsyncWriteExt};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+
+use crate::{
+    fnv1a_64, log,
+    sys::{app::init::HotConfig, net::worker::Worker},
+};
+
+use super::{
+    action::{Action, Answer},
+    response::{ETag, MultipartWriter},
+};
+
+#[cfg(feature = "image")]
+use super::image::{Image, ImageType};
 
-use crate::{fnv1a_64, log};
+/// Max number of the ranges of the `Range` header, the request with more ranges gets the whole file
+const FILE_RANGES: usize = 16;
+
+/// Hot files, set when the section [hot] is present
+static HOT: OnceLock<HotFiles> = OnceLock::new();
 
 /// Empty struct for working temp file
 pub(crate) struct TempFile;
@@ -31,28 +54,455 @@ impl TempFile {
 
         temp_dir
     }
+}
 
-    /// Write data to the temporary file
-    pub async fn write(path: &PathBuf, data: &[u8]) -> Result<(), ()> {
-        let mut file = match File::create(path).await {
-            Ok(file) => file,
-            Err(_e) => {
-                log!(warning, 0, "{}. Error: {}", path.display(), _e);
-                return Err(());
+/// Content of the hot file in memory
+#[derive(Debug, Clone)]
+struct HotFile {
+    data: Arc<Vec<u8>>,
+    modified: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct HotEntry {
+    /// `None` until the file has enough requests, or after its change
+    file: Option<HotFile>,
+    hits: u64,
+    /// Last check of the file on the disk
+    checked: Instant,
+}
+
+#[derive(Debug, Default)]
+struct HotState {
+    files: HashMap<PathBuf, HotEntry>,
+    /// Size of the files in memory
+    total: usize,
+}
+
+/// Hot files of `Action::file` kept in memory, the section [hot]
+///
+/// The file is kept after `hits` requests if it is not larger than `size` and all files are not larger than `total`.
+/// The file is checked by the size and the time of the change not more often than `check`,
+/// the changed file is removed from memory and is kept again by the next full reading.
+#[derive(Debug)]
+pub(crate) struct HotFiles {
+    config: Arc<HotConfig>,
+    state: Mutex<HotState>,
+}
+
+impl HotFiles {
+    /// Max number of the counted files, the others are read from the disk
+    const MAX_FILES: usize = 4096;
+
+    /// Keep the hot files, only once
+    pub(crate) fn start(config: Arc<HotConfig>) {
+        let _ = HOT.set(HotFiles {
+            config,
+            state: Mutex::new(HotState::default()),
+        });
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, HotState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(e) => e.into_inner(),
+        }
+    }
+
+    /// File in memory, the file changed on the disk is removed
+    async fn get(path: &Path) -> Option<HotFile> {
+        let hot = HOT.get()?;
+        let file = {
+            let state = hot.state();
+            let entry = state.files.get(path)?;
+            let file = entry.file.clone()?;
+            if entry.checked.elapsed() < hot.config.check {
+                return Some(file);
             }
+            file
         };
-        let len = match file.write(data).await {
-            Ok(len) => len,
-            Err(_e) => {
+        let same = match tokio::fs::metadata(path).await {
+            Ok(meta) => {
+                meta.len() == file.data.len() as u64
+                    && meta.modified().is_ok_and(|modified| DateTime::<Utc>::from(modified).timestamp() == file.modified.timestamp())
+            }
+            Err(_) => false,
+        };
+        let mut state = hot.state();
+        let state = &mut *state;
+        let entry = state.files.get_mut(path)?;
+        if same {
+            entry.checked = Instant::now();
+            return Some(file);
+        }
+        if let Some(file) = entry.file.take() {
+            state.total -= file.data.len();
+        }
+        None
+    }
+
+    /// The file is not larger than the hot file, it is read whole for `HotFiles::add`
+    fn fits(size: u64) -> bool {
+        HOT.get().is_some_and(|hot| size <= hot.config.size as u64)
+    }
+
+    /// Count the full reading of the file from the disk, the hot one is kept in memory
+    fn add(path: &Path, modified: DateTime<Utc>, data: &[u8]) {
+        let hot = match HOT.get() {
+            Some(hot) => hot,
+            None => return,
+        };
+        let config = &hot.config;
+        let mut state = hot.state();
+        let state = &mut *state;
+        if state.files.len() >= HotFiles::MAX_FILES && !state.files.contains_key(path) {
+            return;
+        }
+        let entry = state.files.entry(path.to_path_buf()).or_insert_with(|| HotEntry {
+            file: None,
+            hits: 0,
+            checked: Instant::now(),
+        });
+        entry.hits += 1;
+        if entry.file.is_some() || entry.hits < config.hits || data.len() > config.size || state.total + data.len() > config.total {
+            return;
+        }
+        state.total += data.len();
+        entry.file = Some(HotFile {
+            data: Arc::new(data.to_vec()),
+            modified,
+        });
+        entry.checked = Instant::now();
+    }
+}
+
+/// Source of the data of `Action::file`
+enum FileSource {
+    Disk(File),
+    Memory(Arc<Vec<u8>>),
+}
+
+impl Action {
+    /// Send file from disk
+    ///
+    /// The file is sent from the disk by parts, it is not read whole into memory.
+    /// With the section [hot] the frequently sent small files are kept in memory, see `HotFiles`.
+    /// Honours `Range` and `If-Range` headers and answers `206 Partial Content`,
+    /// so browsers can resume downloads and stream video. Several ranges are sent as `multipart/byteranges`.
+    /// If `name` is set, the file is sent as an attachment.
+    pub async fn file(&mut self, path: &Path, name: Option<&str>) -> Answer {
+        let (source, size, modified) = match HotFiles::get(path).await {
+            Some(hot) => {
+                let size = hot.data.len() as u64;
+                (FileSource::Memory(hot.data), size, hot.modified)
+            }
+            None => {
+                let file = match File::open(path).await {
+                    Ok(file) => file,
+                    Err(_e) => {
+                        log!(warning, 0, "{}. Error: {}", path.display(), _e);
+                        self.response.http_code = Some(404);
+                        return Answer::None;
+                    }
+                };
+                let meta = match file.metadata().await {
+                    Ok(meta) => meta,
+                    Err(_e) => {
+                        log!(warning, 0, "{}. Error: {}", path.display(), _e);
+                        self.response.http_code = Some(500);
+                        return Answer::None;
+                    }
+                };
+                let modified: DateTime<Utc> = match meta.modified() {
+                    Ok(modified) => modified.into(),
+                    Err(_) => Utc::now(),
+                };
+                (FileSource::Disk(file), meta.len(), modified)
+            }
+        };
+        let tag = ETag::Strong(format!("{:x}-{:x}", size, modified.timestamp()));
+        let etag = tag.header().unwrap_or_default();
+        let last_modified = modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+
+        if self.response.content_type.is_none() {
+            self.response.content_type = Some(Action::file_mime(path).to_owned());
+        }
+        self.response.headers.push(("Accept-Ranges".to_owned(), "bytes".to_owned()));
+        if let Some(name) = name {
+            self.response.headers.push(("Content-Disposition".to_owned(), format!("attachment; filename=\"{}\"", name.replace('"', ""))));
+        }
+
+        // The range is ignored if the file has been changed
+        let range = match self.request.header("Range") {
+            Some(range) => match self.request.header("If-Range") {
+                Some(value) if value != etag && value != last_modified => None,
+                _ => Some(range.to_owned()),
+            },
+            None => None,
+        };
+        self.response.last_modified = Some(modified);
+        if self.etag(tag) {
+            return Answer::None;
+        }
+
+        let ranges = match range {
+            Some(range) => match Action::file_range(&range, size) {
+                Ok(Some(ranges)) => ranges,
+                Ok(None) => Vec::new(),
+                Err(_) => {
+                    self.response.http_code = Some(416);
+                    self.response.headers.push(("Content-Range".to_owned(), format!("bytes */{}", size)));
+                    return Answer::None;
+                }
+            },
+            None => Vec::new(),
+        };
+        if ranges.len() > 1 {
+            return self.file_parts(source, size, &ranges).await;
+        }
+        let (start, end) = match ranges.first() {
+            Some(&(start, end)) => {
+                self.response.http_code = Some(206);
+                self.response.headers.push(("Content-Range".to_owned(), format!("bytes {}-{}/{}", start, end, size)));
+                (start, end + 1)
+            }
+            None => (0, size),
+        };
+
+        let mut file = match source {
+            FileSource::Memory(data) => return Answer::Raw(data[start as usize..end as usize].to_vec()),
+            FileSource::Disk(file) => file,
+        };
+        if start > 0 {
+            if let Err(_e) = file.seek(SeekFrom::Start(start)).await {
+                log!(warning, 0, "{}. Error: {}", path.display(), _e);
+                self.response.http_code = Some(500);
+                return Answer::None;
+            }
+        }
+        if start == 0 && end == size && HotFiles::fits(size) {
+            let mut data = vec![0; size as usize];
+            if let Err(_e) = file.read_exact(&mut data).await {
                 log!(warning, 0, "{}. Error: {}", path.display(), _e);
-                return Err(());
+                self.response.http_code = Some(500);
+                return Answer::None;
             }
+            HotFiles::add(path, modified, &data);
+            return Answer::Raw(data);
+        }
+        if let Err(_e) = Worker::send_file(self, &mut file, end - start).await {
+            log!(warning, 0, "{}. Error: {}", path.display(), _e);
+        }
+        Answer::None
+    }
+
+    /// Send image from disk in the best format accepted by the browser
+    ///
+    /// The variants are the files beside the original with the extension "avif" or "webp",
+    /// for example "photo.avif" and "photo.webp" for "photo.jpg". AVIF is preferred, then WebP, then the original.
+    /// With the feature "image" the PNG or GIF without the variant is converted to WebP on the first request,
+    /// the variant is kept in the temporary folder by the path, the time of the change and the format,
+    /// and is sent only if it is smaller than the original.
+    /// The answer has `Vary: Accept`, so the proxies and the browser cache every format separately.
+    pub async fn image(&mut self, path: &Path) -> Answer {
+        let accept = self.request.header("Accept").unwrap_or_default().to_owned();
+        let mut file = path.to_path_buf();
+        for (ext, mime) in [("avif", "image/avif"), ("webp", "image/webp")] {
+            if path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case(ext)) {
+                break;
+            }
+            if Action::accept_mime(&accept, mime) {
+                let variant = path.with_extension(ext);
+                if tokio::fs::metadata(&variant).await.is_ok_and(|meta| meta.is_file()) {
+                    file = variant;
+                    break;
+                }
+            }
+        }
+        #[cfg(feature = "image")]
+        if file == path && Action::accept_mime(&accept, ImageType::Webp.mime()) {
+            if let Some(variant) = Action::image_variant(path, ImageType::Webp).await {
+                file = variant;
+            }
+        }
+        self.response.headers.push(("Vary".to_owned(), "Accept".to_owned()));
+        self.file(&file, None).await
+    }
+
+    /// Variant of the image converted to `format`, `None` if it is not smaller than the original
+    ///
+    /// The variant is converted once, the same requests during the conversion make their own copies,
+    /// the whole file appears by the rename.
+    #[cfg(feature = "image")]
+    async fn image_variant(path: &Path, format: ImageType) -> Option<PathBuf> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        if !matches!(ext.as_str(), "png" | "gif") {
+            return None;
+        }
+        let meta = tokio::fs::metadata(path).await.ok()?;
+        let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+        let key = fnv1a_64(format!("{}:{}:{}", path.display(), modified, format.ext()).as_bytes());
+        let mut variant = env::temp_dir();
+        variant.push(format!("tiny_image_{:x}.{}", key, format.ext()));
+        let size = match tokio::fs::metadata(&variant).await {
+            Ok(meta) => meta.len(),
+            Err(_) => {
+                let image = match format {
+                    ImageType::Png => Image::from_path(path).png(),
+                    ImageType::Jpeg(quality) => Image::from_path(path).jpeg(quality),
+                    ImageType::Webp => Image::from_path(path).webp(),
+                };
+                let tmp = TempFile::new_name();
+                image.save(&tmp).await.ok()?;
+                if let Err(_e) = tokio::fs::rename(&tmp, &variant).await {
+                    log!(warning, 0, "{}. Error: {}", variant.display(), _e);
+                    let _ = tokio::fs::remove_file(&tmp).await;
+                    return None;
+                }
+                tokio::fs::metadata(&variant).await.ok()?.len()
+            }
+        };
+        (size < meta.len()).then_some(variant)
+    }
+
+    /// The Accept header lists the type explicitly with q > 0
+    fn accept_mime(accept: &str, mime: &str) -> bool {
+        accept.split(',').any(|item| {
+            let mut parts = item.split(';');
+            if !parts.next().unwrap_or_default().trim().eq_ignore_ascii_case(mime) {
+                return false;
+            }
+            for param in parts {
+                if let Some((key, value)) = param.split_once('=') {
+                    if key.trim().eq_ignore_ascii_case("q") {
+                        return value.trim().parse::<f32>().is_ok_and(|q| q > 0.0);
+                    }
+                }
+            }
+            true
+        })
+    }
+
+    /// Parse header "Range: bytes=start-end"
+    ///
+    /// Returns `Ok(None)` when the header must be ignored, `Err` when the range is not satisfiable.
+    /// The ranges are sorted and the overlapping or adjacent ones are joined (RFC 9110 §14.2),
+    /// so the answer is never larger than the file.
+    fn file_range(range: &str, size: u64) -> Result<Option<Vec<(u64, u64)>>, ()> {
+        let range = match range.trim().strip_prefix("bytes=") {
+            Some(range) => range.trim(),
+            None => return Ok(None),
+        };
+        let mut list = Vec::new();
+        for range in range.split(',') {
+            // Too many ranges are the whole file
+            if list.len() == FILE_RANGES {
+                return Ok(None);
+            }
+            let (start, end) = match range.split_once('-') {
+                Some(range) => range,
+                None => return Ok(None),
+            };
+            let (start, end) = match (start.trim(), end.trim()) {
+                ("", "") => return Ok(None),
+                ("", suffix) => match suffix.parse::<u64>() {
+                    Ok(0) => continue,
+                    Ok(suffix) => (size.saturating_sub(suffix), size.saturating_sub(1)),
+                    Err(_) => return Ok(None),
+                },
+                (start, "") => match start.parse::<u64>() {
+                    Ok(start) => (start, size.saturating_sub(1)),
+                    Err(_) => return Ok(None),
+                },
+                (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+                    (Ok(start), Ok(end)) if start <= end => (start, end.min(size.saturating_sub(1))),
+                    _ => return Ok(None),
+                },
+            };
+            // The unsatisfiable range is skipped, 416 only if all ranges are unsatisfiable
+            if size > 0 && start < size {
+                list.push((start, end));
+            }
+        }
+        if list.is_empty() {
+            return Err(());
+        }
+        list.sort_unstable();
+        let mut ranges: Vec<(u64, u64)> = Vec::with_capacity(list.len());
+        for (start, end) in list {
+            match ranges.last_mut() {
+                Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                _ => ranges.push((start, end)),
+            }
+        }
+        Ok(Some(ranges))
+    }
+
+    /// Answer `multipart/byteranges` of several ranges, every part has the Content-Range header
+    async fn file_parts(&mut self, source: FileSource, size: u64, ranges: &[(u64, u64)]) -> Answer {
+        let content_type = self.response.content_type.take().unwrap_or_else(|| "application/octet-stream".to_owned());
+        let writer = MultipartWriter::new();
+        let mut answer = Vec::new();
+        let mut file = match source {
+            FileSource::Memory(data) => {
+                for &(start, end) in ranges {
+                    let range = [("Content-Range", format!("bytes {}-{}/{}", start, end, size))];
+                    answer.extend_from_slice(&writer.part(&content_type, &range, &data[start as usize..=end as usize]));
+                }
+                None
+            }
+            FileSource::Disk(file) => Some(file),
+        };
+        if let Some(file) = &mut file {
+            for &(start, end) in ranges {
+                let mut data = vec![0; (end - start + 1) as usize];
+                if let Err(_e) = file.seek(SeekFrom::Start(start)).await {
+                    log!(warning, 0, "{}. Error: {}", self.request.url, _e);
+                    self.response.http_code = Some(500);
+                    return Answer::None;
+                }
+                if let Err(_e) = file.read_exact(&mut data).await {
+                    log!(warning, 0, "{}. Error: {}", self.request.url, _e);
+                    self.response.http_code = Some(500);
+                    return Answer::None;
+                }
+                let range = [("Content-Range", format!("bytes {}-{}/{}", start, end, size))];
+                answer.extend_from_slice(&writer.part(&content_type, &range, &data));
+            }
+        }
+        answer.extend_from_slice(&writer.close());
+        self.response.http_code = Some(206);
+        self.response.content_type = Some(writer.content_type("byteranges"));
+        Answer::Raw(answer)
+    }
+
+    /// Get Content-Type by file extension
+    fn file_mime(path: &Path) -> &'static str {
+        let ext = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => ext.to_lowercase(),
+            None => return "application/octet-stream",
         };
-        if data.len() == len {
-            Ok(())
-        } else {
-            log!(warning, 0, "{}. Written len: {} ({})", path.display(), len, data.len());
-            Err(())
+        match ext.as_str() {
+            "txt" => "text/plain; charset=utf-8",
+            "html" | "htm" => "text/html; charset=utf-8",
+            "css" => "text/css",
+            "js" => "text/javascript",
+            "json" => "application/json",
+            "xml" => "application/xml",
+            "pdf" => "application/pdf",
+            "zip" => "application/zip",
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "gif" => "image/gif",
+            "webp" => "image/webp",
+            "avif" => "image/avif",
+            "svg" => "image/svg+xml",
+            "ico" => "image/x-icon",
+            "mp3" => "audio/mpeg",
+            "ogg" => "audio/ogg",
+            "mp4" => "video/mp4",
+            "webm" => "video/webm",
+            _ => "application/octet-stream",
         }
     }
 }
@@ -0,0 +1,89 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{sync::watch, time::timeout};
+
+/// Single flight of the loading of the missing keys, the parameter [web] coalesce
+///
+/// The first request of the missing key is the leader, it loads the value while the others with the same key
+/// wait for the end of its flight and read the cache again. Only the keys with the prefixes of the list are joined.
+#[derive(Debug, Default)]
+pub(crate) struct Flight {
+    /// Prefixes of the keys, for example "sys:page:" for the pages of `cache_page`
+    prefix: Vec<String>,
+    /// Max time of the waiting for the leader
+    wait: Duration,
+    /// Key -> end of the flight
+    list: Arc<Mutex<HashMap<String, watch::Receiver<()>>>>,
+}
+
+/// Flight of the leader, the waiting requests are released by the drop
+#[derive(Debug)]
+pub(crate) struct FlightGuard {
+    key: String,
+    list: Arc<Mutex<HashMap<String, watch::Receiver<()>>>>,
+    _end: watch::Sender<()>,
+}
+
+impl Flight {
+    pub(crate) fn new(prefix: Vec<String>, wait: Duration) -> Flight {
+        Flight {
+            prefix,
+            wait,
+            list: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The key is joined by the prefix
+    pub(crate) fn is_on(&self, key: &str) -> bool {
+        self.prefix.iter().any(|prefix| key.starts_with(prefix.as_str()))
+    }
+
+    /// The flight of the key, `None` if the flight of the other request is going on
+    pub(crate) fn lead(&self, key: &str) -> Option<FlightGuard> {
+        let mut list = match self.list.lock() {
+            Ok(list) => list,
+            Err(e) => e.into_inner(),
+        };
+        if list.contains_key(key) {
+            return None;
+        }
+        let (end, rx) = watch::channel(());
+        list.insert(key.to_owned(), rx);
+        Some(FlightGuard {
+            key: key.to_owned(),
+            list: Arc::clone(&self.list),
+            _end: end,
+        })
+    }
+
+    /// Wait for the end of the flight of the key, `false` without the flight
+    pub(crate) async fn wait(&self, key: &str) -> bool {
+        let mut rx = {
+            let list = match self.list.lock() {
+                Ok(list) => list,
+                Err(e) => e.into_inner(),
+            };
+            match list.get(key) {
+                Some(rx) => rx.clone(),
+                None => return false,
+            }
+        };
+        // The error is the end of the flight, nothing is sent
+        let _ = timeout(self.wait, rx.changed()).await;
+        true
+    }
+}
+
+impl Drop for FlightGuard {
+    fn drop(&mut self) {
+        let mut list = match self.list.lock() {
+            Ok(list) => list,
+            Err(e) => e.into_inner(),
+        };
+        list.remove(&self.key);
+    }
+}
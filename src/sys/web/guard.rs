@@ -0,0 +1,53 @@
+use super::{action::Action, action::Answer, response::Redirect};
+
+/// Pre-check of the controller
+///
+/// Usually set by the attribute `#[guard]` and checked by `Action::dispatch` before the controller.
+#[derive(Debug, Clone)]
+pub enum Guard {
+    /// The user must be authorized, otherwise 401
+    Auth,
+    /// The role of the user or its parent must have the permission "role.{name}", otherwise 403, see `Action::can`
+    Role(&'static str),
+    /// On failure, redirect to this url instead of 401/403 (ignored for ajax requests)
+    Redirect(&'static str),
+}
+
+impl Action {
+    /// Checks guards of the controller
+    ///
+    /// Returns `None` when all checks are passed, otherwise `Some(Answer)` which must be returned from the controller.
+    pub async fn guard(&mut self, guards: &[Guard]) -> Option<Answer> {
+        let mut redirect = None;
+        let mut code = None;
+        for guard in guards {
+            match guard {
+                Guard::Auth => {
+                    if code.is_none() && !matches!(self.session.user_id, Some(user_id) if user_id > 0) {
+                        code = Some(401);
+                    }
+                }
+                Guard::Role(name) => {
+                    if code.is_none() && !self.can(&format!("role.{}", name)).await {
+                        code = match self.session.user_id {
+                            Some(user_id) if user_id > 0 => Some(403),
+                            _ => Some(401),
+                        };
+                    }
+                }
+                Guard::Redirect(url) => redirect = Some(*url),
+            }
+        }
+        let code = code?;
+        match redirect {
+            Some(url) if !self.request.ajax => {
+                self.response.redirect = Some(Redirect {
+                    url: url.to_owned(),
+                    permanently: false,
+                });
+            }
+            _ => self.response.http_code = Some(code),
+        }
+        Some(Answer::None)
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, RwLock},
+};
+
+use crate::fnv1a_64;
+
+use super::{
+    action::{Action, Answer, ModuleMap},
+    controller::Controller,
+};
+
+#[cfg(feature = "access-db")]
+use super::guard::Guard;
+
+/// Controller of `Register`, it may have the state
+pub type Handler = Arc<dyn for<'a> Fn(&'a mut Action) -> Pin<Box<dyn Future<Output = Answer> + Send + 'a>> + Send + Sync>;
+
+/// Controllers of `Register` by module, class and action
+static HANDLERS: RwLock<BTreeMap<(i64, i64, i64), Handler>> = RwLock::new(BTreeMap::new());
+
+/// Async function of the controller `async fn(this: &mut Action) -> Answer`
+pub trait AsyncAction<'a>: Send + Sync + 'static {
+    type Future: Future<Output = Answer> + Send + 'a;
+
+    fn call(&self, this: &'a mut Action) -> Self::Future;
+}
+
+impl<'a, F, Fut> AsyncAction<'a> for F
+where
+    F: Fn(&'a mut Action) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Answer> + Send + 'a,
+{
+    type Future = Fut;
+
+    fn call(&self, this: &'a mut Action) -> Fut {
+        self(this)
+    }
+}
+
+/// Registration of the controllers without the macro `addfn!`
+///
+/// The controllers are registered for the process and may be added or removed while the server is running.
+/// The controller of `addfn!` with the same route has the priority.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut engine = addfn!(...);
+/// engine.register("blog", "post", "index", post::index);
+///
+/// // The controller with the state
+/// let title = Arc::new(String::from("News"));
+/// engine.register_handler("blog", "post", "title", handler(move |this| {
+///     let title = Arc::clone(&title);
+///     Box::pin(async move { Answer::String(format!("{} {}", title, this.request.url)) })
+/// }));
+/// tiny_web::run(name, version, desc, engine);
+/// ```
+pub trait Register {
+    /// Add the async function of the controller "/module/class/action"
+    fn register<F>(&mut self, module: &str, class: &str, action: &str, act: F) -> &mut Self
+    where
+        F: for<'a> AsyncAction<'a>;
+
+    /// Add the controller with the state, see `handler`
+    fn register_handler(&mut self, module: &str, class: &str, action: &str, handler: Handler) -> &mut Self;
+
+    /// Remove the controller of `register`, returns `true` if it was registered
+    fn unregister(&mut self, module: &str, class: &str, action: &str) -> bool;
+}
+
+impl Register for ModuleMap {
+    fn register<F>(&mut self, module: &str, class: &str, action: &str, act: F) -> &mut Self
+    where
+        F: for<'a> AsyncAction<'a>,
+    {
+        self.register_handler(module, class, action, handler(move |this| Box::pin(act.call(this))))
+    }
+
+    fn register_handler(&mut self, module: &str, class: &str, action: &str, handler: Handler) -> &mut Self {
+        let mut list = match HANDLERS.write() {
+            Ok(list) => list,
+            Err(e) => e.into_inner(),
+        };
+        list.insert(Handlers::key(module, class, action), handler);
+        self
+    }
+
+    fn unregister(&mut self, module: &str, class: &str, action: &str) -> bool {
+        let mut list = match HANDLERS.write() {
+            Ok(list) => list,
+            Err(e) => e.into_inner(),
+        };
+        list.remove(&Handlers::key(module, class, action)).is_some()
+    }
+}
+
+/// Controller of the closure with the state for `Register::register_handler`
+///
+/// The closure returns `Box::pin(async move { ... })`, the function also helps the compiler with the lifetime of `this`.
+pub fn handler<F>(f: F) -> Handler
+where
+    F: for<'a> Fn(&'a mut Action) -> Pin<Box<dyn Future<Output = Answer> + Send + 'a>> + Send + Sync + 'static,
+{
+    Arc::new(f)
+}
+
+/// Attributes of the controller, `#[guard]` and `#[controller]`, applied by `Action::dispatch`
+///
+/// The attribute keeps the body of the controller and makes the function of `addfn!` the wrapper of the dispatch.
+#[derive(Debug, Default)]
+pub struct Attributes {
+    #[cfg(feature = "access-db")]
+    guards: &'static [Guard],
+    options: &'static [Controller],
+}
+
+impl Attributes {
+    pub const fn new() -> Attributes {
+        Attributes {
+            #[cfg(feature = "access-db")]
+            guards: &[],
+            options: &[],
+        }
+    }
+
+    /// Guards of `#[guard]`
+    #[cfg(feature = "access-db")]
+    pub const fn guards(mut self, guards: &'static [Guard]) -> Attributes {
+        self.guards = guards;
+        self
+    }
+
+    /// Options of `#[controller]`
+    pub const fn options(mut self, options: &'static [Controller]) -> Attributes {
+        self.options = options;
+        self
+    }
+}
+
+impl Action {
+    /// Run the controller with the attributes
+    ///
+    /// The guards are checked before the controller, the options are applied only to the controller of the request,
+    /// not to the internal call of `Action::load`.
+    #[doc(hidden)]
+    pub async fn dispatch<F>(&mut self, attributes: &Attributes, act: F) -> Answer
+    where
+        F: for<'a> AsyncAction<'a>,
+    {
+        #[cfg(feature = "access-db")]
+        if let Some(answer) = self.guard(attributes.guards).await {
+            return answer;
+        }
+        if !self.internal {
+            self.controller(attributes.options);
+        }
+        act.call(self).await
+    }
+}
+
+/// Search of the controllers of `Register`
+pub(crate) struct Handlers;
+
+impl Handlers {
+    pub(crate) fn get(module_id: i64, class_id: i64, action_id: i64) -> Option<Handler> {
+        let list = match HANDLERS.read() {
+            Ok(list) => list,
+            Err(e) => e.into_inner(),
+        };
+        list.get(&(module_id, class_id, action_id)).cloned()
+    }
+
+    fn key(module: &str, class: &str, action: &str) -> (i64, i64, i64) {
+        (fnv1a_64(module.as_bytes()), fnv1a_64(class.as_bytes()), fnv1a_64(action.as_bytes()))
+    }
+}
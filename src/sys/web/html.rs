@@ -6,7 +6,7 @@ use std::{
 };
 
 #[cfg(feature = "html-reload")]
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 #[cfg(feature = "html-reload")]
 use std::time::SystemTime;
@@ -20,12 +20,18 @@ use tokio::sync::OnceCell;
 #[cfg(feature = "html-reload")]
 use tokio::sync::RwLock;
 
+use chrono::DateTime;
+
 use crate::{fnv1a_64, log};
 
 #[cfg(feature = "html-reload")]
 use crate::sys::wrlock::WrLock;
 
-use super::{action::Answer, data::Data};
+use super::{
+    action::Answer,
+    data::Data,
+    locale::{DateStyle, Locale},
+};
 
 /// The filter of the variable
 #[derive(Debug, Clone, PartialEq)]
@@ -44,6 +50,10 @@ pub(crate) enum Filter {
     Index,
     /// Dump of value
     Dump,
+    /// Date or time by the rules of the language of the template
+    Date(DateStyle),
+    /// Number with the separators of the language of the template
+    Number,
 }
 
 /// The value of the variable
@@ -237,6 +247,10 @@ pub(crate) type Nodes = Vec<Node>;
 #[cfg(feature = "html-reload")]
 static WRLOCK: OnceCell<WrLock> = OnceCell::const_new();
 
+/// Number of the reloads of the templates
+#[cfg(feature = "html-reload")]
+pub(crate) static RELOADS: AtomicU64 = AtomicU64::new(0);
+
 /// Html template marker
 #[derive(Debug)]
 pub(crate) struct Html {
@@ -684,6 +698,24 @@ impl Html {
                                     None
                                 }
                             }
+                            "date" | "longdate" | "time" | "datetime" | "longdatetime" | "number" => {
+                                if exp.is_none() {
+                                    let filter = match &val[idx + 1..] {
+                                        "date" => Filter::Date(DateStyle::Short),
+                                        "longdate" => Filter::Date(DateStyle::Long),
+                                        "time" => Filter::Date(DateStyle::Time),
+                                        "datetime" => Filter::Date(DateStyle::ShortTime),
+                                        "longdatetime" => Filter::Date(DateStyle::LongTime),
+                                        _ => Filter::Number,
+                                    };
+                                    Some(Value::Value {
+                                        name: vl.iter().map(|s| s.to_string()).collect(),
+                                        filter,
+                                    })
+                                } else {
+                                    None
+                                }
+                            }
                             _ => None,
                         }
                     } else {
@@ -1598,6 +1630,69 @@ impl Html {
                 Filter::Set => "{{err::Set}}".to_owned(),
                 Filter::Unset => "{{err::Unset}}".to_owned(),
                 Filter::Dump => Html::data_to_dump(name, data, tmp),
+                Filter::Date(_) | Filter::Number => Html::escape(Html::data_to_locale(name, filter, data, tmp)),
+            },
+        }
+    }
+
+    /// Date or number by the rules of the variable `locale` of the template, English without it
+    ///
+    /// The dates are `Data::Date`, the strings of RFC 3339 and the unix time in seconds.
+    /// The numbers have 2 decimals for the floats and the decimals of the string, for example "10.500".
+    fn data_to_locale(name: &[String], filter: &Filter, data: &HashMap<i64, Data>, tmp: &HashMap<i64, Data>) -> String {
+        let locale = match data.get(&fnv1a_64(b"locale")) {
+            Some(Data::String(code)) => Locale::new(code),
+            _ => Locale::default(),
+        };
+        let mut val = match name.first().map(|name| fnv1a_64(name.as_bytes())).and_then(|key| data.get(&key).or_else(|| tmp.get(&key))) {
+            Some(v) => v,
+            None => return format!("{{{{{}}}}}", name.join(".")),
+        };
+        for name in &name[1..] {
+            val = match val {
+                Data::Map(map) => match map.get(&fnv1a_64(name.as_bytes())) {
+                    Some(v) => v,
+                    None => return String::new(),
+                },
+                _ => return String::new(),
+            };
+        }
+        match filter {
+            Filter::Date(style) => match val {
+                Data::Date(date) => locale.date(date, *style),
+                Data::String(str) => match DateTime::parse_from_rfc3339(str) {
+                    Ok(date) => locale.date(&date, *style),
+                    Err(_) => str.to_owned(),
+                },
+                Data::I64(time) => match DateTime::from_timestamp(*time, 0) {
+                    Some(date) => locale.date(&date, *style),
+                    None => time.to_string(),
+                },
+                val => Html::print_data(val),
+            },
+            _ => match val {
+                Data::F32(f) => locale.number(f64::from(*f), 2),
+                Data::F64(f) => locale.number(*f, 2),
+                Data::String(str) => match str.trim().parse::<f64>() {
+                    Ok(f) => locale.number(f, str.split_once('.').map(|(_, frac)| frac.trim().len()).unwrap_or(0)),
+                    Err(_) => str.to_owned(),
+                },
+                Data::I8(_)
+                | Data::I16(_)
+                | Data::I32(_)
+                | Data::I64(_)
+                | Data::U8(_)
+                | Data::U16(_)
+                | Data::U32(_)
+                | Data::U64(_)
+                | Data::Usize(_) => {
+                    let text = Html::print_data(val);
+                    match text.parse::<f64>() {
+                        Ok(f) if f.abs() < 1e15 => locale.number(f, 0),
+                        _ => text,
+                    }
+                }
+                val => Html::print_data(val),
             },
         }
     }
@@ -1921,7 +2016,8 @@ impl Html {
         } else {
             let reload = html.read().await.check_time().await;
             if reload {
-                html.write().await.load().await
+                html.write().await.load().await;
+                RELOADS.fetch_add(1, Ordering::Relaxed);
             }
             wr.lock.store(false, Ordering::SeqCst);
             wr.notify.notify_waiters();
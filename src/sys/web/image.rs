@@ -0,0 +1,256 @@
+use std::{io::Cursor, path::Path};
+
+#[cfg(feature = "file-disk")]
+use std::path::PathBuf;
+
+use ::image::{
+    codecs::{jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage, ImageDecoder, ImageFormat, ImageReader,
+};
+#[cfg(feature = "file-disk")]
+use tokio::fs::read;
+use tokio::{fs::write, task::spawn_blocking};
+
+use crate::log;
+
+use super::request::WebFile;
+
+#[cfg(feature = "storage")]
+use super::storage::Storage;
+
+/// Max width and height of the source image, the protection from the decompression bombs
+const MAX_SIDE: u32 = 16_384;
+
+/// Default quality of JPEG
+const JPEG_QUALITY: u8 = 85;
+
+/// Source of the image
+enum Source {
+    #[cfg(feature = "file-disk")]
+    Path(PathBuf),
+    Data(Vec<u8>),
+}
+
+/// Step of the processing, in the order of the calls
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Resize(u32, u32),
+    Crop(u32, u32, u32, u32),
+    Thumbnail(u32, u32),
+}
+
+/// Format of the result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageType {
+    Png,
+    Jpeg(u8),
+    /// Lossless WebP
+    Webp,
+}
+
+impl ImageType {
+    /// Extension of the file
+    pub fn ext(&self) -> &'static str {
+        match self {
+            ImageType::Png => "png",
+            ImageType::Jpeg(_) => "jpg",
+            ImageType::Webp => "webp",
+        }
+    }
+
+    /// Content-Type
+    pub fn mime(&self) -> &'static str {
+        match self {
+            ImageType::Png => "image/png",
+            ImageType::Jpeg(_) => "image/jpeg",
+            ImageType::Webp => "image/webp",
+        }
+    }
+}
+
+/// Processing of the uploaded image: PNG, JPEG, GIF or WebP
+///
+/// The steps are only remembered, the image is decoded, processed and encoded once by `encode`, `save` or `store`
+/// in the blocking thread of tokio. The orientation of EXIF is applied right after the decoding,
+/// so the photos of the phones are not rotated.
+///
+/// ```ignore
+/// if let Some(file) = this.request.file.iter().find(|file| file.name == "avatar") {
+///     let image = this.image_of(file).thumbnail(200, 200).webp();
+///     image.save("upload/avatar/42.webp").await?;
+/// }
+/// ```
+///
+/// Without the format the result is PNG, or JPEG for JPEG.
+pub struct Image {
+    source: Source,
+    steps: Vec<Step>,
+    format: Option<ImageType>,
+}
+
+impl Image {
+    /// Image of the uploaded file
+    pub fn new(file: &WebFile) -> Image {
+        #[cfg(feature = "file-disk")]
+        let source = Source::Path(file.tmp.clone());
+        #[cfg(feature = "file-memory")]
+        let source = Source::Data(file.data.clone());
+        Image {
+            source,
+            steps: Vec::new(),
+            format: None,
+        }
+    }
+
+    /// Image of the file on the disk
+    #[cfg(feature = "file-disk")]
+    pub fn from_path(path: impl Into<PathBuf>) -> Image {
+        Image {
+            source: Source::Path(path.into()),
+            steps: Vec::new(),
+            format: None,
+        }
+    }
+
+    /// Image of the bytes of the file
+    pub fn from_bytes(data: Vec<u8>) -> Image {
+        Image {
+            source: Source::Data(data),
+            steps: Vec::new(),
+            format: None,
+        }
+    }
+
+    /// Reduce the image to fit into `width` x `height`, the proportions are kept, the smaller image is not enlarged
+    pub fn resize(mut self, width: u32, height: u32) -> Image {
+        self.steps.push(Step::Resize(width, height));
+        self
+    }
+
+    /// Cut the rectangle, it is limited by the borders of the image
+    pub fn crop(mut self, x: u32, y: u32, width: u32, height: u32) -> Image {
+        self.steps.push(Step::Crop(x, y, width, height));
+        self
+    }
+
+    /// Exactly `width` x `height`: the image is scaled to fill it and the center is cut
+    pub fn thumbnail(mut self, width: u32, height: u32) -> Image {
+        self.steps.push(Step::Thumbnail(width, height));
+        self
+    }
+
+    pub fn png(mut self) -> Image {
+        self.format = Some(ImageType::Png);
+        self
+    }
+
+    /// `quality` from 1 to 100
+    pub fn jpeg(mut self, quality: u8) -> Image {
+        self.format = Some(ImageType::Jpeg(quality.clamp(1, 100)));
+        self
+    }
+
+    /// Lossless WebP
+    pub fn webp(mut self) -> Image {
+        self.format = Some(ImageType::Webp);
+        self
+    }
+
+    /// Bytes of the result and its format
+    pub async fn encode(self) -> Result<(Vec<u8>, ImageType), ()> {
+        #[cfg(feature = "file-disk")]
+        let data = match self.source {
+            Source::Path(path) => match read(&path).await {
+                Ok(data) => data,
+                Err(_e) => {
+                    log!(warning, 0, "{:?}. Error: {}", path, _e);
+                    return Err(());
+                }
+            },
+            Source::Data(data) => data,
+        };
+        #[cfg(not(feature = "file-disk"))]
+        let Source::Data(data) = self.source;
+        let steps = self.steps;
+        let format = self.format;
+        match spawn_blocking(move || Image::process(&data, &steps, format)).await {
+            Ok(Ok(res)) => Ok(res),
+            Ok(Err(_e)) => {
+                log!(warning, 0, "{}", _e);
+                Err(())
+            }
+            Err(_e) => {
+                log!(warning, 0, "{}", _e);
+                Err(())
+            }
+        }
+    }
+
+    /// Write the result to the file, the extension of `path` does not change the format
+    pub async fn save(self, path: impl AsRef<Path>) -> Result<ImageType, ()> {
+        let (data, format) = self.encode().await?;
+        match write(path.as_ref(), data).await {
+            Ok(()) => Ok(format),
+            Err(_e) => {
+                log!(warning, 0, "{:?}. Error: {}", path.as_ref(), _e);
+                Err(())
+            }
+        }
+    }
+
+    /// Put the result to `Storage` with Content-Type of the format
+    #[cfg(feature = "storage")]
+    pub async fn store(self, key: &str) -> Result<ImageType, ()> {
+        let (data, format) = self.encode().await?;
+        Storage::global().put(key, &data, Some(format.mime())).await?;
+        Ok(format)
+    }
+
+    fn process(data: &[u8], steps: &[Step], format: Option<ImageType>) -> Result<(Vec<u8>, ImageType), String> {
+        let reader = ImageReader::new(Cursor::new(data)).with_guessed_format().map_err(|e| e.to_string())?;
+        let source = reader.format();
+        let mut decoder = reader.into_decoder().map_err(|e| e.to_string())?;
+        let (width, height) = decoder.dimensions();
+        if width > MAX_SIDE || height > MAX_SIDE {
+            return Err(format!("The image {}x{} is larger than {}x{}", width, height, MAX_SIDE, MAX_SIDE));
+        }
+        let orientation = decoder.orientation().map_err(|e| e.to_string())?;
+        let mut img = DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+        img.apply_orientation(orientation);
+
+        for step in steps {
+            img = match *step {
+                Step::Resize(width, height) => {
+                    if img.width() <= width && img.height() <= height {
+                        img
+                    } else {
+                        img.resize(width, height, FilterType::Lanczos3)
+                    }
+                }
+                Step::Crop(x, y, width, height) => {
+                    let x = x.min(img.width());
+                    let y = y.min(img.height());
+                    img.crop_imm(x, y, width.min(img.width() - x), height.min(img.height() - y))
+                }
+                Step::Thumbnail(width, height) => img.resize_to_fill(width, height, FilterType::Lanczos3),
+            };
+        }
+
+        let format = format.unwrap_or(match source {
+            Some(ImageFormat::Jpeg) => ImageType::Jpeg(JPEG_QUALITY),
+            _ => ImageType::Png,
+        });
+        let mut res = Vec::new();
+        match format {
+            ImageType::Png => img.write_with_encoder(PngEncoder::new(&mut res)),
+            // JPEG has no alpha channel
+            ImageType::Jpeg(quality) => {
+                DynamicImage::ImageRgb8(img.to_rgb8()).write_with_encoder(JpegEncoder::new_with_quality(&mut res, quality))
+            }
+            ImageType::Webp => DynamicImage::ImageRgba8(img.to_rgba8()).write_with_encoder(WebPEncoder::new_lossless(&mut res)),
+        }
+        .map_err(|e| e.to_string())?;
+        Ok((res, format))
+    }
+}
@@ -0,0 +1,759 @@
+use std::borrow::Cow;
+
+use percent_encoding::percent_decode_str;
+use serde_json::Value;
+
+use crate::tool::base64_decode;
+
+use super::{action::Action, request::RawData};
+
+#[cfg(any(feature = "pgsql", feature = "mssql"))]
+use crate::log;
+
+/// Mark the address invalid, the last bounce replaces the previous one
+#[cfg(feature = "pgsql")]
+const MAIL_INVALID: &str = r#"
+    INSERT INTO mail_invalid(email, kind, reason, "create") VALUES ($1, $2, $3, now())
+    ON CONFLICT (email) DO UPDATE SET kind=EXCLUDED.kind, reason=EXCLUDED.reason, "create"=EXCLUDED."create"
+"#;
+#[cfg(feature = "mssql")]
+const MAIL_INVALID: &str = "
+    UPDATE [mail_invalid] SET [kind]=@P2, [reason]=@P3, [create]=SYSDATETIMEOFFSET() WHERE [email]=@P1;
+    IF @@ROWCOUNT=0 INSERT INTO [mail_invalid]([email], [kind], [reason], [create]) VALUES (@P1, @P2, @P3, SYSDATETIMEOFFSET());
+";
+#[cfg(feature = "pgsql")]
+const MAIL_IS_INVALID: &str = "SELECT 1 FROM mail_invalid WHERE email=$1";
+#[cfg(feature = "mssql")]
+const MAIL_IS_INVALID: &str = "SELECT 1 FROM [mail_invalid] WHERE [email]=@P1";
+
+/// Max depth of the nested parts
+const MAX_DEPTH: usize = 8;
+
+/// Incoming message, the reply or the bounce, see `Action::mail_inbound`
+///
+/// # Example
+///
+/// ```ignore
+/// let mail = match this.mail_inbound() {
+///     Some(mail) => mail,
+///     None => return this.http_code(400),
+/// };
+/// this.mail_bounce(&mail).await;
+/// if let Some(id) = &mail.in_reply_to {
+///     // The reply to the own message
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct InboundMail {
+    /// Address of From
+    pub from: Option<String>,
+    /// Addresses of To
+    pub to: Vec<String>,
+    /// Addresses of Cc
+    pub cc: Vec<String>,
+    /// Address of Reply-To
+    pub reply_to: Option<String>,
+    pub subject: String,
+    pub message_id: Option<String>,
+    /// Message-ID of the message of the reply
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+    /// Headers of the message in the order of the message, the encoded words are decoded
+    pub headers: Vec<(String, String)>,
+    /// Text parts
+    pub text: Option<String>,
+    /// Html parts
+    pub html: Option<String>,
+    /// Attachments and inline files
+    pub files: Vec<InboundFile>,
+    /// Recipients of the delivery status notification, the feedback report or the webhook of the provider
+    pub bounces: Vec<Bounce>,
+}
+
+/// File of the incoming message
+#[derive(Debug, Clone)]
+pub struct InboundFile {
+    pub name: String,
+    pub mime: String,
+    /// Content-ID of the inline file without "<>"
+    pub content_id: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Failed recipient
+#[derive(Debug, Clone)]
+pub struct Bounce {
+    /// Address in lower case
+    pub email: String,
+    pub kind: BounceKind,
+    /// Status code, for example "5.1.1"
+    pub status: Option<String>,
+    /// Diagnostic of the server
+    pub reason: Option<String>,
+}
+
+/// Kind of the failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BounceKind {
+    /// Permanent failure, the address is invalid
+    Hard,
+    /// Temporary failure, for example the full mailbox
+    Soft,
+    /// The recipient marked the message as spam
+    Complaint,
+}
+
+/// Headers and body of the part
+struct Part<'a> {
+    headers: Vec<(String, String)>,
+    body: &'a [u8],
+}
+
+/// Report of the multipart/report
+#[derive(Default)]
+struct Report {
+    complaint: bool,
+    /// Recipients of the original message, for the feedback report without Original-Rcpt-To
+    original_to: Vec<String>,
+}
+
+impl InboundMail {
+    /// Parse the MIME message, RFC 5322 with MIME and the encoded words
+    ///
+    /// The delivery status notifications (multipart/report, RFC 3464) and the feedback reports (ARF, RFC 5965)
+    /// are the bounces, the text of the report is the text of the message.
+    pub fn parse(raw: &[u8]) -> Option<InboundMail> {
+        let part = InboundMail::split(raw)?;
+        if part.headers.is_empty() {
+            return None;
+        }
+        let mut mail = InboundMail::default();
+        for (name, value) in &part.headers {
+            match name.to_ascii_lowercase().as_str() {
+                "from" => mail.from = InboundMail::addresses(value).into_iter().next(),
+                "to" => mail.to.extend(InboundMail::addresses(value)),
+                "cc" => mail.cc.extend(InboundMail::addresses(value)),
+                "reply-to" => mail.reply_to = InboundMail::addresses(value).into_iter().next(),
+                "subject" => mail.subject = value.clone(),
+                "message-id" => mail.message_id = InboundMail::ids(value).into_iter().next(),
+                "in-reply-to" => mail.in_reply_to = InboundMail::ids(value).into_iter().next(),
+                "references" => mail.references = InboundMail::ids(value),
+                _ => {}
+            }
+        }
+        let mut report = Report::default();
+        mail.part(&part, 0, &mut report);
+        if report.complaint && !mail.bounces.iter().any(|bounce| bounce.kind == BounceKind::Complaint) {
+            for email in report.original_to {
+                mail.bounces.push(Bounce {
+                    email,
+                    kind: BounceKind::Complaint,
+                    status: None,
+                    reason: None,
+                });
+            }
+        }
+        mail.headers = part.headers;
+        Some(mail)
+    }
+
+    /// Webhook of the provider: the inbound JSON and the bounces of Postmark, the events of Mailgun
+    /// and the notifications of Amazon SES by SNS
+    ///
+    /// The message "SubscriptionConfirmation" of SNS is `None`, the subscription is confirmed by SubscribeURL.
+    pub fn webhook(json: &Value) -> Option<InboundMail> {
+        let str = |value: &Value| value.as_str().filter(|v| !v.is_empty()).map(|v| v.to_owned());
+        // Amazon SNS
+        if json["Type"] == "Notification" {
+            let message: Value = serde_json::from_str(json["Message"].as_str()?).ok()?;
+            return InboundMail::webhook(&message);
+        }
+        // Amazon SES
+        if let Some(kind) = json["notificationType"].as_str().or_else(|| json["eventType"].as_str()) {
+            let mut mail = match json["content"].as_str() {
+                Some(content) => InboundMail::parse(&base64_decode(content).unwrap_or_else(|| content.as_bytes().to_vec()))?,
+                None => InboundMail::default(),
+            };
+            let common = &json["mail"]["commonHeaders"];
+            if mail.from.is_none() {
+                mail.from = json["mail"]["source"].as_str().and_then(|v| InboundMail::addresses(v).into_iter().next());
+            }
+            if mail.to.is_empty() {
+                if let Some(list) = json["mail"]["destination"].as_array() {
+                    mail.to = list.iter().filter_map(|v| InboundMail::addresses(v.as_str()?).into_iter().next()).collect();
+                }
+            }
+            if mail.subject.is_empty() {
+                mail.subject = common["subject"].as_str().unwrap_or_default().to_owned();
+            }
+            if mail.message_id.is_none() {
+                mail.message_id = common["messageId"].as_str().and_then(|v| InboundMail::ids(v).into_iter().next());
+            }
+            match kind {
+                "Bounce" => {
+                    let bounce = &json["bounce"];
+                    let kind = match bounce["bounceType"].as_str() {
+                        Some("Permanent") => BounceKind::Hard,
+                        _ => BounceKind::Soft,
+                    };
+                    for item in bounce["bouncedRecipients"].as_array().into_iter().flatten() {
+                        if let Some(email) = item["emailAddress"].as_str().and_then(|v| InboundMail::addresses(v).into_iter().next()) {
+                            mail.bounces.push(Bounce {
+                                email,
+                                kind,
+                                status: str(&item["status"]),
+                                reason: str(&item["diagnosticCode"]),
+                            });
+                        }
+                    }
+                }
+                "Complaint" => {
+                    for item in json["complaint"]["complainedRecipients"].as_array().into_iter().flatten() {
+                        if let Some(email) = item["emailAddress"].as_str().and_then(|v| InboundMail::addresses(v).into_iter().next()) {
+                            mail.bounces.push(Bounce {
+                                email,
+                                kind: BounceKind::Complaint,
+                                status: None,
+                                reason: str(&json["complaint"]["complaintFeedbackType"]),
+                            });
+                        }
+                    }
+                }
+                _ => {}
+            }
+            return Some(mail);
+        }
+        // Mailgun
+        if let Some(event) = json.get("event-data") {
+            let email = event["recipient"].as_str().and_then(|v| InboundMail::addresses(v).into_iter().next())?;
+            let kind = match (event["event"].as_str(), event["severity"].as_str()) {
+                (Some("failed"), Some("permanent")) => BounceKind::Hard,
+                (Some("failed"), _) => BounceKind::Soft,
+                (Some("complained"), _) => BounceKind::Complaint,
+                _ => return Some(InboundMail::default()),
+            };
+            let status = &event["delivery-status"];
+            let headers = &event["message"]["headers"];
+            return Some(InboundMail {
+                from: headers["from"].as_str().and_then(|v| InboundMail::addresses(v).into_iter().next()),
+                to: headers["to"].as_str().map(InboundMail::addresses).unwrap_or_default(),
+                subject: headers["subject"].as_str().unwrap_or_default().to_owned(),
+                message_id: headers["message-id"].as_str().and_then(|v| InboundMail::ids(v).into_iter().next()),
+                bounces: vec![Bounce {
+                    email,
+                    kind,
+                    status: status["code"].as_i64().map(|code| code.to_string()).or_else(|| str(&status["code"])),
+                    reason: str(&status["description"]).or_else(|| str(&status["message"])),
+                }],
+                ..InboundMail::default()
+            });
+        }
+        // Postmark
+        match json["RecordType"].as_str() {
+            Some("Bounce") | Some("SpamComplaint") => {
+                let email = json["Email"].as_str().and_then(|v| InboundMail::addresses(v).into_iter().next())?;
+                let kind = match json["Type"].as_str() {
+                    Some("SpamComplaint") | Some("SpamNotification") => BounceKind::Complaint,
+                    Some("HardBounce") | Some("BadEmailAddress") | Some("ManuallyDeactivated") | Some("Blocked") => BounceKind::Hard,
+                    _ if json["RecordType"] == "SpamComplaint" => BounceKind::Complaint,
+                    _ => BounceKind::Soft,
+                };
+                Some(InboundMail {
+                    from: json["From"].as_str().and_then(|v| InboundMail::addresses(v).into_iter().next()),
+                    subject: json["Subject"].as_str().unwrap_or_default().to_owned(),
+                    message_id: str(&json["MessageID"]),
+                    bounces: vec![Bounce {
+                        email,
+                        kind,
+                        status: json["TypeCode"].as_i64().map(|code| code.to_string()),
+                        reason: str(&json["Details"]).or_else(|| str(&json["Description"])),
+                    }],
+                    ..InboundMail::default()
+                })
+            }
+            _ if json.get("FromFull").is_some() || json.get("TextBody").is_some() || json.get("HtmlBody").is_some() => {
+                let full = |value: &Value| -> Vec<String> {
+                    value
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|v| InboundMail::addresses(v["Email"].as_str()?).into_iter().next())
+                        .collect()
+                };
+                let mut mail = InboundMail {
+                    from: json["FromFull"]["Email"]
+                        .as_str()
+                        .or_else(|| json["From"].as_str())
+                        .and_then(|v| InboundMail::addresses(v).into_iter().next()),
+                    to: full(&json["ToFull"]),
+                    cc: full(&json["CcFull"]),
+                    reply_to: json["ReplyTo"].as_str().and_then(|v| InboundMail::addresses(v).into_iter().next()),
+                    subject: json["Subject"].as_str().unwrap_or_default().to_owned(),
+                    text: str(&json["TextBody"]),
+                    html: str(&json["HtmlBody"]),
+                    ..InboundMail::default()
+                };
+                for header in json["Headers"].as_array().into_iter().flatten() {
+                    if let (Some(name), Some(value)) = (header["Name"].as_str(), header["Value"].as_str()) {
+                        match name.to_ascii_lowercase().as_str() {
+                            "message-id" => mail.message_id = InboundMail::ids(value).into_iter().next(),
+                            "in-reply-to" => mail.in_reply_to = InboundMail::ids(value).into_iter().next(),
+                            "references" => mail.references = InboundMail::ids(value),
+                            _ => {}
+                        }
+                        mail.headers.push((name.to_owned(), value.to_owned()));
+                    }
+                }
+                if mail.message_id.is_none() {
+                    mail.message_id = str(&json["MessageID"]);
+                }
+                for file in json["Attachments"].as_array().into_iter().flatten() {
+                    if let Some(data) = file["Content"].as_str().and_then(base64_decode) {
+                        mail.files.push(InboundFile {
+                            name: file["Name"].as_str().unwrap_or_default().to_owned(),
+                            mime: file["ContentType"].as_str().unwrap_or("application/octet-stream").to_owned(),
+                            content_id: file["ContentID"].as_str().filter(|v| !v.is_empty()).map(|v| v.trim_matches(['<', '>']).to_owned()),
+                            data,
+                        });
+                    }
+                }
+                Some(mail)
+            }
+            _ => None,
+        }
+    }
+
+    /// Headers and body, the folded headers are joined
+    fn split(raw: &[u8]) -> Option<Part<'_>> {
+        let (head, body) = match InboundMail::find(raw, b"\r\n\r\n") {
+            Some(pos) => (&raw[..pos], &raw[pos + 4..]),
+            None => match InboundMail::find(raw, b"\n\n") {
+                Some(pos) => (&raw[..pos], &raw[pos + 2..]),
+                None if raw.starts_with(b"\r\n") => (&raw[..0], &raw[2..]),
+                None if raw.starts_with(b"\n") => (&raw[..0], &raw[1..]),
+                None => (raw, &raw[raw.len()..]),
+            },
+        };
+        let head = String::from_utf8_lossy(head);
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in head.lines() {
+            if line.starts_with([' ', '\t']) {
+                match headers.last_mut() {
+                    Some((_, value)) => {
+                        value.push(' ');
+                        value.push_str(line.trim());
+                    }
+                    None => return None,
+                }
+            } else if let Some((name, value)) = line.split_once(':') {
+                if name.is_empty() || name.contains(' ') {
+                    return None;
+                }
+                headers.push((name.to_owned(), value.trim().to_owned()));
+            } else if !line.trim().is_empty() {
+                return None;
+            }
+        }
+        for (_, value) in headers.iter_mut() {
+            if value.contains("=?") {
+                *value = InboundMail::words(value);
+            }
+        }
+        Some(Part { headers, body })
+    }
+
+    /// Text, html, files and reports of the part and its nested parts
+    fn part(&mut self, part: &Part<'_>, depth: usize, report: &mut Report) {
+        let content_type = InboundMail::header(&part.headers, "content-type").unwrap_or("text/plain");
+        let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        let disposition = InboundMail::header(&part.headers, "content-disposition").unwrap_or_default();
+        let name = InboundMail::param(disposition, "filename").or_else(|| InboundMail::param(content_type, "name"));
+        let attachment = disposition.trim_start().to_ascii_lowercase().starts_with("attachment") || name.is_some();
+
+        if let Some(boundary) = mime.starts_with("multipart/").then(|| InboundMail::param(content_type, "boundary")).flatten() {
+            if depth >= MAX_DEPTH {
+                return;
+            }
+            if mime == "multipart/report"
+                && InboundMail::param(content_type, "report-type").is_some_and(|v| v.eq_ignore_ascii_case("feedback-report"))
+            {
+                report.complaint = true;
+            }
+            for body in InboundMail::parts(part.body, &boundary) {
+                if let Some(child) = InboundMail::split(body) {
+                    self.part(&child, depth + 1, report);
+                }
+            }
+            return;
+        }
+        let data = InboundMail::decode(part.body, InboundMail::header(&part.headers, "content-transfer-encoding"));
+        match mime.as_str() {
+            "message/delivery-status" | "message/global-delivery-status" => self.delivery_status(&data),
+            "message/feedback-report" => {
+                report.complaint = true;
+                self.feedback_report(&data);
+            }
+            "message/rfc822" | "text/rfc822-headers" | "message/global" | "message/global-headers" if depth > 0 => {
+                if let Some(original) = InboundMail::split(&data) {
+                    if let Some(to) = InboundMail::header(&original.headers, "to") {
+                        report.original_to.extend(InboundMail::addresses(to));
+                    }
+                }
+                if mime.starts_with("message/") && !attachment {
+                    self.files.push(InboundFile {
+                        name: "message.eml".to_owned(),
+                        mime,
+                        content_id: None,
+                        data,
+                    });
+                }
+            }
+            "text/plain" | "text/html" if !attachment => {
+                let text = InboundMail::charset(&data, InboundMail::param(content_type, "charset").as_deref());
+                let target = match mime.as_str() {
+                    "text/html" => &mut self.html,
+                    _ => &mut self.text,
+                };
+                match target {
+                    Some(target) => {
+                        target.push('\n');
+                        target.push_str(&text);
+                    }
+                    None => *target = Some(text),
+                }
+            }
+            _ => self.files.push(InboundFile {
+                name: name.unwrap_or_default(),
+                mime,
+                content_id: InboundMail::header(&part.headers, "content-id").map(|v| v.trim().trim_matches(['<', '>']).to_owned()),
+                data,
+            }),
+        }
+    }
+
+    /// Recipients of the delivery status notification, RFC 3464
+    fn delivery_status(&mut self, data: &[u8]) {
+        let text = String::from_utf8_lossy(data).replace("\r\n", "\n");
+        // The first group is the fields of the message, the next ones are the fields of the recipients
+        for group in text.split("\n\n").skip(1) {
+            let fields = match InboundMail::split(format!("{}\n\n", group.trim_matches('\n')).as_bytes()) {
+                Some(part) => part.headers,
+                None => continue,
+            };
+            let email = InboundMail::header(&fields, "final-recipient")
+                .or_else(|| InboundMail::header(&fields, "original-recipient"))
+                .map(|v| v.split_once(';').map(|(_, v)| v).unwrap_or(v))
+                .and_then(|v| InboundMail::addresses(v).into_iter().next());
+            let action = InboundMail::header(&fields, "action").unwrap_or_default().trim().to_ascii_lowercase();
+            let status = InboundMail::header(&fields, "status").map(|v| v.split_whitespace().next().unwrap_or_default().to_owned());
+            let kind = match action.as_str() {
+                "failed" if status.as_deref().is_none_or(|v| v.starts_with('5')) => BounceKind::Hard,
+                "failed" | "delayed" => BounceKind::Soft,
+                _ => continue,
+            };
+            if let Some(email) = email {
+                self.bounces.push(Bounce {
+                    email,
+                    kind,
+                    status,
+                    reason: InboundMail::header(&fields, "diagnostic-code")
+                        .map(|v| v.split_once(';').map(|(_, v)| v).unwrap_or(v).trim().to_owned()),
+                });
+            }
+        }
+    }
+
+    /// Recipient of the feedback report, RFC 5965
+    fn feedback_report(&mut self, data: &[u8]) {
+        let fields = match InboundMail::split(&[data, b"\n\n"].concat()) {
+            Some(part) => part.headers,
+            None => return,
+        };
+        let reason = InboundMail::header(&fields, "feedback-type").map(|v| v.trim().to_owned());
+        for (name, value) in &fields {
+            if name.eq_ignore_ascii_case("original-rcpt-to") || name.eq_ignore_ascii_case("removal-recipient") {
+                for email in InboundMail::addresses(value) {
+                    self.bounces.push(Bounce {
+                        email,
+                        kind: BounceKind::Complaint,
+                        status: None,
+                        reason: reason.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Bodies of the parts of the multipart between the lines "--boundary"
+    fn parts<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+        let delimiter = format!("--{}", boundary);
+        let mut list = Vec::new();
+        let mut start = None;
+        let mut pos = 0;
+        while pos <= body.len() {
+            let end = InboundMail::find(&body[pos..], b"\n").map(|i| pos + i).unwrap_or(body.len());
+            let line = &body[pos..end];
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.starts_with(delimiter.as_bytes()) {
+                let rest = &line[delimiter.len()..];
+                if let Some(start) = start {
+                    // The line break before the delimiter belongs to it
+                    let mut stop = pos;
+                    if stop > start && body[stop - 1] == b'\n' {
+                        stop -= 1;
+                        if stop > start && body[stop - 1] == b'\r' {
+                            stop -= 1;
+                        }
+                    }
+                    list.push(&body[start..stop]);
+                }
+                if rest.starts_with(b"--") {
+                    return list;
+                }
+                if rest.iter().all(|b| b.is_ascii_whitespace()) {
+                    start = Some((end + 1).min(body.len()));
+                }
+            }
+            pos = end + 1;
+        }
+        if let Some(start) = start {
+            list.push(&body[start..]);
+        }
+        list
+    }
+
+    /// Body by Content-Transfer-Encoding
+    fn decode(body: &[u8], encoding: Option<&str>) -> Vec<u8> {
+        match encoding.map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+            Some("base64") => base64_decode(&String::from_utf8_lossy(body)).unwrap_or_default(),
+            Some("quoted-printable") => InboundMail::quoted(body, false),
+            _ => body.to_vec(),
+        }
+    }
+
+    /// Quoted-printable, "_" is the space in the encoded word
+    fn quoted(data: &[u8], word: bool) -> Vec<u8> {
+        let mut res = Vec::with_capacity(data.len());
+        let mut i = 0;
+        while i < data.len() {
+            match data[i] {
+                b'=' if data.get(i + 1) == Some(&b'\r') && data.get(i + 2) == Some(&b'\n') => i += 3,
+                b'=' if data.get(i + 1) == Some(&b'\n') => i += 2,
+                b'=' => match data.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()) {
+                    Some(byte) => {
+                        res.push(byte);
+                        i += 3;
+                    }
+                    None => {
+                        res.push(b'=');
+                        i += 1;
+                    }
+                },
+                b'_' if word => {
+                    res.push(b' ');
+                    i += 1;
+                }
+                byte => {
+                    res.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        res
+    }
+
+    /// Text by the charset, UTF-8 and Latin-1, the others as UTF-8 with the replacement
+    fn charset(data: &[u8], charset: Option<&str>) -> String {
+        match charset.map(|v| v.to_ascii_lowercase()).as_deref() {
+            Some("iso-8859-1" | "latin1" | "windows-1252" | "cp1252") => data.iter().map(|b| *b as char).collect(),
+            _ => String::from_utf8_lossy(data).to_string(),
+        }
+    }
+
+    /// Encoded words "=?charset?B?...?=" and "=?charset?Q?...?=" of the header, RFC 2047
+    fn words(value: &str) -> String {
+        let mut res = String::with_capacity(value.len());
+        let mut rest = value;
+        let mut encoded = false;
+        while let Some(start) = rest.find("=?") {
+            let word = rest[start + 2..].splitn(3, '?').collect::<Vec<_>>();
+            let decoded = match word[..] {
+                [charset, kind, text] => text.find("?=").and_then(|end| {
+                    let data = match kind {
+                        "B" | "b" => base64_decode(&text[..end])?,
+                        "Q" | "q" => InboundMail::quoted(&text.as_bytes()[..end], true),
+                        _ => return None,
+                    };
+                    let charset = charset.split('*').next().unwrap_or_default();
+                    Some((InboundMail::charset(&data, Some(charset)), start + 2 + charset.len() + kind.len() + 2 + end + 2))
+                }),
+                _ => None,
+            };
+            match decoded {
+                Some((text, len)) => {
+                    let between = &rest[..start];
+                    // The spaces between the encoded words are skipped
+                    if !(encoded && between.trim().is_empty()) {
+                        res.push_str(between);
+                    }
+                    res.push_str(&text);
+                    rest = &rest[len..];
+                    encoded = true;
+                }
+                None => {
+                    res.push_str(&rest[..start + 2]);
+                    rest = &rest[start + 2..];
+                    encoded = false;
+                }
+            }
+        }
+        res.push_str(rest);
+        res
+    }
+
+    /// Addresses of the list "Name <user@example.com>, user2@example.com" in lower case
+    fn addresses(value: &str) -> Vec<String> {
+        let mut list = Vec::new();
+        let mut item = String::new();
+        let mut quoted = false;
+        let mut angle = false;
+        for c in value.chars().chain([',']) {
+            match c {
+                '"' if !angle => quoted = !quoted,
+                '<' if !quoted => {
+                    angle = true;
+                    item.clear();
+                }
+                '>' if angle => {
+                    angle = false;
+                    item.push(' ');
+                }
+                ',' | ';' if !quoted && !angle => {
+                    let email = item.split_whitespace().find(|part| part.contains('@')).map(|v| v.trim_matches(['(', ')', '"']));
+                    if let Some(email) = email.filter(|v| v.len() > 2) {
+                        list.push(email.to_lowercase());
+                    }
+                    item.clear();
+                }
+                _ if quoted => {}
+                _ => item.push(c),
+            }
+        }
+        list
+    }
+
+    /// Message-ID of the list "<id1> <id2>" without "<>"
+    fn ids(value: &str) -> Vec<String> {
+        let list: Vec<String> = value
+            .split('<')
+            .skip(1)
+            .filter_map(|part| part.split_once('>').map(|(id, _)| id.trim().to_owned()))
+            .filter(|id| !id.is_empty())
+            .collect();
+        if list.is_empty() && !value.trim().is_empty() {
+            return vec![value.trim().to_owned()];
+        }
+        list
+    }
+
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    /// Parameter of the header, "name*" of RFC 2231 is decoded
+    fn param(value: &str, name: &str) -> Option<String> {
+        let mut extended = String::new();
+        for item in value.split(';').skip(1) {
+            let (key, val) = match item.split_once('=') {
+                Some((key, val)) => (key.trim().to_ascii_lowercase(), val.trim()),
+                None => continue,
+            };
+            if key == name {
+                return Some(val.trim_matches('"').to_owned());
+            }
+            // name*=utf-8''file%20name.pdf, name*0*=..., name*1*=...
+            if let Some(index) = key.strip_prefix(name).and_then(|rest| rest.strip_prefix('*')) {
+                if index.is_empty() || index.ends_with('*') || index.chars().all(|c| c.is_ascii_digit()) {
+                    let val = val.trim_matches('"');
+                    let parts: Vec<&str> = val.splitn(3, '\'').collect();
+                    let val = match (extended.is_empty(), parts.as_slice()) {
+                        (true, [_, _, text]) => text,
+                        _ => val,
+                    };
+                    extended.push_str(val);
+                }
+            }
+        }
+        if extended.is_empty() {
+            return None;
+        }
+        Some(match percent_decode_str(&extended).decode_utf8_lossy() {
+            Cow::Borrowed(str) => str.to_owned(),
+            Cow::Owned(str) => str,
+        })
+    }
+
+    fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+        data.windows(needle.len()).position(|window| window == needle)
+    }
+}
+
+impl Action {
+    /// Incoming message of the request
+    ///
+    /// The body of the request is the MIME message or the JSON of the webhook, see `InboundMail::webhook`.
+    /// The form of the route "forward" of Mailgun has the MIME message in the field "body-mime".
+    pub fn mail_inbound(&self) -> Option<InboundMail> {
+        if let Some(mime) = self.request.input.post.get("body-mime") {
+            return InboundMail::parse(mime.as_bytes());
+        }
+        let raw = match self.request.input.raw.as_ref() {
+            RawData::Raw(raw) => raw,
+            RawData::None => return None,
+        };
+        let json = self.request.content_type.as_deref().is_some_and(|v| v.contains("json"))
+            || raw.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{');
+        match json {
+            true => InboundMail::webhook(&serde_json::from_slice(raw).ok()?),
+            false => InboundMail::parse(raw),
+        }
+    }
+
+    /// Mark the addresses of the hard bounces and the complaints of the message invalid in the table `mail_invalid`
+    ///
+    /// Returns the number of the marked addresses, the soft bounces are skipped.
+    #[cfg(any(feature = "pgsql", feature = "mssql"))]
+    pub async fn mail_bounce(&self, mail: &InboundMail) -> usize {
+        let mut count = 0;
+        for bounce in &mail.bounces {
+            let kind = match bounce.kind {
+                BounceKind::Hard => "hard",
+                BounceKind::Complaint => "complaint",
+                BounceKind::Soft => continue,
+            };
+            let reason = match (&bounce.status, &bounce.reason) {
+                (Some(status), Some(reason)) => format!("{} {}", status, reason),
+                (Some(text), None) | (None, Some(text)) => text.clone(),
+                (None, None) => String::new(),
+            };
+            let email = bounce.email.to_lowercase();
+            if self.db.execute(MAIL_INVALID, &[&email.as_str(), &kind, &reason.as_str()]).await.is_some() {
+                count += 1;
+            } else {
+                log!(warning, 0, "mail_invalid {}", email);
+            }
+        }
+        count
+    }
+
+    /// The address is marked invalid by `mail_bounce`
+    #[cfg(any(feature = "pgsql", feature = "mssql"))]
+    pub async fn mail_invalid(&self, email: &str) -> bool {
+        let email = email.trim().to_lowercase();
+        #[cfg(feature = "row-data")]
+        let rows = self.db.query(MAIL_IS_INVALID, &[&email.as_str()], false).await;
+        #[cfg(feature = "row-native")]
+        let rows = self.db.query(MAIL_IS_INVALID, &[&email.as_str()]).await;
+        rows.is_some_and(|rows| !rows.is_empty())
+    }
+}
@@ -0,0 +1,93 @@
+use ring::{
+    digest::{digest, SHA256},
+    hmac,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::tool::{base64_url_decode, base64_url_encode};
+
+use super::clock::Clock;
+
+/// Header of the token, only HS256 is supported
+const JWT_HEADER: &str = r#"{"alg":"HS256","typ":"JWT"}"#;
+
+/// Claims of the JSON Web Token
+///
+/// `sub` and `role` are mapped to `session.user_id` and `session.role_id` of the API request, `sub` only if it is a number.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwtClaims {
+    /// Subject, the string of RFC 7519, usually the user id
+    pub sub: String,
+    /// Role id
+    pub role: usize,
+    /// Issued at, unix time
+    pub iat: u64,
+    /// Expiration time, unix time
+    pub exp: u64,
+    /// Other claims of the application
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+}
+
+impl JwtClaims {
+    /// Claims of the user valid for `ttl` seconds
+    pub fn new(user_id: usize, role_id: usize, ttl: u64) -> JwtClaims {
+        let iat = Clock::unix();
+        JwtClaims {
+            sub: user_id.to_string(),
+            role: role_id,
+            iat,
+            exp: iat + ttl,
+            extra: Map::new(),
+        }
+    }
+}
+
+/// HS256 JSON Web Token, the key is derived from the salt
+pub(crate) struct Jwt;
+
+impl Jwt {
+    /// Signed token "header.claims.signature"
+    pub(crate) fn encode(claims: &JwtClaims, salt: &str) -> Option<String> {
+        let claims = serde_json::to_vec(claims).ok()?;
+        let data = format!("{}.{}", base64_url_encode(JWT_HEADER.as_bytes()), base64_url_encode(&claims));
+        let tag = hmac::sign(&Jwt::key(salt), data.as_bytes());
+        Some(format!("{}.{}", data, base64_url_encode(tag.as_ref())))
+    }
+
+    /// Check the signature, the algorithm and the expiration time of the token
+    pub(crate) fn decode(token: &str, salt: &str) -> Option<JwtClaims> {
+        let (data, tag) = token.rsplit_once('.')?;
+        hmac::verify(&Jwt::key(salt), data.as_bytes(), &base64_url_decode(tag)?).ok()?;
+        let (header, claims) = data.split_once('.')?;
+        let header: JwtHeader = serde_json::from_slice(&base64_url_decode(header)?).ok()?;
+        if header.alg != "HS256" {
+            return None;
+        }
+        let claims: JwtClaims = serde_json::from_slice(&base64_url_decode(claims)?).ok()?;
+        if claims.exp <= Clock::unix() {
+            return None;
+        }
+        Some(claims)
+    }
+
+    /// Token of the `Authorization: Bearer` header
+    pub(crate) fn bearer(header: &str) -> Option<&str> {
+        let (scheme, token) = header.trim().split_once(' ')?;
+        if scheme.eq_ignore_ascii_case("Bearer") {
+            Some(token.trim())
+        } else {
+            None
+        }
+    }
+
+    fn key(salt: &str) -> hmac::Key {
+        hmac::Key::new(hmac::HMAC_SHA256, digest(&SHA256, format!("jwt:{}", salt).as_bytes()).as_ref())
+    }
+}
@@ -1,12 +1,13 @@
 use std::{
     collections::{hash_map::Entry, HashMap},
+    fmt::Display,
     fs::{read_dir, read_to_string},
     path::PathBuf,
     sync::Arc,
 };
 
 #[cfg(feature = "lang-reload")]
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 #[cfg(feature = "lang-reload")]
 use std::time::SystemTime;
@@ -46,6 +47,44 @@ pub struct LangItem {
     pub index: usize,
 }
 
+/// Plural category of CLDR
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Plural {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+/// Plural rule of the language for the integer numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum PluralRule {
+    /// ja, zh, ko, vi, th, id, ms
+    None,
+    /// en, de, es, it, nl, sv and others, 1 is one
+    #[default]
+    One,
+    /// fr, pt, 0 and 1 are one
+    ZeroOne,
+    /// uk, ru, be
+    EastSlavic,
+    /// hr, sr, bs
+    SouthSlavic,
+    Polish,
+    /// cs, sk
+    Czech,
+    Lithuanian,
+    Latvian,
+    Romanian,
+    Slovenian,
+    Arabic,
+    Hebrew,
+    Irish,
+    Welsh,
+}
+
 struct LangFile {
     path: PathBuf,
     module: String,
@@ -67,9 +106,17 @@ type LangList = HashMap<usize, HashMap<i64, HashMap<i64, Arc<HashMap<i64, String
 #[cfg(feature = "lang-reload")]
 static WRLOCK: OnceCell<WrLock> = OnceCell::const_new();
 
+/// Number of the reloads of the translations
+#[cfg(feature = "lang-reload")]
+pub(crate) static RELOADS: AtomicU64 = AtomicU64::new(0);
+
 pub(crate) struct LangParam {
     pub root: Arc<PathBuf>,
     pub default_lang: Arc<String>,
+    /// Language of the url prefix "/en/...", [web] lang_prefix
+    pub prefix: bool,
+    /// Language of the header Accept-Language, [web] lang_accept
+    pub accept: bool,
     #[cfg(feature = "session-db")]
     pub db: Arc<DB>,
 }
@@ -92,6 +139,10 @@ pub(crate) struct Lang {
     /// Path to langs' files
     #[cfg(feature = "lang-reload")]
     root: Arc<PathBuf>,
+    /// Language of the url prefix "/en/..."
+    pub prefix: bool,
+    /// Language of the header Accept-Language without the language of the session
+    accept: bool,
 
     codes: HashMap<String, usize>,
 }
@@ -134,6 +185,14 @@ impl Lang {
     /// To get a translation, it is enough to set the `this.lang("contact")` function,
     /// which will return the corresponding translation.<br />
     /// If no translation is found, the key will be returned.
+    ///
+    /// ## Interpolation and plural forms:
+    ///
+    /// The placeholders `{name}` are replaced by `this.lang_fmt("greeting", &[("name", &user)])`.<br />
+    /// The table of the key has the plural forms of CLDR "zero", "one", "two", "few", "many" and "other",
+    /// `this.lang_plural("apples", 5)` selects the form by the language and replaces `{n}`:<br />
+    /// apples = { one = "{n} apple", other = "{n} apples" }<br />
+    /// The form is also the key "apples.one", `this.lang("apples")` returns the form "other".
     pub async fn new(param: LangParam) -> Result<Lang, ()> {
         #[cfg(feature = "lang-reload")]
         if WRLOCK.set(WrLock::default()).is_err() {
@@ -187,6 +246,8 @@ impl Lang {
             hash: 0,
             #[cfg(feature = "lang-reload")]
             root: Arc::new(root),
+            prefix: param.prefix,
+            accept: param.accept,
             codes,
         };
         lang.load(files).await;
@@ -225,25 +286,39 @@ impl Lang {
                             }
                         };
                         for (key, value) in text {
-                            if let Value::String(val) = value {
-                                let l1 = match list.entry(*id) {
-                                    Entry::Vacant(v) => v.insert(HashMap::new()),
-                                    Entry::Occupied(o) => o.into_mut(),
-                                };
-                                // module
-                                let l2 = match l1.entry(fnv1a_64(file.module.as_bytes())) {
-                                    Entry::Vacant(v) => v.insert(HashMap::new()),
-                                    Entry::Occupied(o) => o.into_mut(),
-                                };
-                                // class
-                                let l3 = match l2.entry(fnv1a_64(file.class.as_bytes())) {
-                                    Entry::Vacant(v) => v.insert(HashMap::new()),
-                                    Entry::Occupied(o) => o.into_mut(),
-                                };
-                                l3.insert(fnv1a_64(key.as_bytes()), val);
-                            } else {
-                                log!(warning, 0, "{:?} {} ", file.path, value);
-                                continue;
+                            let l1 = match list.entry(*id) {
+                                Entry::Vacant(v) => v.insert(HashMap::new()),
+                                Entry::Occupied(o) => o.into_mut(),
+                            };
+                            // module
+                            let l2 = match l1.entry(fnv1a_64(file.module.as_bytes())) {
+                                Entry::Vacant(v) => v.insert(HashMap::new()),
+                                Entry::Occupied(o) => o.into_mut(),
+                            };
+                            // class
+                            let l3 = match l2.entry(fnv1a_64(file.class.as_bytes())) {
+                                Entry::Vacant(v) => v.insert(HashMap::new()),
+                                Entry::Occupied(o) => o.into_mut(),
+                            };
+                            match value {
+                                Value::String(val) => {
+                                    l3.insert(fnv1a_64(key.as_bytes()), val);
+                                }
+                                // Plural forms as the keys "key.one", "key.other"
+                                Value::Table(forms) => {
+                                    for (form, val) in forms {
+                                        match val {
+                                            Value::String(val) => {
+                                                if form == "other" {
+                                                    l3.entry(fnv1a_64(key.as_bytes())).or_insert_with(|| val.clone());
+                                                }
+                                                l3.insert(fnv1a_64(format!("{}.{}", key, form).as_bytes()), val);
+                                            }
+                                            _val => log!(warning, 0, "{:?} {}.{} {}", file.path, key, form, _val),
+                                        }
+                                    }
+                                }
+                                _value => log!(warning, 0, "{:?} {} ", file.path, _value),
                             }
                         }
                     }
@@ -374,6 +449,63 @@ impl Lang {
         vec
     }
 
+    /// Language of the prefix of the url and the url without the prefix, "/en/blog?page=2" is "/blog?page=2"
+    pub(crate) fn prefix(&self, url: &str) -> Option<(usize, String)> {
+        if !self.prefix {
+            return None;
+        }
+        let rest = url.strip_prefix('/')?;
+        let end = rest.find(['/', '?']).unwrap_or(rest.len());
+        let id = *self.codes.get(&rest[..end])?;
+        let rest = &rest[end..];
+        match rest.starts_with('/') {
+            true => Some((id, rest.to_owned())),
+            false => Some((id, format!("/{}", rest))),
+        }
+    }
+
+    /// Language of the header "uk-UA,uk;q=0.9,en;q=0.8" by the weight, only the known languages
+    pub(crate) fn accept(&self, header: &str) -> Option<usize> {
+        if !self.accept {
+            return None;
+        }
+        let mut list: Vec<(f32, usize)> = header
+            .split(',')
+            .filter_map(|item| {
+                let mut parts = item.split(';');
+                let code = parts.next()?.trim().split('-').next()?.to_ascii_lowercase();
+                let q =
+                    parts.find_map(|part| part.trim().strip_prefix("q=").map(|q| q.trim().parse::<f32>().unwrap_or(0.0))).unwrap_or(1.0);
+                let id = *self.codes.get(&code)?;
+                (q > 0.0).then_some((q, id))
+            })
+            .collect();
+        // The stable sort keeps the order of the header for the same weight
+        list.sort_by(|a, b| b.0.total_cmp(&a.0));
+        list.first().map(|(_, id)| *id)
+    }
+
+    /// Replace the placeholders `{name}` of the text, the unknown placeholders are kept
+    pub(crate) fn format(text: &str, args: &[(&str, &dyn Display)]) -> String {
+        let mut res = String::with_capacity(text.len() + 16);
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            res.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = match rest.find('}') {
+                Some(end) => end,
+                None => break,
+            };
+            match args.iter().find(|(name, _)| *name == &rest[1..end]) {
+                Some((_, value)) => res.push_str(&value.to_string()),
+                None => res.push_str(&rest[..=end]),
+            }
+            rest = &rest[end + 1..];
+        }
+        res.push_str(rest);
+        res
+    }
+
     /// Check system time
     #[cfg(feature = "lang-reload")]
     pub(crate) async fn check_time(&self) -> bool {
@@ -413,7 +545,8 @@ impl Lang {
             if reload {
                 let root = Arc::clone(&lang.read().await.root);
                 let files = Lang::get_files(root).await;
-                lang.write().await.load(files).await
+                lang.write().await.load(files).await;
+                RELOADS.fetch_add(1, Ordering::Relaxed);
             }
             wr.lock.store(false, Ordering::SeqCst);
             wr.notify.notify_waiters();
@@ -636,3 +769,123 @@ impl Lang {
         ]
     }
 }
+
+impl Plural {
+    /// Suffix of the key of the form, for example ".few"
+    pub(crate) fn suffix(&self) -> &'static str {
+        match self {
+            Plural::Zero => ".zero",
+            Plural::One => ".one",
+            Plural::Two => ".two",
+            Plural::Few => ".few",
+            Plural::Many => ".many",
+            Plural::Other => ".other",
+        }
+    }
+}
+
+impl PluralRule {
+    /// Rule by the code ISO 639-1 of the language
+    pub(crate) fn new(code: &str) -> PluralRule {
+        match code {
+            "ja" | "zh" | "ko" | "vi" | "th" | "id" | "ms" | "lo" | "my" | "km" => PluralRule::None,
+            "fr" | "pt" => PluralRule::ZeroOne,
+            "uk" | "ru" | "be" => PluralRule::EastSlavic,
+            "hr" | "sr" | "bs" => PluralRule::SouthSlavic,
+            "pl" => PluralRule::Polish,
+            "cs" | "sk" => PluralRule::Czech,
+            "lt" => PluralRule::Lithuanian,
+            "lv" => PluralRule::Latvian,
+            "ro" | "mo" => PluralRule::Romanian,
+            "sl" => PluralRule::Slovenian,
+            "ar" => PluralRule::Arabic,
+            "he" => PluralRule::Hebrew,
+            "ga" => PluralRule::Irish,
+            "cy" => PluralRule::Welsh,
+            _ => PluralRule::One,
+        }
+    }
+
+    /// Category of the integer number
+    pub(crate) fn category(&self, n: i64) -> Plural {
+        let n = n.unsigned_abs();
+        let (n10, n100) = (n % 10, n % 100);
+        match self {
+            PluralRule::None => Plural::Other,
+            PluralRule::One => match n {
+                1 => Plural::One,
+                _ => Plural::Other,
+            },
+            PluralRule::ZeroOne => match n {
+                0 | 1 => Plural::One,
+                _ => Plural::Other,
+            },
+            PluralRule::EastSlavic | PluralRule::SouthSlavic => match (n10, n100) {
+                (1, n100) if n100 != 11 => Plural::One,
+                (2..=4, n100) if !(12..=14).contains(&n100) => Plural::Few,
+                _ if *self == PluralRule::EastSlavic => Plural::Many,
+                _ => Plural::Other,
+            },
+            PluralRule::Polish => match (n, n10, n100) {
+                (1, _, _) => Plural::One,
+                (_, 2..=4, n100) if !(12..=14).contains(&n100) => Plural::Few,
+                _ => Plural::Many,
+            },
+            PluralRule::Czech => match n {
+                1 => Plural::One,
+                2..=4 => Plural::Few,
+                _ => Plural::Other,
+            },
+            PluralRule::Lithuanian => match (n10, n100) {
+                (_, 11..=19) => Plural::Other,
+                (1, _) => Plural::One,
+                (2..=9, _) => Plural::Few,
+                _ => Plural::Other,
+            },
+            PluralRule::Latvian => match (n10, n100) {
+                (0, _) | (_, 11..=19) => Plural::Zero,
+                (1, _) => Plural::One,
+                _ => Plural::Other,
+            },
+            PluralRule::Romanian => match (n, n100) {
+                (1, _) => Plural::One,
+                (0, _) | (_, 2..=19) => Plural::Few,
+                _ => Plural::Other,
+            },
+            PluralRule::Slovenian => match n100 {
+                1 => Plural::One,
+                2 => Plural::Two,
+                3 | 4 => Plural::Few,
+                _ => Plural::Other,
+            },
+            PluralRule::Arabic => match (n, n100) {
+                (0, _) => Plural::Zero,
+                (1, _) => Plural::One,
+                (2, _) => Plural::Two,
+                (_, 3..=10) => Plural::Few,
+                (_, 11..=99) => Plural::Many,
+                _ => Plural::Other,
+            },
+            PluralRule::Hebrew => match n {
+                1 => Plural::One,
+                2 => Plural::Two,
+                _ => Plural::Other,
+            },
+            PluralRule::Irish => match n {
+                1 => Plural::One,
+                2 => Plural::Two,
+                3..=6 => Plural::Few,
+                7..=10 => Plural::Many,
+                _ => Plural::Other,
+            },
+            PluralRule::Welsh => match n {
+                0 => Plural::Zero,
+                1 => Plural::One,
+                2 => Plural::Two,
+                3 => Plural::Few,
+                6 => Plural::Many,
+                _ => Plural::Other,
+            },
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use chrono::{DateTime, Datelike, TimeZone};
+use std::fmt::Display;
+
+/// Style of the date for `Action::format_date` and the filters of the templates
+///
+/// | Style       | en                        | uk                      |
+/// |-------------|---------------------------|-------------------------|
+/// | `Short`     | 12/31/2024                | 31.12.2024              |
+/// | `Long`      | December 31, 2024         | 31 грудня 2024 р.       |
+/// | `Time`      | 2:05 PM                   | 14:05                   |
+/// | `ShortTime` | 12/31/2024 2:05 PM        | 31.12.2024 14:05        |
+/// | `LongTime`  | December 31, 2024 2:05 PM | 31 грудня 2024 р. 14:05 |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateStyle {
+    #[default]
+    Short,
+    Long,
+    Time,
+    ShortTime,
+    LongTime,
+}
+
+/// Rules of the formatting of the dates and the numbers of the language
+///
+/// The unknown languages are formatted as English.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Locale {
+    /// Code ISO 639-1 of the rules
+    pub code: &'static str,
+    decimal: &'static str,
+    group: &'static str,
+    /// Format of chrono
+    short: &'static str,
+    /// "{day}", "{month}" and "{year}", the month is from `months`
+    long: &'static str,
+    /// Format of chrono
+    time: &'static str,
+    /// Names of the months in the long date, the genitive case for the Slavic languages
+    months: &'static [&'static str; 12],
+    /// The symbol of the currency is before the number
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    prefix: bool,
+}
+
+const EN: Locale = Locale {
+    code: "en",
+    decimal: ".",
+    group: ",",
+    short: "%m/%d/%Y",
+    long: "{month} {day}, {year}",
+    time: "%-I:%M %p",
+    months: &["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"],
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    prefix: true,
+};
+
+const UK: Locale = Locale {
+    code: "uk",
+    decimal: ",",
+    group: "\u{a0}",
+    short: "%d.%m.%Y",
+    long: "{day} {month} {year} р.",
+    time: "%H:%M",
+    months: &["січня", "лютого", "березня", "квітня", "травня", "червня", "липня", "серпня", "вересня", "жовтня", "листопада", "грудня"],
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    prefix: false,
+};
+
+const RU: Locale = Locale {
+    code: "ru",
+    decimal: ",",
+    group: "\u{a0}",
+    short: "%d.%m.%Y",
+    long: "{day} {month} {year} г.",
+    time: "%H:%M",
+    months: &["января", "февраля", "марта", "апреля", "мая", "июня", "июля", "августа", "сентября", "октября", "ноября", "декабря"],
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    prefix: false,
+};
+
+const DE: Locale = Locale {
+    code: "de",
+    decimal: ",",
+    group: ".",
+    short: "%d.%m.%Y",
+    long: "{day}. {month} {year}",
+    time: "%H:%M",
+    months: &["Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August", "September", "Oktober", "November", "Dezember"],
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    prefix: false,
+};
+
+const FR: Locale = Locale {
+    code: "fr",
+    decimal: ",",
+    group: "\u{202f}",
+    short: "%d/%m/%Y",
+    long: "{day} {month} {year}",
+    time: "%H:%M",
+    months: &["janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre", "octobre", "novembre", "décembre"],
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    prefix: false,
+};
+
+const ES: Locale = Locale {
+    code: "es",
+    decimal: ",",
+    group: ".",
+    short: "%d/%m/%Y",
+    long: "{day} de {month} de {year}",
+    time: "%H:%M",
+    months: &["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    prefix: false,
+};
+
+const IT: Locale = Locale {
+    code: "it",
+    decimal: ",",
+    group: ".",
+    short: "%d/%m/%Y",
+    long: "{day} {month} {year}",
+    time: "%H:%M",
+    months: &[
+        "gennaio",
+        "febbraio",
+        "marzo",
+        "aprile",
+        "maggio",
+        "giugno",
+        "luglio",
+        "agosto",
+        "settembre",
+        "ottobre",
+        "novembre",
+        "dicembre",
+    ],
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    prefix: false,
+};
+
+const PL: Locale = Locale {
+    code: "pl",
+    decimal: ",",
+    group: "\u{a0}",
+    short: "%d.%m.%Y",
+    long: "{day} {month} {year}",
+    time: "%H:%M",
+    months: &[
+        "stycznia",
+        "lutego",
+        "marca",
+        "kwietnia",
+        "maja",
+        "czerwca",
+        "lipca",
+        "sierpnia",
+        "września",
+        "października",
+        "listopada",
+        "grudnia",
+    ],
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    prefix: false,
+};
+
+const PT: Locale = Locale {
+    code: "pt",
+    decimal: ",",
+    group: ".",
+    short: "%d/%m/%Y",
+    long: "{day} de {month} de {year}",
+    time: "%H:%M",
+    months: &["janeiro", "fevereiro", "março", "abril", "maio", "junho", "julho", "agosto", "setembro", "outubro", "novembro", "dezembro"],
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    prefix: false,
+};
+
+/// Languages with the rules, the others are English
+const LOCALES: [Locale; 9] = [EN, UK, RU, DE, FR, ES, IT, PL, PT];
+
+impl Default for Locale {
+    fn default() -> Locale {
+        EN
+    }
+}
+
+impl Locale {
+    /// Rules by the code ISO 639-1 of the language
+    pub(crate) fn new(code: &str) -> Locale {
+        LOCALES.iter().find(|locale| locale.code == code).copied().unwrap_or(EN)
+    }
+
+    /// Date in the time zone of `dt`
+    pub(crate) fn date<Tz: TimeZone>(&self, dt: &DateTime<Tz>, style: DateStyle) -> String
+    where
+        Tz::Offset: Display,
+    {
+        match style {
+            DateStyle::Short => dt.format(self.short).to_string(),
+            DateStyle::Long => self.long(dt),
+            DateStyle::Time => dt.format(self.time).to_string(),
+            DateStyle::ShortTime => format!("{} {}", dt.format(self.short), dt.format(self.time)),
+            DateStyle::LongTime => format!("{} {}", self.long(dt), dt.format(self.time)),
+        }
+    }
+
+    /// Number with the separators of the groups and the decimal separator, rounded to `decimals`
+    pub(crate) fn number(&self, value: f64, decimals: usize) -> String {
+        let text = format!("{:.*}", decimals, value.abs());
+        let (int, frac) = text.split_once('.').unwrap_or((&text, ""));
+        let mut res = String::with_capacity(text.len() + int.len() / 3 * self.group.len() + 1);
+        if value < 0.0 && text.bytes().any(|b| b.is_ascii_digit() && b != b'0') {
+            res.push('-');
+        }
+        for (i, c) in int.chars().enumerate() {
+            if i > 0 && (int.len() - i) % 3 == 0 {
+                res.push_str(self.group);
+            }
+            res.push(c);
+        }
+        if !frac.is_empty() {
+            res.push_str(self.decimal);
+            res.push_str(frac);
+        }
+        res
+    }
+
+    /// Amount with the symbol of the currency ISO 4217, for example "$1,234.50" or "1 234,50 ₴"
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    pub(crate) fn currency(&self, value: f64, currency: &str) -> String {
+        let (symbol, decimals) = match currency {
+            "USD" => ("$", 2),
+            "EUR" => ("€", 2),
+            "GBP" => ("£", 2),
+            "UAH" => ("₴", 2),
+            "PLN" => ("zł", 2),
+            "JPY" => ("¥", 0),
+            "CNY" => ("¥", 2),
+            currency => (currency, 2),
+        };
+        let number = self.number(value, decimals);
+        match (self.prefix, number.strip_prefix('-')) {
+            (true, Some(number)) => format!("-{}{}", symbol, number),
+            (true, None) => format!("{}{}", symbol, number),
+            (false, _) => format!("{}\u{a0}{}", number, symbol),
+        }
+    }
+
+    fn long<Tz: TimeZone>(&self, dt: &DateTime<Tz>) -> String {
+        self.long
+            .replace("{day}", &dt.day().to_string())
+            .replace("{month}", self.months[dt.month0() as usize])
+            .replace("{year}", &dt.year().to_string())
+    }
+}
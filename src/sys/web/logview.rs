@@ -0,0 +1,448 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::SeekFrom,
+    time::{Duration, Instant},
+};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde_json::{json, Value};
+use tiny_web_macro::fnv1a_64 as m_fnv1a_64;
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, BufReader},
+    time::sleep,
+};
+
+use crate::{
+    log,
+    sys::log::{Log, LogView},
+};
+
+use super::{
+    action::{Action, Answer, ModuleMap},
+    controller::Controller,
+    feed::Feed,
+    guard::Guard,
+    request::HttpMethod,
+    response::CacheControl,
+};
+
+/// Default number of the records of the page and of the JSON
+const LOG_LIMIT: usize = 200;
+/// Max number of the records of the page and of the JSON
+const LOG_LIMIT_MAX: usize = 5000;
+/// Max number of the records of the download
+const LOG_DOWNLOAD: usize = 100_000;
+/// Max bytes of the one read of the tail
+const TAIL_CHUNK: u64 = 1024 * 1024;
+/// Interval of the check of the new records of the tail
+const TAIL_INTERVAL: Duration = Duration::from_secs(1);
+/// Comment to keep the connection of the tail through the proxy
+const TAIL_PING: Duration = Duration::from_secs(15);
+/// Max time of the one connection of the tail, then the browser reconnects with Last-Event-ID
+const TAIL_TIME: Duration = Duration::from_secs(600);
+
+/// Parsed record of the log file
+#[derive(Debug, Clone)]
+pub(crate) struct LogEntry {
+    pub pid: u32,
+    pub time: NaiveDateTime,
+    pub view: LogView,
+    pub number: u16,
+    /// Number of the request, the record is written inside the request
+    pub request: Option<u64>,
+    pub line: u32,
+    pub file: String,
+    pub text: String,
+    /// Lines of the record as they are in the file
+    pub raw: String,
+}
+
+/// Filter of the records by the fields of the query string "level", "code", "request", "from", "to" and "q"
+#[derive(Debug, Default)]
+pub(crate) struct LogFilter {
+    /// Min level, "warning" shows Warning, Stop, Error and Critical
+    pub view: Option<LogView>,
+    pub number: Option<u16>,
+    pub request: Option<u64>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+    /// Substring of the text or the file in lower case
+    pub text: Option<String>,
+}
+
+/// Built-in log viewer of the admin feature, works with the debug features only, they write the log
+///
+/// The controllers are "/admin/log/index" with the HTML page, "/admin/log/json" with the records in JSON,
+/// "/admin/log/tail" with the new records by Server-Sent Events and "/admin/log/download" with the lines of the file.
+/// All of them have the same filter in the query string, for example "?level=warning&code=500&from=2026-10-15".
+/// "/admin/log/level" shows the verbosity in JSON, POST with "level=vv" changes it until the restart.
+pub(crate) struct LogViewer;
+
+impl LogViewer {
+    /// Add the controllers, the existing ones are not replaced
+    pub(crate) fn add(engine: &mut ModuleMap) {
+        let class = engine.entry(m_fnv1a_64!("admin")).or_default().entry(m_fnv1a_64!("log")).or_default();
+        class.entry(m_fnv1a_64!("index")).or_insert(|this| Box::pin(LogViewer::index(this)));
+        class.entry(m_fnv1a_64!("json")).or_insert(|this| Box::pin(LogViewer::json(this)));
+        class.entry(m_fnv1a_64!("tail")).or_insert(|this| Box::pin(LogViewer::tail(this)));
+        class.entry(m_fnv1a_64!("download")).or_insert(|this| Box::pin(LogViewer::download(this)));
+        class.entry(m_fnv1a_64!("level")).or_insert(|this| Box::pin(LogViewer::level(this)));
+    }
+
+    async fn index(this: &mut Action) -> Answer {
+        if let Some(answer) = this.guard(&[Guard::Auth]).await {
+            return answer;
+        }
+        this.controller(&[Controller::ContentType("text/html; charset=utf-8"), Controller::NoSession]);
+        let filter = LogFilter::new(&this.request.input.get);
+        let list = LogFile::read(&filter, LogViewer::limit(&this.request.input.get)).await;
+        let html = LogViewer::render(&this.request.input.get, &list, this.csp_nonce());
+        Answer::String(html)
+    }
+
+    async fn json(this: &mut Action) -> Answer {
+        if let Some(answer) = this.guard(&[Guard::Auth]).await {
+            return answer;
+        }
+        this.controller(&[Controller::Json, Controller::NoSession]);
+        let filter = LogFilter::new(&this.request.input.get);
+        let list = LogFile::read(&filter, LogViewer::limit(&this.request.input.get)).await;
+        Answer::String(json!({ "entries": list.iter().rev().map(LogEntry::json).collect::<Vec<Value>>() }).to_string())
+    }
+
+    /// New records by Server-Sent Events, the id of the event is the offset in the file
+    async fn tail(this: &mut Action) -> Answer {
+        if let Some(answer) = this.guard(&[Guard::Auth]).await {
+            return answer;
+        }
+        this.controller(&[Controller::ContentType("text/event-stream"), Controller::NoSession]);
+        this.cache_control(CacheControl::no_store());
+        let filter = LogFilter::new(&this.request.input.get);
+        let mut offset = match this.request.header("last-event-id").and_then(|id| id.trim().parse().ok()) {
+            Some(offset) => offset,
+            None => LogFile::len().await,
+        };
+        this.write(Answer::String("retry: 1000\n\n".to_owned())).await;
+        let start = Instant::now();
+        let mut ping = Instant::now();
+        while start.elapsed() < TAIL_TIME {
+            sleep(TAIL_INTERVAL).await;
+            let (list, next) = LogFile::tail(offset).await;
+            offset = next;
+            let mut text = String::new();
+            for entry in list.iter().filter(|entry| filter.matches(entry)) {
+                text.push_str(&format!("id: {}\ndata: {}\n\n", offset, entry.json()));
+            }
+            if text.is_empty() && ping.elapsed() >= TAIL_PING {
+                text.push_str(": ping\n\n");
+            }
+            if !text.is_empty() {
+                this.write(Answer::String(text)).await;
+                ping = Instant::now();
+            }
+        }
+        Answer::None
+    }
+
+    /// Verbosity of the log, POST "level" with "v", "vv" or "vvv" changes it
+    async fn level(this: &mut Action) -> Answer {
+        if let Some(answer) = this.guard(&[Guard::Auth]).await {
+            return answer;
+        }
+        this.controller(&[Controller::Json, Controller::NoSession]);
+        if matches!(this.request.method, HttpMethod::Post) {
+            match this.request.input.post.get("level").and_then(|level| Log::parse_level(level.trim())) {
+                Some(level) => {
+                    let _level = Log::set_level(level);
+                    log!(info, 0, "Log level: {}", _level);
+                }
+                None => {
+                    this.response.http_code = Some(400);
+                    return Answer::String(json!({ "error": "level must be \"v\", \"vv\" or \"vvv\"" }).to_string());
+                }
+            }
+        }
+        Answer::String(json!({ "level": Log::level(), "max": Log::level_max() }).to_string())
+    }
+
+    /// Lines of the records by the filter as the file "app.log"
+    async fn download(this: &mut Action) -> Answer {
+        if let Some(answer) = this.guard(&[Guard::Auth]).await {
+            return answer;
+        }
+        this.controller(&[Controller::ContentType("text/plain; charset=utf-8"), Controller::NoSession]);
+        this.response.headers.push(("Content-Disposition".to_owned(), "attachment; filename=\"app.log\"".to_owned()));
+        let filter = LogFilter::new(&this.request.input.get);
+        let list = LogFile::read(&filter, LOG_DOWNLOAD).await;
+        let mut text = String::with_capacity(list.iter().map(|entry| entry.raw.len() + 1).sum());
+        for entry in &list {
+            text.push_str(&entry.raw);
+            text.push('\n');
+        }
+        Answer::String(text)
+    }
+
+    fn limit(get: &HashMap<String, String>) -> usize {
+        get.get("limit").and_then(|limit| limit.parse().ok()).unwrap_or(LOG_LIMIT).clamp(1, LOG_LIMIT_MAX)
+    }
+
+    /// Query string of the filter for the links of the page
+    fn query(get: &HashMap<String, String>) -> String {
+        ["level", "code", "request", "from", "to", "q"]
+            .iter()
+            .filter_map(|key| get.get(*key).filter(|value| !value.is_empty()).map(|value| (key, value)))
+            .map(|(key, value)| format!("{}={}", key, utf8_percent_encode(value, NON_ALPHANUMERIC)))
+            .collect::<Vec<String>>()
+            .join("&")
+    }
+
+    /// HTML page with the filter, the records from the new ones and the live tail
+    fn render(get: &HashMap<String, String>, list: &[LogEntry], nonce: Option<&str>) -> String {
+        let value = |key: &str| Feed::escape(get.get(key).map(|v| v.as_str()).unwrap_or_default());
+        let mut html = String::with_capacity(4096 + list.len() * 256);
+        html.push_str(concat!(
+            r#"<!DOCTYPE html><html><head><meta charset="utf-8"><title>Log</title>"#,
+            r#"<style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin-top:1em}"#,
+            r#"td,th{border:1px solid #ccc;padding:4px 8px;text-align:left;vertical-align:top}td pre{margin:0;white-space:pre-wrap}"#,
+            r#"form input{width:10em}</style></head><body><h1>Log</h1><form method="get">"#
+        ));
+        html.push_str(r#"<select name="level"><option value="">all</option>"#);
+        for level in ["info", "warning", "stop", "error", "critical"] {
+            let selected = if get.get("level").is_some_and(|v| v.eq_ignore_ascii_case(level)) { " selected" } else { "" };
+            html.push_str(&format!(r#"<option value="{0}"{1}>{0} and above</option>"#, level, selected));
+        }
+        html.push_str("</select> ");
+        for (key, title) in [("code", "Code"), ("request", "Request"), ("from", "From"), ("to", "To"), ("q", "Text")] {
+            html.push_str(&format!(r#"<input name="{}" placeholder="{}" value="{}"> "#, key, title, value(key)));
+        }
+        let query = LogViewer::query(get);
+        html.push_str(&format!(
+            r#"<button>Filter</button> <a href="download?{0}">Download</a> <label><input type="checkbox" id="tail" style="width:auto"> Live</label></form>"#,
+            Feed::escape(&query)
+        ));
+        html.push_str("<table><thead><tr><th>Time</th><th>Level</th><th>Code</th><th>Request</th><th>File</th><th>Text</th></tr></thead><tbody id=\"log\">");
+        for entry in list.iter().rev() {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{:?}</td><td>{}</td><td>{}</td><td>{}:{}</td><td><pre>{}</pre></td></tr>",
+                entry.time.format("%Y-%m-%d %H:%M:%S%.3f"),
+                entry.view,
+                entry.number,
+                entry.request.map(|request| request.to_string()).unwrap_or_default(),
+                Feed::escape(&entry.file),
+                entry.line,
+                Feed::escape(&entry.text)
+            ));
+        }
+        html.push_str("</tbody></table>");
+        match nonce {
+            Some(nonce) => html.push_str(&format!(r#"<script nonce="{}">"#, Feed::escape(nonce))),
+            None => html.push_str("<script>"),
+        }
+        html.push_str(&format!(
+            concat!(
+                r#"var es=null;document.getElementById("tail").onchange=function(){{if(es){{es.close();es=null}}if(!this.checked)return;"#,
+                r#"es=new EventSource("tail?{}");es.onmessage=function(e){{var d=JSON.parse(e.data),r=document.createElement("tr");"#,
+                r#"[d.time,d.level,d.code,d.request==null?"":d.request,d.file+":"+d.line,d.text].forEach(function(v,i){{"#,
+                r#"var c=document.createElement("td");if(i==5){{var p=document.createElement("pre");p.textContent=v;c.appendChild(p)}}"#,
+                r#"else c.textContent=v;r.appendChild(c)}});var t=document.getElementById("log");t.insertBefore(r,t.firstChild)}}}};"#,
+                "</script></body></html>"
+            ),
+            query
+        ));
+        html
+    }
+}
+
+impl LogFilter {
+    pub(crate) fn new(get: &HashMap<String, String>) -> LogFilter {
+        let value = |key: &str| get.get(key).map(|v| v.trim()).filter(|v| !v.is_empty());
+        LogFilter {
+            view: value("level").and_then(LogEntry::view),
+            number: value("code").and_then(|v| v.parse().ok()),
+            request: value("request").and_then(|v| v.parse().ok()),
+            from: value("from").and_then(|v| LogFilter::time(v, false)),
+            to: value("to").and_then(|v| LogFilter::time(v, true)),
+            text: value("q").map(|v| v.to_lowercase()),
+        }
+    }
+
+    pub(crate) fn matches(&self, entry: &LogEntry) -> bool {
+        self.view.is_none_or(|view| entry.view >= view)
+            && self.number.is_none_or(|number| entry.number == number)
+            && self.request.is_none_or(|request| entry.request == Some(request))
+            && self.from.is_none_or(|from| entry.time >= from)
+            && self.to.is_none_or(|to| entry.time <= to)
+            && self.text.as_ref().is_none_or(|text| entry.text.to_lowercase().contains(text) || entry.file.contains(text.as_str()))
+    }
+
+    /// "2026-10-15 12:00:00", "2026-10-15T12:00" or "2026-10-15" as the start or the end of the day
+    fn time(value: &str, end: bool) -> Option<NaiveDateTime> {
+        let value = value.replace('T', " ");
+        for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M"] {
+            if let Ok(time) = NaiveDateTime::parse_from_str(&value, format) {
+                return Some(time);
+            }
+        }
+        let date = NaiveDate::parse_from_str(&value, "%Y-%m-%d").ok()?;
+        match end {
+            true => date.and_hms_nano_opt(23, 59, 59, 999_999_999),
+            false => date.and_hms_opt(0, 0, 0),
+        }
+    }
+}
+
+impl LogEntry {
+    /// Record of the line "ID: 1 Time: ... Type: Warning. Number: 0 Request: 7 Line:40 File:src/main.rs text"
+    fn parse(line: &str) -> Option<LogEntry> {
+        let (pid, rest) = line.strip_prefix("ID: ")?.split_once(" Time: ")?;
+        let (time, rest) = rest.split_once(" Type: ")?;
+        let (view, rest) = rest.split_once(". Number: ")?;
+        let (number, mut rest) = rest.split_once(' ').unwrap_or((rest, ""));
+        let mut request = None;
+        if let Some((id, next)) = rest.strip_prefix("Request: ").and_then(|rest| rest.split_once(' ')) {
+            request = id.parse().ok();
+            rest = next;
+        }
+        // The record of the panic has no line and file
+        let (line_number, file, text) = match rest.strip_prefix("Line:").and_then(|rest| rest.split_once(" File:")) {
+            Some((number, rest)) => {
+                let (file, text) = rest.split_once(' ').unwrap_or((rest, ""));
+                (number.parse().unwrap_or_default(), file, text)
+            }
+            None => (0, "", rest),
+        };
+        Some(LogEntry {
+            pid: pid.parse().ok()?,
+            time: NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.f").ok()?,
+            view: LogEntry::view(view)?,
+            number: number.parse().unwrap_or_default(),
+            request,
+            line: line_number,
+            file: file.to_owned(),
+            text: text.to_owned(),
+            raw: line.to_owned(),
+        })
+    }
+
+    fn view(value: &str) -> Option<LogView> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "info" => Some(LogView::Info),
+            "warning" => Some(LogView::Warning),
+            "stop" => Some(LogView::Stop),
+            "error" => Some(LogView::Error),
+            "critical" => Some(LogView::Critical),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn json(&self) -> Value {
+        json!({
+            "pid": self.pid,
+            "time": self.time.format("%Y-%m-%d %H:%M:%S%.f").to_string(),
+            "level": format!("{:?}", self.view),
+            "code": self.number,
+            "request": self.request,
+            "line": self.line,
+            "file": self.file,
+            "text": self.text,
+        })
+    }
+
+    /// Add the line, the line without the header continues the text of the last record
+    ///
+    /// Returns `true` if the line starts the new record.
+    fn push(list: &mut VecDeque<LogEntry>, line: &str) -> bool {
+        match LogEntry::parse(line) {
+            Some(entry) => {
+                list.push_back(entry);
+                true
+            }
+            None => {
+                if let Some(last) = list.back_mut() {
+                    last.text.push('\n');
+                    last.text.push_str(line);
+                    last.raw.push('\n');
+                    last.raw.push_str(line);
+                }
+                false
+            }
+        }
+    }
+}
+
+/// Reading of the log file
+pub(crate) struct LogFile;
+
+impl LogFile {
+    /// Last `limit` records by the filter, the old ones first
+    pub(crate) async fn read(filter: &LogFilter, limit: usize) -> Vec<LogEntry> {
+        let file = match LogFile::open().await {
+            Some(file) => file,
+            None => return Vec::new(),
+        };
+        let mut list = VecDeque::new();
+        let mut lines = BufReader::new(file).lines();
+        // The record is checked when the next one starts, the text can have several lines
+        let mut pending = VecDeque::with_capacity(2);
+        while let Ok(Some(line)) = lines.next_line().await {
+            if LogEntry::push(&mut pending, &line) && pending.len() > 1 {
+                if let Some(entry) = pending.pop_front().filter(|entry| filter.matches(entry)) {
+                    list.push_back(entry);
+                    if list.len() > limit {
+                        list.pop_front();
+                    }
+                }
+            }
+        }
+        list.extend(pending.into_iter().filter(|entry| filter.matches(entry)));
+        while list.len() > limit {
+            list.pop_front();
+        }
+        list.into()
+    }
+
+    /// Size of the log file, the start of the tail
+    pub(crate) async fn len() -> u64 {
+        match Log::path() {
+            Some(path) => tokio::fs::metadata(path).await.map(|meta| meta.len()).unwrap_or_default(),
+            None => 0,
+        }
+    }
+
+    /// Records written after the offset and the new offset, the truncated or rotated file is read from the start
+    pub(crate) async fn tail(offset: u64) -> (Vec<LogEntry>, u64) {
+        let len = LogFile::len().await;
+        let offset = if len < offset { 0 } else { offset };
+        if len == offset {
+            return (Vec::new(), offset);
+        }
+        let mut file = match LogFile::open().await {
+            Some(file) => file,
+            None => return (Vec::new(), offset),
+        };
+        if file.seek(SeekFrom::Start(offset)).await.is_err() {
+            return (Vec::new(), offset);
+        }
+        let mut buf = Vec::new();
+        if file.take(TAIL_CHUNK).read_to_end(&mut buf).await.is_err() {
+            return (Vec::new(), offset);
+        }
+        // Only the complete lines, the rest is read the next time
+        let end = match buf.iter().rposition(|byte| *byte == b'\n') {
+            Some(pos) => pos + 1,
+            None if buf.len() as u64 == TAIL_CHUNK => buf.len(),
+            None => return (Vec::new(), offset),
+        };
+        let mut list = VecDeque::new();
+        for line in String::from_utf8_lossy(&buf[..end]).lines() {
+            LogEntry::push(&mut list, line);
+        }
+        (list.into(), offset + end as u64)
+    }
+
+    async fn open() -> Option<File> {
+        File::open(Log::path()?).await.ok()
+    }
+}
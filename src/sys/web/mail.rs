@@ -8,7 +8,7 @@ use serde::Serialize;
 #[cfg(feature = "mail-smtp")]
 use lettre::transport::smtp::{
     authentication::{Credentials, Mechanism},
-    client::{Tls, TlsParametersBuilder},
+    client::{Tls, TlsParameters, TlsParametersBuilder},
 };
 
 #[cfg(feature = "mail-file")]
@@ -23,7 +23,12 @@ use lettre::AsyncSmtpTransport;
 #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
 use lettre::{AsyncTransport, Tokio1Executor};
 
-#[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+#[cfg(any(
+    feature = "mail-sendmail",
+    feature = "mail-smtp",
+    feature = "mail-file",
+    feature = "mail-api"
+))]
 use lettre::{
     message::{header::ContentType, Attachment, Mailbox, MultiPart, SinglePart},
     Message,
@@ -35,12 +40,20 @@ use tiny_web_macro::fnv1a_64 as m_fnv1a_64;
 use crate::log;
 
 #[cfg(feature = "mail-smtp")]
-use crate::sys::app::init::{Auth as InitAuth, Tls as InitTls};
+use crate::sys::{
+    app::init::{Auth as InitAuth, Tls as InitTls},
+    net::eyeballs::HappyEyeballs,
+};
 
 #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
 use crate::sys::app::init::MailConfig;
 
-#[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+#[cfg(any(
+    feature = "mail-sendmail",
+    feature = "mail-smtp",
+    feature = "mail-file",
+    feature = "mail-api"
+))]
 use crate::tool::generate_uuid;
 
 #[cfg(feature = "mail-db")]
@@ -121,14 +134,31 @@ impl Mail {
         let sender = AsyncFileTransport::<Tokio1Executor>::new(&init.path);
         #[cfg(feature = "mail-smtp")]
         let sender = {
+            // lettre connects by itself, it gets the address which answered first, the name of the server is for TLS
+            let relay = match HappyEyeballs::address(&init.server, init.port).await {
+                Ok(addr) => addr.ip().to_string(),
+                Err(_e) => {
+                    log!(warning, 0, "{}:{}. Error: {}", init.server, init.port, _e);
+                    return Err(());
+                }
+            };
             let sender = match init.tls {
-                InitTls::None => match AsyncSmtpTransport::<Tokio1Executor>::relay(&init.server) {
-                    Ok(s) => s.port(init.port),
-                    Err(_e) => {
-                        log!(warning, 0, "{}", _e);
-                        return Err(());
+                InitTls::None => {
+                    let param = match TlsParameters::new(init.server.clone()) {
+                        Ok(param) => param,
+                        Err(_e) => {
+                            log!(warning, 0, "{}", _e);
+                            return Err(());
+                        }
+                    };
+                    match AsyncSmtpTransport::<Tokio1Executor>::relay(&relay) {
+                        Ok(s) => s.tls(Tls::Wrapper(param)).port(init.port),
+                        Err(_e) => {
+                            log!(warning, 0, "{}", _e);
+                            return Err(());
+                        }
                     }
-                },
+                }
                 InitTls::Start => {
                     let param = match TlsParametersBuilder::new(init.server.clone()).dangerous_accept_invalid_certs(true).build() {
                         Ok(param) => param,
@@ -137,7 +167,7 @@ impl Mail {
                             return Err(());
                         }
                     };
-                    match AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&init.server) {
+                    match AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&relay) {
                         Ok(s) => s.tls(Tls::Required(param)).port(init.port),
                         Err(_e) => {
                             log!(warning, 0, "{}", _e);
@@ -153,7 +183,7 @@ impl Mail {
                             return Err(());
                         }
                     };
-                    match AsyncSmtpTransport::<Tokio1Executor>::relay(&init.server) {
+                    match AsyncSmtpTransport::<Tokio1Executor>::relay(&relay) {
                         Ok(s) => s.tls(Tls::Wrapper(param)).port(init.port),
                         Err(_e) => {
                             log!(warning, 0, "{}", _e);
@@ -207,15 +237,25 @@ impl Mail {
         Ok(())
     }
 
-    #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
+    #[cfg(any(
+        feature = "mail-sendmail",
+        feature = "mail-smtp",
+        feature = "mail-file",
+        feature = "mail-api"
+    ))]
     fn create_message_uuid(host: &str) -> String {
         let id = generate_uuid();
         format!("<{}@{}>", &id[..60], host)
     }
 
     /// Create text email message from struct MailMessage
-    #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
-    fn create_message(host: &str, message: MailMessage<'_>) -> Result<Message, ()> {
+    #[cfg(any(
+        feature = "mail-sendmail",
+        feature = "mail-smtp",
+        feature = "mail-file",
+        feature = "mail-api"
+    ))]
+    pub(crate) fn create_message(host: &str, message: MailMessage<'_>) -> Result<Message, ()> {
         let from = match message.from.parse::<Mailbox>() {
             Ok(f) => f,
             Err(_e) => {
@@ -347,9 +387,154 @@ impl Mail {
         Ok(mes)
     }
 
+    /// Text part of the html message, the tags are removed and the link is "text (url)"
+    #[cfg(any(feature = "html-static", feature = "html-reload"))]
+    pub(crate) fn text(html: &str) -> String {
+        let mut text = String::with_capacity(html.len());
+        let mut rest = html;
+        // Closing tag of the skipped content
+        let mut skip: Option<String> = None;
+        // Url and the start of the text of the link
+        let mut link: Option<(String, usize)> = None;
+        loop {
+            let end = rest.find('<').unwrap_or(rest.len());
+            if skip.is_none() {
+                Mail::push_text(&mut text, &rest[..end]);
+            }
+            if end == rest.len() {
+                break;
+            }
+            rest = &rest[end + 1..];
+            if let Some(comment) = rest.strip_prefix("!--") {
+                rest = comment.find("-->").map(|end| &comment[end + 3..]).unwrap_or_default();
+                continue;
+            }
+            let end = match rest.find('>') {
+                Some(end) => end,
+                None => break,
+            };
+            let tag = &rest[..end];
+            rest = &rest[end + 1..];
+            let closing = tag.starts_with('/');
+            let name =
+                tag.trim_start_matches('/').split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or_default().to_ascii_lowercase();
+            if let Some(close) = &skip {
+                if closing && name == *close {
+                    skip = None;
+                }
+                continue;
+            }
+            match name.as_str() {
+                "head" | "style" | "script" | "title" if !closing => skip = Some(name),
+                "br" | "p" | "div" | "tr" | "table" | "ul" | "ol" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "blockquote" => {
+                    text.push('\n')
+                }
+                "hr" => text.push_str("\n----------\n"),
+                "li" if !closing => text.push_str("\n- "),
+                "td" | "th" if closing => text.push(' '),
+                "a" if !closing => link = Mail::attr(tag, "href").map(|href| (href, text.len())),
+                "a" => {
+                    if let Some((href, start)) = link.take() {
+                        if !href.starts_with('#') && text[start..].trim() != href {
+                            text.push_str(&format!(" ({})", href));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let mut res = String::with_capacity(text.len());
+        let mut empty = 0;
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() {
+                empty += 1;
+                if empty > 1 || res.is_empty() {
+                    continue;
+                }
+            } else {
+                empty = 0;
+            }
+            res.push_str(line);
+            res.push('\n');
+        }
+        res.trim_end().to_owned()
+    }
+
+    /// Text between the tags, the spaces are collapsed as in the browser and the entities are decoded
+    #[cfg(any(feature = "html-static", feature = "html-reload"))]
+    fn push_text(text: &mut String, html: &str) {
+        let mut rest = html;
+        while !rest.is_empty() {
+            let c = match rest.chars().next() {
+                Some(c) => c,
+                None => break,
+            };
+            if c.is_whitespace() {
+                if !text.ends_with([' ', '\n']) && !text.is_empty() {
+                    text.push(' ');
+                }
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+            if c == '&' {
+                if let Some(end) = rest[..rest.len().min(12)].find(';') {
+                    let decoded = match &rest[1..end] {
+                        "amp" => Some('&'),
+                        "lt" => Some('<'),
+                        "gt" => Some('>'),
+                        "quot" => Some('"'),
+                        "apos" => Some('\''),
+                        "nbsp" => Some(' '),
+                        entity => match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                            Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                            None => entity.strip_prefix('#').and_then(|num| num.parse().ok()).and_then(char::from_u32),
+                        },
+                    };
+                    if let Some(decoded) = decoded {
+                        text.push(decoded);
+                        rest = &rest[end + 1..];
+                        continue;
+                    }
+                }
+            }
+            text.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    /// Value of the attribute of the tag
+    #[cfg(any(feature = "html-static", feature = "html-reload"))]
+    fn attr(tag: &str, name: &str) -> Option<String> {
+        let lower = tag.to_ascii_lowercase();
+        let mut from = 0;
+        while let Some(pos) = lower[from..].find(name) {
+            let start = from + pos;
+            from = start + name.len();
+            if !lower[..start].ends_with(char::is_whitespace) {
+                continue;
+            }
+            let value = lower[from..].trim_start().strip_prefix('=')?.trim_start();
+            let offset = tag.len() - value.len();
+            let value = &tag[offset..];
+            let value = match value.chars().next()? {
+                quote @ ('"' | '\'') => &value[1..value[1..].find(quote).map(|end| end + 1).unwrap_or(value.len())],
+                _ => value.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or_default(),
+            };
+            let mut res = String::new();
+            Mail::push_text(&mut res, value);
+            return Some(res);
+        }
+        None
+    }
+
     /// Get mime from file extension
-    #[cfg(any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file"))]
-    fn get_mime(ext: &str) -> &'static str {
+    #[cfg(any(
+        feature = "mail-sendmail",
+        feature = "mail-smtp",
+        feature = "mail-file",
+        feature = "mail-api"
+    ))]
+    pub(crate) fn get_mime(ext: &str) -> &'static str {
         match ext {
             "7z" => "application/x-7z-compressed",
             "aac" => "audio/aac",
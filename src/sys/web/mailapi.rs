@@ -0,0 +1,291 @@
+use std::{
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use lettre::Message;
+use ring::{
+    digest::{digest, SHA256},
+    hmac,
+};
+use rustls::{pki_types::ServerName, ClientConfig, RootCertStore};
+use serde_json::{json, Map, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    time::{sleep, timeout},
+};
+use tokio_rustls::TlsConnector;
+
+use crate::{
+    log,
+    sys::{
+        app::init::{MailConfig, MailProvider},
+        net::eyeballs::HappyEyeballs,
+    },
+    tool::{base64_encode, generate_uuid, to_hex},
+};
+
+use super::{
+    action::Action,
+    mail::{Mail, MailBody, MailMessage},
+};
+
+/// Max time of the request to the API
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time of the next message by the rate limit of the provider
+static NEXT: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Mozilla roots for the API servers
+static TLS: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+
+/// Request to the API
+struct ApiRequest {
+    host: String,
+    path: String,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+/// Sending of the mail by the HTTP API of Mailgun, Amazon SES or Postmark
+///
+/// Mailgun and Amazon SES get the message as MIME, the same as SMTP, Postmark gets the parts of the message as JSON.
+/// The parameters "mail_key" and "mail_secret" of the table setting replace [mail] key and secret with `setting-db`.
+pub(crate) struct MailApi;
+
+impl MailApi {
+    pub(crate) async fn send(action: &Action, message: MailMessage<'_>) -> Result<(), ()> {
+        let init = &action.mail;
+        #[cfg(feature = "setting-db")]
+        let (key, secret) = (action.get_setting("mail_key").await, action.get_setting("mail_secret").await);
+        #[cfg(feature = "setting-db")]
+        let (key, secret) = (key.as_deref().unwrap_or(&init.key), secret.as_deref().or(init.secret.as_deref()));
+        #[cfg(not(feature = "setting-db"))]
+        let (key, secret) = (init.key.as_str(), init.secret.as_deref());
+
+        let request = match init.provider {
+            MailProvider::Mailgun => MailApi::mailgun(init, key, Mail::create_message(&action.request.host, message)?),
+            MailProvider::Ses => MailApi::ses(init, key, secret.unwrap_or_default(), Mail::create_message(&action.request.host, message)?),
+            MailProvider::Postmark => MailApi::postmark(key, message),
+        };
+        MailApi::wait(init.interval).await;
+        match timeout(TIMEOUT, MailApi::post(&request)).await {
+            Ok(Ok((status, _))) if (200..300).contains(&status) => Ok(()),
+            Ok(Ok((_status, _body))) => {
+                log!(warning, 0, "{:?} {}. Error: {}", init.provider, _status, _body);
+                Err(())
+            }
+            Ok(Err(_e)) => {
+                log!(warning, 0, "{}. Error: {}", request.host, _e);
+                Err(())
+            }
+            Err(_) => {
+                log!(warning, 0, "{}. Error: timeout", request.host);
+                Err(())
+            }
+        }
+    }
+
+    /// Wait for the rate limit, the messages are sent with the interval
+    async fn wait(interval: Option<Duration>) {
+        let interval = match interval {
+            Some(interval) => interval,
+            None => return,
+        };
+        let delay = {
+            let mut next = match NEXT.lock() {
+                Ok(next) => next,
+                Err(e) => e.into_inner(),
+            };
+            let now = Instant::now();
+            let at = next.filter(|at| *at > now).unwrap_or(now);
+            *next = Some(at + interval);
+            at - now
+        };
+        if !delay.is_zero() {
+            sleep(delay).await;
+        }
+    }
+
+    /// POST /v3/<domain>/messages.mime, the recipients with Bcc are the fields "to"
+    fn mailgun(init: &MailConfig, key: &str, message: Message) -> ApiRequest {
+        let boundary = format!("tiny{}", &generate_uuid()[..32]);
+        let mut body = Vec::new();
+        for to in message.envelope().to() {
+            body.extend_from_slice(format!("--{}\r\nContent-Disposition: form-data; name=\"to\"\r\n\r\n{}\r\n", boundary, to).as_bytes());
+        }
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"message\"; filename=\"message.mime\"\r\nContent-Type: message/rfc822\r\n\r\n",
+                boundary
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(&message.formatted());
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+        let host = match init.region.as_deref() {
+            Some("eu") => "api.eu.mailgun.net",
+            _ => "api.mailgun.net",
+        };
+        ApiRequest {
+            host: host.to_owned(),
+            path: format!("/v3/{}/messages.mime", init.domain.as_deref().unwrap_or_default()),
+            headers: vec![
+                ("Authorization", format!("Basic {}", base64_encode(format!("api:{}", key).as_bytes()))),
+                ("Content-Type", format!("multipart/form-data; boundary={}", boundary)),
+            ],
+            body,
+        }
+    }
+
+    /// POST /v2/email/outbound-emails with the signature AWS4-HMAC-SHA256
+    fn ses(init: &MailConfig, key: &str, secret: &str, message: Message) -> ApiRequest {
+        let region = init.region.as_deref().unwrap_or_default();
+        let envelope = message.envelope();
+        let body = json!({
+            "FromEmailAddress": envelope.from().map(|from| from.to_string()),
+            "Destination": { "ToAddresses": envelope.to().iter().map(|to| to.to_string()).collect::<Vec<_>>() },
+            "Content": { "Raw": { "Data": base64_encode(&message.formatted()) } },
+        })
+        .to_string()
+        .into_bytes();
+
+        let host = format!("email.{}.amazonaws.com", region);
+        let path = "/v2/email/outbound-emails";
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/ses/aws4_request", date, region);
+        let canonical = format!(
+            "POST\n{}\n\ncontent-type:application/json\nhost:{}\nx-amz-date:{}\n\ncontent-type;host;x-amz-date\n{}",
+            path,
+            host,
+            amz_date,
+            to_hex(digest(&SHA256, &body).as_ref())
+        );
+        let sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", amz_date, scope, to_hex(digest(&SHA256, canonical.as_bytes()).as_ref()));
+        let mut signing = MailApi::hmac(format!("AWS4{}", secret).as_bytes(), date.as_bytes());
+        for part in [region, "ses", "aws4_request"] {
+            signing = MailApi::hmac(&signing, part.as_bytes());
+        }
+        let signature = to_hex(&MailApi::hmac(&signing, sign.as_bytes()));
+        ApiRequest {
+            host,
+            path: path.to_owned(),
+            headers: vec![
+                ("Content-Type", "application/json".to_owned()),
+                ("X-Amz-Date", amz_date),
+                (
+                    "Authorization",
+                    format!(
+                        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders=content-type;host;x-amz-date, Signature={}",
+                        key, scope, signature
+                    ),
+                ),
+            ],
+            body,
+        }
+    }
+
+    /// POST /email, the text and the html are joined from the parts, the inline files have ContentID "cid:<name>"
+    fn postmark(key: &str, message: MailMessage<'_>) -> ApiRequest {
+        let mut json = Map::new();
+        json.insert("From".to_owned(), Value::String(message.from.into_owned()));
+        json.insert("To".to_owned(), Value::String(message.to.join(", ")));
+        if let Some(cc) = message.cc {
+            json.insert("Cc".to_owned(), Value::String(cc.join(", ")));
+        }
+        if let Some(bcc) = message.bcc {
+            json.insert("Bcc".to_owned(), Value::String(bcc.join(", ")));
+        }
+        if let Some(reply_to) = message.reply_to {
+            json.insert("ReplyTo".to_owned(), Value::String(reply_to.into_owned()));
+        }
+        json.insert("Subject".to_owned(), Value::String(message.subject.into_owned()));
+        let mut text = String::new();
+        let mut html = String::new();
+        let mut files = Vec::new();
+        let attachment = |name: &str, mime: Option<&str>, data: &[u8]| {
+            let mime = mime.unwrap_or_else(|| Mail::get_mime(name.rsplit_once('.').map(|(_, ext)| ext).unwrap_or_default()));
+            json!({ "Name": name, "Content": base64_encode(data), "ContentType": mime })
+        };
+        for body in message.body {
+            match body {
+                MailBody::Text(str) => text.push_str(&str),
+                MailBody::Html(part) => {
+                    if let Some(str) = part.text {
+                        text.push_str(&str);
+                    }
+                    html.push_str(&part.html);
+                    for file in part.file {
+                        let mut item = attachment(&file.name, file.mime.as_deref(), &file.data);
+                        item["ContentID"] = Value::String(format!("cid:{}", file.name));
+                        files.push(item);
+                    }
+                }
+                MailBody::File(file) => files.push(attachment(&file.name, file.mime.as_deref(), &file.data)),
+            }
+        }
+        if !text.is_empty() {
+            json.insert("TextBody".to_owned(), Value::String(text));
+        }
+        if !html.is_empty() {
+            json.insert("HtmlBody".to_owned(), Value::String(html));
+        }
+        if !files.is_empty() {
+            json.insert("Attachments".to_owned(), Value::Array(files));
+        }
+        ApiRequest {
+            host: "api.postmarkapp.com".to_owned(),
+            path: "/email".to_owned(),
+            headers: vec![
+                ("Accept", "application/json".to_owned()),
+                ("Content-Type", "application/json".to_owned()),
+                ("X-Postmark-Server-Token", key.to_owned()),
+            ],
+            body: Value::Object(json).to_string().into_bytes(),
+        }
+    }
+
+    /// Status and body of the answer, HTTP/1.1 over TLS with `Connection: close`
+    async fn post(request: &ApiRequest) -> Result<(u16, String), String> {
+        let tcp = HappyEyeballs::connect(&request.host, 443).await.map_err(|e| e.to_string())?;
+        let name = ServerName::try_from(request.host.clone()).map_err(|e| e.to_string())?;
+        let tls = TLS.get_or_init(|| {
+            let mut roots = RootCertStore::empty();
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            Arc::new(ClientConfig::builder().with_root_certificates(roots).with_no_client_auth())
+        });
+        let mut stream = TlsConnector::from(Arc::clone(tls)).connect(name, tcp).await.map_err(|e| e.to_string())?;
+
+        let mut head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            request.path,
+            request.host,
+            request.body.len()
+        );
+        for (name, value) in &request.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes()).await.map_err(|e| e.to_string())?;
+        stream.write_all(&request.body).await.map_err(|e| e.to_string())?;
+        stream.flush().await.map_err(|e| e.to_string())?;
+
+        let mut answer = Vec::new();
+        // Some servers close the connection without close_notify
+        if let Err(e) = stream.read_to_end(&mut answer).await {
+            if answer.is_empty() {
+                return Err(e.to_string());
+            }
+        }
+        let answer = String::from_utf8_lossy(&answer);
+        let status = answer.split(' ').nth(1).and_then(|status| status.parse().ok()).ok_or_else(|| "Invalid answer".to_owned())?;
+        let body = answer.split_once("\r\n\r\n").map(|(_, body)| body.to_owned()).unwrap_or_default();
+        Ok((status, body))
+    }
+
+    fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+        hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data).as_ref().to_vec()
+    }
+}
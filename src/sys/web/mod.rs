@@ -1,34 +1,99 @@
 pub mod action;
 
-#[cfg(feature = "cache")]
+#[cfg(feature = "admin")]
+pub(crate) mod admin;
+
+#[cfg(all(feature = "cache", not(feature = "cache-redis")))]
 pub(crate) mod cache;
 
+/// Cache in Redis by the same path as the memory cache
+#[cfg(feature = "cache-redis")]
+#[path = "redis.rs"]
+pub(crate) mod cache;
+
+pub mod clock;
+
+pub mod controller;
+
 pub mod data;
 
+pub mod feed;
+
+#[cfg(feature = "cache")]
+pub(crate) mod flight;
+
 #[cfg(feature = "file-disk")]
 pub(crate) mod file;
 
+#[cfg(feature = "access-db")]
+pub mod guard;
+
+pub mod handler;
+
 #[cfg(any(feature = "html-static", feature = "html-reload"))]
 pub(crate) mod html;
 
+#[cfg(feature = "image")]
+pub mod image;
+
+#[cfg(feature = "mail-inbound")]
+pub mod inbound;
+
+#[cfg(feature = "jwt")]
+pub mod jwt;
+
 #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
 pub(crate) mod lang;
 
+#[cfg(any(
+    feature = "lang-static",
+    feature = "lang-reload",
+    feature = "html-static",
+    feature = "html-reload"
+))]
+pub mod locale;
+
 #[cfg(any(
     feature = "mail-sendmail",
     feature = "mail-smtp",
     feature = "mail-file",
-    feature = "mail-db"
+    feature = "mail-db",
+    feature = "mail-api"
 ))]
 pub(crate) mod mail;
 
+#[cfg(feature = "mail-api")]
+pub(crate) mod mailapi;
+
+/// The log file is written with the debug features only
+#[cfg(all(feature = "admin", any(feature = "debug-v", feature = "debug-vv", feature = "debug-vvv")))]
+pub(crate) mod logview;
+
+pub(crate) mod pattern;
+
+#[cfg(feature = "privacy")]
+pub mod privacy;
+
 pub mod request;
 
 pub mod response;
 
+pub mod router;
+
+pub mod schema;
+
 #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
 pub mod session;
 
+pub mod sitemap;
+
+#[cfg(feature = "storage")]
+pub mod storage;
+
+pub mod timing;
+
+pub(crate) mod toggle;
+
 #[cfg(all(feature = "html-static", feature = "html-reload"))]
 compile_error!("It is impossible to simultaneously have the features of 'html-static' and 'html-reload'");
 
@@ -50,30 +115,66 @@ compile_error!(
     "It is impossible to simultaneously have either 'session-memory', or 'session-file', or 'session-db' features at the same time"
 );
 
+#[cfg(all(feature = "image", not(any(feature = "file-disk", feature = "file-memory"))))]
+compile_error!("Cannot have feature 'image' without 'file-disk' or 'file-memory'");
+
 #[cfg(all(feature = "session-db", not(any(feature = "pgsql", feature = "mssql"))))]
 compile_error!("Cannot have feature 'session-db'  without 'pgsql' or 'mssql'");
 
+#[cfg(all(feature = "jwt", not(feature = "access-db")))]
+compile_error!("Cannot have feature 'jwt' without 'access-db'");
+
+#[cfg(all(feature = "admin", not(feature = "access-db")))]
+compile_error!("Cannot have feature 'admin' without 'access-db'");
+
+#[cfg(all(feature = "privacy", not(all(feature = "pgsql", feature = "access-db"))))]
+compile_error!("Cannot have feature 'privacy' without 'pgsql' and 'access-db'");
+
 #[cfg(all(feature = "mail-db", not(any(feature = "pgsql", feature = "mssql"))))]
 compile_error!("Cannot have features 'mail-sendmail' or 'mail-smtp' or 'mail-file' or 'mail-db' without 'pgsql' or 'mssql'");
 
 #[cfg(any(
     all(
         feature = "mail-sendmail",
-        any(feature = "mail-smtp", feature = "mail-file", feature = "mail-db")
+        any(feature = "mail-smtp", feature = "mail-file", feature = "mail-db", feature = "mail-api")
     ),
     all(
         feature = "mail-smtp",
-        any(feature = "mail-sendmail", feature = "mail-file", feature = "mail-db")
+        any(
+            feature = "mail-sendmail",
+            feature = "mail-file",
+            feature = "mail-db",
+            feature = "mail-api"
+        )
     ),
     all(
         feature = "mail-file",
-        any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-db")
+        any(
+            feature = "mail-sendmail",
+            feature = "mail-smtp",
+            feature = "mail-db",
+            feature = "mail-api"
+        )
     ),
     all(
         feature = "mail-db",
-        any(feature = "mail-sendmail", feature = "mail-smtp", feature = "mail-file")
+        any(
+            feature = "mail-sendmail",
+            feature = "mail-smtp",
+            feature = "mail-file",
+            feature = "mail-api"
+        )
+    ),
+    all(
+        feature = "mail-api",
+        any(
+            feature = "mail-sendmail",
+            feature = "mail-smtp",
+            feature = "mail-file",
+            feature = "mail-db"
+        )
     )
 ))]
 compile_error!(
-    "It is impossible to simultaneously have either 'mail-sendmail', or 'mail-smtp', or 'mail-file', or 'mail-db' features at the same time"
+    "It is impossible to simultaneously have either 'mail-sendmail', or 'mail-smtp', or 'mail-file', or 'mail-db', or 'mail-api' features at the same time"
 );
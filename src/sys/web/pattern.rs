@@ -0,0 +1,218 @@
+/// Constraint of the route parameter
+///
+/// Supports a subset of the regular expressions, the whole value must match:
+/// literals, `.`, `[a-z0-9_]`, `[^/]`, `\d`, `\w`, `\s`, groups `(..)`, alternation `a|b`
+/// and quantifiers `*`, `+`, `?`, `{n}`, `{n,}`, `{n,m}`.
+#[derive(Debug)]
+pub(crate) struct Constraint {
+    root: Vec<Vec<Item>>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Vec<(char, char)>, bool),
+    Group(Vec<Vec<Item>>),
+}
+
+#[derive(Debug)]
+struct Item {
+    node: Node,
+    min: usize,
+    max: usize,
+}
+
+impl Constraint {
+    /// Parse the constraint, for example `[0-9]+` or `[a-z]{2}`
+    pub fn new(pattern: &str) -> Result<Constraint, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut pos = 0;
+        let root = Constraint::parse_alt(&chars, &mut pos)?;
+        if pos != chars.len() {
+            return Err(format!("Unexpected ')' at position {} in the constraint {}", pos, pattern));
+        }
+        Ok(Constraint { root })
+    }
+
+    /// Checks the value
+    pub fn check(&self, value: &str) -> bool {
+        let chars: Vec<char> = value.chars().collect();
+        Constraint::match_alt(&self.root, &chars, 0, &|pos| pos == chars.len())
+    }
+
+    fn parse_alt(chars: &[char], pos: &mut usize) -> Result<Vec<Vec<Item>>, String> {
+        let mut alt = vec![Constraint::parse_seq(chars, pos)?];
+        while chars.get(*pos) == Some(&'|') {
+            *pos += 1;
+            alt.push(Constraint::parse_seq(chars, pos)?);
+        }
+        Ok(alt)
+    }
+
+    fn parse_seq(chars: &[char], pos: &mut usize) -> Result<Vec<Item>, String> {
+        let mut seq = Vec::new();
+        while let Some(c) = chars.get(*pos) {
+            let node = match c {
+                '|' | ')' => break,
+                '(' => {
+                    *pos += 1;
+                    let alt = Constraint::parse_alt(chars, pos)?;
+                    if chars.get(*pos) != Some(&')') {
+                        return Err("Expected ')' in the constraint".to_owned());
+                    }
+                    *pos += 1;
+                    Node::Group(alt)
+                }
+                '[' => {
+                    *pos += 1;
+                    Constraint::parse_class(chars, pos)?
+                }
+                '\\' => {
+                    *pos += 1;
+                    let c = *chars.get(*pos).ok_or_else(|| "Unexpected end after '\\' in the constraint".to_owned())?;
+                    *pos += 1;
+                    Constraint::escape(c)
+                }
+                '.' => {
+                    *pos += 1;
+                    Node::Any
+                }
+                '*' | '+' | '?' | '{' => return Err(format!("Unexpected '{}' in the constraint", c)),
+                c => {
+                    *pos += 1;
+                    Node::Char(*c)
+                }
+            };
+            let (min, max) = Constraint::parse_quantifier(chars, pos)?;
+            seq.push(Item { node, min, max });
+        }
+        Ok(seq)
+    }
+
+    fn parse_class(chars: &[char], pos: &mut usize) -> Result<Node, String> {
+        let mut list = Vec::new();
+        let negative = chars.get(*pos) == Some(&'^');
+        if negative {
+            *pos += 1;
+        }
+        loop {
+            let c = *chars.get(*pos).ok_or_else(|| "Expected ']' in the constraint".to_owned())?;
+            *pos += 1;
+            let start = match c {
+                ']' if !list.is_empty() => break,
+                '\\' => {
+                    let c = *chars.get(*pos).ok_or_else(|| "Unexpected end after '\\' in the constraint".to_owned())?;
+                    *pos += 1;
+                    match Constraint::escape(c) {
+                        Node::Class(class, false) => {
+                            list.extend(class);
+                            continue;
+                        }
+                        Node::Char(c) => c,
+                        _ => return Err(format!("Unexpected '\\{}' in the constraint", c)),
+                    }
+                }
+                c => c,
+            };
+            match (chars.get(*pos), chars.get(*pos + 1)) {
+                (Some('-'), Some(end)) if *end != ']' => {
+                    if *end < start {
+                        return Err(format!("Invalid range {}-{} in the constraint", start, end));
+                    }
+                    list.push((start, *end));
+                    *pos += 2;
+                }
+                _ => list.push((start, start)),
+            }
+        }
+        Ok(Node::Class(list, negative))
+    }
+
+    fn parse_quantifier(chars: &[char], pos: &mut usize) -> Result<(usize, usize), String> {
+        let res = match chars.get(*pos) {
+            Some('*') => (0, usize::MAX),
+            Some('+') => (1, usize::MAX),
+            Some('?') => (0, 1),
+            Some('{') => {
+                let end = chars[*pos..].iter().position(|c| *c == '}').ok_or_else(|| "Expected '}' in the constraint".to_owned())?;
+                let value: String = chars[*pos + 1..*pos + end].iter().collect();
+                *pos += end;
+                let err = || format!("Invalid quantifier {{{}}} in the constraint", value);
+                match value.split_once(',') {
+                    Some((min, "")) => (min.trim().parse().map_err(|_| err())?, usize::MAX),
+                    Some((min, max)) => {
+                        let min = min.trim().parse().map_err(|_| err())?;
+                        let max = max.trim().parse().map_err(|_| err())?;
+                        if max < min {
+                            return Err(err());
+                        }
+                        (min, max)
+                    }
+                    None => {
+                        let n = value.trim().parse().map_err(|_| err())?;
+                        (n, n)
+                    }
+                }
+            }
+            _ => return Ok((1, 1)),
+        };
+        *pos += 1;
+        Ok(res)
+    }
+
+    fn escape(c: char) -> Node {
+        match c {
+            'd' => Node::Class(vec![('0', '9')], false),
+            'w' => Node::Class(vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')], false),
+            's' => Node::Class(vec![(' ', ' '), ('\t', '\t'), ('\r', '\r'), ('\n', '\n')], false),
+            c => Node::Char(c),
+        }
+    }
+
+    fn match_alt(alt: &[Vec<Item>], chars: &[char], pos: usize, next: &dyn Fn(usize) -> bool) -> bool {
+        alt.iter().any(|seq| Constraint::match_seq(seq, chars, pos, next))
+    }
+
+    fn match_seq(seq: &[Item], chars: &[char], pos: usize, next: &dyn Fn(usize) -> bool) -> bool {
+        match seq.split_first() {
+            Some((item, rest)) => Constraint::match_item(item, 0, chars, pos, &|pos| Constraint::match_seq(rest, chars, pos, next)),
+            None => next(pos),
+        }
+    }
+
+    /// Greedy repetition of the item with backtracking
+    fn match_item(item: &Item, count: usize, chars: &[char], pos: usize, next: &dyn Fn(usize) -> bool) -> bool {
+        if count < item.max
+            && Constraint::match_node(&item.node, chars, pos, &|new| {
+                if new == pos {
+                    count + 1 >= item.min && next(new)
+                } else {
+                    Constraint::match_item(item, count + 1, chars, new, next)
+                }
+            })
+        {
+            return true;
+        }
+        count >= item.min && next(pos)
+    }
+
+    fn match_node(node: &Node, chars: &[char], pos: usize, next: &dyn Fn(usize) -> bool) -> bool {
+        match node {
+            Node::Group(alt) => Constraint::match_alt(alt, chars, pos, next),
+            node => {
+                let c = match chars.get(pos) {
+                    Some(c) => *c,
+                    None => return false,
+                };
+                let found = match node {
+                    Node::Char(ch) => *ch == c,
+                    Node::Any => true,
+                    Node::Class(list, negative) => list.iter().any(|(start, end)| *start <= c && c <= *end) != *negative,
+                    Node::Group(_) => false,
+                };
+                found && next(pos + 1)
+            }
+        }
+    }
+}
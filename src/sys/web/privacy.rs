@@ -0,0 +1,180 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, OnceLock},
+};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    log,
+    sys::db::{adapter::DB, pgsql::Transaction},
+};
+
+/// Data of the user from the application for `Privacy::export`, the value is added to the archive by the name of the provider
+pub type ExportFn = for<'a> fn(&'a mut Transaction, i64) -> Pin<Box<dyn Future<Output = Option<Value>> + Send + 'a>>;
+
+/// Erasure of the data of the user of the application for `Privacy::erase`, `false` rolls back the whole erasure
+pub type EraseFn = for<'a> fn(&'a mut Transaction, i64) -> Pin<Box<dyn Future<Output = bool> + Send + 'a>>;
+
+/// Data provider of the application, see `Server::privacy`
+#[derive(Debug, Clone)]
+pub(crate) struct PrivacyProvider {
+    pub name: String,
+    pub export: Option<ExportFn>,
+    pub erase: Option<EraseFn>,
+}
+
+/// Providers of the application, set at the start of the server
+static PROVIDERS: OnceLock<Vec<PrivacyProvider>> = OnceLock::new();
+
+/// Data of the library by the user, the name in the archive and the query with the JSON text
+const EXPORT: [(&str, &str); 5] = [
+    (
+        "user",
+        r#"SELECT row_to_json(t)::text FROM (SELECT user_id, enable, lang_id, "create", role_id, data FROM "user" WHERE user_id=$1) t"#,
+    ),
+    (
+        "sessions",
+        "SELECT COALESCE(json_agg(t), '[]')::text FROM (SELECT session_id, lang_id, created, last FROM session WHERE user_id=$1 ORDER BY session_id) t",
+    ),
+    (
+        "providers",
+        r#"SELECT COALESCE(json_agg(t), '[]')::text FROM (SELECT provider_id, enable, data, "update", expire FROM user_provider WHERE user_id=$1 ORDER BY user_provider_id) t"#,
+    ),
+    (
+        "mail",
+        r#"SELECT COALESCE(json_agg(t), '[]')::text FROM (SELECT mail_id, mail, "create" FROM mail WHERE user_id=$1 ORDER BY mail_id) t"#,
+    ),
+    (
+        "audit",
+        r#"SELECT COALESCE(json_agg(t), '[]')::text FROM (SELECT audit_id, action, data, "create" FROM audit WHERE user_id=$1 ORDER BY audit_id) t"#,
+    ),
+];
+
+/// Erasure of the data of the library, the user stays disabled without the profile for the references of the other tables
+const ERASE: [&str; 4] = [
+    "DELETE FROM session WHERE user_id=$1",
+    "DELETE FROM user_provider WHERE user_id=$1",
+    "DELETE FROM mail WHERE user_id=$1",
+    r#"UPDATE "user" SET enable=false, data='{}' WHERE user_id=$1"#,
+];
+
+/// Export and erasure of the personal data of the user, GDPR
+///
+/// # Example
+///
+/// ```ignore
+/// let archive = this.privacy.export(user_id).await?;
+/// this.response.file_name("data.json");
+/// Answer::String(archive.to_string())
+/// ```
+///
+/// The data of the application is added by the providers, see `Server::privacy`.
+/// Each export and erasure is written to the table `audit`.
+#[derive(Debug)]
+pub struct Privacy {
+    db: Arc<DB>,
+}
+
+impl Privacy {
+    pub(crate) fn new(db: Arc<DB>) -> Privacy {
+        Privacy { db }
+    }
+
+    /// Set the providers of the application, only once
+    pub(crate) fn register(providers: Vec<PrivacyProvider>) {
+        let _ = PROVIDERS.set(providers);
+    }
+
+    /// JSON archive of the data of the user: the profile, the sessions, the providers, the mail, the audit
+    /// and the data of the providers of the application
+    ///
+    /// The data is read in one transaction. `None` if the user is not found or any query failed.
+    pub async fn export(&self, user_id: i64) -> Option<Value> {
+        let mut tx = self.db.begin().await?;
+        let mut archive = Map::new();
+        for (name, sql) in EXPORT {
+            let value = Privacy::json(&mut tx, sql, user_id).await?;
+            archive.insert(name.to_owned(), value);
+        }
+        if archive.get("user").is_none_or(Value::is_null) {
+            return None;
+        }
+        let mut application = Map::new();
+        for provider in PROVIDERS.get().into_iter().flatten() {
+            if let Some(export) = provider.export {
+                match export(&mut tx, user_id).await {
+                    Some(value) => {
+                        application.insert(provider.name.clone(), value);
+                    }
+                    None => {
+                        log!(warning, 0, "Privacy export provider {} failed", provider.name);
+                        return None;
+                    }
+                }
+            }
+        }
+        archive.insert("application".to_owned(), Value::Object(application));
+        Privacy::audit(&mut tx, user_id, "export", json!({})).await?;
+        tx.commit().await?;
+        Some(Value::Object(archive))
+    }
+
+    /// Erase the data of the user by the erasers of the application and the library in one transaction
+    ///
+    /// Returns `false` if the user is protected or not found, or any eraser failed, then nothing is erased.
+    pub async fn erase(&self, user_id: i64) -> bool {
+        self.try_erase(user_id).await.is_some()
+    }
+
+    async fn try_erase(&self, user_id: i64) -> Option<()> {
+        let mut tx = self.db.begin().await?;
+        let protect = Privacy::json(&mut tx, r#"SELECT to_json(protect)::text FROM "user" WHERE user_id=$1"#, user_id).await?;
+        if protect != Value::Bool(false) {
+            return None;
+        }
+        let mut erased = Vec::new();
+        for provider in PROVIDERS.get().into_iter().flatten() {
+            if let Some(erase) = provider.erase {
+                if !erase(&mut tx, user_id).await {
+                    log!(warning, 0, "Privacy eraser {} failed", provider.name);
+                    return None;
+                }
+                erased.push(provider.name.clone());
+            }
+        }
+        for sql in ERASE {
+            tx.execute(sql, &[&user_id]).await?;
+        }
+        Privacy::audit(&mut tx, user_id, "erase", json!({ "application": erased })).await?;
+        tx.commit().await
+    }
+
+    /// Record of the table `audit`
+    async fn audit(tx: &mut Transaction, user_id: i64, action: &str, data: Value) -> Option<()> {
+        tx.execute(
+            r#"INSERT INTO audit (user_id, action, data, "create") VALUES ($1, $2, $3::text::jsonb, now())"#,
+            &[&user_id, &action, &data.to_string()],
+        )
+        .await
+    }
+
+    /// Value of the JSON text of the first column, `Null` without rows
+    async fn json(tx: &mut Transaction, sql: &str, user_id: i64) -> Option<Value> {
+        #[cfg(feature = "row-data")]
+        let text = match tx.query(sql, &[&user_id], false).await?.first() {
+            Some(crate::sys::web::data::Data::Vec(row)) => match row.first() {
+                Some(crate::sys::web::data::Data::String(text)) => Some(text.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+        #[cfg(feature = "row-native")]
+        let text = tx.query(sql, &[&user_id]).await?.first().and_then(|row| row.try_get::<_, String>(0).ok());
+        match text {
+            Some(text) => serde_json::from_str(&text).ok(),
+            None => Some(Value::Null),
+        }
+    }
+}
@@ -0,0 +1,705 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use ring::rand::{SecureRandom, SystemRandom};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream},
+    net::TcpStream,
+    sync::{Mutex, MutexGuard},
+    time::{sleep, timeout},
+};
+
+use crate::{
+    log,
+    sys::{app::init::RedisConfig, net::eyeballs::HappyEyeballs},
+    tool::to_hex,
+};
+
+use super::{clock::Clock, data::Data, flight::Flight};
+
+#[cfg(feature = "otel")]
+use crate::sys::otel::Span;
+
+#[cfg(feature = "profile")]
+use crate::sys::profile::Frame;
+
+/// Max size of the bulk string of the answer, the limit of Redis
+const MAX_BULK: usize = 512 * 1024 * 1024;
+
+/// Number of the keys of the one step of SCAN
+const SCAN_COUNT: &[u8] = b"1000";
+
+/// Answer of Redis, RESP2
+#[derive(Debug)]
+enum Reply {
+    Status,
+    Error(String),
+    Int,
+    Bulk(Option<Vec<u8>>),
+    Array(Option<Vec<Reply>>),
+}
+
+type Connection = BufStream<TcpStream>;
+
+/// Element of the cache in the memory
+#[derive(Debug)]
+struct LocalEntry {
+    data: Data,
+    expire: Instant,
+    /// Key of the order of the use
+    tick: u64,
+}
+
+#[derive(Debug, Default)]
+struct LocalState {
+    data: HashMap<String, LocalEntry>,
+    /// Order of the use, the first is the least recently used
+    order: BTreeMap<u64, String>,
+    tick: u64,
+    /// Changed by each change, the value read from Redis before it is not kept
+    version: u64,
+}
+
+/// Cache in the memory of the instance before Redis, LRU, the parameter [redis] l1
+#[derive(Debug)]
+struct Local {
+    max: usize,
+    ttl: Duration,
+    state: std::sync::Mutex<LocalState>,
+}
+
+impl Local {
+    fn state(&self) -> std::sync::MutexGuard<'_, LocalState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(e) => e.into_inner(),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<Data> {
+        let mut state = self.state();
+        let state = &mut *state;
+        let entry = state.data.get_mut(key)?;
+        if entry.expire <= Clock::instant() {
+            state.order.remove(&entry.tick);
+            state.data.remove(key);
+            return None;
+        }
+        state.order.remove(&entry.tick);
+        state.tick += 1;
+        entry.tick = state.tick;
+        state.order.insert(state.tick, key.to_owned());
+        Some(entry.data.clone())
+    }
+
+    fn version(&self) -> u64 {
+        self.state().version
+    }
+
+    /// Keep the value read from Redis, if there were no changes after `version`
+    fn keep(&self, key: &str, data: Data, version: u64) {
+        let mut state = self.state();
+        if state.version == version {
+            self.insert(&mut state, key, data, None);
+        }
+    }
+
+    /// Keep the value written to Redis
+    fn put(&self, key: &str, data: Data, ttl: Option<Duration>) {
+        let mut state = self.state();
+        state.version += 1;
+        self.insert(&mut state, key, data, ttl);
+    }
+
+    fn insert(&self, state: &mut LocalState, key: &str, data: Data, ttl: Option<Duration>) {
+        state.tick += 1;
+        let entry = LocalEntry {
+            data,
+            expire: Clock::instant() + ttl.map_or(self.ttl, |ttl| ttl.min(self.ttl)),
+            tick: state.tick,
+        };
+        if let Some(old) = state.data.insert(key.to_owned(), entry) {
+            state.order.remove(&old.tick);
+        }
+        state.order.insert(state.tick, key.to_owned());
+        while state.data.len() > self.max {
+            match state.order.pop_first() {
+                Some((_, key)) => state.data.remove(&key),
+                None => break,
+            };
+        }
+    }
+
+    /// Remove the key, the group with the trailing ":", all with the empty key
+    fn remove(&self, key: &str) {
+        let mut state = self.state();
+        let state = &mut *state;
+        state.version += 1;
+        if key.is_empty() {
+            state.data.clear();
+            state.order.clear();
+        } else if key.ends_with(':') {
+            let order = &mut state.order;
+            state.data.retain(|name, entry| {
+                let keep = !name.starts_with(key);
+                if !keep {
+                    order.remove(&entry.tick);
+                }
+                keep
+            });
+        } else if let Some(entry) = state.data.remove(key) {
+            state.order.remove(&entry.tick);
+        }
+    }
+}
+
+/// Cache shared by the instances in Redis, the feature "cache-redis"
+///
+/// The same API as the memory cache: the key "user:1:name" is in the groups "user:" and "user:1:",
+/// `remove("user:1:")` removes the group. The keys in Redis are "<prefix>:<key>", the values are `Data` by bincode.
+/// The group is removed by SCAN, so the removal of the large group is slower than in the memory.
+/// The errors of Redis are logged, then the value is missing and the request goes on without the cache.
+///
+/// With the parameter [redis] l1 the values are kept in the memory of the instance too. The writing goes to Redis
+/// and the memory, the other instances remove the key from the memory by the message of PUBLISH.
+/// The element in the memory lives not longer than l1_ttl, the limit of the staleness if the message is lost.
+#[derive(Debug)]
+pub struct Cache {
+    config: Arc<RedisConfig>,
+    /// Connections opened on demand, `None` is closed
+    connections: Vec<Mutex<Option<Connection>>>,
+    /// Connection to wait for when all are busy
+    next: AtomicUsize,
+    local: Option<Arc<Local>>,
+    /// Channel of the invalidation of the memory, "<prefix>:l1"
+    channel: String,
+    /// Id of the instance, the own messages of the channel are skipped
+    node: String,
+    flight: Flight,
+}
+
+impl Cache {
+    /// Delay of the reconnection of the subscription after the error
+    const RECONNECT: Duration = Duration::from_secs(5);
+
+    pub(crate) fn new(config: Arc<RedisConfig>) -> Cache {
+        let mut node = [0u8; 8];
+        let _ = SystemRandom::new().fill(&mut node);
+        let cache = Cache {
+            connections: (0..config.max).map(|_| Mutex::new(None)).collect(),
+            local: (config.l1 > 0).then(|| {
+                Arc::new(Local {
+                    max: config.l1,
+                    ttl: config.l1_ttl,
+                    state: std::sync::Mutex::new(LocalState::default()),
+                })
+            }),
+            channel: format!("{}:l1", config.prefix),
+            node: to_hex(&node),
+            config,
+            next: AtomicUsize::new(0),
+            flight: Flight::default(),
+        };
+        if let Some(local) = &cache.local {
+            tokio::spawn(Cache::subscribe(Arc::clone(&cache.config), Arc::clone(local), cache.channel.clone(), cache.node.clone()));
+        }
+        cache
+    }
+
+    /// Single flight of the missing keys with the prefixes, see `get_or_load`
+    pub(crate) fn coalesce(&mut self, prefix: Vec<String>, wait: Duration) {
+        self.flight = Flight::new(prefix, wait);
+    }
+
+    pub(crate) fn flight(&self) -> &Flight {
+        &self.flight
+    }
+
+    /// Get cache
+    pub async fn get(&self, key: &str) -> Option<Data> {
+        #[cfg(feature = "otel")]
+        let mut span = Span::internal("cache.get");
+        #[cfg(feature = "profile")]
+        let _frame = Frame::start("cache.get");
+        if !Cache::is_key(key) {
+            return None;
+        }
+        let version = match &self.local {
+            Some(local) => match local.get(key) {
+                Some(data) => {
+                    #[cfg(feature = "otel")]
+                    span.attr("cache.hit", true);
+                    return Some(data);
+                }
+                None => local.version(),
+            },
+            None => 0,
+        };
+        let data = match self.command(&[b"GET", &self.key(key)]).await? {
+            Reply::Bulk(Some(value)) => Cache::decode(&value),
+            _ => None,
+        };
+        if let (Some(local), Some(data)) = (&self.local, &data) {
+            local.keep(key, data.clone(), version);
+        }
+        #[cfg(feature = "otel")]
+        span.attr("cache.hit", data.is_some());
+        data
+    }
+
+    /// Get cache or load and set the missing value with the time to live of the parameter [redis] ttl
+    ///
+    /// With the prefix of the key in [web] coalesce only the first request of the instance loads the missing value,
+    /// the concurrent requests of the same key wait for it. `None` of `load` is not cached.
+    pub async fn get_or_load<F, Fut>(&self, key: &str, load: F) -> Option<Data>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Option<Data>>,
+    {
+        if let Some(data) = self.get(key).await {
+            return Some(data);
+        }
+        let _guard = if self.flight.is_on(key) {
+            loop {
+                match self.flight.lead(key) {
+                    Some(guard) => {
+                        if let Some(data) = self.get(key).await {
+                            return Some(data);
+                        }
+                        break Some(guard);
+                    }
+                    None => {
+                        if self.flight.wait(key).await {
+                            if let Some(data) = self.get(key).await {
+                                return Some(data);
+                            }
+                            // The leader has not loaded the value, load it without the flight
+                            break None;
+                        }
+                    }
+                }
+            }
+        } else {
+            None
+        };
+        let data = load().await?;
+        self.set(key, data.clone()).await;
+        Some(data)
+    }
+
+    /// Set cache with the time to live of the parameter [redis] ttl
+    ///
+    /// Returns the previous value, SET with GET of Redis 6.2.
+    pub async fn set(&self, key: &str, data: impl Into<Data>) -> Option<Data> {
+        self.set_expire(key, data.into(), self.config.ttl).await
+    }
+
+    /// Set cache with the own time to live
+    pub async fn set_ttl(&self, key: &str, data: impl Into<Data>, ttl: Duration) -> Option<Data> {
+        self.set_expire(key, data.into(), Some(ttl)).await
+    }
+
+    /// Set cache with the tags, all keys of the tag are removed by `invalidate_tag`
+    ///
+    /// The tag is the set "<prefix>::tag:<tag>" of Redis with the keys, it is kept until `invalidate_tag` or `clear`.
+    /// The tags are added to the previous tags of the key.
+    pub async fn set_with_tags(&self, key: &str, data: impl Into<Data>, tags: &[&str]) -> Option<Data> {
+        if !Cache::is_key(key) {
+            log!(warning, 0, "{}", key);
+            return None;
+        }
+        let data = self.set_expire(key, data.into(), self.config.ttl).await;
+        for tag in tags.iter().filter(|tag| !tag.is_empty()) {
+            self.command(&[b"SADD", &self.tag(tag), key.as_bytes()]).await;
+        }
+        data
+    }
+
+    /// Remove all keys of the tag on all instances, see `set_with_tags`
+    pub async fn invalidate_tag(&self, tag: &str) {
+        if tag.is_empty() {
+            return;
+        }
+        let tag = self.tag(tag);
+        let keys = match self.command(&[b"SMEMBERS", &tag]).await {
+            Some(Reply::Array(Some(list))) => list
+                .into_iter()
+                .filter_map(|key| match key {
+                    Reply::Bulk(Some(key)) => String::from_utf8(key).ok(),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let names: Vec<Vec<u8>> = keys.iter().map(|key| self.key(key)).collect();
+        for names in names.chunks(1000) {
+            let mut args: Vec<&[u8]> = Vec::with_capacity(names.len() + 1);
+            args.push(b"UNLINK");
+            args.extend(names.iter().map(|name| name.as_slice()));
+            self.command(&args).await;
+        }
+        self.command(&[b"UNLINK", &tag]).await;
+        if let Some(local) = &self.local {
+            for key in &keys {
+                local.remove(key);
+                self.publish(key).await;
+            }
+        }
+    }
+
+    async fn set_expire(&self, key: &str, data: Data, ttl: Option<Duration>) -> Option<Data> {
+        if !Cache::is_key(key) {
+            log!(warning, 0, "{}", key);
+            return None;
+        }
+        let value = match bincode::serialize(&data) {
+            Ok(value) => value,
+            Err(_e) => {
+                log!(warning, 0, "{}. Error: {}", key, _e);
+                return None;
+            }
+        };
+        let name = self.key(key);
+        let ms = ttl.map(|ttl| ttl.as_millis().max(1).to_string());
+        let reply = match &ms {
+            Some(ms) => self.command(&[b"SET", &name, &value, b"GET", b"PX", ms.as_bytes()]).await?,
+            None => self.command(&[b"SET", &name, &value, b"GET"]).await?,
+        };
+        if let Some(local) = &self.local {
+            local.put(key, data, ttl);
+            self.publish(key).await;
+        }
+        match reply {
+            Reply::Bulk(Some(value)) => Cache::decode(&value),
+            _ => None,
+        }
+    }
+
+    /// Number of the elements
+    #[cfg(feature = "admin")]
+    pub(crate) async fn len(&self) -> usize {
+        let mut len = 0;
+        let tags = format!("{}::", self.config.prefix).into_bytes();
+        self.scan("", |keys| {
+            len += keys.iter().filter(|key| !key.starts_with(&tags)).count();
+            true
+        })
+        .await;
+        len
+    }
+
+    /// The group, for example "user:1:", has the data
+    #[cfg(any(feature = "session-memory", feature = "session-file", feature = "session-db"))]
+    pub(crate) async fn contains_group(&self, key: &str) -> bool {
+        if key.len() < 2 || !key.ends_with(':') || !Cache::is_key(&key[..key.len() - 1]) {
+            return false;
+        }
+        let mut found = false;
+        self.scan(key, |keys| {
+            found = !keys.is_empty();
+            !found
+        })
+        .await;
+        found
+    }
+
+    /// Remove cache
+    /// If `key` is &str and ends with a `:` character, all data beginning with that `key` is deleted.
+    pub async fn remove(&self, key: &str) {
+        match key.strip_suffix(':') {
+            Some(group) if Cache::is_key(group) => {
+                let mut list = Vec::new();
+                self.scan(key, |keys| {
+                    list.extend(keys);
+                    true
+                })
+                .await;
+                for keys in list.chunks(1000) {
+                    let mut args: Vec<&[u8]> = Vec::with_capacity(keys.len() + 1);
+                    args.push(b"UNLINK");
+                    args.extend(keys.iter().map(|key| key.as_slice()));
+                    self.command(&args).await;
+                }
+            }
+            None if Cache::is_key(key) => {
+                self.command(&[b"UNLINK", &self.key(key)]).await;
+            }
+            _ => {
+                log!(warning, 0, "{}", key);
+                return;
+            }
+        }
+        if let Some(local) = &self.local {
+            local.remove(key);
+            self.publish(key).await;
+        }
+    }
+
+    /// Clear all cache of the application
+    pub async fn clear(&mut self) {
+        let mut list = Vec::new();
+        self.scan("", |keys| {
+            list.extend(keys);
+            true
+        })
+        .await;
+        for keys in list.chunks(1000) {
+            let mut args: Vec<&[u8]> = Vec::with_capacity(keys.len() + 1);
+            args.push(b"UNLINK");
+            args.extend(keys.iter().map(|key| key.as_slice()));
+            self.command(&args).await;
+        }
+        if let Some(local) = &self.local {
+            local.remove("");
+            self.publish("").await;
+        }
+    }
+
+    /// Remove the key from the memory of the other instances, the empty key removes all
+    async fn publish(&self, key: &str) {
+        let message = format!("{}\n{}", self.node, key);
+        self.command(&[b"PUBLISH", self.channel.as_bytes(), message.as_bytes()]).await;
+    }
+
+    /// Remove the keys of the messages of the other instances from the memory until the stop of the server
+    async fn subscribe(config: Arc<RedisConfig>, local: Arc<Local>, channel: String, node: String) {
+        loop {
+            if let Err(_e) = Cache::listen(&config, &local, &channel, &node).await {
+                log!(warning, 0, "Redis {}:{}. Error: {}", config.host, config.port, _e);
+            }
+            sleep(Cache::RECONNECT).await;
+        }
+    }
+
+    /// Messages of the subscription until the break of the connection, the memory is cleared after the connection
+    async fn listen(config: &RedisConfig, local: &Local, channel: &str, node: &str) -> Result<(), String> {
+        let mut stream = Cache::connect(config).await?;
+        Cache::write(&mut stream, &[b"SUBSCRIBE", channel.as_bytes()]).await?;
+        if let Reply::Error(e) = Cache::read(&mut stream).await? {
+            return Err(e);
+        }
+        local.remove("");
+        loop {
+            if let Reply::Array(Some(list)) = Cache::read(&mut stream).await? {
+                if let [Reply::Bulk(Some(kind)), _, Reply::Bulk(Some(message))] = list.as_slice() {
+                    if kind == b"message" {
+                        let message = String::from_utf8_lossy(message);
+                        match message.split_once('\n') {
+                            Some((from, _)) if from == node => {}
+                            Some((_, key)) => local.remove(key),
+                            None => {}
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The key has no empty parts, as in the memory cache
+    fn is_key(key: &str) -> bool {
+        !key.is_empty() && !key.starts_with(':') && !key.ends_with(':') && !key.contains("::")
+    }
+
+    /// Key of Redis with the prefix of the application
+    fn key(&self, key: &str) -> Vec<u8> {
+        format!("{}:{}", self.config.prefix, key).into_bytes()
+    }
+
+    /// Set of the keys of the tag, "::" is not in the keys of the cache
+    fn tag(&self, tag: &str) -> Vec<u8> {
+        format!("{}::tag:{}", self.config.prefix, tag).into_bytes()
+    }
+
+    fn decode(value: &[u8]) -> Option<Data> {
+        match bincode::deserialize::<Data>(value) {
+            Ok(data) => Some(data),
+            Err(_e) => {
+                log!(warning, 0, "{}", _e);
+                None
+            }
+        }
+    }
+
+    /// Keys of the group by the steps of SCAN, `f` returns `false` to stop
+    async fn scan(&self, group: &str, mut f: impl FnMut(Vec<Vec<u8>>) -> bool) {
+        let mut pattern = format!("{}:", self.config.prefix);
+        for c in group.chars() {
+            if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                pattern.push('\\');
+            }
+            pattern.push(c);
+        }
+        pattern.push('*');
+        let mut cursor = b"0".to_vec();
+        loop {
+            let reply = match self.command(&[b"SCAN", &cursor, b"MATCH", pattern.as_bytes(), b"COUNT", SCAN_COUNT]).await {
+                Some(reply) => reply,
+                None => return,
+            };
+            let (next, keys) = match reply {
+                Reply::Array(Some(mut list)) if list.len() == 2 => match (list.remove(0), list.remove(0)) {
+                    (Reply::Bulk(Some(next)), Reply::Array(Some(keys))) => (
+                        next,
+                        keys.into_iter()
+                            .filter_map(|key| match key {
+                                Reply::Bulk(Some(key)) => Some(key),
+                                _ => None,
+                            })
+                            .collect(),
+                    ),
+                    _ => return,
+                },
+                _ => return,
+            };
+            if !f(keys) || next == b"0" {
+                return;
+            }
+            cursor = next;
+        }
+    }
+
+    /// PING at the start during [redis] wait with the delay from 0.5 to 10 seconds, `true` without [redis] wait
+    pub(crate) async fn wait(&self) -> bool {
+        let end = match self.config.wait {
+            Some(wait) => Instant::now() + wait,
+            None => return true,
+        };
+        let mut delay = Duration::from_millis(500);
+        loop {
+            if matches!(self.command(&[b"PING"]).await, Some(Reply::Status)) {
+                return true;
+            }
+            let left = end.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                log!(stop, 0, "Redis {}:{}. Немає з'єднання після [redis] wait", self.config.host, self.config.port);
+                return false;
+            }
+            log!(warning, 0, "Redis {}:{}. Немає з'єднання, повтор через {:?}", self.config.host, self.config.port, delay.min(left));
+            sleep(delay.min(left)).await;
+            delay = (delay * 2).min(Duration::from_secs(10));
+        }
+    }
+
+    /// Run the command on the free connection, the connection is closed after the error of the network
+    async fn command(&self, args: &[&[u8]]) -> Option<Reply> {
+        let mut connection = self.free().await;
+        match timeout(self.config.timeout, Cache::exec(&self.config, &mut connection, args)).await {
+            Ok(Ok(Reply::Error(_e))) => {
+                log!(warning, 0, "Redis. Error: {}", _e);
+                None
+            }
+            Ok(Ok(reply)) => Some(reply),
+            Ok(Err(_e)) => {
+                log!(warning, 0, "Redis {}:{}. Error: {}", self.config.host, self.config.port, _e);
+                *connection = None;
+                None
+            }
+            Err(_e) => {
+                log!(warning, 0, "Redis {}:{}. Error: {}", self.config.host, self.config.port, _e);
+                *connection = None;
+                None
+            }
+        }
+    }
+
+    /// Free connection, or the next one by the turn
+    async fn free(&self) -> MutexGuard<'_, Option<Connection>> {
+        for connection in &self.connections {
+            if let Ok(connection) = connection.try_lock() {
+                return connection;
+            }
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.connections.len();
+        self.connections[index].lock().await
+    }
+
+    async fn exec(config: &RedisConfig, connection: &mut Option<Connection>, args: &[&[u8]]) -> Result<Reply, String> {
+        let stream = match connection {
+            Some(stream) => stream,
+            None => connection.insert(Cache::connect(config).await?),
+        };
+        Cache::write(stream, args).await?;
+        Cache::read(stream).await
+    }
+
+    async fn connect(config: &RedisConfig) -> Result<Connection, String> {
+        let tcp = HappyEyeballs::connect(&config.host, config.port).await.map_err(|e| e.to_string())?;
+        tcp.set_nodelay(true).map_err(|e| e.to_string())?;
+        let mut stream = BufStream::new(tcp);
+        if let Some(pwd) = &config.pwd {
+            match &config.user {
+                Some(user) => Cache::write(&mut stream, &[b"AUTH", user.as_bytes(), pwd.as_bytes()]).await?,
+                None => Cache::write(&mut stream, &[b"AUTH", pwd.as_bytes()]).await?,
+            }
+            if let Reply::Error(e) = Cache::read(&mut stream).await? {
+                return Err(e);
+            }
+        }
+        if config.db > 0 {
+            Cache::write(&mut stream, &[b"SELECT", config.db.to_string().as_bytes()]).await?;
+            if let Reply::Error(e) = Cache::read(&mut stream).await? {
+                return Err(e);
+            }
+        }
+        Ok(stream)
+    }
+
+    /// Command as the array of the bulk strings
+    async fn write(stream: &mut Connection, args: &[&[u8]]) -> Result<(), String> {
+        let mut buf = Vec::with_capacity(16 + args.iter().map(|arg| arg.len() + 16).sum::<usize>());
+        buf.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+        for arg in args {
+            buf.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            buf.extend_from_slice(arg);
+            buf.extend_from_slice(b"\r\n");
+        }
+        stream.write_all(&buf).await.map_err(|e| e.to_string())?;
+        stream.flush().await.map_err(|e| e.to_string())
+    }
+
+    fn read(stream: &mut Connection) -> Pin<Box<dyn Future<Output = Result<Reply, String>> + Send + '_>> {
+        Box::pin(async move {
+            let mut line = Vec::new();
+            stream.read_until(b'\n', &mut line).await.map_err(|e| e.to_string())?;
+            let line = line.strip_suffix(b"\r\n").ok_or_else(|| "Connection is closed".to_owned())?;
+            let (kind, text) = line.split_first().ok_or_else(|| "Empty answer".to_owned())?;
+            let text = String::from_utf8_lossy(text).to_string();
+            match kind {
+                b'+' => Ok(Reply::Status),
+                b'-' => Ok(Reply::Error(text)),
+                b':' => text.parse::<i64>().map(|_| Reply::Int).map_err(|_| format!("Wrong answer :{}", text)),
+                b'$' => match text.parse::<i64>() {
+                    Ok(len) if len < 0 => Ok(Reply::Bulk(None)),
+                    Ok(len) if len as usize <= MAX_BULK => {
+                        let mut data = vec![0; len as usize + 2];
+                        stream.read_exact(&mut data).await.map_err(|e| e.to_string())?;
+                        data.truncate(len as usize);
+                        Ok(Reply::Bulk(Some(data)))
+                    }
+                    _ => Err(format!("Wrong answer ${}", text)),
+                },
+                b'*' => match text.parse::<i64>() {
+                    Ok(len) if len < 0 => Ok(Reply::Array(None)),
+                    Ok(len) => {
+                        let mut list = Vec::with_capacity((len as usize).min(1024));
+                        for _ in 0..len {
+                            list.push(Cache::read(stream).await?);
+                        }
+                        Ok(Reply::Array(Some(list)))
+                    }
+                    Err(_) => Err(format!("Wrong answer *{}", text)),
+                },
+                _ => Err(format!("Wrong answer {}", String::from_utf8_lossy(line))),
+            }
+        })
+    }
+}
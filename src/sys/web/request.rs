@@ -1,9 +1,27 @@
-use std::{collections::HashMap, net::IpAddr, path::PathBuf, str::FromStr, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    net::IpAddr,
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, LazyLock, Mutex,
+    },
+};
 
+use percent_encoding::percent_decode_str;
 use serde::Serialize;
 
-#[cfg(all(feature = "route-db", feature = "cache"))]
-use crate::log;
+#[cfg(feature = "file-disk")]
+use tokio::{fs::File, io::AsyncWriteExt};
+
+use crate::{log, sys::app::init::UploadConfig};
+
+use super::response::Cookie;
+
+#[cfg(feature = "file-disk")]
+use super::file::TempFile;
 
 #[cfg(all(feature = "route-db", feature = "cache"))]
 use super::cache::Cache;
@@ -28,6 +46,116 @@ impl HttpVersion {
     }
 }
 
+/// Kind of the client by the User-Agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientKind {
+    Browser,
+    /// Search engines, social networks, AI crawlers and HTTP libraries
+    Bot,
+    /// Uptime checkers and health probes
+    Monitoring,
+    Unknown,
+}
+
+impl ClientKind {
+    /// Uptime checkers, checked before the bots, many of them have "bot" in the name
+    const MONITORING: [&'static str; 22] = [
+        "uptimerobot",
+        "pingdom",
+        "statuscake",
+        "site24x7",
+        "newrelicpinger",
+        "datadog",
+        "kube-probe",
+        "elb-healthchecker",
+        "googlehc",
+        "betteruptime",
+        "better uptime",
+        "uptime-kuma",
+        "nagios",
+        "zabbix",
+        "prometheus",
+        "blackbox",
+        "checkly",
+        "freshping",
+        "hetrixtools",
+        "monit/",
+        "consul health",
+        "updown.io",
+    ];
+
+    /// Known crawlers and tools, the generic words are the last
+    const BOT: [&'static str; 43] = [
+        "googlebot",
+        "bingbot",
+        "yandex",
+        "baiduspider",
+        "duckduckbot",
+        "slurp",
+        "applebot",
+        "facebookexternalhit",
+        "twitterbot",
+        "linkedinbot",
+        "telegrambot",
+        "whatsapp",
+        "discordbot",
+        "slackbot",
+        "petalbot",
+        "semrushbot",
+        "ahrefsbot",
+        "mj12bot",
+        "dotbot",
+        "bytespider",
+        "gptbot",
+        "claudebot",
+        "ccbot",
+        "amazonbot",
+        "seznambot",
+        "sogou",
+        "ia_archiver",
+        "archive.org_bot",
+        "headlesschrome",
+        "phantomjs",
+        "curl/",
+        "wget/",
+        "python-requests",
+        "python-urllib",
+        "go-http-client",
+        "java/",
+        "libwww-perl",
+        "okhttp",
+        "scrapy",
+        "linkcheck",
+        "bot",
+        "crawl",
+        "spider",
+    ];
+
+    /// Engines of the browsers after "Mozilla/"
+    const BROWSER: [&'static str; 7] = ["chrome/", "firefox/", "safari/", "edg/", "opera", "msie", "trident/"];
+
+    /// Classify the User-Agent
+    pub fn from_agent(agent: &str) -> ClientKind {
+        let agent = agent.trim().to_lowercase();
+        if agent.is_empty() {
+            ClientKind::Unknown
+        } else if ClientKind::MONITORING.iter().any(|key| agent.contains(key)) {
+            ClientKind::Monitoring
+        } else if ClientKind::BOT.iter().any(|key| agent.contains(key)) {
+            ClientKind::Bot
+        } else if agent.starts_with("mozilla/") && ClientKind::BROWSER.iter().any(|key| agent.contains(key)) {
+            ClientKind::Browser
+        } else {
+            ClientKind::Unknown
+        }
+    }
+
+    /// Bots and monitoring, they do not get sessions
+    pub fn is_robot(&self) -> bool {
+        matches!(self, ClientKind::Bot | ClientKind::Monitoring)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum HttpMethod {
     Get,
@@ -61,6 +189,41 @@ impl FromStr for HttpMethod {
     }
 }
 
+impl HttpMethod {
+    /// Name of the method, for example "GET"
+    pub fn as_str(&self) -> &str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Head => "HEAD",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+            HttpMethod::Delete => "DELETE",
+            HttpMethod::Connect => "CONNECT",
+            HttpMethod::Options => "OPTIONS",
+            HttpMethod::Trace => "TRACE",
+            HttpMethod::Patch => "PATCH",
+            HttpMethod::Other(method) => method,
+        }
+    }
+
+    /// Methods with the own controllers, the suffix of the action name, for example `save_post`
+    pub(crate) const DISPATCH: [(&'static str, &'static str); 5] =
+        [("GET", "_get"), ("POST", "_post"), ("PUT", "_put"), ("DELETE", "_delete"), ("PATCH", "_patch")];
+
+    /// Suffix of the action name for this method, HEAD uses GET controller
+    pub(crate) fn suffix(&self) -> Option<&'static str> {
+        match self {
+            HttpMethod::Get | HttpMethod::Head => Some("_get"),
+            HttpMethod::Post => Some("_post"),
+            HttpMethod::Put => Some("_put"),
+            HttpMethod::Delete => Some("_delete"),
+            HttpMethod::Patch => Some("_patch"),
+            HttpMethod::Options => Some("_options"),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct WebFile {
     pub name: String,
@@ -72,6 +235,454 @@ pub struct WebFile {
     pub data: Vec<u8>,
 }
 
+/// Client and the value of the "X-Upload-Id" header
+pub(crate) type UploadKey = (String, String);
+
+/// Uploads in progress
+static UPLOAD: LazyLock<Mutex<HashMap<UploadKey, Arc<UploadProgress>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Progress of the upload
+#[derive(Debug)]
+pub struct UploadProgress {
+    /// Received bytes
+    pub received: AtomicUsize,
+    /// Content-Length of the request
+    pub total: usize,
+}
+
+impl UploadProgress {
+    /// Key of the upload of the request with the "X-Upload-Id" header
+    ///
+    /// The client is the session cookie, or the IP address without the session,
+    /// so the progress can't be read by the other client with the same id.
+    #[cfg(any(feature = "http", feature = "https", feature = "scgi", feature = "uwsgi"))]
+    pub(crate) fn key(request: &Request, session: Option<&str>) -> Option<UploadKey> {
+        let id = request.header("X-Upload-Id").filter(|id| !id.is_empty())?;
+        let client = match session {
+            Some(session) => session.to_owned(),
+            None => request.ip?.to_string(),
+        };
+        Some((client, id.to_owned()))
+    }
+
+    /// Get progress of the upload of the client by the value of the "X-Upload-Id" header
+    ///
+    /// Returns (received, total) while the request body is being received.
+    pub(crate) fn get(client: String, id: &str) -> Option<(usize, usize)> {
+        let list = match UPLOAD.lock() {
+            Ok(list) => list,
+            Err(_e) => {
+                log!(warning, 0, "{}", _e);
+                return None;
+            }
+        };
+        list.get(&(client, id.to_owned())).map(|progress| (progress.received.load(Ordering::Relaxed), progress.total))
+    }
+}
+
+/// Error of the multipart parser
+#[derive(Debug)]
+pub(crate) enum MultipartError {
+    /// File or body is too large, or too many files
+    Limit,
+    /// Content-Type of the file is not allowed
+    Mime,
+    /// Can't write temp file
+    #[cfg(feature = "file-disk")]
+    File,
+}
+
+impl MultipartError {
+    /// HTTP status code of the error
+    pub fn code(&self) -> u16 {
+        match self {
+            MultipartError::Limit => 413,
+            MultipartError::Mime => 415,
+            #[cfg(feature = "file-disk")]
+            MultipartError::File => 500,
+        }
+    }
+}
+
+impl Display for MultipartError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MultipartError::Limit => write!(f, "Upload limit exceeded"),
+            MultipartError::Mime => write!(f, "Upload file type is not allowed"),
+            #[cfg(feature = "file-disk")]
+            MultipartError::File => write!(f, "Can't write upload temp file"),
+        }
+    }
+}
+
+/// Part of the multipart body
+enum Part {
+    Post {
+        name: String,
+        data: Vec<u8>,
+    },
+    File {
+        name: String,
+        file: String,
+        size: usize,
+        #[cfg(feature = "file-disk")]
+        tmp: PathBuf,
+        #[cfg(feature = "file-disk")]
+        handle: File,
+        #[cfg(feature = "file-memory")]
+        data: Vec<u8>,
+    },
+}
+
+enum MultipartState {
+    /// Before the first boundary
+    Preamble,
+    /// After boundary, waiting "\r\n" or "--"
+    Boundary,
+    /// Headers of the part
+    Header,
+    /// Body of the part
+    Body(Part),
+    /// After the last boundary
+    End,
+}
+
+/// Incremental parser of the multipart/form-data body
+///
+/// Files are written to disk as soon as the data comes, without buffering the entire body.
+pub(crate) struct Multipart {
+    /// "\r\n--" + boundary
+    boundary: Vec<u8>,
+    /// Unprocessed data
+    buf: Vec<u8>,
+    state: MultipartState,
+    post: HashMap<String, String>,
+    file: Vec<WebFile>,
+    /// Size of the received body
+    size: usize,
+    progress: Option<(UploadKey, Arc<UploadProgress>)>,
+    upload: Arc<UploadConfig>,
+}
+
+impl Multipart {
+    /// Creates a parser, if the content type is `multipart/form-data`
+    ///
+    /// `key` is the key of the progress of the upload, see `UploadProgress::key`.
+    pub fn new(content_type: Option<&str>, content_len: usize, key: Option<UploadKey>, upload: &Arc<UploadConfig>) -> Option<Multipart> {
+        let boundary = Multipart::boundary(content_type?)?;
+        let mut buf = Vec::with_capacity(8192);
+        // The first boundary may not have the leading "\r\n"
+        buf.extend_from_slice(b"\r\n");
+        let progress = match key {
+            Some(key) => {
+                let progress = Arc::new(UploadProgress {
+                    received: AtomicUsize::new(0),
+                    total: content_len,
+                });
+                match UPLOAD.lock() {
+                    Ok(mut list) => {
+                        list.insert(key.clone(), Arc::clone(&progress));
+                        Some((key, progress))
+                    }
+                    Err(_e) => {
+                        log!(warning, 0, "{}", _e);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        Some(Multipart {
+            boundary: format!("\r\n--{}", boundary).into_bytes(),
+            buf,
+            state: MultipartState::Preamble,
+            post: HashMap::new(),
+            file: Vec::new(),
+            size: 0,
+            progress,
+            upload: Arc::clone(upload),
+        })
+    }
+
+    /// Adds the next chunk of the body
+    pub async fn push(&mut self, data: &[u8]) -> Result<(), MultipartError> {
+        self.size += data.len();
+        if let Some((_, progress)) = &self.progress {
+            progress.received.store(self.size, Ordering::Relaxed);
+        }
+        if self.size > self.upload.max_body_size {
+            return Err(MultipartError::Limit);
+        }
+        self.buf.extend_from_slice(data);
+
+        loop {
+            match &mut self.state {
+                MultipartState::Preamble => match Multipart::find(&self.buf, &self.boundary) {
+                    Some(pos) => {
+                        self.buf.drain(..pos + self.boundary.len());
+                        self.state = MultipartState::Boundary;
+                    }
+                    None => {
+                        let keep = self.boundary.len() - 1;
+                        if self.buf.len() > keep {
+                            self.buf.drain(..self.buf.len() - keep);
+                        }
+                        return Ok(());
+                    }
+                },
+                MultipartState::Boundary => {
+                    if self.buf.len() < 2 {
+                        return Ok(());
+                    }
+                    if &self.buf[..2] == b"--" {
+                        self.buf.clear();
+                        self.state = MultipartState::End;
+                    } else {
+                        self.buf.drain(..2);
+                        self.state = MultipartState::Header;
+                    }
+                }
+                MultipartState::Header => match Multipart::find(&self.buf, b"\r\n\r\n") {
+                    Some(pos) => {
+                        let part = Multipart::part(&self.buf[..pos], &self.upload, self.file.len()).await?;
+                        self.buf.drain(..pos + 4);
+                        self.state = MultipartState::Body(part);
+                    }
+                    None => {
+                        if self.buf.len() > 16384 {
+                            return Err(MultipartError::Limit);
+                        }
+                        return Ok(());
+                    }
+                },
+                MultipartState::Body(part) => match Multipart::find(&self.buf, &self.boundary) {
+                    Some(pos) => {
+                        Multipart::write(part, &self.buf[..pos], &self.upload).await?;
+                        self.buf.drain(..pos + self.boundary.len());
+                        if let MultipartState::Body(part) = std::mem::replace(&mut self.state, MultipartState::Boundary) {
+                            self.complete(part).await?;
+                        }
+                    }
+                    None => {
+                        let keep = self.boundary.len() - 1;
+                        if self.buf.len() > keep {
+                            let len = self.buf.len() - keep;
+                            Multipart::write(part, &self.buf[..len], &self.upload).await?;
+                            self.buf.drain(..len);
+                        }
+                        return Ok(());
+                    }
+                },
+                MultipartState::End => {
+                    self.buf.clear();
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Finishes parsing
+    pub async fn finish(mut self) -> (HashMap<String, String>, Vec<WebFile>) {
+        self.remove_progress();
+        if let MultipartState::Body(part) = std::mem::replace(&mut self.state, MultipartState::End) {
+            // The body ended without the final boundary
+            if self.complete(part).await.is_err() {
+                self.abort().await;
+                return (HashMap::new(), Vec::new());
+            }
+        }
+        (self.post, self.file)
+    }
+
+    /// Stops parsing and removes temp files
+    pub async fn abort(mut self) {
+        self.remove_progress();
+        #[cfg(feature = "file-disk")]
+        {
+            if let MultipartState::Body(Part::File { tmp, handle, .. }) = std::mem::replace(&mut self.state, MultipartState::End) {
+                drop(handle);
+                let _ = tokio::fs::remove_file(tmp).await;
+            }
+            for f in self.file {
+                let _ = tokio::fs::remove_file(f.tmp).await;
+            }
+        }
+    }
+
+    fn remove_progress(&mut self) {
+        if let Some((key, _)) = self.progress.take() {
+            match UPLOAD.lock() {
+                Ok(mut list) => {
+                    list.remove(&key);
+                }
+                Err(_e) => {
+                    log!(warning, 0, "{}", _e);
+                }
+            }
+        }
+    }
+
+    /// Boundary of the `multipart/form-data` content type
+    ///
+    /// The type and the names of the parameters are case-insensitive, the boundary may be quoted.
+    fn boundary(content_type: &str) -> Option<&str> {
+        let mut params = content_type.split(';');
+        if !params.next()?.trim().eq_ignore_ascii_case("multipart/form-data") {
+            return None;
+        }
+        params
+            .find_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                if !name.trim().eq_ignore_ascii_case("boundary") {
+                    return None;
+                }
+                let value = value.trim();
+                Some(value.strip_prefix('"').and_then(|value| value.strip_suffix('"')).unwrap_or(value))
+            })
+            .filter(|boundary| !boundary.is_empty())
+    }
+
+    /// Creates new part from its headers
+    async fn part(header: &[u8], upload: &UploadConfig, files: usize) -> Result<Part, MultipartError> {
+        let header = String::from_utf8_lossy(header);
+        let mut name = String::new();
+        let mut file = None;
+        let mut mime = None;
+        for line in header.split("\r\n") {
+            let (key, value) = match line.split_once(':') {
+                Some(line) => line,
+                None => continue,
+            };
+            if key.trim().eq_ignore_ascii_case("content-type") {
+                mime = Some(value.trim().to_owned());
+                continue;
+            }
+            if !key.trim().eq_ignore_ascii_case("content-disposition") {
+                continue;
+            }
+            for item in value.split(';') {
+                match item.trim().split_once('=') {
+                    Some(("name", value)) => name = value.trim_matches('"').to_owned(),
+                    Some(("filename", value)) => file = Some(value.trim_matches('"').to_owned()),
+                    _ => {}
+                }
+            }
+        }
+        match file {
+            Some(file) => {
+                if files >= upload.max_files {
+                    return Err(MultipartError::Limit);
+                }
+                if !upload.allow_mime(mime.as_deref().unwrap_or("application/octet-stream")) {
+                    return Err(MultipartError::Mime);
+                }
+                #[cfg(feature = "file-disk")]
+                let tmp = TempFile::new_name();
+                #[cfg(feature = "file-disk")]
+                let handle = match File::create(&tmp).await {
+                    Ok(handle) => handle,
+                    Err(_e) => {
+                        log!(warning, 0, "{}. Error: {}", tmp.display(), _e);
+                        return Err(MultipartError::File);
+                    }
+                };
+                Ok(Part::File {
+                    name,
+                    file,
+                    size: 0,
+                    #[cfg(feature = "file-disk")]
+                    tmp,
+                    #[cfg(feature = "file-disk")]
+                    handle,
+                    #[cfg(feature = "file-memory")]
+                    data: Vec::new(),
+                })
+            }
+            None => Ok(Part::Post { name, data: Vec::new() }),
+        }
+    }
+
+    /// Writes data of the part
+    async fn write(part: &mut Part, chunk: &[u8], upload: &UploadConfig) -> Result<(), MultipartError> {
+        match part {
+            Part::Post { data, .. } => {
+                if data.len() + chunk.len() > upload.max_file_size {
+                    return Err(MultipartError::Limit);
+                }
+                data.extend_from_slice(chunk);
+            }
+            Part::File {
+                size,
+                #[cfg(feature = "file-disk")]
+                handle,
+                #[cfg(feature = "file-memory")]
+                data,
+                ..
+            } => {
+                *size += chunk.len();
+                if *size > upload.max_file_size {
+                    return Err(MultipartError::Limit);
+                }
+                #[cfg(feature = "file-disk")]
+                if let Err(_e) = handle.write_all(chunk).await {
+                    log!(warning, 0, "{}", _e);
+                    return Err(MultipartError::File);
+                }
+                #[cfg(feature = "file-memory")]
+                data.extend_from_slice(chunk);
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves the finished part
+    async fn complete(&mut self, part: Part) -> Result<(), MultipartError> {
+        match part {
+            Part::Post { name, data } => {
+                if let Ok(value) = String::from_utf8(data) {
+                    self.post.insert(name, value);
+                }
+            }
+            Part::File {
+                name,
+                file,
+                size,
+                #[cfg(feature = "file-disk")]
+                tmp,
+                #[cfg(feature = "file-disk")]
+                mut handle,
+                #[cfg(feature = "file-memory")]
+                data,
+            } => {
+                #[cfg(feature = "file-disk")]
+                if let Err(_e) = handle.flush().await {
+                    log!(warning, 0, "{}. Error: {}", tmp.display(), _e);
+                    let _ = tokio::fs::remove_file(tmp).await;
+                    return Err(MultipartError::File);
+                }
+                self.file.push(WebFile {
+                    name,
+                    file,
+                    size,
+                    #[cfg(feature = "file-disk")]
+                    tmp,
+                    #[cfg(feature = "file-memory")]
+                    data,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Search the needle in the data
+    fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+        if data.len() < needle.len() {
+            return None;
+        }
+        data.windows(needle.len()).position(|window| window == needle)
+    }
+}
+
 #[derive(Debug)]
 pub enum RawData {
     None,
@@ -85,6 +696,8 @@ pub struct Input {
     pub file: Arc<Vec<WebFile>>,
     pub cookie: Arc<HashMap<String, String>>,
     pub params: Arc<HashMap<String, String>>,
+    /// Request headers in the order of the request
+    pub headers: Arc<Vec<(String, String)>>,
     pub raw: Arc<RawData>,
 }
 
@@ -103,6 +716,115 @@ pub struct Request {
     pub site: String,
     pub version: HttpVersion,
     pub content_type: Option<String>,
+    /// The HTTP connection is closed after the answer, the answer has "Connection: close"
+    pub(crate) close: bool,
+}
+
+impl Request {
+    /// Kind of the client by the User-Agent, for example to skip the sessions and the counters for the crawlers
+    pub fn client_kind(&self) -> ClientKind {
+        ClientKind::from_agent(&self.agent)
+    }
+
+    /// Get request header by name, for example "Range"
+    ///
+    /// Works with the HTTP protocol (RANGE) and with the CGI-like protocols (HTTP_RANGE).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_uppercase();
+        if let Some(value) = self.input.params.get(&name) {
+            return Some(value);
+        }
+        self.input.params.get(&format!("HTTP_{}", name.replace('-', "_"))).map(|value| value.as_str())
+    }
+
+    /// Get all cookies with the decoded values, without the session cookie
+    pub fn cookies(&self) -> HashMap<String, String> {
+        self.input
+            .cookie
+            .iter()
+            .map(|(name, value)| (percent_decode_str(name).decode_utf8_lossy().to_string(), Request::cookie_value(value)))
+            .collect()
+    }
+
+    /// Get the decoded value of the cookie
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        match self.input.cookie.get(name) {
+            Some(value) => Some(Request::cookie_value(value)),
+            None => self
+                .input
+                .cookie
+                .iter()
+                .find(|(key, _)| percent_decode_str(key).decode_utf8_lossy() == name)
+                .map(|(_, value)| Request::cookie_value(value)),
+        }
+    }
+
+    /// Get the value of the cookie signed with `Response::set_signed_cookie`, `None` if the signature is wrong
+    pub fn signed_cookie(&self, name: &str, salt: &str) -> Option<String> {
+        Cookie::verify(name, &self.cookie(name)?, salt)
+    }
+
+    /// Get the value of the cookie encrypted with `Response::set_encrypted_cookie`, `None` if the value is changed
+    pub fn encrypted_cookie(&self, name: &str, salt: &str) -> Option<String> {
+        Cookie::decrypt(name, &self.cookie(name)?, salt)
+    }
+
+    /// Decode the value, the quotes are removed
+    fn cookie_value(value: &str) -> String {
+        let value = value.trim();
+        let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+        percent_decode_str(value).decode_utf8_lossy().to_string()
+    }
+
+    /// Get all request headers and other variables of the web server
+    ///
+    /// The CGI-like variables "HTTP_X_REAL_IP" become "X-Real-Ip", other variables, for example "UWSGI_SCHEME", are kept as is.
+    pub fn headers(&self) -> HashMap<String, &str> {
+        let mut list = HashMap::with_capacity(self.input.params.len());
+        for (key, value) in self.input.params.iter() {
+            let name = match key.strip_prefix("HTTP_") {
+                Some(name) => Request::header_name(&name.replace('_', "-")),
+                None if key.contains('_') => key.to_owned(),
+                None => Request::header_name(key),
+            };
+            list.insert(name, value.as_str());
+        }
+        list
+    }
+
+    /// All request headers in the order of the request, with the duplicates
+    ///
+    /// HTTP keeps the original case of the names. The CGI-like protocols get the headers from the web server
+    /// as "HTTP_X_REAL_IP" variables, so the names become "X-Real-Ip" and the web server may join the duplicates.
+    pub fn headers_all(&self) -> &[(String, String)] {
+        &self.input.headers
+    }
+
+    /// Header name of the CGI-like variable, "HTTP_X_REAL_IP" to "X-Real-Ip", `None` for other variables
+    #[cfg_attr(not(feature = "fuzz"), allow(dead_code))]
+    pub(crate) fn cgi_header(key: &[u8]) -> Option<String> {
+        let name = match key {
+            b"CONTENT_TYPE" => "CONTENT-TYPE".to_owned(),
+            b"CONTENT_LENGTH" => "CONTENT-LENGTH".to_owned(),
+            _ => std::str::from_utf8(key.strip_prefix(b"HTTP_")?).ok()?.replace('_', "-"),
+        };
+        Some(Request::header_name(&name))
+    }
+
+    /// "X-REAL-IP" to "X-Real-Ip"
+    fn header_name(name: &str) -> String {
+        let mut res = String::with_capacity(name.len());
+        let mut upper = true;
+        for c in name.chars() {
+            if upper {
+                res.extend(c.to_uppercase());
+            } else {
+                res.extend(c.to_lowercase());
+            }
+            upper = c == '-';
+        }
+        res
+    }
 }
 
 #[derive(Debug, Clone)]
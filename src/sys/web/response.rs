@@ -1,12 +1,30 @@
+use std::fmt::Write;
+
 #[cfg(all(feature = "redirect-db", feature = "cache"))]
 use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    digest::{digest, SHA256},
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+
 #[cfg(all(feature = "redirect-db", feature = "cache"))]
 use crate::log;
 
+use crate::tool::{from_hex, to_hex};
+
 #[cfg(all(feature = "redirect-db", feature = "cache"))]
 use super::cache::Cache;
 
+use super::{
+    action::{Action, Answer},
+    controller::SizePolicy,
+};
+
 #[cfg(all(feature = "redirect-db", feature = "cache"))]
 use super::data::Data;
 
@@ -16,15 +34,440 @@ pub struct Redirect {
     pub permanently: bool,
 }
 
+/// Characters encoded in the cookie value
+const COOKIE_VALUE_ENCODE: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b',').add(b';').add(b'\\').add(b'%');
+
+/// Characters encoded in the cookie name
+const COOKIE_NAME_ENCODE: &AsciiSet = &COOKIE_VALUE_ENCODE
+    .add(b'=')
+    .add(b'(')
+    .add(b')')
+    .add(b'<')
+    .add(b'>')
+    .add(b'@')
+    .add(b':')
+    .add(b'/')
+    .add(b'[')
+    .add(b']')
+    .add(b'?')
+    .add(b'{')
+    .add(b'}');
+
+/// SameSite attribute of the cookie
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+/// Cookie for the `Set-Cookie` header
+///
+/// # Example
+///
+/// ```ignore
+/// let mut cookie = Cookie::new("theme", "dark");
+/// cookie.max_age = Some(86400 * 365);
+/// this.response.set_cookie(cookie);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cookie {
+    pub name: String,
+    /// The value is percent-encoded in the header
+    pub value: String,
+    pub path: Option<String>,
+    pub domain: Option<String>,
+    /// Max-Age in seconds, `Some(0)` removes the cookie, `None` is the session cookie
+    pub max_age: Option<i64>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+}
+
+impl Cookie {
+    /// New cookie with Path=/, HttpOnly and SameSite=Lax
+    pub fn new(name: &str, value: &str) -> Cookie {
+        Cookie {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            path: Some("/".to_owned()),
+            domain: None,
+            max_age: None,
+            secure: false,
+            http_only: true,
+            same_site: Some(SameSite::Lax),
+        }
+    }
+
+    /// Cookie which removes the cookie in the browser
+    pub fn remove(name: &str) -> Cookie {
+        let mut cookie = Cookie::new(name, "");
+        cookie.max_age = Some(0);
+        cookie
+    }
+
+    /// Key derived from the salt, `purpose` separates the keys of the signature and the encryption
+    fn key(salt: &str, purpose: &str) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        key.copy_from_slice(digest(&SHA256, format!("{}:{}", purpose, salt).as_bytes()).as_ref());
+        key
+    }
+
+    /// Signed value "value.signature", the signature is HMAC-SHA256 of the name and the value
+    pub(crate) fn sign(name: &str, value: &str, salt: &str) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, &Cookie::key(salt, "cookie-sign"));
+        let tag = hmac::sign(&key, format!("{}={}", name, value).as_bytes());
+        format!("{}.{}", value, to_hex(tag.as_ref()))
+    }
+
+    /// Check the signed value, `None` if the value is changed
+    pub(crate) fn verify(name: &str, value: &str, salt: &str) -> Option<String> {
+        let (value, tag) = value.rsplit_once('.')?;
+        let key = hmac::Key::new(hmac::HMAC_SHA256, &Cookie::key(salt, "cookie-sign"));
+        hmac::verify(&key, format!("{}={}", name, value).as_bytes(), &from_hex(tag)?).ok()?;
+        Some(value.to_owned())
+    }
+
+    /// Encrypted value, hex of the random nonce and AES-256-GCM of the value, the name is the additional data
+    pub(crate) fn encrypt(name: &str, value: &str, salt: &str) -> Option<String> {
+        let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &Cookie::key(salt, "cookie-encrypt")).ok()?);
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new().fill(&mut nonce).ok()?;
+        let mut data = value.as_bytes().to_vec();
+        key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(name.as_bytes()), &mut data).ok()?;
+        let mut res = nonce.to_vec();
+        res.extend_from_slice(&data);
+        Some(to_hex(&res))
+    }
+
+    /// Decrypt the value, `None` if the value is changed
+    pub(crate) fn decrypt(name: &str, value: &str, salt: &str) -> Option<String> {
+        let data = from_hex(value)?;
+        if data.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, data) = data.split_at(NONCE_LEN);
+        let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &Cookie::key(salt, "cookie-encrypt")).ok()?);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+        let mut data = data.to_vec();
+        let value = key.open_in_place(nonce, Aad::from(name.as_bytes()), &mut data).ok()?;
+        String::from_utf8(value.to_vec()).ok()
+    }
+
+    /// Value of the `Set-Cookie` header
+    pub(crate) fn header(&self) -> String {
+        let mut header =
+            format!("{}={}", utf8_percent_encode(&self.name, COOKIE_NAME_ENCODE), utf8_percent_encode(&self.value, COOKIE_VALUE_ENCODE));
+        if let Some(path) = &self.path {
+            let _ = write!(header, "; Path={}", path.replace(';', ""));
+        }
+        if let Some(domain) = &self.domain {
+            let _ = write!(header, "; Domain={}", domain.replace(';', ""));
+        }
+        if let Some(max_age) = self.max_age {
+            let _ = write!(header, "; Max-Age={}", max_age);
+        }
+        if self.secure || self.same_site == Some(SameSite::None) {
+            // Browsers reject SameSite=None without Secure
+            header.push_str("; Secure");
+        }
+        if self.http_only {
+            header.push_str("; HttpOnly");
+        }
+        match self.same_site {
+            Some(SameSite::Strict) => header.push_str("; SameSite=Strict"),
+            Some(SameSite::Lax) => header.push_str("; SameSite=Lax"),
+            Some(SameSite::None) => header.push_str("; SameSite=None"),
+            None => {}
+        }
+        header
+    }
+}
+
+/// ETag of the answer
+///
+/// # Example
+///
+/// ```ignore
+/// if this.etag(ETag::Weak(format!("{}-{}", article.id, article.version))) {
+///     return Answer::None;
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ETag {
+    /// `"value"`, the answer is the same byte by byte
+    Strong(String),
+    /// `W/"value"`, the answer is the same by the meaning
+    Weak(String),
+    /// Weak ETag of the hash of the answer, it is computed after the controller
+    Hash,
+}
+
+impl ETag {
+    /// Strong ETag of the version of the row, see `Action::etag_version`
+    pub fn version(version: i64) -> ETag {
+        ETag::Strong(version.to_string())
+    }
+
+    /// Value of the header, `None` until the hash is computed
+    pub(crate) fn header(&self) -> Option<String> {
+        match self {
+            ETag::Strong(value) => Some(format!("\"{}\"", value)),
+            ETag::Weak(value) => Some(format!("W/\"{}\"", value)),
+            ETag::Hash => None,
+        }
+    }
+
+    /// The list of the header If-None-Match has the same version, the weak comparison
+    pub(crate) fn matches(&self, list: &str) -> bool {
+        let value = match self {
+            ETag::Strong(value) | ETag::Weak(value) => value,
+            ETag::Hash => return false,
+        };
+        list.split(',').map(str::trim).any(|item| item == "*" || item.strip_prefix("W/").unwrap_or(item).trim_matches('"') == value)
+    }
+}
+
+/// Typed Cache-Control header
+///
+/// # Example
+///
+/// ```ignore
+/// this.cache_control(CacheControl::public().max_age(600).stale_while_revalidate(60));
+/// ```
+///
+/// `public` is sent as `private` when the answer sets a cookie or belongs to the authenticated user.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    public: bool,
+    private: bool,
+    no_cache: bool,
+    no_store: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+    stale_while_revalidate: Option<u64>,
+    stale_if_error: Option<u64>,
+    must_revalidate: bool,
+    immutable: bool,
+}
+
+impl CacheControl {
+    /// Shared caches and browsers can store the answer
+    pub fn public() -> CacheControl {
+        CacheControl { public: true, ..Default::default() }
+    }
+
+    /// Only the browser of the user can store the answer
+    pub fn private() -> CacheControl {
+        CacheControl { private: true, ..Default::default() }
+    }
+
+    /// The cache must revalidate the answer before each use
+    pub fn no_cache() -> CacheControl {
+        CacheControl { no_cache: true, ..Default::default() }
+    }
+
+    /// The answer must not be stored
+    pub fn no_store() -> CacheControl {
+        CacheControl { no_store: true, ..Default::default() }
+    }
+
+    /// Seconds of the freshness
+    pub fn max_age(mut self, secs: u64) -> CacheControl {
+        self.max_age = Some(secs);
+        self
+    }
+
+    /// Seconds of the freshness in the shared caches, only for `public`
+    pub fn s_maxage(mut self, secs: u64) -> CacheControl {
+        self.s_maxage = Some(secs);
+        self
+    }
+
+    /// Seconds when the stale answer is served while the cache revalidates it
+    pub fn stale_while_revalidate(mut self, secs: u64) -> CacheControl {
+        self.stale_while_revalidate = Some(secs);
+        self
+    }
+
+    /// Seconds when the stale answer is served if the server fails
+    pub fn stale_if_error(mut self, secs: u64) -> CacheControl {
+        self.stale_if_error = Some(secs);
+        self
+    }
+
+    pub fn must_revalidate(mut self) -> CacheControl {
+        self.must_revalidate = true;
+        self
+    }
+
+    /// The answer never changes, for the versioned static files
+    pub fn immutable(mut self) -> CacheControl {
+        self.immutable = true;
+        self
+    }
+
+    pub(crate) fn is_public(&self) -> bool {
+        self.public
+    }
+
+    /// Value of the header, `personal` answer is never stored by the shared caches
+    pub(crate) fn header(&self, personal: bool) -> String {
+        if self.no_store {
+            return "no-store".to_owned();
+        }
+        let mut list = Vec::with_capacity(6);
+        let public = self.public && !personal;
+        if public {
+            list.push("public".to_owned());
+        } else if self.private || self.public {
+            list.push("private".to_owned());
+        }
+        if self.no_cache {
+            list.push("no-cache".to_owned());
+        }
+        if let Some(secs) = self.max_age {
+            list.push(format!("max-age={}", secs));
+        }
+        if let (true, Some(secs)) = (public, self.s_maxage) {
+            list.push(format!("s-maxage={}", secs));
+        }
+        if let Some(secs) = self.stale_while_revalidate {
+            list.push(format!("stale-while-revalidate={}", secs));
+        }
+        if let Some(secs) = self.stale_if_error {
+            list.push(format!("stale-if-error={}", secs));
+        }
+        if self.must_revalidate {
+            list.push("must-revalidate".to_owned());
+        }
+        if self.immutable {
+            list.push("immutable".to_owned());
+        }
+        list.join(", ")
+    }
+}
+
 #[derive(Debug)]
 pub struct Response {
     pub redirect: Option<Redirect>,
     pub content_type: Option<String>,
     pub headers: Vec<(String, String)>,
     pub http_code: Option<u16>,
+    /// Cache-Control header, see `Action::cache_control`
+    pub cache: Option<CacheControl>,
+    /// ETag header, see `Action::etag`
+    pub etag: Option<ETag>,
+    /// Last-Modified header, see `Action::last_modified`
+    pub last_modified: Option<DateTime<Utc>>,
     pub css: Vec<String>,
     pub js: Vec<String>,
     pub meta: Vec<String>,
+    /// Cookies for the `Set-Cookie` headers
+    pub cookies: Vec<Cookie>,
+    /// Max size of the answer in bytes, see `Controller::MaxSize`
+    pub max_size: Option<usize>,
+    /// What to do with the answer larger than `max_size`
+    pub size_policy: SizePolicy,
+}
+
+impl Response {
+    /// Set the cookie, replaces the cookie with the same name, path and domain
+    pub fn set_cookie(&mut self, cookie: Cookie) {
+        self.cookies.retain(|item| !(item.name == cookie.name && item.path == cookie.path && item.domain == cookie.domain));
+        self.cookies.push(cookie);
+    }
+
+    /// Set the cookie signed with the salt, the client can read the value but cannot change it
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// this.response.set_signed_cookie(Cookie::new("cart", "42"), &this.salt);
+    /// let cart = this.request.signed_cookie("cart", &this.salt);
+    /// ```
+    pub fn set_signed_cookie(&mut self, mut cookie: Cookie, salt: &str) {
+        cookie.value = Cookie::sign(&cookie.name, &cookie.value, salt);
+        self.set_cookie(cookie);
+    }
+
+    /// Set the cookie encrypted with the salt (AES-256-GCM), the client can neither read nor change the value
+    ///
+    /// Returns `false` if the value cannot be encrypted, the cookie is not set.
+    pub fn set_encrypted_cookie(&mut self, mut cookie: Cookie, salt: &str) -> bool {
+        match Cookie::encrypt(&cookie.name, &cookie.value, salt) {
+            Some(value) => {
+                cookie.value = value;
+                self.set_cookie(cookie);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Writer of the parts of the multipart answer
+///
+/// `multipart/x-mixed-replace` shows every part instead of the previous one, for example the frames of the camera,
+/// `multipart/mixed` is the list of the parts. `Action::file` answers `multipart/byteranges` itself.
+/// The parts of HTTP/1.1 are sent by the chunks, so the connection stays open for the next requests.
+///
+/// # Example
+///
+/// ```ignore
+/// let parts = this.multipart("x-mixed-replace");
+/// while let Some(frame) = camera.next().await {
+///     parts.write(this, "image/jpeg", &frame).await;
+/// }
+/// parts.end(this).await;
+/// ```
+#[derive(Debug, Clone)]
+pub struct MultipartWriter {
+    boundary: String,
+}
+
+impl MultipartWriter {
+    /// Writer with the random boundary
+    pub(crate) fn new() -> MultipartWriter {
+        let mut random = [0u8; 16];
+        let _ = SystemRandom::new().fill(&mut random);
+        MultipartWriter {
+            boundary: format!("tiny{}", to_hex(&random)),
+        }
+    }
+
+    /// Value of the Content-Type header, for example "multipart/mixed; boundary=tiny..."
+    pub fn content_type(&self, subtype: &str) -> String {
+        format!("multipart/{}; boundary={}", subtype, self.boundary)
+    }
+
+    /// Send the part, the headers are sent with the first part
+    pub async fn write(&self, action: &mut Action, content_type: &str, body: &[u8]) {
+        action.write(Answer::Raw(self.part(content_type, &[], body))).await;
+    }
+
+    /// Send the closing boundary
+    pub async fn end(self, action: &mut Action) {
+        action.write(Answer::Raw(self.close())).await;
+    }
+
+    /// Part with the Content-Type, the headers and the Content-Length
+    pub(crate) fn part(&self, content_type: &str, headers: &[(&str, String)], body: &[u8]) -> Vec<u8> {
+        let mut head = format!("--{}\r\nContent-Type: {}\r\n", self.boundary, content_type);
+        for (name, value) in headers {
+            let _ = write!(head, "{}: {}\r\n", name, value);
+        }
+        let _ = write!(head, "Content-Length: {}\r\n\r\n", body.len());
+        let mut part = Vec::with_capacity(head.len() + body.len() + 2);
+        part.extend_from_slice(head.as_bytes());
+        part.extend_from_slice(body);
+        part.extend_from_slice(b"\r\n");
+        part
+    }
+
+    pub(crate) fn close(&self) -> Vec<u8> {
+        format!("--{}--\r\n", self.boundary).into_bytes()
+    }
 }
 
 impl Redirect {
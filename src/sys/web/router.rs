@@ -0,0 +1,722 @@
+use std::{collections::HashMap, future::Future, pin::Pin};
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
+
+use crate::fnv1a_64;
+
+use super::{
+    action::{Action, Answer},
+    controller::Controller,
+    pattern::Constraint,
+    request::{HttpMethod, Request},
+};
+
+#[cfg(feature = "access-db")]
+use super::guard::Guard;
+
+/// Middleware function
+///
+/// Returns `Some(Answer)` to stop processing of the request.
+pub type MiddlewareFn = fn(&mut Action) -> Pin<Box<dyn Future<Output = Option<Answer>> + Send + '_>>;
+
+/// Middleware of the route group
+#[derive(Clone)]
+pub enum Middleware {
+    /// Pre-check of the controller
+    #[cfg(feature = "access-db")]
+    Guard(Guard),
+    /// Options of the controller
+    Controller(Controller),
+    /// Add header to the response
+    Header(&'static str, &'static str),
+    /// Custom function
+    Fn(MiddlewareFn),
+}
+
+impl std::fmt::Debug for Middleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            #[cfg(feature = "access-db")]
+            Middleware::Guard(guard) => write!(f, "Guard({:?})", guard),
+            Middleware::Controller(controller) => write!(f, "Controller({:?})", controller),
+            Middleware::Header(name, value) => write!(f, "Header({}: {})", name, value),
+            Middleware::Fn(_) => write!(f, "Fn"),
+        }
+    }
+}
+
+/// Group of the modules with shared URL prefix and middleware
+#[derive(Debug)]
+pub struct RouteGroup {
+    /// URL prefix without trailing "/", for example "/admin"
+    prefix: String,
+    /// Registered modules
+    modules: Vec<i64>,
+    middleware: Vec<Middleware>,
+}
+
+impl RouteGroup {
+    /// Add middleware to all modules of the group
+    pub fn middleware(&mut self, middleware: Middleware) -> &mut RouteGroup {
+        self.middleware.push(middleware);
+        self
+    }
+
+    /// Register the module in the group
+    ///
+    /// If the group has one module, urls are `/prefix/class/action/param`,
+    /// otherwise `/prefix/module/class/action/param`.
+    pub fn register(&mut self, module: &str) -> &mut RouteGroup {
+        self.modules.push(fnv1a_64(module.as_bytes()));
+        self
+    }
+}
+
+/// Type of the path parameter
+#[derive(Debug)]
+enum ParamKind {
+    Str,
+    I64,
+    U64,
+    F64,
+    /// Regular expression, for example `[0-9]+`
+    Pattern(Constraint),
+}
+
+impl ParamKind {
+    /// Checks the value of the path parameter
+    fn check(&self, value: &str) -> bool {
+        match self {
+            ParamKind::Str => !value.is_empty(),
+            ParamKind::I64 => value.parse::<i64>().is_ok(),
+            ParamKind::U64 => value.parse::<u64>().is_ok(),
+            ParamKind::F64 => value.parse::<f64>().is_ok(),
+            ParamKind::Pattern(constraint) => constraint.check(value),
+        }
+    }
+}
+
+/// Segment of the path pattern
+#[derive(Debug)]
+enum Segment {
+    Static(String),
+    Param(String, ParamKind),
+    /// The rest of the path including slashes, only the last segment
+    Tail(String),
+}
+
+/// Path pattern mapped to the controller, for example `/blog/{slug}/comments/{id:i64}`
+#[derive(Debug)]
+struct PathRoute {
+    segments: Vec<Segment>,
+    module_id: i64,
+    class_id: i64,
+    action_id: i64,
+}
+
+impl PathRoute {
+    /// Parse the pattern
+    fn new(pattern: &str, module_id: i64, class_id: i64, action_id: i64) -> Result<PathRoute, String> {
+        let mut segments = Vec::new();
+        for segment in pattern.split('/').filter(|s| !s.is_empty()) {
+            if let Some(Segment::Tail(_)) = segments.last() {
+                return Err(format!("The catch-all parameter must be the last in the route {}", pattern));
+            }
+            let param = match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                Some(param) => Some(param),
+                None => segment.strip_prefix('<').and_then(|s| s.strip_suffix('>')),
+            };
+            match param {
+                None if segment.len() > 1 && segment.starts_with('*') => {
+                    segments.push(Segment::Tail(segment[1..].trim().to_owned()));
+                }
+                Some(param) if param.ends_with("...") => {
+                    segments.push(Segment::Tail(param.trim_end_matches('.').trim().to_owned()));
+                }
+                Some(param) => {
+                    let (name, kind) = match param.split_once(':') {
+                        Some((name, kind)) => (name, kind),
+                        None => (param, "str"),
+                    };
+                    let kind = match kind.trim() {
+                        "str" | "String" => ParamKind::Str,
+                        "i64" => ParamKind::I64,
+                        "u64" => ParamKind::U64,
+                        "f64" => ParamKind::F64,
+                        kind => match Constraint::new(kind) {
+                            Ok(constraint) => ParamKind::Pattern(constraint),
+                            Err(e) => return Err(format!("{}. Parameter {} in the route {}", e, param, pattern)),
+                        },
+                    };
+                    segments.push(Segment::Param(name.trim().to_owned(), kind));
+                }
+                None => segments.push(Segment::Static(segment.to_owned())),
+            }
+        }
+        Ok(PathRoute {
+            segments,
+            module_id,
+            class_id,
+            action_id,
+        })
+    }
+
+    /// Name of the catch-all parameter
+    fn tail(&self) -> Option<&str> {
+        match self.segments.last() {
+            Some(Segment::Tail(name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Match the url, returns captured parameters
+    fn find(&self, url: &str) -> Option<HashMap<String, String>> {
+        let mut params = HashMap::new();
+        let mut parts = url.split('/').filter(|s| !s.is_empty());
+        for segment in &self.segments {
+            let part = parts.next()?;
+            match segment {
+                Segment::Static(value) => {
+                    if value != part {
+                        return None;
+                    }
+                }
+                Segment::Param(name, kind) => {
+                    if !kind.check(part) {
+                        return None;
+                    }
+                    params.insert(name.to_owned(), part.to_owned());
+                }
+                Segment::Tail(name) => {
+                    let mut tail = part.to_owned();
+                    for part in parts.by_ref() {
+                        tail.push('/');
+                        tail.push_str(part);
+                    }
+                    params.insert(name.to_owned(), tail);
+                }
+            }
+        }
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(params)
+    }
+
+    /// Build the url from the parameters, other parameters are added to the query string
+    fn build(&self, params: &[(&str, String)]) -> Option<String> {
+        let mut url = String::new();
+        let mut used = Vec::with_capacity(params.len());
+        for segment in &self.segments {
+            url.push('/');
+            match segment {
+                Segment::Static(value) => url.push_str(value),
+                Segment::Param(name, kind) => {
+                    let (idx, (_, value)) = params.iter().enumerate().find(|(_, (key, _))| key == name)?;
+                    if !kind.check(value) {
+                        return None;
+                    }
+                    url.push_str(&utf8_percent_encode(value, SEGMENT_ENCODE).to_string());
+                    used.push(idx);
+                }
+                Segment::Tail(name) => {
+                    let (idx, (_, value)) = params.iter().enumerate().find(|(_, (key, _))| key == name)?;
+                    let value = value.trim_matches('/');
+                    if value.is_empty() {
+                        return None;
+                    }
+                    url.push_str(&utf8_percent_encode(value, PATH_ENCODE).to_string());
+                    used.push(idx);
+                }
+            }
+        }
+        if url.is_empty() {
+            url.push('/');
+        }
+        let query: Vec<&(&str, String)> =
+            params.iter().enumerate().filter(|(idx, _)| !used.contains(idx)).map(|(_, param)| param).collect();
+        Router::push_query(&mut url, &query);
+        Some(url)
+    }
+}
+
+/// Characters encoded in the path of the canonical url
+const PATH_ENCODE: &AsciiSet =
+    &CONTROLS.add(b' ').add(b'"').add(b'#').add(b'%').add(b'<').add(b'>').add(b'?').add(b'`').add(b'{').add(b'}');
+
+/// Characters encoded in the one segment of the path
+const SEGMENT_ENCODE: &AsciiSet = &PATH_ENCODE.add(b'/');
+
+/// Characters encoded in the query of the canonical url
+const QUERY_ENCODE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+/// Rules of the query string canonicalization
+#[derive(Debug, Default)]
+struct Canonical {
+    /// Allowed query parameters, empty list allows everything
+    allow: Vec<String>,
+    /// Removed query parameters, "utm_*" removes all parameters with prefix
+    strip: Vec<String>,
+}
+
+impl Canonical {
+    /// Checks the query parameter
+    fn allow(&self, key: &str) -> bool {
+        if !self.allow.is_empty() && !self.allow.iter().any(|allow| allow == key) {
+            return false;
+        }
+        !self.strip.iter().any(|strip| match strip.strip_suffix('*') {
+            Some(prefix) => key.starts_with(prefix),
+            None => strip == key,
+        })
+    }
+}
+
+/// Subdomain of the site mapped to the module
+#[derive(Debug)]
+struct Subdomain {
+    /// Name of the subdomain, `None` for any subdomain
+    name: Option<String>,
+    /// Name of the route parameter of the captured subdomain
+    param: Option<String>,
+    module_id: i64,
+}
+
+/// Result of the route search
+#[derive(Debug)]
+pub(crate) struct RouteMatch<'a> {
+    pub module_id: i64,
+    /// Class_id and action_id of the path pattern
+    pub target: Option<(i64, i64)>,
+    /// The rest of the url (class/action/param) without leading "/"
+    pub rest: &'a str,
+    /// Captured route parameters
+    pub params: HashMap<String, String>,
+    /// The rest of the path captured by the catch-all parameter
+    pub param: Option<String>,
+}
+
+/// Static route table
+///
+/// # Example
+///
+/// ```ignore
+/// let mut router = Router::default();
+/// router.group("/admin").middleware(Middleware::Fn(admin_auth)).register("admin");
+/// router.domain("example.com").subdomain("api", "api").subdomain("{tenant}", "shop");
+/// router.query_strip(&["utm_*", "fbclid"]).canonical_redirect(true);
+/// tiny_web::run_router(name, version, desc, addfn!(...), router);
+/// ```
+#[derive(Debug, Default)]
+pub struct Router {
+    groups: Vec<RouteGroup>,
+    /// module_id => index of group
+    modules: HashMap<i64, usize>,
+    /// Main domain of the site, for example "example.com"
+    domain: Option<String>,
+    subdomains: Vec<Subdomain>,
+    routes: Vec<PathRoute>,
+    canonical: Canonical,
+    /// Redirect 301 to the canonical url
+    canonical_redirect: bool,
+}
+
+impl Router {
+    /// Add a new route group
+    pub fn group(&mut self, prefix: &str) -> &mut RouteGroup {
+        let prefix = prefix.trim_end_matches('/');
+        let prefix = if prefix.starts_with('/') { prefix.to_owned() } else { format!("/{}", prefix) };
+        self.groups.push(RouteGroup {
+            prefix,
+            modules: Vec::new(),
+            middleware: Vec::new(),
+        });
+        let len = self.groups.len();
+        &mut self.groups[len - 1]
+    }
+
+    /// Map the path pattern to the controller
+    ///
+    /// Parameters are `{name}` or `{name:type}`, where type is `str`, `i64`, `u64`, `f64`
+    /// or a regular expression, for example `{id:[0-9]+}` or `{lang:[a-z]{2}}`.
+    /// The last parameter `{path...}` or `*path` captures the non-empty rest of the path including slashes, for example `/docs/{path...}`.
+    /// The captured path is also available split by "/" as `Action::param()`.
+    /// The values are available as `this.path_param::<i64>("id")`.
+    /// Patterns are checked in the order they are added.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is invalid.
+    pub fn route(&mut self, pattern: &str, module: &str, class: &str, action: &str) -> &mut Router {
+        let module_id = fnv1a_64(module.as_bytes());
+        let class_id = fnv1a_64(class.as_bytes());
+        let action_id = fnv1a_64(action.as_bytes());
+        if let Err(e) = self.add_route(pattern, module_id, class_id, action_id) {
+            panic!("{}", e);
+        }
+        self
+    }
+
+    /// Map the path pattern from the config or the database to the controller
+    pub(crate) fn add_route(&mut self, pattern: &str, module_id: i64, class_id: i64, action_id: i64) -> Result<(), String> {
+        self.routes.push(PathRoute::new(pattern, module_id, class_id, action_id)?);
+        Ok(())
+    }
+
+    /// Set allowed query parameters, for example `&["page", "sort"]`
+    ///
+    /// Other parameters are not part of the canonical url.
+    pub fn query_allow(&mut self, params: &[&str]) -> &mut Router {
+        self.canonical.allow = params.iter().map(|param| (*param).to_owned()).collect();
+        self
+    }
+
+    /// Set removed query parameters, for example `&["utm_*", "fbclid", "gclid"]`
+    pub fn query_strip(&mut self, params: &[&str]) -> &mut Router {
+        self.canonical.strip = params.iter().map(|param| (*param).to_owned()).collect();
+        self
+    }
+
+    /// Redirect GET requests with removed query parameters to the canonical url with 301 Moved Permanently
+    pub fn canonical_redirect(&mut self, redirect: bool) -> &mut Router {
+        self.canonical_redirect = redirect;
+        self
+    }
+
+    /// Build the canonical url: the path and the allowed query parameters sorted by name
+    pub(crate) fn canonical_url(&self, request: &Request) -> String {
+        let mut list: Vec<(&String, &String)> = request.input.get.iter().filter(|(key, _)| self.canonical.allow(key)).collect();
+        list.sort();
+        let mut url = utf8_percent_encode(&request.url, PATH_ENCODE).to_string();
+        for (idx, (key, value)) in list.into_iter().enumerate() {
+            url.push(if idx == 0 { '?' } else { '&' });
+            url.push_str(&utf8_percent_encode(key, QUERY_ENCODE).to_string());
+            if !value.is_empty() {
+                url.push('=');
+                url.push_str(&utf8_percent_encode(value, QUERY_ENCODE).to_string());
+            }
+        }
+        url
+    }
+
+    /// Get the canonical url for redirect, if the request has removed query parameters
+    pub(crate) fn canonical_redirect_url(&self, request: &Request) -> Option<String> {
+        if !self.canonical_redirect || request.ajax || !matches!(request.method, HttpMethod::Get | HttpMethod::Head) {
+            return None;
+        }
+        if request.input.get.keys().all(|key| self.canonical.allow(key)) {
+            return None;
+        }
+        Some(self.canonical_url(request))
+    }
+
+    /// Set the main domain of the site, for example "example.com"
+    ///
+    /// If the domain is not set, the subdomain is the first part of the host with at least three parts.
+    pub fn domain(&mut self, domain: &str) -> &mut Router {
+        let domain = domain.trim().trim_matches('.').to_lowercase();
+        self.domain = if domain.is_empty() { None } else { Some(domain) };
+        self
+    }
+
+    /// Map the subdomain to the module
+    ///
+    /// * `"api"` - `api.example.com/class/action/param` is dispatched to the module.
+    /// * `"{tenant}"` - any subdomain, the subdomain is available as `this.path_param::<String>("tenant")`.
+    /// * `"*"` - any subdomain without capture.
+    ///
+    /// Named subdomains are checked before any subdomain.
+    pub fn subdomain(&mut self, subdomain: &str, module: &str) -> &mut Router {
+        let subdomain = subdomain.trim().to_lowercase();
+        let (name, param) = if subdomain == "*" {
+            (None, None)
+        } else if let Some(param) = subdomain.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            (None, Some(param.to_owned()))
+        } else {
+            (Some(subdomain), None)
+        };
+        let item = Subdomain {
+            name,
+            param,
+            module_id: fnv1a_64(module.as_bytes()),
+        };
+        match item.name {
+            Some(_) => {
+                let idx = self.subdomains.iter().position(|s| s.name.is_none()).unwrap_or(self.subdomains.len());
+                self.subdomains.insert(idx, item);
+            }
+            None => self.subdomains.push(item),
+        }
+        self
+    }
+
+    /// Prepare the table after all groups are added
+    pub(crate) fn build(mut self) -> Router {
+        self.modules.clear();
+        for (idx, group) in self.groups.iter().enumerate() {
+            for module_id in &group.modules {
+                self.modules.insert(*module_id, idx);
+            }
+        }
+        self
+    }
+
+    /// Find the module by host and url
+    pub(crate) fn find<'a>(&self, host: &str, url: &'a str) -> Option<RouteMatch<'a>> {
+        for route in &self.routes {
+            if let Some(params) = route.find(url) {
+                let param = route.tail().and_then(|name| params.get(name).cloned());
+                return Some(RouteMatch {
+                    module_id: route.module_id,
+                    target: Some((route.class_id, route.action_id)),
+                    rest: "",
+                    params,
+                    param,
+                });
+            }
+        }
+        if let Some(found) = self.find_subdomain(host, url) {
+            return Some(found);
+        }
+        let (module_id, rest) = self.find_group(url)?;
+        Some(RouteMatch {
+            module_id,
+            target: None,
+            rest,
+            params: HashMap::new(),
+            param: None,
+        })
+    }
+
+    /// Find the module by subdomain
+    fn find_subdomain<'a>(&self, host: &str, url: &'a str) -> Option<RouteMatch<'a>> {
+        if self.subdomains.is_empty() {
+            return None;
+        }
+        let sub = self.get_subdomain(host)?;
+        for item in &self.subdomains {
+            let found = match &item.name {
+                Some(name) => *name == sub,
+                None => true,
+            };
+            if found {
+                let mut params = HashMap::new();
+                if let Some(param) = &item.param {
+                    params.insert(param.to_owned(), sub);
+                }
+                return Some(RouteMatch {
+                    module_id: item.module_id,
+                    target: None,
+                    rest: url.trim_start_matches('/'),
+                    params,
+                    param: None,
+                });
+            }
+        }
+        None
+    }
+
+    /// Extract the subdomain from the host, for example "api" from "api.example.com:8080"
+    fn get_subdomain(&self, host: &str) -> Option<String> {
+        if host.starts_with('[') {
+            return None;
+        }
+        let host = match host.rsplit_once(':') {
+            Some((host, port)) if port.bytes().all(|b| b.is_ascii_digit()) => host,
+            _ => host,
+        };
+        let host = host.trim_end_matches('.').to_lowercase();
+        let sub = match &self.domain {
+            Some(domain) => host.strip_suffix(domain.as_str())?.strip_suffix('.')?,
+            None => {
+                let (sub, domain) = host.split_once('.')?;
+                if !domain.contains('.') || host.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+                    return None;
+                }
+                sub
+            }
+        };
+        if sub.is_empty() {
+            return None;
+        }
+        Some(sub.to_owned())
+    }
+
+    /// Find the group by url
+    ///
+    /// Returns module_id and the rest of the url (class/action/param) without leading "/".
+    fn find_group<'a>(&self, url: &'a str) -> Option<(i64, &'a str)> {
+        for group in &self.groups {
+            if group.modules.is_empty() {
+                continue;
+            }
+            let rest = match url.strip_prefix(group.prefix.as_str()) {
+                Some(rest) if rest.is_empty() || rest.starts_with('/') || group.prefix == "/" => rest,
+                _ => continue,
+            };
+            let rest = rest.trim_start_matches('/');
+            if group.modules.len() == 1 {
+                return Some((group.modules[0], rest));
+            }
+            let (module, rest) = match rest.split_once('/') {
+                Some((module, rest)) => (module, rest),
+                None => (rest, ""),
+            };
+            let module_id = fnv1a_64(module.as_bytes());
+            if group.modules.contains(&module_id) {
+                return Some((module_id, rest));
+            }
+        }
+        None
+    }
+
+    /// Build the url of the controller
+    ///
+    /// Searches path patterns, then route groups, otherwise returns `/module/class/action`.
+    /// Parameters that are not in the pattern are added to the query string.
+    pub(crate) fn url_for(&self, module: &str, class: &str, action: &str, params: &[(&str, String)]) -> String {
+        let module_id = fnv1a_64(module.as_bytes());
+        let class_id = fnv1a_64(class.as_bytes());
+        let action_id = fnv1a_64(action.as_bytes());
+        for route in &self.routes {
+            if route.module_id == module_id && route.class_id == class_id && route.action_id == action_id {
+                if let Some(url) = route.build(params) {
+                    return url;
+                }
+            }
+        }
+        let mut url = match self.modules.get(&module_id).and_then(|idx| self.groups.get(*idx)) {
+            Some(group) if group.modules.len() == 1 => format!("{}/{}/{}", group.prefix.trim_end_matches('/'), class, action),
+            Some(group) => format!("{}/{}/{}/{}", group.prefix.trim_end_matches('/'), module, class, action),
+            None => format!("/{}/{}/{}", module, class, action),
+        };
+        let query: Vec<&(&str, String)> = params.iter().collect();
+        Router::push_query(&mut url, &query);
+        url
+    }
+
+    /// Add parameters to the query string of the url
+    fn push_query(url: &mut String, params: &[&(&str, String)]) {
+        for (idx, (key, value)) in params.iter().enumerate() {
+            url.push(if idx == 0 { '?' } else { '&' });
+            url.push_str(&utf8_percent_encode(key, QUERY_ENCODE).to_string());
+            url.push('=');
+            url.push_str(&utf8_percent_encode(value, QUERY_ENCODE).to_string());
+        }
+    }
+
+    /// Get middleware of the module
+    pub(crate) fn middleware(&self, module_id: i64) -> Option<&[Middleware]> {
+        let idx = self.modules.get(&module_id)?;
+        self.groups.get(*idx).map(|group| group.middleware.as_slice())
+    }
+}
+
+impl Action {
+    /// Build the url of the controller from the route table
+    ///
+    /// `this.url_for("blog", "comment", "view", &[("slug", &"hello"), ("id", &15)])` returns
+    /// `/blog/hello/comments/15` for the pattern `/blog/{slug}/comments/{id:i64}`.
+    /// The `url_for!` macro checks the controller at compile time.
+    /// With [web] lang_prefix the url gets the prefix of the current language, see `Action::route`.
+    pub fn url_for(&self, module: &str, class: &str, action: &str, params: &[(&str, &dyn std::fmt::Display)]) -> String {
+        let params: Vec<(&str, String)> = params.iter().map(|(key, value)| (*key, value.to_string())).collect();
+        self.route(&self.router.url_for(module, class, action, &params))
+    }
+
+    /// Local url of the current language, "/blog/post" is "/en/blog/post" with [web] lang_prefix
+    ///
+    /// The url with the prefix of any language and the external url are returned as is.
+    pub fn route(&self, url: &str) -> String {
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        if let Some(prefix) = self.lang_prefix() {
+            let local = url.starts_with('/') && !url.starts_with("//");
+            let prefixed = url.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?']));
+            if local && !prefixed {
+                return match url {
+                    "/" => format!("{}/", prefix),
+                    url => format!("{}{}", prefix, url),
+                };
+            }
+        }
+        url.to_owned()
+    }
+
+    /// Get the canonical url of the request, for example for the cache key or `<link rel="canonical">`
+    ///
+    /// Contains the path and the query parameters allowed by `Router::query_allow` and `Router::query_strip`.
+    pub fn canonical_url(&self) -> String {
+        self.router.canonical_url(&self.request)
+    }
+
+    /// Run middleware of the route group
+    pub(crate) async fn run_middleware(&mut self, module_id: i64) -> Option<Answer> {
+        let router = std::sync::Arc::clone(&self.router);
+        let list = router.middleware(module_id)?;
+        for middleware in list {
+            match middleware {
+                #[cfg(feature = "access-db")]
+                Middleware::Guard(guard) => {
+                    if let Some(answer) = self.guard(std::slice::from_ref(guard)).await {
+                        return Some(answer);
+                    }
+                }
+                Middleware::Controller(controller) => self.controller(std::slice::from_ref(controller)),
+                Middleware::Header(name, value) => self.response.headers.push(((*name).to_owned(), (*value).to_owned())),
+                Middleware::Fn(func) => {
+                    if let Some(answer) = func(self).await {
+                        return Some(answer);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Creates the route table of the path patterns
+///
+/// # Example
+///
+/// ```ignore
+/// let router = routes! {
+///     "/blog/{slug}" => blog::post::index,
+///     "/blog/{slug}/comments/{id:i64}" => blog::comment::view,
+/// };
+/// tiny_web::run_router(name, version, desc, addfn!(...), router);
+/// ```
+#[macro_export]
+macro_rules! routes {
+    ($($pattern:expr => $module:ident :: $class:ident :: $action:ident),* $(,)?) => {{
+        let mut router = $crate::sys::web::router::Router::default();
+        $(
+            router.route($pattern, stringify!($module), stringify!($class), stringify!($action));
+        )*
+        router
+    }};
+}
+
+/// Builds the url of the controller with compile-time check of the controller
+///
+/// The controller is checked as the function `app::module::class::action` of the application
+/// (the same layout is used by `addfn!`).
+///
+/// # Example
+///
+/// ```ignore
+/// let url = url_for!(this, blog::comment::view, slug = "hello", id = 15);
+/// ```
+// `crate::app` is the application crate, where `addfn!` is called
+#[allow(clippy::crate_in_macro_def)]
+#[macro_export]
+macro_rules! url_for {
+    ($this:expr, $module:ident :: $class:ident :: $action:ident $(, $key:ident = $value:expr)* $(,)?) => {{
+        let _ = crate::app::$module::$class::$action;
+        $this.url_for(
+            stringify!($module),
+            stringify!($class),
+            stringify!($action),
+            &[$((stringify!($key), &$value as &dyn std::fmt::Display)),*],
+        )
+    }};
+}
@@ -0,0 +1,348 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use crate::log;
+
+use super::{
+    action::{Action, Answer},
+    request::RawData,
+};
+
+/// JSON Schema of the payload of the request
+///
+/// The keywords: `type`, `enum`, `const`, `properties`, `required`, `additionalProperties`, `items`,
+/// `minItems`, `maxItems`, `uniqueItems`, `minLength`, `maxLength`, `minimum`, `maximum`, `exclusiveMinimum`,
+/// `exclusiveMaximum`, `multipleOf`, `allOf`, `anyOf`, `oneOf`, `not` and `format` "date", "date-time",
+/// "email" and "uuid". The other keywords are ignored, `true` accepts any value and `false` rejects it.
+///
+/// # Example
+///
+/// ```ignore
+/// let value = schema!(this, r#"{"type": "object", "required": ["name"], "properties": {"name": {"type": "string", "minLength": 1}}}"#);
+/// ```
+#[derive(Debug, Clone)]
+pub struct JsonSchema {
+    schema: Value,
+}
+
+/// Error of the validation, `pointer` is the JSON Pointer of the value, for example "/items/0/name"
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SchemaError {
+    pub pointer: String,
+    pub message: String,
+}
+
+impl FromStr for JsonSchema {
+    type Err = serde_json::Error;
+
+    fn from_str(text: &str) -> Result<JsonSchema, serde_json::Error> {
+        Ok(JsonSchema { schema: serde_json::from_str(text)? })
+    }
+}
+
+impl JsonSchema {
+    pub fn new(schema: Value) -> JsonSchema {
+        JsonSchema { schema }
+    }
+
+    /// Schema of the text, the invalid text is the schema `false`, so every payload is rejected
+    pub fn parse(text: &str) -> JsonSchema {
+        match text.parse() {
+            Ok(schema) => schema,
+            Err(_e) => {
+                log!(warning, 0, "{}", _e);
+                JsonSchema { schema: Value::Bool(false) }
+            }
+        }
+    }
+
+    /// Errors of the value, empty for the valid value
+    pub fn validate(&self, value: &Value) -> Vec<SchemaError> {
+        let mut errors = Vec::new();
+        JsonSchema::check(&self.schema, value, "", &mut errors);
+        errors
+    }
+
+    fn error(errors: &mut Vec<SchemaError>, pointer: &str, message: String) {
+        errors.push(SchemaError { pointer: pointer.to_owned(), message });
+    }
+
+    fn check(schema: &Value, value: &Value, pointer: &str, errors: &mut Vec<SchemaError>) {
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => return JsonSchema::error(errors, pointer, "Value is not allowed".to_owned()),
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+
+        if let Some(kind) = schema.get("type") {
+            let ok = match kind {
+                Value::String(kind) => JsonSchema::is_type(kind, value),
+                Value::Array(list) => list.iter().filter_map(Value::as_str).any(|kind| JsonSchema::is_type(kind, value)),
+                _ => true,
+            };
+            if !ok {
+                let kind = match kind {
+                    Value::Array(list) => list.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(" or "),
+                    kind => kind.as_str().unwrap_or_default().to_owned(),
+                };
+                return JsonSchema::error(errors, pointer, format!("Must be {}", kind));
+            }
+        }
+        if let Some(Value::Array(list)) = schema.get("enum") {
+            if !list.contains(value) {
+                let list: Vec<String> = list.iter().map(Value::to_string).collect();
+                JsonSchema::error(errors, pointer, format!("Must be one of {}", list.join(", ")));
+            }
+        }
+        if let Some(item) = schema.get("const") {
+            if item != value {
+                JsonSchema::error(errors, pointer, format!("Must be {}", item));
+            }
+        }
+
+        match value {
+            Value::Object(map) => JsonSchema::check_object(schema, map, pointer, errors),
+            Value::Array(list) => JsonSchema::check_array(schema, list, pointer, errors),
+            Value::String(str) => JsonSchema::check_string(schema, str, pointer, errors),
+            Value::Number(_) => {
+                if let Some(num) = value.as_f64() {
+                    JsonSchema::check_number(schema, num, pointer, errors);
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(Value::Array(list)) = schema.get("allOf") {
+            for item in list {
+                JsonSchema::check(item, value, pointer, errors);
+            }
+        }
+        if let Some(Value::Array(list)) = schema.get("anyOf") {
+            if !list.iter().any(|item| JsonSchema::is_valid(item, value, pointer)) {
+                JsonSchema::error(errors, pointer, "Must match at least one schema of anyOf".to_owned());
+            }
+        }
+        if let Some(Value::Array(list)) = schema.get("oneOf") {
+            if list.iter().filter(|item| JsonSchema::is_valid(item, value, pointer)).count() != 1 {
+                JsonSchema::error(errors, pointer, "Must match exactly one schema of oneOf".to_owned());
+            }
+        }
+        if let Some(item) = schema.get("not") {
+            if JsonSchema::is_valid(item, value, pointer) {
+                JsonSchema::error(errors, pointer, "Must not match the schema of not".to_owned());
+            }
+        }
+    }
+
+    fn check_object(schema: &Map<String, Value>, map: &Map<String, Value>, pointer: &str, errors: &mut Vec<SchemaError>) {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !map.contains_key(name) {
+                    JsonSchema::error(errors, &JsonSchema::pointer(pointer, name), "Is required".to_owned());
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (name, item) in map {
+            let path = JsonSchema::pointer(pointer, name);
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(property) => JsonSchema::check(property, item, &path, errors),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => JsonSchema::error(errors, &path, "Is not allowed".to_owned()),
+                    Some(additional) => JsonSchema::check(additional, item, &path, errors),
+                    None => {}
+                },
+            }
+        }
+    }
+
+    fn check_array(schema: &Map<String, Value>, list: &[Value], pointer: &str, errors: &mut Vec<SchemaError>) {
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if (list.len() as u64) < min {
+                JsonSchema::error(errors, pointer, format!("Must have at least {} items", min));
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if list.len() as u64 > max {
+                JsonSchema::error(errors, pointer, format!("Must have at most {} items", max));
+            }
+        }
+        if schema.get("uniqueItems").and_then(Value::as_bool) == Some(true) {
+            for (idx, item) in list.iter().enumerate() {
+                if list[..idx].contains(item) {
+                    JsonSchema::error(errors, &format!("{}/{}", pointer, idx), "Must be unique".to_owned());
+                }
+            }
+        }
+        if let Some(items) = schema.get("items") {
+            for (idx, item) in list.iter().enumerate() {
+                JsonSchema::check(items, item, &format!("{}/{}", pointer, idx), errors);
+            }
+        }
+    }
+
+    fn check_string(schema: &Map<String, Value>, str: &str, pointer: &str, errors: &mut Vec<SchemaError>) {
+        let len = str.chars().count() as u64;
+        if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+            if len < min {
+                JsonSchema::error(errors, pointer, format!("Must be at least {} characters", min));
+            }
+        }
+        if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+            if len > max {
+                JsonSchema::error(errors, pointer, format!("Must be at most {} characters", max));
+            }
+        }
+        let ok = match schema.get("format").and_then(Value::as_str) {
+            Some("date") => NaiveDate::parse_from_str(str, "%Y-%m-%d").is_ok(),
+            Some("date-time") => DateTime::parse_from_rfc3339(str).is_ok(),
+            Some("uuid") => Uuid::parse_str(str).is_ok(),
+            Some("email") => match str.split_once('@') {
+                Some((user, domain)) => !user.is_empty() && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.'),
+                None => false,
+            },
+            _ => true,
+        };
+        if !ok {
+            let format = schema.get("format").and_then(Value::as_str).unwrap_or_default();
+            JsonSchema::error(errors, pointer, format!("Must be the format {}", format));
+        }
+    }
+
+    fn check_number(schema: &Map<String, Value>, num: f64, pointer: &str, errors: &mut Vec<SchemaError>) {
+        if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+            if num < min {
+                JsonSchema::error(errors, pointer, format!("Must be at least {}", min));
+            }
+        }
+        if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+            if num > max {
+                JsonSchema::error(errors, pointer, format!("Must be at most {}", max));
+            }
+        }
+        if let Some(min) = schema.get("exclusiveMinimum").and_then(Value::as_f64) {
+            if num <= min {
+                JsonSchema::error(errors, pointer, format!("Must be greater than {}", min));
+            }
+        }
+        if let Some(max) = schema.get("exclusiveMaximum").and_then(Value::as_f64) {
+            if num >= max {
+                JsonSchema::error(errors, pointer, format!("Must be less than {}", max));
+            }
+        }
+        if let Some(step) = schema.get("multipleOf").and_then(Value::as_f64) {
+            if step > 0.0 && ((num / step).round() * step - num).abs() > f64::EPSILON * num.abs().max(1.0) {
+                JsonSchema::error(errors, pointer, format!("Must be a multiple of {}", step));
+            }
+        }
+    }
+
+    fn is_type(kind: &str, value: &Value) -> bool {
+        match kind {
+            "null" => value.is_null(),
+            "boolean" => value.is_boolean(),
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "number" => value.is_number(),
+            "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|num| num.fract() == 0.0),
+            _ => true,
+        }
+    }
+
+    fn is_valid(schema: &Value, value: &Value, pointer: &str) -> bool {
+        let mut errors = Vec::new();
+        JsonSchema::check(schema, value, pointer, &mut errors);
+        errors.is_empty()
+    }
+
+    /// JSON Pointer of the property, "~" and "/" are escaped
+    fn pointer(pointer: &str, name: &str) -> String {
+        format!("{}/{}", pointer, name.replace('~', "~0").replace('/', "~1"))
+    }
+}
+
+impl Action {
+    /// Body of the request checked by the schema
+    ///
+    /// Otherwise the answer is `422 Unprocessable Content` with `{"errors": [{"pointer": "/name", "message": "..."}]}`,
+    /// which must be returned from the controller.
+    pub fn json_valid(&mut self, schema: &JsonSchema) -> Result<Value, Answer> {
+        let value = match self.request.input.raw.as_ref() {
+            RawData::Raw(raw) => serde_json::from_slice::<Value>(raw),
+            RawData::None => serde_json::from_slice::<Value>(b""),
+        };
+        let value = match value {
+            Ok(value) => value,
+            Err(e) => {
+                return Err(self.json_invalid(vec![SchemaError {
+                    pointer: String::new(),
+                    message: e.to_string(),
+                }]))
+            }
+        };
+        let errors = schema.validate(&value);
+        if !errors.is_empty() {
+            return Err(self.json_invalid(errors));
+        }
+        Ok(value)
+    }
+
+    /// Body of the request checked by the schema and deserialized into `T`
+    ///
+    /// The error of the deserialization is the error of the pointer "".
+    pub fn json_input<T: DeserializeOwned>(&mut self, schema: &JsonSchema) -> Result<T, Answer> {
+        let value = self.json_valid(schema)?;
+        serde_json::from_value(value).map_err(|e| {
+            self.json_invalid(vec![SchemaError {
+                pointer: String::new(),
+                message: e.to_string(),
+            }])
+        })
+    }
+
+    /// Answer 422 with the errors of the validation
+    fn json_invalid(&mut self, errors: Vec<SchemaError>) -> Answer {
+        self.response.http_code = Some(422);
+        self.response.content_type = Some("application/json; charset=utf-8".to_owned());
+        Answer::String(json!({ "errors": errors }).to_string())
+    }
+}
+
+/// Checks the JSON body of the request by the schema before the controller
+///
+/// The schema is parsed once, the invalid body is answered with 422 and the errors by the pointers.
+///
+/// # Example
+///
+/// ```ignore
+/// pub async fn create(this: &mut Action) -> Answer {
+///     let value = schema!(this, r#"{"type": "object", "required": ["name"]}"#);
+///     let article: Article = schema!(this, r#"{"type": "object", "required": ["title"]}"# => Article);
+///     ...
+/// }
+/// ```
+#[macro_export]
+macro_rules! schema {
+    (@schema $schema:expr) => {{
+        static SCHEMA: std::sync::OnceLock<$crate::sys::web::schema::JsonSchema> = std::sync::OnceLock::new();
+        SCHEMA.get_or_init(|| $crate::sys::web::schema::JsonSchema::parse($schema))
+    }};
+    ($this:expr, $schema:expr => $type:ty) => {
+        match $this.json_input::<$type>($crate::schema!(@schema $schema)) {
+            Ok(value) => value,
+            Err(answer) => return answer,
+        }
+    };
+    ($this:expr, $schema:expr) => {
+        match $this.json_valid($crate::schema!(@schema $schema)) {
+            Ok(value) => value,
+            Err(answer) => return answer,
+        }
+    };
+}
@@ -1,16 +1,24 @@
 use std::{
     collections::{hash_map::Entry, HashMap},
     mem::take,
+    net::IpAddr,
     sync::Arc,
 };
 
+#[cfg(any(feature = "session-file", feature = "session-db"))]
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
 #[cfg(any(feature = "session-memory", feature = "session-file"))]
 use std::io::ErrorKind;
 
 #[cfg(any(feature = "session-memory", feature = "session-file"))]
 use std::path::PathBuf;
 
-use serde::{Deserialize, Serialize};
+use bincode::Options;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 #[cfg(feature = "session-db")]
 use tiny_web_macro::fnv1a_64 as m_fnv1a_64;
@@ -18,6 +26,12 @@ use tiny_web_macro::fnv1a_64 as m_fnv1a_64;
 #[cfg(any(feature = "session-memory", feature = "session-file"))]
 use tokio::fs::{read, remove_file, write};
 
+#[cfg(feature = "session-file")]
+use tokio::{fs::read_dir, task::yield_now};
+
+#[cfg(any(feature = "session-file", feature = "session-db"))]
+use tokio::time::{interval, MissedTickBehavior};
+
 #[cfg(feature = "session-memory")]
 use tokio::sync::Mutex;
 
@@ -28,7 +42,24 @@ use crate::{log, tool::generate_uuid};
 #[cfg(feature = "session-db")]
 use crate::sys::db::adapter::DB;
 
-use super::data::{Data, StrOrI64};
+use crate::sys::app::init::SessionBind;
+
+#[cfg(any(feature = "session-file", feature = "session-db"))]
+use crate::sys::app::init::SessionGcConfig;
+
+use super::{
+    clock::Clock,
+    data::{Data, DataType, StrOrI64},
+    response::Cookie,
+};
+
+/// Additional data of the encrypted ticket, separates the ticket from the cookies
+const SESSION_TICKET: &str = "session-ticket";
+
+/// Header of the binary format of the sessions, the version byte follows it
+const SESSION_MAGIC: &[u8; 3] = b"TWS";
+/// Version of the binary format of the sessions
+const SESSION_VERSION: u8 = 2;
 
 #[repr(u8)]
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize, Hash)]
@@ -55,14 +86,130 @@ impl From<Flash> for u8 {
         value as u8
     }
 }
+
+impl Flash {
+    /// Name of the kind in the templates
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Flash::Info => "info",
+            Flash::Success => "success",
+            Flash::Warning => "warning",
+            Flash::Error => "error",
+        }
+    }
+}
+
+/// Category of the cookies and the scripts for the consent of the visitor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Purpose {
+    /// Session, security, the choice of the consent itself, always allowed
+    Necessary,
+    /// Language, theme and other settings of the visitor
+    Preferences,
+    /// Statistics of the visits
+    Analytics,
+    /// Advertising and tracking
+    Marketing,
+}
+
+/// Consent of the visitor to the non-essential cookies and scripts, stored with the session
+///
+/// # Example
+///
+/// ```ignore
+/// if this.consent().analytics() {
+///     this.response.js.push("/js/analytics.js".to_owned());
+/// }
+/// ```
+///
+/// The templates get the variable `consent` with the keys "given", "preferences", "analytics" and "marketing",
+/// for example `{% if consent.analytics %}`. Without the answer of the visitor only the necessary cookies are allowed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Consent {
+    given: bool,
+    preferences: bool,
+    analytics: bool,
+    marketing: bool,
+}
+
+impl Consent {
+    /// Answer of the visitor by the categories
+    pub fn new(preferences: bool, analytics: bool, marketing: bool) -> Consent {
+        Consent {
+            given: true,
+            preferences,
+            analytics,
+            marketing,
+        }
+    }
+
+    /// All categories are accepted
+    pub fn all() -> Consent {
+        Consent::new(true, true, true)
+    }
+
+    /// All non-essential categories are rejected, the banner is not shown again
+    pub fn necessary() -> Consent {
+        Consent::new(false, false, false)
+    }
+
+    /// The visitor made the choice, otherwise the banner should be shown
+    pub fn given(&self) -> bool {
+        self.given
+    }
+
+    pub fn preferences(&self) -> bool {
+        self.preferences
+    }
+
+    pub fn analytics(&self) -> bool {
+        self.analytics
+    }
+
+    pub fn marketing(&self) -> bool {
+        self.marketing
+    }
+
+    /// The category is allowed
+    pub fn allows(&self, purpose: Purpose) -> bool {
+        match purpose {
+            Purpose::Necessary => true,
+            Purpose::Preferences => self.preferences,
+            Purpose::Analytics => self.analytics,
+            Purpose::Marketing => self.marketing,
+        }
+    }
+}
+
+/// Map for the templates
+impl From<Consent> for Data {
+    fn from(consent: Consent) -> Data {
+        let mut map = HashMap::with_capacity(4);
+        map.insert(fnv1a_64(b"given"), Data::Bool(consent.given));
+        map.insert(fnv1a_64(b"preferences"), Data::Bool(consent.preferences));
+        map.insert(fnv1a_64(b"analytics"), Data::Bool(consent.analytics));
+        map.insert(fnv1a_64(b"marketing"), Data::Bool(consent.marketing));
+        Data::Map(map)
+    }
+}
 #[cfg(feature = "session-file")]
 const PATH_DEEP: usize = 6;
 
+/// One step of the removal of the expired sessions, the removed ids are returned for the count
+#[cfg(all(feature = "session-db", feature = "pgsql"))]
+const SESSION_GC: &str = "DELETE FROM session WHERE session_id IN (SELECT session_id FROM session WHERE last < now() - $1::int8 * interval '1 second' LIMIT $2) RETURNING session_id";
+/// One step of the removal of the expired sessions, the removed ids are returned for the count
+#[cfg(all(feature = "session-db", feature = "mssql"))]
+const SESSION_GC: &str =
+    "DELETE TOP (@P2) FROM [session] OUTPUT deleted.session_id WHERE [last] < DATEADD(second, -CAST(@P1 AS INT), SYSDATETIMEOFFSET())";
+
 pub(crate) struct SessionArg {
     /// Session key
     pub session_key: Arc<String>,
     #[cfg(any(feature = "session-memory", feature = "session-file"))]
     pub session_path: Arc<PathBuf>,
+    /// Binding of the session to the client
+    pub bind: SessionBind,
     #[cfg(feature = "session-db")]
     pub db: Arc<DB>,
 }
@@ -72,6 +219,8 @@ pub(crate) struct SessionArg {
 pub struct SessionLoader {
     /// Session key
     pub session_key: Arc<String>,
+    /// Binding of the session to the client
+    bind: SessionBind,
     #[cfg(feature = "session-db")]
     db: Arc<DB>,
     #[cfg(feature = "session-memory")]
@@ -103,11 +252,11 @@ impl SessionLoader {
                     return Err(());
                 }
             };
-            match bincode::deserialize::<HashMap<i64, Session>>(&data) {
+            match SessionCodec::decode::<HashMap<i64, Session>>(&data) {
                 Ok(data) => data,
                 Err(_e) => {
-                    log!(stop, 0, "{}", _e);
-                    return Err(());
+                    log!(warning, 0, "Sessions are not loaded from {:?}: {}", root, _e);
+                    HashMap::new()
                 }
             }
         } else {
@@ -116,6 +265,7 @@ impl SessionLoader {
 
         Ok(SessionLoader {
             session_key: arg.session_key,
+            bind: arg.bind,
             #[cfg(feature = "session-db")]
             db: arg.db,
             #[cfg(feature = "session-memory")]
@@ -130,7 +280,7 @@ impl SessionLoader {
         {
             let lock = self.data.lock().await;
             if !lock.is_empty() {
-                let data = match bincode::serialize(&*lock) {
+                let data = match SessionCodec::encode(&*lock) {
                     Ok(data) => data,
                     Err(_e) => {
                         log!(stop, 0, "{}", _e);
@@ -151,7 +301,8 @@ impl SessionLoader {
         Ok(())
     }
 
-    pub(crate) async fn load(&self, session: Option<String>) -> Result<Session, ()> {
+    /// Load the session of the client, the session of the other client is cleared by the binding
+    pub(crate) async fn load(&self, session: Option<String>, ip: Option<IpAddr>, agent: &str) -> Result<Session, ()> {
         let s = match session {
             Some(session) => {
                 let key = fnv1a_64(session.as_bytes());
@@ -161,6 +312,7 @@ impl SessionLoader {
                         let mut s = s.clone();
                         s.session = session;
                         s.change = false;
+                        s.created = false;
                         s
                     }
                     None => Session {
@@ -174,6 +326,10 @@ impl SessionLoader {
                         #[cfg(feature = "access-db")]
                         user_id: None,
                         change: false,
+                        created: false,
+                        robot: false,
+                        client: None,
+                        consent: Consent::default(),
                     },
                 }
                 #[cfg(feature = "session-file")]
@@ -191,17 +347,18 @@ impl SessionLoader {
                             return Err(());
                         }
                     };
-                    let mut s = match bincode::deserialize::<Session>(&data) {
+                    let mut s = match SessionCodec::decode::<Session>(&data) {
                         Ok(data) => data,
                         Err(_e) => {
-                            log!(stop, 0, "{}", _e);
-                            return Err(());
+                            log!(warning, 0, "Session is not loaded from {:?}: {}", path, _e);
+                            Session::new()
                         }
                     };
                     s.session = session;
                     s.path = Some(path);
                     s.new = false;
                     s.change = false;
+                    s.created = false;
                     s
                 } else {
                     Session {
@@ -215,8 +372,12 @@ impl SessionLoader {
                         #[cfg(feature = "access-db")]
                         user_id: None,
                         change: false,
+                        created: false,
+                        robot: false,
                         path: Some(path),
                         new: true,
+                        client: None,
+                        consent: Consent::default(),
                     }
                 }
                 #[cfg(feature = "session-db")]
@@ -234,22 +395,30 @@ impl SessionLoader {
                                 #[cfg(feature = "access-db")]
                                 user_id: None,
                                 change: false,
+                                created: false,
+                                robot: false,
                                 new: true,
+                                client: None,
+                                consent: Consent::default(),
                             }
                         } else {
                             let row = unsafe { res.get_unchecked(0) };
                             let mut s = {
-                                let data: Vec<u8> = row.get(2);
-                                match bincode::deserialize::<Session>(&data) {
+                                #[cfg(feature = "pgsql")]
+                                let data: &[u8] = row.get(2);
+                                #[cfg(feature = "mssql")]
+                                let data = row.get::<&[u8], _>(2).unwrap_or_default();
+                                match SessionCodec::decode::<Session>(data) {
                                     Ok(data) => data,
                                     Err(_e) => {
-                                        log!(stop, 0, "{}", _e);
-                                        return Err(());
+                                        log!(warning, 0, "Session {} is not loaded: {}", key, _e);
+                                        Session::new()
                                     }
                                 }
                             };
                             s.session = session;
                             s.change = false;
+                            s.created = false;
                             s.new = false;
                             s
                         }
@@ -265,12 +434,17 @@ impl SessionLoader {
                         #[cfg(feature = "access-db")]
                         user_id: None,
                         change: false,
+                        created: false,
+                        robot: false,
                         new: true,
+                        client: None,
+                        consent: Consent::default(),
                     },
                 }
             }
+            // The id is generated when the data is stored for the first time
             None => Session {
-                session: generate_uuid(),
+                session: String::new(),
                 data: HashMap::new(),
                 flash: HashMap::new(),
                 #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
@@ -280,12 +454,71 @@ impl SessionLoader {
                 #[cfg(feature = "access-db")]
                 user_id: None,
                 change: false,
+                created: false,
+                robot: false,
                 #[cfg(feature = "session-file")]
                 path: None,
                 #[cfg(any(feature = "session-file", feature = "session-db"))]
                 new: true,
+                client: None,
+                consent: Consent::default(),
             },
         };
+        self.bind(s, SessionClient { ip, agent: fnv1a_64(agent.as_bytes()) }).await
+    }
+
+    /// Check the binding of the loaded session
+    ///
+    /// The stored session of the other client is cleared, the request gets the new anonymous session.
+    /// The new session and the session of the version 0 have no client, they are bound to the current client.
+    async fn bind(&self, mut s: Session, client: SessionClient) -> Result<Session, ()> {
+        let stored = match &s.client {
+            Some(stored) => stored,
+            None => {
+                if !s.is_empty() {
+                    s.change = true;
+                }
+                s.client = Some(client);
+                return Ok(s);
+            }
+        };
+        // The agent is stored as the hash
+        let (_field, _from, _to) = if self.bind.ip && stored.ip != client.ip {
+            ("IP address", format!("{:?}", stored.ip), format!("{:?}", client.ip))
+        } else if self.bind.subnet && !SessionClient::same_subnet(stored.ip, client.ip) {
+            ("network", format!("{:?}", stored.ip), format!("{:?}", client.ip))
+        } else if self.bind.agent && stored.agent != client.agent {
+            ("User-Agent", format!("{:x}", stored.agent), format!("{:x}", client.agent))
+        } else {
+            return Ok(s);
+        };
+        log!(warning, 0, "Session is cleared, the {} of the client is changed from {} to {}", _field, _from, _to);
+        s.data.clear();
+        s.flash.clear();
+        s.consent = Consent::default();
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        {
+            s.lang_id = None;
+        }
+        #[cfg(feature = "access-db")]
+        {
+            s.role_id = None;
+            s.user_id = None;
+        }
+        s.change = true;
+        self.save(s.clone()).await?;
+
+        s.session = String::new();
+        s.change = false;
+        s.client = Some(client);
+        #[cfg(feature = "session-file")]
+        {
+            s.path = None;
+        }
+        #[cfg(any(feature = "session-file", feature = "session-db"))]
+        {
+            s.new = true;
+        }
         Ok(s)
     }
 
@@ -302,18 +535,18 @@ impl SessionLoader {
     }
 
     pub(crate) async fn save(&self, session: Session) -> Result<(), ()> {
-        if session.change {
+        if session.change && !session.session.is_empty() {
             #[cfg(feature = "session-memory")]
             {
                 let key = fnv1a_64(session.session.as_bytes());
-                if session.data.is_empty() && session.flash.is_empty() {
+                if session.is_empty() {
                     self.data.lock().await.remove(&key);
                 } else {
                     self.data.lock().await.insert(key, session);
                 }
             }
             #[cfg(feature = "session-file")]
-            if session.data.is_empty() && session.flash.is_empty() {
+            if session.is_empty() {
                 if !session.new {
                     let path = unsafe { session.path.unwrap_unchecked() };
                     if let Err(e) = remove_file(path).await {
@@ -324,7 +557,7 @@ impl SessionLoader {
                     }
                 }
             } else {
-                let data = match bincode::serialize(&session) {
+                let data = match SessionCodec::encode(&session) {
                     Ok(data) => data,
                     Err(_e) => {
                         log!(stop, 0, "{}", _e);
@@ -348,7 +581,7 @@ impl SessionLoader {
             }
             #[cfg(feature = "session-db")]
             {
-                let data = match bincode::serialize(&session) {
+                let data = match SessionCodec::encode(&session) {
                     Ok(data) => data,
                     Err(_e) => {
                         log!(stop, 0, "{}", _e);
@@ -356,11 +589,14 @@ impl SessionLoader {
                     }
                 };
                 let key = fnv1a_64(session.session.as_bytes());
+                #[cfg(feature = "access-db")]
+                let user_id = session.user_id.unwrap_or(0) as i64;
+                #[cfg(not(feature = "access-db"))]
                 let user_id = 0_i64;
                 let lang_id = 0_i64;
                 if !session.new {
                     self.db.execute_prepare(m_fnv1a_64!("lib_set_session"), &[&user_id, &lang_id, &data, &key]).await;
-                } else {
+                } else if !session.is_empty() {
                     self.db.execute_prepare(m_fnv1a_64!("lib_add_session"), &[&session.session, &key, &user_id, &lang_id, &data]).await;
                 }
             }
@@ -369,6 +605,318 @@ impl SessionLoader {
     }
 }
 
+/// Binary format of the sessions of all backends
+///
+/// Version 1 and later is "TWS", the version byte and bincode with the varint integers,
+/// the keys, the lengths of the strings and the small numbers take 1-2 bytes instead of 8.
+/// The data without the header is version 0 of the previous releases, bincode with the fixed integers.
+/// The older versions are read with the layouts `SessionV0` and `SessionV1`, they are migrated on the load
+/// and are written in the current version on the next save.
+/// The data which can not be decoded is replaced by the new session.
+/// The fields of the session depend on the features "lang-*" and "access-db",
+/// so the sessions are lost after the change of these features.
+struct SessionCodec;
+
+impl SessionCodec {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, bincode::Error> {
+        let options = bincode::options();
+        let mut data = Vec::with_capacity(SESSION_MAGIC.len() + 1 + options.serialized_size(value)? as usize);
+        data.extend_from_slice(SESSION_MAGIC);
+        data.push(SESSION_VERSION);
+        options.serialize_into(&mut data, value)?;
+        Ok(data)
+    }
+
+    fn decode<T: SessionLayout>(data: &[u8]) -> Result<T, bincode::Error> {
+        match data.strip_prefix(SESSION_MAGIC.as_slice()) {
+            Some([SESSION_VERSION, data @ ..]) => bincode::options().deserialize(data),
+            Some([1, data @ ..]) => bincode::options().deserialize::<T::V1>(data).map(T::from_v1),
+            Some([version, ..]) => Err(Box::new(bincode::ErrorKind::Custom(format!("Unknown version {} of the session", version)))),
+            _ => bincode::deserialize::<T::V0>(data).map(T::from_v0),
+        }
+    }
+}
+
+/// Stored value of the sessions with the layouts of the previous versions
+trait SessionLayout: DeserializeOwned {
+    /// Layout of the version 0
+    type V0: DeserializeOwned;
+    /// Layout of the version 1
+    type V1: DeserializeOwned;
+
+    fn from_v0(value: Self::V0) -> Self;
+    fn from_v1(value: Self::V1) -> Self;
+}
+
+impl SessionLayout for Session {
+    type V0 = SessionV0;
+    type V1 = SessionV1;
+
+    fn from_v0(value: SessionV0) -> Self {
+        value.into()
+    }
+
+    fn from_v1(value: SessionV1) -> Self {
+        value.into()
+    }
+}
+
+impl SessionLayout for HashMap<i64, Session> {
+    type V0 = HashMap<i64, SessionV0>;
+    type V1 = HashMap<i64, SessionV1>;
+
+    fn from_v0(value: HashMap<i64, SessionV0>) -> Self {
+        value.into_iter().map(|(key, s)| (key, s.into())).collect()
+    }
+
+    fn from_v1(value: HashMap<i64, SessionV1>) -> Self {
+        value.into_iter().map(|(key, s)| (key, s.into())).collect()
+    }
+}
+
+/// Session of the version 0, the layout of the previous releases
+///
+/// The layout is frozen, the new fields of `Session` are added only with the new version.
+#[derive(Debug, Deserialize)]
+struct SessionV0 {
+    data: HashMap<i64, Data>,
+    flash: HashMap<Flash, Vec<String>>,
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    lang_id: Option<usize>,
+    #[cfg(feature = "access-db")]
+    role_id: Option<usize>,
+    #[cfg(feature = "access-db")]
+    user_id: Option<usize>,
+}
+
+impl From<SessionV0> for Session {
+    fn from(value: SessionV0) -> Self {
+        Session {
+            data: value.data,
+            flash: value.flash,
+            #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+            lang_id: value.lang_id,
+            #[cfg(feature = "access-db")]
+            role_id: value.role_id,
+            #[cfg(feature = "access-db")]
+            user_id: value.user_id,
+            ..Session::new()
+        }
+    }
+}
+
+/// Session of the version 1, with the client and without the consent
+///
+/// The layout is frozen, the consent of the migrated session is not given.
+#[derive(Debug, Deserialize)]
+struct SessionV1 {
+    data: HashMap<i64, Data>,
+    flash: HashMap<Flash, Vec<String>>,
+    #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+    lang_id: Option<usize>,
+    #[cfg(feature = "access-db")]
+    role_id: Option<usize>,
+    #[cfg(feature = "access-db")]
+    user_id: Option<usize>,
+    client: Option<SessionClient>,
+}
+
+impl From<SessionV1> for Session {
+    fn from(value: SessionV1) -> Self {
+        Session {
+            data: value.data,
+            flash: value.flash,
+            #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+            lang_id: value.lang_id,
+            #[cfg(feature = "access-db")]
+            role_id: value.role_id,
+            #[cfg(feature = "access-db")]
+            user_id: value.user_id,
+            client: value.client,
+            ..Session::new()
+        }
+    }
+}
+
+/// Result of the removal of the expired sessions
+#[cfg(any(feature = "session-file", feature = "session-db"))]
+#[derive(Debug, Default)]
+pub(crate) struct SessionGcStat {
+    /// Checked files, the database checks only the removed rows
+    pub checked: u64,
+    pub removed: u64,
+    pub errors: u64,
+    pub time: Duration,
+}
+
+#[cfg(any(feature = "session-file", feature = "session-db"))]
+impl fmt::Display for SessionGcStat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Sessions checked: {}, removed: {}, errors: {}, time: {} ms",
+            self.checked,
+            self.removed,
+            self.errors,
+            self.time.as_millis()
+        )
+    }
+}
+
+/// Removal of the expired sessions by the steps, the command "sessions gc" and the parameter [web] session_gc
+///
+/// The file is expired by the time of the last change, the row of the database by the column `last`
+/// that is updated by each request. The memory sessions have no time and are not removed.
+#[cfg(any(feature = "session-file", feature = "session-db"))]
+pub(crate) struct SessionGc {
+    config: SessionGcConfig,
+    #[cfg(feature = "session-file")]
+    path: Arc<PathBuf>,
+    #[cfg(feature = "session-db")]
+    db: Arc<DB>,
+}
+
+#[cfg(any(feature = "session-file", feature = "session-db"))]
+impl SessionGc {
+    pub(crate) fn new(
+        config: SessionGcConfig,
+        #[cfg(feature = "session-file")] path: Arc<PathBuf>,
+        #[cfg(feature = "session-db")] db: Arc<DB>,
+    ) -> SessionGc {
+        SessionGc {
+            config,
+            #[cfg(feature = "session-file")]
+            path,
+            #[cfg(feature = "session-db")]
+            db,
+        }
+    }
+
+    /// Remove the expired sessions by the interval until the stop of the server, the first removal is after the interval
+    pub(crate) fn start(self) {
+        let period = match self.config.interval {
+            Some(period) => period,
+            None => return,
+        };
+        tokio::spawn(async move {
+            let mut timer = interval(period);
+            timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            timer.tick().await;
+            loop {
+                timer.tick().await;
+                let _stat = self.run().await;
+                log!(info, 0, "{}", _stat);
+            }
+        });
+    }
+
+    /// Remove all expired sessions
+    pub(crate) async fn run(&self) -> SessionGcStat {
+        let start = Instant::now();
+        let mut stat = SessionGcStat::default();
+        #[cfg(feature = "session-file")]
+        self.files(&mut stat).await;
+        #[cfg(feature = "session-db")]
+        self.rows(&mut stat).await;
+        stat.time = start.elapsed();
+        stat
+    }
+
+    /// Files "*.bin" of the folder [web] session_path that were not changed longer than the lifetime
+    #[cfg(feature = "session-file")]
+    async fn files(&self, stat: &mut SessionGcStat) {
+        let expire = match Clock::system().checked_sub(self.config.ttl) {
+            Some(expire) => expire,
+            None => return,
+        };
+        let mut dirs = vec![self.path.as_ref().clone()];
+        let mut step = 0;
+        while let Some(dir) = dirs.pop() {
+            let mut list = match read_dir(&dir).await {
+                Ok(list) => list,
+                Err(_e) => {
+                    log!(warning, 0, "{:?}. Error: {}", dir, _e);
+                    stat.errors += 1;
+                    continue;
+                }
+            };
+            loop {
+                let entry = match list.next_entry().await {
+                    Ok(Some(entry)) => entry,
+                    Ok(None) => break,
+                    Err(_e) => {
+                        log!(warning, 0, "{:?}. Error: {}", dir, _e);
+                        stat.errors += 1;
+                        break;
+                    }
+                };
+                let path = entry.path();
+                let modified = match entry.metadata().await {
+                    Ok(meta) if meta.is_dir() => {
+                        dirs.push(path);
+                        continue;
+                    }
+                    Ok(meta) => meta.modified(),
+                    Err(e) => Err(e),
+                };
+                if path.extension().and_then(|ext| ext.to_str()) != Some("bin") {
+                    continue;
+                }
+                stat.checked += 1;
+                match modified {
+                    Ok(modified) if modified < expire => match remove_file(&path).await {
+                        Ok(()) => stat.removed += 1,
+                        Err(e) if e.kind() == ErrorKind::NotFound => {}
+                        Err(_e) => {
+                            log!(warning, 0, "{:?}. Error: {}", path, _e);
+                            stat.errors += 1;
+                        }
+                    },
+                    Ok(_) => {}
+                    Err(_e) => {
+                        log!(warning, 0, "{:?}. Error: {}", path, _e);
+                        stat.errors += 1;
+                    }
+                }
+                step += 1;
+                if step == self.config.batch {
+                    step = 0;
+                    log!(info, 0, "Sessions checked: {}, removed: {}", stat.checked, stat.removed);
+                    yield_now().await;
+                }
+            }
+        }
+    }
+
+    /// Rows of the table `session` without requests longer than the lifetime, the step is one short transaction
+    #[cfg(feature = "session-db")]
+    async fn rows(&self, stat: &mut SessionGcStat) {
+        // DATEADD of MS SQL Server takes INT
+        let ttl = self.config.ttl.as_secs().min(i32::MAX as u64) as i64;
+        let batch = self.config.batch as i64;
+        loop {
+            #[cfg(feature = "row-data")]
+            let res = self.db.try_query(SESSION_GC, &[&ttl, &batch], false).await;
+            #[cfg(feature = "row-native")]
+            let res = self.db.try_query(SESSION_GC, &[&ttl, &batch]).await;
+            let removed = match res {
+                Ok(rows) => rows.len() as u64,
+                Err(_e) => {
+                    log!(warning, 0, "{}", _e);
+                    stat.errors += 1;
+                    return;
+                }
+            };
+            stat.checked += removed;
+            stat.removed += removed;
+            if removed < batch as u64 {
+                return;
+            }
+            log!(info, 0, "Sessions removed: {}", stat.removed);
+        }
+    }
+}
+
 /// User session
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
@@ -389,22 +937,157 @@ pub struct Session {
     /// User data is changed
     #[serde(skip)]
     change: bool,
+    /// The id is generated in this request, the cookie must be sent
+    #[serde(skip)]
+    created: bool,
+    /// The client is a bot, the new session is not created
+    #[serde(skip)]
+    robot: bool,
     #[cfg(feature = "session-file")]
     #[serde(skip)]
     path: Option<PathBuf>,
     #[cfg(any(feature = "session-file", feature = "session-db"))]
     #[serde(skip)]
     new: bool,
+    /// Client of the session for the binding
+    client: Option<SessionClient>,
+    /// Consent to the non-essential cookies
+    consent: Consent,
+}
+
+/// Client of the session, see `SessionBind`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct SessionClient {
+    ip: Option<IpAddr>,
+    /// Hash of the User-Agent
+    agent: i64,
+}
+
+impl SessionClient {
+    /// The same network, /24 for IPv4 and /64 for IPv6
+    fn same_subnet(a: Option<IpAddr>, b: Option<IpAddr>) -> bool {
+        match (a, b) {
+            (Some(IpAddr::V4(a)), Some(IpAddr::V4(b))) => a.octets()[..3] == b.octets()[..3],
+            (Some(IpAddr::V6(a)), Some(IpAddr::V6(b))) => a.segments()[..4] == b.segments()[..4],
+            (a, b) => a == b,
+        }
+    }
 }
 
 impl Session {
-    /// Set session data
+    /// Session without the data and the client
+    fn new() -> Session {
+        Session {
+            session: String::new(),
+            data: HashMap::new(),
+            flash: HashMap::new(),
+            #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+            lang_id: None,
+            #[cfg(feature = "access-db")]
+            role_id: None,
+            #[cfg(feature = "access-db")]
+            user_id: None,
+            change: false,
+            created: false,
+            robot: false,
+            #[cfg(feature = "session-file")]
+            path: None,
+            #[cfg(any(feature = "session-file", feature = "session-db"))]
+            new: false,
+            client: None,
+            consent: Consent::default(),
+        }
+    }
+
+    /// Mark the session as changed, the anonymous visitor gets the id only here
+    fn touch(&mut self) {
+        self.change = true;
+        if self.session.is_empty() && !self.robot {
+            self.session = generate_uuid();
+            self.created = true;
+        }
+    }
+
+    /// The session has no data
+    fn is_empty(&self) -> bool {
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        if self.lang_id.is_some() {
+            return false;
+        }
+        #[cfg(feature = "access-db")]
+        if self.role_id.is_some() || self.user_id.is_some() {
+            return false;
+        }
+        self.data.is_empty() && self.flash.is_empty() && !self.consent.given
+    }
+
+    /// Session cookie for the answer
+    ///
+    /// The cookie is sent only when the new session gets the data, or removed when the data of the session is cleared.
+    pub(crate) fn cookie(&self, session_key: &str) -> Option<Cookie> {
+        if self.created && !self.is_empty() {
+            Some(Cookie::new(session_key, &self.session))
+        } else if !self.created && self.change && !self.session.is_empty() && self.is_empty() {
+            Some(Cookie::remove(session_key))
+        } else {
+            None
+        }
+    }
+
+    /// Encrypted ticket "session:user:expires" of the stored session, `None` for the anonymous visitor without data
+    pub(crate) fn ticket(&self, salt: &str, ttl: u64) -> Option<String> {
+        if self.session.is_empty() || self.is_empty() {
+            return None;
+        }
+        #[cfg(feature = "access-db")]
+        let user_id = self.user_id.unwrap_or(0);
+        #[cfg(not(feature = "access-db"))]
+        let user_id = 0;
+        let expires = Clock::unix() + ttl;
+        Cookie::encrypt(SESSION_TICKET, &format!("{}:{}:{}", self.session, user_id, expires), salt)
+    }
+
+    /// Check the ticket, returns the session id and the user id of the unexpired ticket
+    pub(crate) fn check_ticket(ticket: &str, salt: &str) -> Option<(String, usize)> {
+        let value = Cookie::decrypt(SESSION_TICKET, ticket, salt)?;
+        let mut parts = value.rsplitn(3, ':');
+        let expires: u64 = parts.next()?.parse().ok()?;
+        let user_id: usize = parts.next()?.parse().ok()?;
+        let session = parts.next()?;
+        if Clock::unix() > expires {
+            return None;
+        }
+        Some((session.to_owned(), user_id))
+    }
+
+    /// The session of the ticket still belongs to the same user
+    pub(crate) fn is_ticket_user(&self, _user_id: usize) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        #[cfg(feature = "access-db")]
+        if self.user_id.unwrap_or(0) != _user_id {
+            return false;
+        }
+        true
+    }
+
+    /// Do not create the new session, for the bots
+    pub(crate) fn set_robot(&mut self) {
+        self.robot = true;
+    }
+
+    /// Set session data, the same value does not change the session
     pub fn set<T>(&mut self, key: impl StrOrI64, value: T)
     where
         T: Into<Data>,
     {
-        self.change = true;
-        self.data.insert(key.to_i64(), value.into());
+        let key = key.to_i64();
+        let value = value.into();
+        if self.data.get(&key) != Some(&value) {
+            self.touch();
+            self.data.insert(key, value);
+        }
     }
 
     #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
@@ -414,7 +1097,7 @@ impl Session {
             None => true,
         };
         if change {
-            self.change = true;
+            self.touch();
             self.lang_id = Some(lang_id);
         }
     }
@@ -424,6 +1107,13 @@ impl Session {
         self.data.get(&key.to_i64())
     }
 
+    /// Get session data of the type, for example `this.session.try_get::<i64>("cart")`
+    ///
+    /// Returns `None` if there is no data or it has the other type.
+    pub fn try_get<T: DataType>(&self, key: impl StrOrI64) -> Option<&T> {
+        T::from_data(self.data.get(&key.to_i64())?)
+    }
+
     #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
     pub fn get_lang_id(&self) -> Option<usize> {
         self.lang_id
@@ -431,8 +1121,33 @@ impl Session {
 
     /// Getting session data by deleting it
     pub fn take(&mut self, key: impl StrOrI64) -> Option<Data> {
-        self.change = true;
-        self.data.remove(&key.to_i64())
+        let value = self.data.remove(&key.to_i64());
+        if value.is_some() {
+            self.change = true;
+        }
+        value
+    }
+
+    /// Getting session data of the type by deleting it
+    ///
+    /// Returns `None` if there is no data or it has the other type, the data of the other type is kept.
+    pub fn try_take<T: DataType>(&mut self, key: impl StrOrI64) -> Option<T> {
+        let key = key.to_i64();
+        match T::take_data(self.data.remove(&key)?) {
+            Ok(value) => {
+                self.change = true;
+                Some(value)
+            }
+            Err(value) => {
+                self.data.insert(key, value);
+                None
+            }
+        }
+    }
+
+    /// Session data exists
+    pub fn contains(&self, key: impl StrOrI64) -> bool {
+        self.data.contains_key(&key.to_i64())
     }
 
     #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
@@ -444,19 +1159,22 @@ impl Session {
 
     /// Remove session data
     pub fn remove(&mut self, key: impl StrOrI64) {
-        self.change = true;
-        self.data.remove(&key.to_i64());
+        if self.data.remove(&key.to_i64()).is_some() {
+            self.change = true;
+        }
     }
 
     /// Clear session data
     pub fn clear(&mut self) {
-        self.change = true;
-        self.data.clear();
+        if !self.data.is_empty() {
+            self.change = true;
+            self.data.clear();
+        }
     }
 
     /// Set flash message to session data
     pub(crate) fn set_flash(&mut self, kind: Flash, value: String) {
-        self.change = true;
+        self.touch();
         match self.flash.entry(kind) {
             Entry::Vacant(entry) => {
                 entry.insert(vec![value]);
@@ -477,4 +1195,175 @@ impl Session {
             Some(take(&mut self.flash))
         }
     }
+
+    /// Take flash messages as the list for the templates, every item has `kind` and `text`
+    ///
+    /// The kinds go from `info` to `error`, the messages of the kind in the order of adding.
+    #[cfg(any(feature = "html-static", feature = "html-reload"))]
+    pub(crate) fn take_flash_data(&mut self) -> Option<Data> {
+        let mut flash: Vec<(Flash, Vec<String>)> = self.take_flash()?.into_iter().collect();
+        flash.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut vec = Vec::with_capacity(flash.iter().map(|(_, list)| list.len()).sum());
+        for (kind, list) in flash {
+            for text in list {
+                let mut map = HashMap::with_capacity(2);
+                map.insert(fnv1a_64(b"kind"), Data::String(kind.as_str().to_owned()));
+                map.insert(fnv1a_64(b"text"), Data::String(text));
+                vec.push(Data::Map(map));
+            }
+        }
+        Some(Data::Vec(vec))
+    }
+
+    pub(crate) fn consent(&self) -> Consent {
+        self.consent
+    }
+
+    /// Store the answer of the visitor, the anonymous visitor gets the session
+    pub(crate) fn set_consent(&mut self, consent: Consent) {
+        self.touch();
+        self.consent = consent;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, hint::black_box, time::Instant};
+
+    use serde::Serialize;
+
+    use crate::sys::web::data::Data;
+
+    use super::{Flash, Session, SessionCodec, SessionLayout};
+
+    const ROUNDS: u32 = 100_000;
+
+    impl SessionLayout for HashMap<i64, Data> {
+        type V0 = Self;
+        type V1 = Self;
+
+        fn from_v0(value: Self) -> Self {
+            value
+        }
+
+        fn from_v1(value: Self) -> Self {
+            value
+        }
+    }
+
+    /// Typical data of the session: the ids, the flags, the texts and the small list
+    fn data() -> HashMap<i64, Data> {
+        let mut data = HashMap::new();
+        for key in 0..8_i64 {
+            data.insert(key, Data::I64(key * 1_000));
+            data.insert(100 + key, Data::Bool(key % 2 == 0));
+            data.insert(200 + key, Data::String(format!("value of the session {}", key)));
+        }
+        data.insert(300, Data::Vec((0..16).map(Data::U32).collect()));
+        data
+    }
+
+    /// Session of the previous releases, the layout of the baseline `Session` without the skipped fields
+    #[derive(Serialize)]
+    struct Baseline {
+        data: HashMap<i64, Data>,
+        flash: HashMap<Flash, Vec<String>>,
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        lang_id: Option<usize>,
+        #[cfg(feature = "access-db")]
+        role_id: Option<usize>,
+        #[cfg(feature = "access-db")]
+        user_id: Option<usize>,
+    }
+
+    fn baseline() -> Baseline {
+        Baseline {
+            data: data(),
+            flash: HashMap::from([(Flash::Info, vec!["Saved".to_owned()])]),
+            #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+            lang_id: Some(1),
+            #[cfg(feature = "access-db")]
+            role_id: Some(2),
+            #[cfg(feature = "access-db")]
+            user_id: Some(3),
+        }
+    }
+
+    fn assert_baseline(s: &Session) {
+        let baseline = baseline();
+        assert_eq!(s.data, baseline.data);
+        assert_eq!(s.flash, baseline.flash);
+        #[cfg(any(feature = "lang-static", feature = "lang-reload"))]
+        assert_eq!(s.lang_id, baseline.lang_id);
+        #[cfg(feature = "access-db")]
+        {
+            assert_eq!(s.role_id, baseline.role_id);
+            assert_eq!(s.user_id, baseline.user_id);
+        }
+        assert!(s.client.is_none());
+        assert!(!s.consent.given);
+    }
+
+    /// The session of the version 0 is written by `bincode::serialize` with the fixed integers
+    #[test]
+    fn session_v0() {
+        let data = bincode::serialize(&baseline()).unwrap();
+        assert_baseline(&SessionCodec::decode::<Session>(&data).unwrap());
+
+        let data = bincode::serialize(&HashMap::from([(7_i64, baseline())])).unwrap();
+        let list = SessionCodec::decode::<HashMap<i64, Session>>(&data).unwrap();
+        assert_eq!(list.len(), 1);
+        assert_baseline(&list[&7]);
+    }
+
+    /// The migrated session is written and read in the current version, the broken data is an error
+    #[test]
+    fn session_current() {
+        let data = bincode::serialize(&baseline()).unwrap();
+        let s = SessionCodec::decode::<Session>(&data).unwrap();
+        let data = SessionCodec::encode(&s).unwrap();
+        assert_baseline(&SessionCodec::decode::<Session>(&data).unwrap());
+
+        assert!(SessionCodec::decode::<Session>(&data[..data.len() - 1]).is_err());
+        assert!(SessionCodec::decode::<Session>(b"TWS\xff").is_err());
+    }
+
+    /// Save and load of the session in the version 0 and in the version 2
+    ///
+    /// `cargo test --release --features session-memory session_codec -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn session_codec() {
+        let data = data();
+
+        let v0 = bincode::serialize(&data).unwrap();
+        let v2 = SessionCodec::encode(&data).unwrap();
+        assert_eq!(SessionCodec::decode::<HashMap<i64, Data>>(&v0).unwrap(), data);
+        assert_eq!(SessionCodec::decode::<HashMap<i64, Data>>(&v2).unwrap(), data);
+
+        let time = Instant::now();
+        for _ in 0..ROUNDS {
+            black_box(bincode::serialize(black_box(&data)).unwrap());
+        }
+        let save_v0 = time.elapsed() / ROUNDS;
+        let time = Instant::now();
+        for _ in 0..ROUNDS {
+            black_box(SessionCodec::encode(black_box(&data)).unwrap());
+        }
+        let save_v2 = time.elapsed() / ROUNDS;
+        let time = Instant::now();
+        for _ in 0..ROUNDS {
+            black_box(SessionCodec::decode::<HashMap<i64, Data>>(black_box(&v0)).unwrap());
+        }
+        let load_v0 = time.elapsed() / ROUNDS;
+        let time = Instant::now();
+        for _ in 0..ROUNDS {
+            black_box(SessionCodec::decode::<HashMap<i64, Data>>(black_box(&v2)).unwrap());
+        }
+        let load_v2 = time.elapsed() / ROUNDS;
+
+        println!("v0: {} bytes, save {:?}, load {:?}", v0.len(), save_v0, load_v0);
+        println!("v2: {} bytes, save {:?}, load {:?}", v2.len(), save_v2, load_v2);
+        assert!(v2.len() < v0.len());
+    }
 }
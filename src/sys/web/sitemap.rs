@@ -0,0 +1,162 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+
+#[cfg(all(feature = "route-db", any(feature = "lang-static", feature = "lang-reload")))]
+use std::collections::HashMap;
+
+#[cfg(all(feature = "route-db", any(feature = "lang-static", feature = "lang-reload")))]
+use tiny_web_macro::fnv1a_64 as m_fnv1a_64;
+
+use super::{
+    action::{Action, Answer},
+    feed::Feed,
+};
+
+/// Versions of the page: the controller and the params, the language and the url of each version
+#[cfg(all(feature = "route-db", any(feature = "lang-static", feature = "lang-reload")))]
+type SitemapPage = ((i64, String), Vec<(Option<i64>, String)>);
+
+/// Sitemap of the site, sitemaps.org 0.9
+///
+/// # Example
+///
+/// ```ignore
+/// let mut sitemap = this.sitemap_routes("https://example.com").await;
+/// sitemap.urls.push(SitemapUrl::new("https://example.com/blog/hello"));
+/// this.sitemap(&sitemap)
+/// ```
+///
+/// The localized versions of the page are the links `xhtml:link rel="alternate" hreflang` of each version.
+#[derive(Debug, Clone, Default)]
+pub struct Sitemap {
+    pub urls: Vec<SitemapUrl>,
+}
+
+/// Page of the sitemap
+#[derive(Debug, Clone)]
+pub struct SitemapUrl {
+    pub loc: String,
+    pub lastmod: Option<DateTime<Utc>>,
+    /// "always", "hourly", "daily", "weekly", "monthly", "yearly" or "never"
+    pub changefreq: Option<String>,
+    /// From 0.0 to 1.0
+    pub priority: Option<f32>,
+    /// Localized versions of the page with this one, the code of the language or "x-default" and the url
+    pub alternates: Vec<(String, String)>,
+}
+
+impl Sitemap {
+    pub fn new() -> Sitemap {
+        Sitemap { urls: Vec::new() }
+    }
+
+    /// XML document, the namespace xhtml is added for the localized versions
+    pub fn xml(&self) -> String {
+        let mut xml = String::with_capacity(256 + self.urls.len() * 256);
+        xml.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        if self.urls.iter().any(|url| !url.alternates.is_empty()) {
+            xml.push_str(r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9" xmlns:xhtml="http://www.w3.org/1999/xhtml">"#);
+        } else {
+            xml.push_str(r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#);
+        }
+        for url in &self.urls {
+            xml.push_str("<url>");
+            xml.push_str(&format!("<loc>{}</loc>", Feed::escape(&url.loc)));
+            if let Some(lastmod) = &url.lastmod {
+                xml.push_str(&format!("<lastmod>{}</lastmod>", lastmod.to_rfc3339_opts(SecondsFormat::Secs, true)));
+            }
+            if let Some(changefreq) = &url.changefreq {
+                xml.push_str(&format!("<changefreq>{}</changefreq>", Feed::escape(changefreq)));
+            }
+            if let Some(priority) = url.priority {
+                xml.push_str(&format!("<priority>{:.1}</priority>", priority.clamp(0.0, 1.0)));
+            }
+            for (lang, href) in &url.alternates {
+                xml.push_str(&format!(r#"<xhtml:link rel="alternate" hreflang="{}" href="{}"/>"#, Feed::escape(lang), Feed::escape(href)));
+            }
+            xml.push_str("</url>");
+        }
+        xml.push_str("</urlset>");
+        xml
+    }
+}
+
+impl SitemapUrl {
+    pub fn new(loc: &str) -> SitemapUrl {
+        SitemapUrl {
+            loc: loc.to_owned(),
+            lastmod: None,
+            changefreq: None,
+            priority: None,
+            alternates: Vec::new(),
+        }
+    }
+}
+
+impl Action {
+    /// Answer with the sitemap, Content-Type application/xml
+    pub fn sitemap(&mut self, sitemap: &Sitemap) -> Answer {
+        self.response.content_type = Some("application/xml; charset=utf-8".to_owned());
+        Answer::String(sitemap.xml())
+    }
+
+    /// Sitemap of the routes without the patterns of the table `route`, `base` is "https://example.com"
+    ///
+    /// The routes of the same controller and params are the versions of one page, the route with `lang_id`
+    /// is the version of the language, the route without it is "x-default". Without such route the version
+    /// of the default language is "x-default". The routes of the disabled languages are skipped.
+    #[cfg(all(feature = "route-db", any(feature = "lang-static", feature = "lang-reload")))]
+    pub async fn sitemap_routes(&self, base: &str) -> Sitemap {
+        let base = base.trim_end_matches('/');
+        let langs = self.lang_list().await;
+        let codes: HashMap<i64, &str> = langs.iter().map(|lang| (lang.id as i64, lang.code.as_str())).collect();
+        let default = self.lang_default().await as i64;
+
+        let rows = match self.db.query_prepare(m_fnv1a_64!("lib_get_route_all"), &[]).await {
+            Some(rows) => rows,
+            None => return Sitemap::new(),
+        };
+        let mut pages: Vec<SitemapPage> = Vec::new();
+        for row in rows {
+            #[cfg(feature = "pgsql")]
+            let (page, lang_id, url) =
+                ((row.get::<_, i64>(0), row.get::<_, String>(1)), row.get::<_, Option<i64>>(2), row.get::<_, String>(3));
+            #[cfg(feature = "mssql")]
+            let (page, lang_id, url) = match (row.get::<i64, _>(0), row.get::<&str, _>(1), row.get::<&str, _>(3)) {
+                (Some(controller_id), Some(params), Some(url)) => {
+                    ((controller_id, params.to_owned()), row.get::<i64, _>(2), url.to_owned())
+                }
+                _ => continue,
+            };
+            if lang_id.is_some_and(|lang_id| !codes.contains_key(&lang_id)) {
+                continue;
+            }
+            match pages.last_mut() {
+                Some((last, list)) if *last == page => list.push((lang_id, format!("{}{}", base, url))),
+                _ => pages.push((page, vec![(lang_id, format!("{}{}", base, url))])),
+            }
+        }
+
+        let mut sitemap = Sitemap::new();
+        for (_, list) in pages {
+            let mut alternates: Vec<(String, String)> =
+                list.iter().filter_map(|(lang_id, url)| Some(((*codes.get(lang_id.as_ref()?)?).to_owned(), url.clone()))).collect();
+            if alternates.len() > 1 || (alternates.len() == 1 && list.len() > 1) {
+                let x_default = list
+                    .iter()
+                    .find(|(lang_id, _)| lang_id.is_none())
+                    .or_else(|| list.iter().find(|(lang_id, _)| *lang_id == Some(default)));
+                if let Some((_, url)) = x_default {
+                    alternates.push(("x-default".to_owned(), url.clone()));
+                }
+            } else {
+                alternates.clear();
+            }
+            for (_, url) in list {
+                let mut item = SitemapUrl::new(&url);
+                item.alternates = alternates.clone();
+                sitemap.urls.push(item);
+            }
+        }
+        sitemap
+    }
+}
@@ -0,0 +1,451 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::Duration,
+};
+
+use ring::{
+    digest::{digest, SHA256},
+    hmac,
+};
+use rustls::{pki_types::ServerName, ClientConfig, RootCertStore};
+use tokio::{
+    fs::{create_dir_all, read, remove_file, rename, write},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    time::timeout,
+};
+use tokio_rustls::TlsConnector;
+
+use crate::{
+    log,
+    sys::{
+        app::init::{S3Config, StorageBackend, StorageConfig},
+        net::eyeballs::HappyEyeballs,
+    },
+    tool::to_hex,
+};
+
+use super::clock::Clock;
+
+#[cfg(any(feature = "file-disk", feature = "file-memory"))]
+use super::request::WebFile;
+
+/// Max time of the request to S3
+const S3_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Started by the server
+static STORAGE: OnceLock<Storage> = OnceLock::new();
+
+/// Number of the temporary file of the local backend
+static TMP: AtomicU64 = AtomicU64::new(0);
+
+/// Mozilla roots for the HTTPS endpoints
+static TLS: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+
+/// Storage of the files by the key "avatar/42.png", the section [storage]
+///
+/// The backend is chosen by the configuration, so the same code works with the local folder in the development
+/// and with S3-compatible storage on the stateless containers:
+///
+/// * `local` - the folder [storage] path, the front web server serves it by the prefix [storage] url.
+/// * `s3` - Amazon S3, MinIO, Cloudflare R2 and the others with the signature AWS4-HMAC-SHA256.
+///   `url` is the public url of the bucket, or the presigned url for [storage] url_ttl seconds.
+/// * `memory` - the files of the process, for the tests.
+///
+/// ```ignore
+/// let key = format!("avatar/{}.png", user_id);
+/// if this.storage().put_file(&key, file).await.is_ok() {
+///     this.set("avatar", Data::String(this.storage().url(&key)));
+/// }
+/// ```
+pub struct Storage {
+    config: Arc<StorageConfig>,
+    /// Files of the memory backend
+    memory: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl Storage {
+    /// Start the storage of the server, only once
+    pub(crate) fn start(config: Arc<StorageConfig>) {
+        let _ = STORAGE.set(Storage::new(config));
+    }
+
+    /// Storage of the server, the memory backend outside of the server
+    pub(crate) fn global() -> &'static Storage {
+        STORAGE.get_or_init(|| {
+            Storage::new(Arc::new(StorageConfig {
+                backend: StorageBackend::Memory,
+                url: Some("/storage".to_owned()),
+            }))
+        })
+    }
+
+    fn new(config: Arc<StorageConfig>) -> Storage {
+        Storage {
+            config,
+            memory: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Save the file, `mime` is the Content-Type for S3
+    pub async fn put(&self, key: &str, data: &[u8], mime: Option<&str>) -> Result<(), ()> {
+        if !Storage::check(key) {
+            log!(warning, 0, "{}", key);
+            return Err(());
+        }
+        let res = match &self.config.backend {
+            StorageBackend::Local(root) => Storage::local_put(root.join(key), data).await,
+            StorageBackend::S3(s3) => S3::put(s3, key, data, mime).await,
+            StorageBackend::Memory => {
+                self.memory().insert(key.to_owned(), data.to_vec());
+                Ok(())
+            }
+        };
+        res.map_err(|_e| log!(warning, 0, "{}. Error: {}", key, _e))
+    }
+
+    /// Save the uploaded file of the request
+    #[cfg(any(feature = "file-disk", feature = "file-memory"))]
+    pub async fn put_file(&self, key: &str, file: &WebFile) -> Result<(), ()> {
+        #[cfg(feature = "file-disk")]
+        let data = match read(&file.tmp).await {
+            Ok(data) => data,
+            Err(_e) => {
+                log!(warning, 0, "{:?}. Error: {}", file.tmp, _e);
+                return Err(());
+            }
+        };
+        #[cfg(feature = "file-memory")]
+        let data = &file.data;
+        let mime = Storage::mime(&file.file);
+        self.put(key, &data[..], mime).await
+    }
+
+    /// Content of the file, `None` if there is no file
+    pub async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        if !Storage::check(key) {
+            log!(warning, 0, "{}", key);
+            return None;
+        }
+        let res = match &self.config.backend {
+            StorageBackend::Local(root) => {
+                let path = root.join(key);
+                match read(&path).await {
+                    Ok(data) => Ok(Some(data)),
+                    Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            StorageBackend::S3(s3) => S3::get(s3, key).await,
+            StorageBackend::Memory => Ok(self.memory().get(key).cloned()),
+        };
+        res.unwrap_or_else(|_e| {
+            log!(warning, 0, "{}. Error: {}", key, _e);
+            None
+        })
+    }
+
+    /// Remove the file, the missing file is not the error
+    pub async fn delete(&self, key: &str) -> Result<(), ()> {
+        if !Storage::check(key) {
+            log!(warning, 0, "{}", key);
+            return Err(());
+        }
+        let res = match &self.config.backend {
+            StorageBackend::Local(root) => match remove_file(root.join(key)).await {
+                Ok(()) => Ok(()),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e.to_string()),
+            },
+            StorageBackend::S3(s3) => S3::delete(s3, key).await,
+            StorageBackend::Memory => {
+                self.memory().remove(key);
+                Ok(())
+            }
+        };
+        res.map_err(|_e| log!(warning, 0, "{}. Error: {}", key, _e))
+    }
+
+    /// Url of the file for the browser
+    pub fn url(&self, key: &str) -> String {
+        match (&self.config.url, &self.config.backend) {
+            (Some(url), _) => format!("{}/{}", url.trim_end_matches('/'), Storage::encode(key, true)),
+            (None, StorageBackend::S3(s3)) => S3::presign(s3, key),
+            (None, _) => format!("/{}", Storage::encode(key, true)),
+        }
+    }
+
+    /// The key is the relative path without ".." and "\"
+    fn check(key: &str) -> bool {
+        !key.is_empty()
+            && !key.starts_with('/')
+            && !key.contains('\\')
+            && !key.chars().any(char::is_control)
+            && key.split('/').all(|part| !part.is_empty() && part != "." && part != "..")
+    }
+
+    /// The file is written near and renamed, the readers do not see the part of the file
+    async fn local_put(path: PathBuf, data: &[u8]) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir).await.map_err(|e| e.to_string())?;
+        }
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(format!(".{}.tmp", TMP.fetch_add(1, Ordering::Relaxed)));
+        write(&tmp, data).await.map_err(|e| e.to_string())?;
+        if let Err(e) = rename(&tmp, &path).await {
+            let _ = remove_file(&tmp).await;
+            return Err(e.to_string());
+        }
+        Ok(())
+    }
+
+    fn memory(&self) -> std::sync::MutexGuard<'_, HashMap<String, Vec<u8>>> {
+        match self.memory.lock() {
+            Ok(memory) => memory,
+            Err(e) => e.into_inner(),
+        }
+    }
+
+    #[cfg(any(feature = "file-disk", feature = "file-memory"))]
+    fn mime(name: &str) -> Option<&'static str> {
+        let ext = name.rsplit_once('.')?.1.to_ascii_lowercase();
+        Some(match ext.as_str() {
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "gif" => "image/gif",
+            "webp" => "image/webp",
+            "svg" => "image/svg+xml",
+            "pdf" => "application/pdf",
+            "txt" => "text/plain",
+            "csv" => "text/csv",
+            "json" => "application/json",
+            "zip" => "application/zip",
+            "mp4" => "video/mp4",
+            "mp3" => "audio/mpeg",
+            _ => return None,
+        })
+    }
+
+    /// URI encoding of AWS, the unreserved characters are not encoded
+    fn encode(value: &str, path: bool) -> String {
+        let mut res = String::with_capacity(value.len());
+        for byte in value.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => res.push(byte as char),
+                b'/' if path => res.push('/'),
+                _ => res.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+        res
+    }
+}
+
+/// Requests of the S3 API
+struct S3;
+
+/// Signed request
+struct S3Request {
+    /// Name of the server for the connection and TLS
+    name: String,
+    /// Header "Host"
+    host: String,
+    path: String,
+    headers: Vec<(&'static str, String)>,
+}
+
+impl S3 {
+    async fn put(s3: &S3Config, key: &str, data: &[u8], mime: Option<&str>) -> Result<(), String> {
+        let mut request = S3::sign(s3, "PUT", key, data);
+        if let Some(mime) = mime {
+            request.headers.push(("Content-Type", mime.to_owned()));
+        }
+        match S3::send(s3, "PUT", request, data).await? {
+            (200, _) => Ok(()),
+            (status, body) => Err(S3::error(status, &body)),
+        }
+    }
+
+    async fn get(s3: &S3Config, key: &str) -> Result<Option<Vec<u8>>, String> {
+        let request = S3::sign(s3, "GET", key, &[]);
+        match S3::send(s3, "GET", request, &[]).await? {
+            (200, body) => Ok(Some(body)),
+            (404, _) => Ok(None),
+            (status, body) => Err(S3::error(status, &body)),
+        }
+    }
+
+    async fn delete(s3: &S3Config, key: &str) -> Result<(), String> {
+        let request = S3::sign(s3, "DELETE", key, &[]);
+        match S3::send(s3, "DELETE", request, &[]).await? {
+            (200 | 204 | 404, _) => Ok(()),
+            (status, body) => Err(S3::error(status, &body)),
+        }
+    }
+
+    /// Server, header "Host" and path of the key, "bucket.host/key" or "host/bucket/key"
+    fn address(s3: &S3Config, key: &str) -> (String, String, String) {
+        let (name, path) = if s3.path_style {
+            (s3.host.clone(), format!("/{}/{}", Storage::encode(&s3.bucket, false), Storage::encode(key, true)))
+        } else {
+            (format!("{}.{}", s3.bucket, s3.host), format!("/{}", Storage::encode(key, true)))
+        };
+        let host = if name.contains(':') { format!("[{}]", name) } else { name.clone() };
+        let host = match (s3.tls, s3.port) {
+            (true, 443) | (false, 80) => host,
+            _ => format!("{}:{}", host, s3.port),
+        };
+        (name, host, path)
+    }
+
+    /// Headers with the signature AWS4-HMAC-SHA256
+    fn sign(s3: &S3Config, method: &str, key: &str, body: &[u8]) -> S3Request {
+        let (name, host, path) = S3::address(s3, key);
+        let amz_date = Clock::utc().format("%Y%m%dT%H%M%SZ").to_string();
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/s3/aws4_request", date, s3.region);
+        let hash = to_hex(digest(&SHA256, body).as_ref());
+        let canonical = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\nhost;x-amz-content-sha256;x-amz-date\n{}",
+            method, path, host, hash, amz_date, hash
+        );
+        let signature = S3::signature(s3, date, &amz_date, &scope, &canonical);
+        S3Request {
+            name,
+            host,
+            path,
+            headers: vec![
+                ("X-Amz-Content-Sha256", hash),
+                ("X-Amz-Date", amz_date),
+                (
+                    "Authorization",
+                    format!(
+                        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={}",
+                        s3.key, scope, signature
+                    ),
+                ),
+            ],
+        }
+    }
+
+    /// GET url with the signature in the query, valid for [storage] url_ttl seconds
+    fn presign(s3: &S3Config, key: &str) -> String {
+        let (_, host, path) = S3::address(s3, key);
+        let amz_date = Clock::utc().format("%Y%m%dT%H%M%SZ").to_string();
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/s3/aws4_request", date, s3.region);
+        // The parameters are sorted by the name
+        let query = format!(
+            "X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Credential={}&X-Amz-Date={}&X-Amz-Expires={}&X-Amz-SignedHeaders=host",
+            Storage::encode(&format!("{}/{}", s3.key, scope), false),
+            amz_date,
+            s3.url_ttl
+        );
+        let canonical = format!("GET\n{}\n{}\nhost:{}\n\nhost\nUNSIGNED-PAYLOAD", path, query, host);
+        let signature = S3::signature(s3, date, &amz_date, &scope, &canonical);
+        format!("{}://{}{}?{}&X-Amz-Signature={}", if s3.tls { "https" } else { "http" }, host, path, query, signature)
+    }
+
+    fn signature(s3: &S3Config, date: &str, amz_date: &str, scope: &str, canonical: &str) -> String {
+        let sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", amz_date, scope, to_hex(digest(&SHA256, canonical.as_bytes()).as_ref()));
+        let mut signing = S3::hmac(format!("AWS4{}", s3.secret).as_bytes(), date.as_bytes());
+        for part in [s3.region.as_str(), "s3", "aws4_request"] {
+            signing = S3::hmac(&signing, part.as_bytes());
+        }
+        to_hex(&S3::hmac(&signing, sign.as_bytes()))
+    }
+
+    /// Status and body of the answer, HTTP/1.1 with `Connection: close`
+    async fn send(s3: &S3Config, method: &str, request: S3Request, body: &[u8]) -> Result<(u16, Vec<u8>), String> {
+        timeout(S3_TIMEOUT, async {
+            let tcp = HappyEyeballs::connect(&request.name, s3.port).await.map_err(|e| e.to_string())?;
+            let mut head = format!(
+                "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+                method,
+                request.path,
+                request.host,
+                body.len()
+            );
+            for (name, value) in &request.headers {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+            head.push_str("\r\n");
+            if s3.tls {
+                let name = ServerName::try_from(request.name.clone()).map_err(|e| e.to_string())?;
+                let tls = TLS.get_or_init(|| {
+                    let mut roots = RootCertStore::empty();
+                    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+                    Arc::new(ClientConfig::builder().with_root_certificates(roots).with_no_client_auth())
+                });
+                let mut stream = TlsConnector::from(Arc::clone(tls)).connect(name, tcp).await.map_err(|e| e.to_string())?;
+                S3::exchange(&mut stream, &head, body).await
+            } else {
+                let mut stream = tcp;
+                S3::exchange(&mut stream, &head, body).await
+            }
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S, head: &str, body: &[u8]) -> Result<(u16, Vec<u8>), String> {
+        stream.write_all(head.as_bytes()).await.map_err(|e| e.to_string())?;
+        stream.write_all(body).await.map_err(|e| e.to_string())?;
+        stream.flush().await.map_err(|e| e.to_string())?;
+        let mut answer = Vec::new();
+        // Some servers close the connection without close_notify
+        if let Err(e) = stream.read_to_end(&mut answer).await {
+            if answer.is_empty() {
+                return Err(e.to_string());
+            }
+        }
+        let pos = answer.windows(4).position(|w| w == b"\r\n\r\n").ok_or_else(|| "Invalid answer".to_owned())?;
+        let head = String::from_utf8_lossy(&answer[..pos]).to_ascii_lowercase();
+        let status = head.split(' ').nth(1).and_then(|status| status.parse().ok()).ok_or_else(|| "Invalid answer".to_owned())?;
+        let body = answer.split_off(pos + 4);
+        if head.contains("\r\ntransfer-encoding: chunked") {
+            return Ok((status, S3::unchunk(&body)));
+        }
+        Ok((status, body))
+    }
+
+    /// Body of "Transfer-Encoding: chunked"
+    fn unchunk(mut data: &[u8]) -> Vec<u8> {
+        let mut res = Vec::with_capacity(data.len());
+        while let Some(pos) = data.windows(2).position(|w| w == b"\r\n") {
+            let size = String::from_utf8_lossy(&data[..pos]);
+            let size = match usize::from_str_radix(size.split(';').next().unwrap_or_default().trim(), 16) {
+                Ok(size) if size > 0 => size,
+                _ => break,
+            };
+            let start = pos + 2;
+            let end = (start + size).min(data.len());
+            res.extend_from_slice(&data[start..end]);
+            data = data.get(end + 2..).unwrap_or_default();
+        }
+        res
+    }
+
+    /// Code and message of the XML error
+    fn error(status: u16, body: &[u8]) -> String {
+        let body = String::from_utf8_lossy(body);
+        let tag = |name: &str| {
+            let start = body.find(&format!("<{}>", name))? + name.len() + 2;
+            let end = body[start..].find("</")? + start;
+            Some(body[start..end].to_owned())
+        };
+        match (tag("Code"), tag("Message")) {
+            (Some(code), Some(message)) => format!("{} {}: {}", status, code, message),
+            (Some(code), None) => format!("{} {}", status, code),
+            _ => status.to_string(),
+        }
+    }
+
+    fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+        hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data).as_ref().to_vec()
+    }
+}
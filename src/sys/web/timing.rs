@@ -0,0 +1,62 @@
+use std::time::{Duration, Instant};
+
+/// Timings of the request phases
+///
+/// The engine adds the marks "queue", "route", "session" and "controller", the controller can add its own marks.
+/// Each mark is the time from the previous mark.
+#[derive(Debug, Clone)]
+pub struct Timings {
+    /// Start of the request
+    start: Instant,
+    /// Time of the last mark
+    last: Instant,
+    marks: Vec<(String, Duration)>,
+}
+
+impl Timings {
+    pub(crate) fn new() -> Timings {
+        let start = Instant::now();
+        Timings {
+            start,
+            last: start,
+            marks: Vec::with_capacity(8),
+        }
+    }
+
+    /// Close the current phase, the name is the token of the Server-Timing header, for example "db"
+    pub fn mark(&mut self, name: &str) {
+        let now = Instant::now();
+        self.marks.push((name.to_owned(), now - self.last));
+        self.last = now;
+    }
+
+    /// Time from the start of the request
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Time from the last mark, for example the time of the controller so far
+    pub fn current(&self) -> Duration {
+        self.last.elapsed()
+    }
+
+    /// Time of the phase, the sum if the mark is added several times
+    pub fn get(&self, name: &str) -> Option<Duration> {
+        self.marks.iter().filter(|(mark, _)| mark == name).map(|(_, time)| *time).reduce(|sum, time| sum + time)
+    }
+
+    /// All marks in the order of adding
+    pub fn list(&self) -> &[(String, Duration)] {
+        &self.marks
+    }
+
+    /// Value of the Server-Timing header, milliseconds
+    pub(crate) fn header(&self) -> String {
+        let mut res = String::with_capacity(32 * (self.marks.len() + 1));
+        for (name, time) in &self.marks {
+            res.push_str(&format!("{};dur={:.3}, ", name, time.as_secs_f64() * 1000.0));
+        }
+        res.push_str(&format!("total;dur={:.3}", self.elapsed().as_secs_f64() * 1000.0));
+        res
+    }
+}
@@ -0,0 +1,108 @@
+use std::{collections::BTreeMap, sync::RwLock};
+
+#[cfg(feature = "setting-db")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "setting-db")]
+use crate::fnv1a_64;
+
+#[cfg(feature = "setting-db")]
+use super::action::Action;
+
+/// Time of the setting "module_off" in the memory
+#[cfg(feature = "setting-db")]
+const SETTING_TTL: Duration = Duration::from_secs(10);
+
+/// States of the control command "module", 0 is the enabled module
+static MANUAL: RwLock<BTreeMap<i64, u16>> = RwLock::new(BTreeMap::new());
+
+/// States of the setting "module_off"
+#[cfg(feature = "setting-db")]
+static SETTING: RwLock<Setting> = RwLock::new(Setting { loaded: None, list: BTreeMap::new() });
+
+#[cfg(feature = "setting-db")]
+struct Setting {
+    loaded: Option<Instant>,
+    list: BTreeMap<i64, u16>,
+}
+
+/// Switching off the whole modules at runtime, the routes of the module answer 404 or 503
+///
+/// The control command `module off <name> [--code 404]` and `module on <name>` has the priority,
+/// `module on` enables the module even if it is in the setting.
+/// With `setting-db` the setting "module_off" is the list of the modules, for example "blog, shop:404",
+/// it is read again every 10 seconds. The code is 503 by default.
+pub(crate) struct ModuleToggle;
+
+impl ModuleToggle {
+    /// State of the control command, 0 is the enabled module
+    pub(crate) fn set(module_id: i64, code: u16) {
+        let code = match code {
+            0 | 404 => code,
+            _ => 503,
+        };
+        let mut manual = match MANUAL.write() {
+            Ok(manual) => manual,
+            Err(e) => e.into_inner(),
+        };
+        manual.insert(module_id, code);
+    }
+
+    /// Code of the answer of the switched off module
+    pub(crate) fn get(module_id: i64) -> Option<u16> {
+        let manual = match MANUAL.read() {
+            Ok(manual) => manual,
+            Err(e) => e.into_inner(),
+        };
+        if let Some(code) = manual.get(&module_id) {
+            return (*code != 0).then_some(*code);
+        }
+        #[cfg(feature = "setting-db")]
+        let code = match SETTING.read() {
+            Ok(setting) => setting,
+            Err(e) => e.into_inner(),
+        }
+        .list
+        .get(&module_id)
+        .copied();
+        #[cfg(not(feature = "setting-db"))]
+        let code = None;
+        code
+    }
+
+    /// Read the setting "module_off" if it is older than 10 seconds, only one request reads it
+    #[cfg(feature = "setting-db")]
+    pub(crate) async fn refresh(action: &Action) {
+        {
+            let mut setting = match SETTING.write() {
+                Ok(setting) => setting,
+                Err(e) => e.into_inner(),
+            };
+            if setting.loaded.is_some_and(|loaded| loaded.elapsed() < SETTING_TTL) {
+                return;
+            }
+            setting.loaded = Some(Instant::now());
+        }
+        let list = ModuleToggle::parse(&action.get_setting("module_off").await.unwrap_or_default());
+        let mut setting = match SETTING.write() {
+            Ok(setting) => setting,
+            Err(e) => e.into_inner(),
+        };
+        setting.list = list;
+    }
+
+    /// "blog, shop:404" => {fnv1a_64("blog"): 503, fnv1a_64("shop"): 404}
+    #[cfg(feature = "setting-db")]
+    fn parse(value: &str) -> BTreeMap<i64, u16> {
+        value
+            .split([',', ';', '\n'])
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| match item.split_once(':') {
+                Some((name, "404")) => (fnv1a_64(name.trim().as_bytes()), 404),
+                Some((name, _)) => (fnv1a_64(name.trim().as_bytes()), 503),
+                None => (fnv1a_64(item.as_bytes()), 503),
+            })
+            .collect()
+    }
+}
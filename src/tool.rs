@@ -1,30 +1,44 @@
+/// iCalendar builder
+pub mod ics;
+
+/// schema.org JSON-LD
+pub mod jsonld;
+
+/// vCard builder
+pub mod vcard;
+
+/// Pages of the list
+pub mod paginator;
+
+use std::any::Any;
+
 #[cfg(any(
     feature = "mail-sendmail",
     feature = "mail-smtp",
     feature = "mail-file",
     feature = "mail-db",
+    feature = "mail-api",
     feature = "session-memory",
     feature = "session-file",
     feature = "session-db"
 ))]
 use chrono::Local;
 
-#[cfg(any(
-    feature = "mail-sendmail",
-    feature = "mail-smtp",
-    feature = "mail-file",
-    feature = "mail-db",
-    feature = "session-memory",
-    feature = "session-file",
-    feature = "session-db"
-))]
 use ring::rand::{SecureRandom, SystemRandom};
 
+/// Standard base64 alphabet
+#[cfg(feature = "mail-api")]
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+/// Base64url alphabet
+#[cfg(feature = "jwt")]
+const BASE64_URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
 #[cfg(any(
     feature = "mail-sendmail",
     feature = "mail-smtp",
     feature = "mail-file",
     feature = "mail-db",
+    feature = "mail-api",
     feature = "session-memory",
     feature = "session-file",
     feature = "session-db"
@@ -36,6 +50,7 @@ use sha3::{Digest, Sha3_512};
     feature = "mail-smtp",
     feature = "mail-file",
     feature = "mail-db",
+    feature = "mail-api",
     feature = "session-memory",
     feature = "session-file",
     feature = "session-db"
@@ -76,3 +91,167 @@ pub(crate) fn generate_uuid() -> String {
     hasher.update(random_bytes);
     format!("{:#x}", hasher.finalize())
 }
+
+/// Random nonce of the Content-Security-Policy, 16 bytes in hex
+pub(crate) fn generate_nonce() -> String {
+    let mut bytes = [0u8; 16];
+    if SystemRandom::new().fill(&mut bytes).is_err() {
+        bytes.copy_from_slice(&(chrono::Local::now().timestamp_nanos_opt().unwrap_or_default() as u128).to_be_bytes());
+    }
+    to_hex(&bytes)
+}
+
+/// Lowercase hex of the bytes
+pub(crate) fn to_hex(data: &[u8]) -> String {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut res = String::with_capacity(data.len() * 2);
+    for byte in data {
+        res.push(HEX[(byte >> 4) as usize] as char);
+        res.push(HEX[(byte & 0x0f) as usize] as char);
+    }
+    res
+}
+
+/// Bytes of the hex, the upper and the lower case
+pub(crate) fn from_hex(data: &str) -> Option<Vec<u8>> {
+    if !data.len().is_multiple_of(2) {
+        return None;
+    }
+    data.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let high = (pair[0] as char).to_digit(16)?;
+            let low = (pair[1] as char).to_digit(16)?;
+            Some((high << 4 | low) as u8)
+        })
+        .collect()
+}
+
+/// Standard base64 with the padding
+#[cfg(feature = "mail-api")]
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    base64(data, BASE64, true)
+}
+
+/// Base64url without the padding
+#[cfg(feature = "jwt")]
+pub(crate) fn base64_url_encode(data: &[u8]) -> String {
+    base64(data, BASE64_URL, false)
+}
+
+/// Base64url without the padding, strict
+///
+/// Only the base64url alphabet, the length of the last group and its unused bits are checked, so every data has one text.
+#[cfg(feature = "jwt")]
+pub(crate) fn base64_url_decode(data: &str) -> Option<Vec<u8>> {
+    if data.len() % 4 == 1 {
+        return None;
+    }
+    let mut res = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buf = 0u32;
+    let mut bits = 0;
+    for byte in data.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'-' => 62,
+            b'_' => 63,
+            _ => return None,
+        };
+        buf = (buf << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            res.push((buf >> bits) as u8);
+        }
+    }
+    if buf & ((1 << bits) - 1) != 0 {
+        return None;
+    }
+    Some(res)
+}
+
+/// Standard base64 or base64url, the padding and the spaces (the line breaks of MIME) are skipped
+#[cfg(feature = "mail-inbound")]
+pub(crate) fn base64_decode(data: &str) -> Option<Vec<u8>> {
+    let mut res = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buf = 0u32;
+    let mut bits = 0;
+    for byte in data.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' | b'\r' | b'\n' | b' ' | b'\t' => continue,
+            _ => return None,
+        };
+        buf = (buf << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            res.push((buf >> bits) as u8);
+        }
+    }
+    Some(res)
+}
+
+#[cfg(any(feature = "mail-api", feature = "jwt"))]
+fn base64(data: &[u8], alphabet: &[u8; 64], padding: bool) -> String {
+    let mut res = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], chunk.get(1).copied().unwrap_or(0), chunk.get(2).copied().unwrap_or(0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                res.push(alphabet[((n >> (18 - i * 6)) & 0x3f) as usize] as char);
+            } else if padding {
+                res.push('=');
+            }
+        }
+    }
+    res
+}
+
+/// Message of the panic, the payload of `panic!` is `&str` or `String`
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(text) = payload.downcast_ref::<&str>() {
+        text
+    } else if let Some(text) = payload.downcast_ref::<String>() {
+        text
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+/// Escape the TEXT value of iCalendar and vCard
+pub(crate) fn escape_text(text: &str) -> String {
+    let mut res = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        match c {
+            '\\' => res.push_str("\\\\"),
+            ';' => res.push_str("\\;"),
+            ',' => res.push_str("\\,"),
+            '\n' => res.push_str("\\n"),
+            '\r' => {}
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Add the content line of iCalendar and vCard folded at 75 octets
+pub(crate) fn fold_line(res: &mut String, line: &str) {
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            res.push_str("\r\n ");
+            len = 1;
+        }
+        res.push(c);
+        len += c.len_utf8();
+    }
+    res.push_str("\r\n");
+}
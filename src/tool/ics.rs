@@ -0,0 +1,259 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, Utc, Weekday};
+
+use crate::sys::web::action::{Action, Answer};
+
+use super::{escape_text, fold_line};
+
+/// Time of the event
+#[derive(Debug, Clone)]
+pub enum EventTime {
+    /// Time in UTC, "20250101T100000Z"
+    Utc(DateTime<Utc>),
+    /// Local time of the timezone, the timezone must be added by `Calendar::timezone`
+    Local(NaiveDateTime, String),
+    /// All-day event
+    Date(NaiveDate),
+}
+
+/// Frequency of the recurrence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// Recurrence rule of the event (RRULE)
+#[derive(Debug, Clone)]
+pub struct Recurrence {
+    pub freq: Frequency,
+    /// Every `interval` periods, 1 by default
+    pub interval: u32,
+    /// Number of the occurrences
+    pub count: Option<u32>,
+    /// Last occurrence
+    pub until: Option<DateTime<Utc>>,
+    /// Days of the week
+    pub by_day: Vec<Weekday>,
+}
+
+/// Reminder of the event (VALARM)
+#[derive(Debug, Clone)]
+pub struct Alarm {
+    /// Time before the start of the event
+    pub before: Duration,
+    pub description: String,
+}
+
+/// Event of the calendar (VEVENT)
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// Globally unique id, for example "booking-42@example.com"
+    pub uid: String,
+    pub summary: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub url: Option<String>,
+    pub start: EventTime,
+    pub end: Option<EventTime>,
+    pub recurrence: Option<Recurrence>,
+    pub alarms: Vec<Alarm>,
+}
+
+/// Timezone with the fixed offset (VTIMEZONE)
+#[derive(Debug, Clone)]
+struct Timezone {
+    tzid: String,
+    offset: FixedOffset,
+}
+
+/// RFC 5545 calendar
+///
+/// # Example
+///
+/// ```ignore
+/// let mut calendar = Calendar::new("-//Example//Booking//EN");
+/// calendar.events.push(Event::new("booking-42@example.com", "Meeting", EventTime::Utc(start)));
+/// this.ics(&calendar, "meeting.ics")
+/// ```
+#[derive(Debug, Clone)]
+pub struct Calendar {
+    /// Product id, "-//Company//Product//EN"
+    pub prodid: String,
+    /// Name of the calendar (X-WR-CALNAME)
+    pub name: Option<String>,
+    /// iTIP method, for example "PUBLISH" or "REQUEST"
+    pub method: Option<String>,
+    pub events: Vec<Event>,
+    timezones: Vec<Timezone>,
+}
+
+impl Recurrence {
+    pub fn new(freq: Frequency) -> Recurrence {
+        Recurrence {
+            freq,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+        }
+    }
+
+    fn rule(&self) -> String {
+        let freq = match self.freq {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+        let mut rule = format!("FREQ={}", freq);
+        if self.interval > 1 {
+            rule.push_str(&format!(";INTERVAL={}", self.interval));
+        }
+        if let Some(count) = self.count {
+            rule.push_str(&format!(";COUNT={}", count));
+        } else if let Some(until) = self.until {
+            rule.push_str(&format!(";UNTIL={}", until.format("%Y%m%dT%H%M%SZ")));
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<&str> = self
+                .by_day
+                .iter()
+                .map(|day| match day {
+                    Weekday::Mon => "MO",
+                    Weekday::Tue => "TU",
+                    Weekday::Wed => "WE",
+                    Weekday::Thu => "TH",
+                    Weekday::Fri => "FR",
+                    Weekday::Sat => "SA",
+                    Weekday::Sun => "SU",
+                })
+                .collect();
+            rule.push_str(&format!(";BYDAY={}", days.join(",")));
+        }
+        rule
+    }
+}
+
+impl Event {
+    pub fn new(uid: &str, summary: &str, start: EventTime) -> Event {
+        Event {
+            uid: uid.to_owned(),
+            summary: summary.to_owned(),
+            description: None,
+            location: None,
+            url: None,
+            start,
+            end: None,
+            recurrence: None,
+            alarms: Vec::new(),
+        }
+    }
+}
+
+impl Calendar {
+    pub fn new(prodid: &str) -> Calendar {
+        Calendar {
+            prodid: prodid.to_owned(),
+            name: None,
+            method: None,
+            events: Vec::new(),
+            timezones: Vec::new(),
+        }
+    }
+
+    /// Add the timezone of `EventTime::Local`, for example ("Europe/Kyiv", UTC+2)
+    ///
+    /// The timezone has the fixed offset without the daylight saving rules,
+    /// the clients usually apply their own rules for the known tzid.
+    pub fn timezone(&mut self, tzid: &str, offset: FixedOffset) -> &mut Calendar {
+        self.timezones.retain(|tz| tz.tzid != tzid);
+        self.timezones.push(Timezone { tzid: tzid.to_owned(), offset });
+        self
+    }
+
+    /// Calendar in the iCalendar format
+    pub fn render(&self) -> String {
+        let mut ics = String::with_capacity(512 + self.events.len() * 512);
+        fold_line(&mut ics, "BEGIN:VCALENDAR");
+        fold_line(&mut ics, "VERSION:2.0");
+        fold_line(&mut ics, &format!("PRODID:{}", escape_text(&self.prodid)));
+        fold_line(&mut ics, "CALSCALE:GREGORIAN");
+        if let Some(method) = &self.method {
+            fold_line(&mut ics, &format!("METHOD:{}", method));
+        }
+        if let Some(name) = &self.name {
+            fold_line(&mut ics, &format!("X-WR-CALNAME:{}", escape_text(name)));
+        }
+        for tz in &self.timezones {
+            let offset = Calendar::offset(tz.offset);
+            fold_line(&mut ics, "BEGIN:VTIMEZONE");
+            fold_line(&mut ics, &format!("TZID:{}", tz.tzid));
+            fold_line(&mut ics, "BEGIN:STANDARD");
+            fold_line(&mut ics, "DTSTART:19700101T000000");
+            fold_line(&mut ics, &format!("TZOFFSETFROM:{}", offset));
+            fold_line(&mut ics, &format!("TZOFFSETTO:{}", offset));
+            fold_line(&mut ics, "END:STANDARD");
+            fold_line(&mut ics, "END:VTIMEZONE");
+        }
+        let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        for event in &self.events {
+            fold_line(&mut ics, "BEGIN:VEVENT");
+            fold_line(&mut ics, &format!("UID:{}", escape_text(&event.uid)));
+            fold_line(&mut ics, &format!("DTSTAMP:{}", stamp));
+            fold_line(&mut ics, &Calendar::time("DTSTART", &event.start));
+            if let Some(end) = &event.end {
+                fold_line(&mut ics, &Calendar::time("DTEND", end));
+            }
+            fold_line(&mut ics, &format!("SUMMARY:{}", escape_text(&event.summary)));
+            if let Some(description) = &event.description {
+                fold_line(&mut ics, &format!("DESCRIPTION:{}", escape_text(description)));
+            }
+            if let Some(location) = &event.location {
+                fold_line(&mut ics, &format!("LOCATION:{}", escape_text(location)));
+            }
+            if let Some(url) = &event.url {
+                fold_line(&mut ics, &format!("URL:{}", url));
+            }
+            if let Some(recurrence) = &event.recurrence {
+                fold_line(&mut ics, &format!("RRULE:{}", recurrence.rule()));
+            }
+            for alarm in &event.alarms {
+                fold_line(&mut ics, "BEGIN:VALARM");
+                fold_line(&mut ics, "ACTION:DISPLAY");
+                fold_line(&mut ics, &format!("DESCRIPTION:{}", escape_text(&alarm.description)));
+                fold_line(&mut ics, &format!("TRIGGER:-PT{}M", alarm.before.num_minutes().max(0)));
+                fold_line(&mut ics, "END:VALARM");
+            }
+            fold_line(&mut ics, "END:VEVENT");
+        }
+        fold_line(&mut ics, "END:VCALENDAR");
+        ics
+    }
+
+    fn time(name: &str, time: &EventTime) -> String {
+        match time {
+            EventTime::Utc(time) => format!("{}:{}", name, time.format("%Y%m%dT%H%M%SZ")),
+            EventTime::Local(time, tzid) => format!("{};TZID={}:{}", name, tzid, time.format("%Y%m%dT%H%M%S")),
+            EventTime::Date(date) => format!("{};VALUE=DATE:{}", name, date.format("%Y%m%d")),
+        }
+    }
+
+    /// "+0200"
+    fn offset(offset: FixedOffset) -> String {
+        let secs = offset.local_minus_utc();
+        let sign = if secs < 0 { '-' } else { '+' };
+        let secs = secs.abs();
+        format!("{}{:02}{:02}", sign, secs / 3600, secs % 3600 / 60)
+    }
+}
+
+impl Action {
+    /// Answer with the calendar file, for example "booking.ics"
+    pub fn ics(&mut self, calendar: &Calendar, name: &str) -> Answer {
+        self.response.content_type = Some("text/calendar; charset=utf-8".to_owned());
+        self.response.headers.push(("Content-Disposition".to_owned(), format!("attachment; filename=\"{}\"", name.replace('"', ""))));
+        Answer::String(calendar.render())
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    fnv1a_64,
+    sys::web::{action::Action, data::Data},
+};
+
+/// Product of the shop (schema.org/Product)
+#[derive(Debug, Clone)]
+pub struct Product {
+    pub name: String,
+    pub description: Option<String>,
+    pub image: Vec<String>,
+    pub sku: Option<String>,
+    pub brand: Option<String>,
+    /// Price as the text, for example "19.99"
+    pub price: Option<String>,
+    /// ISO 4217 code, for example "UAH"
+    pub currency: Option<String>,
+    /// Availability, for example "InStock" or "OutOfStock"
+    pub availability: Option<String>,
+    pub url: Option<String>,
+}
+
+/// Article of the blog or the news (schema.org/Article)
+#[derive(Debug, Clone)]
+pub struct Article {
+    pub headline: String,
+    pub description: Option<String>,
+    pub image: Vec<String>,
+    pub author: Option<String>,
+    pub publisher: Option<String>,
+    pub published: DateTime<Utc>,
+    pub modified: Option<DateTime<Utc>>,
+    pub url: Option<String>,
+}
+
+/// Item of the breadcrumb trail (schema.org/BreadcrumbList)
+#[derive(Debug, Clone)]
+pub struct Breadcrumb {
+    pub name: String,
+    pub url: String,
+}
+
+/// schema.org structured data
+///
+/// # Example
+///
+/// ```ignore
+/// let product = Product::from(&row);
+/// this.json_ld(&JsonLd::Product(product));
+/// ```
+///
+/// The template outputs the block with the other `meta` items.
+#[derive(Debug, Clone)]
+pub enum JsonLd {
+    Product(Product),
+    Article(Article),
+    Breadcrumb(Vec<Breadcrumb>),
+    /// Any other type, "@context" is added if missing
+    Custom(Value),
+}
+
+impl JsonLd {
+    /// JSON-LD document
+    pub fn to_value(&self) -> Value {
+        let mut value = match self {
+            JsonLd::Product(product) => {
+                let mut value = json!({ "@type": "Product", "name": product.name });
+                JsonLd::insert(&mut value, "description", product.description.as_deref());
+                JsonLd::insert_vec(&mut value, "image", &product.image);
+                JsonLd::insert(&mut value, "sku", product.sku.as_deref());
+                if let Some(brand) = &product.brand {
+                    value["brand"] = json!({ "@type": "Brand", "name": brand });
+                }
+                JsonLd::insert(&mut value, "url", product.url.as_deref());
+                if let Some(price) = &product.price {
+                    let mut offer = json!({ "@type": "Offer", "price": price });
+                    JsonLd::insert(&mut offer, "priceCurrency", product.currency.as_deref());
+                    if let Some(availability) = &product.availability {
+                        offer["availability"] = Value::String(format!("https://schema.org/{}", availability));
+                    }
+                    JsonLd::insert(&mut offer, "url", product.url.as_deref());
+                    value["offers"] = offer;
+                }
+                value
+            }
+            JsonLd::Article(article) => {
+                let mut value = json!({
+                    "@type": "Article",
+                    "headline": article.headline,
+                    "datePublished": article.published.to_rfc3339_opts(SecondsFormat::Secs, true),
+                });
+                JsonLd::insert(&mut value, "description", article.description.as_deref());
+                JsonLd::insert_vec(&mut value, "image", &article.image);
+                if let Some(modified) = article.modified {
+                    value["dateModified"] = Value::String(modified.to_rfc3339_opts(SecondsFormat::Secs, true));
+                }
+                if let Some(author) = &article.author {
+                    value["author"] = json!({ "@type": "Person", "name": author });
+                }
+                if let Some(publisher) = &article.publisher {
+                    value["publisher"] = json!({ "@type": "Organization", "name": publisher });
+                }
+                if let Some(url) = &article.url {
+                    value["mainEntityOfPage"] = Value::String(url.clone());
+                }
+                value
+            }
+            JsonLd::Breadcrumb(list) => {
+                let items: Vec<Value> = list
+                    .iter()
+                    .enumerate()
+                    .map(|(index, item)| {
+                        let mut value = json!({ "@type": "ListItem", "position": index + 1, "name": item.name });
+                        // The current page may be without the url
+                        if !item.url.is_empty() {
+                            value["item"] = Value::String(item.url.clone());
+                        }
+                        value
+                    })
+                    .collect();
+                json!({ "@type": "BreadcrumbList", "itemListElement": items })
+            }
+            JsonLd::Custom(value) => value.clone(),
+        };
+        if let Value::Object(map) = &mut value {
+            if !map.contains_key("@context") {
+                let mut res = Map::with_capacity(map.len() + 1);
+                res.insert("@context".to_owned(), Value::String("https://schema.org".to_owned()));
+                res.append(map);
+                *map = res;
+            }
+        }
+        value
+    }
+
+    /// Block `<script type="application/ld+json">` with the nonce of the Content-Security-Policy
+    pub fn to_script(&self, nonce: Option<&str>) -> String {
+        // "</script>" and "<!--" inside the JSON must not close the block
+        let json = self.to_value().to_string().replace('<', "\\u003c");
+        match nonce {
+            Some(nonce) => format!(r#"<script type="application/ld+json" nonce="{}">{}</script>"#, nonce, json),
+            None => format!(r#"<script type="application/ld+json">{}</script>"#, json),
+        }
+    }
+
+    fn insert(value: &mut Value, key: &str, item: Option<&str>) {
+        if let Some(item) = item {
+            value[key] = Value::String(item.to_owned());
+        }
+    }
+
+    fn insert_vec(value: &mut Value, key: &str, items: &[String]) {
+        match items.len() {
+            0 => {}
+            1 => value[key] = Value::String(items[0].clone()),
+            _ => value[key] = Value::Array(items.iter().map(|item| Value::String(item.clone())).collect()),
+        }
+    }
+
+    /// Text value of the field of `Data::Map`
+    fn text(map: &HashMap<i64, Data>, key: &str) -> Option<String> {
+        match map.get(&fnv1a_64(key.as_bytes()))? {
+            Data::String(value) if !value.is_empty() => Some(value.clone()),
+            Data::U8(value) => Some(value.to_string()),
+            Data::U16(value) => Some(value.to_string()),
+            Data::U32(value) => Some(value.to_string()),
+            Data::U64(value) => Some(value.to_string()),
+            Data::Usize(value) => Some(value.to_string()),
+            Data::I8(value) => Some(value.to_string()),
+            Data::I16(value) => Some(value.to_string()),
+            Data::I32(value) => Some(value.to_string()),
+            Data::I64(value) => Some(value.to_string()),
+            Data::F32(value) => Some(value.to_string()),
+            Data::F64(value) => Some(value.to_string()),
+            Data::Json(Value::String(value)) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    fn date(map: &HashMap<i64, Data>, key: &str) -> Option<DateTime<Utc>> {
+        match map.get(&fnv1a_64(key.as_bytes()))? {
+            Data::Date(date) => Some(*date),
+            Data::String(date) => DateTime::parse_from_rfc3339(date).ok().map(|date| date.with_timezone(&Utc)),
+            _ => None,
+        }
+    }
+
+    /// One url or the list of the urls
+    fn images(map: &HashMap<i64, Data>, key: &str) -> Vec<String> {
+        match map.get(&fnv1a_64(key.as_bytes())) {
+            Some(Data::Vec(vec)) => vec
+                .iter()
+                .filter_map(|item| match item {
+                    Data::String(url) => Some(url.clone()),
+                    _ => None,
+                })
+                .collect(),
+            _ => JsonLd::text(map, key).into_iter().collect(),
+        }
+    }
+}
+
+/// The product from `Data::Map` with the keys "name", "description", "image", "sku", "brand", "price", "currency", "availability", "url"
+///
+/// The row of the query result has the same keys as the columns.
+impl From<&Data> for Product {
+    fn from(data: &Data) -> Product {
+        let empty = HashMap::new();
+        let map = match data {
+            Data::Map(map) => map,
+            _ => &empty,
+        };
+        Product {
+            name: JsonLd::text(map, "name").unwrap_or_default(),
+            description: JsonLd::text(map, "description"),
+            image: JsonLd::images(map, "image"),
+            sku: JsonLd::text(map, "sku"),
+            brand: JsonLd::text(map, "brand"),
+            price: JsonLd::text(map, "price"),
+            currency: JsonLd::text(map, "currency"),
+            availability: JsonLd::text(map, "availability"),
+            url: JsonLd::text(map, "url"),
+        }
+    }
+}
+
+/// The article from `Data::Map` with the keys "headline", "description", "image", "author", "publisher", "published", "modified", "url"
+impl From<&Data> for Article {
+    fn from(data: &Data) -> Article {
+        let empty = HashMap::new();
+        let map = match data {
+            Data::Map(map) => map,
+            _ => &empty,
+        };
+        Article {
+            headline: JsonLd::text(map, "headline").unwrap_or_default(),
+            description: JsonLd::text(map, "description"),
+            image: JsonLd::images(map, "image"),
+            author: JsonLd::text(map, "author"),
+            publisher: JsonLd::text(map, "publisher"),
+            published: JsonLd::date(map, "published").unwrap_or_else(Utc::now),
+            modified: JsonLd::date(map, "modified"),
+            url: JsonLd::text(map, "url"),
+        }
+    }
+}
+
+/// The trail from `Data::Vec` of `Data::Map` with the keys "name" and "url"
+impl From<&Data> for JsonLd {
+    fn from(data: &Data) -> JsonLd {
+        let list = match data {
+            Data::Vec(vec) => vec
+                .iter()
+                .filter_map(|item| match item {
+                    Data::Map(map) => Some(Breadcrumb {
+                        name: JsonLd::text(map, "name")?,
+                        url: JsonLd::text(map, "url").unwrap_or_default(),
+                    }),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        JsonLd::Breadcrumb(list)
+    }
+}
+
+impl Action {
+    /// Add the JSON-LD block to the `meta` of the template
+    pub fn json_ld(&mut self, ld: &JsonLd) {
+        let script = ld.to_script(self.csp_nonce());
+        self.response.meta.push(script);
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use crate::{fnv1a_64, sys::web::data::Data};
+
+/// Pages of the list
+///
+/// # Example
+///
+/// ```ignore
+/// let page = this.db.paginate("SELECT id, name FROM article ORDER BY id DESC", &[], number, 20).await?;
+/// this.set("list", page.rows);
+/// this.set("paginator", &page.paginator);
+/// ```
+///
+/// In the template `paginator.links` is the list of the page numbers, 0 is the gap "…".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Paginator {
+    /// Current page, from 1
+    pub page: u64,
+    pub per_page: u64,
+    /// Number of the rows
+    pub total: u64,
+}
+
+impl Paginator {
+    /// Links to the pages around the current page
+    const AROUND: u64 = 2;
+
+    /// The page is from 1, `per_page` is at least 1
+    pub fn new(page: u64, per_page: u64, total: u64) -> Paginator {
+        Paginator {
+            page: page.max(1),
+            per_page: per_page.max(1),
+            total,
+        }
+    }
+
+    /// Number of the pages, at least 1
+    pub fn pages(&self) -> u64 {
+        self.total.div_ceil(self.per_page).max(1)
+    }
+
+    /// Offset of the first row of the page
+    pub fn offset(&self) -> u64 {
+        (self.page - 1).saturating_mul(self.per_page)
+    }
+
+    pub fn prev(&self) -> Option<u64> {
+        (self.page > 1).then(|| (self.page - 1).min(self.pages()))
+    }
+
+    pub fn next(&self) -> Option<u64> {
+        (self.page < self.pages()).then_some(self.page + 1)
+    }
+
+    /// The first, the last and the pages around the current one, 0 is the gap
+    pub fn links(&self) -> Vec<u64> {
+        let pages = self.pages();
+        let page = self.page.min(pages);
+        let start = page.saturating_sub(Paginator::AROUND).max(1);
+        let end = page.saturating_add(Paginator::AROUND).min(pages);
+        let mut links = Vec::with_capacity((2 * Paginator::AROUND + 5) as usize);
+        if start > 1 {
+            links.push(1);
+            if start > 2 {
+                links.push(0);
+            }
+        }
+        links.extend(start..=end);
+        if end < pages {
+            if end + 1 < pages {
+                links.push(0);
+            }
+            links.push(pages);
+        }
+        links
+    }
+}
+
+/// Map with the keys "page", "per_page", "total", "pages", "prev", "next" and "links", prev and next are 0 if missing
+impl From<&Paginator> for Data {
+    fn from(paginator: &Paginator) -> Data {
+        let mut map = HashMap::with_capacity(7);
+        map.insert(fnv1a_64(b"page"), Data::U64(paginator.page));
+        map.insert(fnv1a_64(b"per_page"), Data::U64(paginator.per_page));
+        map.insert(fnv1a_64(b"total"), Data::U64(paginator.total));
+        map.insert(fnv1a_64(b"pages"), Data::U64(paginator.pages()));
+        map.insert(fnv1a_64(b"prev"), Data::U64(paginator.prev().unwrap_or(0)));
+        map.insert(fnv1a_64(b"next"), Data::U64(paginator.next().unwrap_or(0)));
+        map.insert(fnv1a_64(b"links"), Data::Vec(paginator.links().into_iter().map(Data::U64).collect()));
+        Data::Map(map)
+    }
+}
+
+impl From<Paginator> for Data {
+    fn from(paginator: Paginator) -> Data {
+        Data::from(&paginator)
+    }
+}
@@ -0,0 +1,145 @@
+use chrono::NaiveDate;
+
+use crate::sys::web::action::{Action, Answer};
+
+use super::{escape_text, fold_line};
+
+/// Postal address of the contact (ADR)
+#[derive(Debug, Clone, Default)]
+pub struct Address {
+    pub street: String,
+    pub city: String,
+    pub region: String,
+    pub postcode: String,
+    pub country: String,
+}
+
+/// Contact card, vCard 3.0 (RFC 2426)
+///
+/// # Example
+///
+/// ```ignore
+/// let mut card = VCard::new("Ivan Petrenko");
+/// card.email.push(("work".to_owned(), "ivan@example.com".to_owned()));
+/// this.vcard(&card, "ivan.vcf")
+/// ```
+#[derive(Debug, Clone)]
+pub struct VCard {
+    /// Formatted name (FN)
+    pub name: String,
+    pub family_name: Option<String>,
+    pub given_name: Option<String>,
+    pub organization: Option<String>,
+    /// Job title
+    pub title: Option<String>,
+    /// Type and email, for example ("work", "ivan@example.com")
+    pub email: Vec<(String, String)>,
+    /// Type and phone, for example ("cell", "+380501234567")
+    pub phone: Vec<(String, String)>,
+    /// Type and address, for example ("work", address)
+    pub address: Vec<(String, Address)>,
+    pub url: Option<String>,
+    /// Url of the photo
+    pub photo: Option<String>,
+    pub birthday: Option<NaiveDate>,
+    pub note: Option<String>,
+}
+
+impl VCard {
+    pub fn new(name: &str) -> VCard {
+        VCard {
+            name: name.to_owned(),
+            family_name: None,
+            given_name: None,
+            organization: None,
+            title: None,
+            email: Vec::new(),
+            phone: Vec::new(),
+            address: Vec::new(),
+            url: None,
+            photo: None,
+            birthday: None,
+            note: None,
+        }
+    }
+
+    /// Card in the vCard format
+    pub fn render(&self) -> String {
+        let mut vcf = String::with_capacity(512);
+        fold_line(&mut vcf, "BEGIN:VCARD");
+        fold_line(&mut vcf, "VERSION:3.0");
+        fold_line(&mut vcf, &format!("FN:{}", escape_text(&self.name)));
+        fold_line(
+            &mut vcf,
+            &format!(
+                "N:{};{};;;",
+                escape_text(self.family_name.as_deref().unwrap_or_default()),
+                escape_text(self.given_name.as_deref().unwrap_or_default())
+            ),
+        );
+        if let Some(organization) = &self.organization {
+            fold_line(&mut vcf, &format!("ORG:{}", escape_text(organization)));
+        }
+        if let Some(title) = &self.title {
+            fold_line(&mut vcf, &format!("TITLE:{}", escape_text(title)));
+        }
+        for (kind, email) in &self.email {
+            fold_line(&mut vcf, &format!("EMAIL;TYPE=INTERNET,{}:{}", VCard::kind(kind), VCard::value(email)));
+        }
+        for (kind, phone) in &self.phone {
+            fold_line(&mut vcf, &format!("TEL;TYPE={}:{}", VCard::kind(kind), VCard::value(phone)));
+        }
+        for (kind, address) in &self.address {
+            fold_line(
+                &mut vcf,
+                &format!(
+                    "ADR;TYPE={}:;;{};{};{};{};{}",
+                    VCard::kind(kind),
+                    escape_text(&address.street),
+                    escape_text(&address.city),
+                    escape_text(&address.region),
+                    escape_text(&address.postcode),
+                    escape_text(&address.country)
+                ),
+            );
+        }
+        if let Some(url) = &self.url {
+            fold_line(&mut vcf, &format!("URL:{}", VCard::value(url)));
+        }
+        if let Some(photo) = &self.photo {
+            fold_line(&mut vcf, &format!("PHOTO;VALUE=URI:{}", VCard::value(photo)));
+        }
+        if let Some(birthday) = self.birthday {
+            fold_line(&mut vcf, &format!("BDAY:{}", birthday.format("%Y-%m-%d")));
+        }
+        if let Some(note) = &self.note {
+            fold_line(&mut vcf, &format!("NOTE:{}", escape_text(note)));
+        }
+        fold_line(&mut vcf, "END:VCARD");
+        vcf
+    }
+
+    /// Value without the line breaks
+    fn value(value: &str) -> String {
+        value.chars().filter(|c| *c != '\r' && *c != '\n').collect()
+    }
+
+    /// Type of the parameter, only letters and digits, "work" and "home" are common
+    fn kind(kind: &str) -> String {
+        let kind: String = kind.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '-').collect();
+        if kind.is_empty() {
+            "OTHER".to_owned()
+        } else {
+            kind.to_ascii_uppercase()
+        }
+    }
+}
+
+impl Action {
+    /// Answer with the contact file, for example "contact.vcf"
+    pub fn vcard(&mut self, card: &VCard, name: &str) -> Answer {
+        self.response.content_type = Some("text/vcard; charset=utf-8".to_owned());
+        self.response.headers.push(("Content-Disposition".to_owned(), format!("attachment; filename=\"{}\"", name.replace('"', ""))));
+        Answer::String(card.render())
+    }
+}